@@ -147,7 +147,7 @@ fn mk_builtin_table() -> Vec<Builtin> {
             min_args: Q(1),
             max_args: Q(2),
             types: vec![Typed(TYPE_INT), Any],
-            implemented: false,
+            implemented: true,
         },
         Builtin {
             name: "function_info".to_string(),
@@ -614,8 +614,8 @@ fn mk_builtin_table() -> Vec<Builtin> {
         Builtin {
             name: "set_player_flag".to_string(),
             min_args: Q(2),
-            max_args: Q(2),
-            types: vec![Typed(TYPE_OBJ), Any],
+            max_args: Q(3),
+            types: vec![Typed(TYPE_OBJ), Any, Any],
             implemented: true,
         },
         Builtin {
@@ -686,14 +686,14 @@ fn mk_builtin_table() -> Vec<Builtin> {
             min_args: Q(1),
             max_args: Q(1),
             types: vec![Typed(TYPE_OBJ)],
-            implemented: false,
+            implemented: true,
         },
         Builtin {
             name: "reset_max_object".to_string(),
             min_args: Q(0),
             max_args: Q(0),
             types: vec![],
-            implemented: false,
+            implemented: true,
         },
         Builtin {
             name: "memory_usage".to_string(),
@@ -947,6 +947,132 @@ fn mk_builtin_table() -> Vec<Builtin> {
             types: vec![Typed(TYPE_STR)],
             implemented: true,
         },
+        Builtin {
+            name: "verb_metrics".to_string(),
+            min_args: Q(2),
+            max_args: Q(2),
+            types: vec![Typed(TYPE_OBJ), Any],
+            implemented: true,
+        },
+        Builtin {
+            name: "notify_oob".to_string(),
+            min_args: Q(2),
+            max_args: Q(2),
+            types: vec![Typed(TYPE_OBJ), Typed(TYPE_STR)],
+            implemented: true,
+        },
+        Builtin {
+            name: "server_options".to_string(),
+            min_args: Q(0),
+            max_args: Q(0),
+            types: vec![],
+            implemented: true,
+        },
+        Builtin {
+            name: "verify_database".to_string(),
+            min_args: Q(0),
+            max_args: Q(0),
+            types: vec![],
+            implemented: true,
+        },
+        Builtin {
+            name: "repair_database".to_string(),
+            min_args: Q(0),
+            max_args: Q(1),
+            types: vec![Any],
+            implemented: true,
+        },
+        Builtin {
+            name: "match_object".to_string(),
+            min_args: Q(1),
+            max_args: Q(2),
+            types: vec![Typed(TYPE_STR), Typed(TYPE_OBJ)],
+            implemented: true,
+        },
+        Builtin {
+            name: "create_with_id".to_string(),
+            min_args: Q(2),
+            max_args: Q(3),
+            types: vec![Typed(TYPE_OBJ), Typed(TYPE_OBJ), Typed(TYPE_OBJ)],
+            implemented: true,
+        },
+        Builtin {
+            name: "parents".to_string(),
+            min_args: Q(1),
+            max_args: Q(1),
+            types: vec![Typed(TYPE_OBJ)],
+            implemented: true,
+        },
+        Builtin {
+            name: "chparents".to_string(),
+            min_args: Q(2),
+            max_args: Q(2),
+            types: vec![Typed(TYPE_OBJ), Typed(TYPE_LIST)],
+            implemented: true,
+        },
+        Builtin {
+            name: "isa".to_string(),
+            min_args: Q(2),
+            max_args: Q(2),
+            types: vec![Typed(TYPE_OBJ), Typed(TYPE_OBJ)],
+            implemented: true,
+        },
+        Builtin {
+            name: "byte_length".to_string(),
+            min_args: Q(1),
+            max_args: Q(1),
+            types: vec![Typed(TYPE_STR)],
+            implemented: true,
+        },
+        Builtin {
+            name: "password_verify".to_string(),
+            min_args: Q(2),
+            max_args: Q(2),
+            types: vec![Typed(TYPE_STR), Typed(TYPE_STR)],
+            implemented: true,
+        },
+        Builtin {
+            name: "stable_hash".to_string(),
+            min_args: Q(1),
+            max_args: Q(1),
+            types: vec![Any],
+            implemented: true,
+        },
+        Builtin {
+            name: "verb_cfg".to_string(),
+            min_args: Q(2),
+            max_args: Q(2),
+            types: vec![Typed(TYPE_OBJ), Any],
+            implemented: true,
+        },
+        Builtin {
+            name: "coverage".to_string(),
+            min_args: Q(0),
+            max_args: Q(0),
+            types: vec![],
+            implemented: true,
+        },
+        Builtin {
+            name: "call_after_commit".to_string(),
+            min_args: Q(2),
+            max_args: Q(3),
+            types: vec![Typed(TYPE_OBJ), Typed(TYPE_STR), Typed(TYPE_LIST)],
+            implemented: true,
+        },
+        Builtin {
+            name: "savepoint".to_string(),
+            min_args: Q(0),
+            max_args: Q(0),
+            types: vec![],
+            implemented: true,
+        },
+        Builtin {
+            name: "rollback_to".to_string(),
+            min_args: Q(1),
+            max_args: Q(1),
+            types: vec![Typed(TYPE_INT)],
+            implemented: true,
+        },
     ]
 }
 
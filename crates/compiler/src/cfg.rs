@@ -0,0 +1,223 @@
+// Copyright (C) 2024 Ryan Daum <ryan.daum@gmail.com>
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU General Public License as published by the Free Software
+// Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// this program. If not, see <https://www.gnu.org/licenses/>.
+//
+
+//! Control flow graph construction for compiled programs, for debugging / visualization purposes.
+
+use std::collections::BTreeSet;
+use std::fmt::Write;
+
+use crate::labels::Label;
+use crate::opcode::{Op, ScatterLabel};
+use crate::program::Program;
+
+/// How control reaches one basic block from another.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EdgeKind {
+    /// Execution runs off the end of the block into the next one.
+    Fallthrough,
+    /// Unconditional jump.
+    Jump,
+    /// Conditional jump, taken depending on a runtime value.
+    Branch,
+    /// Where control goes if an error is raised (or a finally is triggered) inside a protected region.
+    Handler,
+}
+
+/// A maximal straight-line run of opcodes, `start..end` in the main vector.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BasicBlock {
+    pub start: usize,
+    pub end: usize,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Edge {
+    pub from: usize,
+    pub to: usize,
+    pub kind: EdgeKind,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ControlFlowGraph {
+    pub blocks: Vec<BasicBlock>,
+    pub edges: Vec<Edge>,
+}
+
+/// The jump targets of an opcode, and whether execution can also continue to the next opcode.
+fn successors(op: &Op) -> (Vec<(Label, EdgeKind)>, bool) {
+    match op {
+        Op::If(label)
+        | Op::Eif(label)
+        | Op::IfQues(label)
+        | Op::While(label)
+        | Op::And(label)
+        | Op::Or(label) => (vec![(*label, EdgeKind::Branch)], true),
+        Op::ForList { end_label, .. }
+        | Op::ForRange { end_label, .. }
+        | Op::WhileId { end_label, .. } => (vec![(*end_label, EdgeKind::Branch)], true),
+        Op::PushLabel(label) | Op::TryFinally(label) => (vec![(*label, EdgeKind::Handler)], true),
+        Op::Jump { label }
+        | Op::ExitId(label)
+        | Op::Exit { label, .. }
+        | Op::EndCatch(label)
+        | Op::EndExcept(label) => (vec![(*label, EdgeKind::Jump)], false),
+        Op::Scatter(sa) => {
+            let mut targets: Vec<_> = sa
+                .labels
+                .iter()
+                .filter_map(|l| match l {
+                    ScatterLabel::Optional(_, Some(label)) => Some((*label, EdgeKind::Branch)),
+                    _ => None,
+                })
+                .collect();
+            targets.push((sa.done, EdgeKind::Jump));
+            (targets, false)
+        }
+        Op::Return | Op::Return0 | Op::Done => (vec![], false),
+        _ => (vec![], true),
+    }
+}
+
+/// Whether an opcode ends its basic block.
+fn ends_block(op: &Op) -> bool {
+    let (targets, falls_through) = successors(op);
+    !targets.is_empty() || !falls_through
+}
+
+impl ControlFlowGraph {
+    /// Split the program's main vector into basic blocks, and derive the edges between them from
+    /// the jump labels of each block's final opcode.
+    pub fn build(program: &Program) -> Self {
+        let ops = &program.main_vector;
+        let position = |label: &Label| program.jump_labels[label.0 as usize].position.0 as usize;
+
+        let mut leaders = BTreeSet::new();
+        if !ops.is_empty() {
+            leaders.insert(0);
+        }
+        for (pc, op) in ops.iter().enumerate() {
+            if !ends_block(op) {
+                continue;
+            }
+            leaders.insert(pc + 1);
+            for (label, _) in successors(op).0 {
+                leaders.insert(position(&label));
+            }
+        }
+        let leaders: Vec<_> = leaders.into_iter().filter(|pc| *pc < ops.len()).collect();
+
+        let blocks: Vec<_> = leaders
+            .iter()
+            .enumerate()
+            .map(|(i, start)| BasicBlock {
+                start: *start,
+                end: leaders.get(i + 1).copied().unwrap_or(ops.len()),
+            })
+            .collect();
+        let block_at = |pc: usize| blocks.iter().position(|b| b.start == pc);
+
+        let mut edges = vec![];
+        for (i, block) in blocks.iter().enumerate() {
+            let (targets, falls_through) = successors(&ops[block.end - 1]);
+            if falls_through && block.end < ops.len() {
+                edges.push(Edge {
+                    from: i,
+                    to: i + 1,
+                    kind: EdgeKind::Fallthrough,
+                });
+            }
+            for (label, kind) in targets {
+                if let Some(to) = block_at(position(&label)) {
+                    edges.push(Edge { from: i, to, kind });
+                }
+            }
+        }
+
+        Self { blocks, edges }
+    }
+
+    /// Render the graph in Graphviz DOT format, one node per basic block listing its opcodes.
+    pub fn to_dot(&self, program: &Program) -> String {
+        let mut dot = String::new();
+        writeln!(dot, "digraph cfg {{").unwrap();
+        writeln!(dot, "  node [shape=box, fontname=\"monospace\"];").unwrap();
+        for (i, block) in self.blocks.iter().enumerate() {
+            let mut label = String::new();
+            for pc in block.start..block.end {
+                write!(label, "{}: {:?}\\l", pc, program.main_vector[pc]).unwrap();
+            }
+            let label = label.replace('"', "\\\"");
+            writeln!(dot, "  b{} [label=\"{}\"];", i, label).unwrap();
+        }
+        for edge in &self.edges {
+            let attrs = match edge.kind {
+                EdgeKind::Fallthrough => "",
+                EdgeKind::Jump => " [label=\"jump\"]",
+                EdgeKind::Branch => " [label=\"branch\"]",
+                EdgeKind::Handler => " [label=\"handler\", style=dashed]",
+            };
+            writeln!(dot, "  b{} -> b{}{};", edge.from, edge.to, attrs).unwrap();
+        }
+        writeln!(dot, "}}").unwrap();
+        dot
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cfg::{ControlFlowGraph, EdgeKind};
+    use crate::codegen::compile;
+
+    #[test]
+    fn straight_line_has_no_edges() {
+        let program = compile("x = 1; y = x + 2; return y;").unwrap();
+        let cfg = ControlFlowGraph::build(&program);
+        // The return ends the first block; the trailing Done is unreachable but still listed.
+        assert_eq!(cfg.blocks.len(), 2);
+        assert!(cfg.edges.is_empty());
+    }
+
+    #[test]
+    fn if_else_diamond() {
+        let program = compile("if (args) x = 1; else x = 2; endif return x;").unwrap();
+        let cfg = ControlFlowGraph::build(&program);
+        // cond, then, else, join (+ the unreachable trailing Done)
+        assert_eq!(cfg.blocks.len(), 5);
+        let kinds: Vec<_> = cfg.edges.iter().map(|e| (e.from, e.to, e.kind)).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                (0, 1, EdgeKind::Fallthrough),
+                (0, 2, EdgeKind::Branch),
+                (1, 3, EdgeKind::Jump),
+                (2, 3, EdgeKind::Fallthrough),
+            ]
+        );
+    }
+
+    #[test]
+    fn while_loop_back_edge() {
+        let program = compile("x = 0; while (x < 5) x = x + 1; endwhile return x;").unwrap();
+        let cfg = ControlFlowGraph::build(&program);
+        let dot = cfg.to_dot(&program);
+        assert!(dot.starts_with("digraph cfg {"));
+        assert_eq!(dot.matches("\\l\"];").count(), cfg.blocks.len());
+        assert_eq!(dot.matches(" -> ").count(), cfg.edges.len());
+        // The body jumps back to the loop condition.
+        assert!(cfg
+            .edges
+            .iter()
+            .any(|e| e.kind == EdgeKind::Jump && e.to < e.from));
+    }
+}
@@ -18,6 +18,7 @@ use thiserror::Error;
 
 mod ast;
 mod builtins;
+mod cfg;
 mod codegen;
 mod decompile;
 mod labels;
@@ -29,6 +30,7 @@ mod opcode;
 mod program;
 
 pub use crate::builtins::{offset_for_builtin, ArgCount, ArgType, Builtin, BUILTIN_DESCRIPTORS};
+pub use crate::cfg::{BasicBlock, ControlFlowGraph, Edge, EdgeKind};
 pub use crate::codegen::compile;
 pub use crate::decompile::program_to_tree;
 pub use crate::labels::{JumpLabel, Label, Name, Names, Offset};
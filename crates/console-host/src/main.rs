@@ -241,28 +241,8 @@ fn console_loop(
     std::thread::Builder::new()
         .name("output-loop".to_string())
         .spawn(move || loop {
-            match narrative_recv(client_id, &narr_sub_socket) {
-                Ok(ConnectionEvent::Narrative(_, msg)) => {
-                    printer
-                        .print(
-                            (match msg.event() {
-                                moor_values::model::Event::TextNotify(s) => s,
-                            })
-                            .to_string(),
-                        )
-                        .unwrap();
-                }
-                Ok(ConnectionEvent::SystemMessage(o, msg)) => {
-                    printer
-                        .print(format!("System message from {}: {}", o.yellow(), msg.red()))
-                        .unwrap();
-                }
-                Ok(ConnectionEvent::Disconnect()) => {
-                    printer
-                        .print("Received disconnect event; Session ending.".to_string())
-                        .unwrap();
-                    return;
-                }
+            let events = match narrative_recv(client_id, &narr_sub_socket) {
+                Ok(events) => events,
                 Err(error) => {
                     printer
                         .print(format!(
@@ -272,9 +252,34 @@ fn console_loop(
                         .unwrap();
                     return;
                 }
-                Ok(ConnectionEvent::RequestInput(requested_input_id)) => {
-                    (*output_input_request_id.lock().unwrap()) =
-                        Some(Uuid::from_u128(requested_input_id));
+            };
+            for event in events {
+                match event {
+                    ConnectionEvent::Narrative(_, msg) => {
+                        printer
+                            .print(
+                                (match msg.event() {
+                                    moor_values::model::Event::TextNotify(s, _) => s,
+                                })
+                                .to_string(),
+                            )
+                            .unwrap();
+                    }
+                    ConnectionEvent::SystemMessage(o, msg) => {
+                        printer
+                            .print(format!("System message from {}: {}", o.yellow(), msg.red()))
+                            .unwrap();
+                    }
+                    ConnectionEvent::Disconnect() => {
+                        printer
+                            .print("Received disconnect event; Session ending.".to_string())
+                            .unwrap();
+                        return;
+                    }
+                    ConnectionEvent::RequestInput(requested_input_id) => {
+                        (*output_input_request_id.lock().unwrap()) =
+                            Some(Uuid::from_u128(requested_input_id));
+                    }
                 }
             }
         })?;
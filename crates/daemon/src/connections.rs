@@ -59,6 +59,10 @@ pub trait ConnectionsDB {
     /// Return all connection objects (player or not)
     fn connections(&self) -> Vec<Objid>;
 
+    /// Return all players with at least one client connected, leaving out connections which
+    /// haven't logged in yet.
+    fn connected_players(&self) -> Vec<Objid>;
+
     /// Return whether the given client is a valid client.
     fn is_valid_client(&self, client_id: Uuid) -> bool;
 
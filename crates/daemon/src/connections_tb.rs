@@ -14,9 +14,9 @@
 
 //! An implementation of the connections db that uses rdb.
 
-use std::collections::HashSet;
+use std::collections::HashMap;
 use std::path::PathBuf;
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
 use std::time::{Duration, SystemTime};
 
 use eyre::Error;
@@ -85,6 +85,9 @@ enum ConnectionRelation {
 const CONNECTIONS_DB_MEM_SIZE: usize = 1 << 26;
 pub struct ConnectionsTb {
     tb: Arc<RelBox>,
+    /// The number of clients attached to each connection object, kept up to date as clients
+    /// connect, log in, and go away, so that listing connections doesn't need a scan.
+    client_counts: RwLock<HashMap<Objid, usize>>,
 }
 
 impl ConnectionsTb {
@@ -94,11 +97,83 @@ impl ConnectionsTb {
         relations[ConnectionRelation::ClientConnection as usize].secondary_indexed = true;
 
         let tb = RelBox::new(CONNECTIONS_DB_MEM_SIZE, path, &relations, 1);
-        Self { tb }
+
+        // Pick up whatever connections were persisted from the last run.
+        let mut client_counts = HashMap::new();
+        let tx = tb.clone().start_tx();
+        let clients = tx
+            .relation(RelationId(ConnectionRelation::ClientConnection as usize))
+            .predicate_scan(&|_| true)
+            .expect("Unable to scan client connection relation");
+        for client in clients {
+            let connection = client.codomain_as::<Objid>().expect("Invalid connection");
+            *client_counts.entry(connection).or_insert(0) += 1;
+        }
+        tx.commit().expect("Unable to commit transaction");
+
+        Self {
+            tb,
+            client_counts: RwLock::new(client_counts),
+        }
+    }
+}
+
+/// The clients a transaction attaches to and detaches from connection objects, to be applied to
+/// the counts only once it has committed.
+#[derive(Default)]
+struct ClientCountChanges {
+    attached: Vec<(Objid, usize)>,
+    detached: Vec<(Objid, usize)>,
+}
+
+impl ClientCountChanges {
+    fn attach(&mut self, connection: Objid, count: usize) {
+        self.attached.push((connection, count));
+    }
+
+    fn detach(&mut self, connection: Objid, count: usize) {
+        self.detached.push((connection, count));
+    }
+
+    fn apply(self, client_counts: &mut HashMap<Objid, usize>) {
+        for (connection, count) in self.detached {
+            let Some(remaining) = client_counts.get_mut(&connection) else {
+                continue;
+            };
+            *remaining = remaining.saturating_sub(count);
+            if *remaining == 0 {
+                client_counts.remove(&connection);
+            }
+        }
+        for (connection, count) in self.attached {
+            *client_counts.entry(connection).or_insert(0) += count;
+        }
     }
 }
 
 impl ConnectionsTb {
+    /// Run `f` in a transaction which changes which clients are attached to which connection
+    /// objects, and commit it. The counts stay locked until then, and get the changes `f`
+    /// records only if the commit succeeds, so they can't drift from the relations.
+    fn with_client_counts_tx<R>(
+        &self,
+        f: impl FnOnce(&Transaction, &mut ClientCountChanges) -> Result<R, Error>,
+    ) -> Result<R, Error> {
+        let mut client_counts = self.client_counts.write().unwrap();
+        let tx = self.tb.clone().start_tx();
+        let mut changes = ClientCountChanges::default();
+        let result = match f(&tx, &mut changes) {
+            Ok(result) => result,
+            Err(e) => {
+                tx.rollback()?;
+                return Err(e);
+            }
+        };
+        tx.commit()?;
+        changes.apply(&mut client_counts);
+        Ok(result)
+    }
+
     fn most_recent_client_connection(
         tx: &Transaction,
         connection_obj: Objid,
@@ -158,29 +233,32 @@ impl ConnectionsDB for ConnectionsTb {
         from_connection: Objid,
         to_player: Objid,
     ) -> Result<(), Error> {
-        let tx = self.tb.clone().start_tx();
-        let client_ids = tx
-            .relation(RelationId(ConnectionRelation::ClientConnection as usize))
-            .seek_by_codomain(
-                from_connection
-                    .as_sliceref()
-                    .expect("Invalid connection object"),
-            )
-            .expect("Unable to seek client connection");
-        if client_ids.is_empty() {
-            error!(?from_connection, ?to_player, "No client ids for connection");
-            return Err(Error::msg("No client ids for connection"));
-        }
-        for client_id in client_ids {
-            let _ = tx
+        self.with_client_counts_tx(|tx, changes| {
+            let client_ids = tx
                 .relation(RelationId(ConnectionRelation::ClientConnection as usize))
-                .update_by_domain(
-                    client_id.domain().clone(),
-                    to_player.as_sliceref().expect("Invalid player object"),
-                );
-        }
-        tx.commit()?;
-        Ok(())
+                .seek_by_codomain(
+                    from_connection
+                        .as_sliceref()
+                        .expect("Invalid connection object"),
+                )
+                .expect("Unable to seek client connection");
+            if client_ids.is_empty() {
+                error!(?from_connection, ?to_player, "No client ids for connection");
+                return Err(Error::msg("No client ids for connection"));
+            }
+            let num_clients = client_ids.len();
+            for client_id in client_ids {
+                let _ = tx
+                    .relation(RelationId(ConnectionRelation::ClientConnection as usize))
+                    .update_by_domain(
+                        client_id.domain().clone(),
+                        to_player.as_sliceref().expect("Invalid player object"),
+                    );
+            }
+            changes.detach(from_connection, num_clients);
+            changes.attach(to_player, num_clients);
+            Ok(())
+        })
     }
 
     fn new_connection(
@@ -201,30 +279,32 @@ impl ConnectionsDB for ConnectionsTb {
         };
 
         // Insert the initial tuples for the connection.
-        let tx = self.tb.clone().start_tx();
-        let client_id = SliceRef::from_bytes(client_id.as_bytes());
-        tx.relation(RelationId(ConnectionRelation::ClientConnection as usize))
-            .insert_tuple(
-                client_id.clone(),
-                connection_oid
-                    .as_sliceref()
-                    .expect("Invalid connection object"),
-            )
-            .expect("Unable to insert client connection");
-        tx.relation(RelationId(ConnectionRelation::ClientActivity as usize))
-            .insert_tuple(client_id.clone(), now_as_sliceref())
-            .expect("Unable to insert client activity");
-        tx.relation(RelationId(ConnectionRelation::ClientConnectTime as usize))
-            .insert_tuple(client_id.clone(), now_as_sliceref())
-            .expect("Unable to insert client connect time");
-        tx.relation(RelationId(ConnectionRelation::ClientPingTime as usize))
-            .insert_tuple(client_id.clone(), now_as_sliceref())
-            .expect("Unable to insert client ping time");
-        tx.relation(RelationId(ConnectionRelation::ClientName as usize))
-            .insert_tuple(client_id.clone(), SliceRef::from_bytes(hostname.as_bytes()))
-            .expect("Unable to insert client name");
-
-        tx.commit().expect("Unable to commit transaction");
+        self.with_client_counts_tx(|tx, changes| {
+            let client_id = SliceRef::from_bytes(client_id.as_bytes());
+            tx.relation(RelationId(ConnectionRelation::ClientConnection as usize))
+                .insert_tuple(
+                    client_id.clone(),
+                    connection_oid
+                        .as_sliceref()
+                        .expect("Invalid connection object"),
+                )
+                .expect("Unable to insert client connection");
+            tx.relation(RelationId(ConnectionRelation::ClientActivity as usize))
+                .insert_tuple(client_id.clone(), now_as_sliceref())
+                .expect("Unable to insert client activity");
+            tx.relation(RelationId(ConnectionRelation::ClientConnectTime as usize))
+                .insert_tuple(client_id.clone(), now_as_sliceref())
+                .expect("Unable to insert client connect time");
+            tx.relation(RelationId(ConnectionRelation::ClientPingTime as usize))
+                .insert_tuple(client_id.clone(), now_as_sliceref())
+                .expect("Unable to insert client ping time");
+            tx.relation(RelationId(ConnectionRelation::ClientName as usize))
+                .insert_tuple(client_id.clone(), SliceRef::from_bytes(hostname.as_bytes()))
+                .expect("Unable to insert client name");
+            changes.attach(connection_oid, 1);
+            Ok(())
+        })
+        .expect("Unable to commit transaction");
 
         Ok(connection_oid)
     }
@@ -260,36 +340,45 @@ impl ConnectionsDB for ConnectionsTb {
         // Full scan the last ping relation, and compare the last ping time to the current time.
         // If the difference is greater than the timeout duration, then we need to remove the
         // connection from all the relations.
-        let tx = self.tb.clone().start_tx();
-
-        let last_ping_relation =
-            tx.relation(RelationId(ConnectionRelation::ClientPingTime as usize));
-        let expired = last_ping_relation
-            .predicate_scan(&|ping| {
-                let last_ping_time = sliceref_as_time(ping.codomain());
-                last_ping_time < timeout_threshold
-            })
-            .expect("Unable to scan last ping relation");
-
-        for expired_ping in expired {
-            let client_id = expired_ping.domain().clone();
-            let _ = tx
-                .relation(RelationId(ConnectionRelation::ClientConnection as usize))
-                .remove_by_domain(client_id.clone());
-            let _ = tx
-                .relation(RelationId(ConnectionRelation::ClientActivity as usize))
-                .remove_by_domain(client_id.clone());
-            let _ = tx
-                .relation(RelationId(ConnectionRelation::ClientConnectTime as usize))
-                .remove_by_domain(client_id.clone());
-            let _ = tx
-                .relation(RelationId(ConnectionRelation::ClientPingTime as usize))
-                .remove_by_domain(client_id.clone());
-            let _ = tx
-                .relation(RelationId(ConnectionRelation::ClientName as usize))
-                .remove_by_domain(client_id.clone());
-        }
-        tx.commit().expect("Unable to commit transaction");
+        self.with_client_counts_tx(|tx, changes| {
+            let last_ping_relation =
+                tx.relation(RelationId(ConnectionRelation::ClientPingTime as usize));
+            let expired = last_ping_relation
+                .predicate_scan(&|ping| {
+                    let last_ping_time = sliceref_as_time(ping.codomain());
+                    last_ping_time < timeout_threshold
+                })
+                .expect("Unable to scan last ping relation");
+
+            for expired_ping in expired {
+                let client_id = expired_ping.domain().clone();
+                let client_connection =
+                    tx.relation(RelationId(ConnectionRelation::ClientConnection as usize));
+                if let Ok(connection) = client_connection.seek_unique_by_domain(client_id.clone()) {
+                    changes.detach(
+                        connection
+                            .codomain_as::<Objid>()
+                            .expect("Invalid connection"),
+                        1,
+                    );
+                }
+                let _ = client_connection.remove_by_domain(client_id.clone());
+                let _ = tx
+                    .relation(RelationId(ConnectionRelation::ClientActivity as usize))
+                    .remove_by_domain(client_id.clone());
+                let _ = tx
+                    .relation(RelationId(ConnectionRelation::ClientConnectTime as usize))
+                    .remove_by_domain(client_id.clone());
+                let _ = tx
+                    .relation(RelationId(ConnectionRelation::ClientPingTime as usize))
+                    .remove_by_domain(client_id.clone());
+                let _ = tx
+                    .relation(RelationId(ConnectionRelation::ClientName as usize))
+                    .remove_by_domain(client_id.clone());
+            }
+            Ok(())
+        })
+        .expect("Unable to commit transaction");
     }
 
     fn last_activity_for(&self, connection_obj: Objid) -> Result<SystemTime, SessionError> {
@@ -372,22 +461,18 @@ impl ConnectionsDB for ConnectionsTb {
     }
 
     fn connections(&self) -> Vec<Objid> {
-        // Full scan from ClientConnection relation to get all connections, and dump them into a
-        // hashset (to remove dupes) and return as a vector.
-        let tx = self.tb.clone().start_tx();
-        let mut connections = HashSet::new();
-        let clients = tx
-            .relation(RelationId(ConnectionRelation::ClientConnection as usize))
-            .predicate_scan(&|_| true)
-            .expect("Unable to scan client connection relation");
-
-        for client in clients {
-            let connection = Objid::from_sliceref(client.codomain()).expect("Invalid connection");
-            connections.insert(connection);
-        }
+        self.client_counts.read().unwrap().keys().cloned().collect()
+    }
 
-        tx.commit().expect("Unable to commit transaction");
-        connections.into_iter().collect()
+    fn connected_players(&self) -> Vec<Objid> {
+        // Connection objects for clients which haven't logged in are all negative.
+        self.client_counts
+            .read()
+            .unwrap()
+            .keys()
+            .filter(|o| o.0 > 0)
+            .cloned()
+            .collect()
     }
 
     fn is_valid_client(&self, client_id: Uuid) -> bool {
@@ -415,9 +500,11 @@ impl ConnectionsDB for ConnectionsTb {
                     .as_sliceref()
                     .expect("Invalid client id"),
             ) {
-            Ok(connection) => {
-                Some(Objid::from_sliceref(connection.codomain()).expect("Invalid connection"))
-            }
+            Ok(connection) => Some(
+                connection
+                    .codomain_as::<Objid>()
+                    .expect("Invalid connection"),
+            ),
             Err(_) => None,
         };
         tx.commit().expect("Unable to commit transaction");
@@ -425,50 +512,36 @@ impl ConnectionsDB for ConnectionsTb {
     }
 
     fn remove_client_connection(&self, client_id: Uuid) -> Result<(), Error> {
-        let tx = self.tb.clone().start_tx();
-        let _ = tx
-            .relation(RelationId(ConnectionRelation::ClientConnection as usize))
-            .remove_by_domain(
-                client_id
-                    .as_bytes()
-                    .as_sliceref()
-                    .expect("Invalid client id"),
-            );
-        let _ = tx
-            .relation(RelationId(ConnectionRelation::ClientActivity as usize))
-            .remove_by_domain(
-                client_id
-                    .as_bytes()
-                    .as_sliceref()
-                    .expect("Invalid client id"),
-            );
-        let _ = tx
-            .relation(RelationId(ConnectionRelation::ClientConnectTime as usize))
-            .remove_by_domain(
-                client_id
-                    .as_bytes()
-                    .as_sliceref()
-                    .expect("Invalid client id"),
-            );
-        let _ = tx
-            .relation(RelationId(ConnectionRelation::ClientPingTime as usize))
-            .remove_by_domain(
-                client_id
-                    .as_bytes()
-                    .as_sliceref()
-                    .expect("Invalid client id"),
-            );
-        let _ = tx
-            .relation(RelationId(ConnectionRelation::ClientName as usize))
-            .remove_by_domain(
-                client_id
-                    .as_bytes()
-                    .as_sliceref()
-                    .expect("Invalid client id"),
-            );
-
-        tx.commit()?;
-        Ok(())
+        let client_id = client_id
+            .as_bytes()
+            .as_sliceref()
+            .expect("Invalid client id");
+        self.with_client_counts_tx(|tx, changes| {
+            let client_connection =
+                tx.relation(RelationId(ConnectionRelation::ClientConnection as usize));
+            if let Ok(connection) = client_connection.seek_unique_by_domain(client_id.clone()) {
+                changes.detach(
+                    connection
+                        .codomain_as::<Objid>()
+                        .expect("Invalid connection"),
+                    1,
+                );
+            }
+            let _ = client_connection.remove_by_domain(client_id.clone());
+            let _ = tx
+                .relation(RelationId(ConnectionRelation::ClientActivity as usize))
+                .remove_by_domain(client_id.clone());
+            let _ = tx
+                .relation(RelationId(ConnectionRelation::ClientConnectTime as usize))
+                .remove_by_domain(client_id.clone());
+            let _ = tx
+                .relation(RelationId(ConnectionRelation::ClientPingTime as usize))
+                .remove_by_domain(client_id.clone());
+            let _ = tx
+                .relation(RelationId(ConnectionRelation::ClientName as usize))
+                .remove_by_domain(client_id.clone());
+            Ok(())
+        })
     }
 }
 
@@ -582,6 +655,67 @@ mod tests {
         }
     }
 
+    /// Connected players are tracked as clients connect, log in (possibly several at once, to the
+    /// same player), attach directly, and detach.
+    #[test]
+    fn test_connected_players() {
+        let db = ConnectionsTb::new(None);
+        let connected = |db: &ConnectionsTb| {
+            let mut players = db.connected_players();
+            players.sort_by_key(|o| o.0);
+            players
+        };
+
+        // A fresh connection isn't a player until it logs in.
+        let client1 = uuid::Uuid::new_v4();
+        let conn1 = db
+            .new_connection(client1, "localhost".to_string(), None)
+            .unwrap();
+        assert!(connected(&db).is_empty());
+        assert_eq!(db.connections(), vec![conn1]);
+
+        db.update_client_connection(conn1, Objid(5)).unwrap();
+        assert_eq!(connected(&db), vec![Objid(5)]);
+        assert_eq!(db.connections(), vec![Objid(5)]);
+
+        // A second client logging in as the same player takes over alongside the first.
+        let client2 = uuid::Uuid::new_v4();
+        let conn2 = db
+            .new_connection(client2, "localhost".to_string(), None)
+            .unwrap();
+        db.update_client_connection(conn2, Objid(5)).unwrap();
+        assert_eq!(connected(&db), vec![Objid(5)]);
+        assert_eq!(db.client_ids_for(Objid(5)).unwrap().len(), 2);
+
+        // Attaching with a player already known.
+        let client3 = uuid::Uuid::new_v4();
+        db.new_connection(client3, "localhost".to_string(), Some(Objid(6)))
+            .unwrap();
+        assert_eq!(connected(&db), vec![Objid(5), Objid(6)]);
+
+        // The player stays connected until their last client goes away.
+        db.remove_client_connection(client1).unwrap();
+        assert_eq!(connected(&db), vec![Objid(5), Objid(6)]);
+        db.remove_client_connection(client2).unwrap();
+        assert_eq!(connected(&db), vec![Objid(6)]);
+        db.remove_client_connection(client3).unwrap();
+        assert!(connected(&db).is_empty());
+        assert!(db.connections().is_empty());
+
+        // Removing an unknown client is harmless.
+        db.remove_client_connection(client3).unwrap();
+        assert!(db.connections().is_empty());
+
+        // A login that fails and rolls back changes no counts.
+        let client4 = uuid::Uuid::new_v4();
+        let conn4 = db
+            .new_connection(client4, "localhost".to_string(), None)
+            .unwrap();
+        assert!(db.update_client_connection(Objid(-1000), Objid(7)).is_err());
+        assert!(connected(&db).is_empty());
+        assert_eq!(db.connections(), vec![conn4]);
+    }
+
     // Validate that ping check works.
     #[test]
     fn ping_test() {
@@ -27,9 +27,10 @@ use rusty_paseto::core::Key;
 use tracing::info;
 
 use moor_db::DatabaseBuilder;
-use moor_kernel::config::Config;
+use moor_kernel::config::{Config, TaskLimits};
 use moor_kernel::tasks::scheduler::Scheduler;
-use moor_kernel::textdump::textdump_load;
+use moor_kernel::textdump::{textdump_load, VerbCompilation};
+use strum::VariantNames;
 
 use crate::rpc_server::zmq_loop;
 
@@ -59,6 +60,18 @@ struct Args {
     #[arg(short, long, value_name = "textdump", help = "Path to textdump to import", value_hint = ValueHint::FilePath)]
     textdump: Option<PathBuf>,
 
+    #[arg(
+        long,
+        value_name = "verb-compilation",
+        help = "When to compile the verbs in an imported textdump: all of them during import \
+                (eager), or each on its first call (lazy), which imports large cores much faster \
+                but defers compile errors until the broken verb is called, or all of them during \
+                import but storing any that fail uncompiled rather than failing (lenient)",
+        value_parser = clap_enum_variants!(VerbCompilation),
+        default_value = "eager"
+    )]
+    verb_compilation: VerbCompilation,
+
     #[arg(
         long,
         value_name = "textdump-output",
@@ -66,6 +79,29 @@ struct Args {
     )]
     textdump_out: Option<PathBuf>,
 
+    #[arg(
+        long,
+        value_name = "max-tasks-per-player",
+        help = "Maximum number of tasks a non-wizard player may have running or queued at once. \
+                Unlimited if not set."
+    )]
+    max_tasks_per_player: Option<usize>,
+
+    #[arg(
+        long,
+        value_name = "wizard-max-ticks",
+        help = "Tick limit for tasks running wizard-owned verbs. Wizards get the ordinary limits \
+                if neither this nor --wizard-max-seconds is set."
+    )]
+    wizard_max_ticks: Option<usize>,
+
+    #[arg(
+        long,
+        value_name = "wizard-max-seconds",
+        help = "Time limit, in seconds, for tasks running wizard-owned verbs."
+    )]
+    wizard_max_seconds: Option<u64>,
+
     #[arg(
         short,
         long,
@@ -137,6 +173,21 @@ struct Args {
     )]
     max_buffer_pool_bytes: usize,
 
+    #[arg(
+        long,
+        help = "Log an audit record of every property and verb definition, reparenting, and recycle",
+        default_value = "false"
+    )]
+    audit_log: bool,
+
+    #[arg(
+        long,
+        help = "Record which opcodes and lines of each verb every task executes, readable from MOO \
+                with coverage(). For test harnesses; slows execution down.",
+        default_value = "false"
+    )]
+    coverage: bool,
+
     #[arg(long, help = "Enable debug logging", default_value = "false")]
     debug: bool,
 }
@@ -205,7 +256,8 @@ fn main() -> Result<(), Report> {
     info!("Daemon starting...");
     let db_source_builder = DatabaseBuilder::new()
         .with_path(args.db.clone())
-        .with_memory_size(args.max_buffer_pool_bytes);
+        .with_memory_size(args.max_buffer_pool_bytes)
+        .with_audit_log(args.audit_log);
     let (db_source, freshly_made) = db_source_builder.open_db().unwrap();
     info!(path = ?args.db, "Opened database");
 
@@ -220,7 +272,7 @@ fn main() -> Result<(), Report> {
                 .clone()
                 .loader_client()
                 .expect("Unable to get loader interface from database");
-            textdump_load(loader_interface.clone(), textdump).unwrap();
+            textdump_load(loader_interface.clone(), textdump, args.verb_compilation).unwrap();
             let duration = start.elapsed();
             info!("Loaded textdump in {:?}", duration);
             loader_interface
@@ -229,8 +281,20 @@ fn main() -> Result<(), Report> {
         }
     }
 
+    let wizard_limits = match (args.wizard_max_ticks, args.wizard_max_seconds) {
+        (None, None) => None,
+        (max_ticks, max_seconds) => Some(TaskLimits {
+            max_ticks: max_ticks.unwrap_or(TaskLimits::FOREGROUND.max_ticks),
+            max_seconds: max_seconds.unwrap_or(TaskLimits::FOREGROUND.max_seconds),
+            max_stack_depth: TaskLimits::FOREGROUND.max_stack_depth,
+        }),
+    };
     let config = Config {
         textdump_output: args.textdump_out,
+        max_tasks_per_player: args.max_tasks_per_player,
+        wizard_limits,
+        coverage: args.coverage,
+        ..Default::default()
     };
 
     let state_source = db_source
@@ -12,6 +12,7 @@
 // this program. If not, see <https://www.gnu.org/licenses/>.
 //
 
+use std::collections::HashSet;
 use std::path::PathBuf;
 /// The core of the server logic for the RPC daemon
 use std::sync::{Arc, Mutex};
@@ -33,6 +34,7 @@ use moor_kernel::tasks::sessions::SessionError::DeliveryError;
 use moor_kernel::tasks::sessions::{Session, SessionError};
 use moor_kernel::tasks::TaskId;
 use moor_values::model::NarrativeEvent;
+use moor_values::model::ObjFlag;
 use moor_values::model::WorldStateSource;
 use moor_values::util::parse_into_words;
 use moor_values::var::Objid;
@@ -42,9 +44,9 @@ use moor_values::var::{v_bool, v_objid, v_str, v_string};
 use moor_values::SYSTEM_OBJECT;
 use rpc_common::RpcResponse::{LoginResult, NewConnection};
 use rpc_common::{
-    AuthToken, BroadcastEvent, ClientToken, ConnectType, ConnectionEvent, RpcRequest,
-    RpcRequestError, RpcResponse, RpcResult, BROADCAST_TOPIC, MOOR_AUTH_TOKEN_FOOTER,
-    MOOR_SESSION_TOKEN_FOOTER,
+    encode_payload, AuthToken, BroadcastEvent, ClientToken, ConnectType, ConnectionEvent,
+    RpcRequest, RpcRequestError, RpcResponse, RpcResult, BROADCAST_TOPIC, COMPRESSION_THRESHOLD,
+    MOOR_AUTH_TOKEN_FOOTER, MOOR_SESSION_TOKEN_FOOTER,
 };
 
 use crate::connections::ConnectionsDB;
@@ -57,6 +59,8 @@ pub struct RpcServer {
     world_state_source: Arc<dyn WorldStateSource>,
     scheduler: Arc<Scheduler>,
     connections: Arc<dyn ConnectionsDB + Send + Sync>,
+    /// Clients which have asked for their large event payloads to be compressed.
+    compressed_clients: Mutex<HashSet<Uuid>>,
 }
 
 #[derive(Debug, Clone, Eq, PartialEq)]
@@ -105,6 +109,7 @@ impl RpcServer {
             scheduler,
             connections,
             publish: Arc::new(Mutex::new(publish)),
+            compressed_clients: Mutex::new(HashSet::new()),
         }
     }
 
@@ -295,7 +300,7 @@ impl RpcServer {
                 )
             }
 
-            RpcRequest::Eval(token, auth_token, evalstr) => {
+            RpcRequest::Eval(token, auth_token, evalstr, show_result) => {
                 let Some(connection) = self.connections.connection_object_for_client(client_id)
                 else {
                     return make_response(Err(RpcRequestError::NoConnection));
@@ -318,7 +323,10 @@ impl RpcServer {
                     );
                     return make_response(Err(RpcRequestError::PermissionDenied));
                 };
-                make_response(self.clone().eval(client_id, connection, evalstr))
+                make_response(
+                    self.clone()
+                        .eval(client_id, connection, evalstr, show_result),
+                )
             }
             RpcRequest::Detach(token) => {
                 let Ok(_) = self.validate_client_token(token, client_id) else {
@@ -334,9 +342,21 @@ impl RpcServer {
                         "Unable to remove client connection".to_string(),
                     )));
                 };
+                self.compressed_clients.lock().unwrap().remove(&client_id);
 
                 make_response(Ok(RpcResponse::Disconnected))
             }
+            RpcRequest::RequestCompression(token) => {
+                let Ok(_) = self.validate_client_token(token, client_id) else {
+                    warn!(?client_id, "Client token validation failed for request");
+                    return make_response(Err(RpcRequestError::PermissionDenied));
+                };
+
+                debug!(?client_id, "Enabling payload compression for client");
+                self.compressed_clients.lock().unwrap().insert(client_id);
+
+                make_response(Ok(RpcResponse::CompressionEnabled))
+            }
         }
     }
 
@@ -386,7 +406,7 @@ impl RpcServer {
         let event_bytes = bincode::encode_to_vec(event, bincode::config::standard())
             .expect("Unable to serialize disconnection event");
         for client_id in all_client_ids {
-            let payload = vec![client_id.as_bytes().to_vec(), event_bytes.clone()];
+            let payload = self.client_payload(client_id, std::slice::from_ref(&event_bytes));
             publish.send_multipart(payload, 0).map_err(|e| {
                 error!(
                     "Unable to send disconnection event to narrative channel: {}",
@@ -400,8 +420,24 @@ impl RpcServer {
     }
 
     pub(crate) fn connected_players(&self) -> Result<Vec<Objid>, SessionError> {
-        let connections = self.connections.connections();
-        Ok(connections.iter().filter(|o| o.0 > 0).cloned().collect())
+        Ok(self.connections.connected_players())
+    }
+
+    /// Whether the object has the player flag. Only players can be logged in as; in particular,
+    /// clearing the flag with `set_player_flag` stops any further logins.
+    fn is_player(&self, obj: Objid) -> bool {
+        let Ok(mut world_state) = self.world_state_source.new_world_state() else {
+            return false;
+        };
+        let is_player = world_state
+            .flags_of(obj)
+            .map(|flags| flags.contains(ObjFlag::User))
+            .unwrap_or(false);
+        // Only read from, so there's nothing to commit.
+        if let Err(e) = world_state.rollback() {
+            warn!(?e, "Could not roll back player flag check");
+        }
+        is_player
     }
 
     fn request_sys_prop(
@@ -491,7 +527,7 @@ impl RpcServer {
                 // with its new player objid and login result.
                 // If it's not an objid, that's considered an auth failure.
                 match v.variant() {
-                    Variant::Obj(o) => *o,
+                    Variant::Obj(o) if self.is_player(*o) => *o,
                     _ => {
                         return Ok(LoginResult(None));
                     }
@@ -721,16 +757,19 @@ impl RpcServer {
         client_id: Uuid,
         connection: Objid,
         expression: String,
+        show_result: bool,
     ) -> Result<RpcResponse, RpcRequestError> {
         let Ok(session) = self.clone().new_session(client_id, connection) else {
             return Err(RpcRequestError::CreateSessionFailed);
         };
 
-        let task_id = match self
-            .clone()
-            .scheduler
-            .submit_eval_task(connection, connection, expression, session)
-        {
+        let task_id = match self.clone().scheduler.submit_eval_task(
+            connection,
+            connection,
+            expression,
+            show_result,
+            session,
+        ) {
             Ok(t) => t,
             Err(e) => {
                 error!(error = ?e, "Error submitting eval task");
@@ -764,23 +803,48 @@ impl RpcServer {
         }
     }
 
+    /// Build the multipart message carrying `events` to `client_id`: the client id, then each
+    /// event in order, framed by `encode_payload`. If the client asked for compression and the
+    /// events add up to enough to be worth it, they're instead packed back to back into a single
+    /// compressed frame.
+    fn client_payload(&self, client_id: Uuid, events: &[Vec<u8>]) -> Vec<Vec<u8>> {
+        let mut payload = vec![client_id.as_bytes().to_vec()];
+        let compress = self.compressed_clients.lock().unwrap().contains(&client_id);
+        let total_len: usize = events.iter().map(|e| e.len()).sum();
+        if compress && total_len >= COMPRESSION_THRESHOLD {
+            payload.push(encode_payload(&events.concat(), true));
+        } else {
+            payload.extend(events.iter().map(|e| encode_payload(e, false)));
+        }
+        payload
+    }
+
+    /// Publish a task's narrative events. All the events bound for one client go out together as
+    /// a single multipart message, rather than as a message apiece.
     pub(crate) fn publish_narrative_events(
         &self,
         events: &[(Objid, NarrativeEvent)],
     ) -> Result<(), Error> {
-        let publish = self.publish.lock().unwrap();
+        let mut batches: Vec<(Uuid, Vec<Vec<u8>>)> = vec![];
         for (player, event) in events {
             let client_ids = self.connections.client_ids_for(*player)?;
             let event = ConnectionEvent::Narrative(*player, event.clone());
             let event_bytes = bincode::encode_to_vec(&event, bincode::config::standard())?;
-            for client_id in &client_ids {
-                let payload = vec![client_id.as_bytes().to_vec(), event_bytes.clone()];
-                publish.send_multipart(payload, 0).map_err(|e| {
-                    error!(error = ?e, "Unable to send narrative event");
-                    DeliveryError
-                })?;
+            for client_id in client_ids {
+                match batches.iter_mut().find(|(id, _)| *id == client_id) {
+                    Some((_, client_events)) => client_events.push(event_bytes.clone()),
+                    None => batches.push((client_id, vec![event_bytes.clone()])),
+                }
             }
         }
+        let publish = self.publish.lock().unwrap();
+        for (client_id, client_events) in batches {
+            let payload = self.client_payload(client_id, &client_events);
+            publish.send_multipart(payload, 0).map_err(|e| {
+                error!(error = ?e, "Unable to send narrative event");
+                DeliveryError
+            })?;
+        }
         Ok(())
     }
 
@@ -793,7 +857,7 @@ impl RpcServer {
         let event = ConnectionEvent::SystemMessage(player, message);
         let event_bytes = bincode::encode_to_vec(event, bincode::config::standard())
             .expect("Unable to serialize system message");
-        let payload = vec![client_id.as_bytes().to_vec(), event_bytes];
+        let payload = self.client_payload(client_id, &[event_bytes]);
         {
             let publish = self.publish.lock().unwrap();
             publish.send_multipart(payload, 0).map_err(|e| {
@@ -816,24 +880,31 @@ impl RpcServer {
         // Mark this client as in `input mode`, which means that instead of dispatching its next
         // line to the scheduler as a command, it should instead dispatch it as an input event.
 
-        // Validate first.
-        let Some(connection) = self.connections.connection_object_for_client(client_id) else {
-            return Err(SessionError::NoConnectionForPlayer(player));
+        // Usually the task is reading from the player on this client. But a wizard can read()
+        // from someone else, in which case we ask all of that player's clients, and whichever
+        // answers first gets the line in.
+        let client_ids = if self.connections.connection_object_for_client(client_id) == Some(player)
+        {
+            vec![client_id]
+        } else {
+            self.connections.client_ids_for(player)?
         };
-        if connection != player {
+        if client_ids.is_empty() {
             return Err(SessionError::NoConnectionForPlayer(player));
         }
 
         let event = ConnectionEvent::RequestInput(input_request_id.as_u128());
         let event_bytes = bincode::encode_to_vec(event, bincode::config::standard())
             .expect("Unable to serialize input request");
-        let payload = vec![client_id.as_bytes().to_vec(), event_bytes];
         {
             let publish = self.publish.lock().unwrap();
-            publish.send_multipart(payload, 0).map_err(|e| {
-                error!(error = ?e, "Unable to send input request");
-                DeliveryError
-            })?;
+            for client_id in client_ids {
+                let payload = self.client_payload(client_id, std::slice::from_ref(&event_bytes));
+                publish.send_multipart(payload, 0).map_err(|e| {
+                    error!(error = ?e, "Unable to send input request");
+                    DeliveryError
+                })?;
+            }
         }
         Ok(())
     }
@@ -843,7 +914,11 @@ impl RpcServer {
         let event_bytes = bincode::encode_to_vec(event, bincode::config::standard()).unwrap();
 
         // We want responses from all clients, so send on this broadcast "topic"
-        let payload = vec![BROADCAST_TOPIC.to_vec(), event_bytes];
+        // Broadcasts go to every client alike, so they're never compressed.
+        let payload = vec![
+            BROADCAST_TOPIC.to_vec(),
+            encode_payload(&event_bytes, false),
+        ];
         {
             let publish = self.publish.lock().unwrap();
             publish.send_multipart(payload, 0).map_err(|e| {
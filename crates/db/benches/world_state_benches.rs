@@ -0,0 +1,111 @@
+// Copyright (C) 2024 Ryan Daum <ryan.daum@gmail.com>
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU General Public License as published by the Free Software
+// Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// this program. If not, see <https://www.gnu.org/licenses/>.
+//
+
+//! Benchmarks of the world state lookups done on every command: finding the verb to run for a
+//! parsed command by trying the player, their location, and the direct object, in that order.
+
+use criterion::{criterion_group, criterion_main, Criterion};
+
+use moor_db::odb::RelBoxWorldState;
+use moor_values::model::{ArgSpec, BinaryType, PrepSpec, VerbArgsSpec, VerbFlag};
+use moor_values::model::{CommitResult, ObjFlag, WorldState, WorldStateSource};
+use moor_values::util::BitEnum;
+use moor_values::var::Objid;
+use moor_values::{NOTHING, SYSTEM_OBJECT};
+
+struct World {
+    db: RelBoxWorldState,
+    player: Objid,
+    room: Objid,
+    ball: Objid,
+}
+
+fn create_world() -> World {
+    let (db, _) = RelBoxWorldState::open(None, 1 << 24);
+    let mut tx = db.new_world_state().unwrap();
+    let sysobj = tx
+        .create_object(SYSTEM_OBJECT, NOTHING, SYSTEM_OBJECT, BitEnum::all())
+        .unwrap();
+    // The player isn't the owner of anything, so everything has to be readable for their sake.
+    let readable = BitEnum::new_with(ObjFlag::Read);
+    let room = tx
+        .create_object(SYSTEM_OBJECT, sysobj, SYSTEM_OBJECT, readable)
+        .unwrap();
+    let player = tx
+        .create_object(SYSTEM_OBJECT, sysobj, SYSTEM_OBJECT, readable)
+        .unwrap();
+    let ball = tx
+        .create_object(SYSTEM_OBJECT, sysobj, SYSTEM_OBJECT, readable)
+        .unwrap();
+    tx.move_object(SYSTEM_OBJECT, player, room).unwrap();
+    tx.move_object(SYSTEM_OBJECT, ball, room).unwrap();
+
+    // Only the ball has a verb for the command, so the player and room are both misses first.
+    tx.add_verb(
+        SYSTEM_OBJECT,
+        ball,
+        vec!["kick".to_string()],
+        SYSTEM_OBJECT,
+        VerbFlag::rxd(),
+        VerbArgsSpec {
+            dobj: ArgSpec::This,
+            prep: PrepSpec::None,
+            iobj: ArgSpec::None,
+        },
+        vec![],
+        BinaryType::LambdaMoo18X,
+    )
+    .unwrap();
+    assert_eq!(tx.commit().unwrap(), CommitResult::Success);
+    World {
+        db,
+        player,
+        room,
+        ball,
+    }
+}
+
+fn find_command_verb(tx: &dyn WorldState, world: &World) {
+    for obj in [world.player, world.room, world.ball] {
+        let found = tx
+            .find_command_verb_on(
+                world.player,
+                obj,
+                "kick",
+                world.ball,
+                PrepSpec::None,
+                NOTHING,
+            )
+            .unwrap();
+        if found.is_some() {
+            return;
+        }
+    }
+    panic!("no verb found for command");
+}
+
+fn command_match(c: &mut Criterion) {
+    let world = create_world();
+    let tx = world.db.new_world_state().unwrap();
+
+    let mut group = c.benchmark_group("command_match");
+    group.throughput(criterion::Throughput::Elements(1));
+    group.bench_function("find_command_verb", |b| {
+        b.iter(|| find_command_verb(tx.as_ref(), &world));
+    });
+    group.finish();
+}
+
+criterion_group!(benches, command_match);
+criterion_main!(benches);
@@ -39,9 +39,24 @@ impl LoaderInterface for DbTxWorldState {
     ) -> Result<Objid, WorldStateError> {
         self.tx.create_object(objid, attrs.clone())
     }
+    fn object_valid(&self, obj: Objid) -> Result<bool, WorldStateError> {
+        self.tx.object_valid(obj)
+    }
+    fn update_object(&self, obj: Objid, attrs: &ObjAttrs) -> Result<(), WorldStateError> {
+        if let Some(name) = &attrs.name {
+            self.tx.set_object_name(obj, name.clone())?;
+        }
+        if let Some(flags) = attrs.flags {
+            self.tx.set_object_flags(obj, flags)?;
+        }
+        Ok(())
+    }
     fn set_object_parent(&self, obj: Objid, parent: Objid) -> Result<(), WorldStateError> {
         self.tx.set_object_parent(obj, parent)
     }
+    fn set_object_parents(&self, obj: Objid, parents: ObjSet) -> Result<(), WorldStateError> {
+        self.tx.set_object_parents(obj, parents)
+    }
     fn set_object_location(&self, o: Objid, location: Objid) -> Result<(), WorldStateError> {
         self.tx.set_object_location(o, location)
     }
@@ -56,15 +71,18 @@ impl LoaderInterface for DbTxWorldState {
         flags: BitEnum<VerbFlag>,
         args: VerbArgsSpec,
         binary: Vec<u8>,
+        binary_type: BinaryType,
+        uuid: Option<Uuid>,
     ) -> Result<(), WorldStateError> {
         self.tx.add_object_verb(
             obj,
             owner,
             names.iter().map(|s| s.to_string()).collect(),
             binary,
-            BinaryType::LambdaMoo18X,
+            binary_type,
             flags,
             args,
+            uuid,
         )?;
         Ok(())
     }
@@ -77,9 +95,17 @@ impl LoaderInterface for DbTxWorldState {
         owner: Objid,
         flags: BitEnum<PropFlag>,
         value: Option<Var>,
+        uuid: Option<Uuid>,
     ) -> Result<(), WorldStateError> {
-        self.tx
-            .define_property(definer, objid, propname.to_string(), owner, flags, value)?;
+        self.tx.define_property(
+            definer,
+            objid,
+            propname.to_string(),
+            owner,
+            flags,
+            value,
+            uuid,
+        )?;
         Ok(())
     }
     fn set_property(
@@ -109,6 +135,14 @@ impl LoaderInterface for DbTxWorldState {
         Ok(())
     }
 
+    fn remove_verb(&self, obj: Objid, uuid: Uuid) -> Result<(), WorldStateError> {
+        self.tx.delete_verb(obj, uuid)
+    }
+
+    fn remove_property(&self, obj: Objid, uuid: Uuid) -> Result<(), WorldStateError> {
+        self.tx.delete_property(obj, uuid)
+    }
+
     fn commit(&self) -> Result<CommitResult, WorldStateError> {
         let cr = self.tx.commit()?;
         Ok(cr)
@@ -118,18 +152,24 @@ impl LoaderInterface for DbTxWorldState {
         self.tx.get_objects()
     }
 
+    fn timestamp(&self) -> u64 {
+        self.tx.timestamp()
+    }
+
+    fn objects_modified_since(&self, ts: u64) -> Result<ObjSet, WorldStateError> {
+        self.tx.objects_modified_since(ts)
+    }
+
     fn get_players(&self) -> Result<ObjSet, WorldStateError> {
         self.tx.get_players()
     }
 
     fn get_object(&self, objid: Objid) -> Result<ObjAttrs, WorldStateError> {
-        Ok(ObjAttrs {
-            owner: Some(self.tx.get_object_owner(objid)?),
-            name: Some(self.tx.get_object_name(objid)?),
-            parent: Some(self.tx.get_object_parent(objid)?),
-            location: Some(self.tx.get_object_location(objid)?),
-            flags: Some(self.tx.get_object_flags(objid)?),
-        })
+        self.tx.get_object_attrs(objid)
+    }
+
+    fn get_object_parents(&self, objid: Objid) -> Result<ObjSet, WorldStateError> {
+        self.tx.get_object_parents(objid)
     }
 
     fn get_object_verbs(&self, objid: Objid) -> Result<VerbDefs, WorldStateError> {
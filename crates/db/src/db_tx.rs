@@ -15,11 +15,11 @@
 use uuid::Uuid;
 
 use moor_values::model::ObjSet;
-use moor_values::model::PropFlag;
 use moor_values::model::VerbArgsSpec;
 use moor_values::model::{BinaryType, VerbAttrs, VerbFlag};
 use moor_values::model::{CommitResult, WorldStateError};
 use moor_values::model::{ObjAttrs, ObjFlag};
+use moor_values::model::{PropAttrs, PropFlag};
 use moor_values::model::{PropDef, PropDefs};
 use moor_values::model::{VerbDef, VerbDefs};
 use moor_values::util::BitEnum;
@@ -34,9 +34,16 @@ pub trait DbTransaction {
     /// Check the validity of the given object.
     fn object_valid(&self, obj: Objid) -> Result<bool, WorldStateError>;
 
-    /// Returns all the ancestors (+ self) of the given object, in order from self to root.
+    /// Returns all the ancestors (+ self) of the given object, in the order that verbs and property
+    /// values are resolved: self first, then each parent's ancestors in turn, depth-first and left
+    /// to right, with an ancestor shared by several parents appearing only once, at its first
+    /// occurrence. For single-parent objects that is simply self to root.
     fn ancestors(&self, obj: Objid) -> Result<ObjSet, WorldStateError>;
 
+    /// Returns all the descendants (not including self) of the given object, including those that
+    /// inherit from it as an additional parent.
+    fn descendants(&self, obj: Objid) -> Result<ObjSet, WorldStateError>;
+
     /// Get the list of all objects
     fn get_objects(&self) -> Result<ObjSet, WorldStateError>;
 
@@ -49,6 +56,10 @@ pub trait DbTransaction {
     /// Get the highest "object #" in the database.
     fn get_max_object(&self) -> Result<Objid, WorldStateError>;
 
+    /// Lower the highest "object #" to that of the highest valid object, so that the numbers of
+    /// recycled objects above it are handed out again.
+    fn reset_max_object(&self) -> Result<(), WorldStateError>;
+
     /// Get the owner of the given object.
     fn get_object_owner(&self, obj: Objid) -> Result<Objid, WorldStateError>;
 
@@ -61,6 +72,10 @@ pub trait DbTransaction {
     /// Get the name of the given object.
     fn get_object_name(&self, obj: Objid) -> Result<String, WorldStateError>;
 
+    /// Get the flags, owner, name, parent, and location of the given object all at once, for
+    /// callers that need several of them.
+    fn get_object_attrs(&self, obj: Objid) -> Result<ObjAttrs, WorldStateError>;
+
     /// Set the name of the given object.
     fn set_object_name(&self, obj: Objid, name: String) -> Result<(), WorldStateError>;
 
@@ -70,6 +85,11 @@ pub trait DbTransaction {
 
     /// Destroy the given object, and restructure the property inheritance accordingly.
     fn recycle_object(&self, obj: Objid) -> Result<(), WorldStateError>;
+
+    /// Move the given object to the lowest free object number below its current one (if there is
+    /// one), rewriting the parent, location, and owner references of other objects, verbs, and
+    /// properties to match. Returns the object's new number.
+    fn renumber_object(&self, obj: Objid) -> Result<Objid, WorldStateError>;
     /// Get the parent of the given object.
 
     fn get_object_parent(&self, obj: Objid) -> Result<Objid, WorldStateError>;
@@ -77,6 +97,16 @@ pub trait DbTransaction {
     /// Set the parent of the given object, and restructure the property inheritance accordingly.
     fn set_object_parent(&self, obj: Objid, parent: Objid) -> Result<(), WorldStateError>;
 
+    /// Get the full ordered list of parents of the given object: the primary parent (as returned
+    /// by `get_object_parent`), then any additional parents. Empty if the object has no parent.
+    fn get_object_parents(&self, obj: Objid) -> Result<ObjSet, WorldStateError>;
+
+    /// Replace the parent list of the given object. The first entry becomes the primary parent,
+    /// the rest are additional parents consulted in order after it. Inherited property definitions
+    /// are rebuilt for the object and its descendants; fails without changing anything if that
+    /// would leave an object inheriting two distinct properties of the same name.
+    fn set_object_parents(&self, obj: Objid, parents: ObjSet) -> Result<(), WorldStateError>;
+
     /// Get the children of the given object.
     fn get_object_children(&self, obj: Objid) -> Result<ObjSet, WorldStateError>;
 
@@ -122,7 +152,8 @@ pub trait DbTransaction {
         verb_attrs: VerbAttrs,
     ) -> Result<(), WorldStateError>;
 
-    /// Define a new verb on the given object.
+    /// Define a new verb on the given object. It's given a fresh identity unless `uuid` is
+    /// provided, e.g. to keep the one it had in a textdump.
     // Yes yes I know it's a lot of arguments, but wrapper object here is redundant.
     #[allow(clippy::too_many_arguments)]
     fn add_object_verb(
@@ -134,6 +165,7 @@ pub trait DbTransaction {
         binary_type: BinaryType,
         flags: BitEnum<VerbFlag>,
         args: VerbArgsSpec,
+        uuid: Option<Uuid>,
     ) -> Result<(), WorldStateError>;
 
     /// Remove the given verb from the given object.
@@ -145,7 +177,9 @@ pub trait DbTransaction {
     /// Set the property value on the given object.
     fn set_property(&self, obj: Objid, uuid: Uuid, value: Var) -> Result<(), WorldStateError>;
 
-    /// Define a new property on the given object, and propagate it to all children.
+    /// Define a new property on the given object, and propagate it to all children. As with verbs,
+    /// it gets a fresh identity unless `uuid` is provided.
+    #[allow(clippy::too_many_arguments)]
     fn define_property(
         &self,
         definer: Objid,
@@ -154,6 +188,7 @@ pub trait DbTransaction {
         owner: Objid,
         perms: BitEnum<PropFlag>,
         value: Option<Var>,
+        uuid: Option<Uuid>,
     ) -> Result<Uuid, WorldStateError>;
 
     /// Set the property info on the given object.
@@ -166,6 +201,14 @@ pub trait DbTransaction {
         new_name: Option<String>,
     ) -> Result<(), WorldStateError>;
 
+    /// Set the property info for several properties on the given object at once. If any of them
+    /// can't be found, none of them are updated.
+    fn update_property_definitions(
+        &self,
+        obj: Objid,
+        updates: Vec<(Uuid, PropAttrs)>,
+    ) -> Result<(), WorldStateError>;
+
     /// "Clear" the local value of the property on the given object so that it inherits from its
     /// parent.
     fn clear_property(&self, obj: Objid, uuid: Uuid) -> Result<(), WorldStateError>;
@@ -184,9 +227,32 @@ pub trait DbTransaction {
     /// Return the (rough) size of the database in bytes.
     fn db_usage(&self) -> Result<usize, WorldStateError>;
 
+    /// The timestamp of this transaction; everything it can see was committed before it.
+    fn timestamp(&self) -> u64;
+
+    /// The objects changed by transactions committed after timestamp `ts`, including ones which
+    /// have since been recycled. Anything loaded from disk at startup counts as committed at 0.
+    fn objects_modified_since(&self, ts: u64) -> Result<ObjSet, WorldStateError>;
+
+    /// Check the relations for broken invariants (parent/children and location/contents links
+    /// that don't agree, dangling references, missing verb programs, and the like), returning a
+    /// description of each problem found.
+    fn verify_database(&self) -> Result<Vec<String>, WorldStateError>;
+
+    /// Fix what `verify_database` finds, where there's an obvious fix, returning a description
+    /// of each one. With `dry_run`, only report what would be done.
+    fn repair_database(&self, dry_run: bool) -> Result<Vec<String>, WorldStateError>;
+
     /// Attempt to commit the transaction, returning the result of the commit.
     fn commit(&self) -> Result<CommitResult, WorldStateError>;
 
     /// Throw away all local mutations.
     fn rollback(&self) -> Result<(), WorldStateError>;
+
+    /// Mark the current state of the transaction, returning a handle for `rollback_to`.
+    fn savepoint(&self) -> Result<usize, WorldStateError>;
+
+    /// Throw away the local mutations made since `savepoint` was taken, along with any savepoints
+    /// taken after it.
+    fn rollback_to(&self, savepoint: usize) -> Result<(), WorldStateError>;
 }
@@ -12,10 +12,14 @@
 // this program. If not, see <https://www.gnu.org/licenses/>.
 //
 
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::time::{SystemTime, UNIX_EPOCH};
 use uuid::Uuid;
 
 use moor_values::model::CommitResult;
 use moor_values::model::HasUuid;
+use moor_values::model::Named;
 use moor_values::model::ObjSet;
 use moor_values::model::Perms;
 use moor_values::model::VerbInfo;
@@ -36,15 +40,238 @@ use moor_values::NOTHING;
 
 use crate::db_tx::DbTransaction;
 
+/// The tracing target audit records are logged under, so they can be filtered out or sent elsewhere.
+pub const AUDIT_TARGET: &str = "moor_db::audit";
+
 pub struct DbTxWorldState {
     pub tx: Box<dyn DbTransaction>,
+    /// Properties resolved by `retrieve_property` in this transaction, by object and name, so that
+    /// repeated reads don't walk to the definer every time.
+    property_cache: RefCell<HashMap<(Objid, String), (PropDef, Var)>>,
+    /// The flags and owners of objects looked up in this transaction. Permission checks want these
+    /// over and over, often for the same few objects.
+    flags_cache: RefCell<HashMap<Objid, BitEnum<ObjFlag>>>,
+    owner_cache: RefCell<HashMap<Objid, Objid>>,
+    /// Whether to log an audit record for each change to the world's structure (definitions,
+    /// parentage, recycling).
+    audit_log: bool,
 }
 
 impl DbTxWorldState {
+    pub fn new(tx: Box<dyn DbTransaction>) -> Self {
+        Self {
+            tx,
+            property_cache: RefCell::new(HashMap::new()),
+            flags_cache: RefCell::new(HashMap::new()),
+            owner_cache: RefCell::new(HashMap::new()),
+            audit_log: false,
+        }
+    }
+
+    pub fn with_audit_log(mut self, audit_log: bool) -> Self {
+        self.audit_log = audit_log;
+        self
+    }
+
+    /// Log who did what to which object, for after-the-fact forensics, if auditing is on.
+    fn audit(&self, event: &str, actor: Objid, obj: Objid, detail: &str) {
+        if !self.audit_log {
+            return;
+        }
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis())
+            .unwrap_or_default();
+        tracing::info!(target: AUDIT_TARGET, event, %actor, object = %obj, detail, timestamp);
+    }
+
     fn perms(&self, who: Objid) -> Result<Perms, WorldStateError> {
         let flags = self.flags_of(who)?;
         Ok(Perms { who, flags })
     }
+
+    /// If `owner` has an integer `ownership_quota` property, return it along with the uuid of the
+    /// property so it can be written back.
+    fn ownership_quota(&self, owner: Objid) -> Option<(Uuid, i64)> {
+        let (ph, value) = self
+            .tx
+            .resolve_property(owner, "ownership_quota".to_string())
+            .ok()?;
+        let Variant::Int(quota) = value.variant() else {
+            return None;
+        };
+        Some((ph.uuid(), *quota))
+    }
+
+    /// Check that giving the properties on `obj` the new names in `renames` (by uuid) leaves no
+    /// two properties with the same name there or on its descendants. The renames are checked
+    /// against each other too, so a batch can't give two properties one name, but can swap
+    /// names between properties.
+    fn check_property_renames(
+        &self,
+        obj: Objid,
+        renames: &[(Uuid, &str)],
+    ) -> Result<(), WorldStateError> {
+        let collides = |uuid: Uuid, name: &str| {
+            renames
+                .iter()
+                .any(|(other, other_name)| *other != uuid && other_name.eq_ignore_ascii_case(name))
+        };
+        let renamed = |p: &PropDef| renames.iter().any(|(uuid, _)| *uuid == p.uuid());
+        for (uuid, name) in renames {
+            if collides(*uuid, name) {
+                return Err(WorldStateError::DuplicatePropertyDefinition(
+                    obj,
+                    name.to_string(),
+                ));
+            }
+        }
+        let locations = ObjSet::from(&[obj]).with_concatenated(self.tx.descendants(obj)?);
+        for location in locations.iter() {
+            for p in self.tx.get_properties(location)?.iter() {
+                if renamed(&p) {
+                    continue;
+                }
+                if let Some((_, name)) = renames.iter().find(|(_, name)| p.matches_name(name)) {
+                    return Err(WorldStateError::DuplicatePropertyDefinition(
+                        location,
+                        name.to_string(),
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Find the property `pname` on `obj`, and check that `perms` has `access` to it.
+    /// The errors come in a fixed order for every property operation: an invalid object is
+    /// `ObjectNotFound`, then a missing property is `PropertyNotFound`, and only then are
+    /// permissions checked. As in LambdaMOO, that means E_PERM vs E_PROPNF shows whether an
+    /// unreadable property exists, but it does so the same way everywhere.
+    fn find_property(
+        &self,
+        perms: Objid,
+        obj: Objid,
+        pname: &str,
+        access: PropFlag,
+    ) -> Result<PropDef, WorldStateError> {
+        if !self.valid(obj)? {
+            return Err(WorldStateError::ObjectNotFound(obj));
+        }
+        let ph = self
+            .tx
+            .get_properties(obj)?
+            .find_first_named(pname)
+            .ok_or(WorldStateError::PropertyNotFound(obj, pname.into()))?;
+        self.perms(perms)?
+            .check_property_allows(ph.owner(), ph.flags(), access)?;
+        Ok(ph)
+    }
+
+    fn resolve_property(&self, obj: Objid, pname: &str) -> Result<(PropDef, Var), WorldStateError> {
+        let key = (obj, pname.to_string());
+        if let Some(resolved) = self.property_cache.borrow().get(&key) {
+            return Ok(resolved.clone());
+        }
+        let resolved = self.tx.resolve_property(obj, pname.to_string())?;
+        self.property_cache
+            .borrow_mut()
+            .insert(key, resolved.clone());
+        Ok(resolved)
+    }
+
+    /// Create an object, numbered `id` if given, or the next number up otherwise.
+    fn do_create_object(
+        &mut self,
+        perms: Objid,
+        id: Option<Objid>,
+        parent: Objid,
+        owner: Objid,
+        flags: BitEnum<ObjFlag>,
+    ) -> Result<Objid, WorldStateError> {
+        if parent != NOTHING {
+            let (flags, parent_owner) = (self.flags_of(parent)?, self.owner_of(parent)?);
+            self.perms(perms)?.check_object_allows(
+                parent_owner,
+                flags,
+                BitEnum::new_with(ObjFlag::Read) | ObjFlag::Fertile,
+            )?;
+        }
+
+        let owner = (owner != NOTHING).then_some(owner);
+
+        // If the intended owner of the new object has a property named `ownership_quota' and the
+        // value of that property is an integer, then `create()' treats that value as a "quota".
+        // If the quota is less than or equal to zero, then the quota is considered to be exhausted
+        // and `create()' raises `E_QUOTA' instead of creating an object.  Otherwise, the quota is
+        // decremented and stored back into the `ownership_quota' property as a part of the
+        // creation of the new object.
+        if let Some(owner) = owner {
+            if let Some((quota_prop, quota)) = self.ownership_quota(owner) {
+                if quota <= 0 {
+                    return Err(WorldStateError::QuotaExceeded(owner));
+                }
+                self.invalidate_properties(owner)?;
+                self.tx.set_property(owner, quota_prop, v_int(quota - 1))?;
+            }
+        }
+
+        let attrs = ObjAttrs {
+            owner,
+            name: None,
+            parent: Some(parent),
+            location: None,
+            flags: Some(flags),
+        };
+        self.tx.create_object(id, attrs)
+    }
+
+    fn cached_flags(&self, obj: Objid) -> Result<BitEnum<ObjFlag>, WorldStateError> {
+        if let Some(flags) = self.flags_cache.borrow().get(&obj) {
+            return Ok(*flags);
+        }
+        let flags = self.tx.get_object_flags(obj)?;
+        self.flags_cache.borrow_mut().insert(obj, flags);
+        Ok(flags)
+    }
+
+    fn cached_owner(&self, obj: Objid) -> Result<Objid, WorldStateError> {
+        if let Some(owner) = self.owner_cache.borrow().get(&obj) {
+            return Ok(*owner);
+        }
+        let owner = self.tx.get_object_owner(obj)?;
+        self.owner_cache.borrow_mut().insert(obj, owner);
+        Ok(owner)
+    }
+
+    fn set_object_flags(&self, obj: Objid, flags: BitEnum<ObjFlag>) -> Result<(), WorldStateError> {
+        self.flags_cache.borrow_mut().remove(&obj);
+        self.tx.set_object_flags(obj, flags)
+    }
+
+    fn set_object_owner(&self, obj: Objid, owner: Objid) -> Result<(), WorldStateError> {
+        self.owner_cache.borrow_mut().remove(&obj);
+        self.tx.set_object_owner(obj, owner)
+    }
+
+    /// Forget everything cached in this transaction.
+    fn clear_caches(&self) {
+        self.property_cache.borrow_mut().clear();
+        self.flags_cache.borrow_mut().clear();
+        self.owner_cache.borrow_mut().clear();
+    }
+
+    /// Forget any cached properties of `obj` and of everything that inherits from it.
+    fn invalidate_properties(&self, obj: Objid) -> Result<(), WorldStateError> {
+        if self.property_cache.borrow().is_empty() {
+            return Ok(());
+        }
+        let descendants = self.tx.descendants(obj)?;
+        self.property_cache
+            .borrow_mut()
+            .retain(|(o, _), _| *o != obj && !descendants.contains(*o));
+        Ok(())
+    }
 }
 
 impl WorldState for DbTxWorldState {
@@ -54,12 +281,12 @@ impl WorldState for DbTxWorldState {
 
     #[tracing::instrument(skip(self))]
     fn owner_of(&self, obj: Objid) -> Result<Objid, WorldStateError> {
-        self.tx.get_object_owner(obj)
+        self.cached_owner(obj)
     }
 
     #[tracing::instrument(skip(self))]
     fn flags_of(&self, obj: Objid) -> Result<BitEnum<ObjFlag>, WorldStateError> {
-        self.tx.get_object_flags(obj)
+        self.cached_flags(obj)
     }
 
     fn set_flags_of(
@@ -72,7 +299,7 @@ impl WorldState for DbTxWorldState {
         let (flags, owner) = (self.flags_of(obj)?, self.owner_of(obj)?);
         self.perms(perms)?
             .check_object_allows(owner, flags, ObjFlag::Write.into())?;
-        self.tx.set_object_flags(obj, new_flags)
+        self.set_object_flags(obj, new_flags)
     }
 
     #[tracing::instrument(skip(self))]
@@ -94,43 +321,60 @@ impl WorldState for DbTxWorldState {
         owner: Objid,
         flags: BitEnum<ObjFlag>,
     ) -> Result<Objid, WorldStateError> {
-        if parent != NOTHING {
-            let (flags, parent_owner) = (self.flags_of(parent)?, self.owner_of(parent)?);
-            self.perms(perms)?.check_object_allows(
-                parent_owner,
-                flags,
-                BitEnum::new_with(ObjFlag::Read) | ObjFlag::Fertile,
-            )?;
-        }
-
-        let owner = (owner != NOTHING).then_some(owner);
+        self.do_create_object(perms, None, parent, owner, flags)
+    }
 
-        // TODO: ownership_quota support
-        //    If the intended owner of the new object has a property named `ownership_quota' and the value of that property is an integer, then `create()' treats that value
-        //    as a "quota".  If the quota is less than or equal to zero, then the quota is considered to be exhausted and `create()' raises `E_QUOTA' instead of creating an
-        //    object.  Otherwise, the quota is decremented and stored back into the `ownership_quota' property as a part of the creation of the new object.
-        let attrs = ObjAttrs {
-            owner,
-            name: None,
-            parent: Some(parent),
-            location: None,
-            flags: Some(flags),
-        };
-        self.tx.create_object(None, attrs)
+    #[tracing::instrument(skip(self))]
+    fn create_object_with_id(
+        &mut self,
+        perms: Objid,
+        id: Objid,
+        parent: Objid,
+        owner: Objid,
+        flags: BitEnum<ObjFlag>,
+    ) -> Result<Objid, WorldStateError> {
+        self.perms(perms)?.check_wizard()?;
+        if self.valid(id)? {
+            return Err(WorldStateError::ObjectAlreadyExists(id));
+        }
+        self.do_create_object(perms, Some(id), parent, owner, flags)
     }
 
     fn recycle_object(&mut self, perms: Objid, obj: Objid) -> Result<(), WorldStateError> {
-        let (flags, owner) = (self.flags_of(obj)?, self.owner_of(obj)?);
-        self.perms(perms)?
-            .check_object_allows(owner, flags, ObjFlag::Write.into())?;
+        // Owner or wizard only; the object being writable isn't enough.
+        let owner = self.owner_of(obj)?;
+        self.perms(perms)?.check_obj_owner_perms(owner)?;
+
+        // Give the object back to its owner's quota, if they have one.
+        if let Some((quota_prop, quota)) = self.ownership_quota(owner) {
+            self.invalidate_properties(owner)?;
+            self.tx.set_property(owner, quota_prop, v_int(quota + 1))?;
+        }
+
+        // Its children are about to inherit from its parent instead.
+        self.invalidate_properties(obj)?;
+        self.flags_cache.borrow_mut().remove(&obj);
+        self.owner_cache.borrow_mut().remove(&obj);
+        self.tx.recycle_object(obj)?;
+        self.audit("recycle", perms, obj, "");
+        Ok(())
+    }
 
-        self.tx.recycle_object(obj)
+    fn renumber_object(&mut self, perms: Objid, obj: Objid) -> Result<Objid, WorldStateError> {
+        self.perms(perms)?.check_wizard()?;
+        self.clear_caches();
+        self.tx.renumber_object(obj)
     }
 
     fn max_object(&self, _perms: Objid) -> Result<Objid, WorldStateError> {
         self.tx.get_max_object()
     }
 
+    fn reset_max_object(&mut self, perms: Objid) -> Result<(), WorldStateError> {
+        self.perms(perms)?.check_wizard()?;
+        self.tx.reset_max_object()
+    }
+
     fn move_object(
         &mut self,
         perms: Objid,
@@ -170,6 +414,45 @@ impl WorldState for DbTxWorldState {
         Ok(properties)
     }
 
+    #[tracing::instrument(skip(self))]
+    fn all_verbs(&self, perms: Objid, obj: Objid) -> Result<Vec<(VerbDef, bool)>, WorldStateError> {
+        let (flags, owner) = (self.flags_of(obj)?, self.owner_of(obj)?);
+        self.perms(perms)?
+            .check_object_allows(owner, flags, ObjFlag::Read.into())?;
+
+        let mut verbs = vec![];
+        for definer in self.tx.ancestors(obj)?.iter() {
+            verbs.extend(
+                self.tx
+                    .get_verbs(definer)?
+                    .iter()
+                    .map(|v| (v, definer == obj)),
+            );
+        }
+        Ok(verbs)
+    }
+
+    #[tracing::instrument(skip(self))]
+    fn all_properties(
+        &self,
+        perms: Objid,
+        obj: Objid,
+    ) -> Result<Vec<(PropDef, bool)>, WorldStateError> {
+        let (flags, owner) = (self.flags_of(obj)?, self.owner_of(obj)?);
+        self.perms(perms)?
+            .check_object_allows(owner, flags, ObjFlag::Read.into())?;
+
+        // Inherited definitions are copied down to every descendant, so they're all here already.
+        let properties = self.tx.get_properties(obj)?;
+        Ok(properties
+            .iter()
+            .map(|p| {
+                let local = p.definer() == obj;
+                (p, local)
+            })
+            .collect())
+    }
+
     #[tracing::instrument(skip(self))]
     fn retrieve_property(
         &self,
@@ -229,7 +512,8 @@ impl WorldState for DbTxWorldState {
             };
         }
 
-        let (ph, value) = self.tx.resolve_property(obj, pname.to_string())?;
+        // Existence before permissions, as in `find_property`.
+        let (ph, value) = self.resolve_property(obj, pname)?;
         self.perms(perms)?
             .check_property_allows(ph.owner(), ph.flags(), PropFlag::Read)?;
         Ok(value)
@@ -241,14 +525,7 @@ impl WorldState for DbTxWorldState {
         obj: Objid,
         pname: &str,
     ) -> Result<PropDef, WorldStateError> {
-        let properties = self.tx.get_properties(obj)?;
-        let ph = properties
-            .find_first_named(pname)
-            .ok_or(WorldStateError::PropertyNotFound(obj, pname.into()))?;
-        self.perms(perms)?
-            .check_property_allows(ph.owner(), ph.flags(), PropFlag::Read)?;
-
-        Ok(ph.clone())
+        self.find_property(perms, obj, pname, PropFlag::Read)
     }
 
     fn set_property_info(
@@ -258,24 +535,47 @@ impl WorldState for DbTxWorldState {
         pname: &str,
         attrs: PropAttrs,
     ) -> Result<(), WorldStateError> {
-        let properties = self.tx.get_properties(obj)?;
-        let ph = properties
-            .find_first_named(pname)
-            .ok_or(WorldStateError::PropertyNotFound(obj, pname.into()))?;
-
-        self.perms(perms)?
-            .check_property_allows(ph.owner(), ph.flags(), PropFlag::Write)?;
+        let ph = self.find_property(perms, obj, pname, PropFlag::Write)?;
+        if let Some(name) = &attrs.name {
+            self.check_property_renames(obj, &[(ph.uuid(), name.as_str())])?;
+        }
 
-        // TODO Also keep a close eye on 'clear' & perms:
-        //  "raises `E_INVARG' if <owner> is not valid" & If <object> is the definer of the property
-        //   <prop-name>, as opposed to an inheritor of the property, then `clear_property()' raises
-        //   `E_INVARG'
+        // TODO Also keep a close eye on perms:
+        //  "raises `E_INVARG' if <owner> is not valid"
 
+        self.invalidate_properties(obj)?;
         self.tx
             .update_property_definition(obj, ph.uuid(), attrs.owner, attrs.flags, attrs.name)?;
         Ok(())
     }
 
+    fn batch_set_property_info(
+        &mut self,
+        perms: Objid,
+        obj: Objid,
+        updates: Vec<(Uuid, PropAttrs)>,
+    ) -> Result<(), WorldStateError> {
+        if !self.valid(obj)? {
+            return Err(WorldStateError::ObjectNotFound(obj));
+        }
+        let properties = self.tx.get_properties(obj)?;
+        let perms = self.perms(perms)?;
+        for (uuid, _) in &updates {
+            let ph = properties
+                .find(uuid)
+                .ok_or(WorldStateError::PropertyNotFound(obj, format!("{}", uuid)))?;
+            perms.check_property_allows(ph.owner(), ph.flags(), PropFlag::Write)?;
+        }
+        let renames: Vec<_> = updates
+            .iter()
+            .filter_map(|(uuid, attrs)| Some((*uuid, attrs.name.as_deref()?)))
+            .collect();
+        self.check_property_renames(obj, &renames)?;
+
+        self.invalidate_properties(obj)?;
+        self.tx.update_property_definitions(obj, updates)
+    }
+
     #[tracing::instrument(skip(self))]
     fn update_property(
         &mut self,
@@ -307,7 +607,7 @@ impl WorldState for DbTxWorldState {
                 let Variant::Obj(owner) = value.variant() else {
                     return Err(WorldStateError::PropertyTypeMismatch);
                 };
-                self.tx.set_object_owner(obj, *owner)?;
+                self.set_object_owner(obj, *owner)?;
                 return Ok(());
             }
 
@@ -320,7 +620,7 @@ impl WorldState for DbTxWorldState {
                 } else {
                     flags.clear(ObjFlag::Read);
                 }
-                self.tx.set_object_flags(obj, flags)?;
+                self.set_object_flags(obj, flags)?;
                 return Ok(());
             }
 
@@ -333,7 +633,7 @@ impl WorldState for DbTxWorldState {
                 } else {
                     flags.clear(ObjFlag::Write);
                 }
-                self.tx.set_object_flags(obj, flags)?;
+                self.set_object_flags(obj, flags)?;
                 return Ok(());
             }
 
@@ -346,7 +646,7 @@ impl WorldState for DbTxWorldState {
                 } else {
                     flags.clear(ObjFlag::Fertile);
                 }
-                self.tx.set_object_flags(obj, flags)?;
+                self.set_object_flags(obj, flags)?;
                 return Ok(());
             }
         }
@@ -357,24 +657,24 @@ impl WorldState for DbTxWorldState {
 
             // Gott get and then set flags
             let mut flags = self.flags_of(obj)?;
-            if pname == "programmer" {
-                flags.set(ObjFlag::Programmer);
-            } else if pname == "wizard" {
-                flags.set(ObjFlag::Wizard);
+            let flag = if pname == "programmer" {
+                ObjFlag::Programmer
+            } else {
+                ObjFlag::Wizard
+            };
+            if value.is_true() {
+                flags.set(flag);
+            } else {
+                flags.clear(flag);
             }
 
-            self.tx.set_object_flags(obj, flags)?;
+            self.set_object_flags(obj, flags)?;
             return Ok(());
         }
 
-        let properties = self.tx.get_properties(obj)?;
-        let ph = properties
-            .find_first_named(pname)
-            .ok_or(WorldStateError::PropertyNotFound(obj, pname.into()))?;
-
-        self.perms(perms)?
-            .check_property_allows(ph.owner(), ph.flags(), PropFlag::Write)?;
+        let ph = self.find_property(perms, obj, pname, PropFlag::Write)?;
 
+        self.invalidate_properties(obj)?;
         self.tx.set_property(obj, ph.uuid(), value.clone())?;
         Ok(())
     }
@@ -385,12 +685,7 @@ impl WorldState for DbTxWorldState {
         obj: Objid,
         pname: &str,
     ) -> Result<bool, WorldStateError> {
-        let properties = self.tx.get_properties(obj)?;
-        let ph = properties
-            .find_first_named(pname)
-            .ok_or(WorldStateError::PropertyNotFound(obj, pname.into()))?;
-        self.perms(perms)?
-            .check_property_allows(ph.owner(), ph.flags(), PropFlag::Read)?;
+        let ph = self.find_property(perms, obj, pname, PropFlag::Read)?;
 
         // Now RetrieveProperty and if it's not there, it's clear.
         let result = self.tx.retrieve_property(obj, ph.uuid());
@@ -411,13 +706,17 @@ impl WorldState for DbTxWorldState {
     ) -> Result<(), WorldStateError> {
         // This is just deleting the local *value* portion of the property.
         // First seek the property handle.
-        let properties = self.tx.get_properties(obj)?;
-        let ph = properties
-            .find_first_named(pname)
-            .ok_or(WorldStateError::PropertyNotFound(obj, pname.into()))?;
-        self.perms(perms)?
-            .check_property_allows(ph.owner(), ph.flags(), PropFlag::Write)?;
+        let ph = self.find_property(perms, obj, pname, PropFlag::Write)?;
+
+        // Only inheritors can clear; the definer's value is what they'd fall back to.
+        if ph.definer() == obj {
+            return Err(WorldStateError::ClearPropertyOnDefiner(
+                obj,
+                pname.to_string(),
+            ));
+        }
 
+        self.invalidate_properties(obj)?;
         self.tx.clear_property(obj, ph.uuid())?;
         Ok(())
     }
@@ -440,6 +739,21 @@ impl WorldState for DbTxWorldState {
             .check_object_allows(objowner, flags, ObjFlag::Write.into())?;
         self.perms(perms)?.check_obj_owner_perms(propowner)?;
 
+        // A property of that name defined anywhere up the chain would be shadowed by this one.
+        for ancestor in self.ancestors(perms, location)?.iter() {
+            if self
+                .tx
+                .get_properties(ancestor)?
+                .find_first_named(pname)
+                .is_some_and(|p| p.definer() == ancestor)
+            {
+                return Err(WorldStateError::DuplicatePropertyDefinition(
+                    ancestor,
+                    pname.to_string(),
+                ));
+            }
+        }
+
         self.tx.define_property(
             definer,
             location,
@@ -447,7 +761,9 @@ impl WorldState for DbTxWorldState {
             propowner,
             prop_flags,
             initial_value,
+            None,
         )?;
+        self.audit("define_property", perms, location, pname);
         Ok(())
     }
 
@@ -458,13 +774,9 @@ impl WorldState for DbTxWorldState {
         obj: Objid,
         pname: &str,
     ) -> Result<(), WorldStateError> {
-        let properties = self.tx.get_properties(obj)?;
-        let ph = properties
-            .find_first_named(pname)
-            .ok_or(WorldStateError::PropertyNotFound(obj, pname.into()))?;
-        self.perms(perms)?
-            .check_property_allows(ph.owner(), ph.flags(), PropFlag::Write)?;
+        let ph = self.find_property(perms, obj, pname, PropFlag::Write)?;
 
+        self.invalidate_properties(obj)?;
         self.tx.delete_property(obj, ph.uuid())
     }
 
@@ -483,9 +795,13 @@ impl WorldState for DbTxWorldState {
         let (objflags, obj_owner) = (self.flags_of(obj)?, self.owner_of(obj)?);
         self.perms(perms)?
             .check_object_allows(obj_owner, objflags, ObjFlag::Write.into())?;
+        // Only a wizard can make a verb owned by someone else.
+        self.perms(perms)?.check_obj_owner_perms(owner)?;
 
+        let detail = names.join(" ");
         self.tx
-            .add_object_verb(obj, owner, names, binary, binary_type, flags, args)?;
+            .add_object_verb(obj, owner, names, binary, binary_type, flags, args, None)?;
+        self.audit("add_verb", perms, obj, &detail);
         Ok(())
     }
 
@@ -548,6 +864,49 @@ impl WorldState for DbTxWorldState {
         Ok(())
     }
 
+    #[tracing::instrument(skip(self))]
+    fn rename_verb(
+        &mut self,
+        perms: Objid,
+        obj: Objid,
+        old_names: &[String],
+        new_names: Vec<String>,
+    ) -> Result<(), WorldStateError> {
+        let verbs = self.tx.get_verbs(obj)?;
+        let vh = verbs
+            .iter()
+            .find(|v| v.names() == old_names)
+            .ok_or_else(|| WorldStateError::VerbNotFound(obj, old_names.join(" ")))?;
+        self.perms(perms)?
+            .check_verb_allows(vh.owner(), vh.flags(), VerbFlag::Write)?;
+
+        for other in verbs.iter().filter(|v| v.uuid() != vh.uuid()) {
+            if let Some(name) = new_names
+                .iter()
+                .find(|n| other.names().iter().any(|o| o.eq_ignore_ascii_case(n)))
+            {
+                return Err(WorldStateError::DuplicateVerb(obj, name.clone()));
+            }
+        }
+
+        let detail = new_names.join(" ");
+        self.tx.update_verb(
+            obj,
+            vh.uuid(),
+            VerbAttrs {
+                definer: None,
+                owner: None,
+                names: Some(new_names),
+                flags: None,
+                args_spec: None,
+                binary_type: None,
+                binary: None,
+            },
+        )?;
+        self.audit("rename_verb", perms, obj, &detail);
+        Ok(())
+    }
+
     #[tracing::instrument(skip(self))]
     fn get_verb(&self, perms: Objid, obj: Objid, vname: &str) -> Result<VerbDef, WorldStateError> {
         if !self.tx.object_valid(obj)? {
@@ -614,11 +973,13 @@ impl WorldState for DbTxWorldState {
         prep: PrepSpec,
         iobj: Objid,
     ) -> Result<Option<VerbInfo>, WorldStateError> {
-        if !self.valid(obj)? {
-            return Ok(None);
-        }
-
-        let (objflags, owner) = (self.flags_of(obj)?, self.owner_of(obj)?);
+        // Every valid object has an owner, so looking it up doubles as the validity check.
+        let owner = match self.owner_of(obj) {
+            Ok(owner) => owner,
+            Err(WorldStateError::ObjectNotFound(_)) => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let objflags = self.flags_of(obj)?;
         self.perms(perms)?
             .check_object_allows(owner, objflags, ObjFlag::Read.into())?;
 
@@ -667,8 +1028,13 @@ impl WorldState for DbTxWorldState {
         obj: Objid,
         new_parent: Objid,
     ) -> Result<(), WorldStateError> {
-        if obj == new_parent {
-            return Err(WorldStateError::RecursiveMove(obj, new_parent));
+        // The new parent can't be the object itself or anything descended from it.
+        let mut ancestor = new_parent;
+        while ancestor != NOTHING {
+            if ancestor == obj {
+                return Err(WorldStateError::RecursiveMove(obj, new_parent));
+            }
+            ancestor = self.tx.get_object_parent(ancestor)?;
         }
 
         let (objflags, owner) = (self.flags_of(obj)?, self.owner_of(obj)?);
@@ -685,7 +1051,82 @@ impl WorldState for DbTxWorldState {
         self.perms(perms)?
             .check_object_allows(owner, objflags, ObjFlag::Write.into())?;
 
-        self.tx.set_object_parent(obj, new_parent)
+        // Nothing defined on the object or its descendants may share a name with a property it
+        // would inherit from its new ancestors.
+        if new_parent != NOTHING {
+            let inherited = self.tx.get_properties(new_parent)?;
+            let mut definers = vec![obj];
+            definers.extend(self.tx.descendants(obj)?.iter());
+            for definer in definers {
+                for p in self.tx.get_properties(definer)?.iter() {
+                    if p.definer() == definer && inherited.find_first_named(p.name()).is_some() {
+                        return Err(WorldStateError::DuplicatePropertyDefinition(
+                            definer,
+                            p.name().to_string(),
+                        ));
+                    }
+                }
+            }
+        }
+
+        let new_parents = if new_parent == NOTHING {
+            ObjSet::empty()
+        } else {
+            ObjSet::from(&[new_parent])
+        };
+        self.invalidate_properties(obj)?;
+        self.tx.set_object_parents(obj, new_parents)?;
+        self.audit("change_parent", perms, obj, &new_parent.to_string());
+        Ok(())
+    }
+
+    #[tracing::instrument(skip(self))]
+    fn parents_of(&self, _perms: Objid, obj: Objid) -> Result<ObjSet, WorldStateError> {
+        self.tx.get_object_parents(obj)
+    }
+
+    fn change_parents(
+        &mut self,
+        perms: Objid,
+        obj: Objid,
+        new_parents: &ObjSet,
+    ) -> Result<(), WorldStateError> {
+        let (objflags, owner) = (self.flags_of(obj)?, self.owner_of(obj)?);
+        for new_parent in new_parents.iter() {
+            let (parentflags, parentowner) =
+                (self.flags_of(new_parent)?, self.owner_of(new_parent)?);
+            self.perms(perms)?.check_object_allows(
+                parentowner,
+                parentflags,
+                BitEnum::new_with(ObjFlag::Write) | ObjFlag::Fertile,
+            )?;
+        }
+        self.perms(perms)?
+            .check_object_allows(owner, objflags, ObjFlag::Write.into())?;
+
+        // Cycles and property name conflicts are caught while the inheritance is rebuilt.
+        self.invalidate_properties(obj)?;
+        self.tx.set_object_parents(obj, new_parents.clone())?;
+        let detail = new_parents
+            .iter()
+            .map(|p| p.to_string())
+            .collect::<Vec<_>>()
+            .join(", ");
+        self.audit("change_parents", perms, obj, &detail);
+        Ok(())
+    }
+
+    #[tracing::instrument(skip(self))]
+    fn ancestors(&self, _perms: Objid, obj: Objid) -> Result<ObjSet, WorldStateError> {
+        // The transaction's walk starts with the object itself.
+        Ok(ObjSet::from_oid_iter(
+            self.tx.ancestors(obj)?.iter().skip(1),
+        ))
+    }
+
+    #[tracing::instrument(skip(self))]
+    fn is_a(&self, obj: Objid, ancestor: Objid) -> Result<bool, WorldStateError> {
+        Ok(self.tx.ancestors(obj)?.contains(ancestor))
     }
 
     #[tracing::instrument(skip(self))]
@@ -697,6 +1138,15 @@ impl WorldState for DbTxWorldState {
         self.tx.get_object_children(obj)
     }
 
+    #[tracing::instrument(skip(self))]
+    fn descendants(&self, perms: Objid, obj: Objid) -> Result<ObjSet, WorldStateError> {
+        let (objflags, owner) = (self.flags_of(obj)?, self.owner_of(obj)?);
+        self.perms(perms)?
+            .check_object_allows(owner, objflags, ObjFlag::Read.into())?;
+
+        self.tx.descendants(obj)
+    }
+
     #[tracing::instrument(skip(self))]
     fn valid(&self, obj: Objid) -> Result<bool, WorldStateError> {
         self.tx.object_valid(obj)
@@ -728,13 +1178,627 @@ impl WorldState for DbTxWorldState {
         self.tx.db_usage()
     }
 
+    fn verify_database(&self) -> Result<Vec<String>, WorldStateError> {
+        self.tx.verify_database()
+    }
+
+    fn repair_database(&mut self, dry_run: bool) -> Result<Vec<String>, WorldStateError> {
+        self.clear_caches();
+        self.tx.repair_database(dry_run)
+    }
+
     #[tracing::instrument(skip(self))]
     fn commit(&mut self) -> Result<CommitResult, WorldStateError> {
+        self.clear_caches();
         self.tx.commit()
     }
 
     #[tracing::instrument(skip(self))]
     fn rollback(&mut self) -> Result<(), WorldStateError> {
+        self.clear_caches();
         self.tx.rollback()
     }
+
+    fn savepoint(&mut self, perms: Objid) -> Result<usize, WorldStateError> {
+        self.perms(perms)?.check_wizard()?;
+        self.tx.savepoint()
+    }
+
+    fn rollback_to(&mut self, perms: Objid, savepoint: usize) -> Result<(), WorldStateError> {
+        self.perms(perms)?.check_wizard()?;
+        self.clear_caches();
+        self.tx.rollback_to(savepoint)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use strum::{EnumCount, IntoEnumIterator};
+    use tracing_test::traced_test;
+
+    use moor_rdb::{relation_info_for, RelBox, RelationInfo};
+    use moor_values::model::{BinaryType, HasUuid, Named, PropAttrs, VerbArgsSpec, VerbFlag};
+    use moor_values::model::{ObjFlag, ObjSet, WorldState, WorldStateError};
+    use moor_values::util::BitEnum;
+    use moor_values::var::{v_int, v_objid};
+    use moor_values::{NOTHING, SYSTEM_OBJECT};
+
+    use crate::db_worldstate::DbTxWorldState;
+    use crate::odb::{RelBoxTransaction, WorldStateRelation, WorldStateSequences};
+
+    fn test_world_state() -> DbTxWorldState {
+        let relations: Vec<RelationInfo> =
+            WorldStateRelation::iter().map(relation_info_for).collect();
+        let db: Arc<RelBox> = RelBox::new(1 << 24, None, &relations, WorldStateSequences::COUNT);
+        DbTxWorldState::new(Box::new(RelBoxTransaction::new(db)))
+    }
+
+    // Repeated permission checks on an object are answered from the transaction's memo of its flags
+    // and owner, rather than going back to the db; until they're changed through the world state.
+    #[test]
+    fn test_flags_and_owner_memo() {
+        let mut ws = test_world_state();
+        let wizard = ws
+            .create_object(SYSTEM_OBJECT, NOTHING, SYSTEM_OBJECT, BitEnum::all())
+            .unwrap();
+        let obj = ws
+            .create_object(wizard, NOTHING, wizard, BitEnum::new())
+            .unwrap();
+        assert_eq!(
+            ws.verbs(obj, obj).unwrap_err(),
+            WorldStateError::ObjectPermissionDenied
+        );
+
+        // Changed behind the world state's back, so the memo doesn't know.
+        ws.tx
+            .set_object_flags(obj, BitEnum::new_with(ObjFlag::Read))
+            .unwrap();
+        ws.tx.set_object_owner(obj, obj).unwrap();
+        for _ in 0..3 {
+            assert_eq!(ws.flags_of(obj).unwrap(), BitEnum::new());
+            assert_eq!(ws.owner_of(obj).unwrap(), wizard);
+            assert_eq!(
+                ws.verbs(obj, obj).unwrap_err(),
+                WorldStateError::ObjectPermissionDenied
+            );
+        }
+
+        // Changed through it, so it does.
+        ws.set_flags_of(wizard, obj, BitEnum::new_with(ObjFlag::Read))
+            .unwrap();
+        assert_eq!(ws.flags_of(obj).unwrap(), BitEnum::new_with(ObjFlag::Read));
+        ws.update_property(wizard, obj, "owner", &v_objid(obj))
+            .unwrap();
+        assert_eq!(ws.owner_of(obj).unwrap(), obj);
+        assert!(ws.verbs(obj, obj).is_ok());
+    }
+
+    // With auditing on, adding a verb logs who added it to what. With it off, nothing does.
+    #[test]
+    #[traced_test]
+    fn test_audit_add_verb() {
+        let mut ws = test_world_state();
+        let wizard = ws
+            .create_object(SYSTEM_OBJECT, NOTHING, SYSTEM_OBJECT, BitEnum::all())
+            .unwrap();
+        let add_verb = |ws: &mut DbTxWorldState, obj, name: &str| {
+            ws.add_verb(
+                wizard,
+                obj,
+                vec![name.to_string()],
+                wizard,
+                BitEnum::new(),
+                VerbArgsSpec::this_none_this(),
+                vec![],
+                BinaryType::LambdaMoo18X,
+            )
+            .unwrap();
+        };
+
+        let quiet = ws
+            .create_object(wizard, NOTHING, wizard, BitEnum::new())
+            .unwrap();
+        add_verb(&mut ws, quiet, "unheard");
+        assert!(!logs_contain("add_verb"));
+
+        let mut ws = ws.with_audit_log(true);
+        let target = ws
+            .create_object(wizard, NOTHING, wizard, BitEnum::new())
+            .unwrap();
+        add_verb(&mut ws, target, "look");
+        assert!(logs_contain("event=\"add_verb\""));
+        assert!(logs_contain(&format!("actor={wizard}")));
+        assert!(logs_contain(&format!("object={target}")));
+        assert!(logs_contain("detail=\"look\""));
+        assert!(logs_contain("timestamp="));
+    }
+
+    // A programmer's verbs are their own; only a wizard can add a verb owned by someone else.
+    #[test]
+    fn test_add_verb_owner() {
+        let mut ws = test_world_state();
+        let wizard = ws
+            .create_object(SYSTEM_OBJECT, NOTHING, SYSTEM_OBJECT, BitEnum::all())
+            .unwrap();
+        let programmer = ws
+            .create_object(
+                wizard,
+                NOTHING,
+                wizard,
+                BitEnum::new_with(ObjFlag::Programmer),
+            )
+            .unwrap();
+        ws.update_property(wizard, programmer, "owner", &v_objid(programmer))
+            .unwrap();
+        let thing = ws
+            .create_object(programmer, NOTHING, programmer, BitEnum::new())
+            .unwrap();
+        let add_verb = |ws: &mut DbTxWorldState, name: &str, owner| {
+            ws.add_verb(
+                programmer,
+                thing,
+                vec![name.to_string()],
+                owner,
+                BitEnum::new(),
+                VerbArgsSpec::this_none_this(),
+                vec![],
+                BinaryType::LambdaMoo18X,
+            )
+        };
+
+        add_verb(&mut ws, "mine", programmer).unwrap();
+        let verbs = ws.verbs(programmer, thing).unwrap();
+        assert_eq!(verbs.find_first_named("mine").unwrap().owner(), programmer);
+
+        assert_eq!(
+            add_verb(&mut ws, "theirs", wizard),
+            Err(WorldStateError::ObjectPermissionDenied)
+        );
+    }
+
+    // A child lists its own definitions and its parent's, and knows which are which.
+    #[test]
+    fn test_all_verbs_and_properties() {
+        let mut ws = test_world_state();
+        let wizard = ws
+            .create_object(SYSTEM_OBJECT, NOTHING, SYSTEM_OBJECT, BitEnum::all())
+            .unwrap();
+        let parent = ws
+            .create_object(wizard, NOTHING, wizard, BitEnum::new_with(ObjFlag::Fertile))
+            .unwrap();
+        let child = ws
+            .create_object(wizard, parent, wizard, BitEnum::new())
+            .unwrap();
+        for (obj, name) in [(parent, "inherited"), (child, "own")] {
+            ws.define_property(wizard, obj, obj, name, wizard, BitEnum::new(), None)
+                .unwrap();
+            ws.add_verb(
+                wizard,
+                obj,
+                vec![name.to_string()],
+                wizard,
+                BitEnum::new(),
+                VerbArgsSpec::this_none_this(),
+                vec![],
+                BinaryType::LambdaMoo18X,
+            )
+            .unwrap();
+        }
+
+        let mut props: Vec<_> = ws
+            .all_properties(wizard, child)
+            .unwrap()
+            .into_iter()
+            .map(|(p, local)| (p.name().to_string(), p.definer(), local))
+            .collect();
+        props.sort();
+        assert_eq!(
+            props,
+            vec![
+                ("inherited".to_string(), parent, false),
+                ("own".to_string(), child, true),
+            ]
+        );
+
+        let verbs: Vec<_> = ws
+            .all_verbs(wizard, child)
+            .unwrap()
+            .into_iter()
+            .map(|(v, local)| (v.names()[0].to_string(), v.location(), local))
+            .collect();
+        assert_eq!(
+            verbs,
+            vec![
+                ("own".to_string(), child, true),
+                ("inherited".to_string(), parent, false),
+            ]
+        );
+
+        // The parent has nothing inherited.
+        assert!(ws
+            .all_properties(wizard, parent)
+            .unwrap()
+            .iter()
+            .all(|(_, local)| *local));
+    }
+
+    // Renaming a verb changes its names and nothing else, and won't take a name another verb on
+    // the object already has.
+    #[test]
+    fn test_rename_verb() {
+        let mut ws = test_world_state();
+        let wizard = ws
+            .create_object(SYSTEM_OBJECT, NOTHING, SYSTEM_OBJECT, BitEnum::all())
+            .unwrap();
+        let thing = ws
+            .create_object(wizard, NOTHING, wizard, BitEnum::new())
+            .unwrap();
+        let flags = BitEnum::new_with(VerbFlag::Read) | VerbFlag::Exec;
+        for names in [vec!["look", "l"], vec!["get"]] {
+            ws.add_verb(
+                wizard,
+                thing,
+                names.into_iter().map(String::from).collect(),
+                wizard,
+                flags,
+                VerbArgsSpec::this_none_this(),
+                vec![],
+                BinaryType::LambdaMoo18X,
+            )
+            .unwrap();
+        }
+
+        let old_names = vec!["look".to_string(), "l".to_string()];
+        ws.rename_verb(
+            wizard,
+            thing,
+            &old_names,
+            vec!["examine".to_string(), "ex".to_string()],
+        )
+        .unwrap();
+        let renamed = ws.get_verb(wizard, thing, "examine").unwrap();
+        assert_eq!(renamed.names(), vec!["examine", "ex"]);
+        assert_eq!(renamed.flags(), flags);
+        assert_eq!(renamed.owner(), wizard);
+        assert_eq!(renamed.args(), VerbArgsSpec::this_none_this());
+        assert!(ws.get_verb(wizard, thing, "look").is_err());
+
+        assert_eq!(
+            ws.rename_verb(
+                wizard,
+                thing,
+                &["examine".to_string(), "ex".to_string()],
+                vec!["GET".to_string()],
+            ),
+            Err(WorldStateError::DuplicateVerb(thing, "GET".to_string()))
+        );
+        assert_eq!(
+            ws.rename_verb(wizard, thing, &old_names, vec!["peer".to_string()]),
+            Err(WorldStateError::VerbNotFound(thing, "look l".to_string()))
+        );
+    }
+
+    // Rolling back to a savepoint undoes what came after it, including anything memoized since,
+    // and discards the savepoints taken after it. Only wizards can use savepoints.
+    #[test]
+    fn test_savepoint_rollback() {
+        let mut ws = test_world_state();
+        let wizard = ws
+            .create_object(SYSTEM_OBJECT, NOTHING, SYSTEM_OBJECT, BitEnum::all())
+            .unwrap();
+        let obj = ws
+            .create_object(wizard, NOTHING, wizard, BitEnum::new())
+            .unwrap();
+        ws.define_property(
+            wizard,
+            obj,
+            obj,
+            "size",
+            wizard,
+            BitEnum::new(),
+            Some(v_int(1)),
+        )
+        .unwrap();
+
+        let outer = ws.savepoint(wizard).unwrap();
+        ws.update_property(wizard, obj, "size", &v_int(2)).unwrap();
+        assert_eq!(ws.retrieve_property(wizard, obj, "size").unwrap(), v_int(2));
+        let inner = ws.savepoint(wizard).unwrap();
+        ws.update_property(wizard, obj, "size", &v_int(3)).unwrap();
+
+        ws.rollback_to(wizard, outer).unwrap();
+        assert_eq!(ws.retrieve_property(wizard, obj, "size").unwrap(), v_int(1));
+        assert_eq!(
+            ws.rollback_to(wizard, inner),
+            Err(WorldStateError::SavepointNotFound(inner))
+        );
+
+        // The outer savepoint is still there to go back to.
+        ws.update_property(wizard, obj, "size", &v_int(4)).unwrap();
+        ws.rollback_to(wizard, outer).unwrap();
+        assert_eq!(ws.retrieve_property(wizard, obj, "size").unwrap(), v_int(1));
+
+        assert_eq!(
+            ws.savepoint(obj),
+            Err(WorldStateError::ObjectPermissionDenied)
+        );
+        assert_eq!(
+            ws.rollback_to(obj, outer),
+            Err(WorldStateError::ObjectPermissionDenied)
+        );
+    }
+
+    // Renaming a property, alone or in a batch, won't take a name that another property on the
+    // object or its descendants has, or that another rename in the batch is taking.
+    #[test]
+    fn test_rename_property_collisions() {
+        let mut ws = test_world_state();
+        let wizard = ws
+            .create_object(SYSTEM_OBJECT, NOTHING, SYSTEM_OBJECT, BitEnum::all())
+            .unwrap();
+        let thing = ws
+            .create_object(wizard, NOTHING, wizard, BitEnum::new_with(ObjFlag::Fertile))
+            .unwrap();
+        let child = ws
+            .create_object(wizard, thing, wizard, BitEnum::new())
+            .unwrap();
+        for (obj, name) in [(thing, "colour"), (thing, "size"), (child, "weight")] {
+            ws.define_property(wizard, obj, obj, name, wizard, BitEnum::new(), None)
+                .unwrap();
+        }
+        let uuid_of =
+            |ws: &DbTxWorldState, name| ws.get_property_info(wizard, thing, name).unwrap().uuid();
+        let (colour, size) = (uuid_of(&ws, "colour"), uuid_of(&ws, "size"));
+        let rename = |name: &str| PropAttrs {
+            name: Some(name.to_string()),
+            ..PropAttrs::new()
+        };
+
+        assert_eq!(
+            ws.set_property_info(wizard, thing, "colour", rename("SIZE")),
+            Err(WorldStateError::DuplicatePropertyDefinition(
+                thing,
+                "SIZE".to_string()
+            ))
+        );
+        assert_eq!(
+            ws.batch_set_property_info(wizard, thing, vec![(colour, rename("weight"))]),
+            Err(WorldStateError::DuplicatePropertyDefinition(
+                child,
+                "weight".to_string()
+            ))
+        );
+        assert_eq!(
+            ws.batch_set_property_info(
+                wizard,
+                thing,
+                vec![(colour, rename("hue")), (size, rename("hue"))]
+            ),
+            Err(WorldStateError::DuplicatePropertyDefinition(
+                thing,
+                "hue".to_string()
+            ))
+        );
+        assert!(ws.get_property_info(wizard, thing, "colour").is_ok());
+
+        // Names can be swapped in one batch.
+        ws.batch_set_property_info(
+            wizard,
+            thing,
+            vec![(colour, rename("size")), (size, rename("colour"))],
+        )
+        .unwrap();
+        assert_eq!(uuid_of(&ws, "size"), colour);
+        assert_eq!(uuid_of(&ws, "colour"), size);
+    }
+
+    // A grandparent reached through both sides of a diamond counts once, and the walk ends.
+    #[test]
+    fn test_is_a_diamond() {
+        let mut ws = test_world_state();
+        let wizard = ws
+            .create_object(SYSTEM_OBJECT, NOTHING, SYSTEM_OBJECT, BitEnum::all())
+            .unwrap();
+        let create = |ws: &mut DbTxWorldState, parent| {
+            ws.create_object(wizard, parent, wizard, BitEnum::new_with(ObjFlag::Fertile))
+                .unwrap()
+        };
+        let root = create(&mut ws, NOTHING);
+        let left = create(&mut ws, root);
+        let right = create(&mut ws, root);
+        let thing = create(&mut ws, left);
+        let unrelated = create(&mut ws, NOTHING);
+        ws.change_parents(wizard, thing, &ObjSet::from(&[left, right]))
+            .unwrap();
+
+        for ancestor in [thing, left, right, root] {
+            assert!(ws.is_a(thing, ancestor).unwrap());
+        }
+        assert!(!ws.is_a(thing, unrelated).unwrap());
+        assert!(!ws.is_a(root, thing).unwrap());
+        assert!(!ws.is_a(left, right).unwrap());
+    }
+
+    // Descendants go all the way down, where children stop at the first level; something
+    // inheriting from two objects in the subtree is there once.
+    #[test]
+    fn test_descendants() {
+        let mut ws = test_world_state();
+        let wizard = ws
+            .create_object(SYSTEM_OBJECT, NOTHING, SYSTEM_OBJECT, BitEnum::all())
+            .unwrap();
+        let create = |ws: &mut DbTxWorldState, parent| {
+            ws.create_object(wizard, parent, wizard, BitEnum::new_with(ObjFlag::Fertile))
+                .unwrap()
+        };
+        let root = create(&mut ws, NOTHING);
+        let left = create(&mut ws, root);
+        let right = create(&mut ws, root);
+        let left_child = create(&mut ws, left);
+        let grandchild = create(&mut ws, left_child);
+        ws.change_parents(wizard, grandchild, &ObjSet::from(&[left_child, right]))
+            .unwrap();
+
+        assert!(ws
+            .children_of(wizard, root)
+            .unwrap()
+            .is_same(ObjSet::from(&[left, right])));
+        let descendants = ws.descendants(wizard, root).unwrap();
+        assert_eq!(descendants.len(), 4);
+        assert!(descendants.is_same(ObjSet::from(&[left, right, left_child, grandchild])));
+        assert!(ws
+            .descendants(wizard, left)
+            .unwrap()
+            .is_same(ObjSet::from(&[left_child, grandchild])));
+        assert_eq!(ws.descendants(wizard, grandchild).unwrap(), ObjSet::empty());
+    }
+
+    #[test]
+    fn test_ancestors() {
+        let mut ws = test_world_state();
+        let wizard = ws
+            .create_object(SYSTEM_OBJECT, NOTHING, SYSTEM_OBJECT, BitEnum::all())
+            .unwrap();
+        let create = |ws: &mut DbTxWorldState, parent| {
+            ws.create_object(wizard, parent, wizard, BitEnum::new_with(ObjFlag::Fertile))
+                .unwrap()
+        };
+        let root = create(&mut ws, NOTHING);
+        let middle = create(&mut ws, root);
+        let leaf = create(&mut ws, middle);
+
+        assert_eq!(
+            ws.ancestors(wizard, leaf).unwrap(),
+            ObjSet::from(&[middle, root])
+        );
+        assert_eq!(ws.ancestors(wizard, root).unwrap(), ObjSet::empty());
+    }
+
+    // A property already defined further up the chain can't be defined again lower down.
+    #[test]
+    fn test_define_property_on_ancestor_rejected() {
+        let mut ws = test_world_state();
+        let wizard = ws
+            .create_object(SYSTEM_OBJECT, NOTHING, SYSTEM_OBJECT, BitEnum::all())
+            .unwrap();
+        let create = |ws: &mut DbTxWorldState, parent| {
+            ws.create_object(wizard, parent, wizard, BitEnum::new_with(ObjFlag::Fertile))
+                .unwrap()
+        };
+        let root = create(&mut ws, NOTHING);
+        let middle = create(&mut ws, root);
+        let leaf = create(&mut ws, middle);
+        ws.define_property(wizard, root, root, "test", wizard, BitEnum::new(), None)
+            .unwrap();
+
+        let result = ws.define_property(wizard, leaf, leaf, "test", wizard, BitEnum::new(), None);
+        assert_eq!(
+            result,
+            Err(WorldStateError::DuplicatePropertyDefinition(
+                root,
+                "test".to_string()
+            ))
+        );
+        ws.define_property(wizard, leaf, leaf, "other", wizard, BitEnum::new(), None)
+            .unwrap();
+    }
+
+    #[test]
+    fn test_define_property_on_descendant_rejected() {
+        let mut ws = test_world_state();
+        let wizard = ws
+            .create_object(SYSTEM_OBJECT, NOTHING, SYSTEM_OBJECT, BitEnum::all())
+            .unwrap();
+        let create = |ws: &mut DbTxWorldState, parent| {
+            ws.create_object(wizard, parent, wizard, BitEnum::new_with(ObjFlag::Fertile))
+                .unwrap()
+        };
+        let parent = create(&mut ws, NOTHING);
+        let child = create(&mut ws, parent);
+        let grandchild = create(&mut ws, child);
+        ws.define_property(wizard, parent, parent, "x", wizard, BitEnum::new(), None)
+            .unwrap();
+        assert_eq!(
+            ws.define_property(wizard, child, child, "x", wizard, BitEnum::new(), None),
+            Err(WorldStateError::DuplicatePropertyDefinition(
+                parent,
+                "x".to_string()
+            ))
+        );
+
+        // The other way around: the child already has `y`, so the parent can't take it on. None
+        // of the objects in between should be left with a definition.
+        ws.define_property(
+            wizard,
+            grandchild,
+            grandchild,
+            "y",
+            wizard,
+            BitEnum::new(),
+            None,
+        )
+        .unwrap();
+        assert_eq!(
+            ws.define_property(wizard, parent, parent, "y", wizard, BitEnum::new(), None),
+            Err(WorldStateError::DuplicatePropertyDefinition(
+                grandchild,
+                "y".to_string()
+            ))
+        );
+        for obj in [parent, child] {
+            assert!(ws
+                .properties(wizard, obj)
+                .unwrap()
+                .find_first_named("y")
+                .is_none());
+        }
+
+        // Once removed, the name is free to be defined again, up or down the chain.
+        ws.delete_property(wizard, parent, "x").unwrap();
+        ws.define_property(wizard, child, child, "x", wizard, BitEnum::new(), None)
+            .unwrap();
+        ws.delete_property(wizard, grandchild, "y").unwrap();
+        ws.define_property(wizard, parent, parent, "y", wizard, BitEnum::new(), None)
+            .unwrap();
+    }
+
+    #[test]
+    fn test_clear_property_on_definer_rejected() {
+        let mut ws = test_world_state();
+        let wizard = ws
+            .create_object(SYSTEM_OBJECT, NOTHING, SYSTEM_OBJECT, BitEnum::all())
+            .unwrap();
+        let parent = ws
+            .create_object(wizard, NOTHING, wizard, BitEnum::new_with(ObjFlag::Fertile))
+            .unwrap();
+        let child = ws
+            .create_object(wizard, parent, wizard, BitEnum::new())
+            .unwrap();
+        ws.define_property(
+            wizard,
+            parent,
+            parent,
+            "x",
+            wizard,
+            BitEnum::new(),
+            Some(v_int(1)),
+        )
+        .unwrap();
+        ws.update_property(wizard, child, "x", &v_int(2)).unwrap();
+
+        assert_eq!(
+            ws.clear_property(wizard, parent, "x"),
+            Err(WorldStateError::ClearPropertyOnDefiner(
+                parent,
+                "x".to_string()
+            ))
+        );
+        assert_eq!(ws.retrieve_property(wizard, parent, "x").unwrap(), v_int(1));
+
+        ws.clear_property(wizard, child, "x").unwrap();
+        assert!(ws.is_property_clear(wizard, child, "x").unwrap());
+        assert_eq!(ws.retrieve_property(wizard, child, "x").unwrap(), v_int(1));
+    }
 }
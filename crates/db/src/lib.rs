@@ -21,9 +21,13 @@ use moor_values::model::WorldStateSource;
 use crate::loader::LoaderInterface;
 use crate::odb::RelBoxWorldState;
 
+pub use crate::db_worldstate::AUDIT_TARGET;
+
 mod db_loader_client;
 pub mod db_tx;
 mod db_worldstate;
+#[cfg(test)]
+mod isolation_check;
 pub mod loader;
 
 pub mod odb;
@@ -31,6 +35,7 @@ pub mod odb;
 pub struct DatabaseBuilder {
     path: Option<std::path::PathBuf>,
     memory_size: Option<usize>,
+    audit_log: bool,
 }
 
 pub trait Database {
@@ -43,6 +48,7 @@ impl DatabaseBuilder {
         Self {
             path: None,
             memory_size: None,
+            audit_log: false,
         }
     }
 
@@ -56,12 +62,19 @@ impl DatabaseBuilder {
         self
     }
 
+    /// Log an audit record (under the `AUDIT_TARGET` tracing target) for every property and verb
+    /// definition, reparenting, and recycle, with who did it to what and when.
+    pub fn with_audit_log(mut self, audit_log: bool) -> Self {
+        self.audit_log = audit_log;
+        self
+    }
+
     /// Returns a new database instance. The second value in the result tuple is true if the
     /// database was newly created, and false if it was already present.
     pub fn open_db(&self) -> Result<(Arc<dyn Database + Send + Sync>, bool), String> {
         let (db, fresh) =
             RelBoxWorldState::open(self.path.clone(), self.memory_size.unwrap_or(1 << 40));
-        Ok((Arc::new(db), fresh))
+        Ok((Arc::new(db.with_audit_log(self.audit_log)), fresh))
     }
 }
 
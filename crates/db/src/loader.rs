@@ -14,6 +14,7 @@
 
 use uuid::Uuid;
 
+use moor_values::model::BinaryType;
 use moor_values::model::ObjAttrs;
 use moor_values::model::ObjSet;
 use moor_values::model::PropFlag;
@@ -36,11 +37,19 @@ pub trait LoaderInterface {
         objid: Option<Objid>,
         attrs: &ObjAttrs,
     ) -> Result<Objid, WorldStateError>;
+    /// Whether the object already exists, e.g. when applying a delta dump on top of a world.
+    fn object_valid(&self, obj: Objid) -> Result<bool, WorldStateError>;
+    /// Set the name and flags of an existing object to those in `attrs`, where given.
+    fn update_object(&self, obj: Objid, attrs: &ObjAttrs) -> Result<(), WorldStateError>;
     fn set_object_parent(&self, obj: Objid, parent: Objid) -> Result<(), WorldStateError>;
+    /// Replace the object's whole parent list, the first being its primary parent.
+    fn set_object_parents(&self, obj: Objid, parents: ObjSet) -> Result<(), WorldStateError>;
 
     fn set_object_location(&self, o: Objid, location: Objid) -> Result<(), WorldStateError>;
     fn set_object_owner(&self, obj: Objid, owner: Objid) -> Result<(), WorldStateError>;
 
+    /// Add a verb, keeping `uuid` as its identity if the textdump had one.
+    #[allow(clippy::too_many_arguments)]
     fn add_verb(
         &self,
         obj: Objid,
@@ -49,8 +58,12 @@ pub trait LoaderInterface {
         flags: BitEnum<VerbFlag>,
         args: VerbArgsSpec,
         binary: Vec<u8>,
+        binary_type: BinaryType,
+        uuid: Option<Uuid>,
     ) -> Result<(), WorldStateError>;
 
+    /// Define a property, keeping `uuid` as its identity if the textdump had one.
+    #[allow(clippy::too_many_arguments)]
     fn define_property(
         &self,
         definer: Objid,
@@ -59,6 +72,7 @@ pub trait LoaderInterface {
         owner: Objid,
         flags: BitEnum<PropFlag>,
         value: Option<Var>,
+        uuid: Option<Uuid>,
     ) -> Result<(), WorldStateError>;
 
     fn set_property(
@@ -70,6 +84,12 @@ pub trait LoaderInterface {
         value: Option<Var>,
     ) -> Result<(), WorldStateError>;
 
+    /// Remove a verb, so that an object's verbs can be replaced wholesale.
+    fn remove_verb(&self, obj: Objid, uuid: Uuid) -> Result<(), WorldStateError>;
+
+    /// Remove a property definition from the object that defines it, and its descendants.
+    fn remove_property(&self, obj: Objid, uuid: Uuid) -> Result<(), WorldStateError>;
+
     fn commit(&self) -> Result<CommitResult, WorldStateError>;
 
     // For writing textdumps...
@@ -77,12 +97,21 @@ pub trait LoaderInterface {
     /// Get the list of all active objects in the database
     fn get_objects(&self) -> Result<ObjSet, WorldStateError>;
 
+    /// The timestamp of the transaction, for later use with `objects_modified_since`.
+    fn timestamp(&self) -> u64;
+
+    /// The objects changed by transactions committed after timestamp `ts`.
+    fn objects_modified_since(&self, ts: u64) -> Result<ObjSet, WorldStateError>;
+
     /// Get the list of all players.
     fn get_players(&self) -> Result<ObjSet, WorldStateError>;
 
     /// Get the attributes of a given object
     fn get_object(&self, objid: Objid) -> Result<ObjAttrs, WorldStateError>;
 
+    /// Get the ordered parents of a given object: its primary parent, then any additional ones.
+    fn get_object_parents(&self, objid: Objid) -> Result<ObjSet, WorldStateError>;
+
     /// Get the verbs living on a given object
     fn get_object_verbs(&self, objid: Objid) -> Result<VerbDefs, WorldStateError>;
 
@@ -69,6 +69,9 @@ pub enum WorldStateRelation {
     /// Property UUID->PropertyValue (Var)
     #[strum(props(DomainType = "Bytes", CodomainType = "Bytes", IndexType = "Hash"))]
     ObjectPropertyValue = 8,
+    /// Object->Additional parents beyond the one in ObjectParent (ObjSet), in resolution order
+    #[strum(props(DomainType = "Integer", CodomainType = "Bytes", IndexType = "Hash"))]
+    ObjectExtraParents = 9,
 }
 
 impl From<WorldStateRelation> for RelationId {
@@ -78,7 +81,7 @@ impl From<WorldStateRelation> for RelationId {
 }
 
 pub fn composite_key_for(o: Objid, u: &Uuid) -> SliceRef {
-    let mut key = o.0.to_le_bytes().to_vec();
+    let mut key = oid_bytes(o).to_vec();
     key.extend_from_slice(u.as_bytes());
     SliceRef::from_vec(key)
 }
@@ -89,16 +92,24 @@ pub enum WorldStateSequences {
     MaximumObject = 0,
 }
 
-/// Just write OIDs out as signed ints.
+/// OIDs are written big-endian with the sign bit flipped, so that their byte order (which is the
+/// order domains are ranged over in) is their numeric order, negative ones included.
+fn oid_bytes(oid: Objid) -> [u8; 8] {
+    ((oid.0 as u64) ^ (1 << 63)).to_be_bytes()
+}
+
 pub fn encode_oid(oid: Objid) -> SliceRef {
-    let bytes = oid.0.to_le_bytes();
-    SliceRef::from_vec(bytes.to_vec())
+    SliceRef::from_vec(oid_bytes(oid).to_vec())
+}
+
+/// Decode an OID from the start of `bytes`, which is where it is in composite keys too.
+fn oid_from_bytes(bytes: &[u8]) -> Objid {
+    let bytes = bytes[..8].try_into().expect("Could not decode OID");
+    Objid((u64::from_be_bytes(bytes) ^ (1 << 63)) as i64)
 }
 
-fn decode_oid(sr: &SliceRef) -> Objid {
-    let bytes = sr.as_slice();
-    let oid_i = i64::from_le_bytes(bytes.try_into().expect("Could not decode OID"));
-    Objid(oid_i)
+pub fn decode_oid(sr: &SliceRef) -> Objid {
+    oid_from_bytes(sr.as_slice())
 }
 
 pub fn upsert_object_value<Codomain: Clone + Eq + PartialEq + AsByteBuffer>(
@@ -178,10 +189,10 @@ where
 {
     let relation = tx.relation(RelationId(rel as usize));
     let Ok(all_tuples) = relation.predicate_scan(&|t| {
-        let oid = Objid::from_sliceref(t.domain()).expect("Could not decode OID");
+        let oid = decode_oid(&t.domain());
         pred(
             oid,
-            Codomain::from_sliceref(t.codomain()).expect("Could not decode value"),
+            t.codomain_as::<Codomain>().expect("Could not decode value"),
         )
     }) else {
         return Err(WorldStateError::DatabaseError(
@@ -192,6 +203,25 @@ where
     Ok(ObjSet::from_oid_iter(objs))
 }
 
+/// The objects with a tuple in `rel` that was committed after `ts` (or is pending in `tx`). Every
+/// relation's domain starts with the object id, including those keyed by object and uuid.
+pub fn objects_modified_since(
+    tx: &Transaction,
+    rel: WorldStateRelation,
+    ts: u64,
+) -> Result<ObjSet, WorldStateError> {
+    let relation = tx.relation(RelationId(rel as usize));
+    let Ok(tuples) = relation.predicate_scan(&|t| t.ts() > ts) else {
+        return Err(WorldStateError::DatabaseError(
+            "Unable to scan relation".to_string(),
+        ));
+    };
+    let objs = tuples
+        .into_iter()
+        .map(|t| oid_from_bytes(t.domain().as_slice()));
+    Ok(ObjSet::from_oid_iter(objs))
+}
+
 pub fn get_object_object(tx: &Transaction, rel: WorldStateRelation, oid: Objid) -> Option<Objid> {
     let relation = tx.relation(RelationId(rel as usize));
     match relation.seek_unique_by_domain(encode_oid(oid)) {
@@ -208,9 +238,10 @@ pub fn get_object_value<Codomain: Clone + Eq + PartialEq + AsByteBuffer>(
 ) -> Option<Codomain> {
     let relation = tx.relation(RelationId(rel as usize));
     match relation.seek_unique_by_domain(encode_oid(oid)) {
-        Ok(v) => {
-            Some(Codomain::from_sliceref(v.codomain()).expect("Could not decode codomain value"))
-        }
+        Ok(v) => Some(
+            v.codomain_as::<Codomain>()
+                .expect("Could not decode codomain value"),
+        ),
         Err(RelationError::TupleNotFound) => None,
         Err(e) => panic!("Unexpected error: {:?}", e),
     }
@@ -247,6 +278,9 @@ pub fn get_object_by_object_codomain(
     objs.into_iter().next().unwrap()
 }
 
+/// The objects related to `codomain` (e.g. an object's children or contents), in object number
+/// order. The codomain index is a hash, so without sorting they'd come out in a different order
+/// from one lookup to the next.
 pub fn get_objects_by_object_codomain(
     tx: &Transaction,
     rel: WorldStateRelation,
@@ -254,11 +288,13 @@ pub fn get_objects_by_object_codomain(
 ) -> ObjSet {
     let relation = tx.relation(RelationId(rel as usize));
     let result = relation.seek_by_codomain(encode_oid(codomain));
-    let objs = result
+    let mut objs: Vec<_> = result
         .expect("Unable to seek by codomain")
         .into_iter()
-        .map(|v| decode_oid(&v.domain()));
-    ObjSet::from_oid_iter(objs)
+        .map(|v| decode_oid(&v.domain()))
+        .collect();
+    objs.sort();
+    ObjSet::from_oid_iter(objs.into_iter())
 }
 
 pub fn tuple_size_for_object_codomain(
@@ -282,9 +318,10 @@ pub fn get_composite_value<Codomain: Clone + Eq + PartialEq + AsByteBuffer>(
     let key_bytes = composite_key_for(oid, &uuid);
     let relation = tx.relation(RelationId(rel as usize));
     match relation.seek_unique_by_domain(key_bytes) {
-        Ok(v) => {
-            Some(Codomain::from_sliceref(v.codomain()).expect("Could not decode codomain value"))
-        }
+        Ok(v) => Some(
+            v.codomain_as::<Codomain>()
+                .expect("Could not decode codomain value"),
+        ),
         Err(RelationError::TupleNotFound) => None,
         Err(e) => panic!("Unexpected error: {:?}", e),
     }
@@ -374,6 +411,7 @@ pub fn upsert_obj_uuid_value<Codomain: Clone + Eq + PartialEq + AsByteBuffer>(
 
 #[cfg(test)]
 mod tests {
+    use std::ops::Bound;
     use std::sync::Arc;
 
     use strum::{EnumCount, IntoEnumIterator};
@@ -383,10 +421,11 @@ mod tests {
 
     use crate::odb::object_relations::WorldStateRelation::ObjectParent;
     use crate::odb::object_relations::{
-        get_object_by_object_codomain, get_object_object, get_objects_by_object_codomain,
-        insert_object_object, upsert_object_object, WorldStateRelation, WorldStateSequences,
+        decode_oid, encode_oid, get_object_by_object_codomain, get_object_object,
+        get_objects_by_object_codomain, insert_object_object, upsert_object_object,
+        WorldStateRelation, WorldStateSequences,
     };
-    use moor_rdb::{relation_info_for, RelBox, RelationInfo};
+    use moor_rdb::{relation_info_for, RelBox, RelationId, RelationInfo};
 
     fn test_db() -> Arc<RelBox> {
         let relations: Vec<RelationInfo> =
@@ -395,6 +434,35 @@ mod tests {
         RelBox::new(1 << 24, None, &relations, WorldStateSequences::COUNT)
     }
 
+    /// Encoded object ids order the way the numbers do, so a range of them can be scanned.
+    #[test]
+    fn test_oid_encoding_order() {
+        let oids = [i64::MIN, -300, -2, -1, 0, 1, 255, 256, i64::MAX].map(Objid);
+        for pair in oids.windows(2) {
+            assert!(encode_oid(pair[0]) < encode_oid(pair[1]));
+        }
+        for oid in oids {
+            assert_eq!(decode_oid(&encode_oid(oid)), oid);
+        }
+
+        let db = test_db();
+        let tx = db.clone().start_tx();
+        for oid in oids {
+            insert_object_object(&tx, ObjectParent, oid, Objid(0)).unwrap();
+        }
+        let found: Vec<_> = tx
+            .relation(RelationId::from(ObjectParent))
+            .scan_range(
+                Bound::Included(encode_oid(Objid(-2))),
+                Bound::Excluded(encode_oid(Objid(256))),
+            )
+            .unwrap()
+            .iter()
+            .map(|t| decode_oid(&t.domain()))
+            .collect();
+        assert_eq!(found, [-2, -1, 0, 1, 255].map(Objid));
+    }
+
     /// Test simple relations mapping oid->oid (with secondary index), independent of all other
     /// worldstate voodoo.
     #[test]
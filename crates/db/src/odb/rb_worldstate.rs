@@ -12,26 +12,28 @@
 // this program. If not, see <https://www.gnu.org/licenses/>.
 //
 
-use std::collections::{HashMap, HashSet, VecDeque};
+use std::cell::RefCell;
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+use std::fmt::{Display, Formatter};
 use std::path::PathBuf;
 use std::rc::Rc;
 use std::sync::Arc;
 
 use strum::{EnumCount, IntoEnumIterator};
-use tracing::warn;
+use tracing::{debug, warn};
 use uuid::Uuid;
 
 use moor_values::model::ObjSet;
-use moor_values::model::PropFlag;
 use moor_values::model::VerbArgsSpec;
 use moor_values::model::{BinaryType, VerbAttrs, VerbFlag};
 use moor_values::model::{CommitResult, WorldStateError};
 use moor_values::model::{HasUuid, Named};
 use moor_values::model::{ObjAttrs, ObjFlag};
+use moor_values::model::{PropAttrs, PropFlag};
 use moor_values::model::{PropDef, PropDefs};
 use moor_values::model::{VerbDef, VerbDefs};
 use moor_values::model::{WorldState, WorldStateSource};
-use moor_values::util::BitEnum;
+use moor_values::util::{BitEnum, SliceRef};
 use moor_values::var::Objid;
 use moor_values::var::{v_none, Var};
 use moor_values::{AsByteBuffer, NOTHING, SYSTEM_OBJECT};
@@ -41,16 +43,17 @@ use crate::db_worldstate::DbTxWorldState;
 use crate::loader::LoaderInterface;
 use crate::odb::object_relations;
 use crate::odb::object_relations::{
-    encode_oid, get_all_object_keys_matching, WorldStateRelation, WorldStateSequences,
+    decode_oid, encode_oid, get_all_object_keys_matching, WorldStateRelation, WorldStateSequences,
 };
 use crate::Database;
 use moor_rdb::{relation_info_for, RelationError};
-use moor_rdb::{CommitError, Transaction};
+use moor_rdb::{CommitError, Savepoint, Transaction};
 use moor_rdb::{RelBox, RelationInfo};
 
 /// An implementation of `WorldState` / `WorldStateSource` that uses the rdb as its backing
 pub struct RelBoxWorldState {
     db: Arc<RelBox>,
+    audit_log: bool,
 }
 
 impl RelBoxWorldState {
@@ -68,19 +71,108 @@ impl RelBoxWorldState {
                 .expect("Could not seek for freshness check on DB")
                 .is_empty()
         };
-        (Self { db }, fresh_db)
+        (
+            Self {
+                db,
+                audit_log: false,
+            },
+            fresh_db,
+        )
+    }
+
+    /// Have world states from this source log an audit record of structural changes (see
+    /// `DbTxWorldState::with_audit_log`).
+    pub fn with_audit_log(mut self, audit_log: bool) -> Self {
+        self.audit_log = audit_log;
+        self
     }
 }
 
 impl WorldStateSource for RelBoxWorldState {
     fn new_world_state(&self) -> Result<Box<dyn WorldState>, WorldStateError> {
         let tx = RelBoxTransaction::new(self.db.clone());
-        Ok(Box::new(DbTxWorldState { tx: Box::new(tx) }))
+        Ok(Box::new(
+            DbTxWorldState::new(Box::new(tx)).with_audit_log(self.audit_log),
+        ))
     }
 }
 
 pub struct RelBoxTransaction {
     tx: Transaction,
+    /// Savepoints taken in this transaction, oldest first; a savepoint's handle is its index here.
+    savepoints: RefCell<Vec<Savepoint>>,
+}
+
+/// A broken invariant in the world state relations, as found by `verify_database`.
+enum Inconsistency {
+    InvalidParent { obj: Objid, parent: Objid },
+    NotAmongChildren { obj: Objid, parent: Objid },
+    InvalidLocation { obj: Objid, location: Objid },
+    NotAmongContents { obj: Objid, location: Objid },
+    InvalidChild { obj: Objid, child: Objid },
+    InvalidContent { obj: Objid, item: Objid },
+    AncestryCycle { obj: Objid },
+    InvalidDefiner { obj: Objid, propdef: PropDef },
+    DefinerNotAncestor { obj: Objid, propdef: PropDef },
+    MissingProgram { obj: Objid, verbdef: VerbDef },
+}
+
+impl Display for Inconsistency {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Inconsistency::InvalidParent { obj, parent } => {
+                write!(f, "{} has invalid parent {}", obj, parent)
+            }
+            Inconsistency::NotAmongChildren { obj, parent } => {
+                write!(f, "{} is missing from the children of {}", obj, parent)
+            }
+            Inconsistency::InvalidLocation { obj, location } => {
+                write!(f, "{} is located in invalid object {}", obj, location)
+            }
+            Inconsistency::NotAmongContents { obj, location } => {
+                write!(f, "{} is missing from the contents of {}", obj, location)
+            }
+            Inconsistency::InvalidChild { obj, child } => {
+                write!(f, "{} has invalid child {}", obj, child)
+            }
+            Inconsistency::InvalidContent { obj, item } => {
+                write!(f, "{} contains invalid object {}", obj, item)
+            }
+            Inconsistency::AncestryCycle { obj } => {
+                write!(f, "{} has a cycle in its ancestry", obj)
+            }
+            Inconsistency::InvalidDefiner { obj, propdef } => write!(
+                f,
+                "property {} on {} has invalid definer {}",
+                propdef.name(),
+                obj,
+                propdef.definer()
+            ),
+            Inconsistency::DefinerNotAncestor { obj, propdef } => write!(
+                f,
+                "property {} on {} is defined by {}, which is not an ancestor",
+                propdef.name(),
+                obj,
+                propdef.definer()
+            ),
+            Inconsistency::MissingProgram { obj, verbdef } => write!(
+                f,
+                "verb {} on {} has no program",
+                verbdef.names().join(" "),
+                obj
+            ),
+        }
+    }
+}
+
+/// What `repair_database` does about an `Inconsistency`.
+enum RepairAction {
+    /// Remove the tuple with this domain.
+    Remove(WorldStateRelation, SliceRef),
+    /// Write the tuple with this domain back as it is, so its secondary index is rebuilt.
+    Rewrite(WorldStateRelation, SliceRef),
+    /// Remove a property definition (and any value for it) from an object.
+    DropProperty(Objid, Uuid),
 }
 
 impl DbTransaction for RelBoxTransaction {
@@ -131,6 +223,18 @@ impl DbTransaction for RelBoxTransaction {
             .ok_or(WorldStateError::ObjectNotFound(obj))
     }
 
+    fn get_object_attrs(&self, obj: Objid) -> Result<ObjAttrs, WorldStateError> {
+        // Every valid object has an owner, so that doubles as the validity check.
+        let owner = self.get_object_owner(obj)?;
+        Ok(ObjAttrs {
+            owner: Some(owner),
+            name: Some(self.get_object_name(obj)?),
+            parent: Some(self.get_object_parent(obj)?),
+            location: Some(self.get_object_location(obj)?),
+            flags: Some(self.get_object_flags(obj)?),
+        })
+    }
+
     fn create_object(&self, id: Option<Objid>, attrs: ObjAttrs) -> Result<Objid, WorldStateError> {
         let id = match id {
             Some(id) => id,
@@ -200,7 +304,23 @@ impl DbTransaction for RelBoxTransaction {
         let parent = self.get_object_parent(obj)?;
         let children = self.get_object_children(obj)?;
         for c in children.iter() {
-            self.set_object_parent(c, parent)?;
+            let extras = self.extra_parents(c);
+            if extras.is_empty() {
+                self.set_object_parent(c, parent)?;
+            } else {
+                let parents: Vec<_> = std::iter::once(parent)
+                    .filter(|p| *p != NOTHING)
+                    .chain(extras.iter())
+                    .collect();
+                self.set_object_parents(c, ObjSet::from(&parents))?;
+            }
+        }
+        // And anything using us as an additional parent just loses us.
+        if let Some(extra_children) = self.extra_children()?.remove(&obj) {
+            for c in extra_children {
+                let parents = self.get_object_parents(c)?.with_removed(obj);
+                self.set_object_parents(c, parents)?;
+            }
         }
 
         // Now we can remove this object from all relevant column relations
@@ -212,12 +332,15 @@ impl DbTransaction for RelBoxTransaction {
             WorldStateRelation::ObjectParent,
             WorldStateRelation::ObjectLocation,
             WorldStateRelation::ObjectVerbs,
+            WorldStateRelation::ObjectExtraParents,
         ];
         for rel in oid_relations.iter() {
             let relation = self.tx.relation((*rel).into());
-            relation
-                .remove_by_domain(obj.0.as_sliceref().expect("Unable to encode object id"))
-                .map_err(|e| WorldStateError::DatabaseError(e.to_string()))?;
+            // Not every object has a tuple in every relation (no name, no location, no verbs...)
+            match relation.remove_by_domain(encode_oid(obj)) {
+                Ok(_) | Err(RelationError::TupleNotFound) => {}
+                Err(e) => return Err(WorldStateError::DatabaseError(e.to_string())),
+            }
         }
 
         let propdefs = self.get_properties(obj)?;
@@ -230,13 +353,181 @@ impl DbTransaction for RelBoxTransaction {
         }
 
         let obj_propdefs_rel = self.tx.relation(WorldStateRelation::ObjectPropDefs.into());
-        obj_propdefs_rel
-            .remove_by_domain(obj.0.as_sliceref().expect("Unable to encode object id"))
-            .expect("Unable to delete propdefs");
+        match obj_propdefs_rel.remove_by_domain(encode_oid(obj)) {
+            Ok(_) | Err(RelationError::TupleNotFound) => {}
+            Err(e) => return Err(WorldStateError::DatabaseError(e.to_string())),
+        }
 
         Ok(())
     }
 
+    fn renumber_object(&self, obj: Objid) -> Result<Objid, WorldStateError> {
+        if !self.object_valid(obj)? {
+            return Err(WorldStateError::ObjectNotFound(obj));
+        }
+
+        // The lowest free number below the object's own; if there isn't one, it stays put.
+        let mut new = obj;
+        for candidate in (0..obj.0).map(Objid) {
+            if !self.object_valid(candidate)? {
+                new = candidate;
+                break;
+            }
+        }
+        if new == obj {
+            return Ok(obj);
+        }
+
+        // Move the object's own tuples over to the new number. Verb programs and property values
+        // are keyed by object and uuid, so those have to move too.
+        let verbdefs = self.get_verbs(obj)?;
+        let propdefs = self.get_properties(obj)?;
+        let oid_relations = [
+            WorldStateRelation::ObjectFlags,
+            WorldStateRelation::ObjectName,
+            WorldStateRelation::ObjectOwner,
+            WorldStateRelation::ObjectParent,
+            WorldStateRelation::ObjectLocation,
+            WorldStateRelation::ObjectVerbs,
+            WorldStateRelation::ObjectPropDefs,
+            WorldStateRelation::ObjectExtraParents,
+        ];
+        for rel in oid_relations {
+            self.move_tuple(rel, encode_oid(obj), encode_oid(new))?;
+        }
+        for v in verbdefs.iter() {
+            self.move_tuple(
+                WorldStateRelation::VerbProgram,
+                object_relations::composite_key_for(obj, &v.uuid()),
+                object_relations::composite_key_for(new, &v.uuid()),
+            )?;
+        }
+        for p in propdefs.iter() {
+            self.move_tuple(
+                WorldStateRelation::ObjectPropertyValue,
+                object_relations::composite_key_for(obj, &p.uuid()),
+                object_relations::composite_key_for(new, &p.uuid()),
+            )?;
+        }
+
+        // Then point everything which referred to the old number at the new one: children,
+        // contents, and anything owned by the object...
+        let old_key = encode_oid(obj);
+        for rel in [
+            WorldStateRelation::ObjectParent,
+            WorldStateRelation::ObjectLocation,
+            WorldStateRelation::ObjectOwner,
+        ] {
+            let relation = self.tx.relation(rel.into());
+            let referrers = relation
+                .predicate_scan(&|t| t.codomain().as_slice() == old_key.as_slice())
+                .map_err(|e| WorldStateError::DatabaseError(e.to_string()))?;
+            for t in referrers {
+                relation
+                    .upsert_by_domain(t.domain(), encode_oid(new))
+                    .map_err(|e| WorldStateError::DatabaseError(e.to_string()))?;
+            }
+        }
+
+        // ... any additional parent lists we're in...
+        let renumbered = |o: Objid| if o == obj { new } else { o };
+        let extras_rel = self
+            .tx
+            .relation(WorldStateRelation::ObjectExtraParents.into());
+        let all_extras = extras_rel
+            .predicate_scan(&|_| true)
+            .map_err(|e| WorldStateError::DatabaseError(e.to_string()))?;
+        for t in all_extras {
+            let extras = t
+                .codomain_as::<ObjSet>()
+                .map_err(|e| WorldStateError::DatabaseError(e.to_string()))?;
+            if !extras.contains(obj) {
+                continue;
+            }
+            let extras = ObjSet::from_oid_iter(extras.iter().map(renumbered));
+            extras_rel
+                .upsert_by_domain(
+                    t.domain(),
+                    extras.as_sliceref().expect("Could not encode parents"),
+                )
+                .map_err(|e| WorldStateError::DatabaseError(e.to_string()))?;
+        }
+
+        // ... and the definer, location, and owner fields of verb and property definitions,
+        // wherever they live.
+        let verbs_rel = self.tx.relation(WorldStateRelation::ObjectVerbs.into());
+        let all_verbdefs = verbs_rel
+            .predicate_scan(&|_| true)
+            .map_err(|e| WorldStateError::DatabaseError(e.to_string()))?;
+        for t in all_verbdefs {
+            let verbdefs = VerbDefs::from_sliceref(t.codomain());
+            if !verbdefs
+                .iter()
+                .any(|v| v.location() == obj || v.owner() == obj)
+            {
+                continue;
+            }
+            let verbdefs: Vec<_> = verbdefs
+                .iter()
+                .map(|v| {
+                    VerbDef::new(
+                        v.uuid(),
+                        renumbered(v.location()),
+                        renumbered(v.owner()),
+                        &v.names(),
+                        v.flags(),
+                        v.binary_type(),
+                        v.args(),
+                    )
+                })
+                .collect();
+            verbs_rel
+                .upsert_by_domain(
+                    t.domain(),
+                    VerbDefs::from_items(&verbdefs)
+                        .as_sliceref()
+                        .expect("Could not encode verbdefs"),
+                )
+                .map_err(|e| WorldStateError::DatabaseError(e.to_string()))?;
+        }
+        let props_rel = self.tx.relation(WorldStateRelation::ObjectPropDefs.into());
+        let all_propdefs = props_rel
+            .predicate_scan(&|_| true)
+            .map_err(|e| WorldStateError::DatabaseError(e.to_string()))?;
+        for t in all_propdefs {
+            let propdefs = PropDefs::from_sliceref(t.codomain());
+            if !propdefs
+                .iter()
+                .any(|p| p.definer() == obj || p.location() == obj || p.owner() == obj)
+            {
+                continue;
+            }
+            let propdefs: Vec<_> = propdefs
+                .iter()
+                .map(|p| {
+                    PropDef::new(
+                        p.uuid(),
+                        renumbered(p.definer()),
+                        renumbered(p.location()),
+                        p.name(),
+                        p.flags(),
+                        renumbered(p.owner()),
+                    )
+                })
+                .collect();
+            props_rel
+                .upsert_by_domain(
+                    t.domain(),
+                    PropDefs::from_items(&propdefs)
+                        .as_sliceref()
+                        .expect("Could not encode propdefs"),
+                )
+                .map_err(|e| WorldStateError::DatabaseError(e.to_string()))?;
+        }
+
+        Ok(new)
+    }
+
     fn set_object_name(&self, obj: Objid, name: String) -> Result<(), WorldStateError> {
         object_relations::upsert_object_value(&self.tx, WorldStateRelation::ObjectName, obj, name)
     }
@@ -392,6 +683,67 @@ impl DbTransaction for RelBoxTransaction {
         Ok(())
     }
 
+    fn get_object_parents(&self, obj: Objid) -> Result<ObjSet, WorldStateError> {
+        let parent = self.get_object_parent(obj)?;
+        if parent == NOTHING {
+            return Ok(ObjSet::empty());
+        }
+        Ok(ObjSet::from(&[parent]).with_concatenated(self.extra_parents(obj)))
+    }
+
+    fn set_object_parents(&self, obj: Objid, parents: ObjSet) -> Result<(), WorldStateError> {
+        // Work out everybody's new property definitions up front, so that a conflict leaves
+        // nothing half-done.
+        let rebuilt = self.rebuild_inherited_propdefs(obj, &parents)?;
+
+        let mut parents = parents.iter();
+        let primary = parents.next().unwrap_or(NOTHING);
+        let extras = ObjSet::from_oid_iter(parents);
+        object_relations::upsert_object_object(
+            &self.tx,
+            WorldStateRelation::ObjectParent,
+            obj,
+            primary,
+        )?;
+        if extras.is_empty() {
+            let relation = self
+                .tx
+                .relation(WorldStateRelation::ObjectExtraParents.into());
+            match relation.remove_by_domain(encode_oid(obj)) {
+                Ok(_) | Err(RelationError::TupleNotFound) => {}
+                Err(e) => return Err(WorldStateError::DatabaseError(e.to_string())),
+            }
+        } else {
+            object_relations::upsert_object_value(
+                &self.tx,
+                WorldStateRelation::ObjectExtraParents,
+                obj,
+                extras,
+            )?;
+        }
+
+        // Values of properties that are no longer inherited go away with their definitions.
+        for (o, old_props, new_props) in rebuilt {
+            for p in old_props.iter() {
+                if !new_props.contains(p.uuid()) {
+                    object_relations::delete_composite_if_exists(
+                        &self.tx,
+                        WorldStateRelation::ObjectPropertyValue,
+                        o,
+                        p.uuid(),
+                    )?;
+                }
+            }
+            object_relations::upsert_object_value(
+                &self.tx,
+                WorldStateRelation::ObjectPropDefs,
+                o,
+                new_props,
+            )?;
+        }
+        Ok(())
+    }
+
     fn get_object_children(&self, obj: Objid) -> Result<ObjSet, WorldStateError> {
         Ok(object_relations::get_objects_by_object_codomain(
             &self.tx,
@@ -471,6 +823,17 @@ impl DbTransaction for RelBoxTransaction {
         ))
     }
 
+    fn reset_max_object(&self) -> Result<(), WorldStateError> {
+        // Like the rest of the sequence handling this isn't transactional: the new maximum is
+        // set now, from what this transaction can see.
+        let max = self.get_objects()?.iter().map(|o| o.0).max().unwrap_or(-1);
+        self.tx.set_sequence(
+            WorldStateSequences::MaximumObject as usize,
+            (max + 1) as u64,
+        );
+        Ok(())
+    }
+
     fn get_verbs(&self, obj: Objid) -> Result<VerbDefs, WorldStateError> {
         Ok(
             object_relations::get_object_value(&self.tx, WorldStateRelation::ObjectVerbs, obj)
@@ -511,8 +874,8 @@ impl DbTransaction for RelBoxTransaction {
         name: String,
         argspec: Option<VerbArgsSpec>,
     ) -> Result<VerbDef, WorldStateError> {
-        let mut search_o = obj;
-        loop {
+        // Walk the object and then its ancestors, in resolution order.
+        for search_o in self.ancestors(obj)?.iter() {
             if let Some(verbdefs) = object_relations::get_object_value::<VerbDefs>(
                 &self.tx,
                 WorldStateRelation::ObjectVerbs,
@@ -533,18 +896,6 @@ impl DbTransaction for RelBoxTransaction {
                     }
                 }
             }
-            // Otherwise, find our parent.  If it's, then set o to it and continue unless we've
-            // hit the end of the chain.
-            search_o = match object_relations::get_object_object(
-                &self.tx,
-                WorldStateRelation::ObjectParent,
-                search_o,
-            ) {
-                Some(NOTHING) | None => {
-                    break;
-                }
-                Some(parent) => parent,
-            };
         }
         Err(WorldStateError::VerbNotFound(obj, name))
     }
@@ -607,12 +958,13 @@ impl DbTransaction for RelBoxTransaction {
         binary_type: BinaryType,
         flags: BitEnum<VerbFlag>,
         args: VerbArgsSpec,
+        uuid: Option<Uuid>,
     ) -> Result<(), WorldStateError> {
         let verbdefs =
             object_relations::get_object_value(&self.tx, WorldStateRelation::ObjectVerbs, oid)
                 .unwrap_or(VerbDefs::empty());
 
-        let uuid = Uuid::new_v4();
+        let uuid = uuid.unwrap_or_else(Uuid::new_v4);
         let verbdef = VerbDef::new(
             uuid,
             oid,
@@ -691,27 +1043,40 @@ impl DbTransaction for RelBoxTransaction {
         owner: Objid,
         perms: BitEnum<PropFlag>,
         value: Option<Var>,
+        uuid: Option<Uuid>,
     ) -> Result<Uuid, WorldStateError> {
         let descendants = self.descendants(location)?;
         let locations = ObjSet::from(&[location]).with_concatenated(descendants);
 
-        // Generate a new property ID. This will get shared all the way down the pipe.
-        // But the key for the actual value is always composite of oid,uuid
-        let u = Uuid::new_v4();
+        // Generate a new property ID, unless we were given one. This will get shared all the way
+        // down the pipe. But the key for the actual value is always composite of oid,uuid
+        let u = uuid.unwrap_or_else(Uuid::new_v4);
 
-        for location in locations.iter() {
-            let props = object_relations::get_object_value(
-                &self.tx,
-                WorldStateRelation::ObjectPropDefs,
-                location,
-            )
-            .unwrap_or(PropDefs::empty());
+        let existing = locations
+            .iter()
+            .map(|location| {
+                let props = object_relations::get_object_value(
+                    &self.tx,
+                    WorldStateRelation::ObjectPropDefs,
+                    location,
+                )
+                .unwrap_or(PropDefs::empty());
+                (location, props)
+            })
+            .collect::<Vec<_>>();
 
-            // Verify we don't already have a property with this name. If we do, return an error.
-            if props.find_first_named(name.as_str()).is_some() {
-                return Err(WorldStateError::DuplicatePropertyDefinition(location, name));
-            }
+        // Verify nobody down the chain already has a property with this name before touching any
+        // of them, so that a collision doesn't leave a half-defined property behind.
+        if let Some((location, _)) = existing
+            .iter()
+            .find(|(_, props)| props.find_first_named(name.as_str()).is_some())
+        {
+            return Err(WorldStateError::DuplicatePropertyDefinition(
+                *location, name,
+            ));
+        }
 
+        for (location, props) in existing {
             let prop = PropDef::new(u, definer, location, name.as_str(), perms, owner);
             object_relations::upsert_object_value(
                 &self.tx,
@@ -776,6 +1141,42 @@ impl DbTransaction for RelBoxTransaction {
         Ok(())
     }
 
+    fn update_property_definitions(
+        &self,
+        obj: Objid,
+        updates: Vec<(Uuid, PropAttrs)>,
+    ) -> Result<(), WorldStateError> {
+        let mut props =
+            object_relations::get_object_value(&self.tx, WorldStateRelation::ObjectPropDefs, obj)
+                .unwrap_or(PropDefs::empty());
+
+        // Apply everything to our copy first, so that a missing property leaves nothing written.
+        for (uuid, attrs) in updates {
+            let Some(updated) = props.with_updated(uuid, |p| {
+                PropDef::new(
+                    p.uuid(),
+                    p.definer(),
+                    p.location(),
+                    attrs.name.as_deref().unwrap_or_else(|| p.name()),
+                    attrs.flags.unwrap_or_else(|| p.flags()),
+                    attrs.owner.unwrap_or_else(|| p.owner()),
+                )
+            }) else {
+                return Err(WorldStateError::PropertyNotFound(obj, format!("{}", uuid)));
+            };
+            props = updated;
+        }
+
+        object_relations::upsert_object_value(
+            &self.tx,
+            WorldStateRelation::ObjectPropDefs,
+            obj,
+            props,
+        )?;
+
+        Ok(())
+    }
+
     fn clear_property(&self, obj: Objid, uuid: Uuid) -> Result<(), WorldStateError> {
         let key = object_relations::composite_key_for(obj, &uuid);
         let rel = self
@@ -837,10 +1238,10 @@ impl DbTransaction for RelBoxTransaction {
             .ok_or_else(|| WorldStateError::PropertyNotFound(obj, name.clone()))?;
 
         // Then we're going to resolve the value up the tree, skipping 'clear' (un-found) until we
-        // get a value.
-        let mut search_obj = obj;
-        loop {
-            // Look for the value. If we're not 'clear', we can return straight away. that's our thing.
+        // get a value. (But we return the propdef we got from the object itself, because this is
+        // what we want to return for information about permissions, etc.) If it's clear all the
+        // way up, our value ends up being NONE.
+        for search_obj in self.ancestors(obj)?.iter() {
             if let Some(found) = object_relations::get_composite_value::<Var>(
                 &self.tx,
                 WorldStateRelation::ObjectPropertyValue,
@@ -849,46 +1250,51 @@ impl DbTransaction for RelBoxTransaction {
             ) {
                 return Ok((propdef, found));
             }
-
-            // But if it was clear, we have to continue up the inheritance hierarchy. (But we return
-            // the of handle we got, because this is what we want to return for information
-            // about permissions, etc.)
-            let Some(parent) = object_relations::get_object_object(
-                &self.tx,
-                WorldStateRelation::ObjectParent,
-                search_obj,
-            ) else {
-                // If we hit the end of the chain, we're done.
-                break;
-            };
-
-            if parent == NOTHING {
-                // This is an odd one, clear all the way up. so our value will end up being
-                // NONE, I guess.
-                break;
-            }
-            search_obj = parent;
         }
         Ok((propdef, v_none()))
     }
 
     fn ancestors(&self, obj: Objid) -> Result<ObjSet, WorldStateError> {
+        // Depth-first, left to right through each object's parents, keeping only the first
+        // occurrence of an ancestor reachable by more than one path.
         let mut ancestors = vec![];
-        let mut search = obj;
-        loop {
-            if search == NOTHING {
-                break;
+        let mut seen = HashSet::new();
+        let mut stack = vec![obj];
+        while let Some(search) = stack.pop() {
+            if search == NOTHING || !seen.insert(search) {
+                continue;
             }
             ancestors.push(search);
-            let parent = object_relations::get_object_object(
+            let parents: Vec<_> = self.get_object_parents(search)?.iter().collect();
+            stack.extend(parents.into_iter().rev());
+        }
+        Ok(ObjSet::from(&ancestors))
+    }
+
+    fn descendants(&self, obj: Objid) -> Result<ObjSet, WorldStateError> {
+        let extra_children = self.extra_children()?;
+        let children_of = |o: Objid| {
+            let children = object_relations::get_objects_by_object_codomain(
                 &self.tx,
                 WorldStateRelation::ObjectParent,
-                search,
-            )
-            .unwrap_or(NOTHING);
-            search = parent;
+                o,
+            );
+            let extra = extra_children.get(&o).cloned().unwrap_or_default();
+            children.iter().chain(extra).collect::<Vec<_>>()
+        };
+
+        let mut descendants = vec![];
+        let mut seen = HashSet::new();
+        let mut queue: VecDeque<_> = children_of(obj).into();
+        while let Some(o) = queue.pop_front() {
+            if !seen.insert(o) {
+                continue;
+            }
+            descendants.push(o);
+            queue.extend(children_of(o));
         }
-        Ok(ObjSet::from(&ancestors))
+
+        Ok(ObjSet::from(&descendants))
     }
 
     fn object_valid(&self, obj: Objid) -> Result<bool, WorldStateError> {
@@ -993,8 +1399,18 @@ impl DbTransaction for RelBoxTransaction {
     fn commit(&self) -> Result<CommitResult, WorldStateError> {
         match self.tx.commit() {
             Ok(_) => Ok(CommitResult::Success),
-            Err(CommitError::TupleVersionConflict) => Ok(CommitResult::ConflictRetry),
-            Err(CommitError::UniqueConstraintViolation) => Ok(CommitResult::ConflictRetry),
+            Err(CommitError::TupleVersionConflict { relation, domain }) => {
+                debug!(?relation, ?domain, "Version conflict on commit");
+                Ok(CommitResult::ConflictRetry)
+            }
+            Err(CommitError::UniqueConstraintViolation { relation, domain }) => {
+                debug!(?relation, ?domain, "Unique constraint violation on commit");
+                Ok(CommitResult::ConflictRetry)
+            }
+            Err(CommitError::SerializationConflict { relation }) => {
+                debug!(?relation, "Serialization conflict on commit");
+                Ok(CommitResult::ConflictRetry)
+            }
             Err(CommitError::RelationContentionConflict) => {
                 warn!("Contention conflict; too many concurrent writes on the same relation(s) after retries.");
                 Ok(CommitResult::ConflictRetry)
@@ -1009,39 +1425,326 @@ impl DbTransaction for RelBoxTransaction {
         }
     }
 
+    fn savepoint(&self) -> Result<usize, WorldStateError> {
+        let mut savepoints = self.savepoints.borrow_mut();
+        savepoints.push(self.tx.savepoint());
+        Ok(savepoints.len() - 1)
+    }
+
+    fn rollback_to(&self, savepoint: usize) -> Result<(), WorldStateError> {
+        let mut savepoints = self.savepoints.borrow_mut();
+        let Some(sp) = savepoints.get(savepoint) else {
+            return Err(WorldStateError::SavepointNotFound(savepoint));
+        };
+        self.tx
+            .rollback_to(sp)
+            .map_err(|e| WorldStateError::DatabaseError(e.to_string()))?;
+        savepoints.truncate(savepoint + 1);
+        Ok(())
+    }
+
     fn db_usage(&self) -> Result<usize, WorldStateError> {
         Ok(self.tx.db_usage_bytes())
     }
+
+    fn timestamp(&self) -> u64 {
+        self.tx.ts()
+    }
+
+    fn objects_modified_since(&self, ts: u64) -> Result<ObjSet, WorldStateError> {
+        let mut modified = BTreeSet::new();
+        for rel in WorldStateRelation::iter() {
+            let objs = object_relations::objects_modified_since(&self.tx, rel, ts)?;
+            modified.extend(objs.iter());
+        }
+        Ok(ObjSet::from_oid_iter(modified.into_iter()))
+    }
+
+    fn verify_database(&self) -> Result<Vec<String>, WorldStateError> {
+        Ok(self
+            .inconsistencies()?
+            .iter()
+            .map(|i| i.to_string())
+            .collect())
+    }
+
+    fn repair_database(&self, dry_run: bool) -> Result<Vec<String>, WorldStateError> {
+        let mut repairs = vec![];
+        for inconsistency in self.inconsistencies()? {
+            let Some(fix) = self.repair(&inconsistency, dry_run)? else {
+                continue;
+            };
+            repairs.push(format!("{}: {}", inconsistency, fix));
+        }
+        Ok(repairs)
+    }
 }
 
 impl RelBoxTransaction {
     pub fn new(db: Arc<RelBox>) -> Self {
         let tx = db.start_tx();
-        Self { tx }
+        Self {
+            tx,
+            savepoints: RefCell::new(vec![]),
+        }
     }
 
-    pub(crate) fn descendants(&self, obj: Objid) -> Result<ObjSet, WorldStateError> {
-        let children = object_relations::get_objects_by_object_codomain(
-            &self.tx,
-            WorldStateRelation::ObjectParent,
-            obj,
-        );
+    /// Walk every object looking for broken invariants between the relations.
+    fn inconsistencies(&self) -> Result<Vec<Inconsistency>, WorldStateError> {
+        let mut found = vec![];
+        for obj in self.get_objects()?.iter() {
+            // Our links to our parent and location must be backed by their children and contents.
+            let parent = self.get_object_parent(obj)?;
+            if parent != NOTHING {
+                if !self.object_valid(parent)? {
+                    found.push(Inconsistency::InvalidParent { obj, parent });
+                } else if !self.get_object_children(parent)?.contains(obj) {
+                    found.push(Inconsistency::NotAmongChildren { obj, parent });
+                }
+            }
+            let location = self.get_object_location(obj)?;
+            if location != NOTHING {
+                if !self.object_valid(location)? {
+                    found.push(Inconsistency::InvalidLocation { obj, location });
+                } else if !self.get_object_contents(location)?.contains(obj) {
+                    found.push(Inconsistency::NotAmongContents { obj, location });
+                }
+            }
+            for child in self.get_object_children(obj)?.iter() {
+                if !self.object_valid(child)? {
+                    found.push(Inconsistency::InvalidChild { obj, child });
+                }
+            }
+            for item in self.get_object_contents(obj)?.iter() {
+                if !self.object_valid(item)? {
+                    found.push(Inconsistency::InvalidContent { obj, item });
+                }
+            }
 
-        let mut descendants = vec![];
-        let mut queue: VecDeque<_> = children.iter().collect();
-        while let Some(o) = queue.pop_front() {
-            descendants.push(o);
-            let children = object_relations::get_objects_by_object_codomain(
-                &self.tx,
-                WorldStateRelation::ObjectParent,
-                o,
-            );
-            queue.extend(children.iter());
-        }
+            // Walk the ancestry by hand, rather than with `ancestors`, so a cycle can't hang us.
+            let mut ancestors = vec![obj];
+            let mut search = parent;
+            while search != NOTHING && self.object_valid(search)? {
+                if ancestors.contains(&search) {
+                    found.push(Inconsistency::AncestryCycle { obj });
+                    break;
+                }
+                ancestors.push(search);
+                search = self.get_object_parent(search)?;
+            }
 
-        Ok(ObjSet::from(&descendants))
+            for propdef in self.get_properties(obj)?.iter() {
+                let definer = propdef.definer();
+                if !self.object_valid(definer)? {
+                    found.push(Inconsistency::InvalidDefiner { obj, propdef });
+                } else if !ancestors.contains(&definer) {
+                    found.push(Inconsistency::DefinerNotAncestor { obj, propdef });
+                }
+            }
+            for verbdef in self.get_verbs(obj)?.iter() {
+                if self.get_verb_binary(obj, verbdef.uuid()).is_err() {
+                    found.push(Inconsistency::MissingProgram { obj, verbdef });
+                }
+            }
+        }
+        Ok(found)
     }
-    fn closest_common_ancestor_with_ancestors(
+
+    /// Describe how `inconsistency` can be fixed, and (unless `dry_run`) fix it. Returns `None`
+    /// for the ones that need a human to decide what was meant.
+    fn repair(
+        &self,
+        inconsistency: &Inconsistency,
+        dry_run: bool,
+    ) -> Result<Option<&'static str>, WorldStateError> {
+        let (fix, description) = match inconsistency {
+            Inconsistency::InvalidParent { obj, .. } => (
+                RepairAction::Remove(WorldStateRelation::ObjectParent, encode_oid(*obj)),
+                "cleared its parent",
+            ),
+            Inconsistency::InvalidLocation { obj, .. } => (
+                RepairAction::Remove(WorldStateRelation::ObjectLocation, encode_oid(*obj)),
+                "moved it to #-1",
+            ),
+            // The link is there but the index doesn't know about it; writing it back rebuilds it.
+            Inconsistency::NotAmongChildren { obj, .. } => (
+                RepairAction::Rewrite(WorldStateRelation::ObjectParent, encode_oid(*obj)),
+                "relinked it",
+            ),
+            Inconsistency::NotAmongContents { obj, .. } => (
+                RepairAction::Rewrite(WorldStateRelation::ObjectLocation, encode_oid(*obj)),
+                "relinked it",
+            ),
+            Inconsistency::InvalidChild { child, .. } => (
+                RepairAction::Remove(WorldStateRelation::ObjectParent, encode_oid(*child)),
+                "dropped the dangling entry",
+            ),
+            Inconsistency::InvalidContent { item, .. } => (
+                RepairAction::Remove(WorldStateRelation::ObjectLocation, encode_oid(*item)),
+                "dropped the dangling entry",
+            ),
+            Inconsistency::InvalidDefiner { obj, propdef }
+            | Inconsistency::DefinerNotAncestor { obj, propdef } => (
+                RepairAction::DropProperty(*obj, propdef.uuid()),
+                "dropped the property",
+            ),
+            Inconsistency::AncestryCycle { .. } | Inconsistency::MissingProgram { .. } => {
+                return Ok(None)
+            }
+        };
+        if dry_run {
+            return Ok(Some(description));
+        }
+        match fix {
+            RepairAction::Remove(rel, domain) => {
+                let relation = self.tx.relation(rel.into());
+                match relation.remove_by_domain(domain) {
+                    Ok(_) | Err(RelationError::TupleNotFound) => {}
+                    Err(e) => return Err(WorldStateError::DatabaseError(e.to_string())),
+                }
+            }
+            RepairAction::Rewrite(rel, domain) => {
+                self.move_tuple(rel, domain.clone(), domain)?;
+            }
+            RepairAction::DropProperty(obj, uuid) => {
+                let propdefs = self.get_properties(obj)?;
+                object_relations::upsert_object_value(
+                    &self.tx,
+                    WorldStateRelation::ObjectPropDefs,
+                    obj,
+                    propdefs.with_all_removed(&[uuid]),
+                )?;
+                object_relations::delete_composite_if_exists(
+                    &self.tx,
+                    WorldStateRelation::ObjectPropertyValue,
+                    obj,
+                    uuid,
+                )?;
+            }
+        }
+        Ok(Some(description))
+    }
+
+    /// Re-key the tuple at `from` (if there is one) to `to`, keeping its value.
+    /// The parents of the given object beyond its primary one, in order.
+    fn extra_parents(&self, obj: Objid) -> ObjSet {
+        object_relations::get_object_value(&self.tx, WorldStateRelation::ObjectExtraParents, obj)
+            .unwrap_or_default()
+    }
+
+    /// Every object which has additional parents, indexed by each of those parents.
+    fn extra_children(&self) -> Result<HashMap<Objid, Vec<Objid>>, WorldStateError> {
+        let relation = self
+            .tx
+            .relation(WorldStateRelation::ObjectExtraParents.into());
+        let tuples = relation
+            .predicate_scan(&|_| true)
+            .map_err(|e| WorldStateError::DatabaseError(e.to_string()))?;
+        let mut extra_children: HashMap<Objid, Vec<Objid>> = HashMap::new();
+        for t in tuples {
+            let child = decode_oid(&t.domain());
+            for parent in t
+                .codomain_as::<ObjSet>()
+                .map_err(|e| WorldStateError::DatabaseError(e.to_string()))?
+                .iter()
+            {
+                extra_children.entry(parent).or_default().push(child);
+            }
+        }
+        Ok(extra_children)
+    }
+
+    /// Work out the property definitions `obj` and each of its descendants would have if `obj`'s
+    /// parents were `parents`: the ones each object defines itself, followed by everything its
+    /// parents have, in parent order. A property reached through more than one parent (a diamond)
+    /// is inherited once; two different properties of the same name are a conflict.
+    /// Returns each affected object with its current and its rebuilt definitions.
+    fn rebuild_inherited_propdefs(
+        &self,
+        obj: Objid,
+        parents: &ObjSet,
+    ) -> Result<Vec<(Objid, PropDefs, PropDefs)>, WorldStateError> {
+        let affected: Vec<_> = std::iter::once(obj)
+            .chain(self.descendants(obj)?.iter())
+            .collect();
+        let affected_set: HashSet<_> = affected.iter().copied().collect();
+        for p in parents.iter() {
+            if affected_set.contains(&p) {
+                return Err(WorldStateError::RecursiveMove(obj, p));
+            }
+        }
+
+        let mut rebuilt: HashMap<Objid, PropDefs> = HashMap::new();
+        let mut order = vec![];
+        let mut pending: VecDeque<_> = affected.into();
+        while let Some(o) = pending.pop_front() {
+            let o_parents = if o == obj {
+                parents.clone()
+            } else {
+                self.get_object_parents(o)?
+            };
+            // Parents inside the affected tree have to be rebuilt before their children.
+            if o_parents
+                .iter()
+                .any(|p| affected_set.contains(&p) && !rebuilt.contains_key(&p))
+            {
+                pending.push_back(o);
+                continue;
+            }
+
+            let current = self.get_properties(o)?;
+            let own: Vec<_> = current.iter().filter(|p| p.definer() == o).collect();
+            let mut props = PropDefs::from_items(&own);
+            for parent in o_parents.iter() {
+                let inherited = match rebuilt.get(&parent) {
+                    Some(inherited) => inherited.clone(),
+                    None => self.get_properties(parent)?,
+                };
+                for p in inherited.iter() {
+                    if props.contains(p.uuid()) {
+                        continue;
+                    }
+                    if props.find_first_named(p.name()).is_some() {
+                        return Err(WorldStateError::DuplicatePropertyDefinition(
+                            o,
+                            p.name().to_string(),
+                        ));
+                    }
+                    props = props.with_added(p);
+                }
+            }
+            rebuilt.insert(o, props);
+            order.push((o, current));
+        }
+
+        Ok(order
+            .into_iter()
+            .map(|(o, current)| {
+                let props = rebuilt.remove(&o).expect("rebuilt every affected object");
+                (o, current, props)
+            })
+            .collect())
+    }
+
+    fn move_tuple(
+        &self,
+        rel: WorldStateRelation,
+        from: SliceRef,
+        to: SliceRef,
+    ) -> Result<(), WorldStateError> {
+        let relation = self.tx.relation(rel.into());
+        let value = match relation.seek_unique_by_domain(from.clone()) {
+            Ok(t) => t.codomain(),
+            Err(RelationError::TupleNotFound) => return Ok(()),
+            Err(e) => return Err(WorldStateError::DatabaseError(e.to_string())),
+        };
+        relation
+            .remove_by_domain(from)
+            .and_then(|_| relation.upsert_by_domain(to, value))
+            .map_err(|e| WorldStateError::DatabaseError(e.to_string()))
+    }
+
+    fn closest_common_ancestor_with_ancestors(
         &self,
         a: Objid,
         b: Objid,
@@ -1101,7 +1804,7 @@ impl Drop for RelBoxTransaction {
 impl Database for RelBoxWorldState {
     fn loader_client(self: Arc<Self>) -> Result<Rc<dyn LoaderInterface>, WorldStateError> {
         let tx = RelBoxTransaction::new(self.db.clone());
-        Ok(Rc::new(DbTxWorldState { tx: Box::new(tx) }))
+        Ok(Rc::new(DbTxWorldState::new(Box::new(tx))))
     }
 
     fn world_state_source(self: Arc<Self>) -> Result<Arc<dyn WorldStateSource>, WorldStateError> {
@@ -1114,19 +1817,24 @@ mod tests {
     use std::sync::Arc;
 
     use strum::{EnumCount, IntoEnumIterator};
+    use uuid::Uuid;
 
-    use moor_values::model::ObjAttrs;
     use moor_values::model::ObjSet;
     use moor_values::model::VerbArgsSpec;
+    use moor_values::model::WorldState;
     use moor_values::model::{BinaryType, VerbAttrs};
     use moor_values::model::{CommitResult, WorldStateError};
     use moor_values::model::{HasUuid, Named};
+    use moor_values::model::{ObjAttrs, ObjFlag};
+    use moor_values::model::{PropAttrs, PropFlag};
     use moor_values::util::BitEnum;
-    use moor_values::var::v_str;
     use moor_values::var::Objid;
+    use moor_values::var::{v_int, v_objid, v_str};
     use moor_values::NOTHING;
 
     use crate::db_tx::DbTransaction;
+    use crate::db_worldstate::DbTxWorldState;
+    use crate::odb::object_relations;
     use crate::odb::object_relations::{WorldStateRelation, WorldStateSequences};
     use crate::odb::rb_worldstate::RelBoxTransaction;
     use moor_rdb::{relation_info_for, RelBox, RelationInfo};
@@ -1168,6 +1876,43 @@ mod tests {
         assert_eq!(tx.get_object_owner(oid).unwrap(), NOTHING);
     }
 
+    #[test]
+    fn test_get_object_attrs() {
+        let db = test_db();
+        let tx = RelBoxTransaction::new(db);
+        let room = tx.create_object(None, ObjAttrs::default()).unwrap();
+        let parent = tx.create_object(None, ObjAttrs::default()).unwrap();
+        let oid = tx
+            .create_object(
+                None,
+                ObjAttrs {
+                    owner: Some(room),
+                    name: Some("test".into()),
+                    parent: Some(parent),
+                    location: Some(room),
+                    flags: Some(BitEnum::new_with(ObjFlag::Read) | ObjFlag::Write),
+                },
+            )
+            .unwrap();
+
+        for obj in [room, parent, oid] {
+            let attrs = tx.get_object_attrs(obj).unwrap();
+            assert_eq!(attrs.owner, Some(tx.get_object_owner(obj).unwrap()));
+            assert_eq!(attrs.name, Some(tx.get_object_name(obj).unwrap()));
+            assert_eq!(attrs.parent, Some(tx.get_object_parent(obj).unwrap()));
+            assert_eq!(attrs.location, Some(tx.get_object_location(obj).unwrap()));
+            assert_eq!(attrs.flags, Some(tx.get_object_flags(obj).unwrap()));
+        }
+        let attrs = tx.get_object_attrs(oid).unwrap();
+        assert_eq!(attrs.owner, Some(room));
+        assert_eq!(attrs.parent, Some(parent));
+
+        assert_eq!(
+            tx.get_object_attrs(Objid(100)).unwrap_err(),
+            WorldStateError::ObjectNotFound(Objid(100))
+        );
+    }
+
     #[test]
     fn test_create_object_fixed_id() {
         let db = test_db();
@@ -1274,6 +2019,564 @@ mod tests {
         assert_eq!(tx.commit(), Ok(CommitResult::Success));
     }
 
+    #[test]
+    fn test_multiple_parents() {
+        let db = test_db();
+        let tx = RelBoxTransaction::new(db);
+        let mk = |name: &str, parent| {
+            tx.create_object(
+                None,
+                ObjAttrs {
+                    owner: Some(NOTHING),
+                    name: Some(name.into()),
+                    parent: Some(parent),
+                    location: Some(NOTHING),
+                    flags: Some(BitEnum::new()),
+                },
+            )
+            .unwrap()
+        };
+
+        // A diamond: root <- {left, right} <- thing.
+        let root = mk("root", NOTHING);
+        let left = mk("left", root);
+        let right = mk("right", root);
+        let thing = mk("thing", left);
+        let shared = tx
+            .define_property(
+                root,
+                root,
+                "shared".into(),
+                NOTHING,
+                BitEnum::new(),
+                None,
+                None,
+            )
+            .unwrap();
+        tx.define_property(
+            right,
+            right,
+            "colour".into(),
+            NOTHING,
+            BitEnum::new(),
+            Some(v_str("blue")),
+            None,
+        )
+        .unwrap();
+        tx.add_object_verb(
+            right,
+            right,
+            vec!["paint".into()],
+            vec![],
+            BinaryType::LambdaMoo18X,
+            BitEnum::new(),
+            VerbArgsSpec::this_none_this(),
+            None,
+        )
+        .unwrap();
+        tx.add_object_verb(
+            root,
+            root,
+            vec!["look".into()],
+            vec![],
+            BinaryType::LambdaMoo18X,
+            BitEnum::new(),
+            VerbArgsSpec::this_none_this(),
+            None,
+        )
+        .unwrap();
+
+        tx.set_object_parents(thing, ObjSet::from(&[left, right]))
+            .unwrap();
+        assert_eq!(tx.get_object_parent(thing).unwrap(), left);
+        let in_order = |objs: ObjSet| objs.iter().collect::<Vec<_>>();
+        assert_eq!(
+            in_order(tx.get_object_parents(thing).unwrap()),
+            vec![left, right]
+        );
+        // Depth-first, left to right, with root only once: it's first reached through left, so
+        // it comes before right.
+        assert_eq!(
+            in_order(tx.ancestors(thing).unwrap()),
+            vec![thing, left, root, right]
+        );
+        assert!(tx
+            .descendants(right)
+            .unwrap()
+            .is_same(ObjSet::from(&[thing])));
+
+        // The verb and property only the second parent has are found through it.
+        assert_eq!(
+            tx.resolve_verb(thing, "paint".into(), None)
+                .unwrap()
+                .location(),
+            right
+        );
+        assert_eq!(
+            tx.resolve_verb(thing, "look".into(), None)
+                .unwrap()
+                .location(),
+            root
+        );
+        let (colour, value) = tx.resolve_property(thing, "colour".into()).unwrap();
+        assert_eq!(colour.definer(), right);
+        assert_eq!(value, v_str("blue"));
+
+        // The property defined on the shared root is inherited just the once.
+        let props = tx.get_properties(thing).unwrap();
+        assert_eq!(props.iter().filter(|p| p.uuid() == shared).count(), 1);
+
+        // Dropping the second parent drops what came from it, values included.
+        tx.set_property(thing, colour.uuid(), v_str("red")).unwrap();
+        tx.set_object_parents(thing, ObjSet::from(&[left])).unwrap();
+        assert!(tx
+            .ancestors(thing)
+            .unwrap()
+            .is_same(ObjSet::from(&[thing, left, root])));
+        assert!(tx.resolve_property(thing, "colour".into()).is_err());
+        assert!(tx.retrieve_property(thing, colour.uuid()).is_err());
+        assert!(tx.resolve_verb(thing, "paint".into(), None).is_err());
+        assert!(tx.get_properties(thing).unwrap().contains(shared));
+        assert_eq!(tx.commit(), Ok(CommitResult::Success));
+    }
+
+    #[test]
+    fn test_multiple_parents_conflict() {
+        let db = test_db();
+        let tx = RelBoxTransaction::new(db);
+        let mk = |name: &str, parent| {
+            tx.create_object(
+                None,
+                ObjAttrs {
+                    owner: Some(NOTHING),
+                    name: Some(name.into()),
+                    parent: Some(parent),
+                    location: Some(NOTHING),
+                    flags: Some(BitEnum::new()),
+                },
+            )
+            .unwrap()
+        };
+
+        // Two unrelated parents each defining their own "name" can't both be inherited.
+        let left = mk("left", NOTHING);
+        let right = mk("right", NOTHING);
+        let thing = mk("thing", left);
+        let child = mk("child", thing);
+        tx.define_property(
+            left,
+            left,
+            "name".into(),
+            NOTHING,
+            BitEnum::new(),
+            None,
+            None,
+        )
+        .unwrap();
+        tx.define_property(
+            right,
+            right,
+            "name".into(),
+            NOTHING,
+            BitEnum::new(),
+            None,
+            None,
+        )
+        .unwrap();
+        let before = tx.get_properties(child).unwrap();
+        assert_eq!(
+            tx.set_object_parents(thing, ObjSet::from(&[left, right])),
+            Err(WorldStateError::DuplicatePropertyDefinition(
+                thing,
+                "name".into()
+            ))
+        );
+        // ... and nothing changed.
+        assert!(tx
+            .get_object_parents(thing)
+            .unwrap()
+            .is_same(ObjSet::from(&[left])));
+        assert_eq!(tx.get_properties(child).unwrap(), before);
+
+        // Nor can an object become its own ancestor through an additional parent.
+        assert_eq!(
+            tx.set_object_parents(thing, ObjSet::from(&[left, child])),
+            Err(WorldStateError::RecursiveMove(thing, child))
+        );
+        assert_eq!(tx.commit(), Ok(CommitResult::Success));
+    }
+
+    #[test]
+    fn test_recycle_object() {
+        let db = test_db();
+        let tx = RelBoxTransaction::new(db.clone());
+
+        // p <- a <- {b, c}, with x inside a.
+        let mk = |name: &str, parent| {
+            tx.create_object(
+                None,
+                ObjAttrs {
+                    owner: Some(NOTHING),
+                    name: Some(name.into()),
+                    parent: Some(parent),
+                    location: Some(NOTHING),
+                    flags: Some(BitEnum::new()),
+                },
+            )
+            .unwrap()
+        };
+        let p = mk("p", NOTHING);
+        // a is deliberately sparse: no name, location, or verbs.
+        let a = tx
+            .create_object(
+                None,
+                ObjAttrs {
+                    parent: Some(p),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        let b = mk("b", a);
+        let c = mk("c", a);
+        let x = mk("x", NOTHING);
+        tx.set_object_location(x, a).unwrap();
+        tx.define_property(
+            p,
+            p,
+            "p_prop".into(),
+            NOTHING,
+            BitEnum::new(),
+            Some(v_str("p")),
+            None,
+        )
+        .unwrap();
+        tx.define_property(
+            a,
+            a,
+            "a_prop".into(),
+            NOTHING,
+            BitEnum::new(),
+            Some(v_str("a")),
+            None,
+        )
+        .unwrap();
+        assert_eq!(tx.commit(), Ok(CommitResult::Success));
+
+        let tx = RelBoxTransaction::new(db.clone());
+        tx.recycle_object(a).unwrap();
+        assert_eq!(tx.commit(), Ok(CommitResult::Success));
+
+        let tx = RelBoxTransaction::new(db);
+        assert!(!tx.object_valid(a).unwrap());
+
+        // a's children now belong to its parent, rather than being orphaned.
+        assert_eq!(tx.get_object_parent(b).unwrap(), p);
+        assert_eq!(tx.get_object_parent(c).unwrap(), p);
+        assert!(tx
+            .get_object_children(p)
+            .unwrap()
+            .is_same(ObjSet::from(&[b, c])));
+
+        // And its contents are out in the void.
+        assert_eq!(tx.get_object_location(x).unwrap(), NOTHING);
+
+        // The children keep what they inherit from p, but lose what a defined.
+        let (_, v) = tx.resolve_property(b, "p_prop".into()).unwrap();
+        assert_eq!(v, v_str("p"));
+        assert_eq!(
+            tx.resolve_property(b, "a_prop".into()).err().unwrap(),
+            WorldStateError::PropertyNotFound(b, "a_prop".into())
+        );
+        assert_eq!(tx.commit(), Ok(CommitResult::Success));
+    }
+
+    #[test]
+    fn test_players_and_max_object() {
+        let db = test_db();
+        let tx = RelBoxTransaction::new(db.clone());
+        let mk = |flags| {
+            tx.create_object(
+                None,
+                ObjAttrs {
+                    owner: Some(NOTHING),
+                    flags: Some(flags),
+                    ..Default::default()
+                },
+            )
+            .unwrap()
+        };
+        let thing = mk(BitEnum::new());
+        let alice = mk(BitEnum::new_with(ObjFlag::User));
+        let room = mk(BitEnum::new_with(ObjFlag::Read));
+        let bob = mk(BitEnum::new_with(ObjFlag::User) | ObjFlag::Wizard);
+        let last = mk(BitEnum::new());
+        assert_eq!(tx.commit(), Ok(CommitResult::Success));
+
+        let tx = RelBoxTransaction::new(db);
+        let players = tx.get_players().unwrap();
+        assert!(players.is_same(ObjSet::from(&[alice, bob])));
+        assert!(!players.contains(thing));
+        assert!(!players.contains(room));
+        assert_eq!(tx.get_max_object().unwrap(), last);
+
+        // Recycling doesn't give back the highest number.
+        tx.recycle_object(last).unwrap();
+        assert_eq!(tx.get_max_object().unwrap(), last);
+        assert_eq!(tx.commit(), Ok(CommitResult::Success));
+    }
+
+    #[test]
+    fn test_renumber_object() {
+        let db = test_db();
+        let tx = RelBoxTransaction::new(db.clone());
+
+        let mk = |name: &str, owner, parent, location| {
+            tx.create_object(
+                None,
+                ObjAttrs {
+                    owner: Some(owner),
+                    name: Some(name.into()),
+                    parent: Some(parent),
+                    location: Some(location),
+                    flags: Some(BitEnum::new()),
+                },
+            )
+            .unwrap()
+        };
+        let room = mk("room", NOTHING, NOTHING, NOTHING);
+        let gap = mk("gap", NOTHING, NOTHING, NOTHING);
+        let thing = mk("thing", NOTHING, NOTHING, room);
+        let kid = mk("kid", NOTHING, thing, NOTHING);
+        let inside = mk("inside", NOTHING, NOTHING, thing);
+        let owned = mk("owned", thing, NOTHING, NOTHING);
+        tx.define_property(
+            thing,
+            thing,
+            "ref".into(),
+            thing,
+            BitEnum::new(),
+            Some(v_objid(thing)),
+            None,
+        )
+        .unwrap();
+        tx.add_object_verb(
+            thing,
+            thing,
+            vec!["look".into()],
+            vec![1, 2, 3],
+            BinaryType::LambdaMoo18X,
+            BitEnum::new(),
+            VerbArgsSpec::this_none_this(),
+            None,
+        )
+        .unwrap();
+        tx.recycle_object(gap).unwrap();
+        assert_eq!(tx.commit(), Ok(CommitResult::Success));
+
+        let tx = RelBoxTransaction::new(db.clone());
+        let renumbered = tx.renumber_object(thing).unwrap();
+        assert_eq!(renumbered, gap);
+        assert_eq!(tx.commit(), Ok(CommitResult::Success));
+
+        let tx = RelBoxTransaction::new(db);
+        assert!(!tx.object_valid(thing).unwrap());
+        assert!(tx.object_valid(renumbered).unwrap());
+        assert_eq!(tx.get_object_name(renumbered).unwrap(), "thing");
+
+        // Location/contents and parent/children links follow the object to its new number...
+        assert_eq!(tx.get_object_location(renumbered).unwrap(), room);
+        assert!(tx
+            .get_object_contents(room)
+            .unwrap()
+            .is_same(ObjSet::from(&[renumbered])));
+        assert_eq!(tx.get_object_location(inside).unwrap(), renumbered);
+        assert!(tx
+            .get_object_contents(renumbered)
+            .unwrap()
+            .is_same(ObjSet::from(&[inside])));
+        assert_eq!(tx.get_object_parent(kid).unwrap(), renumbered);
+        assert!(tx
+            .get_object_children(renumbered)
+            .unwrap()
+            .is_same(ObjSet::from(&[kid])));
+
+        // ... as do ownership, and the verb and property definitions.
+        assert_eq!(tx.get_object_owner(owned).unwrap(), renumbered);
+        let verb = tx.get_verb_by_name(renumbered, "look".into()).unwrap();
+        assert_eq!(verb.location(), renumbered);
+        assert_eq!(verb.owner(), renumbered);
+        assert_eq!(
+            tx.get_verb_binary(renumbered, verb.uuid()).unwrap(),
+            vec![1, 2, 3]
+        );
+        let (prop, _) = tx.resolve_property(kid, "ref".into()).unwrap();
+        assert_eq!(prop.definer(), renumbered);
+        assert_eq!(prop.owner(), renumbered);
+
+        // But object numbers held in values are left as they were.
+        let (_, value) = tx.resolve_property(renumbered, "ref".into()).unwrap();
+        assert_eq!(value, v_objid(thing));
+
+        // With nothing free below it any more, it stays where it is.
+        assert_eq!(tx.renumber_object(renumbered).unwrap(), renumbered);
+        assert_eq!(tx.commit(), Ok(CommitResult::Success));
+    }
+
+    #[test]
+    fn test_verify_database() {
+        let db = test_db();
+        let tx = RelBoxTransaction::new(db.clone());
+        let mk = |parent, location| {
+            tx.create_object(
+                None,
+                ObjAttrs {
+                    owner: Some(NOTHING),
+                    parent: Some(parent),
+                    location: Some(location),
+                    flags: Some(BitEnum::new()),
+                    ..Default::default()
+                },
+            )
+            .unwrap()
+        };
+        let room = mk(NOTHING, NOTHING);
+        let thing = mk(NOTHING, room);
+        let kid = mk(thing, room);
+        tx.add_object_verb(
+            thing,
+            thing,
+            vec!["look".into(), "l".into()],
+            vec![1, 2, 3],
+            BinaryType::LambdaMoo18X,
+            BitEnum::new(),
+            VerbArgsSpec::this_none_this(),
+            None,
+        )
+        .unwrap();
+        assert_eq!(tx.verify_database().unwrap(), Vec::<String>::new());
+        assert_eq!(tx.commit(), Ok(CommitResult::Success));
+
+        // Now break things underneath the usual accessors.
+        let tx = RelBoxTransaction::new(db);
+        object_relations::upsert_object_object(
+            &tx.tx,
+            WorldStateRelation::ObjectParent,
+            kid,
+            Objid(999),
+        )
+        .unwrap();
+        object_relations::upsert_object_object(
+            &tx.tx,
+            WorldStateRelation::ObjectLocation,
+            thing,
+            Objid(998),
+        )
+        .unwrap();
+        let verb = tx.get_verbs(thing).unwrap().iter().next().unwrap();
+        tx.tx
+            .relation(WorldStateRelation::VerbProgram.into())
+            .remove_by_domain(object_relations::composite_key_for(thing, &verb.uuid()))
+            .unwrap();
+        tx.define_property(room, kid, "stray".into(), room, BitEnum::new(), None, None)
+            .unwrap();
+
+        let mut problems = tx.verify_database().unwrap();
+        problems.sort();
+        let mut expected = vec![
+            format!("{} has invalid parent #999", kid),
+            format!("{} is located in invalid object #998", thing),
+            format!("verb look l on {} has no program", thing),
+            format!(
+                "property stray on {} is defined by {}, which is not an ancestor",
+                kid, room
+            ),
+        ];
+        expected.sort();
+        assert_eq!(problems, expected);
+    }
+
+    #[test]
+    fn test_repair_database() {
+        let db = test_db();
+        let tx = RelBoxTransaction::new(db.clone());
+        let mk = |location| {
+            tx.create_object(
+                None,
+                ObjAttrs {
+                    owner: Some(NOTHING),
+                    parent: Some(NOTHING),
+                    location: Some(location),
+                    flags: Some(BitEnum::new()),
+                    ..Default::default()
+                },
+            )
+            .unwrap()
+        };
+        let room = mk(NOTHING);
+        let thing = mk(room);
+        assert_eq!(tx.commit(), Ok(CommitResult::Success));
+
+        // Put an object that doesn't exist into the room's contents, and give the thing a
+        // property it can't have inherited.
+        let tx = RelBoxTransaction::new(db.clone());
+        object_relations::upsert_object_object(
+            &tx.tx,
+            WorldStateRelation::ObjectLocation,
+            Objid(500),
+            room,
+        )
+        .unwrap();
+        tx.define_property(
+            room,
+            thing,
+            "stray".into(),
+            room,
+            BitEnum::new(),
+            None,
+            None,
+        )
+        .unwrap();
+        assert_eq!(tx.commit(), Ok(CommitResult::Success));
+
+        let mut expected = vec![
+            format!(
+                "{} contains invalid object #500: dropped the dangling entry",
+                room
+            ),
+            format!(
+                "property stray on {} is defined by {}, which is not an ancestor: dropped the property",
+                thing, room
+            ),
+        ];
+        expected.sort();
+
+        // A dry run reports the fixes but leaves everything alone.
+        let tx = RelBoxTransaction::new(db.clone());
+        let mut repairs = tx.repair_database(true).unwrap();
+        repairs.sort();
+        assert_eq!(repairs, expected);
+        assert_eq!(tx.verify_database().unwrap().len(), 2);
+        assert!(tx.get_object_contents(room).unwrap().contains(Objid(500)));
+
+        let mut repairs = tx.repair_database(false).unwrap();
+        repairs.sort();
+        assert_eq!(repairs, expected);
+        assert_eq!(tx.commit(), Ok(CommitResult::Success));
+
+        let tx = RelBoxTransaction::new(db);
+        assert_eq!(tx.verify_database().unwrap(), Vec::<String>::new());
+        assert_eq!(
+            tx.get_object_contents(room).unwrap(),
+            ObjSet::from(&[thing])
+        );
+        assert!(tx
+            .get_properties(thing)
+            .unwrap()
+            .find_first_named("stray")
+            .is_none());
+        assert_eq!(tx.repair_database(false).unwrap(), Vec::<String>::new());
+    }
+
     #[test]
     fn test_descendants() {
         let db = test_db();
@@ -1353,6 +2656,41 @@ mod tests {
         assert_eq!(tx.commit(), Ok(CommitResult::Success));
     }
 
+    /// Contents and children come back in object number order, whatever order the objects went
+    /// in, and the same on every lookup.
+    #[test]
+    fn test_codomain_lookups_sorted() {
+        let db = test_db();
+        let tx = RelBoxTransaction::new(db.clone());
+        let create = |parent, location| {
+            tx.create_object(
+                None,
+                ObjAttrs {
+                    owner: Some(NOTHING),
+                    name: Some("test".into()),
+                    parent: Some(parent),
+                    location: Some(location),
+                    flags: Some(BitEnum::new()),
+                },
+            )
+            .unwrap()
+        };
+        let room = create(NOTHING, NOTHING);
+        let things: Vec<_> = (0..20).map(|_| create(room, NOTHING)).collect();
+        for thing in things.iter().rev() {
+            tx.set_object_location(*thing, room).unwrap();
+        }
+        assert_eq!(tx.commit(), Ok(CommitResult::Success));
+
+        let expected = ObjSet::from(&things);
+        for _ in 0..10 {
+            let tx = RelBoxTransaction::new(db.clone());
+            assert_eq!(tx.get_object_contents(room).unwrap(), expected);
+            assert_eq!(tx.get_object_children(room).unwrap(), expected);
+            assert_eq!(tx.commit(), Ok(CommitResult::Success));
+        }
+    }
+
     #[test]
     fn test_location_contents() {
         let db = test_db();
@@ -1568,6 +2906,7 @@ mod tests {
             NOTHING,
             BitEnum::new(),
             Some(v_str("test")),
+            None,
         )
         .unwrap();
         let (prop, v) = tx.resolve_property(oid, "test".into()).unwrap();
@@ -1576,6 +2915,79 @@ mod tests {
         assert_eq!(tx.commit(), Ok(CommitResult::Success));
     }
 
+    #[test]
+    fn test_batch_property_definition_update() {
+        let db = test_db();
+        let tx = RelBoxTransaction::new(db);
+
+        let oid = tx
+            .create_object(
+                None,
+                ObjAttrs {
+                    owner: Some(NOTHING),
+                    name: Some("test".into()),
+                    parent: Some(NOTHING),
+                    location: Some(NOTHING),
+                    flags: Some(BitEnum::new()),
+                },
+            )
+            .unwrap();
+        let a = tx
+            .define_property(oid, oid, "a".into(), NOTHING, BitEnum::new(), None, None)
+            .unwrap();
+        let b = tx
+            .define_property(oid, oid, "b".into(), NOTHING, BitEnum::new(), None, None)
+            .unwrap();
+        let rename = |name: &str| PropAttrs {
+            name: Some(name.into()),
+            ..PropAttrs::default()
+        };
+
+        // One bad uuid spoils the whole batch.
+        let result = tx.update_property_definitions(
+            oid,
+            vec![
+                (a, rename("a2")),
+                (Uuid::new_v4(), rename("nope")),
+                (b, rename("b2")),
+            ],
+        );
+        assert!(matches!(
+            result,
+            Err(WorldStateError::PropertyNotFound(o, _)) if o == oid
+        ));
+        let names: Vec<_> = tx
+            .get_properties(oid)
+            .unwrap()
+            .iter()
+            .map(|p| p.name().to_string())
+            .collect();
+        assert_eq!(names, vec!["a", "b"]);
+
+        tx.update_property_definitions(
+            oid,
+            vec![
+                (a, rename("a2")),
+                (
+                    b,
+                    PropAttrs {
+                        owner: Some(oid),
+                        flags: Some(BitEnum::new_with(PropFlag::Read)),
+                        ..rename("b2")
+                    },
+                ),
+            ],
+        )
+        .unwrap();
+        let props = tx.get_properties(oid).unwrap();
+        assert_eq!(props.find(&a).unwrap().name(), "a2");
+        let pb = props.find(&b).unwrap();
+        assert_eq!(pb.name(), "b2");
+        assert_eq!(pb.owner(), oid);
+        assert_eq!(pb.flags(), BitEnum::new_with(PropFlag::Read));
+        assert_eq!(tx.commit(), Ok(CommitResult::Success));
+    }
+
     /// Regression test for updating-verbs failing.
     #[test]
     fn test_verb_add_update() {
@@ -1601,6 +3013,7 @@ mod tests {
             BinaryType::LambdaMoo18X,
             BitEnum::new(),
             VerbArgsSpec::this_none_this(),
+            None,
         )
         .unwrap();
         // resolve the verb to its vh.
@@ -1675,6 +3088,7 @@ mod tests {
             NOTHING,
             BitEnum::new(),
             Some(v_str("test_value")),
+            None,
         )
         .unwrap();
         let (prop, v) = tx.resolve_property(b, "test".into()).unwrap();
@@ -1706,6 +3120,60 @@ mod tests {
         assert_eq!(tx.commit(), Ok(CommitResult::Success));
     }
 
+    #[test]
+    fn test_property_resolution_cache() {
+        let db = test_db();
+        let mut ws = DbTxWorldState::new(Box::new(RelBoxTransaction::new(db)));
+        let mk = |ws: &DbTxWorldState, parent, flags| {
+            ws.tx
+                .create_object(
+                    None,
+                    ObjAttrs {
+                        owner: Some(NOTHING),
+                        name: None,
+                        parent: Some(parent),
+                        location: Some(NOTHING),
+                        flags: Some(flags),
+                    },
+                )
+                .unwrap()
+        };
+        let wizard = mk(&ws, NOTHING, BitEnum::new_with(ObjFlag::Wizard));
+        let a = mk(&ws, NOTHING, BitEnum::new());
+        let b = mk(&ws, a, BitEnum::new());
+        let uuid = ws
+            .tx
+            .define_property(
+                a,
+                a,
+                "p".into(),
+                wizard,
+                BitEnum::new(),
+                Some(v_int(1)),
+                None,
+            )
+            .unwrap();
+
+        assert_eq!(ws.retrieve_property(wizard, b, "p").unwrap(), v_int(1));
+
+        // Change the value underneath the world state: the cached resolution is what comes back,
+        // so the second read never went to the transaction.
+        ws.tx.set_property(a, uuid, v_int(2)).unwrap();
+        assert_eq!(ws.retrieve_property(wizard, b, "p").unwrap(), v_int(1));
+
+        // Writes through the world state drop the cached entries of the object's descendants.
+        ws.update_property(wizard, a, "p", &v_int(3)).unwrap();
+        assert_eq!(ws.retrieve_property(wizard, b, "p").unwrap(), v_int(3));
+
+        ws.change_parent(wizard, b, NOTHING).unwrap();
+        assert!(matches!(
+            ws.retrieve_property(wizard, b, "p"),
+            Err(WorldStateError::PropertyNotFound(_, _))
+        ));
+        assert_eq!(ws.retrieve_property(wizard, a, "p").unwrap(), v_int(3));
+        assert_eq!(ws.commit(), Ok(CommitResult::Success));
+    }
+
     #[test]
     fn test_transitive_property_resolution_clear_property() {
         let db = test_db();
@@ -1744,6 +3212,7 @@ mod tests {
             NOTHING,
             BitEnum::new(),
             Some(v_str("test_value")),
+            None,
         )
         .unwrap();
         let (prop, v) = tx.resolve_property(b, "test".into()).unwrap();
@@ -1754,7 +3223,7 @@ mod tests {
         // This should raise an error because the child already *has* this property.
         // MOO will not let this happen. The right way to handle overloading is to set the value
         // on the child.
-        let result = tx.define_property(a, b, "test".into(), NOTHING, BitEnum::new(), None);
+        let result = tx.define_property(a, b, "test".into(), NOTHING, BitEnum::new(), None, None);
         assert!(matches!(
             result,
             Err(WorldStateError::DuplicatePropertyDefinition(_, _))
@@ -1788,6 +3257,7 @@ mod tests {
             BinaryType::LambdaMoo18X,
             BitEnum::new(),
             VerbArgsSpec::this_none_this(),
+            None,
         )
         .unwrap();
 
@@ -1815,6 +3285,7 @@ mod tests {
             BinaryType::LambdaMoo18X,
             BitEnum::new(),
             VerbArgsSpec::this_none_this(),
+            None,
         )
         .unwrap();
 
@@ -1877,6 +3348,7 @@ mod tests {
             BinaryType::LambdaMoo18X,
             BitEnum::new(),
             VerbArgsSpec::this_none_this(),
+            None,
         )
         .unwrap();
 
@@ -1923,6 +3395,7 @@ mod tests {
             BinaryType::LambdaMoo18X,
             BitEnum::new(),
             VerbArgsSpec::this_none_this(),
+            None,
         )
         .unwrap();
 
@@ -1947,4 +3420,63 @@ mod tests {
         );
         assert_eq!(tx.commit(), Ok(CommitResult::Success));
     }
+
+    // Each of the star forms, through resolution: a trailing star matches anything starting with
+    // what's before it, an inner one any prefix at least that long, and a bare star anything at
+    // all. Names that don't match fall through to the parent, whatever their case.
+    #[test]
+    fn test_verb_resolve_star_forms() {
+        let db = test_db();
+        let tx = RelBoxTransaction::new(db);
+        let create = |parent| {
+            tx.create_object(
+                None,
+                ObjAttrs {
+                    owner: Some(NOTHING),
+                    name: Some("test".into()),
+                    parent: Some(parent),
+                    location: Some(NOTHING),
+                    flags: Some(BitEnum::new()),
+                },
+            )
+            .unwrap()
+        };
+        let add_verb = |obj, name: &str| {
+            tx.add_object_verb(
+                obj,
+                obj,
+                vec![name.to_string()],
+                vec![],
+                BinaryType::LambdaMoo18X,
+                BitEnum::new(),
+                VerbArgsSpec::this_none_this(),
+                None,
+            )
+            .unwrap();
+        };
+        let parent = create(NOTHING);
+        let child = create(parent);
+        add_verb(parent, "*");
+        add_verb(child, "get*");
+        add_verb(child, "l*ook");
+
+        let resolves_to = |name: &str| {
+            tx.resolve_verb(child, name.into(), None)
+                .unwrap()
+                .names()
+                .iter()
+                .map(|n| n.to_string())
+                .collect::<Vec<_>>()
+        };
+        for name in ["get", "GETAWAY", "getter"] {
+            assert_eq!(resolves_to(name), vec!["get*"]);
+        }
+        for name in ["l", "lo", "LOOK"] {
+            assert_eq!(resolves_to(name), vec!["l*ook"]);
+        }
+        for name in ["ge", "looking", "anything"] {
+            assert_eq!(resolves_to(name), vec!["*"]);
+        }
+        assert_eq!(tx.commit(), Ok(CommitResult::Success));
+    }
 }
@@ -68,6 +68,7 @@ mod test {
                 BinaryType::LambdaMoo18X,
                 BitEnum::new(),
                 VerbArgsSpec::this_none_this(),
+                None,
             )
             .unwrap();
 
@@ -0,0 +1,119 @@
+// Copyright (C) 2024 Ryan Daum <ryan.daum@gmail.com>
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU General Public License as published by the Free Software
+// Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// this program. If not, see <https://www.gnu.org/licenses/>.
+//
+
+//! Benchmarks of the scheduler's task bookkeeping: how quickly it can spawn and retire tasks
+//! submitted from many threads at once. The verb being run is trivial, so that the cost being
+//! measured is the scheduler's rather than the VM's.
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use criterion::{criterion_group, criterion_main, Criterion};
+
+use moor_compiler::compile;
+use moor_db::odb::RelBoxWorldState;
+use moor_kernel::config::Config;
+use moor_kernel::tasks::scheduler::Scheduler;
+use moor_kernel::tasks::sessions::NoopClientSession;
+use moor_values::model::{BinaryType, VerbArgsSpec, VerbFlag};
+use moor_values::model::{CommitResult, WorldStateSource};
+use moor_values::util::BitEnum;
+use moor_values::{AsByteBuffer, NOTHING, SYSTEM_OBJECT};
+
+fn create_db() -> Arc<RelBoxWorldState> {
+    let (db, _) = RelBoxWorldState::open(None, 1 << 24);
+    let mut tx = db.new_world_state().unwrap();
+    let sysobj = tx
+        .create_object(SYSTEM_OBJECT, NOTHING, SYSTEM_OBJECT, BitEnum::all())
+        .unwrap();
+    tx.add_verb(
+        SYSTEM_OBJECT,
+        sysobj,
+        vec!["noop".to_string()],
+        sysobj,
+        VerbFlag::rxd(),
+        VerbArgsSpec::this_none_this(),
+        compile("return 1;").unwrap().make_copy_as_vec().unwrap(),
+        BinaryType::LambdaMoo18X,
+    )
+    .unwrap();
+    assert_eq!(tx.commit().unwrap(), CommitResult::Success);
+    Arc::new(db)
+}
+
+/// Submit `per_thread` tasks from each of `threads` threads, and wait for all of them to finish.
+fn spawn_tasks(scheduler: &Arc<Scheduler>, threads: usize, per_thread: usize) -> Duration {
+    let start = Instant::now();
+    let spawners: Vec<_> = (0..threads)
+        .map(|_| {
+            let scheduler = scheduler.clone();
+            std::thread::spawn(move || {
+                let task_ids: Vec<_> = (0..per_thread)
+                    .map(|_| {
+                        scheduler
+                            .submit_verb_task(
+                                SYSTEM_OBJECT,
+                                SYSTEM_OBJECT,
+                                "noop".to_string(),
+                                vec![],
+                                "".to_string(),
+                                SYSTEM_OBJECT,
+                                Arc::new(NoopClientSession::new()),
+                            )
+                            .unwrap()
+                    })
+                    .collect();
+                // A task that's already gone drops our subscription straight away, so this
+                // returns once every task has completed either way.
+                for task_id in task_ids {
+                    let _ = scheduler.subscribe_to_task(task_id).unwrap().recv();
+                }
+            })
+        })
+        .collect();
+    for spawner in spawners {
+        spawner.join().unwrap();
+    }
+    start.elapsed()
+}
+
+fn spawn_throughput(c: &mut Criterion) {
+    let scheduler = Arc::new(Scheduler::new(create_db(), Config::default()));
+    let loop_scheduler = scheduler.clone();
+    std::thread::spawn(move || loop_scheduler.run());
+
+    let mut group = c.benchmark_group("spawn_throughput");
+    group.sample_size(20);
+    group.measurement_time(Duration::from_secs(10));
+
+    let per_thread = 50;
+    for threads in [1, 4, 16] {
+        group.throughput(criterion::Throughput::Elements(
+            (threads * per_thread) as u64,
+        ));
+        group.bench_function(format!("{threads}_threads"), |b| {
+            b.iter_custom(|iters| {
+                (0..iters)
+                    .map(|_| spawn_tasks(&scheduler, threads, per_thread))
+                    .sum()
+            });
+        });
+    }
+    group.finish();
+
+    scheduler.stop().unwrap();
+}
+
+criterion_group!(benches, spawn_throughput);
+criterion_main!(benches);
@@ -23,8 +23,11 @@ use criterion::{criterion_group, criterion_main, Criterion};
 
 use moor_compiler::compile;
 use moor_db::odb::RelBoxWorldState;
+use moor_kernel::config::TaskLimits;
+use moor_kernel::tasks::program_cache::ProgramCache;
 use moor_kernel::tasks::scheduler::AbortLimitReason;
 use moor_kernel::tasks::sessions::{NoopClientSession, Session};
+use moor_kernel::tasks::verb_metrics::VerbMetrics;
 use moor_kernel::tasks::vm_host::{VMHostResponse, VmHost};
 use moor_kernel::tasks::VerbCall;
 use moor_values::model::CommitResult;
@@ -55,11 +58,15 @@ pub fn prepare_call_verb(
     let (scs_tx, _scs_rx) = kanal::unbounded();
     let mut vm_host = VmHost::new(
         0,
-        20,
-        max_ticks,
-        Duration::from_secs(15),
+        TaskLimits {
+            max_ticks,
+            max_seconds: 15,
+            max_stack_depth: 20,
+        },
         session.clone(),
         scs_tx,
+        Arc::new(VerbMetrics::default()),
+        Arc::new(ProgramCache::default()),
     );
 
     let vi = world_state
@@ -135,7 +142,7 @@ fn execute(world_state: &mut dyn WorldState, vm_host: &mut VmHost) -> bool {
             VMHostResponse::Suspend(_) => {
                 panic!("Unexpected suspend");
             }
-            VMHostResponse::SuspendNeedInput => {
+            VMHostResponse::SuspendNeedInput(_) => {
                 panic!("Unexpected suspend need input");
             }
             VMHostResponse::CompleteAbort => {
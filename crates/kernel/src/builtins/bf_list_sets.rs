@@ -268,14 +268,27 @@ fn perform_regex_match(
         return Ok(None);
     };
 
-    let overall = ((start + 1) as isize, end as isize);
+    // The regex engine gives byte offsets, but MOO string positions count characters, as with
+    // index() and ranges.
+    let ascii = subject.is_ascii();
+    let chars_before = |i: usize| -> isize {
+        if ascii {
+            i as isize
+        } else {
+            subject[..i].chars().count() as isize
+        }
+    };
+    let span =
+        |(start, end): (usize, usize)| -> Span { (chars_before(start) + 1, chars_before(end)) };
+
+    let overall = span((start, end));
     // Now we'll iterate through the captures, and build up a Vec<Span> of the captured groups.
     // MOO match() returns 9 subpatterns, no more, no less. So we start with a Vec of 9
     // (-1, -1) pairs and then fill that in with the captured groups, if any.
     let mut match_vec = vec![(0, -1); 9];
     for i in 1..=8 {
-        if let Some((start, end)) = region.pos(i) {
-            match_vec[i - 1] = ((start + 1) as isize, end as isize);
+        if let Some(pos) = region.pos(i) {
+            match_vec[i - 1] = span(pos);
         }
     }
 
@@ -333,7 +346,8 @@ bf_declare!(rmatch, bf_rmatch);
 
 fn substitute(template: &str, subs: &[(isize, isize)], source: &str) -> Result<String, Error> {
     // textual patterns of form %<int> (e.g. %1, %9, %11) are replaced by the text matched by the
-    // offsets (1-indexed) into source given by the corresponding value in `subs`.
+    // offsets (1-indexed, in characters) into source given by the corresponding value in `subs`.
+    let source_len = source.chars().count() as isize;
 
     // We'll append to this result.
     let mut result = String::new();
@@ -381,13 +395,13 @@ fn substitute(template: &str, subs: &[(isize, isize)], source: &str) -> Result<S
         let (start, end) = (subs[number].0, subs[number].1);
 
         // Now validate the range in the source string, and raise an E_INVARG if it's invalid.
-        if start < 0 || start > end || end > (source.len() as isize) {
+        if start < 1 || start > end || end > source_len {
             return Err(E_INVARG);
         }
 
         let (start, end) = (start as usize - 1, end as usize);
         // Now append the corresponding substring to `result`.
-        result.push_str(&source[start..end]);
+        result.extend(source.chars().skip(start).take(end - start));
         if let Some(last_c) = last_c {
             result.push(last_c);
         }
@@ -520,6 +534,23 @@ mod tests {
         assert_eq!(result, "edit");
     }
 
+    /// Positions count characters, not bytes, so they agree with index() and string ranges.
+    #[test]
+    fn test_match_substitute_multibyte() {
+        let source = "héllo wörld";
+        let (overall, subs) = perform_regex_match("w%(ö%)r", source, false, false)
+            .unwrap()
+            .unwrap();
+        assert_eq!(overall, (7, 9));
+        assert_eq!(subs[0], (8, 8));
+        assert_eq!(substitute("[%1]", &subs, source).unwrap(), "[ö]");
+
+        let (overall, _) = perform_regex_match("l", source, false, true)
+            .unwrap()
+            .unwrap();
+        assert_eq!(overall, (10, 10));
+    }
+
     #[test]
     fn test_match_regression() {
         let source = "2";
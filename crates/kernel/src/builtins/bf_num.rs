@@ -87,30 +87,47 @@ fn bf_random(bf_args: &mut BfCallState<'_>) -> Result<BfRet, Error> {
 }
 bf_declare!(random, bf_random);
 
+/// Maximum number of digits `floatstr` will render after the decimal point. Matches LambdaMOO's
+/// `DBL_DIG + 4` clamp.
+const MAX_FLOATSTR_PRECISION: usize = 19;
+
+/// Render `x` in C `%.*e` style (e.g. `1.23e+03`), which is what LambdaMOO cores expect, rather
+/// than Rust's native `1.23e3`.
+fn format_scientific(x: f64, precision: usize) -> String {
+    let s = format!("{:.*e}", precision, x);
+    let Some((mantissa, exponent)) = s.split_once('e') else {
+        return s;
+    };
+    let (sign, digits) = match exponent.strip_prefix('-') {
+        Some(digits) => ('-', digits),
+        None => ('+', exponent),
+    };
+    format!("{mantissa}e{sign}{digits:0>2}")
+}
+
 fn bf_floatstr(bf_args: &mut BfCallState<'_>) -> Result<BfRet, Error> {
     if bf_args.args.len() < 2 || bf_args.args.len() > 3 {
         return Err(E_INVARG);
     }
 
     let x = match bf_args.args[0].variant() {
-        Variant::Float(f) => f,
+        Variant::Float(f) => *f,
         _ => return Err(E_TYPE),
     };
 
     let precision = match bf_args.args[1].variant() {
-        Variant::Int(i) if *i > 0 => *i as usize,
+        Variant::Int(i) if *i < 0 => return Err(E_INVARG),
+        Variant::Int(i) => (*i as usize).min(MAX_FLOATSTR_PRECISION),
         _ => return Err(E_TYPE),
     };
 
-    let scientific = match bf_args.args[2].variant() {
-        Variant::Int(b) => *b == 1,
-        _ => return Err(E_TYPE),
-    };
+    let scientific = bf_args.args.len() == 3 && bf_args.args[2].is_true();
 
-    let mut s = format!("{:.*}", precision, x);
-    if scientific {
-        s = format!("{:e}", x);
-    }
+    let s = if scientific {
+        format_scientific(x, precision)
+    } else {
+        format!("{:.*}", precision, x)
+    };
 
     Ok(Ret(v_str(s.as_str())))
 }
@@ -16,18 +16,21 @@ use std::sync::Arc;
 
 use tracing::{debug, error, trace};
 
-use moor_values::model::ObjFlag;
 use moor_values::model::{world_state_err, WorldStateError};
+use moor_values::model::{ObjFlag, ObjSet, VerbInfo};
 use moor_values::util::BitEnum;
 use moor_values::var::Error::{E_INVARG, E_NACC, E_TYPE};
-use moor_values::var::Variant;
 use moor_values::var::{v_bool, v_int, v_none, v_objid, v_str};
 use moor_values::var::{v_listv, Error};
-use moor_values::NOTHING;
+use moor_values::var::{Objid, Variant};
+use moor_values::{NOTHING, SYSTEM_OBJECT};
 
 use crate::bf_declare;
 use crate::builtins::BfRet::{Ret, VmInstr};
 use crate::builtins::{BfCallState, BfRet, BuiltinFunction};
+use crate::matching::match_env::{match_in, MatchConstants, MatchEnvironmentParseMatcher};
+use crate::matching::ws_match_env::WsMatchEnv;
+use crate::tasks::command_parse::ParseMatcher;
 use crate::tasks::VerbCall;
 use crate::vm::ExecutionResult::ContinueVerb;
 use crate::vm::VM;
@@ -86,6 +89,74 @@ fn bf_chparent(bf_args: &mut BfCallState<'_>) -> Result<BfRet, Error> {
 }
 bf_declare!(chparent, bf_chparent);
 
+fn bf_parents(bf_args: &mut BfCallState<'_>) -> Result<BfRet, Error> {
+    if bf_args.args.len() != 1 {
+        return Err(E_INVARG);
+    }
+    let Variant::Obj(obj) = bf_args.args[0].variant() else {
+        return Err(E_TYPE);
+    };
+    if obj.0 < 0 {
+        return Err(E_INVARG);
+    }
+    let parents = bf_args
+        .world_state
+        .parents_of(bf_args.task_perms_who(), *obj)
+        .map_err(world_state_err)?;
+    let parents = parents.iter().map(v_objid).collect::<Vec<_>>();
+    Ok(Ret(v_listv(parents)))
+}
+bf_declare!(parents, bf_parents);
+
+fn bf_chparents(bf_args: &mut BfCallState<'_>) -> Result<BfRet, Error> {
+    if bf_args.args.len() != 2 {
+        return Err(E_INVARG);
+    }
+    let Variant::Obj(obj) = bf_args.args[0].variant() else {
+        return Err(E_TYPE);
+    };
+    let Variant::List(new_parents) = bf_args.args[1].variant() else {
+        return Err(E_TYPE);
+    };
+    let mut parents = vec![];
+    for p in new_parents.iter() {
+        let Variant::Obj(p) = p.variant() else {
+            return Err(E_TYPE);
+        };
+        if parents.contains(p) {
+            return Err(E_INVARG);
+        }
+        parents.push(*p);
+    }
+    bf_args
+        .world_state
+        .change_parents(bf_args.task_perms_who(), *obj, &ObjSet::from(&parents))
+        .map_err(world_state_err)?;
+    Ok(Ret(v_none()))
+}
+bf_declare!(chparents, bf_chparents);
+
+fn bf_isa(bf_args: &mut BfCallState<'_>) -> Result<BfRet, Error> {
+    if bf_args.args.len() != 2 {
+        return Err(E_INVARG);
+    }
+    let Variant::Obj(obj) = bf_args.args[0].variant() else {
+        return Err(E_TYPE);
+    };
+    let Variant::Obj(ancestor) = bf_args.args[1].variant() else {
+        return Err(E_TYPE);
+    };
+    if !bf_args.world_state.valid(*obj).map_err(world_state_err)? {
+        return Err(E_INVARG);
+    }
+    let is_a = bf_args
+        .world_state
+        .is_a(*obj, *ancestor)
+        .map_err(world_state_err)?;
+    Ok(Ret(v_bool(is_a)))
+}
+bf_declare!(isa, bf_isa);
+
 fn bf_children(bf_args: &mut BfCallState<'_>) -> Result<BfRet, Error> {
     if bf_args.args.len() != 1 {
         return Err(E_INVARG);
@@ -179,15 +250,85 @@ fn bf_create(bf_args: &mut BfCallState<'_>) -> Result<BfRet, Error> {
     }
 }
 bf_declare!(create, bf_create);
+
+/// Wizard-only: creates an object numbered `id`, for tooling that has to recreate specific objects
+/// (e.g. when migrating a core). Raises E_INVARG if `id` is already in use. Unlike create(),
+/// :initialize is not called; the caller is expected to set the object up itself.
+fn bf_create_with_id(bf_args: &mut BfCallState<'_>) -> Result<BfRet, Error> {
+    if bf_args.args.len() < 2 || bf_args.args.len() > 3 {
+        return Err(E_INVARG);
+    }
+    let Variant::Obj(id) = bf_args.args[0].variant() else {
+        return Err(E_TYPE);
+    };
+    let Variant::Obj(parent) = bf_args.args[1].variant() else {
+        return Err(E_TYPE);
+    };
+    let owner = if bf_args.args.len() == 3 {
+        let Variant::Obj(owner) = bf_args.args[2].variant() else {
+            return Err(E_TYPE);
+        };
+        *owner
+    } else {
+        bf_args.task_perms_who()
+    };
+    if id.0 < 0 {
+        return Err(E_INVARG);
+    }
+    let new_obj = bf_args
+        .world_state
+        .create_object_with_id(
+            bf_args.task_perms_who(),
+            *id,
+            *parent,
+            owner,
+            BitEnum::new(),
+        )
+        .map_err(world_state_err)?;
+    Ok(Ret(v_objid(new_obj)))
+}
+bf_declare!(create_with_id, bf_create_with_id);
 /*
 Function: none recycle (obj object)
 The given object is destroyed, irrevocably. The programmer must either own object or be a wizard; otherwise, E_PERM is raised. If object is not valid, then E_INVARG is raised. The children of object are reparented to the parent of object. Before object is recycled, each object in its contents is moved to #-1 (implying a call to object's exitfunc verb, if any) and then object's `recycle' verb, if any, is called with no arguments.
+
+In moor, if #0.recycler is an object with a `_recycle' verb, object is instead passed to that
+verb, which is then responsible for it, unless it's that verb calling recycle().
  */
 // This is invoked with a list of objects to move/call :exitfunc on. When the list is empty, the
 // next trampoline is called, to do the actual recycle.
 const BF_RECYCLE_TRAMPOLINE_CALL_EXITFUNC: usize = 0;
 // Do the recycle.
 const BF_RECYCLE_TRAMPOLINE_DONE_MOVE: usize = 1;
+// The object was handed to $recycler, which is now responsible for it.
+const BF_RECYCLE_TRAMPOLINE_HANDED_OFF: usize = 2;
+
+/// If the core has a `$recycler` with a `_recycle` verb, that verb is given objects to recycle in
+/// place of the server freeing them, so that it can (say) keep them for reuse. Not when it's the
+/// recycler itself calling `recycle()`, though, which is how it frees things for real.
+fn recycler_verb(bf_args: &BfCallState<'_>, obj: Objid) -> Option<(Objid, VerbInfo)> {
+    let perms = bf_args.task_perms_who();
+    let recycler = bf_args
+        .world_state
+        .retrieve_property(perms, SYSTEM_OBJECT, "recycler")
+        .ok()?;
+    let Variant::Obj(recycler) = recycler.variant() else {
+        return None;
+    };
+    let recycler = *recycler;
+    if recycler == obj || bf_args.exec_state.caller() == recycler {
+        return None;
+    }
+    if !bf_args.world_state.valid(recycler).ok()? {
+        return None;
+    }
+    let verb = bf_args
+        .world_state
+        .find_method_verb_on(perms, recycler, "_recycle")
+        .ok()?;
+    Some((recycler, verb))
+}
+
 fn bf_recycle(bf_args: &mut BfCallState<'_>) -> Result<BfRet, Error> {
     if bf_args.args.len() != 1 {
         return Err(E_INVARG);
@@ -195,6 +336,25 @@ fn bf_recycle(bf_args: &mut BfCallState<'_>) -> Result<BfRet, Error> {
     let Variant::Obj(obj) = bf_args.args[0].variant() else {
         return Err(E_TYPE);
     };
+    // Whatever the recycler did with the object, it's done with it, and may well have freed it.
+    if bf_args.exec_state.top().bf_trampoline == Some(BF_RECYCLE_TRAMPOLINE_HANDED_OFF) {
+        return Ok(Ret(v_none()));
+    }
+    if !bf_args.world_state.valid(*obj).map_err(world_state_err)? {
+        return Err(E_INVARG);
+    }
+
+    // Check permissions before running any of the object's verbs, rather than waiting for
+    // `recycle_object` to refuse at the very end.
+    let owner = bf_args
+        .world_state
+        .owner_of(*obj)
+        .map_err(world_state_err)?;
+    bf_args
+        .task_perms()
+        .map_err(world_state_err)?
+        .check_obj_owner_perms(owner)
+        .map_err(world_state_err)?;
 
     // Before actually recycling the object, we need to move all its contents to #-1. While
     // `recycle_object` will actually do this, we need to make sure :exitfunc is called on each
@@ -204,6 +364,25 @@ fn bf_recycle(bf_args: &mut BfCallState<'_>) -> Result<BfRet, Error> {
         let tramp = bf_args.exec_state.top().bf_trampoline;
         match tramp {
             None => {
+                if let Some((recycler, dispatch)) = recycler_verb(bf_args, *obj) {
+                    return Ok(VmInstr(ContinueVerb {
+                        permissions: bf_args.task_perms_who(),
+                        resolved_verb: dispatch,
+                        call: VerbCall {
+                            verb_name: "_recycle".to_string(),
+                            location: recycler,
+                            this: recycler,
+                            player: bf_args.exec_state.top().player,
+                            args: vec![v_objid(*obj)],
+                            argstr: "".to_string(),
+                            caller: bf_args.exec_state.caller(),
+                        },
+                        trampoline: Some(BF_RECYCLE_TRAMPOLINE_HANDED_OFF),
+                        trampoline_arg: None,
+                        command: None,
+                    }));
+                }
+
                 // Starting out, we need to call "recycle" on the object, if it exists.
                 // The next point in the trampoline is CALL_EXITFUNC and it will expect a list of
                 // objects to move/call :exitfunc on. So let's get the initial list of objects
@@ -346,6 +525,39 @@ fn bf_max_object(bf_args: &mut BfCallState<'_>) -> Result<BfRet, Error> {
 }
 bf_declare!(max_object, bf_max_object);
 
+fn bf_reset_max_object(bf_args: &mut BfCallState<'_>) -> Result<BfRet, Error> {
+    if !bf_args.args.is_empty() {
+        return Err(E_INVARG);
+    }
+    bf_args
+        .world_state
+        .reset_max_object(bf_args.task_perms_who())
+        .map_err(world_state_err)?;
+    Ok(Ret(v_none()))
+}
+bf_declare!(reset_max_object, bf_reset_max_object);
+
+/// Moves an object to the lowest free object number below its current one. Only the object's own
+/// links (parent/children, location/contents, ownership) are rewritten; object numbers stored in
+/// property values or verb code still refer to the old number, as in LambdaMOO.
+fn bf_renumber(bf_args: &mut BfCallState<'_>) -> Result<BfRet, Error> {
+    if bf_args.args.len() != 1 {
+        return Err(E_INVARG);
+    }
+    let Variant::Obj(obj) = bf_args.args[0].variant() else {
+        return Err(E_TYPE);
+    };
+    if !bf_args.world_state.valid(*obj).map_err(world_state_err)? {
+        return Err(E_INVARG);
+    }
+    let new_obj = bf_args
+        .world_state
+        .renumber_object(bf_args.task_perms_who(), *obj)
+        .map_err(world_state_err)?;
+    Ok(Ret(v_objid(new_obj)))
+}
+bf_declare!(renumber, bf_renumber);
+
 const BF_MOVE_TRAMPOLINE_START_ACCEPT: usize = 0;
 const BF_MOVE_TRAMPOLINE_MOVE_CALL_EXITFUNC: usize = 1;
 const BF_MOVE_TRAMPOLINE_CALL_ENTERFUNC: usize = 2;
@@ -602,25 +814,59 @@ fn bf_properties(bf_args: &mut BfCallState<'_>) -> Result<BfRet, Error> {
 }
 bf_declare!(properties, bf_properties);
 
+/// set_player_flag(obj, [flag,] value) sets or clears one of an object's flags: the player flag by
+/// default, as in LambdaMOO, or the one named by `flag`. The player, programmer and wizard flags
+/// need a wizard; r, w and f need the owner or a wizard. Unlike LambdaMOO, clearing the player
+/// flag doesn't boot a connected player, but they can't log in as that object again.
 fn bf_set_player_flag(bf_args: &mut BfCallState<'_>) -> Result<BfRet, Error> {
-    if bf_args.args.len() != 2 {
+    if bf_args.args.len() != 2 && bf_args.args.len() != 3 {
         return Err(E_INVARG);
     }
 
-    let (Variant::Obj(obj), Variant::Int(f)) =
-        (bf_args.args[0].variant(), bf_args.args[1].variant())
-    else {
-        return Err(E_INVARG);
+    let Variant::Obj(obj) = bf_args.args[0].variant() else {
+        return Err(E_TYPE);
+    };
+    let flag = if bf_args.args.len() == 3 {
+        let Variant::Str(flag) = bf_args.args[1].variant() else {
+            return Err(E_TYPE);
+        };
+        match flag.as_str().to_lowercase().as_str() {
+            "player" => None,
+            "programmer" => Some("programmer"),
+            "wizard" => Some("wizard"),
+            "r" | "read" => Some("r"),
+            "w" | "write" => Some("w"),
+            "f" | "fertile" => Some("f"),
+            _ => return Err(E_INVARG),
+        }
+    } else {
+        None
     };
+    let f = bf_args.args[bf_args.args.len() - 1].is_true();
+
+    // Everything but the base flags needs a wizard.
+    if !matches!(flag, Some("r" | "w" | "f")) {
+        bf_args
+            .task_perms()
+            .map_err(world_state_err)?
+            .check_wizard()
+            .map_err(world_state_err)?;
+    }
 
-    let f = *f == 1;
+    if !bf_args.world_state.valid(*obj).map_err(world_state_err)? {
+        return Err(E_INVARG);
+    }
 
-    // User must be a wizard.
-    bf_args
-        .task_perms()
-        .map_err(world_state_err)?
-        .check_wizard()
-        .map_err(world_state_err)?;
+    // The other flags are all settable as (pseudo-)properties, which does its own permission
+    // checks. Perms are looked up fresh for every check, so a wizard clearing their own wizard
+    // bit is no longer a wizard for the rest of the task.
+    if let Some(pname) = flag {
+        bf_args
+            .world_state
+            .update_property(bf_args.task_perms_who(), *obj, pname, &v_int(f as i64))
+            .map_err(world_state_err)?;
+        return Ok(Ret(v_none()));
+    }
 
     // Get and set object flags
     let mut flags = bf_args
@@ -660,6 +906,62 @@ fn bf_players(bf_args: &mut BfCallState<'_>) -> Result<BfRet, Error> {
 }
 bf_declare!(players, bf_players);
 
+/// match_object(name [, location]) resolves `name` to an object the same way the command parser
+/// does, returning the object, $ambiguous_match (#-2) or $failed_match (#-3).
+/// Without a location, it matches from the player's point of view: "me", "here", `#<num>`, and
+/// the names of the player, their location and its contents. With one, it matches the names of
+/// that location and its contents.
+fn bf_match_object(bf_args: &mut BfCallState<'_>) -> Result<BfRet, Error> {
+    if bf_args.args.is_empty() || bf_args.args.len() > 2 {
+        return Err(E_INVARG);
+    }
+    let Variant::Str(name) = bf_args.args[0].variant() else {
+        return Err(E_TYPE);
+    };
+    let name = name.as_str().to_string();
+    let perms = bf_args.task_perms_who();
+    let constants = MatchConstants::from_world_state(bf_args.world_state, perms);
+
+    let matched = if bf_args.args.len() == 2 {
+        let Variant::Obj(location) = bf_args.args[1].variant() else {
+            return Err(E_TYPE);
+        };
+        let location = *location;
+        if !bf_args
+            .world_state
+            .valid(location)
+            .map_err(world_state_err)?
+        {
+            return Err(E_INVARG);
+        }
+        let search = bf_args
+            .world_state
+            .contents_of(perms, location)
+            .map_err(world_state_err)?
+            .with_appended(&[location]);
+        let mut env = WsMatchEnv {
+            ws: bf_args.world_state,
+            perms,
+        };
+        match_in(&mut env, &search, &name).map(|m| m.map(|oid| constants.translate(oid)))
+    } else {
+        let player = bf_args.exec_state.top().player;
+        let mut matcher = MatchEnvironmentParseMatcher {
+            env: WsMatchEnv {
+                ws: bf_args.world_state,
+                perms,
+            },
+            player,
+            constants,
+        };
+        matcher.match_object(&name)
+    }
+    .map_err(world_state_err)?;
+
+    Ok(Ret(v_objid(matched.unwrap_or(constants.failed_match))))
+}
+bf_declare!(match_object, bf_match_object);
+
 impl VM {
     pub(crate) fn register_bf_objects(&mut self) {
         self.builtins[offset_for_builtin("create")] = Arc::new(BfCreate {});
@@ -673,6 +975,13 @@ impl VM {
         self.builtins[offset_for_builtin("set_player_flag")] = Arc::new(BfSetPlayerFlag {});
         self.builtins[offset_for_builtin("recycle")] = Arc::new(BfRecycle {});
         self.builtins[offset_for_builtin("max_object")] = Arc::new(BfMaxObject {});
+        self.builtins[offset_for_builtin("reset_max_object")] = Arc::new(BfResetMaxObject {});
+        self.builtins[offset_for_builtin("renumber")] = Arc::new(BfRenumber {});
         self.builtins[offset_for_builtin("players")] = Arc::new(BfPlayers {});
+        self.builtins[offset_for_builtin("match_object")] = Arc::new(BfMatchObject {});
+        self.builtins[offset_for_builtin("create_with_id")] = Arc::new(BfCreateWithId {});
+        self.builtins[offset_for_builtin("parents")] = Arc::new(BfParents {});
+        self.builtins[offset_for_builtin("chparents")] = Arc::new(BfChparents {});
+        self.builtins[offset_for_builtin("isa")] = Arc::new(BfIsa {});
     }
 }
@@ -200,7 +200,7 @@ fn bf_add_property(bf_args: &mut BfCallState<'_>) -> Result<BfRet, Error> {
             *location,
             *location,
             name.as_str(),
-            bf_args.caller_perms(),
+            attrs.owner.unwrap(),
             attrs.flags.unwrap(),
             Some(value),
         )
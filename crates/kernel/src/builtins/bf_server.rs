@@ -20,21 +20,22 @@ use chrono::{DateTime, Local, TimeZone};
 use chrono_tz::{OffsetName, Tz};
 use iana_time_zone::get_timezone;
 
-use tracing::{debug, error, info, warn};
+use tracing::{debug, error, info};
 
 use moor_values::model::ObjFlag;
-use moor_values::model::{world_state_err, NarrativeEvent, WorldStateError};
+use moor_values::model::{world_state_err, ContentType, NarrativeEvent, WorldStateError};
 use moor_values::var::Error::{E_INVARG, E_PERM, E_TYPE};
 use moor_values::var::Variant;
-use moor_values::var::{v_bool, v_int, v_list, v_none, v_objid, v_str, v_string, Var};
+use moor_values::var::{v_bool, v_float, v_int, v_list, v_none, v_objid, v_str, v_string, Var};
 use moor_values::var::{v_listv, Error};
 
 use crate::bf_declare;
 use crate::builtins::BfRet::{Ret, VmInstr};
 use crate::builtins::{BfCallState, BfRet, BuiltinFunction};
-use crate::tasks::task_messages::SchedulerControlMsg;
-use crate::tasks::TaskId;
-use crate::vm::{ExecutionResult, VM};
+use crate::tasks::task_messages::{QueuedVerbCall, SchedulerControlMsg};
+use crate::tasks::{TaskId, TaskSuspension};
+use crate::vm::activation::Caller;
+use crate::vm::{ExecutionResult, FinallyReason, VM};
 use moor_compiler::compile;
 use moor_compiler::{offset_for_builtin, ArgCount, ArgType, Builtin, BUILTIN_DESCRIPTORS};
 
@@ -47,8 +48,12 @@ fn bf_noop(bf_args: &mut BfCallState<'_>) -> Result<BfRet, Error> {
 }
 bf_declare!(noop, bf_noop);
 
+/// notify(player, text [, content_type])
+/// If given as a string, `content_type` is a MIME type (e.g. "text/html") which tells rich clients
+/// how to render the text; clients which can't render it show it as plain text. Any other third
+/// argument (LambdaMOO's `no_flush`) is accepted and ignored.
 fn bf_notify(bf_args: &mut BfCallState<'_>) -> Result<BfRet, Error> {
-    if bf_args.args.len() != 2 {
+    if bf_args.args.len() < 2 || bf_args.args.len() > 3 {
         return Err(E_INVARG);
     }
     let player = bf_args.args[0].variant();
@@ -59,6 +64,12 @@ fn bf_notify(bf_args: &mut BfCallState<'_>) -> Result<BfRet, Error> {
     let Variant::Str(msg) = msg else {
         return Err(E_TYPE);
     };
+    let content_type = match bf_args.args.get(2).map(|a| a.variant()) {
+        Some(Variant::Str(content_type)) => {
+            ContentType::from_mime_type(content_type.as_str()).ok_or(E_INVARG)?
+        }
+        _ => ContentType::TextPlain,
+    };
 
     // If player is not the calling task perms, or a caller is not a wizard, raise E_PERM.
     bf_args
@@ -67,7 +78,7 @@ fn bf_notify(bf_args: &mut BfCallState<'_>) -> Result<BfRet, Error> {
         .check_obj_owner_perms(*player)
         .map_err(world_state_err)?;
 
-    let event = NarrativeEvent::notify_text(bf_args.exec_state.caller(), msg.to_string());
+    let event = NarrativeEvent::notify(bf_args.exec_state.caller(), msg.to_string(), content_type);
 
     bf_args
         .scheduler_sender
@@ -85,6 +96,43 @@ fn bf_notify(bf_args: &mut BfCallState<'_>) -> Result<BfRet, Error> {
 }
 bf_declare!(notify, bf_notify);
 
+/// notify_oob(player, payload)
+/// Like `notify`, but tags the line as out-of-band (e.g. an MCP `#$#` message), so that clients
+/// route it to their protocol handlers rather than the screen.
+fn bf_notify_oob(bf_args: &mut BfCallState<'_>) -> Result<BfRet, Error> {
+    if bf_args.args.len() != 2 {
+        return Err(E_INVARG);
+    }
+    let Variant::Obj(player) = bf_args.args[0].variant() else {
+        return Err(E_TYPE);
+    };
+    let Variant::Str(payload) = bf_args.args[1].variant() else {
+        return Err(E_TYPE);
+    };
+
+    bf_args
+        .task_perms()
+        .map_err(world_state_err)?
+        .check_obj_owner_perms(*player)
+        .map_err(world_state_err)?;
+
+    let event = NarrativeEvent::notify_oob(bf_args.exec_state.caller(), payload.to_string());
+
+    bf_args
+        .scheduler_sender
+        .send((
+            bf_args.exec_state.task_id,
+            SchedulerControlMsg::Notify {
+                player: *player,
+                event,
+            },
+        ))
+        .expect("scheduler is not listening");
+
+    Ok(Ret(v_int(1)))
+}
+bf_declare!(notify_oob, bf_notify_oob);
+
 fn bf_connected_players(bf_args: &mut BfCallState<'_>) -> Result<BfRet, Error> {
     if !bf_args.args.is_empty() {
         return Err(E_INVARG);
@@ -148,14 +196,10 @@ fn bf_set_task_perms(bf_args: &mut BfCallState<'_>) -> Result<BfRet, Error> {
 }
 bf_declare!(set_task_perms, bf_set_task_perms);
 
-fn bf_callers(bf_args: &mut BfCallState<'_>) -> Result<BfRet, Error> {
-    if !bf_args.args.is_empty() {
-        return Err(E_INVARG);
-    }
-
-    // We have to exempt ourselves from the callers list.
-    let callers = bf_args.exec_state.callers()[1..].to_vec();
-    Ok(Ret(v_listv(
+/// Turn a call stack into the list of `{this, verb-name, programmer, verb-loc, player, line}`
+/// entries returned by `callers()` and `task_stack()`.
+fn callers_list(callers: &[Caller]) -> Var {
+    v_listv(
         callers
             .iter()
             .map(|c| {
@@ -176,10 +220,51 @@ fn bf_callers(bf_args: &mut BfCallState<'_>) -> Result<BfRet, Error> {
                 v_listv(callers)
             })
             .collect::<Vec<Var>>(),
-    )))
+    )
+}
+
+fn bf_callers(bf_args: &mut BfCallState<'_>) -> Result<BfRet, Error> {
+    if !bf_args.args.is_empty() {
+        return Err(E_INVARG);
+    }
+
+    // We have to exempt ourselves from the callers list.
+    let callers = bf_args.exec_state.callers();
+    Ok(Ret(callers_list(&callers[1..])))
 }
 bf_declare!(callers, bf_callers);
 
+/// task_stack(task-id [, include-line-numbers])
+/// Return the call stack of a suspended task, innermost frame first, in the same form as
+/// `callers()`. The caller must own the task or be a wizard. The second argument is accepted for
+/// compatibility and ignored; frames always carry a line number, as with `callers()`.
+fn bf_task_stack(bf_args: &mut BfCallState<'_>) -> Result<BfRet, Error> {
+    if bf_args.args.is_empty() || bf_args.args.len() > 2 {
+        return Err(E_INVARG);
+    }
+    let Variant::Int(queued_task_id) = bf_args.args[0].variant() else {
+        return Err(E_TYPE);
+    };
+    let queued_task_id = *queued_task_id as TaskId;
+
+    let (send, receive) = kanal::oneshot();
+    bf_args
+        .scheduler_sender
+        .send((
+            bf_args.exec_state.task_id,
+            SchedulerControlMsg::TaskStack {
+                queued_task_id,
+                sender_permissions: bf_args.task_perms().map_err(world_state_err)?,
+                result_sender: send,
+            },
+        ))
+        .expect("scheduler is not listening");
+
+    let callers = receive.recv().expect("scheduler is not listening")?;
+    Ok(Ret(callers_list(&callers)))
+}
+bf_declare!(task_stack, bf_task_stack);
+
 fn bf_task_id(bf_args: &mut BfCallState<'_>) -> Result<BfRet, Error> {
     if !bf_args.args.is_empty() {
         return Err(E_INVARG);
@@ -289,7 +374,9 @@ fn bf_time(bf_args: &mut BfCallState<'_>) -> Result<BfRet, Error> {
         return Err(E_INVARG);
     }
     Ok(Ret(v_int(
-        SystemTime::now()
+        bf_args
+            .clock
+            .now()
             .duration_since(SystemTime::UNIX_EPOCH)
             .unwrap()
             .as_secs() as i64,
@@ -302,7 +389,7 @@ fn bf_ctime(bf_args: &mut BfCallState<'_>) -> Result<BfRet, Error> {
         return Err(E_INVARG);
     }
     let time = if bf_args.args.is_empty() {
-        SystemTime::now()
+        bf_args.clock.now()
     } else {
         let Variant::Int(time) = bf_args.args[0].variant() else {
             return Err(E_TYPE);
@@ -353,14 +440,68 @@ fn bf_server_version(bf_args: &mut BfCallState<'_>) -> Result<BfRet, Error> {
     if !bf_args.args.is_empty() {
         return Err(E_INVARG);
     }
-    // TODO: Support server version flag passed down the pipe, rather than hardcoded
-    //   This is a placeholder for now, should be set by the server on startup. But right now
-    //   there isn't a good place to stash this other than WorldState. I intend on refactoring the
-    //   signature for BF invocations, and when I do this, I'll get additional metadata on there.
-    Ok(Ret(v_string("0.0.1".to_string())))
+    Ok(Ret(v_str(env!("CARGO_PKG_VERSION"))))
 }
 bf_declare!(server_version, bf_server_version);
 
+fn bf_server_options(bf_args: &mut BfCallState<'_>) -> Result<BfRet, Error> {
+    if !bf_args.args.is_empty() {
+        return Err(E_INVARG);
+    }
+
+    let (send, receive) = kanal::oneshot();
+    bf_args
+        .scheduler_sender
+        .send((
+            bf_args.exec_state.task_id,
+            SchedulerControlMsg::DescribeServerOptions(send),
+        ))
+        .expect("scheduler is not listening");
+    let config = receive.recv().expect("scheduler is not listening");
+
+    // There are no maps in MOO, so return a list of {<name>, <value>} pairs. Limits which aren't
+    // set are `none`.
+    let max_tasks_per_player = match config.max_tasks_per_player {
+        None => v_none(),
+        Some(max_tasks) => v_int(max_tasks as i64),
+    };
+    let (wizard_ticks, wizard_seconds) = match config.wizard_limits {
+        None => (v_none(), v_none()),
+        Some(limits) => (
+            v_int(limits.max_ticks as i64),
+            v_int(limits.max_seconds as i64),
+        ),
+    };
+    let (fg, bg) = (config.foreground_limits, config.background_limits);
+    let mut options = vec![
+        ("fg_ticks", v_int(fg.max_ticks as i64)),
+        ("bg_ticks", v_int(bg.max_ticks as i64)),
+        ("fg_seconds", v_int(fg.max_seconds as i64)),
+        ("bg_seconds", v_int(bg.max_seconds as i64)),
+        ("max_stack_depth", v_int(fg.max_stack_depth as i64)),
+        ("wizard_ticks", wizard_ticks),
+        ("wizard_seconds", wizard_seconds),
+        ("max_tasks_per_player", max_tasks_per_player),
+    ];
+
+    // Filesystem paths are only for wizards' eyes.
+    let perms = bf_args.task_perms().map_err(world_state_err)?;
+    if perms.check_is_wizard().map_err(world_state_err)? {
+        let textdump_output = match &config.textdump_output {
+            None => v_none(),
+            Some(path) => v_string(path.to_string_lossy().to_string()),
+        };
+        options.push(("textdump_output", textdump_output));
+    }
+
+    let options: Vec<_> = options
+        .into_iter()
+        .map(|(name, value)| v_list(&[v_str(name), value]))
+        .collect();
+    Ok(Ret(v_listv(options)))
+}
+bf_declare!(server_options, bf_server_options);
+
 fn bf_suspend(bf_args: &mut BfCallState<'_>) -> Result<BfRet, Error> {
     // Syntax:  suspend(<seconds>)   => none
     //
@@ -376,6 +517,9 @@ fn bf_suspend(bf_args: &mut BfCallState<'_>) -> Result<BfRet, Error> {
         let Variant::Int(seconds) = bf_args.args[0].variant() else {
             return Err(E_TYPE);
         };
+        if *seconds < 0 {
+            return Err(E_INVARG);
+        }
         Some(Duration::from_secs(*seconds as u64))
     };
 
@@ -388,26 +532,25 @@ fn bf_read(bf_args: &mut BfCallState<'_>) -> Result<BfRet, Error> {
         return Err(E_INVARG);
     }
 
-    // We don't actually support reading from arbitrary connections that aren't the current player,
-    // so we'll raise E_INVARG for anything else, because we don't support LambdaMOO's
-    // network listener model.
-    if bf_args.args.len() == 1 {
-        let Variant::Obj(requested_player) = bf_args.args[0].variant() else {
-            return Err(E_INVARG);
-        };
-        let player = bf_args.exec_state.top().player;
-        if *requested_player != player {
-            // We log this because we'd like to know if cores are trying to do this.
-            warn!(
-                requested_player = ?requested_player,
-                caller = ?bf_args.exec_state.caller(),
-                ?player,
-                "read() called with non-current player");
-            return Err(E_INVARG);
-        }
+    // With no argument we read from the task's own player. Reading from anyone else's
+    // connection is reserved for wizards.
+    let player = bf_args.exec_state.top().player;
+    let reader = match bf_args.args.first().map(|a| a.variant()) {
+        None => player,
+        Some(Variant::Obj(requested_player)) => *requested_player,
+        Some(_) => return Err(E_TYPE),
+    };
+    if reader != player
+        && !bf_args
+            .task_perms()
+            .map_err(world_state_err)?
+            .check_is_wizard()
+            .map_err(world_state_err)?
+    {
+        return Err(E_PERM);
     }
 
-    Ok(VmInstr(ExecutionResult::NeedInput))
+    Ok(VmInstr(ExecutionResult::NeedInput(reader)))
 }
 bf_declare!(read, bf_read);
 
@@ -430,7 +573,10 @@ fn bf_queued_tasks(bf_args: &mut BfCallState<'_>) -> Result<BfRet, Error> {
 
     // return in form:
     //     {<task-id>, <start-time>, <x>, <y>,
-    //      <programmer>, <verb-loc>, <verb-name>, <line>, <this>}
+    //      <programmer>, <verb-loc>, <verb-name>, <line>, <this>,
+    //      <suspension-reason>, <remaining-delay>}
+    // where the last two are our own addition: the reason is one of "timed", "indefinite" or
+    // "input", and the remaining delay is in (float) seconds, for timed suspensions only.
     let tasks: Vec<_> = tasks
         .iter()
         .map(|task| {
@@ -449,8 +595,17 @@ fn bf_queued_tasks(bf_args: &mut BfCallState<'_>) -> Result<BfRet, Error> {
             let verb_name = v_string(task.verb_name.clone());
             let line = v_int(task.line_number as i64);
             let this = v_objid(task.this);
+            let (reason, remaining) = match task.suspension {
+                Some(TaskSuspension::Timed { remaining }) => {
+                    (v_str("timed"), v_float(remaining.as_secs_f64()))
+                }
+                Some(TaskSuspension::Indefinite) => (v_str("indefinite"), v_none()),
+                Some(TaskSuspension::AwaitingInput) => (v_str("input"), v_none()),
+                None => (v_none(), v_none()),
+            };
             v_list(&[
-                task_id, start_time, x, y, programmer, verb_loc, verb_name, line, this,
+                task_id, start_time, x, y, programmer, verb_loc, verb_name, line, this, reason,
+                remaining,
             ])
         })
         .collect();
@@ -471,13 +626,12 @@ fn bf_kill_task(bf_args: &mut BfCallState<'_>) -> Result<BfRet, Error> {
         return Err(E_TYPE);
     };
 
-    // If the task ID is itself, that means returning an Complete execution result, which will cascade
-    // back to the task loop and it will terminate itself.
-    // Not sure this is *exactly* what MOO does, but it's close enough for now.
+    // If the task ID is itself, we abort, which will cascade back to the task loop, which rolls
+    // back the task's transaction and discards its buffered output, same as for any other kill.
     let victim_task_id = *victim_task_id as TaskId;
 
     if victim_task_id == bf_args.exec_state.task_id {
-        return Ok(VmInstr(ExecutionResult::Complete(v_none())));
+        return Ok(VmInstr(ExecutionResult::Exception(FinallyReason::Abort)));
     }
 
     let (send, receive) = kanal::oneshot();
@@ -502,7 +656,7 @@ fn bf_kill_task(bf_args: &mut BfCallState<'_>) -> Result<BfRet, Error> {
 bf_declare!(kill_task, bf_kill_task);
 
 fn bf_resume(bf_args: &mut BfCallState<'_>) -> Result<BfRet, Error> {
-    if bf_args.args.len() < 2 {
+    if bf_args.args.is_empty() || bf_args.args.len() > 2 {
         return Err(E_INVARG);
     }
 
@@ -898,14 +1052,140 @@ fn db_disk_size(bf_args: &mut BfCallState<'_>) -> Result<BfRet, Error> {
 }
 bf_declare!(db_disk_size, db_disk_size);
 
+fn bf_verify_database(bf_args: &mut BfCallState<'_>) -> Result<BfRet, Error> {
+    // Syntax:  verify_database()   => list
+    //
+    // Returns a list of strings, one for each inconsistency found in the database.
+    if !bf_args.args.is_empty() {
+        return Err(E_INVARG);
+    }
+
+    // Must be wizard.
+    bf_args
+        .task_perms()
+        .map_err(world_state_err)?
+        .check_wizard()
+        .map_err(world_state_err)?;
+
+    let problems = bf_args
+        .world_state
+        .verify_database()
+        .map_err(world_state_err)?;
+
+    Ok(Ret(v_listv(problems.into_iter().map(v_string).collect())))
+}
+bf_declare!(verify_database, bf_verify_database);
+
+fn bf_repair_database(bf_args: &mut BfCallState<'_>) -> Result<BfRet, Error> {
+    // Syntax:  repair_database([dry_run])   => list
+    //
+    // Fixes what verify_database() finds where it can, returning a list of strings describing
+    // each fix. If dry_run is true, only reports what would be fixed.
+    if bf_args.args.len() > 1 {
+        return Err(E_INVARG);
+    }
+    let dry_run = bf_args.args.first().map(|a| a.is_true()).unwrap_or(false);
+
+    // Must be wizard.
+    bf_args
+        .task_perms()
+        .map_err(world_state_err)?
+        .check_wizard()
+        .map_err(world_state_err)?;
+
+    let repairs = bf_args
+        .world_state
+        .repair_database(dry_run)
+        .map_err(world_state_err)?;
+
+    Ok(Ret(v_listv(repairs.into_iter().map(v_string).collect())))
+}
+bf_declare!(repair_database, bf_repair_database);
+
+fn bf_call_after_commit(bf_args: &mut BfCallState<'_>) -> Result<BfRet, Error> {
+    // Syntax:  call_after_commit(obj <object>, str <verb> [, list <args>])   => none
+    //
+    // Calls <object>:<verb>(@<args>) in a new background task once the current task's changes
+    // have been committed, with the current task's permissions. If they're rolled back instead,
+    // or the task is aborted, the call is never made.
+    if bf_args.args.len() < 2 || bf_args.args.len() > 3 {
+        return Err(E_INVARG);
+    }
+    let Variant::Obj(vloc) = bf_args.args[0].variant() else {
+        return Err(E_TYPE);
+    };
+    let Variant::Str(verb) = bf_args.args[1].variant() else {
+        return Err(E_TYPE);
+    };
+    let args = match bf_args.args.get(2).map(|a| a.variant()) {
+        None => vec![],
+        Some(Variant::List(args)) => args.iter().cloned().collect(),
+        Some(_) => return Err(E_TYPE),
+    };
+    if !bf_args.world_state.valid(*vloc).map_err(world_state_err)? {
+        return Err(E_INVARG);
+    }
+
+    let call = QueuedVerbCall {
+        player: bf_args.exec_state.top().player,
+        vloc: *vloc,
+        verb: verb.as_str().to_string(),
+        args,
+        perms: bf_args.task_perms_who(),
+    };
+    bf_args.exec_state.queued_calls.push(call);
+    Ok(Ret(v_none()))
+}
+bf_declare!(call_after_commit, bf_call_after_commit);
+
+fn bf_savepoint(bf_args: &mut BfCallState<'_>) -> Result<BfRet, Error> {
+    // Syntax:  savepoint()   => int
+    //
+    // Marks the current state of the world in this task's transaction, for `rollback_to`. The
+    // returned handle is only good until the transaction ends, e.g. by the task suspending.
+    if !bf_args.args.is_empty() {
+        return Err(E_INVARG);
+    }
+    let savepoint = bf_args
+        .world_state
+        .savepoint(bf_args.task_perms_who())
+        .map_err(world_state_err)?;
+    Ok(Ret(v_int(savepoint as i64)))
+}
+bf_declare!(savepoint, bf_savepoint);
+
+fn bf_rollback_to(bf_args: &mut BfCallState<'_>) -> Result<BfRet, Error> {
+    // Syntax:  rollback_to(int <savepoint>)   => none
+    //
+    // Undoes the changes to the world made in this task since <savepoint> was taken, leaving
+    // earlier ones in place. Output already sent and the task's own variables are unaffected.
+    if bf_args.args.len() != 1 {
+        return Err(E_INVARG);
+    }
+    let Variant::Int(savepoint) = bf_args.args[0].variant() else {
+        return Err(E_TYPE);
+    };
+    let Ok(savepoint) = usize::try_from(*savepoint) else {
+        return Err(E_INVARG);
+    };
+    bf_args
+        .world_state
+        .rollback_to(bf_args.task_perms_who(), savepoint)
+        .map_err(world_state_err)?;
+    Ok(Ret(v_none()))
+}
+bf_declare!(rollback_to, bf_rollback_to);
+
 impl VM {
     pub(crate) fn register_bf_server(&mut self) {
         self.builtins[offset_for_builtin("notify")] = Arc::new(BfNotify {});
+        self.builtins[offset_for_builtin("notify_oob")] = Arc::new(BfNotifyOob {});
         self.builtins[offset_for_builtin("connected_players")] = Arc::new(BfConnectedPlayers {});
         self.builtins[offset_for_builtin("is_player")] = Arc::new(BfIsPlayer {});
         self.builtins[offset_for_builtin("caller_perms")] = Arc::new(BfCallerPerms {});
         self.builtins[offset_for_builtin("set_task_perms")] = Arc::new(BfSetTaskPerms {});
         self.builtins[offset_for_builtin("callers")] = Arc::new(BfCallers {});
+        self.builtins[offset_for_builtin("task_stack")] = Arc::new(BfTaskStack {});
         self.builtins[offset_for_builtin("task_id")] = Arc::new(BfTaskId {});
         self.builtins[offset_for_builtin("idle_seconds")] = Arc::new(BfIdleSeconds {});
         self.builtins[offset_for_builtin("connected_seconds")] = Arc::new(BfConnectedSeconds {});
@@ -914,6 +1194,7 @@ impl VM {
         self.builtins[offset_for_builtin("ctime")] = Arc::new(BfCtime {});
         self.builtins[offset_for_builtin("raise")] = Arc::new(BfRaise {});
         self.builtins[offset_for_builtin("server_version")] = Arc::new(BfServerVersion {});
+        self.builtins[offset_for_builtin("server_options")] = Arc::new(BfServerOptions {});
         self.builtins[offset_for_builtin("shutdown")] = Arc::new(BfShutdown {});
         self.builtins[offset_for_builtin("suspend")] = Arc::new(BfSuspend {});
         self.builtins[offset_for_builtin("queued_tasks")] = Arc::new(BfQueuedTasks {});
@@ -931,5 +1212,10 @@ impl VM {
         self.builtins[offset_for_builtin("dump_database")] = Arc::new(BfDumpDatabase {});
         self.builtins[offset_for_builtin("memory_usage")] = Arc::new(BfMemoryUsage {});
         self.builtins[offset_for_builtin("db_disk_size")] = Arc::new(BfDbDiskSize {});
+        self.builtins[offset_for_builtin("verify_database")] = Arc::new(BfVerifyDatabase {});
+        self.builtins[offset_for_builtin("repair_database")] = Arc::new(BfRepairDatabase {});
+        self.builtins[offset_for_builtin("call_after_commit")] = Arc::new(BfCallAfterCommit {});
+        self.builtins[offset_for_builtin("savepoint")] = Arc::new(BfSavepoint {});
+        self.builtins[offset_for_builtin("rollback_to")] = Arc::new(BfRollbackTo {});
     }
 }
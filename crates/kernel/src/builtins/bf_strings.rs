@@ -17,10 +17,11 @@ use std::sync::Arc;
 use rand::distributions::Alphanumeric;
 use rand::Rng;
 
+use moor_values::util::constant_time_eq;
 use moor_values::var::Error;
 use moor_values::var::Error::{E_INVARG, E_TYPE};
 use moor_values::var::Variant;
-use moor_values::var::{v_int, v_str, v_string};
+use moor_values::var::{v_bool, v_int, v_str, v_string};
 
 use crate::bf_declare;
 use crate::builtins::BfRet::Ret;
@@ -78,26 +79,37 @@ fn bf_strsub(bf_args: &mut BfCallState<'_>) -> Result<BfRet, Error> {
 }
 bf_declare!(strsub, bf_strsub);
 
+/// The 1-based character position of the byte offset `i` in `s`.
+fn char_position(s: &str, i: usize) -> i64 {
+    s[..i].chars().count() as i64 + 1
+}
+
 fn str_index(subject: &str, what: &str, case_matters: bool) -> i64 {
     if case_matters {
-        subject.find(what).map(|i| i as i64 + 1).unwrap_or(0)
+        subject
+            .find(what)
+            .map(|i| char_position(subject, i))
+            .unwrap_or(0)
     } else {
+        let subject = subject.to_lowercase();
         subject
-            .to_lowercase()
             .find(&what.to_lowercase())
-            .map(|i| i as i64 + 1)
+            .map(|i| char_position(&subject, i))
             .unwrap_or(0)
     }
 }
 
 fn str_rindex(subject: &str, what: &str, case_matters: bool) -> i64 {
     if case_matters {
-        subject.rfind(what).map(|i| i as i64 + 1).unwrap_or(0)
+        subject
+            .rfind(what)
+            .map(|i| char_position(subject, i))
+            .unwrap_or(0)
     } else {
+        let subject = subject.to_lowercase();
         subject
-            .to_lowercase()
             .rfind(&what.to_lowercase())
-            .map(|i| i as i64 + 1)
+            .map(|i| char_position(&subject, i))
             .unwrap_or(0)
     }
 }
@@ -201,6 +213,38 @@ fn bf_crypt(bf_args: &mut BfCallState<'_>) -> Result<BfRet, Error> {
 }
 bf_declare!(crypt, bf_crypt);
 
+/// Whether `password` encrypts to `hash`, which also supplies the salt. `None` if `hash` isn't
+/// something `crypt` could have produced.
+fn password_matches(password: &str, hash: &str) -> Option<bool> {
+    let crypted = pwhash::unix::crypt(password, hash).ok()?;
+    Some(constant_time_eq(crypted.as_bytes(), hash.as_bytes()))
+}
+
+/*
+int password_verify (str password, str hash)
+
+moor extension. Returns true if crypt(password, hash) == hash, comparing the two in constant time
+so that repeated guesses can't learn how much of the hash they got right.
+
+The server doesn't check passwords itself; login is up to the core's $do_login_command, so this only
+helps cores whose login verbs call it in place of `crypt(password, hash) == hash`.
+*/
+fn bf_password_verify(bf_args: &mut BfCallState<'_>) -> Result<BfRet, Error> {
+    if bf_args.args.len() != 2 {
+        return Err(E_INVARG);
+    }
+    let (Variant::Str(password), Variant::Str(hash)) =
+        (bf_args.args[0].variant(), bf_args.args[1].variant())
+    else {
+        return Err(E_TYPE);
+    };
+    let Some(matches) = password_matches(password.as_str(), hash.as_str()) else {
+        return Err(E_INVARG);
+    };
+    Ok(Ret(v_bool(matches)))
+}
+bf_declare!(password_verify, bf_password_verify);
+
 fn bf_string_hash(bf_args: &mut BfCallState<'_>) -> Result<BfRet, Error> {
     if bf_args.args.len() != 1 {
         return Err(E_INVARG);
@@ -229,12 +273,13 @@ impl VM {
         self.builtins[offset_for_builtin("crypt")] = Arc::new(BfCrypt {});
         self.builtins[offset_for_builtin("string_hash")] = Arc::new(BfStringHash {});
         self.builtins[offset_for_builtin("binary_hash")] = Arc::new(BfBinaryHash {});
+        self.builtins[offset_for_builtin("password_verify")] = Arc::new(BfPasswordVerify {});
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::builtins::bf_strings::strsub;
+    use crate::builtins::bf_strings::{password_matches, strsub};
 
     #[test]
     fn test_strsub_remove_piece() {
@@ -286,4 +331,12 @@ mod tests {
         let expected = "foo bar baz";
         assert_eq!(strsub(subject, "fizz", "buzz", false), expected);
     }
+
+    #[test]
+    fn test_password_matches() {
+        let hash = pwhash::unix::crypt("sekrit", "Xy").unwrap();
+        assert_eq!(password_matches("sekrit", &hash), Some(true));
+        assert_eq!(password_matches("sekrit!", &hash), Some(false));
+        assert_eq!(password_matches("", &hash), Some(false));
+    }
 }
@@ -16,6 +16,8 @@ use std::collections::hash_map::DefaultHasher;
 use std::hash::{Hash, Hasher};
 use std::sync::Arc;
 
+use sha2::{Digest, Sha256};
+
 use moor_values::var::Error;
 use moor_values::var::Error::{E_INVARG, E_TYPE};
 use moor_values::var::Variant;
@@ -158,6 +160,19 @@ fn bf_value_hash(bf_args: &mut BfCallState<'_>) -> Result<BfRet, Error> {
 }
 bf_declare!(value_hash, bf_value_hash);
 
+/// moor extension. Unlike `value_hash`, which is only good for the life of the process, this is
+/// guaranteed stable across server versions and restarts, for cores keying caches and indexes on
+/// content: the lowercase hex SHA-256 digest of the value's `toliteral()` form, encoded as UTF-8.
+/// Strings are hashed case-sensitively, so values `==` considers equal may hash differently.
+fn bf_stable_hash(bf_args: &mut BfCallState<'_>) -> Result<BfRet, Error> {
+    if bf_args.args.len() != 1 {
+        return Err(E_INVARG);
+    }
+    let digest = Sha256::digest(bf_args.args[0].to_literal().as_bytes());
+    Ok(Ret(v_str(format!("{:x}", digest).as_str())))
+}
+bf_declare!(stable_hash, bf_stable_hash);
+
 fn bf_length(bf_args: &mut BfCallState<'_>) -> Result<BfRet, Error> {
     if bf_args.args.len() != 1 {
         return Err(E_INVARG);
@@ -171,6 +186,17 @@ fn bf_length(bf_args: &mut BfCallState<'_>) -> Result<BfRet, Error> {
 }
 bf_declare!(length, bf_length);
 
+fn bf_byte_length(bf_args: &mut BfCallState<'_>) -> Result<BfRet, Error> {
+    if bf_args.args.len() != 1 {
+        return Err(E_INVARG);
+    }
+    let Variant::Str(s) = bf_args.args[0].variant() else {
+        return Err(E_TYPE);
+    };
+    Ok(Ret(v_int(s.byte_len() as i64)))
+}
+bf_declare!(byte_length, bf_byte_length);
+
 fn bf_object_bytes(bf_args: &mut BfCallState<'_>) -> Result<BfRet, Error> {
     if bf_args.args.len() != 1 {
         return Err(E_INVARG);
@@ -183,7 +209,7 @@ fn bf_object_bytes(bf_args: &mut BfCallState<'_>) -> Result<BfRet, Error> {
     };
     let size = bf_args
         .world_state
-        .object_bytes(bf_args.caller_perms(), *o)?;
+        .object_bytes(bf_args.task_perms_who(), *o)?;
     Ok(Ret(v_int(size as i64)))
 }
 bf_declare!(object_bytes, bf_object_bytes);
@@ -203,5 +229,7 @@ impl VM {
         self.builtins[offset_for_builtin("object_bytes")] = Arc::new(BfObjectBytes {});
         self.builtins[offset_for_builtin("value_hash")] = Arc::new(BfValueHash {});
         self.builtins[offset_for_builtin("length")] = Arc::new(BfLength {});
+        self.builtins[offset_for_builtin("byte_length")] = Arc::new(BfByteLength {});
+        self.builtins[offset_for_builtin("stable_hash")] = Arc::new(BfStableHash {});
     }
 }
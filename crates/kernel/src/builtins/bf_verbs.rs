@@ -29,18 +29,20 @@ use moor_values::var::Error::{E_INVARG, E_INVIND, E_PERM, E_TYPE, E_VERBNF};
 use moor_values::var::List;
 use moor_values::var::Objid;
 use moor_values::var::Variant;
-use moor_values::var::{v_empty_list, v_list, v_none, v_objid, v_str, v_string, Var};
+use moor_values::var::{v_empty_list, v_int, v_list, v_none, v_objid, v_str, v_string, Var};
 use moor_values::var::{v_listv, Error};
 
 use crate::bf_declare;
 use crate::builtins::BfRet::Ret;
 use crate::builtins::{BfCallState, BfRet, BuiltinFunction};
 use crate::tasks::command_parse::{parse_preposition_spec, preposition_to_string};
+use crate::tasks::program_cache::decode_program;
 use crate::vm::VM;
 use moor_compiler::compile;
 use moor_compiler::offset_for_builtin;
 use moor_compiler::program_to_tree;
 use moor_compiler::unparse;
+use moor_compiler::ControlFlowGraph;
 use moor_compiler::GlobalName;
 use moor_compiler::Program;
 
@@ -361,7 +363,10 @@ fn bf_verb_code(bf_args: &mut BfCallState<'_>) -> Result<BfRet, Error> {
 
     // If the verb is not binary type MOO, we don't support decompilation or listing
     // of it yet.
-    if verbdef.binary_type() != BinaryType::LambdaMoo18X {
+    if !matches!(
+        verbdef.binary_type(),
+        BinaryType::LambdaMoo18X | BinaryType::MooSource
+    ) {
         warn!(object=?bf_args.args[0], verb=?bf_args.args[1], binary_type=?verbdef.binary_type(), 
             "verb_code: verb is not binary type MOO");
         return Err(E_TYPE);
@@ -380,6 +385,12 @@ fn bf_verb_code(bf_args: &mut BfCallState<'_>) -> Result<BfRet, Error> {
         return Ok(Ret(v_empty_list()));
     }
 
+    // A verb imported lazily hasn't been compiled yet, so its source is what we list.
+    if verbdef.binary_type() == BinaryType::MooSource {
+        let source = String::from_utf8_lossy(verb_info.binary().as_slice()).into_owned();
+        return Ok(Ret(v_listv(source.lines().map(v_str).collect())));
+    }
+
     // Decode.
     let program = Program::from_sliceref(verb_info.binary()).map_err(|_| {
         error!(object=?bf_args.args[0], verb=?bf_args.args[1], "verb_code: verb program could not be decoded");
@@ -592,7 +603,10 @@ fn bf_disassemble(bf_args: &mut BfCallState<'_>) -> Result<BfRet, Error> {
         Err(e) => return Err(e),
     };
 
-    if verbdef.binary_type() != BinaryType::LambdaMoo18X {
+    if !matches!(
+        verbdef.binary_type(),
+        BinaryType::LambdaMoo18X | BinaryType::MooSource
+    ) {
         warn!(object=?bf_args.args[0], verb=?bf_args.args[1], binary_type=?verbdef.binary_type(),
             "disassemble: verb is not binary type MOO");
         return Err(E_TYPE);
@@ -607,8 +621,8 @@ fn bf_disassemble(bf_args: &mut BfCallState<'_>) -> Result<BfRet, Error> {
         return Ok(Ret(v_empty_list()));
     }
 
-    let program = Program::from_sliceref(verb_info.binary()).map_err(|_| {
-        error!(object=?bf_args.args[0], verb=?bf_args.args[1], "disassemble: verb program could not be decoded");
+    let program = decode_program(verbdef.binary_type(), verb_info.binary().as_slice()).map_err(|e| {
+        error!(object=?bf_args.args[0], verb=?bf_args.args[1], error = ?e, "disassemble: verb program could not be compiled");
         E_INVARG
     })?;
 
@@ -663,6 +677,110 @@ fn bf_disassemble(bf_args: &mut BfCallState<'_>) -> Result<BfRet, Error> {
 }
 bf_declare!(disassemble, bf_disassemble);
 
+// verb_cfg(obj <object>, str <verb-desc>) -> str
+// Returns the control flow graph of the verb's compiled program in Graphviz DOT format: one node
+// per basic block of opcodes, with edges for fallthrough, jumps, branches and error handlers.
+fn bf_verb_cfg(bf_args: &mut BfCallState<'_>) -> Result<BfRet, Error> {
+    if bf_args.args.len() != 2 {
+        return Err(E_INVARG);
+    }
+    let Variant::Obj(obj) = bf_args.args[0].variant() else {
+        return Err(E_TYPE);
+    };
+    if !bf_args.world_state.valid(*obj).map_err(world_state_err)? {
+        return Err(E_INVARG);
+    }
+
+    let verbdef = get_verbdef(*obj, bf_args.args[1].clone(), bf_args)?;
+    if !matches!(
+        verbdef.binary_type(),
+        BinaryType::LambdaMoo18X | BinaryType::MooSource
+    ) {
+        return Err(E_TYPE);
+    }
+
+    let verb_info = bf_args
+        .world_state
+        .retrieve_verb(bf_args.task_perms_who(), *obj, verbdef.uuid())
+        .map_err(world_state_err)?;
+
+    let program = if verb_info.binary().is_empty() {
+        Program::new()
+    } else {
+        decode_program(verbdef.binary_type(), verb_info.binary().as_slice()).map_err(|e| {
+            error!(object=?bf_args.args[0], verb=?bf_args.args[1], error = ?e, "verb_cfg: verb program could not be compiled");
+            E_INVARG
+        })?
+    };
+
+    let cfg = ControlFlowGraph::build(&program);
+    Ok(Ret(v_string(cfg.to_dot(&program))))
+}
+bf_declare!(verb_cfg, bf_verb_cfg);
+
+// verb_metrics(obj <object>, str <verb-desc>) -> {<call-count>, <cumulative-ticks>}
+// Wizard-only. Reports how often the verb has been invoked since server start, and how many
+// ticks it has consumed in total.
+fn bf_verb_metrics(bf_args: &mut BfCallState<'_>) -> Result<BfRet, Error> {
+    if bf_args.args.len() != 2 {
+        return Err(E_INVARG);
+    }
+    let Variant::Obj(obj) = bf_args.args[0].variant() else {
+        return Err(E_TYPE);
+    };
+
+    bf_args
+        .task_perms()
+        .map_err(world_state_err)?
+        .check_wizard()
+        .map_err(world_state_err)?;
+
+    if !bf_args.world_state.valid(*obj).map_err(world_state_err)? {
+        return Err(E_INVARG);
+    }
+
+    let verbdef = get_verbdef(*obj, bf_args.args[1].clone(), bf_args)?;
+    let metric = bf_args
+        .verb_metrics
+        .metric(verbdef.location(), verbdef.uuid());
+
+    Ok(Ret(v_list(&[
+        v_int(metric.calls as i64),
+        v_int(metric.ticks as i64),
+    ])))
+}
+bf_declare!(verb_metrics, bf_verb_metrics);
+
+// coverage() -> {{<definer>, <verb-name>, {<opcode>, ...}, {<line>, ...}}, ...}
+// Reports, for each verb the current task has executed, the offsets of the opcodes and the
+// source lines that ran. Only available when the server is running in coverage mode; E_INVARG
+// otherwise.
+fn bf_coverage(bf_args: &mut BfCallState<'_>) -> Result<BfRet, Error> {
+    if !bf_args.args.is_empty() {
+        return Err(E_INVARG);
+    }
+    let Some(coverage) = &bf_args.coverage else {
+        return Err(E_INVARG);
+    };
+
+    let verbs = coverage
+        .verbs()
+        .into_iter()
+        .map(|(definer, verb)| {
+            let opcodes: Vec<_> = verb.opcodes.iter().map(|pc| v_int(*pc as i64)).collect();
+            let lines: Vec<_> = verb.lines.iter().map(|l| v_int(*l as i64)).collect();
+            v_list(&[
+                v_objid(definer),
+                v_string(verb.name),
+                v_listv(opcodes),
+                v_listv(lines),
+            ])
+        })
+        .collect();
+    Ok(Ret(v_listv(verbs)))
+}
+bf_declare!(coverage, bf_coverage);
+
 impl VM {
     pub(crate) fn register_bf_verbs(&mut self) {
         self.builtins[offset_for_builtin("verb_info")] = Arc::new(BfVerbInfo {});
@@ -674,5 +792,8 @@ impl VM {
         self.builtins[offset_for_builtin("add_verb")] = Arc::new(BfAddVerb {});
         self.builtins[offset_for_builtin("delete_verb")] = Arc::new(BfDeleteVerb {});
         self.builtins[offset_for_builtin("disassemble")] = Arc::new(BfDisassemble {});
+        self.builtins[offset_for_builtin("verb_metrics")] = Arc::new(BfVerbMetrics {});
+        self.builtins[offset_for_builtin("verb_cfg")] = Arc::new(BfVerbCfg {});
+        self.builtins[offset_for_builtin("coverage")] = Arc::new(BfCoverage {});
     }
 }
@@ -32,8 +32,11 @@ use moor_values::var::Error;
 use moor_values::var::Objid;
 use moor_values::var::Var;
 
+use crate::tasks::clock::Clock;
+use crate::tasks::coverage::TaskCoverage;
 use crate::tasks::sessions::Session;
 use crate::tasks::task_messages::SchedulerControlMsg;
+use crate::tasks::verb_metrics::VerbMetrics;
 use crate::tasks::TaskId;
 use crate::vm::{ExecutionResult, VMExecState};
 
@@ -52,6 +55,12 @@ pub struct BfCallState<'a> {
     pub(crate) session: Arc<dyn Session>,
     /// For sending messages up to the scheduler
     pub(crate) scheduler_sender: Sender<(TaskId, SchedulerControlMsg)>,
+    /// Server-wide per-verb execution counters.
+    pub(crate) verb_metrics: Arc<VerbMetrics>,
+    /// What this task has executed so far, if it's running in coverage mode.
+    pub(crate) coverage: Option<Arc<TaskCoverage>>,
+    /// Where the time comes from, for `time()` and friends.
+    pub(crate) clock: Clock,
 }
 
 impl BfCallState<'_> {
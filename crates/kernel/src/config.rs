@@ -17,7 +17,82 @@
 
 use std::path::PathBuf;
 
-#[derive(Debug, Default)]
+use moor_values::model::ObjFlag;
+use moor_values::util::BitEnum;
+
+use crate::tasks::clock::Clock;
+
+/// How far a task may run before it's aborted.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct TaskLimits {
+    /// Opcodes executed since the task started or last resumed.
+    pub max_ticks: usize,
+    /// Wall-clock seconds since the task started or last resumed.
+    pub max_seconds: u64,
+    pub max_stack_depth: usize,
+}
+
+impl TaskLimits {
+    pub const FOREGROUND: TaskLimits = TaskLimits {
+        max_ticks: 60_000,
+        max_seconds: 5,
+        max_stack_depth: 50,
+    };
+    pub const BACKGROUND: TaskLimits = TaskLimits {
+        max_ticks: 30_000,
+        max_seconds: 3,
+        max_stack_depth: 50,
+    };
+}
+
+#[derive(Debug)]
 pub struct Config {
     pub textdump_output: Option<PathBuf>,
+    /// The maximum number of tasks (running, suspended, or forked-and-waiting) a non-wizard
+    /// player may have in the scheduler at once. `None` means no limit.
+    pub max_tasks_per_player: Option<usize>,
+    /// Limits for tasks started by a player (commands, verb calls, evals).
+    pub foreground_limits: TaskLimits,
+    /// Limits for forked tasks.
+    pub background_limits: TaskLimits,
+    /// Limits for tasks whose verb is owned by a wizard, in place of the two above. `None` means
+    /// wizards get the same limits as everyone else.
+    pub wizard_limits: Option<TaskLimits>,
+    /// Record which opcodes and lines each task executes, for `coverage()`. Meant for test
+    /// harnesses; off in normal operation.
+    pub coverage: bool,
+    /// Run one task at a time, handing the turn over in a fixed order, so that what a set of
+    /// tasks does together is the same every time. With a `Clock::Virtual`, timed suspensions
+    /// and fork delays are skipped over rather than waited out. Meant for test harnesses; off in
+    /// normal operation.
+    pub deterministic: bool,
+    /// Where the scheduler and the time builtins get the time from.
+    pub clock: Clock,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            textdump_output: None,
+            max_tasks_per_player: None,
+            foreground_limits: TaskLimits::FOREGROUND,
+            background_limits: TaskLimits::BACKGROUND,
+            wizard_limits: None,
+            coverage: false,
+            deterministic: false,
+            clock: Clock::System,
+        }
+    }
+}
+
+impl Config {
+    /// The limits for a task running a verb whose owner has `owner_flags`.
+    // TODO: Honour in-core overrides ($server_options.fg_ticks etc.), as LambdaMOO does.
+    pub fn task_limits(&self, owner_flags: BitEnum<ObjFlag>, is_background: bool) -> TaskLimits {
+        match self.wizard_limits {
+            Some(limits) if owner_flags.contains(ObjFlag::Wizard) => limits,
+            _ if is_background => self.background_limits,
+            _ => self.foreground_limits,
+        }
+    }
 }
@@ -36,6 +36,40 @@ pub trait MatchEnvironment {
     fn location_of(&mut self, player: Objid) -> Result<Objid, WorldStateError>;
 }
 
+/// The special objects the matcher hands back when it can't come up with a real one: for no
+/// object at all, more than one object, and no object by that name. These are `$nothing`,
+/// `$ambiguous_match` and `$failed_match` in a LambdaMOO core, and are taken from there if the
+/// core defines them (see `MatchConstants::from_world_state`); otherwise they're the usual
+/// #-1, #-2 and #-3.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct MatchConstants {
+    pub nothing: Objid,
+    pub ambiguous_match: Objid,
+    pub failed_match: Objid,
+}
+
+impl Default for MatchConstants {
+    fn default() -> Self {
+        Self {
+            nothing: NOTHING,
+            ambiguous_match: AMBIGUOUS,
+            failed_match: FAILED_MATCH,
+        }
+    }
+}
+
+impl MatchConstants {
+    /// Swap the matcher's own sentinels in `oid` for this core's.
+    pub fn translate(&self, oid: Objid) -> Objid {
+        match oid {
+            NOTHING => self.nothing,
+            AMBIGUOUS => self.ambiguous_match,
+            FAILED_MATCH => self.failed_match,
+            _ => oid,
+        }
+    }
+}
+
 #[derive(Clone, Eq, PartialEq, Debug)]
 struct MatchData {
     exact: Objid,
@@ -82,13 +116,22 @@ pub fn match_contents<M: MatchEnvironment>(
     env: &mut M,
     player: Objid,
     object_name: &str,
+) -> Result<Option<Objid>, WorldStateError> {
+    let search = env.get_surroundings(player)?; // location, contents, player
+    match_in(env, &search, object_name)
+}
+
+/// Match `object_name` against the names of the objects in `search`.
+pub fn match_in<M: MatchEnvironment>(
+    env: &mut M,
+    search: &ObjSet,
+    object_name: &str,
 ) -> Result<Option<Objid>, WorldStateError> {
     let mut match_data = MatchData {
         exact: NOTHING,
         partial: FAILED_MATCH,
     };
 
-    let search = env.get_surroundings(player)?; // location, contents, player
     for oid in search.iter() {
         if !env.obj_valid(oid)? {
             continue;
@@ -110,9 +153,14 @@ pub fn match_contents<M: MatchEnvironment>(
 pub struct MatchEnvironmentParseMatcher<M: MatchEnvironment> {
     pub env: M,
     pub player: Objid,
+    pub constants: MatchConstants,
 }
 
 impl<M: MatchEnvironment> ParseMatcher for MatchEnvironmentParseMatcher<M> {
+    fn nothing(&self) -> Objid {
+        self.constants.nothing
+    }
+
     fn match_object(&mut self, object_name: &str) -> Result<Option<Objid>, WorldStateError> {
         if object_name.is_empty() {
             return Ok(None);
@@ -143,7 +191,8 @@ impl<M: MatchEnvironment> ParseMatcher for MatchEnvironmentParseMatcher<M> {
             return Ok(Some(self.env.location_of(self.player)?));
         }
 
-        match_contents(&mut self.env, self.player, object_name)
+        Ok(match_contents(&mut self.env, self.player, object_name)?
+            .map(|oid| self.constants.translate(oid)))
     }
 }
 
@@ -153,7 +202,7 @@ mod tests {
     use moor_values::{FAILED_MATCH, NOTHING};
 
     use crate::matching::match_env::{
-        do_match_object_names, MatchData, MatchEnvironmentParseMatcher,
+        do_match_object_names, MatchConstants, MatchData, MatchEnvironmentParseMatcher,
     };
     use crate::matching::mock_matching_env::{
         setup_mock_environment, MOCK_PLAYER, MOCK_ROOM1, MOCK_THING1, MOCK_THING2,
@@ -278,6 +327,7 @@ mod tests {
         let mut menv = MatchEnvironmentParseMatcher {
             env,
             player: MOCK_PLAYER,
+            constants: MatchConstants::default(),
         };
         let result = menv.match_object("");
         assert_eq!(result.unwrap(), None);
@@ -289,6 +339,7 @@ mod tests {
         let mut menv = MatchEnvironmentParseMatcher {
             env,
             player: MOCK_PLAYER,
+            constants: MatchConstants::default(),
         };
         let result = menv.match_object("#4");
         assert_eq!(result.unwrap(), Some(MOCK_THING1));
@@ -300,6 +351,7 @@ mod tests {
         let mut menv = MatchEnvironmentParseMatcher {
             env,
             player: MOCK_PLAYER,
+            constants: MatchConstants::default(),
         };
         let result = menv.match_object("me");
         assert_eq!(result.unwrap(), Some(MOCK_PLAYER));
@@ -311,6 +363,7 @@ mod tests {
         let mut menv = MatchEnvironmentParseMatcher {
             env,
             player: MOCK_PLAYER,
+            constants: MatchConstants::default(),
         };
         let result = menv.match_object("here");
         assert_eq!(result.unwrap(), Some(MOCK_ROOM1));
@@ -322,6 +375,7 @@ mod tests {
         let mut menv = MatchEnvironmentParseMatcher {
             env,
             player: MOCK_PLAYER,
+            constants: MatchConstants::default(),
         };
         let result = menv.match_object("room1");
         assert_eq!(result.unwrap(), Some(MOCK_ROOM1));
@@ -333,6 +387,7 @@ mod tests {
         let mut menv = MatchEnvironmentParseMatcher {
             env,
             player: MOCK_PLAYER,
+            constants: MatchConstants::default(),
         };
         let result = menv.match_object("r1");
         assert_eq!(result.unwrap(), Some(MOCK_ROOM1));
@@ -344,6 +399,7 @@ mod tests {
         let mut menv = MatchEnvironmentParseMatcher {
             env,
             player: MOCK_PLAYER,
+            constants: MatchConstants::default(),
         };
         let result = menv.match_object("porcupine");
         assert_eq!(result.unwrap(), Some(MOCK_PLAYER));
@@ -355,6 +411,7 @@ mod tests {
         let mut menv = MatchEnvironmentParseMatcher {
             env,
             player: MOCK_PLAYER,
+            constants: MatchConstants::default(),
         };
         let result = menv.match_object("thing1");
         assert_eq!(result.unwrap(), Some(MOCK_THING1));
@@ -366,6 +423,7 @@ mod tests {
         let mut menv = MatchEnvironmentParseMatcher {
             env,
             player: MOCK_PLAYER,
+            constants: MatchConstants::default(),
         };
         let result = menv.match_object("t2");
         assert_eq!(result.unwrap(), Some(MOCK_THING2));
@@ -377,8 +435,27 @@ mod tests {
         let mut menv = MatchEnvironmentParseMatcher {
             env,
             player: NOTHING,
+            constants: MatchConstants::default(),
         };
         let result = menv.match_object("thing1");
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_match_object_core_constants() {
+        let constants = MatchConstants {
+            nothing: Objid(-10),
+            ambiguous_match: Objid(-20),
+            failed_match: Objid(-30),
+        };
+        let mut menv = MatchEnvironmentParseMatcher {
+            env: setup_mock_environment(),
+            player: MOCK_PLAYER,
+            constants,
+        };
+        assert_eq!(menv.match_object("durian").unwrap(), Some(Objid(-30)));
+        assert_eq!(menv.match_object("t").unwrap(), Some(Objid(-20)));
+        assert_eq!(menv.match_object("thing1").unwrap(), Some(MOCK_THING1));
+        assert_eq!(menv.nothing(), Objid(-10));
+    }
 }
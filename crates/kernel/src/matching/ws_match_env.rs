@@ -16,9 +16,30 @@ use moor_values::model::ObjSet;
 
 use moor_values::model::WorldState;
 use moor_values::model::WorldStateError;
-use moor_values::var::Objid;
+use moor_values::var::{Objid, Variant};
+use moor_values::SYSTEM_OBJECT;
 
-use crate::matching::match_env::MatchEnvironment;
+use crate::matching::match_env::{MatchConstants, MatchEnvironment};
+
+impl MatchConstants {
+    /// Look up `$nothing`, `$ambiguous_match` and `$failed_match` on #0, keeping the defaults for
+    /// any the core doesn't define as objects (or that `perms` can't read).
+    pub fn from_world_state(ws: &dyn WorldState, perms: Objid) -> Self {
+        let mut constants = Self::default();
+        for (name, constant) in [
+            ("nothing", &mut constants.nothing),
+            ("ambiguous_match", &mut constants.ambiguous_match),
+            ("failed_match", &mut constants.failed_match),
+        ] {
+            if let Ok(value) = ws.retrieve_property(perms, SYSTEM_OBJECT, name) {
+                if let Variant::Obj(oid) = value.variant() {
+                    *constant = *oid;
+                }
+            }
+        }
+        constants
+    }
+}
 
 /// A "match environment" which matches out of the current DB world state.
 pub struct WsMatchEnv<'a> {
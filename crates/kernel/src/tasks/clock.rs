@@ -0,0 +1,52 @@
+// Copyright (C) 2024 Ryan Daum <ryan.daum@gmail.com>
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU General Public License as published by the Free Software
+// Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// this program. If not, see <https://www.gnu.org/licenses/>.
+//
+
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+/// Where the scheduler (for suspensions, fork delays and retries) and the time builtins get the
+/// current time from.
+#[derive(Debug, Clone, Default)]
+pub enum Clock {
+    /// The real time.
+    #[default]
+    System,
+    /// A clock that stands still until it's moved forward with `advance_to`. In deterministic
+    /// mode, the scheduler does that whenever every task is waiting on it, so that tests don't
+    /// have to sit out the delays they're testing.
+    Virtual(Arc<Mutex<SystemTime>>),
+}
+
+impl Clock {
+    /// A virtual clock which reads `start` until it's advanced.
+    pub fn virtual_at(start: SystemTime) -> Self {
+        Self::Virtual(Arc::new(Mutex::new(start)))
+    }
+
+    pub fn now(&self) -> SystemTime {
+        match self {
+            Self::System => SystemTime::now(),
+            Self::Virtual(now) => *now.lock().unwrap(),
+        }
+    }
+
+    /// Move a virtual clock forward to `time`, if it isn't already past it. The system clock
+    /// can't be moved, so is left alone.
+    pub fn advance_to(&self, time: SystemTime) {
+        if let Self::Virtual(now) = self {
+            let mut now = now.lock().unwrap();
+            *now = (*now).max(time);
+        }
+    }
+}
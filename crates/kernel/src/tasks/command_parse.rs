@@ -22,6 +22,7 @@ use moor_values::model::{PrepSpec, Preposition, PREP_LIST};
 use moor_values::util;
 use moor_values::var::Objid;
 use moor_values::var::{v_str, Var};
+use moor_values::NOTHING;
 
 lazy_static! {
     static ref PREPOSITIONS: Vec<Prep> = {
@@ -63,11 +64,13 @@ pub struct Prep {
 
 /// Match a preposition for the form used by set_verb_args and friends, which means it must support
 /// numeric arguments for the preposition.
+/// Like LambdaMOO, only the part before any '/' is matched, so the full "on top of/on/onto/upon"
+/// form that verb_args returns can be handed straight back.
 pub fn parse_preposition_spec(repr: &str) -> Option<PrepSpec> {
     match repr {
         "any" => Some(PrepSpec::Any),
         "none" => Some(PrepSpec::None),
-        _ => find_preposition(repr)
+        _ => find_preposition(repr.split('/').next().unwrap_or(repr))
             .map(|p| PrepSpec::Other(Preposition::from_repr(p.id as u16).unwrap())),
     }
 }
@@ -105,6 +108,11 @@ fn match_preposition(prep: &str) -> Option<Prep> {
 
 pub trait ParseMatcher {
     fn match_object(&mut self, name: &str) -> Result<Option<Objid>, WorldStateError>;
+
+    /// What to use for an object the command doesn't name at all.
+    fn nothing(&self) -> Objid {
+        NOTHING
+    }
 }
 
 #[derive(thiserror::Error, Debug, Clone, Decode, Encode)]
@@ -218,11 +226,11 @@ where
         argstr,
         args,
         dobjstr,
-        dobj: dobj.unwrap_or(Objid(-1)),
+        dobj: dobj.unwrap_or(command_environment.nothing()),
         prepstr,
         prep,
         iobjstr,
-        iobj: iobj.unwrap_or(Objid(-1)),
+        iobj: iobj.unwrap_or(command_environment.nothing()),
     })
 }
 
@@ -233,7 +241,7 @@ mod tests {
     use moor_values::var::v_str;
     use moor_values::{FAILED_MATCH, NOTHING};
 
-    use crate::matching::match_env::MatchEnvironmentParseMatcher;
+    use crate::matching::match_env::{MatchConstants, MatchEnvironmentParseMatcher};
     use crate::matching::mock_matching_env::{
         setup_mock_environment, MOCK_PLAYER, MOCK_ROOM1, MOCK_THING1, MOCK_THING2,
     };
@@ -427,6 +435,7 @@ mod tests {
         let match_object_fn = MatchEnvironmentParseMatcher {
             env,
             player: MOCK_PLAYER,
+            constants: MatchConstants::default(),
         };
         let result = parse_command("get thing1", match_object_fn).unwrap();
         assert_eq!(result.verb, "get".to_string());
@@ -446,6 +455,7 @@ mod tests {
         let match_object_fn = MatchEnvironmentParseMatcher {
             env,
             player: MOCK_PLAYER,
+            constants: MatchConstants::default(),
         };
 
         let result = parse_command("put thing1 in t2", match_object_fn).unwrap();
@@ -466,6 +476,7 @@ mod tests {
         let match_object_fn = MatchEnvironmentParseMatcher {
             env,
             player: MOCK_PLAYER,
+            constants: MatchConstants::default(),
         };
 
         let result = parse_command("look at here", match_object_fn).unwrap();
@@ -486,6 +497,7 @@ mod tests {
         let match_object_fn = MatchEnvironmentParseMatcher {
             env,
             player: MOCK_PLAYER,
+            constants: MatchConstants::default(),
         };
 
         // We had a regression where the first numeric argument was being confused with a
@@ -502,4 +514,23 @@ mod tests {
         assert_eq!(result.iobjstr, "".to_string());
         assert_eq!(result.iobj, NOTHING);
     }
+
+    #[test]
+    fn test_preposition_spec_round_trip() {
+        for (id, phrases) in PREP_LIST.iter().enumerate() {
+            let spec = PrepSpec::Other(Preposition::from_repr(id as u16).unwrap());
+            assert_eq!(preposition_to_string(&spec), *phrases);
+            assert_eq!(parse_preposition_spec(phrases), Some(spec), "{phrases}");
+            assert_eq!(parse_preposition_spec(&format!("#{id}")), Some(spec));
+            for phrase in phrases.split('/') {
+                assert_eq!(parse_preposition_spec(phrase), Some(spec), "{phrase}");
+            }
+        }
+        for spec in [PrepSpec::Any, PrepSpec::None] {
+            assert_eq!(
+                parse_preposition_spec(preposition_to_string(&spec)),
+                Some(spec)
+            );
+        }
+    }
 }
@@ -0,0 +1,71 @@
+// Copyright (C) 2024 Ryan Daum <ryan.daum@gmail.com>
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU General Public License as published by the Free Software
+// Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// this program. If not, see <https://www.gnu.org/licenses/>.
+//
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Mutex;
+
+use uuid::Uuid;
+
+use moor_values::var::Objid;
+
+/// The opcodes and source lines of one verb that have executed.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct VerbCoverage {
+    /// The name the verb was first called by.
+    pub name: String,
+    /// Offsets into the verb's main vector.
+    pub opcodes: BTreeSet<usize>,
+    pub lines: BTreeSet<usize>,
+}
+
+/// What a single task has executed, per verb. Only kept when the server is running in coverage
+/// mode; otherwise the VM never sees one of these and pays nothing for it.
+/// Verbs are keyed by their definer and verbdef uuid, as in `VerbMetrics`.
+#[derive(Debug, Default)]
+pub struct TaskCoverage {
+    verbs: Mutex<BTreeMap<(Objid, Uuid), VerbCoverage>>,
+}
+
+impl TaskCoverage {
+    pub fn record(
+        &self,
+        definer: Objid,
+        verb: Uuid,
+        name: &str,
+        opcode: usize,
+        line: Option<usize>,
+    ) {
+        let mut verbs = self.verbs.lock().unwrap();
+        let coverage = verbs
+            .entry((definer, verb))
+            .or_insert_with(|| VerbCoverage {
+                name: name.to_string(),
+                ..Default::default()
+            });
+        coverage.opcodes.insert(opcode);
+        if let Some(line) = line {
+            coverage.lines.insert(line);
+        }
+    }
+
+    /// A snapshot of the coverage so far, ordered by definer and verb.
+    pub fn verbs(&self) -> Vec<(Objid, VerbCoverage)> {
+        self.verbs
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|((definer, _), coverage)| (*definer, coverage.clone()))
+            .collect()
+    }
+}
@@ -17,14 +17,18 @@ use moor_values::var::Var;
 use std::cell::Cell;
 use std::marker::PhantomData;
 use std::sync::MutexGuard;
-use std::time::SystemTime;
+use std::time::{Duration, SystemTime};
 
+pub mod clock;
 pub mod command_parse;
+pub mod coverage;
+pub mod program_cache;
 pub mod scheduler;
 pub mod sessions;
 
 mod task;
 pub mod task_messages;
+pub mod verb_metrics;
 pub mod vm_host;
 
 pub type TaskId = usize;
@@ -54,10 +58,26 @@ pub struct TaskDescription {
     pub verb_definer: Objid,
     pub line_number: usize,
     pub this: Objid,
+    /// Why the task isn't running. Filled in by the scheduler, which owns the timers.
+    pub suspension: Option<TaskSuspension>,
+}
+
+/// Why a queued task is waiting.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum TaskSuspension {
+    /// Suspended or forked with a delay; will wake by itself after `remaining`.
+    Timed { remaining: Duration },
+    /// Suspended without a delay; waits until it is resumed.
+    Indefinite,
+    /// Waiting for a line of input from the player, e.g. via `read()`.
+    AwaitingInput,
 }
 
 pub mod vm_test_utils {
+    use crate::config::TaskLimits;
+    use crate::tasks::program_cache::ProgramCache;
     use crate::tasks::sessions::Session;
+    use crate::tasks::verb_metrics::VerbMetrics;
     use crate::tasks::vm_host::{VMHostResponse, VmHost};
     use crate::tasks::VerbCall;
     use crate::vm::VmExecParams;
@@ -65,28 +85,57 @@ pub mod vm_test_utils {
     use moor_values::var::Var;
     use moor_values::SYSTEM_OBJECT;
     use std::sync::Arc;
-    use std::time::Duration;
 
     pub fn call_verb(
         world_state: &mut dyn WorldState,
         session: Arc<dyn Session>,
         verb_name: &str,
         args: Vec<Var>,
+    ) -> Var {
+        run_verb(world_state, session, verb_name, args, false)
+    }
+
+    /// As `call_verb`, but with the task in coverage mode, so the verb can use `coverage()`.
+    pub fn call_verb_with_coverage(
+        world_state: &mut dyn WorldState,
+        session: Arc<dyn Session>,
+        verb_name: &str,
+        args: Vec<Var>,
+    ) -> Var {
+        run_verb(world_state, session, verb_name, args, true)
+    }
+
+    fn run_verb(
+        world_state: &mut dyn WorldState,
+        session: Arc<dyn Session>,
+        verb_name: &str,
+        args: Vec<Var>,
+        coverage: bool,
     ) -> Var {
         let (scs_tx, _scs_rx) = kanal::unbounded();
         let mut vm_host = VmHost::new(
             0,
-            20,
-            90_000,
-            Duration::from_secs(5),
+            TaskLimits {
+                max_ticks: 90_000,
+                max_seconds: 5,
+                max_stack_depth: 20,
+            },
             session.clone(),
             scs_tx,
+            Arc::new(VerbMetrics::default()),
+            Arc::new(ProgramCache::default()),
         );
+        if coverage {
+            vm_host.enable_coverage();
+        }
 
         let (sched_send, _) = kanal::unbounded();
         let _vm_exec_params = VmExecParams {
             scheduler_sender: sched_send.clone(),
             max_stack_depth: 50,
+            verb_metrics: Arc::new(VerbMetrics::default()),
+            coverage: None,
+            clock: Default::default(),
         };
 
         let vi = world_state
@@ -131,7 +180,7 @@ pub mod vm_test_utils {
                 VMHostResponse::Suspend(_) => {
                     panic!("Unexpected suspend");
                 }
-                VMHostResponse::SuspendNeedInput => {
+                VMHostResponse::SuspendNeedInput(_) => {
                     panic!("Unexpected suspend need input");
                 }
             }
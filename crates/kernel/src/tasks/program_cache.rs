@@ -0,0 +1,78 @@
+// Copyright (C) 2024 Ryan Daum <ryan.daum@gmail.com>
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU General Public License as published by the Free Software
+// Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// this program. If not, see <https://www.gnu.org/licenses/>.
+//
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+use dashmap::DashMap;
+use tracing::warn;
+use uuid::Uuid;
+
+use moor_compiler::{compile, CompileError, Program};
+use moor_values::model::{BinaryType, HasUuid, Named, VerbDef};
+use moor_values::util::SliceRef;
+use moor_values::var::{v_string, Objid};
+use moor_values::AsByteBuffer;
+
+/// Turn a verb's stored binary into a program, compiling it if it's stored as source.
+pub fn decode_program(binary_type: BinaryType, binary: &[u8]) -> Result<Program, CompileError> {
+    match binary_type {
+        BinaryType::LambdaMoo18X => Ok(Program::from_sliceref(SliceRef::from_bytes(binary))
+            .expect("Could not decode MOO program")),
+        BinaryType::MooSource => compile(&String::from_utf8_lossy(binary)),
+        _ => panic!("Unsupported binary type {:?}", binary_type),
+    }
+}
+
+/// Programs compiled from verbs stored as source (see `BinaryType::MooSource`), shared by all
+/// tasks so that each such verb is compiled once, on its first call, rather than at import.
+/// Entries are keyed by definer and verbdef uuid, and remember a hash of the source they were
+/// compiled from, so a verb whose source is replaced gets compiled again.
+#[derive(Default)]
+pub struct ProgramCache {
+    programs: DashMap<(Objid, Uuid), (u64, Program)>,
+}
+
+impl ProgramCache {
+    /// The program for a verb with the given definition and stored binary.
+    /// A verb whose source doesn't compile gets a program that raises `E_INVARG` with the
+    /// compiler's complaint, so that the error surfaces in whichever task calls it.
+    pub fn program_for(&self, verbdef: &VerbDef, binary: &[u8]) -> Program {
+        if verbdef.binary_type() != BinaryType::MooSource {
+            return decode_program(verbdef.binary_type(), binary)
+                .expect("Could not decode MOO program");
+        }
+
+        let mut hasher = DefaultHasher::new();
+        binary.hash(&mut hasher);
+        let source_hash = hasher.finish();
+
+        let key = (verbdef.location(), verbdef.uuid());
+        if let Some(entry) = self.programs.get(&key) {
+            if entry.0 == source_hash {
+                return entry.1.clone();
+            }
+        }
+
+        let program = decode_program(BinaryType::MooSource, binary).unwrap_or_else(|e| {
+            warn!(definer = ?verbdef.location(), names = ?verbdef.names(), error = ?e,
+                "Verb source failed to compile");
+            let message = v_string(format!("Verb failed to compile: {}", e));
+            compile(&format!("raise(E_INVARG, {});", message.to_literal()))
+                .expect("Could not compile compile-error stub")
+        });
+        self.programs.insert(key, (source_hash, program.clone()));
+        program
+    }
+}
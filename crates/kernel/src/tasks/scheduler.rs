@@ -12,14 +12,14 @@
 // this program. If not, see <https://www.gnu.org/licenses/>.
 //
 
-use std::fs::File;
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, SystemTime};
 
 use bincode::{Decode, Encode};
 use dashmap::DashMap;
-use kanal::{OneshotReceiver, OneshotSender, Sender};
+use kanal::{OneshotSender, ReceiveErrorTimeout, SendError, Sender};
 
 use thiserror::Error;
 use tracing::{debug, error, info, instrument, trace, warn};
@@ -33,30 +33,39 @@ use moor_compiler::compile;
 use moor_compiler::CompileError;
 use moor_db::Database;
 use moor_values::model::CommandError;
+use moor_values::model::ObjFlag;
 use moor_values::model::Perms;
 use moor_values::model::WorldStateSource;
 use moor_values::var::Error::{E_INVARG, E_PERM};
 use moor_values::var::{v_err, v_int, v_none, v_string, Var};
-use moor_values::var::{Objid, Variant};
+use moor_values::var::{Error, Objid, Variant};
 use moor_values::SYSTEM_OBJECT;
 use SchedulerError::{
     CommandExecutionError, CouldNotStartTask, EvalCompilationError, InputRequestNotFound,
-    TaskAbortedCancelled, TaskAbortedError, TaskAbortedException, TaskAbortedLimit,
+    TaskAbortedCancelled, TaskAbortedError, TaskAbortedException, TaskAbortedLimit, TooManyTasks,
 };
 
-use crate::config::Config;
+use crate::config::{Config, TaskLimits};
+use crate::tasks::program_cache::ProgramCache;
 use crate::tasks::scheduler::SchedulerError::TaskNotFound;
 use crate::tasks::sessions::Session;
 use crate::tasks::task::Task;
-use crate::tasks::task_messages::{SchedulerControlMsg, TaskControlMsg, TaskStart};
-use crate::tasks::{TaskDescription, TaskId};
-use crate::textdump::{make_textdump, TextdumpWriter};
+use crate::tasks::task_messages::{QueuedVerbCall, SchedulerControlMsg, TaskControlMsg, TaskStart};
+use crate::tasks::verb_metrics::VerbMetrics;
+use crate::tasks::{TaskDescription, TaskId, TaskSuspension};
+use crate::textdump::textdump_save;
+use crate::vm::activation::Caller;
 use crate::vm::Fork;
 use crate::vm::UncaughtException;
 
 const SCHEDULER_TICK_TIME: Duration = Duration::from_millis(5);
 const METRICS_POLLER_TICK_TIME: Duration = Duration::from_secs(5);
 
+/// How many times a task is restarted after its commit conflicts, before it's given up on.
+const MAX_CONFLICT_RETRIES: u8 = 5;
+/// How long to wait before the first restart after a conflict; doubled on each further attempt.
+const CONFLICT_RETRY_BACKOFF: Duration = Duration::from_millis(10);
+
 /// Responsible for the dispatching, control, and accounting of tasks in the system.
 /// There should be only one scheduler per server.
 pub struct Scheduler {
@@ -64,11 +73,37 @@ pub struct Scheduler {
     control_receiver: Receiver<(TaskId, SchedulerControlMsg)>,
     config: Arc<Config>,
 
-    running: Arc<Mutex<bool>>,
+    running: AtomicBool,
     database: Arc<dyn Database + Send + Sync>,
     next_task_id: AtomicUsize,
+    /// All live tasks. This is sharded, so spawning and looking up tasks from many threads at once
+    /// doesn't funnel through a single lock.
     tasks: DashMap<TaskId, TaskControl>,
+    /// How many live tasks each player has, kept alongside `tasks` so the per-player limit can be
+    /// checked without walking the whole task map on every spawn.
+    player_task_counts: DashMap<Objid, usize>,
     input_requests: DashMap<Uuid, TaskId>,
+    /// The input requests waiting on each player, oldest first. Clients only keep track of one
+    /// outstanding request at a time, so only the head of each queue has been put to the player;
+    /// the rest are asked for in turn, which hands lines to readers in the order they called
+    /// `read()`.
+    input_queues: Mutex<HashMap<Objid, VecDeque<(Uuid, TaskId)>>>,
+    verb_metrics: Arc<VerbMetrics>,
+    /// Programs compiled on first call from verbs imported as source.
+    program_cache: Arc<ProgramCache>,
+    /// Whose turn it is to run, in deterministic mode.
+    turns: Turns,
+}
+
+/// In deterministic mode (see `Config::deterministic`), only one task runs at a time. Tasks that
+/// are ready to go wait here for their turn, in the order they became ready, along with the
+/// message that will set them going. Once nobody is waiting, the turn goes to whichever timed
+/// suspension, fork, or retry is due first, with a virtual clock moved forward to it.
+#[derive(Default)]
+struct Turns {
+    /// The task whose turn it is, if any. Only the scheduler loop looks at this.
+    running: Mutex<Option<TaskId>>,
+    waiting: Mutex<VecDeque<(TaskId, TaskControlMsg)>>,
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Decode, Encode)]
@@ -105,6 +140,8 @@ pub enum SchedulerError {
     TaskAbortedException(UncaughtException),
     #[error("Task aborted due to cancellation.")]
     TaskAbortedCancelled,
+    #[error("Too many tasks for player {0}")]
+    TooManyTasks(Objid),
 }
 
 struct KillRequest {
@@ -122,9 +159,15 @@ struct ResumeRequest {
     result_sender: OneshotSender<Var>,
 }
 
+struct TaskStackRequest {
+    queued_task_id: TaskId,
+    sender_permissions: Perms,
+    result_sender: OneshotSender<Result<Vec<Caller>, Error>>,
+}
+
 struct ForkRequest {
     fork_request: Fork,
-    reply: OneshotSender<TaskId>,
+    reply: OneshotSender<Result<TaskId, SchedulerError>>,
     session: Arc<dyn Session>,
 }
 
@@ -138,23 +181,55 @@ struct TaskControl {
     state_source: Arc<dyn WorldStateSource>,
     session: Arc<dyn Session>,
     suspended: bool,
-    waiting_input: Option<Uuid>,
+    /// A delayed fork that hasn't started yet. It's waiting out its delay rather than sitting in
+    /// `suspend()`, so there's nothing for `resume()` to hand a value to.
+    pending_fork: bool,
+    /// For a fork, the task that forked it, until that task has committed its transaction (by
+    /// finishing or suspending). The fork doesn't start before then, so that it sees what its
+    /// parent did.
+    forked_by: Option<TaskId>,
+    /// The input request the task is blocked on in `read()`, and the player it was put to.
+    waiting_input: Option<(Uuid, Objid)>,
     resume_time: Option<SystemTime>,
-    // subscribers for when the task is aborted, succeeded, etc.
-    subscribers: Mutex<Vec<OneshotSender<TaskWaiterResult>>>,
+    /// How many times the task has been restarted after a commit conflict.
+    conflict_retries: u8,
+    /// When a task backing off after a commit conflict should be restarted.
+    retry_time: Option<SystemTime>,
+    // subscribers for when the task is aborted, succeeded, etc. These aren't oneshot channels,
+    // because sending on one of those waits for the receiver, and the scheduler can't wait.
+    subscribers: Mutex<Vec<Sender<TaskWaiterResult>>>,
     _join_handle: std::thread::JoinHandle<()>,
 }
 
+impl TaskControl {
+    /// Why the task is waiting, or `None` if it's running, as of `now`.
+    fn suspension(&self, now: SystemTime) -> Option<TaskSuspension> {
+        if self.waiting_input.is_some() {
+            return Some(TaskSuspension::AwaitingInput);
+        }
+        if !self.suspended {
+            return None;
+        }
+        match self.resume_time {
+            Some(resume_time) => Some(TaskSuspension::Timed {
+                remaining: resume_time.duration_since(now).unwrap_or(Duration::ZERO),
+            }),
+            None => Some(TaskSuspension::Indefinite),
+        }
+    }
+}
+
 /// The set of actions that the scheduler needs to take in response to a task control message.
 enum TaskHandleResult {
     Remove(TaskId),
     Notify(TaskId, TaskWaiterResult),
     Fork(ForkRequest),
+    DispatchCalls(Vec<QueuedVerbCall>, Arc<dyn Session>),
     Describe(TaskId, OneshotSender<Vec<TaskDescription>>),
     Kill(KillRequest),
     Resume(ResumeRequest),
+    TaskStack(TaskStackRequest),
     Disconnect(TaskId, Objid),
-    Retry(TaskId),
 }
 
 /// Public facing interface for the scheduler.
@@ -163,11 +238,16 @@ impl Scheduler {
         let config = Arc::new(config);
         let (control_sender, control_receiver) = kanal::unbounded();
         Self {
-            running: Arc::new(Mutex::new(false)),
+            running: AtomicBool::new(false),
             database,
             next_task_id: Default::default(),
             tasks: DashMap::new(),
+            player_task_counts: DashMap::new(),
             input_requests: Default::default(),
+            input_queues: Default::default(),
+            verb_metrics: Default::default(),
+            program_cache: Default::default(),
+            turns: Default::default(),
             config: config.clone(),
             control_sender,
             control_receiver,
@@ -177,9 +257,9 @@ impl Scheduler {
     pub fn subscribe_to_task(
         &self,
         task_id: TaskId,
-    ) -> Result<OneshotReceiver<TaskWaiterResult>, SchedulerError> {
+    ) -> Result<Receiver<TaskWaiterResult>, SchedulerError> {
         trace!(?task_id, "Subscribing to task");
-        let (sender, receiver) = kanal::oneshot();
+        let (sender, receiver) = kanal::bounded(1);
         if let Some(task) = self.tasks.get_mut(&task_id) {
             let mut subscribers = task.subscribers.lock().unwrap();
             subscribers.push(sender);
@@ -189,15 +269,9 @@ impl Scheduler {
 
     /// Execute the scheduler loop, run from the server process.
     pub fn run(self: Arc<Self>) {
-        {
-            let mut running = self.running.lock().unwrap();
-            *running = true;
-        }
+        self.running.store(true, Ordering::SeqCst);
         self.clone().do_process();
-        {
-            let mut running = self.running.lock().unwrap();
-            *running = false;
-        }
+        self.running.store(false, Ordering::SeqCst);
         info!("Scheduler done.");
     }
 
@@ -220,7 +294,6 @@ impl Scheduler {
             task_start,
             player,
             session,
-            None,
             self.control_sender.clone(),
             player,
             false,
@@ -244,36 +317,49 @@ impl Scheduler {
         // Validate that the given input request is valid, and if so, resume the task, sending it
         // the given input, clearing the input request out.
 
-        let Some(task_ref) = self.input_requests.get(&input_request_id) else {
+        // Copy the task id out rather than holding on to the entry, since we'll be removing it
+        // from the same map below.
+        let Some(task_id) = self.input_requests.get(&input_request_id).map(|r| *r) else {
             return Err(InputRequestNotFound(input_request_id.as_u128()));
         };
 
-        let (_uuid, task_id) = (task_ref.key(), task_ref.value());
-        let Some(mut task) = self.tasks.get_mut(task_id) else {
-            warn!(?task_id, ?input_request_id, "Input received for dead task");
-            return Err(TaskNotFound(*task_id));
-        };
-
-        // If the player doesn't match, we'll pretend we didn't even see it.
-        if task.player != player {
+        // Only the request at the head of the player's queue has been put to them; anything else
+        // is a line for a reader whose turn hasn't come yet.
+        let asked = self
+            .input_queues
+            .lock()
+            .unwrap()
+            .get(&player)
+            .and_then(|queue| queue.front())
+            .map(|(asked, _)| *asked);
+        if asked != Some(input_request_id) {
             warn!(
                 ?task_id,
                 ?input_request_id,
                 ?player,
-                "Task input request received for wrong player"
+                "Input received for a request that wasn't put to the player"
             );
-            return Err(TaskNotFound(*task_id));
+            return Err(InputRequestNotFound(input_request_id.as_u128()));
         }
 
-        // Now we can resume the task with the given input
-        let tcs = task.task_control_sender.clone();
-        tcs.send(TaskControlMsg::ResumeReceiveInput(
-            task.state_source.clone(),
-            input,
-        ))
-        .map_err(|_| CouldNotStartTask)?;
-        task.waiting_input = None;
+        {
+            let Some(mut task) = self.tasks.get_mut(&task_id) else {
+                warn!(?task_id, ?input_request_id, "Input received for dead task");
+                return Err(TaskNotFound(task_id));
+            };
+
+            // Now we can resume the task with the given input
+            let tcs = task.task_control_sender.clone();
+            self.set_going(
+                task_id,
+                &tcs,
+                TaskControlMsg::ResumeReceiveInput(task.state_source.clone(), input),
+            )
+            .map_err(|_| CouldNotStartTask)?;
+            task.waiting_input = None;
+        }
         self.input_requests.remove(&input_request_id);
+        self.dequeue_input_request(player, input_request_id);
 
         Ok(())
     }
@@ -306,7 +392,6 @@ impl Scheduler {
             task_start,
             player,
             session,
-            None,
             self.control_sender.clone(),
             perms,
             false,
@@ -336,7 +421,6 @@ impl Scheduler {
             task_start,
             player,
             session,
-            None,
             self.control_sender.clone(),
             player,
             false,
@@ -345,13 +429,15 @@ impl Scheduler {
         Ok(task_id)
     }
 
-    /// Submit an eval task to the scheduler for execution.
+    /// Submit an eval task to the scheduler for execution. With `show_result`, the player is
+    /// notified of the result once the task completes.
     #[instrument(skip(self, sessions))]
     pub fn submit_eval_task(
         &self,
         player: Objid,
         perms: Objid,
         code: String,
+        show_result: bool,
         sessions: Arc<dyn Session>,
     ) -> Result<TaskId, SchedulerError> {
         // Compile the text into a verb.
@@ -363,13 +449,13 @@ impl Scheduler {
         let task_start = TaskStart::StartEval {
             player,
             program: binary,
+            show_result,
         };
 
         let task_id = self.new_task(
             task_start,
             player,
             sessions,
-            None,
             self.control_sender.clone(),
             perms,
             false,
@@ -410,7 +496,7 @@ impl Scheduler {
                 warn!(task_id, error = ?e, "Could not request task description for task. Dead?");
                 continue;
             }
-            let Ok(task_desc) = t_reply.recv() else {
+            let Ok(mut task_desc) = t_reply.recv() else {
                 warn!(
                     task_id,
                     "Could not request task description for task. Dead?"
@@ -418,6 +504,7 @@ impl Scheduler {
                 continue;
             };
             trace!(task_id, "Got task description");
+            task_desc.suspension = task.suspension(self.config.clock.now());
             tasks.push(task_desc);
         }
         Ok(tasks)
@@ -438,8 +525,7 @@ impl Scheduler {
         while !self.tasks.is_empty() {
             yield_now();
         }
-        let mut runlock = self.running.lock().unwrap();
-        (*runlock) = false;
+        self.running.store(false, Ordering::SeqCst);
 
         Ok(())
     }
@@ -452,6 +538,14 @@ impl Scheduler {
         }
         Ok(())
     }
+
+    /// Replace the limits a task was started with (see `Config::task_limits`).
+    pub fn set_task_limits(&self, id: TaskId, limits: TaskLimits) -> Result<(), SchedulerError> {
+        let task = self.tasks.get(&id).ok_or(TaskNotFound(id))?;
+        task.task_control_sender
+            .send(TaskControlMsg::SetLimits(limits))
+            .map_err(|_| TaskNotFound(id))
+    }
 }
 
 impl Scheduler {
@@ -463,11 +557,7 @@ impl Scheduler {
             .name("metrics-poller".to_string())
             .spawn(move || loop {
                 let this = this.clone();
-                let is_running = {
-                    let running = this.running.lock().unwrap();
-                    *running
-                };
-                if !is_running {
+                if !this.running.load(Ordering::SeqCst) {
                     break;
                 }
                 let mut number_suspended_tasks = 0;
@@ -515,14 +605,11 @@ impl Scheduler {
             .name("scheduler-tick".to_string())
             .spawn(move || loop {
                 let this = this.clone();
-                let is_running = {
-                    let running = this.running.lock().unwrap();
-                    *running
-                };
-                if !is_running {
+                if !this.running.load(Ordering::SeqCst) {
                     break;
                 }
                 let mut to_wake = Vec::new();
+                let mut to_retry = Vec::new();
                 let mut to_prune = Vec::new();
                 for t in this.clone().tasks.iter() {
                     let (task_id, task) = (t.key(), t.value());
@@ -537,19 +624,34 @@ impl Scheduler {
                         continue;
                     }
 
-                    if !task.suspended {
+                    // In deterministic mode, waking tasks is left to `take_turns`.
+                    if this.config.deterministic {
+                        continue;
+                    }
+
+                    if let Some(retry_time) = task.retry_time {
+                        if retry_time <= this.config.clock.now() {
+                            to_retry.push(*task_id);
+                        }
+                        continue;
+                    }
+
+                    if !task.suspended || task.forked_by.is_some() {
                         continue;
                     }
                     let Some(delay) = task.resume_time else {
                         continue;
                     };
-                    if delay <= SystemTime::now() {
+                    if delay <= this.config.clock.now() {
                         to_wake.push(*task_id);
                     }
                 }
                 if !to_wake.is_empty() {
                     this.clone().process_wake_ups(&to_wake);
                 }
+                for task_id in to_retry {
+                    to_prune.extend(this.process_retry_request(task_id));
+                }
                 if !to_prune.is_empty() {
                     this.clone().process_task_removals(&to_prune);
                 }
@@ -561,25 +663,38 @@ impl Scheduler {
 
         info!("Starting scheduler loop");
         loop {
-            let is_running = {
-                let running = this.running.lock().unwrap();
-                *running
-            };
-            if !is_running {
+            if !this.running.load(Ordering::SeqCst) {
                 break;
             }
 
-            match this.control_receiver.recv() {
-                Ok((task_id, msg)) => {
-                    let actions = this.clone().clone().handle_task_control_msg(task_id, msg);
-                    if !actions.is_empty() {
-                        this.clone().process_task_actions(actions);
+            // In deterministic mode, don't block: tasks submitted from outside are waiting for
+            // their turn without anything having told us so.
+            let msg = if this.config.deterministic {
+                match this.control_receiver.recv_timeout(SCHEDULER_TICK_TIME) {
+                    Ok(msg) => Some(msg),
+                    Err(ReceiveErrorTimeout::Timeout) => None,
+                    Err(e) => {
+                        error!(reason = ?e, "Scheduler control channel closed");
+                        return;
                     }
                 }
-                Err(e) => {
-                    error!(reason = ?e, "Scheduler control channel closed");
-                    return;
+            } else {
+                match this.control_receiver.recv() {
+                    Ok(msg) => Some(msg),
+                    Err(e) => {
+                        error!(reason = ?e, "Scheduler control channel closed");
+                        return;
+                    }
                 }
+            };
+            if let Some((task_id, msg)) = msg {
+                let actions = this.clone().clone().handle_task_control_msg(task_id, msg);
+                if !actions.is_empty() {
+                    this.clone().process_task_actions(actions);
+                }
+            }
+            if this.config.deterministic {
+                this.take_turns();
             }
         }
         info!("Done.");
@@ -616,11 +731,47 @@ impl Scheduler {
                 ]
             }
             SchedulerControlMsg::TaskConflictRetry => {
-                trace!(?task_id, "Task retrying due to conflict");
+                let Some(mut task) = self.tasks.get_mut(&task_id) else {
+                    warn!(task_id, "Task not found for conflict retry");
+                    return vec![TaskHandleResult::Remove(task_id)];
+                };
 
-                // Ask the task to restart itself, using its stashed original start info, but with
-                // a brand new transaction.
-                vec![TaskHandleResult::Retry(task_id)]
+                if task.conflict_retries >= MAX_CONFLICT_RETRIES {
+                    warn!(
+                        ?task_id,
+                        retries = task.conflict_retries,
+                        "Task gave up after repeated conflicts"
+                    );
+                    if let Err(send_error) = task.session.send_system_msg(
+                        task.player,
+                        "Aborted: too much contention with other tasks; please try again.",
+                    ) {
+                        warn!("Could not send abort message to player: {:?}", send_error);
+                    };
+                    if let Err(e) = task.session.rollback() {
+                        warn!(?e, "Could not rollback session for conflicted task");
+                    }
+                    return vec![
+                        TaskHandleResult::Notify(
+                            task_id,
+                            TaskWaiterResult::Error(TaskAbortedError),
+                        ),
+                        TaskHandleResult::Remove(task_id),
+                    ];
+                }
+
+                // Back off exponentially, and let the scheduler tick restart the task -- using its
+                // stashed original start info, but with a brand new transaction -- once that's up.
+                let backoff = CONFLICT_RETRY_BACKOFF * 2u32.pow(task.conflict_retries as u32);
+                task.conflict_retries += 1;
+                task.retry_time = Some(self.config.clock.now() + backoff);
+                trace!(
+                    ?task_id,
+                    retries = task.conflict_retries,
+                    ?backoff,
+                    "Task retrying due to conflict"
+                );
+                vec![]
             }
             SchedulerControlMsg::TaskVerbNotFound(this, verb) => {
                 // I'd make this 'warn' but `do_command` gets invoked for every command and
@@ -677,6 +828,29 @@ impl Scheduler {
                     TaskHandleResult::Remove(task_id),
                 ]
             }
+            SchedulerControlMsg::TaskAbortPanicked(panic_msg) => {
+                error!(?task_id, panic_msg, "Task panicked");
+
+                // The task has already rolled back its transaction; do the same for its output.
+                let Some(task) = self.tasks.get_mut(&task_id) else {
+                    warn!(task_id, "Task not found for panic abort");
+                    return vec![TaskHandleResult::Remove(task_id)];
+                };
+                if let Err(send_error) = task
+                    .session
+                    .send_system_msg(task.player, "Aborted due to an internal error.")
+                {
+                    warn!("Could not send abort message to player: {:?}", send_error);
+                };
+                if let Err(e) = task.session.rollback() {
+                    warn!(?e, "Could not rollback session for panicked task");
+                }
+
+                vec![
+                    TaskHandleResult::Notify(task_id, TaskWaiterResult::Error(TaskAbortedError)),
+                    TaskHandleResult::Remove(task_id),
+                ]
+            }
             SchedulerControlMsg::TaskAbortLimitsReached(limit_reason) => {
                 let abort_reason_text = match limit_reason {
                     AbortLimitReason::Ticks(t) => {
@@ -758,6 +932,19 @@ impl Scheduler {
                     session: task.session.clone(),
                 })]
             }
+            SchedulerControlMsg::DispatchQueuedCalls(calls) => {
+                trace!(
+                    task_id,
+                    num_calls = calls.len(),
+                    "Task committed with queued calls"
+                );
+                // As with forks, the new tasks can't be started while self.tasks is borrowed.
+                let Some(task) = self.tasks.get(&task_id) else {
+                    warn!(task_id, "Task not found for queued calls");
+                    return vec![];
+                };
+                vec![TaskHandleResult::DispatchCalls(calls, task.session.clone())]
+            }
             SchedulerControlMsg::TaskSuspend(resume_time) => {
                 trace!(task_id, "Handling task suspension until {:?}", resume_time);
                 // Task is suspended. The resume time (if any) is the system time at which
@@ -781,14 +968,18 @@ impl Scheduler {
                 };
                 task.suspended = true;
                 task.resume_time = resume_time;
+                // It got its transaction committed, so any conflicts before are behind it.
+                task.conflict_retries = 0;
 
                 trace!(task_id, resume_time = ?task.resume_time, "Task suspended");
+                drop(task);
+                self.release_forks(task_id);
                 vec![]
             }
-            SchedulerControlMsg::TaskRequestInput => {
-                // Task has gone into suspension waiting for input from the client.
+            SchedulerControlMsg::TaskRequestInput(reader) => {
+                // Task has gone into suspension waiting for input from the reader's client.
                 // Create a unique ID for this request, and we'll wake the task when the
-                // session receives input.
+                // session receives input from that player.
 
                 let input_request_id = Uuid::new_v4();
                 {
@@ -796,19 +987,26 @@ impl Scheduler {
                         warn!(task_id, "Task not found for input request");
                         return vec![TaskHandleResult::Remove(task_id)];
                     };
-                    let Ok(()) = task.session.request_input(task.player, input_request_id) else {
-                        warn!("Could not request input from session; aborting task");
-                        return vec![
-                            TaskHandleResult::Notify(
-                                task_id,
-                                TaskWaiterResult::Error(TaskAbortedError),
-                            ),
-                            TaskHandleResult::Remove(task_id),
-                        ];
-                    };
-                    task.waiting_input = Some(input_request_id);
+                    task.waiting_input = Some((input_request_id, reader));
+                    task.conflict_retries = 0;
                 }
                 self.input_requests.insert(input_request_id, task_id);
+                self.release_forks(task_id);
+
+                // If someone's already waiting on this player, we'll be asked for in turn.
+                let mut input_queues = self.input_queues.lock().unwrap();
+                let queue = input_queues.entry(reader).or_default();
+                queue.push_back((input_request_id, task_id));
+                if queue.len() == 1 && !self.request_input(reader, input_request_id, task_id) {
+                    warn!("Could not request input from session; aborting task");
+                    return vec![
+                        TaskHandleResult::Notify(
+                            task_id,
+                            TaskWaiterResult::Error(TaskAbortedError),
+                        ),
+                        TaskHandleResult::Remove(task_id),
+                    ];
+                }
                 trace!(?task_id, "Task suspended waiting for input");
                 vec![]
             }
@@ -816,6 +1014,12 @@ impl Scheduler {
                 // Task is asking for a description of all other tasks.
                 vec![TaskHandleResult::Describe(task_id, reply)]
             }
+            SchedulerControlMsg::DescribeServerOptions(reply) => {
+                reply
+                    .send(self.config.clone())
+                    .expect("Could not send server options reply");
+                vec![]
+            }
             SchedulerControlMsg::KillTask {
                 victim_task_id,
                 sender_permissions,
@@ -843,6 +1047,15 @@ impl Scheduler {
                     result_sender,
                 })]
             }
+            SchedulerControlMsg::TaskStack {
+                queued_task_id,
+                sender_permissions,
+                result_sender,
+            } => vec![TaskHandleResult::TaskStack(TaskStackRequest {
+                queued_task_id,
+                sender_permissions,
+                result_sender,
+            })],
             SchedulerControlMsg::BootPlayer {
                 player,
                 sender_permissions: _,
@@ -905,22 +1118,13 @@ impl Scheduler {
                             }
                         };
 
-                        let Ok(mut output) = File::create(&textdump_path) else {
-                            error!("Could not open textdump file for writing");
-                            return;
-                        };
-
-                        info!("Creating textdump...");
-                        let textdump = make_textdump(
+                        info!("Writing textdump to {}", textdump_path.display());
+                        if let Err(e) = textdump_save(
                             loader_client,
+                            textdump_path.clone(),
                             // just to be compatible with LambdaMOO import for now, hopefully.
                             Some("** LambdaMOO Database, Format Version 4 **"),
-                        );
-
-                        info!("Writing textdump to {}", textdump_path.display());
-
-                        let mut writer = TextdumpWriter::new(&mut output);
-                        if let Err(e) = writer.write_textdump(&textdump) {
+                        ) {
                             error!(?e, "Could not write textdump");
                             return;
                         }
@@ -939,19 +1143,17 @@ impl Scheduler {
         fork: Fork,
         session: Arc<dyn Session>,
     ) -> Result<TaskId, SchedulerError> {
-        let suspended = fork.delay.is_some();
-
         let player = fork.player;
-        let delay = fork.delay;
+        let delay = fork.delay.unwrap_or(Duration::ZERO);
         let progr = fork.progr;
+        let parent_task_id = fork.parent_task_id;
         let task_id = self.new_task(
             TaskStart::StartFork {
                 fork_request: fork,
-                suspended,
+                suspended: true,
             },
             player,
             session,
-            delay,
             self.control_sender.clone(),
             progr,
             false,
@@ -961,16 +1163,27 @@ impl Scheduler {
             return Err(TaskNotFound(task_id));
         };
 
-        // If there's a delay on the fork, we will mark it in suspended state and put in the
-        // delay time.
-        if let Some(delay) = delay {
-            task_ref.suspended = true;
-            task_ref.resume_time = Some(SystemTime::now() + delay);
-        }
+        // Forks start out suspended, and are woken once their delay (if any) is up *and* the
+        // parent has committed. As in LambdaMOO, they run after the code that forked them, and see
+        // its changes.
+        task_ref.suspended = true;
+        task_ref.pending_fork = true;
+        task_ref.forked_by = Some(parent_task_id);
+        task_ref.resume_time = Some(self.config.clock.now() + delay);
 
         Ok(task_id)
     }
 
+    /// `parent_task_id` has committed its changes, so the forks it's holding back can start when
+    /// they're due.
+    fn release_forks(&self, parent_task_id: TaskId) {
+        for mut task in self.tasks.iter_mut() {
+            if task.forked_by == Some(parent_task_id) {
+                task.forked_by = None;
+            }
+        }
+    }
+
     fn process_task_actions(self: Arc<Self>, task_actions: Vec<TaskHandleResult>) {
         let mut to_remove = vec![];
         for action in task_actions {
@@ -982,6 +1195,9 @@ impl Scheduler {
                 TaskHandleResult::Fork(fork_request) => {
                     self.clone().process_fork_request(fork_request);
                 }
+                TaskHandleResult::DispatchCalls(calls, session) => {
+                    self.process_queued_calls(calls, session);
+                }
                 TaskHandleResult::Describe(task_id, reply) => {
                     to_remove.extend(self.clone().process_describe_request(task_id, reply))
                 }
@@ -991,12 +1207,12 @@ impl Scheduler {
                 TaskHandleResult::Resume(resume_request) => {
                     to_remove.extend(self.clone().process_resume_request(resume_request))
                 }
+                TaskHandleResult::TaskStack(task_stack_request) => {
+                    to_remove.extend(self.process_task_stack_request(task_stack_request))
+                }
                 TaskHandleResult::Disconnect(task_id, player) => {
                     self.clone().process_disconnect(task_id, player);
                 }
-                TaskHandleResult::Retry(task_id) => {
-                    to_remove.extend(self.clone().process_retry_request(task_id));
-                }
             }
         }
         self.process_task_removals(&to_remove);
@@ -1034,6 +1250,7 @@ impl Scheduler {
         for task_id in to_wake {
             let mut task = self.tasks.get_mut(task_id).unwrap();
             task.suspended = false;
+            task.pending_fork = false;
 
             let world_state_source = self
                 .database
@@ -1061,18 +1278,41 @@ impl Scheduler {
         let mut to_remove = vec![];
         // Fork the session.
         let forked_session = session.clone();
-        let task_id = self
-            .submit_fork_task(fork_request, forked_session)
-            .unwrap_or_else(|e| panic!("Could not fork task: {:?}", e));
+        let result = self.submit_fork_task(fork_request, forked_session);
+        let task_id = result.as_ref().ok().copied();
 
         let reply = reply;
-        if let Err(e) = reply.send(task_id) {
-            error!(task = task_id, error = ?e, "Could not send fork reply. Parent task gone?  Remove.");
-            to_remove.push(task_id);
+        if let Err(e) = reply.send(result) {
+            error!(task = ?task_id, error = ?e, "Could not send fork reply. Parent task gone?  Remove.");
+            to_remove.extend(task_id);
         }
         to_remove
     }
 
+    /// Start each of the calls a task queued up to make once it committed, in a background task
+    /// of its own.
+    fn process_queued_calls(&self, calls: Vec<QueuedVerbCall>, session: Arc<dyn Session>) {
+        for call in calls {
+            let task_start = TaskStart::StartVerb {
+                player: call.player,
+                vloc: call.vloc,
+                verb: call.verb,
+                args: call.args,
+                argstr: String::new(),
+            };
+            if let Err(e) = self.new_task(
+                task_start,
+                call.player,
+                session.clone(),
+                self.control_sender.clone(),
+                call.perms,
+                true,
+            ) {
+                warn!(?e, vloc = ?call.vloc, "Could not start queued verb call");
+            }
+        }
+    }
+
     fn process_describe_request(
         &self,
         requesting_task_id: TaskId,
@@ -1092,12 +1332,13 @@ impl Scheduler {
             task = requesting_task_id,
             "Task requesting task descriptions"
         );
+        let now = self.config.clock.now();
         for t_r in self.tasks.iter() {
             let (task_id, task) = (t_r.key(), t_r.value());
-            // Tasks not in suspended state shouldn't be added.
-            if !task.suspended {
+            // Tasks that are running rather than waiting shouldn't be added.
+            let Some(suspension) = task.suspension(now) else {
                 continue;
-            }
+            };
             if *task_id != requesting_task_id {
                 trace!(
                     requesting_task_id = requesting_task_id,
@@ -1112,7 +1353,7 @@ impl Scheduler {
                     to_remove.push(task.task_id);
                     continue;
                 }
-                let Ok(task_desc) = t_reply.recv() else {
+                let Ok(mut task_desc) = t_reply.recv() else {
                     error!(?task_id, "Could not get task description");
                     to_remove.push(task.task_id);
                     continue;
@@ -1122,6 +1363,7 @@ impl Scheduler {
                     other_task = task_id,
                     "Got task description"
                 );
+                task_desc.suspension = Some(suspension);
                 tasks.push(task_desc);
             }
         }
@@ -1186,9 +1428,14 @@ impl Scheduler {
             return vec![];
         }
 
+        // The task rolls back its own transaction when it gets the abort, and the scheduler then
+        // discards its output. If it's not there to hear us, its output still has to go.
         let tcs = victim_task.task_control_sender.clone();
         if let Err(e) = tcs.send(TaskControlMsg::Abort) {
             error!(task = victim_task_id, error = ?e, "Could not send kill request to task. Task being removed.");
+            if let Err(e) = victim_task.session.rollback() {
+                warn!(task = victim_task_id, error = ?e, "Could not rollback session for killed task");
+            }
             to_remove.push(victim_task_id);
         }
 
@@ -1198,6 +1445,65 @@ impl Scheduler {
         to_remove
     }
 
+    fn process_task_stack_request(
+        &self,
+        TaskStackRequest {
+            queued_task_id,
+            sender_permissions,
+            result_sender,
+        }: TaskStackRequest,
+    ) -> Option<TaskId> {
+        // Only queued tasks have a stack to look at; the requesting task isn't queued, so this
+        // also keeps a task from asking itself, which would deadlock.
+        let queued_task = match self.tasks.get(&queued_task_id) {
+            Some(queued_task) if queued_task.suspension(self.config.clock.now()).is_some() => {
+                queued_task
+            }
+            _ => {
+                result_sender
+                    .send(Err(E_INVARG))
+                    .expect("Could not send task stack result");
+                return None;
+            }
+        };
+
+        if !sender_permissions
+            .check_is_wizard()
+            .expect("Could not check wizard status for task stack request")
+            && sender_permissions.who != queued_task.player
+        {
+            result_sender
+                .send(Err(E_PERM))
+                .expect("Could not send task stack result");
+            return None;
+        }
+
+        let (t_send, t_reply) = kanal::oneshot();
+        let tcs = queued_task.task_control_sender.clone();
+        if let Err(e) = tcs.send(TaskControlMsg::DescribeStack(t_send)) {
+            error!(task = queued_task_id, error = ?e,
+                    "Could not send task stack request to task. Task being removed.");
+            result_sender
+                .send(Err(E_INVARG))
+                .expect("Could not send task stack result");
+            return Some(queued_task_id);
+        }
+        let Ok(callers) = t_reply.recv() else {
+            error!(
+                task = queued_task_id,
+                "Could not get task stack. Task being removed."
+            );
+            result_sender
+                .send(Err(E_INVARG))
+                .expect("Could not send task stack result");
+            return Some(queued_task_id);
+        };
+        result_sender
+            .send(Ok(callers))
+            .expect("Could not send task stack result");
+        None
+    }
+
     fn process_resume_request(
         &self,
         ResumeRequest {
@@ -1210,20 +1516,12 @@ impl Scheduler {
     ) -> Option<TaskId> {
         let result_sender = result_sender;
 
-        // Task can't resume itself, it couldn't be queued. Builtin should not have sent this
-        // request.
-        if requesting_task_id == queued_task_id {
-            error!(
-                task = requesting_task_id,
-                "Task requested to resume itself. Ignoring"
-            );
-            return None;
-        }
-
-        // Task does not exist.
+        // Only a suspended task can be resumed. That rules out tasks that don't exist (or have
+        // already finished), tasks waiting for input, forks that haven't started yet, and running
+        // tasks, including the requester.
         let mut queued_task = match self.tasks.get_mut(&queued_task_id) {
-            Some(queued_task) => queued_task,
-            None => {
+            Some(queued_task) if queued_task.suspended && !queued_task.pending_fork => queued_task,
+            _ => {
                 result_sender
                     .send(v_err(E_INVARG))
                     .expect("Could not send resume result");
@@ -1242,13 +1540,6 @@ impl Scheduler {
                 .expect("Could not send resume result");
             return None;
         }
-        // Task is not suspended.
-        if !queued_task.suspended {
-            result_sender
-                .send(v_err(E_INVARG))
-                .expect("Could not send resume result");
-            return None;
-        }
 
         // Follow the usual task resume logic.
         let state_source = self
@@ -1260,7 +1551,11 @@ impl Scheduler {
         queued_task.suspended = false;
 
         let tcs = queued_task.task_control_sender.clone();
-        if let Err(e) = tcs.send(TaskControlMsg::Resume(state_source, return_value)) {
+        if let Err(e) = self.set_going(
+            queued_task_id,
+            &tcs,
+            TaskControlMsg::Resume(state_source, return_value),
+        ) {
             error!(task = queued_task_id, error = ?e,
                     "Could not send resume request to task. Task being removed.");
             return Some(queued_task_id);
@@ -1289,6 +1584,7 @@ impl Scheduler {
             .expect("Unable to get world source from database");
 
         task.suspended = false;
+        task.retry_time = None;
 
         let tcs = task.task_control_sender.clone();
         if let Err(e) = tcs.send(TaskControlMsg::Restart(state_source)) {
@@ -1299,6 +1595,89 @@ impl Scheduler {
         None
     }
 
+    /// Send a waiting task the message that sets it going again. In deterministic mode it instead
+    /// waits for its turn, and gets the message then.
+    fn set_going(
+        &self,
+        task_id: TaskId,
+        task_control_sender: &Sender<TaskControlMsg>,
+        msg: TaskControlMsg,
+    ) -> Result<(), SendError> {
+        if !self.config.deterministic {
+            return task_control_sender.send(msg);
+        }
+        self.turns.waiting.lock().unwrap().push_back((task_id, msg));
+        Ok(())
+    }
+
+    /// In deterministic mode, once the task whose turn it was has finished or gone back to
+    /// waiting, hand the turn to the next task that's ready; failing that, to the timed
+    /// suspension, fork, or retry that's due first, moving a virtual clock forward to it. Ties go
+    /// to the lowest task id.
+    fn take_turns(&self) {
+        let mut running = self.turns.running.lock().unwrap();
+        if let Some(task_id) = *running {
+            let still_running = self.tasks.get(&task_id).is_some_and(|task| {
+                task.retry_time.is_none() && task.suspension(self.config.clock.now()).is_none()
+            });
+            if still_running {
+                return;
+            }
+            *running = None;
+        }
+
+        loop {
+            let Some((task_id, msg)) = self.turns.waiting.lock().unwrap().pop_front() else {
+                break;
+            };
+            let Some(tcs) = self
+                .tasks
+                .get(&task_id)
+                .map(|task| task.task_control_sender.clone())
+            else {
+                // Killed while it was waiting.
+                continue;
+            };
+            if let Err(e) = tcs.send(msg) {
+                error!(?task_id, error = ?e, "Could not set task going. Task being removed.");
+                self.process_task_removals(&[task_id]);
+                continue;
+            }
+            *running = Some(task_id);
+            return;
+        }
+
+        let next_due = self
+            .tasks
+            .iter()
+            .filter_map(|task| match (task.retry_time, task.resume_time) {
+                (Some(retry_time), _) => Some((retry_time, task.task_id, true)),
+                (None, Some(resume_time)) if task.suspended && task.forked_by.is_none() => {
+                    Some((resume_time, task.task_id, false))
+                }
+                _ => None,
+            })
+            .min();
+        let Some((due_time, task_id, is_retry)) = next_due else {
+            return;
+        };
+        self.config.clock.advance_to(due_time);
+        if due_time > self.config.clock.now() {
+            // Only a virtual clock can be moved; the system one has to be waited out.
+            return;
+        }
+        let to_remove = if is_retry {
+            self.process_retry_request(task_id).into_iter().collect()
+        } else {
+            self.process_wake_ups(&[task_id])
+        };
+        if to_remove.is_empty() {
+            *running = Some(task_id);
+        } else {
+            self.process_task_removals(&to_remove);
+        }
+    }
+
     fn process_disconnect(self: Arc<Self>, disconnect_task_id: TaskId, player: Objid) {
         let Some(task) = self.tasks.get_mut(&disconnect_task_id) else {
             warn!(task = disconnect_task_id, "Disconnecting task not found");
@@ -1334,10 +1713,61 @@ impl Scheduler {
         }
     }
 
+    /// Put `task_id`'s input request to `reader`'s client, returning false if its session
+    /// couldn't.
+    fn request_input(&self, reader: Objid, input_request_id: Uuid, task_id: TaskId) -> bool {
+        let Some(session) = self.tasks.get(&task_id).map(|task| task.session.clone()) else {
+            return false;
+        };
+        if let Err(e) = session.request_input(reader, input_request_id) {
+            warn!(?task_id, ?reader, error = ?e, "Could not request input from session");
+            return false;
+        }
+        true
+    }
+
+    /// Take an answered or abandoned input request out of `reader`'s queue. If it was the one that
+    /// had been put to the player, ask on behalf of whoever is next in line.
+    fn dequeue_input_request(&self, reader: Objid, input_request_id: Uuid) {
+        let mut input_queues = self.input_queues.lock().unwrap();
+        let Some(queue) = input_queues.get_mut(&reader) else {
+            return;
+        };
+        let was_asked = queue.front().map(|(asked, _)| *asked) == Some(input_request_id);
+        queue.retain(|(queued, _)| *queued != input_request_id);
+        let next = if was_asked {
+            queue.front().copied()
+        } else {
+            None
+        };
+        if queue.is_empty() {
+            input_queues.remove(&reader);
+        }
+        let Some((next_request_id, next_task_id)) = next else {
+            return;
+        };
+        if !self.request_input(reader, next_request_id, next_task_id) {
+            // Abort it rather than leave everyone behind it stuck; its removal will move the
+            // queue along again.
+            if let Some(task) = self.tasks.get(&next_task_id) {
+                let _ = task.task_control_sender.send(TaskControlMsg::Abort);
+            }
+        }
+    }
+
     fn process_task_removals(&self, to_remove: &[TaskId]) {
         for task_id in to_remove {
             trace!(task = task_id, "Task removed");
-            self.tasks.remove(task_id);
+            if let Some((_, task)) = self.tasks.remove(task_id) {
+                // A task killed while waiting for input can no longer receive it.
+                if let Some((input_request_id, reader)) = task.waiting_input {
+                    self.input_requests.remove(&input_request_id);
+                    self.dequeue_input_request(reader, input_request_id);
+                }
+                self.release_task_slot(task.player);
+            }
+            // Whether it finished or was aborted, it won't be doing anything more.
+            self.release_forks(*task_id);
         }
     }
 
@@ -1348,25 +1778,38 @@ impl Scheduler {
         task_start: TaskStart,
         player: Objid,
         session: Arc<dyn Session>,
-        delay_start: Option<Duration>,
         control_sender: Sender<(TaskId, SchedulerControlMsg)>,
         perms: Objid,
         is_background: bool,
     ) -> Result<TaskId, SchedulerError> {
-        let task_id = self.next_task_id.fetch_add(1, Ordering::SeqCst);
-        let (task_control_sender, task_control_receiver) = kanal::unbounded();
-
         let state_source = self
             .database
             .clone()
             .world_state_source()
-            .expect("Unable to instantiate database");
+            .map_err(|_| CouldNotStartTask)?;
+
+        // Once the task is in place, its slot is given back when it's removed. A task that can't
+        // get going tells us, so that it is.
+        self.reserve_task_slot(player)?;
+
+        let task_id = self.next_task_id.fetch_add(1, Ordering::SeqCst);
+        let (task_control_sender, task_control_receiver) = kanal::unbounded();
+
+        // Claim the task's slot before its thread exists. The task can start sending us control
+        // messages as soon as it's spawned, and the scheduler loop must not be able to look for
+        // it until its record is in place; holding the entry keeps it waiting until then.
+        let task_entry = self.tasks.entry(task_id);
 
         // TODO: support a queue-size on concurrent executing tasks and allow them to sit in an
         //   initially suspended state without spawning a worker thread, until the queue has space.
         // Spawn the task's thread.
         let task_state_source = state_source.clone();
         let task_session = session.clone();
+        let verb_metrics = self.verb_metrics.clone();
+        let program_cache = self.program_cache.clone();
+        let config = self.config.clone();
+
+        let held = self.config.deterministic && !matches!(task_start, TaskStart::StartFork { .. });
 
         let name = format!("moor-task-{}-player-{}", task_id, player);
         let join_handle = std::thread::Builder::new()
@@ -1377,16 +1820,22 @@ impl Scheduler {
                     task_id,
                     task_start,
                     perms,
-                    delay_start,
                     task_state_source,
                     is_background,
                     task_session,
                     task_control_receiver,
                     control_sender,
+                    verb_metrics,
+                    program_cache,
+                    config,
                 );
                 trace!(?task_id, "Completed task");
             })
-            .expect("Could not spawn task thread");
+            .map_err(|e| error!(?task_id, error = ?e, "Could not spawn task thread"));
+        let Ok(join_handle) = join_handle else {
+            self.release_task_slot(player);
+            return Err(CouldNotStartTask);
+        };
 
         let task_control = TaskControl {
             task_id,
@@ -1395,13 +1844,1726 @@ impl Scheduler {
             state_source,
             session,
             suspended: false,
+            pending_fork: false,
+            forked_by: None,
             waiting_input: None,
             resume_time: None,
+            conflict_retries: 0,
+            retry_time: None,
             subscribers: Mutex::new(vec![]),
             _join_handle: join_handle,
         };
-        self.tasks.insert(task_id, task_control);
+        if held {
+            let start = TaskControlMsg::Restart(task_control.state_source.clone());
+            self.turns
+                .waiting
+                .lock()
+                .unwrap()
+                .push_back((task_id, start));
+        }
+        task_entry.insert(task_control);
 
         Ok(task_id)
     }
+
+    /// Count a new task against `player`, refusing it if they're already at the configured
+    /// `max_tasks_per_player`. Wizards are exempt.
+    fn reserve_task_slot(&self, player: Objid) -> Result<(), SchedulerError> {
+        // Claim the slot first and give it back if we're over, so that concurrent spawns for the
+        // same player can't both squeeze in under the limit.
+        let num_tasks = {
+            let mut count = self.player_task_counts.entry(player).or_insert(0);
+            *count += 1;
+            *count
+        };
+        let Some(max_tasks) = self.config.max_tasks_per_player else {
+            return Ok(());
+        };
+        if num_tasks <= max_tasks {
+            return Ok(());
+        }
+
+        // Only bother looking at the player's flags once they're actually over the limit.
+        match self.is_wizard(player) {
+            Ok(true) => return Ok(()),
+            Ok(false) => self.release_task_slot(player),
+            Err(e) => {
+                self.release_task_slot(player);
+                return Err(e);
+            }
+        }
+
+        warn!(?player, num_tasks, max_tasks, "Player task limit reached");
+        Err(TooManyTasks(player))
+    }
+
+    fn release_task_slot(&self, player: Objid) {
+        if let Some(mut count) = self.player_task_counts.get_mut(&player) {
+            *count = count.saturating_sub(1);
+        }
+        self.player_task_counts
+            .remove_if(&player, |_, count| *count == 0);
+    }
+
+    fn is_wizard(&self, player: Objid) -> Result<bool, SchedulerError> {
+        let mut world_state = self
+            .database
+            .clone()
+            .world_state_source()
+            .map_err(|_| CouldNotStartTask)?
+            .new_world_state()
+            .map_err(|_| CouldNotStartTask)?;
+        let is_wizard = world_state
+            .flags_of(player)
+            .map(|flags| flags.contains(ObjFlag::Wizard))
+            .unwrap_or(false);
+        world_state.rollback().map_err(|_| CouldNotStartTask)?;
+        Ok(is_wizard)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::{Duration, Instant, SystemTime};
+
+    use kanal::Receiver;
+    use moor_compiler::compile;
+    use moor_db::odb::RelBoxWorldState;
+    use moor_values::model::{
+        ArgSpec, BinaryType, ContentType, Event, ObjFlag, PrepSpec, VerbArgsSpec, VerbFlag,
+        WorldStateSource,
+    };
+    use moor_values::util::BitEnum;
+    use moor_values::var::Error::{E_FLOAT, E_INVARG, E_PERM, E_QUOTA};
+    use moor_values::var::{
+        v_empty_list, v_err, v_int, v_list, v_none, v_objid, v_str, Error, Objid, Var, Variant,
+    };
+    use moor_values::{AsByteBuffer, FAILED_MATCH, NOTHING, SYSTEM_OBJECT};
+    use uuid::Uuid;
+
+    use crate::config::{Config, TaskLimits};
+    use crate::tasks::clock::Clock;
+    use crate::tasks::scheduler::SchedulerError::TaskAbortedLimit;
+    use crate::tasks::scheduler::{AbortLimitReason, Scheduler, SchedulerError, TaskWaiterResult};
+    use crate::tasks::sessions::{MockClientSession, NoopClientSession};
+    use crate::tasks::TaskId;
+
+    /// A db with a wizardly #0, a plain (non-wizard) player, and some verbs and properties on #0
+    /// for the tests below to work with.
+    fn test_db() -> (Arc<RelBoxWorldState>, Objid) {
+        let (db, _) = RelBoxWorldState::open(None, 1 << 30);
+        let mut tx = db.new_world_state().unwrap();
+        let sysobj = tx
+            .create_object(SYSTEM_OBJECT, NOTHING, SYSTEM_OBJECT, BitEnum::all())
+            .unwrap();
+        let player = tx
+            .create_object(
+                SYSTEM_OBJECT,
+                NOTHING,
+                SYSTEM_OBJECT,
+                BitEnum::new_with(ObjFlag::User) | ObjFlag::Read,
+            )
+            .unwrap();
+        let verbs = [
+            ("idle", "suspend();"),
+            (
+                "try_fork",
+                "try fork (0) return; endfork return 1; except (E_QUOTA) return E_QUOTA; endtry",
+            ),
+            (
+                "watch_counter",
+                r#"before = #0.counter;
+                   x = {"local", before};
+                   #0.ready = 1;
+                   woken_with = suspend();
+                   return {before, #0.counter, x, woken_with};"#,
+            ),
+            ("bump_counter", "#0.counter = #0.counter + 1;"),
+            // Hang on to what was read for a while, so that concurrent bumps overlap.
+            (
+                "slow_bump_counter",
+                "x = #0.counter; for i in [1..50000] endfor #0.counter = x + 1; return x + 1;",
+            ),
+            // The scheduler may not have processed the target's suspension yet, so keep trying.
+            (
+                "wake",
+                "while (`resume(args[1], args[2]) ! E_INVARG' == E_INVARG) suspend(0); endwhile",
+            ),
+            ("poke_then_break", "#0.counter = 42; #0:broken();"),
+            ("noop", "return 1;"),
+            (
+                "notify_typed",
+                r#"notify(#0, "plain"); notify(#0, "<b>rich</b>", "text/html"); notify(#0, "flushed", 1);"#,
+            ),
+            ("delayed_fork", "fork (100) return; endfork"),
+            (
+                "poke_then_kill_self",
+                r#"#0.counter = 42; notify(#0, "partial"); kill_task(task_id());"#,
+            ),
+            (
+                "queue_then_kill_self",
+                r#"call_after_commit(#0, "bump_counter"); kill_task(task_id());"#,
+            ),
+            (
+                "queue_bump",
+                r#"call_after_commit(#0, "bump_counter", {});"#,
+            ),
+            (
+                "poke_after_suspend",
+                r#"notify(#0, "before"); suspend(); #0.counter = 42; notify(#0, "after");"#,
+            ),
+            (
+                "kill_when_queued",
+                "while (1)
+                   for t in (queued_tasks())
+                     if (t[1] == args[1]) return kill_task(args[1]); endif
+                   endfor
+                   suspend(0);
+                 endwhile",
+            ),
+            ("wait_input", "read();"),
+            ("raise_arg", "raise(args[1]);"),
+            ("overflow_float", "return 1e308 * 10.0;"),
+            (
+                "list_queued",
+                r#"while (length(tasks = queued_tasks()) < 3) suspend(0); endwhile
+                   r = {};
+                   for t in (tasks)
+                     r = {@r, {t[10], typeof(t[11]) == FLOAT && t[11] > 0.0 && t[11] <= 100.0}};
+                   endfor
+                   return r;"#,
+            ),
+            ("nested_idle", "#0:idle();"),
+            // Poll until the target is queued, then return its stack (or whatever error we get),
+            // optionally with someone else's permissions.
+            (
+                "stack_of",
+                "if (length(args) > 1) set_task_perms(args[2]); endif
+                 while ((s = `task_stack(args[1]) ! ANY') == E_INVARG) suspend(0); endwhile
+                 return s;",
+            ),
+            ("own_stack", "return `task_stack(task_id()) ! ANY';"),
+            ("await_resume", "return suspend();"),
+            ("read_line", "return read();"),
+            (
+                "kill_as",
+                "set_task_perms(args[2]); return `kill_task(args[1]) ! ANY';",
+            ),
+            (
+                "kill_fork",
+                "fork f (100) #0.counter = 42; endfork
+                 return {f, this:kill_as(f, args[1]), `kill_task(-1) ! ANY', kill_task(f)};",
+            ),
+            ("read_from", "return read(args[1]);"),
+            ("await_timed_resume", "return suspend(100);"),
+            ("nap", "return suspend(0);"),
+            (
+                "resume_as",
+                "set_task_perms(args[3]); return `resume(args[1], args[2]) ! ANY';",
+            ),
+            // Waits for args[1] to be queued by poking at it as a non-wizard, then tries resuming
+            // a task that doesn't exist and one that's reading input, before resuming args[1].
+            (
+                "check_resume",
+                r#"{victim, reader, who} = args;
+                   while ((perm = this:resume_as(victim, "early", who)) == E_INVARG) suspend(0); endwhile
+                   return {perm,
+                           `resume(-1) ! ANY',
+                           this:resume_as(reader, "early", who),
+                           resume(victim, "woken")};"#,
+            ),
+            ("spin", "for i in [1..1000] this:noop(); endfor return i;"),
+            (
+                "await_then_spin",
+                "suspend(); for i in [1..1000] this:noop(); endfor return i;",
+            ),
+            (
+                "fork_then_resume",
+                "fork f (0) #0.counter = suspend(); endfork
+                 while (`resume(f, 42) ! E_INVARG' == E_INVARG) suspend(0); endwhile",
+            ),
+            (
+                "write_then_fork",
+                "#0.counter = 5; fork (0) #0.ready = #0.counter; endfork #0.counter = 6;
+                 for i in [1..100] this:noop(); endfor",
+            ),
+            (
+                "resume_pending_fork",
+                "fork f (100) #0.counter = 42; endfork return {`resume(f) ! ANY', kill_task(f)};",
+            ),
+            (
+                "server_info",
+                "return {server_version(), server_options()};",
+            ),
+            (
+                "player_server_options",
+                "set_task_perms(player); return server_options();",
+            ),
+            (
+                "notify_mcp",
+                r##"notify(#0, "hello"); notify_oob(#0, "#$#mcp version: 2.1 to: 2.1");"##,
+            ),
+            ("who_called", "return {this, caller};"),
+            (
+                "fork_who_called",
+                "fork (0) #0.ready = {this, caller}; endfork",
+            ),
+            (
+                "eval_who_called",
+                r#"return eval("return {this, caller};");"#,
+            ),
+            (
+                "log_nap",
+                "#0.log = {@#0.log, {args[1], time()}};
+                 suspend(args[2]);
+                 #0.log = {@#0.log, {args[1], time()}};",
+            ),
+            (
+                "log_await",
+                "#0.log = {@#0.log, {args[1], time()}};
+                 v = suspend();
+                 #0.log = {@#0.log, {args[1], v, time()}};",
+            ),
+            (
+                "log_resume_fork",
+                r#"#0.log = {@#0.log, {"c", time()}};
+                   resume(args[1], "woken");
+                   fork (1) #0.log = {@#0.log, {"fork", time()}}; endfork
+                   #0.log = {@#0.log, {"c", time()}};"#,
+            ),
+        ];
+        for (name, code) in verbs {
+            let binary = compile(code).unwrap().make_copy_as_vec().unwrap();
+            tx.add_verb(
+                SYSTEM_OBJECT,
+                sysobj,
+                vec![name.to_string()],
+                sysobj,
+                VerbFlag::rxd(),
+                VerbArgsSpec::this_none_this(),
+                binary,
+                BinaryType::LambdaMoo18X,
+            )
+            .unwrap();
+        }
+        // The same busy loop as `spin`, but in a verb the (non-wizard) player owns.
+        let spin = "for i in [1..1000] this:noop(); endfor return i;";
+        tx.add_verb(
+            SYSTEM_OBJECT,
+            sysobj,
+            vec!["player_spin".to_string()],
+            player,
+            VerbFlag::rxd(),
+            VerbArgsSpec::this_none_this(),
+            compile(spin).unwrap().make_copy_as_vec().unwrap(),
+            BinaryType::LambdaMoo18X,
+        )
+        .unwrap();
+        // A command verb on the player, for command tasks to match.
+        tx.add_verb(
+            SYSTEM_OBJECT,
+            player,
+            vec!["whoami".to_string()],
+            player,
+            VerbFlag::rxd(),
+            VerbArgsSpec {
+                dobj: ArgSpec::None,
+                prep: PrepSpec::None,
+                iobj: ArgSpec::None,
+            },
+            compile("return {this, caller, player};")
+                .unwrap()
+                .make_copy_as_vec()
+                .unwrap(),
+            BinaryType::LambdaMoo18X,
+        )
+        .unwrap();
+        // A command verb which reports what its objects matched.
+        tx.add_verb(
+            SYSTEM_OBJECT,
+            player,
+            vec!["examine".to_string()],
+            player,
+            VerbFlag::rxd(),
+            VerbArgsSpec {
+                dobj: ArgSpec::Any,
+                prep: PrepSpec::None,
+                iobj: ArgSpec::None,
+            },
+            compile("return {dobj, iobj};")
+                .unwrap()
+                .make_copy_as_vec()
+                .unwrap(),
+            BinaryType::LambdaMoo18X,
+        )
+        .unwrap();
+        // A verb whose program can't be decoded, so calling it panics inside the VM host.
+        tx.add_verb(
+            SYSTEM_OBJECT,
+            sysobj,
+            vec!["broken".to_string()],
+            sysobj,
+            VerbFlag::rxd(),
+            VerbArgsSpec::this_none_this(),
+            vec![0xff; 16],
+            BinaryType::LambdaMoo18X,
+        )
+        .unwrap();
+        // A verb the player isn't allowed to read, so resolving it on their behalf fails.
+        tx.add_verb(
+            SYSTEM_OBJECT,
+            sysobj,
+            vec!["unreadable".to_string()],
+            sysobj,
+            BitEnum::new_with(VerbFlag::Exec),
+            VerbArgsSpec::this_none_this(),
+            compile("return 1;").unwrap().make_copy_as_vec().unwrap(),
+            BinaryType::LambdaMoo18X,
+        )
+        .unwrap();
+        tx.define_property(
+            SYSTEM_OBJECT,
+            sysobj,
+            sysobj,
+            "log",
+            SYSTEM_OBJECT,
+            BitEnum::all(),
+            Some(v_empty_list()),
+        )
+        .unwrap();
+        for (name, value) in [("counter", 1), ("ready", 0)] {
+            tx.define_property(
+                SYSTEM_OBJECT,
+                sysobj,
+                sysobj,
+                name,
+                SYSTEM_OBJECT,
+                BitEnum::all(),
+                Some(v_int(value)),
+            )
+            .unwrap();
+        }
+        tx.commit().unwrap();
+        (Arc::new(db), player)
+    }
+
+    fn submit_verb(
+        scheduler: &Scheduler,
+        player: Objid,
+        verb: &str,
+        args: Vec<Var>,
+    ) -> Result<usize, SchedulerError> {
+        scheduler.submit_verb_task(
+            player,
+            SYSTEM_OBJECT,
+            verb.to_string(),
+            args,
+            "".to_string(),
+            player,
+            Arc::new(NoopClientSession::new()),
+        )
+    }
+
+    /// Spin until a committed value of #0.`prop` equals `value`.
+    fn wait_for_property(db: &RelBoxWorldState, prop: &str, value: Var) {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        loop {
+            let mut tx = db.new_world_state().unwrap();
+            let current = tx
+                .retrieve_property(SYSTEM_OBJECT, SYSTEM_OBJECT, prop)
+                .unwrap();
+            tx.rollback().unwrap();
+            if current == value {
+                return;
+            }
+            assert!(Instant::now() < deadline, "timed out waiting for #0.{prop}");
+            std::thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    /// Spin until the scheduler has no outstanding input requests. Tasks' results are handed
+    /// over before the scheduler gets around to cleaning up after them.
+    fn wait_for_no_input_requests(scheduler: &Scheduler) {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while !scheduler.input_requests.is_empty() {
+            assert!(
+                Instant::now() < deadline,
+                "timed out waiting for input requests to be cleared"
+            );
+            std::thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    fn submit_idle(scheduler: &Scheduler, player: Objid) -> Result<usize, SchedulerError> {
+        submit_verb(scheduler, player, "idle", vec![])
+    }
+
+    #[test]
+    fn test_task_limit_per_player() {
+        let (db, player) = test_db();
+        let config = Config {
+            max_tasks_per_player: Some(2),
+            ..Default::default()
+        };
+        let scheduler = Arc::new(Scheduler::new(db, config));
+        let loop_scheduler = scheduler.clone();
+        std::thread::spawn(move || loop_scheduler.run());
+
+        submit_idle(&scheduler, player).unwrap();
+        submit_idle(&scheduler, player).unwrap();
+        let Err(SchedulerError::TooManyTasks(who)) = submit_idle(&scheduler, player) else {
+            panic!("expected the third task to be refused");
+        };
+        assert_eq!(who, player);
+
+        // Wizards aren't held to the limit.
+        for _ in 0..5 {
+            submit_idle(&scheduler, SYSTEM_OBJECT).unwrap();
+        }
+
+        scheduler.stop().unwrap();
+    }
+
+    /// Run `verb` on #0 as `player` under the given limit, and wait for its result.
+    fn run_verb(max_tasks_per_player: usize, verb: &str) -> Var {
+        let (db, player) = test_db();
+        let config = Config {
+            max_tasks_per_player: Some(max_tasks_per_player),
+            ..Default::default()
+        };
+        let scheduler = Arc::new(Scheduler::new(db, config));
+        let task_id = submit_verb(&scheduler, player, verb, vec![]).unwrap();
+
+        // Subscribe before the scheduler loop starts, so we can't miss the result.
+        let receiver = scheduler.subscribe_to_task(task_id).unwrap();
+        let loop_scheduler = scheduler.clone();
+        std::thread::spawn(move || loop_scheduler.run());
+
+        let TaskWaiterResult::Success(result) = receiver.recv().unwrap() else {
+            panic!("expected {verb} to succeed");
+        };
+        scheduler.stop().unwrap();
+        result
+    }
+
+    #[test]
+    fn test_task_limit_fork_raises_quota() {
+        // The forking task itself occupies the player's only slot, so the fork is refused.
+        assert_eq!(run_verb(1, "try_fork"), v_err(E_QUOTA));
+        // With room for one more, it goes through.
+        assert_eq!(run_verb(2, "try_fork"), v_int(1));
+    }
+
+    #[test]
+    fn test_server_version_and_options() {
+        let result = run_verb(7, "server_info");
+        let Variant::List(result) = result.variant() else {
+            panic!("expected a list, got {result:?}");
+        };
+        let Variant::Str(version) = result[0].variant() else {
+            panic!("expected a version string, got {:?}", result[0]);
+        };
+        let parts: Vec<_> = version.as_str().split('.').collect();
+        assert_eq!(
+            parts.len(),
+            3,
+            "version {} isn't major.minor.patch",
+            version.as_str()
+        );
+        assert!(parts.iter().all(|p| p.parse::<u32>().is_ok()));
+
+        let option = |name: &str, value: Var| v_list(&[v_str(name), value]);
+        let public = [
+            option("fg_ticks", v_int(60_000)),
+            option("bg_ticks", v_int(30_000)),
+            option("fg_seconds", v_int(5)),
+            option("bg_seconds", v_int(3)),
+            option("max_stack_depth", v_int(50)),
+            option("wizard_ticks", v_none()),
+            option("wizard_seconds", v_none()),
+            option("max_tasks_per_player", v_int(7)),
+        ];
+        let mut all = public.to_vec();
+        all.push(option("textdump_output", v_none()));
+        assert_eq!(result[1], v_list(&all));
+
+        // Non-wizards don't get to see where the server writes its files.
+        assert_eq!(run_verb(7, "player_server_options"), v_list(&public));
+    }
+
+    /// Tasks running wizard-owned verbs get the wizard limits, whoever the player is. Calling a
+    /// verb on every iteration also makes sure the tick ceiling holds across interpreter slices.
+    #[test]
+    fn test_task_limits_by_verb_owner() {
+        let (db, player) = test_db();
+        let config = Config {
+            foreground_limits: TaskLimits {
+                max_ticks: 2_000,
+                ..TaskLimits::FOREGROUND
+            },
+            wizard_limits: Some(TaskLimits {
+                max_ticks: 100_000,
+                ..TaskLimits::FOREGROUND
+            }),
+            ..Default::default()
+        };
+        let scheduler = Arc::new(Scheduler::new(db, config));
+        let wizard_spin = submit_verb(&scheduler, player, "spin", vec![]).unwrap();
+        let player_spin = submit_verb(&scheduler, player, "player_spin", vec![]).unwrap();
+        let receivers =
+            [wizard_spin, player_spin].map(|task_id| scheduler.subscribe_to_task(task_id).unwrap());
+        let loop_scheduler = scheduler.clone();
+        std::thread::spawn(move || loop_scheduler.run());
+
+        let [wizard_result, player_result] = receivers.map(|receiver| receiver.recv().unwrap());
+        let TaskWaiterResult::Success(result) = wizard_result else {
+            panic!("expected the wizard's loop to finish, got {wizard_result:?}");
+        };
+        assert_eq!(result, v_int(1000));
+        let TaskWaiterResult::Error(TaskAbortedLimit(AbortLimitReason::Ticks(_))) = player_result
+        else {
+            panic!("expected the player's loop to run out of ticks, got {player_result:?}");
+        };
+
+        scheduler.stop().unwrap();
+    }
+
+    #[test]
+    fn test_set_task_limits() {
+        let (db, _) = test_db();
+        let scheduler = Arc::new(Scheduler::new(db, Config::default()));
+        let task_id = submit_verb(&scheduler, SYSTEM_OBJECT, "await_then_spin", vec![]).unwrap();
+        let receiver = scheduler.subscribe_to_task(task_id).unwrap();
+        let loop_scheduler = scheduler.clone();
+        std::thread::spawn(move || loop_scheduler.run());
+
+        let limits = TaskLimits {
+            max_ticks: 100,
+            ..TaskLimits::FOREGROUND
+        };
+        scheduler.set_task_limits(task_id, limits).unwrap();
+        submit_verb(
+            &scheduler,
+            SYSTEM_OBJECT,
+            "wake",
+            vec![v_int(task_id as i64), v_none()],
+        )
+        .unwrap();
+        let result = receiver.recv().unwrap();
+        let TaskWaiterResult::Error(TaskAbortedLimit(AbortLimitReason::Ticks(_))) = result else {
+            panic!("expected the task to run out of ticks, got {result:?}");
+        };
+        assert!(matches!(
+            scheduler.set_task_limits(task_id + 100, limits),
+            Err(SchedulerError::TaskNotFound(_))
+        ));
+
+        scheduler.stop().unwrap();
+    }
+
+    /// A suspended task commits its transaction and gets a fresh one on resume, so it sees what
+    /// other tasks committed in the meantime, while its own stack and locals carry over.
+    #[test]
+    fn test_suspend_resume_sees_committed_changes() {
+        let (db, _) = test_db();
+        let scheduler = Arc::new(Scheduler::new(db.clone(), Config::default()));
+        let loop_scheduler = scheduler.clone();
+        std::thread::spawn(move || loop_scheduler.run());
+
+        // The watcher can't finish until we resume it, so there's no race in subscribing here.
+        let watcher = submit_verb(&scheduler, SYSTEM_OBJECT, "watch_counter", vec![]).unwrap();
+        let receiver = scheduler.subscribe_to_task(watcher).unwrap();
+
+        // Once `ready` is visible, the watcher has read the counter and committed on suspend.
+        wait_for_property(&db, "ready", v_int(1));
+        submit_verb(&scheduler, SYSTEM_OBJECT, "bump_counter", vec![]).unwrap();
+        wait_for_property(&db, "counter", v_int(2));
+
+        submit_verb(
+            &scheduler,
+            SYSTEM_OBJECT,
+            "wake",
+            vec![v_int(watcher as i64), v_str("woken")],
+        )
+        .unwrap();
+
+        let TaskWaiterResult::Success(result) = receiver.recv().unwrap() else {
+            panic!("expected watcher to succeed");
+        };
+        assert_eq!(
+            result,
+            v_list(&[
+                v_int(1),
+                v_int(2),
+                v_list(&[v_str("local"), v_int(1)]),
+                v_str("woken"),
+            ])
+        );
+
+        scheduler.stop().unwrap();
+    }
+
+    /// Tasks that conflict with each other on commit are restarted from the top, with a fresh
+    /// transaction, until they get through.
+    #[test]
+    fn test_conflicting_tasks_retry() {
+        let (db, _) = test_db();
+        let config = Config {
+            wizard_limits: Some(TaskLimits {
+                max_ticks: 1_000_000,
+                ..TaskLimits::FOREGROUND
+            }),
+            ..Default::default()
+        };
+        let scheduler = Arc::new(Scheduler::new(db.clone(), config));
+        let bumps = [(); 2]
+            .map(|_| submit_verb(&scheduler, SYSTEM_OBJECT, "slow_bump_counter", vec![]).unwrap());
+        let receivers = bumps.map(|task_id| scheduler.subscribe_to_task(task_id).unwrap());
+        let loop_scheduler = scheduler.clone();
+        std::thread::spawn(move || loop_scheduler.run());
+
+        let mut results = receivers
+            .map(|receiver| match receiver.recv().unwrap() {
+                TaskWaiterResult::Success(result) => result,
+                other => panic!("expected both bumps to succeed, got {other:?}"),
+            })
+            .to_vec();
+        results.sort();
+        assert_eq!(results, vec![v_int(2), v_int(3)]);
+        wait_for_property(&db, "counter", v_int(3));
+
+        scheduler.stop().unwrap();
+    }
+
+    /// suspend() hands back whatever resume() was given, even when it had a delay, and 0 when the
+    /// delay runs out on its own.
+    #[test]
+    fn test_suspend_returns_resume_value() {
+        let (db, _) = test_db();
+        let scheduler = Arc::new(Scheduler::new(db, Config::default()));
+        let timed = submit_verb(&scheduler, SYSTEM_OBJECT, "await_timed_resume", vec![]).unwrap();
+        let nap = submit_verb(&scheduler, SYSTEM_OBJECT, "nap", vec![]).unwrap();
+        let receivers = [timed, nap].map(|task_id| scheduler.subscribe_to_task(task_id).unwrap());
+        submit_verb(
+            &scheduler,
+            SYSTEM_OBJECT,
+            "wake",
+            vec![v_int(timed as i64), v_list(&[v_str("early"), v_int(1)])],
+        )
+        .unwrap();
+        let loop_scheduler = scheduler.clone();
+        std::thread::spawn(move || loop_scheduler.run());
+
+        let results = receivers.map(|receiver| {
+            let TaskWaiterResult::Success(result) = receiver.recv().unwrap() else {
+                panic!("expected the task to succeed");
+            };
+            result
+        });
+        assert_eq!(results, [v_list(&[v_str("early"), v_int(1)]), v_int(0)]);
+
+        scheduler.stop().unwrap();
+    }
+
+    /// A parent can hand a value to a fork sitting in `suspend()`, but not to one still waiting to
+    /// start.
+    #[test]
+    fn test_resume_forked_task() {
+        let (db, _) = test_db();
+        let scheduler = Arc::new(Scheduler::new(db.clone(), Config::default()));
+        submit_verb(&scheduler, SYSTEM_OBJECT, "fork_then_resume", vec![]).unwrap();
+        let task_id =
+            submit_verb(&scheduler, SYSTEM_OBJECT, "resume_pending_fork", vec![]).unwrap();
+        let receiver = scheduler.subscribe_to_task(task_id).unwrap();
+        let loop_scheduler = scheduler.clone();
+        std::thread::spawn(move || loop_scheduler.run());
+
+        let TaskWaiterResult::Success(result) = receiver.recv().unwrap() else {
+            panic!("expected resume_pending_fork to succeed");
+        };
+        assert_eq!(result, v_list(&[v_err(E_INVARG), v_none()]));
+        wait_for_property(&db, "counter", v_int(42));
+
+        scheduler.stop().unwrap();
+    }
+
+    /// Run the `log_*` verbs as three interleaved tasks in deterministic mode, and return what
+    /// they logged.
+    fn run_logged_tasks() -> Var {
+        let (db, _) = test_db();
+        let config = Config {
+            deterministic: true,
+            clock: Clock::virtual_at(SystemTime::UNIX_EPOCH),
+            ..Default::default()
+        };
+        let scheduler = Arc::new(Scheduler::new(db.clone(), config));
+        let napper = submit_verb(
+            &scheduler,
+            SYSTEM_OBJECT,
+            "log_nap",
+            vec![v_str("a"), v_int(2)],
+        )
+        .unwrap();
+        let awaiter =
+            submit_verb(&scheduler, SYSTEM_OBJECT, "log_await", vec![v_str("b")]).unwrap();
+        submit_verb(
+            &scheduler,
+            SYSTEM_OBJECT,
+            "log_resume_fork",
+            vec![v_int(awaiter as i64)],
+        )
+        .unwrap();
+        let receiver = scheduler.subscribe_to_task(napper).unwrap();
+        let loop_scheduler = scheduler.clone();
+        std::thread::spawn(move || loop_scheduler.run());
+
+        let TaskWaiterResult::Success(_) = receiver.recv().unwrap() else {
+            panic!("expected log_nap to succeed");
+        };
+        scheduler.stop().unwrap();
+
+        let mut tx = db.new_world_state().unwrap();
+        let log = tx
+            .retrieve_property(SYSTEM_OBJECT, SYSTEM_OBJECT, "log")
+            .unwrap();
+        tx.rollback().unwrap();
+        log
+    }
+
+    /// In deterministic mode, tasks take turns in the order they became ready, and the virtual
+    /// clock jumps straight to the next timed wake-up once everyone's waiting, so the same tasks
+    /// interleave the same way every time.
+    #[test]
+    fn test_deterministic_scheduling() {
+        let expected = v_list(&[
+            v_list(&[v_str("a"), v_int(0)]),
+            v_list(&[v_str("b"), v_int(0)]),
+            v_list(&[v_str("c"), v_int(0)]),
+            v_list(&[v_str("c"), v_int(0)]),
+            v_list(&[v_str("b"), v_str("woken"), v_int(0)]),
+            v_list(&[v_str("fork"), v_int(1)]),
+            v_list(&[v_str("a"), v_int(2)]),
+        ]);
+        for _ in 0..3 {
+            assert_eq!(run_logged_tasks(), expected);
+        }
+    }
+
+    /// A fork runs in its own transaction, which only starts once the task that forked it has
+    /// committed. It sees everything its parent did, including what was done after the fork.
+    #[test]
+    fn test_fork_sees_parent_commit() {
+        let (db, _) = test_db();
+        let scheduler = Arc::new(Scheduler::new(db.clone(), Config::default()));
+        let task_id = submit_verb(&scheduler, SYSTEM_OBJECT, "write_then_fork", vec![]).unwrap();
+        let receiver = scheduler.subscribe_to_task(task_id).unwrap();
+        let loop_scheduler = scheduler.clone();
+        std::thread::spawn(move || loop_scheduler.run());
+
+        let TaskWaiterResult::Success(_) = receiver.recv().unwrap() else {
+            panic!("expected write_then_fork to succeed");
+        };
+        wait_for_property(&db, "ready", v_int(6));
+
+        scheduler.stop().unwrap();
+    }
+
+    /// What `this` and `caller` are at the top of each kind of task. As in LambdaMOO, a command's
+    /// verb sees the player as its caller; nothing calls a server-started verb or an eval, so
+    /// theirs is #-1. A fork carries on with its parent's, and `eval()` sees the verb calling it.
+    #[test]
+    fn test_top_level_this_and_caller() {
+        let (db, player) = test_db();
+        let scheduler = Arc::new(Scheduler::new(db.clone(), Config::default()));
+        let session = Arc::new(NoopClientSession::new());
+
+        let command = scheduler
+            .submit_command_task(player, "whoami", session.clone())
+            .unwrap();
+        let eval = scheduler
+            .submit_eval_task(
+                player,
+                player,
+                "return {this, caller};".to_string(),
+                false,
+                session.clone(),
+            )
+            .unwrap();
+        let verb = submit_verb(&scheduler, SYSTEM_OBJECT, "who_called", vec![]).unwrap();
+        let eval_bf = submit_verb(&scheduler, SYSTEM_OBJECT, "eval_who_called", vec![]).unwrap();
+        let receivers: Vec<_> = [command, eval, verb, eval_bf]
+            .into_iter()
+            .map(|task_id| scheduler.subscribe_to_task(task_id).unwrap())
+            .collect();
+        let loop_scheduler = scheduler.clone();
+        std::thread::spawn(move || loop_scheduler.run());
+        let results: Vec<_> = receivers
+            .into_iter()
+            .map(|receiver| match receiver.recv().unwrap() {
+                TaskWaiterResult::Success(v) => v,
+                other => panic!("expected success, got {other:?}"),
+            })
+            .collect();
+        assert_eq!(
+            results,
+            vec![
+                v_list(&[v_objid(player), v_objid(player), v_objid(player)]),
+                v_list(&[v_objid(player), v_objid(NOTHING)]),
+                v_list(&[v_objid(SYSTEM_OBJECT), v_objid(NOTHING)]),
+                v_list(&[
+                    v_int(1),
+                    v_list(&[v_objid(SYSTEM_OBJECT), v_objid(SYSTEM_OBJECT)])
+                ]),
+            ]
+        );
+
+        submit_verb(&scheduler, SYSTEM_OBJECT, "fork_who_called", vec![]).unwrap();
+        wait_for_property(
+            &db,
+            "ready",
+            v_list(&[v_objid(SYSTEM_OBJECT), v_objid(NOTHING)]),
+        );
+
+        scheduler.stop().unwrap();
+    }
+
+    /// Commands match objects using the core's `$failed_match` and `$nothing`, falling back to
+    /// the usual #-3 and #-1 when #0 doesn't define them.
+    #[test]
+    fn test_command_match_constants() {
+        let (db, player) = test_db();
+        let scheduler = Arc::new(Scheduler::new(db.clone(), Config::default()));
+        let loop_scheduler = scheduler.clone();
+        std::thread::spawn(move || loop_scheduler.run());
+        let examine = || {
+            let task_id = scheduler
+                .submit_command_task(player, "examine widget", Arc::new(NoopClientSession::new()))
+                .unwrap();
+            match scheduler
+                .subscribe_to_task(task_id)
+                .unwrap()
+                .recv()
+                .unwrap()
+            {
+                TaskWaiterResult::Success(v) => v,
+                other => panic!("expected success, got {other:?}"),
+            }
+        };
+
+        assert_eq!(
+            examine(),
+            v_list(&[v_objid(FAILED_MATCH), v_objid(NOTHING)])
+        );
+
+        let mut tx = db.new_world_state().unwrap();
+        for (name, value) in [("failed_match", Objid(-30)), ("nothing", Objid(-10))] {
+            tx.define_property(
+                SYSTEM_OBJECT,
+                SYSTEM_OBJECT,
+                SYSTEM_OBJECT,
+                name,
+                SYSTEM_OBJECT,
+                BitEnum::all(),
+                Some(v_objid(value)),
+            )
+            .unwrap();
+        }
+        tx.commit().unwrap();
+        assert_eq!(
+            examine(),
+            v_list(&[v_objid(Objid(-30)), v_objid(Objid(-10))])
+        );
+
+        scheduler.stop().unwrap();
+    }
+
+    /// A panic inside the VM aborts the task and rolls back what it did, and the scheduler carries
+    /// on running other tasks.
+    #[test]
+    fn test_panic_rolls_back_task() {
+        let (db, _) = test_db();
+        let scheduler = Arc::new(Scheduler::new(db.clone(), Config::default()));
+        let task_id = submit_verb(&scheduler, SYSTEM_OBJECT, "poke_then_break", vec![]).unwrap();
+        let receiver = scheduler.subscribe_to_task(task_id).unwrap();
+        let loop_scheduler = scheduler.clone();
+        std::thread::spawn(move || loop_scheduler.run());
+
+        let TaskWaiterResult::Error(SchedulerError::TaskAbortedError) = receiver.recv().unwrap()
+        else {
+            panic!("expected the panicking task to be aborted");
+        };
+        let mut tx = db.new_world_state().unwrap();
+        assert_eq!(
+            tx.retrieve_property(SYSTEM_OBJECT, SYSTEM_OBJECT, "counter")
+                .unwrap(),
+            v_int(1)
+        );
+        tx.rollback().unwrap();
+
+        submit_verb(&scheduler, SYSTEM_OBJECT, "bump_counter", vec![]).unwrap();
+        wait_for_property(&db, "counter", v_int(2));
+
+        scheduler.stop().unwrap();
+    }
+
+    /// The content type given to `notify` reaches the session along with the text, and defaults to
+    /// plain text.
+    #[test]
+    fn test_notify_content_type() {
+        let (db, _) = test_db();
+        let scheduler = Arc::new(Scheduler::new(db, Config::default()));
+        let session = Arc::new(MockClientSession::new());
+        let task_id = scheduler
+            .submit_verb_task(
+                SYSTEM_OBJECT,
+                SYSTEM_OBJECT,
+                "notify_typed".to_string(),
+                vec![],
+                "".to_string(),
+                SYSTEM_OBJECT,
+                session.clone(),
+            )
+            .unwrap();
+        let receiver = scheduler.subscribe_to_task(task_id).unwrap();
+        let loop_scheduler = scheduler.clone();
+        std::thread::spawn(move || loop_scheduler.run());
+
+        let TaskWaiterResult::Success(_) = receiver.recv().unwrap() else {
+            panic!("expected the task to succeed");
+        };
+        let events: Vec<_> = session.committed().into_iter().map(|e| e.event()).collect();
+        assert_eq!(
+            events,
+            vec![
+                Event::TextNotify("plain".to_string(), ContentType::TextPlain),
+                Event::TextNotify("<b>rich</b>".to_string(), ContentType::TextHtml),
+                Event::TextNotify("flushed".to_string(), ContentType::TextPlain),
+            ]
+        );
+
+        scheduler.stop().unwrap();
+    }
+
+    /// An eval's result is shown to the player the way LambdaMOO's `;` shows it, when asked for;
+    /// otherwise it only goes back to whoever submitted the eval.
+    #[test]
+    fn test_eval_result_output() {
+        let (db, player) = test_db();
+        let scheduler = Arc::new(Scheduler::new(db, Config::default()));
+        let shown_session = Arc::new(MockClientSession::new());
+        let quiet_session = Arc::new(MockClientSession::new());
+        let shown = scheduler
+            .submit_eval_task(
+                player,
+                player,
+                "return 1 + 1;".to_string(),
+                true,
+                shown_session.clone(),
+            )
+            .unwrap();
+        let quiet = scheduler
+            .submit_eval_task(
+                player,
+                player,
+                "return 1 + 1;".to_string(),
+                false,
+                quiet_session.clone(),
+            )
+            .unwrap();
+        let receivers: Vec<_> = [shown, quiet]
+            .into_iter()
+            .map(|task_id| scheduler.subscribe_to_task(task_id).unwrap())
+            .collect();
+        let loop_scheduler = scheduler.clone();
+        std::thread::spawn(move || loop_scheduler.run());
+
+        for receiver in receivers {
+            let TaskWaiterResult::Success(result) = receiver.recv().unwrap() else {
+                panic!("expected the eval to succeed");
+            };
+            assert_eq!(result, v_int(2));
+        }
+        let events: Vec<_> = shown_session
+            .committed()
+            .into_iter()
+            .map(|e| e.event())
+            .collect();
+        assert_eq!(
+            events,
+            vec![Event::TextNotify(
+                "=> 2".to_string(),
+                ContentType::TextPlain
+            )]
+        );
+        assert!(quiet_session.committed().is_empty());
+
+        scheduler.stop().unwrap();
+    }
+
+    /// Output from `notify_oob` is tagged as out-of-band in the session's event stream, and output
+    /// from `notify` isn't.
+    #[test]
+    fn test_notify_oob() {
+        let (db, _) = test_db();
+        let scheduler = Arc::new(Scheduler::new(db, Config::default()));
+        let session = Arc::new(MockClientSession::new());
+        let task_id = scheduler
+            .submit_verb_task(
+                SYSTEM_OBJECT,
+                SYSTEM_OBJECT,
+                "notify_mcp".to_string(),
+                vec![],
+                "".to_string(),
+                SYSTEM_OBJECT,
+                session.clone(),
+            )
+            .unwrap();
+        let receiver = scheduler.subscribe_to_task(task_id).unwrap();
+        let loop_scheduler = scheduler.clone();
+        std::thread::spawn(move || loop_scheduler.run());
+
+        let TaskWaiterResult::Success(_) = receiver.recv().unwrap() else {
+            panic!("expected the task to succeed");
+        };
+        let events: Vec<_> = session
+            .committed()
+            .into_iter()
+            .map(|e| (e.event(), e.is_oob()))
+            .collect();
+        assert_eq!(
+            events,
+            vec![
+                (
+                    Event::TextNotify("hello".to_string(), ContentType::TextPlain),
+                    false
+                ),
+                (
+                    Event::TextNotify(
+                        "#$#mcp version: 2.1 to: 2.1".to_string(),
+                        ContentType::TextPlain
+                    ),
+                    true
+                ),
+            ]
+        );
+
+        scheduler.stop().unwrap();
+    }
+
+    /// Submit `verb` as the wizard on #0 with its own mock session, and subscribe to its result.
+    fn submit_with_mock_session(
+        scheduler: &Scheduler,
+        verb: &str,
+    ) -> (TaskId, Arc<MockClientSession>, Receiver<TaskWaiterResult>) {
+        let session = Arc::new(MockClientSession::new());
+        let task_id = scheduler
+            .submit_verb_task(
+                SYSTEM_OBJECT,
+                SYSTEM_OBJECT,
+                verb.to_string(),
+                vec![],
+                "".to_string(),
+                SYSTEM_OBJECT,
+                session.clone(),
+            )
+            .unwrap();
+        let receiver = scheduler.subscribe_to_task(task_id).unwrap();
+        (task_id, session, receiver)
+    }
+
+    /// A task that kills itself part way through leaves neither writes nor output behind.
+    #[test]
+    fn test_kill_self_rolls_back() {
+        let (db, _) = test_db();
+        let scheduler = Arc::new(Scheduler::new(db.clone(), Config::default()));
+        let (_, session, receiver) = submit_with_mock_session(&scheduler, "poke_then_kill_self");
+        let loop_scheduler = scheduler.clone();
+        std::thread::spawn(move || loop_scheduler.run());
+
+        let TaskWaiterResult::Error(SchedulerError::TaskAbortedCancelled) =
+            receiver.recv().unwrap()
+        else {
+            panic!("expected the task to be cancelled");
+        };
+        let mut tx = db.new_world_state().unwrap();
+        assert_eq!(
+            tx.retrieve_property(SYSTEM_OBJECT, SYSTEM_OBJECT, "counter")
+                .unwrap(),
+            v_int(1)
+        );
+        tx.rollback().unwrap();
+        assert!(session.committed().is_empty());
+
+        scheduler.stop().unwrap();
+    }
+
+    /// Every error code, uncaught, ends up with its message on the first line of the traceback.
+    #[test]
+    fn test_error_tracebacks() {
+        let (db, player) = test_db();
+        let scheduler = Arc::new(Scheduler::new(db, Config::default()));
+        let loop_scheduler = scheduler.clone();
+        std::thread::spawn(move || loop_scheduler.run());
+
+        let uncaught = |verb: &str, args: Vec<Var>| {
+            let task_id = submit_verb(&scheduler, player, verb, args).unwrap();
+            let receiver = scheduler.subscribe_to_task(task_id).unwrap();
+            match receiver.recv().unwrap() {
+                TaskWaiterResult::Error(SchedulerError::TaskAbortedException(exception)) => {
+                    exception
+                }
+                other => panic!("expected {verb} to throw, got {other:?}"),
+            }
+        };
+        for code in (0..=Error::E_FLOAT as u8).map(|c| Error::from_repr(c).unwrap()) {
+            let exception = uncaught("raise_arg", vec![v_err(code)]);
+            assert_eq!(exception.code, code);
+            let Variant::Str(first) = exception.backtrace[0].variant() else {
+                panic!("expected a traceback line, got {:?}", exception.backtrace);
+            };
+            assert!(
+                first.as_str().ends_with(&format!(": {}", code.message())),
+                "{code:?}: {}",
+                first.as_str()
+            );
+        }
+
+        let exception = uncaught("overflow_float", vec![]);
+        assert_eq!(exception.code, E_FLOAT);
+        assert_eq!(
+            exception.backtrace[0],
+            v_str("#0:overflow_float (line 1): Floating-point arithmetic error")
+        );
+
+        scheduler.stop().unwrap();
+    }
+
+    /// Calls queued with `call_after_commit` run only once the queueing task commits; an aborted
+    /// task's queue is dropped along with its writes.
+    #[test]
+    fn test_call_after_commit() {
+        let (db, _) = test_db();
+        let scheduler = Arc::new(Scheduler::new(db.clone(), Config::default()));
+        let (_, _, aborted) = submit_with_mock_session(&scheduler, "queue_then_kill_self");
+        let loop_scheduler = scheduler.clone();
+        std::thread::spawn(move || loop_scheduler.run());
+
+        let TaskWaiterResult::Error(SchedulerError::TaskAbortedCancelled) = aborted.recv().unwrap()
+        else {
+            panic!("expected the task to be cancelled");
+        };
+
+        let (_, _, committed) = submit_with_mock_session(&scheduler, "queue_bump");
+        let TaskWaiterResult::Success(_) = committed.recv().unwrap() else {
+            panic!("expected the queueing task to succeed");
+        };
+        wait_for_property(&db, "counter", v_int(2));
+
+        // Give a wrongly-dispatched call from the aborted task the chance to show up.
+        std::thread::sleep(Duration::from_millis(100));
+        let mut tx = db.new_world_state().unwrap();
+        assert_eq!(
+            tx.retrieve_property(SYSTEM_OBJECT, SYSTEM_OBJECT, "counter")
+                .unwrap(),
+            v_int(2)
+        );
+        tx.rollback().unwrap();
+
+        scheduler.stop().unwrap();
+    }
+
+    /// Killing suspended or input-waiting tasks from another task rolls back whatever they had
+    /// open, discards their unflushed output, and forgets their input requests.
+    #[test]
+    fn test_kill_waiting_tasks() {
+        let (db, _) = test_db();
+        let scheduler = Arc::new(Scheduler::new(db.clone(), Config::default()));
+        let (suspended_id, suspended_session, suspended) =
+            submit_with_mock_session(&scheduler, "poke_after_suspend");
+        let (waiting_id, _, waiting_input) = submit_with_mock_session(&scheduler, "wait_input");
+        for victim in [suspended_id, waiting_id] {
+            submit_verb(
+                &scheduler,
+                SYSTEM_OBJECT,
+                "kill_when_queued",
+                vec![v_int(victim as i64)],
+            )
+            .unwrap();
+        }
+        let loop_scheduler = scheduler.clone();
+        std::thread::spawn(move || loop_scheduler.run());
+
+        for receiver in [suspended, waiting_input] {
+            let TaskWaiterResult::Error(SchedulerError::TaskAbortedCancelled) =
+                receiver.recv().unwrap()
+            else {
+                panic!("expected the task to be cancelled");
+            };
+        }
+        let mut tx = db.new_world_state().unwrap();
+        assert_eq!(
+            tx.retrieve_property(SYSTEM_OBJECT, SYSTEM_OBJECT, "counter")
+                .unwrap(),
+            v_int(1)
+        );
+        tx.rollback().unwrap();
+        // Only what was committed when the task suspended got out.
+        let committed: Vec<_> = suspended_session
+            .committed()
+            .into_iter()
+            .map(|e| e.event())
+            .collect();
+        assert_eq!(
+            committed,
+            vec![Event::TextNotify(
+                "before".to_string(),
+                ContentType::TextPlain
+            )]
+        );
+        wait_for_no_input_requests(&scheduler);
+
+        scheduler.stop().unwrap();
+    }
+
+    /// A forked task can be killed before it ever starts, by its owner or a wizard, and it never
+    /// gets to run.
+    #[test]
+    fn test_kill_forked_task() {
+        let (db, player) = test_db();
+        let scheduler = Arc::new(Scheduler::new(db.clone(), Config::default()));
+        let task_id = submit_verb(
+            &scheduler,
+            SYSTEM_OBJECT,
+            "kill_fork",
+            vec![v_objid(player)],
+        )
+        .unwrap();
+        let receiver = scheduler.subscribe_to_task(task_id).unwrap();
+        let loop_scheduler = scheduler.clone();
+        std::thread::spawn(move || loop_scheduler.run());
+
+        let TaskWaiterResult::Success(result) = receiver.recv().unwrap() else {
+            panic!("expected kill_fork to succeed");
+        };
+        let Variant::List(result) = result.variant() else {
+            panic!("expected a list, got {:?}", result);
+        };
+        let Variant::Int(fork_id) = result[0].variant() else {
+            panic!("expected a task id, got {:?}", result[0]);
+        };
+        // The player can't kill the wizard's fork, there's no task -1, and the wizard can.
+        assert_eq!(
+            result[1..].to_vec(),
+            vec![v_err(E_PERM), v_err(E_INVARG), v_none()]
+        );
+
+        // The fork goes away without its body having run.
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while scheduler.tasks.contains_key(&(*fork_id as TaskId)) {
+            assert!(Instant::now() < deadline, "timed out waiting for the fork");
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        let mut tx = db.new_world_state().unwrap();
+        assert_eq!(
+            tx.retrieve_property(SYSTEM_OBJECT, SYSTEM_OBJECT, "counter")
+                .unwrap(),
+            v_int(1)
+        );
+        tx.rollback().unwrap();
+
+        scheduler.stop().unwrap();
+    }
+
+    /// queued_tasks() reports why each task is waiting, and how long delayed ones have left.
+    #[test]
+    fn test_queued_tasks_suspension() {
+        let (db, _) = test_db();
+        let scheduler = Arc::new(Scheduler::new(db, Config::default()));
+        let session = Arc::new(MockClientSession::new());
+        let submit = |verb: &str| {
+            scheduler
+                .submit_verb_task(
+                    SYSTEM_OBJECT,
+                    SYSTEM_OBJECT,
+                    verb.to_string(),
+                    vec![],
+                    "".to_string(),
+                    SYSTEM_OBJECT,
+                    session.clone(),
+                )
+                .unwrap()
+        };
+        for verb in ["delayed_fork", "idle", "wait_input"] {
+            submit(verb);
+        }
+        let task_id = submit("list_queued");
+        let receiver = scheduler.subscribe_to_task(task_id).unwrap();
+        let loop_scheduler = scheduler.clone();
+        std::thread::spawn(move || loop_scheduler.run());
+
+        let TaskWaiterResult::Success(result) = receiver.recv().unwrap() else {
+            panic!("expected list_queued to succeed");
+        };
+        let Variant::List(entries) = result.variant() else {
+            panic!("expected a list, got {:?}", result);
+        };
+        // Tasks come back in no particular order.
+        let mut entries: Vec<_> = entries.iter().cloned().collect();
+        entries.sort();
+        let expected = vec![
+            v_list(&[v_str("indefinite"), v_int(0)]),
+            v_list(&[v_str("input"), v_int(0)]),
+            v_list(&[v_str("timed"), v_int(1)]),
+        ];
+        assert_eq!(entries, expected);
+
+        scheduler.stop().unwrap();
+    }
+
+    /// task_stack() shows a suspended task's frames innermost first, but only to its owner or a
+    /// wizard, and only while it is queued.
+    #[test]
+    fn test_task_stack() {
+        let (db, player) = test_db();
+        let scheduler = Arc::new(Scheduler::new(db, Config::default()));
+        let victim = submit_verb(&scheduler, SYSTEM_OBJECT, "nested_idle", vec![]).unwrap();
+        let mut receivers = vec![];
+        for (args, verb) in [
+            (vec![v_int(victim as i64)], "stack_of"),
+            (vec![v_int(victim as i64), v_objid(player)], "stack_of"),
+            (vec![], "own_stack"),
+        ] {
+            let task_id = submit_verb(&scheduler, SYSTEM_OBJECT, verb, args).unwrap();
+            receivers.push(scheduler.subscribe_to_task(task_id).unwrap());
+        }
+        let loop_scheduler = scheduler.clone();
+        std::thread::spawn(move || loop_scheduler.run());
+
+        let results: Vec<_> = receivers
+            .into_iter()
+            .map(|receiver| {
+                let TaskWaiterResult::Success(result) = receiver.recv().unwrap() else {
+                    panic!("expected the task to succeed");
+                };
+                result
+            })
+            .collect();
+        let frame = |verb: &str| {
+            v_list(&[
+                v_objid(SYSTEM_OBJECT),
+                v_str(verb),
+                v_objid(SYSTEM_OBJECT),
+                v_objid(SYSTEM_OBJECT),
+                v_objid(SYSTEM_OBJECT),
+                v_int(0),
+            ])
+        };
+        assert_eq!(
+            results,
+            vec![
+                v_list(&[frame("idle"), frame("nested_idle")]),
+                v_err(E_PERM),
+                v_err(E_INVARG),
+            ]
+        );
+
+        scheduler.stop().unwrap();
+    }
+
+    /// resume() only wakes suspended tasks, and only for their owner or a wizard.
+    #[test]
+    fn test_resume_validation() {
+        let (db, player) = test_db();
+        let scheduler = Arc::new(Scheduler::new(db, Config::default()));
+        let session = Arc::new(MockClientSession::new());
+        let submit = |verb: &str, args: Vec<Var>| {
+            let task_id = scheduler
+                .submit_verb_task(
+                    SYSTEM_OBJECT,
+                    SYSTEM_OBJECT,
+                    verb.to_string(),
+                    args,
+                    "".to_string(),
+                    SYSTEM_OBJECT,
+                    session.clone(),
+                )
+                .unwrap();
+            scheduler.subscribe_to_task(task_id).map(|r| (task_id, r))
+        };
+        let (victim, victim_result) = submit("await_resume", vec![]).unwrap();
+        let (reader, _) = submit("wait_input", vec![]).unwrap();
+        let (_, check_result) = submit(
+            "check_resume",
+            vec![v_int(victim as i64), v_int(reader as i64), v_objid(player)],
+        )
+        .unwrap();
+        let loop_scheduler = scheduler.clone();
+        std::thread::spawn(move || loop_scheduler.run());
+
+        let TaskWaiterResult::Success(checks) = check_result.recv().unwrap() else {
+            panic!("expected check_resume to succeed");
+        };
+        // A non-owner can't resume the victim, nobody can resume a task that isn't suspended
+        // (whoever they are), and the owner can.
+        assert_eq!(
+            checks,
+            v_list(&[v_err(E_PERM), v_err(E_INVARG), v_err(E_INVARG), v_none()])
+        );
+        let TaskWaiterResult::Success(woken_with) = victim_result.recv().unwrap() else {
+            panic!("expected the victim to succeed");
+        };
+        assert_eq!(woken_with, v_str("woken"));
+
+        scheduler.stop().unwrap();
+    }
+
+    /// Spin until `task_id` is blocked in read() and queued for input, and return its input
+    /// request id.
+    fn wait_for_input_request(scheduler: &Scheduler, task_id: TaskId) -> Uuid {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        loop {
+            let queued = scheduler
+                .input_queues
+                .lock()
+                .unwrap()
+                .values()
+                .flatten()
+                .find(|(_, queued_task)| *queued_task == task_id)
+                .map(|(input_request_id, _)| *input_request_id);
+            if let Some(input_request_id) = queued {
+                return input_request_id;
+            }
+            assert!(Instant::now() < deadline, "timed out waiting for read()");
+            std::thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    /// read() blocks for a line from its own player or, for wizards, from someone else's
+    /// connection, and only takes input from the player it asked.
+    #[test]
+    fn test_read_input() {
+        let (db, player) = test_db();
+        let scheduler = Arc::new(Scheduler::new(db, Config::default()));
+        let session = Arc::new(MockClientSession::new());
+        let submit = |verb: &str, args: Vec<Var>| {
+            let task_id = scheduler
+                .submit_verb_task(
+                    SYSTEM_OBJECT,
+                    SYSTEM_OBJECT,
+                    verb.to_string(),
+                    args,
+                    "".to_string(),
+                    SYSTEM_OBJECT,
+                    session.clone(),
+                )
+                .unwrap();
+            (task_id, scheduler.subscribe_to_task(task_id).unwrap())
+        };
+        let (own, own_result) = submit("read_line", vec![]);
+        let (other, other_result) = submit("read_from", vec![v_objid(player)]);
+        let loop_scheduler = scheduler.clone();
+        std::thread::spawn(move || loop_scheduler.run());
+
+        let own_request = wait_for_input_request(&scheduler, own);
+        let other_request = wait_for_input_request(&scheduler, other);
+        let mut asked = session.system();
+        asked.sort();
+        assert_eq!(
+            asked,
+            vec![
+                "0: input requested".to_string(),
+                format!("{}: input requested", player.0)
+            ]
+        );
+
+        // Lines from the wrong player are turned away, and leave the request open.
+        assert!(scheduler
+            .submit_requested_input(player, own_request, "nope".to_string())
+            .is_err());
+        assert!(scheduler
+            .submit_requested_input(SYSTEM_OBJECT, other_request, "nope".to_string())
+            .is_err());
+
+        scheduler
+            .submit_requested_input(SYSTEM_OBJECT, own_request, "hello".to_string())
+            .unwrap();
+        scheduler
+            .submit_requested_input(player, other_request, "hi there".to_string())
+            .unwrap();
+        let results = [own_result, other_result].map(|receiver| {
+            let TaskWaiterResult::Success(result) = receiver.recv().unwrap() else {
+                panic!("expected read() to succeed");
+            };
+            result
+        });
+        assert_eq!(results, [v_str("hello"), v_str("hi there")]);
+        wait_for_no_input_requests(&scheduler);
+
+        scheduler.stop().unwrap();
+    }
+
+    /// When several tasks are reading from the same player, lines go to them in the order they
+    /// called read(), and each is only asked for once the one ahead of it has its line.
+    #[test]
+    fn test_read_input_queues_readers() {
+        let (db, _) = test_db();
+        let scheduler = Arc::new(Scheduler::new(db, Config::default()));
+        let loop_scheduler = scheduler.clone();
+        std::thread::spawn(move || loop_scheduler.run());
+
+        // Neither reader can finish without input, so there's no race in subscribing here.
+        let (first, first_session, first_result) =
+            submit_with_mock_session(&scheduler, "read_line");
+        let first_request = wait_for_input_request(&scheduler, first);
+        let (second, second_session, second_result) =
+            submit_with_mock_session(&scheduler, "read_line");
+        let second_request = wait_for_input_request(&scheduler, second);
+
+        // Only the first reader has been asked for a line so far, so that's the only request a
+        // client could answer.
+        assert_eq!(first_session.system(), vec!["0: input requested"]);
+        assert!(second_session.system().is_empty());
+        assert!(scheduler
+            .submit_requested_input(SYSTEM_OBJECT, second_request, "jumped".to_string())
+            .is_err());
+
+        let first_result = std::thread::spawn(move || first_result.recv().unwrap());
+        scheduler
+            .submit_requested_input(SYSTEM_OBJECT, first_request, "one".to_string())
+            .unwrap();
+        let TaskWaiterResult::Success(result) = first_result.join().unwrap() else {
+            panic!("expected the first reader to succeed");
+        };
+        assert_eq!(result, v_str("one"));
+
+        // Now it's the second reader's turn.
+        assert_eq!(second_session.system(), vec!["0: input requested"]);
+        let second_result = std::thread::spawn(move || second_result.recv().unwrap());
+        scheduler
+            .submit_requested_input(SYSTEM_OBJECT, second_request, "two".to_string())
+            .unwrap();
+        let TaskWaiterResult::Success(result) = second_result.join().unwrap() else {
+            panic!("expected the second reader to succeed");
+        };
+        assert_eq!(result, v_str("two"));
+        assert!(scheduler.input_queues.lock().unwrap().is_empty());
+
+        scheduler.stop().unwrap();
+    }
+
+    /// Submit `per_thread` tasks running `verb` from each of `threads` threads at once, returning
+    /// the results of the submissions.
+    fn submit_concurrently(
+        scheduler: &Arc<Scheduler>,
+        player: Objid,
+        verb: &'static str,
+        threads: usize,
+        per_thread: usize,
+    ) -> Vec<Result<usize, SchedulerError>> {
+        let spawners: Vec<_> = (0..threads)
+            .map(|_| {
+                let scheduler = scheduler.clone();
+                std::thread::spawn(move || {
+                    (0..per_thread)
+                        .map(|_| submit_verb(&scheduler, player, verb, vec![]))
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        spawners
+            .into_iter()
+            .flat_map(|s| s.join().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_concurrent_task_spawns() {
+        let (db, player) = test_db();
+        let config = Config {
+            max_tasks_per_player: Some(1000),
+            ..Default::default()
+        };
+        let scheduler = Arc::new(Scheduler::new(db, config));
+        let loop_scheduler = scheduler.clone();
+        std::thread::spawn(move || loop_scheduler.run());
+
+        let mut task_ids: Vec<_> = submit_concurrently(&scheduler, player, "noop", 8, 50)
+            .into_iter()
+            .map(|r| r.unwrap())
+            .collect();
+        task_ids.sort();
+        task_ids.dedup();
+        assert_eq!(task_ids.len(), 400);
+
+        // Every task runs to completion, and the per-player counts drain along with them.
+        let deadline = Instant::now() + Duration::from_secs(30);
+        while !scheduler.tasks.is_empty() || !scheduler.player_task_counts.is_empty() {
+            assert!(Instant::now() < deadline, "timed out waiting for tasks");
+            std::thread::sleep(Duration::from_millis(5));
+        }
+
+        scheduler.stop().unwrap();
+    }
+
+    /// A task that can't resolve the verb it was started for (here because the player may not read
+    /// it) is dropped, and gives back its slot.
+    #[test]
+    fn test_failed_start_releases_task_slot() {
+        let (db, player) = test_db();
+        let config = Config {
+            max_tasks_per_player: Some(1),
+            ..Default::default()
+        };
+        let scheduler = Arc::new(Scheduler::new(db, config));
+        let loop_scheduler = scheduler.clone();
+        std::thread::spawn(move || loop_scheduler.run());
+
+        // With a limit of one, the second start is refused if the first one's slot leaked.
+        for _ in 0..2 {
+            scheduler
+                .submit_verb_task(
+                    player,
+                    SYSTEM_OBJECT,
+                    "unreadable".to_string(),
+                    vec![],
+                    "".to_string(),
+                    player,
+                    Arc::new(NoopClientSession::new()),
+                )
+                .unwrap();
+            let deadline = Instant::now() + Duration::from_secs(10);
+            while !scheduler.tasks.is_empty() || !scheduler.player_task_counts.is_empty() {
+                assert!(
+                    Instant::now() < deadline,
+                    "timed out waiting for the task to go"
+                );
+                std::thread::sleep(Duration::from_millis(5));
+            }
+        }
+
+        scheduler.stop().unwrap();
+    }
+
+    #[test]
+    fn test_concurrent_task_spawns_respect_limit() {
+        let (db, player) = test_db();
+        let config = Config {
+            max_tasks_per_player: Some(5),
+            ..Default::default()
+        };
+        let scheduler = Arc::new(Scheduler::new(db, config));
+        let loop_scheduler = scheduler.clone();
+        std::thread::spawn(move || loop_scheduler.run());
+
+        let results = submit_concurrently(&scheduler, player, "idle", 8, 5);
+        assert_eq!(results.iter().filter(|r| r.is_ok()).count(), 5);
+        assert!(results
+            .iter()
+            .filter_map(|r| r.as_ref().err())
+            .all(|e| matches!(e, SchedulerError::TooManyTasks(_))));
+        assert_eq!(*scheduler.player_task_counts.get(&player).unwrap(), 5);
+
+        scheduler.stop().unwrap();
+    }
 }
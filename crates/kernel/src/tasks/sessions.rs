@@ -241,10 +241,11 @@ impl Session for MockClientSession {
     }
 
     fn request_input(&self, player: Objid, _input_request_id: Uuid) -> Result<(), SessionError> {
-        panic!(
-            "MockClientSession::request_input called for player {}",
-            player.0
-        )
+        self.system
+            .write()
+            .unwrap()
+            .push(format!("{}: input requested", player.0));
+        Ok(())
     }
 
     fn send_event(&self, _player: Objid, msg: NarrativeEvent) -> Result<(), SessionError> {
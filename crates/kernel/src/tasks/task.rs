@@ -14,6 +14,8 @@
 
 use kanal::{ReceiveErrorTimeout, Receiver, Sender};
 
+use std::any::Any;
+use std::panic::{catch_unwind, AssertUnwindSafe};
 use std::sync::Arc;
 use std::time::{Duration, SystemTime};
 
@@ -21,19 +23,24 @@ use tracing::{debug, error, trace, warn};
 
 use moor_values::model::CommandError::PermissionDenied;
 use moor_values::model::VerbInfo;
-use moor_values::model::{CommandError, CommitResult, WorldStateError};
+use moor_values::model::{CommandError, CommitResult, NarrativeEvent, WorldStateError};
 use moor_values::model::{WorldState, WorldStateSource};
-use moor_values::util::parse_into_words;
+use moor_values::util::{parse_into_words, BitEnum};
+use moor_values::var::Error::{E_INVARG, E_QUOTA};
 use moor_values::var::Objid;
 use moor_values::var::{v_int, v_string};
 use moor_values::NOTHING;
 
-use crate::matching::match_env::MatchEnvironmentParseMatcher;
+use crate::config::Config;
+use crate::matching::match_env::{MatchConstants, MatchEnvironmentParseMatcher};
 use crate::matching::ws_match_env::WsMatchEnv;
 use crate::tasks::command_parse::{parse_command, ParseCommandError, ParsedCommand};
 
+use crate::tasks::program_cache::ProgramCache;
+use crate::tasks::scheduler::SchedulerError;
 use crate::tasks::sessions::Session;
 use crate::tasks::task_messages::{SchedulerControlMsg, TaskControlMsg, TaskStart};
+use crate::tasks::verb_metrics::VerbMetrics;
 use crate::tasks::vm_host::{VMHostResponse, VmHost};
 use crate::tasks::{PhantomUnsend, PhantomUnsync, TaskDescription, TaskId, VerbCall};
 
@@ -69,72 +76,15 @@ pub(crate) struct Task {
     pub(crate) vm_host: VmHost,
     /// Should I die?
     pub(crate) done: bool,
+    /// Where the limits for this task come from.
+    config: Arc<Config>,
+    /// Whether this is a forked task, which gets the background limits.
+    is_background: bool,
 
     unsend: PhantomUnsend,
     unsync: PhantomUnsync,
 }
 
-// TODO Propagate default ticks, seconds values from global config / args properly.
-//   Note these can be overridden in-core as well, server_options, will need caching, etc.
-const DEFAULT_FG_TICKS: usize = 60_000;
-const DEFAULT_BG_TICKS: usize = 30_000;
-const DEFAULT_FG_SECONDS: u64 = 5;
-const DEFAULT_BG_SECONDS: u64 = 3;
-const DEFAULT_MAX_STACK_DEPTH: usize = 50;
-
-fn max_vm_values(_ws: &mut dyn WorldState, is_background: bool) -> (usize, u64, usize) {
-    let (max_ticks, max_seconds, max_stack_depth) = if is_background {
-        (
-            DEFAULT_BG_TICKS,
-            DEFAULT_BG_SECONDS,
-            DEFAULT_MAX_STACK_DEPTH,
-        )
-    } else {
-        (
-            DEFAULT_FG_TICKS,
-            DEFAULT_FG_SECONDS,
-            DEFAULT_MAX_STACK_DEPTH,
-        )
-    };
-
-    //
-    // // Look up fg_ticks, fg_seconds, and max_stack_depth on $server_options.
-    // // These are optional properties, and if they are not set, we use the defaults.
-    // let wizperms = PermissionsContext::root_for(Objid(2), BitEnum::new_with(ObjFlag::Wizard));
-    // if let Ok(server_options) = ws
-    //     .retrieve_property(wizperms.clone(), Objid(0), "server_options")
-    //
-    // {
-    //     if let Variant::Obj(server_options) = server_options.variant() {
-    //         if let Ok(v) = ws
-    //             .retrieve_property(wizperms.clone(), *server_options, "fg_ticks")
-    //
-    //         {
-    //             if let Variant::Int(v) = v.variant() {
-    //                 max_ticks = *v as usize;
-    //             }
-    //         }
-    //         if let Ok(v) = ws
-    //             .retrieve_property(wizperms.clone(), *server_options, "fg_seconds")
-    //
-    //         {
-    //             if let Variant::Int(v) = v.variant() {
-    //                 max_seconds = *v as u64;
-    //             }
-    //         }
-    //         if let Ok(v) = ws
-    //             .retrieve_property(wizperms, *server_options, "max_stack_depth")
-    //
-    //         {
-    //             if let Variant::Int(v) = v.variant() {
-    //                 max_stack_depth = *v as usize;
-    //             }
-    //         }
-    //     }
-    // }
-    (max_ticks, max_seconds, max_stack_depth)
-}
-
 impl Task {
     // Yes yes I know it's a lot of arguments, but wrapper object here is redundant.
     #[allow(clippy::too_many_arguments)]
@@ -142,38 +92,50 @@ impl Task {
         task_id: TaskId,
         task_start: TaskStart,
         perms: Objid,
-        delay_start: Option<Duration>,
         state_source: Arc<dyn WorldStateSource>,
         is_background: bool,
         session: Arc<dyn Session>,
         task_control_receiver: Receiver<TaskControlMsg>,
         control_sender: Sender<(TaskId, SchedulerControlMsg)>,
+        verb_metrics: Arc<VerbMetrics>,
+        program_cache: Arc<ProgramCache>,
+        config: Arc<Config>,
     ) {
-        // TODO: Defer task delay to the scheduler, and let it handle the delay?
-        //   Instead of performing it in the task startup.
-        if let Some(delay) = delay_start {
-            std::thread::sleep(delay);
-        }
-
-        // Start the transaction.
-        let mut world_state = state_source
-            .new_world_state()
-            .expect("Could not start transaction for new task");
+        // Start the transaction. If we can't, tell the scheduler, so it drops the task rather
+        // than leave it (and the player's task slot) behind.
+        let world_state = match state_source.new_world_state() {
+            Ok(world_state) => world_state,
+            Err(e) => {
+                error!(?task_id, error = ?e, "Could not start transaction for new task");
+                control_sender
+                    .send((
+                        task_id,
+                        SchedulerControlMsg::TaskAbortPanicked(format!(
+                            "Could not start transaction: {:?}",
+                            e
+                        )),
+                    ))
+                    .expect("Could not send start response");
+                return;
+            }
+        };
 
-        // Find out max ticks, etc. for this task. These are either pulled from server constants in
-        // the DB or from default constants.
-        let (max_ticks, max_seconds, max_stack_depth) =
-            max_vm_values(world_state.as_mut(), is_background);
+        // Until we know whose verb we're running, assume the ordinary limits.
+        let limits = config.task_limits(BitEnum::new(), is_background);
 
         let scheduler_control_sender = control_sender.clone();
-        let vm_host = VmHost::new(
+        let mut vm_host = VmHost::new(
             task_id,
-            max_stack_depth,
-            max_ticks,
-            Duration::from_secs(max_seconds),
+            limits,
             session.clone(),
             scheduler_control_sender.clone(),
+            verb_metrics,
+            program_cache,
         );
+        if config.coverage {
+            vm_host.enable_coverage();
+        }
+        vm_host.set_clock(config.clock.clone());
         let mut task = Task {
             task_id,
             task_start,
@@ -183,12 +145,17 @@ impl Task {
             world_state,
             perms,
             done: false,
+            config,
+            is_background,
             unsend: Default::default(),
             unsync: Default::default(),
         };
 
+        // In deterministic mode, everything but a fork (which starts suspended anyway) waits for
+        // its turn, when the scheduler will `Restart` it in a fresh transaction.
         let start = task.task_start.clone();
-        if !task.setup_task_start(start) {
+        let held = task.config.deterministic && !matches!(start, TaskStart::StartFork { .. });
+        if !held && !task.setup_task_start(start) {
             task.done = true;
             return;
         }
@@ -196,7 +163,12 @@ impl Task {
         trace!(task_id = ?task.task_id, "Task started");
         while !task.done {
             if task.vm_host.is_running() {
-                let vm_continuation = task.vm_dispatch();
+                // A panic in here is a bug in the VM, but it shouldn't take the task's thread
+                // down with its transaction still open; turn it into a clean abort instead.
+                let vm_continuation = match catch_unwind(AssertUnwindSafe(|| task.vm_dispatch())) {
+                    Ok(vm_continuation) => vm_continuation,
+                    Err(panic) => Some(task.abort_panicked(panic)),
+                };
                 if let Some(scheduler_msg) = vm_continuation {
                     scheduler_control_sender
                         .send((task.task_id, scheduler_msg))
@@ -248,6 +220,14 @@ impl Task {
         }
     }
 
+    /// Use the configured limits for a task running a verb owned by `owner`.
+    fn apply_task_limits(&mut self, owner: Objid) {
+        // An owner that's gone (or never was) just gets the ordinary limits.
+        let owner_flags = self.world_state.flags_of(owner).unwrap_or_default();
+        let limits = self.config.task_limits(owner_flags, self.is_background);
+        self.vm_host.set_limits(limits);
+    }
+
     /// Set the task up to start executing, based on the task start configuration.
     fn setup_task_start(&mut self, task_start: TaskStart) -> bool {
         match task_start {
@@ -304,9 +284,20 @@ impl Task {
                         error!(task_id = ?self.task_id, this = ?verb_call.this,
                                verb = verb_call.verb_name,
                                "World state error while resolving verb: {:?}", e);
-                        panic!("Could not resolve verb: {:?}", e);
+                        self.scheduler_control_sender
+                            .send((
+                                self.task_id,
+                                SchedulerControlMsg::TaskAbortPanicked(format!(
+                                    "Could not resolve verb: {:?}",
+                                    e
+                                )),
+                            ))
+                            .expect("Could not send start response");
+                        self.done = true;
+                        return false;
                     }
                     Ok(verb_info) => {
+                        self.apply_task_limits(verb_info.verbdef().owner());
                         self.vm_host.start_call_method_verb(
                             self.task_id,
                             self.perms,
@@ -321,11 +312,15 @@ impl Task {
                 suspended,
             } => {
                 trace!(task_id = ?self.task_id, suspended, "Setting up fork");
+                self.apply_task_limits(fork_request.progr);
                 self.vm_host
                     .start_fork(self.task_id, fork_request, suspended);
             }
-            TaskStart::StartEval { player, program } => {
+            TaskStart::StartEval {
+                player, program, ..
+            } => {
                 self.scheduled_start_time = None;
+                self.apply_task_limits(player);
                 self.vm_host.start_eval(self.task_id, player, program);
             }
         };
@@ -359,10 +354,21 @@ impl Task {
                         SchedulerControlMsg::TaskRequestFork(fork_request, send),
                     ))
                     .expect("Could not send fork request");
-                let task_id = reply.recv().expect("Could not get fork reply");
-                if let Some(task_id_var) = task_id_var {
-                    self.vm_host
-                        .set_variable(&task_id_var, v_int(task_id as i64));
+                match reply.recv().expect("Could not get fork reply") {
+                    Ok(task_id) => {
+                        if let Some(task_id_var) = task_id_var {
+                            self.vm_host
+                                .set_variable(&task_id_var, v_int(task_id as i64));
+                        }
+                    }
+                    Err(SchedulerError::TooManyTasks(_)) => {
+                        self.vm_host.raise_error(E_QUOTA);
+                    }
+                    Err(e) => {
+                        // Whatever else went wrong, it's the fork that failed, not this task.
+                        warn!(task_id = self.task_id, error = ?e, "Could not fork task");
+                        self.vm_host.raise_error(E_INVARG);
+                    }
                 }
                 None
             }
@@ -370,12 +376,10 @@ impl Task {
                 trace!(task_id = self.task_id, delay = ?delay, "Task suspend");
 
                 // VMHost is now suspended for execution, and we'll be waiting for a Resume
-                let commit_result = self
-                    .world_state
-                    .commit()
-                    .expect("Could not commit world state before suspend");
+                let commit_result = self.commit();
                 if let CommitResult::ConflictRetry = commit_result {
                     warn!("Conflict during commit before suspend");
+                    self.vm_host.stop();
                     return Some(SchedulerControlMsg::TaskConflictRetry);
                 }
 
@@ -388,28 +392,26 @@ impl Task {
                 // In both cases we'll rely on the scheduler to wake us up in its processing loop
                 // rather than sleep here, which would make this thread unresponsive to other
                 // messages.
-                let resume_time = delay.map(|delay| SystemTime::now() + delay);
+                let resume_time = delay.map(|delay| self.config.clock.now() + delay);
                 Some(SchedulerControlMsg::TaskSuspend(resume_time))
             }
-            VMHostResponse::SuspendNeedInput => {
+            VMHostResponse::SuspendNeedInput(player) => {
                 trace!(task_id = self.task_id, "Task suspend need input");
 
                 // VMHost is now suspended for input, and we'll be waiting for a ResumeReceiveInput
 
                 // Attempt commit... See comments/notes on Suspend above.
-                let commit_result = self
-                    .world_state
-                    .commit()
-                    .expect("Could not commit world state before suspend");
+                let commit_result = self.commit();
                 if let CommitResult::ConflictRetry = commit_result {
                     warn!("Conflict during commit before suspend");
+                    self.vm_host.stop();
                     return Some(SchedulerControlMsg::TaskConflictRetry);
                 }
 
                 trace!(task_id = self.task_id, "Task suspended for input");
                 self.vm_host.stop();
 
-                Some(SchedulerControlMsg::TaskRequestInput)
+                Some(SchedulerControlMsg::TaskRequestInput(player))
             }
             VMHostResponse::ContinueOk => {
                 self.done = false;
@@ -418,16 +420,35 @@ impl Task {
             VMHostResponse::CompleteSuccess(result) => {
                 trace!(task_id = self.task_id, result = ?result, "Task complete, success");
 
-                let CommitResult::Success =
-                    self.world_state.commit().expect("Could not attempt commit")
-                else {
+                let CommitResult::Success = self.commit() else {
                     warn!("Conflict during commit before complete, asking scheduler to retry task");
+                    self.vm_host.stop();
                     return Some(SchedulerControlMsg::TaskConflictRetry);
                 };
 
                 self.done = true;
                 self.vm_host.stop();
 
+                // As with `;` in LambdaMOO, the player is shown what their eval came to.
+                if let TaskStart::StartEval {
+                    player,
+                    show_result: true,
+                    ..
+                } = &self.task_start
+                {
+                    let event =
+                        NarrativeEvent::notify_text(*player, format!("=> {}", result.to_literal()));
+                    self.scheduler_control_sender
+                        .send((
+                            self.task_id,
+                            SchedulerControlMsg::Notify {
+                                player: *player,
+                                event,
+                            },
+                        ))
+                        .expect("Could not send eval result");
+                }
+
                 Some(SchedulerControlMsg::TaskSuccess(result))
             }
             VMHostResponse::CompleteAbort => {
@@ -473,6 +494,64 @@ impl Task {
         }
     }
 
+    /// Commit the task's transaction. If that succeeds, the verb calls queued to run after it are
+    /// passed to the scheduler to start. Either way they're gone from the task: a conflict means
+    /// the task runs again from the top, and queues them again if it still wants them.
+    fn commit(&mut self) -> CommitResult {
+        let commit_result = self
+            .world_state
+            .commit()
+            .expect("Could not commit world state");
+        let queued_calls = self.vm_host.take_queued_calls();
+        if let CommitResult::Success = commit_result {
+            if !queued_calls.is_empty() {
+                self.scheduler_control_sender
+                    .send((
+                        self.task_id,
+                        SchedulerControlMsg::DispatchQueuedCalls(queued_calls),
+                    ))
+                    .expect("Could not send queued calls");
+            }
+        }
+        commit_result
+    }
+
+    /// Clean up after a panic during VM execution: log it with whatever verb context we have,
+    /// roll back the transaction, and stop the task.
+    fn abort_panicked(&mut self, panic: Box<dyn Any + Send>) -> SchedulerControlMsg {
+        let panic_msg = if let Some(msg) = panic.downcast_ref::<&str>() {
+            msg.to_string()
+        } else if let Some(msg) = panic.downcast_ref::<String>() {
+            msg.clone()
+        } else {
+            "(non-string panic payload)".to_string()
+        };
+        if self.vm_host.has_activation() {
+            error!(
+                task_id = self.task_id,
+                verb = self.vm_host.verb_name(),
+                definer = ?self.vm_host.verb_definer(),
+                line = self.vm_host.line_number(),
+                panic_msg,
+                "Task panicked during execution; rolling back"
+            );
+        } else {
+            error!(
+                task_id = self.task_id,
+                panic_msg, "Task panicked during execution; rolling back"
+            );
+        }
+
+        self.vm_host.stop();
+        self.done = true;
+
+        if let Err(e) = self.world_state.rollback() {
+            error!(task_id = self.task_id, ?e, "Could not rollback after panic");
+        }
+
+        SchedulerControlMsg::TaskAbortPanicked(panic_msg)
+    }
+
     /// Handle an inbound control message from the scheduler, and return a response message to send
     ///  back (if any) as well as a flag to indicate if the task loop should continue running.
     fn handle_control_message(&mut self, msg: TaskControlMsg) -> Option<SchedulerControlMsg> {
@@ -500,6 +579,7 @@ impl Task {
                     .new_world_state()
                     .expect("Unable to start new transaction");
                 self.scheduled_start_time = None;
+                self.vm_host.reset();
                 self.setup_task_start(self.task_start.clone());
                 None
             }
@@ -518,6 +598,11 @@ impl Task {
                 self.vm_host.resume_execution(v_string(input));
                 None
             }
+            TaskControlMsg::SetLimits(limits) => {
+                debug!(task_id = self.task_id, ?limits, "Changing task limits");
+                self.vm_host.set_limits(limits);
+                None
+            }
             TaskControlMsg::Abort => {
                 // We've been asked to die. Go tell the VM host to abort, and roll back the
                 // transaction.
@@ -536,6 +621,12 @@ impl Task {
 
                 Some(SchedulerControlMsg::TaskAbortCancelled)
             }
+            TaskControlMsg::DescribeStack(reply_sender) => {
+                reply_sender
+                    .send(self.vm_host.callers())
+                    .expect("Could not send task stack");
+                None
+            }
             TaskControlMsg::Describe(reply_sender) => {
                 let description = TaskDescription {
                     task_id: self.task_id,
@@ -545,6 +636,7 @@ impl Task {
                     verb_definer: self.vm_host.verb_definer(),
                     line_number: self.vm_host.line_number(),
                     this: self.vm_host.this(),
+                    suspension: None,
                 };
                 reply_sender
                     .send(description)
@@ -593,12 +685,18 @@ impl Task {
             }
         };
 
-        // Parse the command in the current environment.
+        // Parse the command in the current environment, with the core's idea of what a failed or
+        // ambiguous match is.
+        let constants = MatchConstants::from_world_state(self.world_state.as_ref(), player);
         let me = WsMatchEnv {
             ws: self.world_state.as_mut(),
             perms: player,
         };
-        let matcher = MatchEnvironmentParseMatcher { env: me, player };
+        let matcher = MatchEnvironmentParseMatcher {
+            env: me,
+            player,
+            constants,
+        };
         let parsed_command = match parse_command(command, matcher) {
             Ok(pc) => pc,
             Err(ParseCommandError::PermissionDenied) => {
@@ -620,6 +718,7 @@ impl Task {
             player,
             player_location,
             &parsed_command,
+            &constants,
             self.world_state.as_mut(),
         ) {
             Ok(results) => results,
@@ -679,6 +778,7 @@ impl Task {
             argstr: parsed_command.argstr.clone(),
             caller: player,
         };
+        self.apply_task_limits(verb_info.verbdef().owner());
         self.vm_host.start_call_command_verb(
             self.task_id,
             verb_info,
@@ -694,12 +794,22 @@ fn find_verb_for_command(
     player: Objid,
     player_location: Objid,
     pc: &ParsedCommand,
+    constants: &MatchConstants,
     ws: &mut dyn WorldState,
 ) -> Result<Option<(VerbInfo, Objid)>, CommandError> {
+    // Verb argspecs know `none` only as #-1, whatever the core calls $nothing.
+    let unnamed = |oid| {
+        if oid == constants.nothing {
+            NOTHING
+        } else {
+            oid
+        }
+    };
+    let (dobj, iobj) = (unnamed(pc.dobj), unnamed(pc.iobj));
     let targets_to_search = vec![player, player_location, pc.dobj, pc.iobj];
     for target in targets_to_search {
         let match_result =
-            ws.find_command_verb_on(player, target, pc.verb.as_str(), pc.dobj, pc.prep, pc.iobj);
+            ws.find_command_verb_on(player, target, pc.verb.as_str(), dobj, pc.prep, iobj);
         let match_result = match match_result {
             Ok(m) => m,
             Err(WorldStateError::VerbPermissionDenied) => return Err(PermissionDenied),
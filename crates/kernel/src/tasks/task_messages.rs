@@ -12,8 +12,10 @@
 // this program. If not, see <https://www.gnu.org/licenses/>.
 //
 
-use crate::tasks::scheduler::AbortLimitReason;
+use crate::config::{Config, TaskLimits};
+use crate::tasks::scheduler::{AbortLimitReason, SchedulerError};
 use crate::tasks::{TaskDescription, TaskId};
+use crate::vm::activation::Caller;
 use crate::vm::vm_unwind::UncaughtException;
 use crate::vm::Fork;
 use std::sync::Arc;
@@ -23,8 +25,8 @@ use moor_compiler::Program;
 
 use moor_values::model::{CommandError, NarrativeEvent};
 use moor_values::model::{Perms, WorldStateSource};
-use moor_values::var::Objid;
 use moor_values::var::Var;
+use moor_values::var::{Error, Objid};
 use std::time::SystemTime;
 
 #[derive(Debug, Clone)]
@@ -50,7 +52,24 @@ pub enum TaskStart {
         suspended: bool,
     },
     /// The scheduler is telling the task to evaluate a specific (MOO) program.
-    StartEval { player: Objid, program: Program },
+    /// If `show_result` is set, the player is notified of the result when it completes.
+    StartEval {
+        player: Objid,
+        program: Program,
+        show_result: bool,
+    },
+}
+
+/// A verb call a task has asked to be made once its transaction commits, in a task of its own.
+/// If the transaction is rolled back instead, the call is never made.
+#[derive(Debug, Clone)]
+pub struct QueuedVerbCall {
+    pub player: Objid,
+    pub vloc: Objid,
+    pub verb: String,
+    pub args: Vec<Var>,
+    /// The permissions the call runs with: those of the task that queued it.
+    pub perms: Objid,
 }
 
 /// Messages sent to tasks from the scheduler to tell the task to do things.
@@ -69,6 +88,10 @@ pub enum TaskControlMsg {
     ///   described, so I need to rethink this. Right now this is prevented by the
     ///   runtime, but it's not a good design.
     Describe(OneshotSender<TaskDescription>),
+    /// The scheduler is asking the task for its call stack, in the form used by `callers()`.
+    DescribeStack(OneshotSender<Vec<Caller>>),
+    /// The scheduler is changing the task's tick, time, and stack depth limits.
+    SetLimits(TaskLimits),
     /// The scheduler is telling the task to abort itself.
     Abort,
 }
@@ -87,18 +110,27 @@ pub enum SchedulerControlMsg {
     TaskVerbNotFound(Objid, String),
     /// An exception was thrown while executing the verb.
     TaskException(UncaughtException),
-    /// The task is requesting that it be forked.
-    TaskRequestFork(Fork, OneshotSender<TaskId>),
+    /// The task's transaction committed, so the verb calls it queued up to run after that can be
+    /// started.
+    DispatchQueuedCalls(Vec<QueuedVerbCall>),
+    /// The task is requesting that it be forked. The reply is the new task's id, or the reason
+    /// the scheduler refused to create it.
+    TaskRequestFork(Fork, OneshotSender<Result<TaskId, SchedulerError>>),
     /// The task is letting us know it was cancelled.
     TaskAbortCancelled,
+    /// The task panicked during execution, or couldn't be set up to run at all, and has rolled
+    /// back its transaction and stopped.
+    TaskAbortPanicked(String),
     /// The task is letting us know that it has reached its abort limits.
     TaskAbortLimitsReached(AbortLimitReason),
     /// Tell the scheduler that the task in a suspended state, with a time to resume (if any)
     TaskSuspend(Option<SystemTime>),
-    /// Tell the scheduler we're suspending until we get input from the client.
-    TaskRequestInput,
+    /// Tell the scheduler we're suspending until we get input from the given player's client.
+    TaskRequestInput(Objid),
     /// Task is requesting a list of all other tasks known to the scheduler.
     DescribeOtherTasks(OneshotSender<Vec<TaskDescription>>),
+    /// Task is requesting the server's configuration, for `server_options()`.
+    DescribeServerOptions(OneshotSender<Arc<Config>>),
     /// Task is requesting that the scheduler abort another task.
     KillTask {
         victim_task_id: TaskId,
@@ -112,6 +144,12 @@ pub enum SchedulerControlMsg {
         return_value: Var,
         result_sender: OneshotSender<Var>,
     },
+    /// Task is requesting the call stack of another, queued, task.
+    TaskStack {
+        queued_task_id: TaskId,
+        sender_permissions: Perms,
+        result_sender: OneshotSender<Result<Vec<Caller>, Error>>,
+    },
     /// Task is requesting that the scheduler boot a player.
     BootPlayer {
         player: Objid,
@@ -0,0 +1,56 @@
+// Copyright (C) 2024 Ryan Daum <ryan.daum@gmail.com>
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU General Public License as published by the Free Software
+// Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// this program. If not, see <https://www.gnu.org/licenses/>.
+//
+
+use dashmap::DashMap;
+use uuid::Uuid;
+
+use moor_values::var::Objid;
+
+/// Execution counters for a single verb.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub struct VerbMetric {
+    /// How many times the verb has been invoked.
+    pub calls: usize,
+    /// Total ticks consumed while the verb was the executing activation.
+    pub ticks: usize,
+}
+
+/// Server-wide per-verb execution counters, shared by all tasks and updated by their VM hosts.
+/// Verbs are keyed by their definer and verbdef uuid, so that aliases and renames don't split
+/// the counts.
+#[derive(Debug, Default)]
+pub struct VerbMetrics {
+    metrics: DashMap<(Objid, Uuid), VerbMetric>,
+}
+
+impl VerbMetrics {
+    pub fn record_call(&self, definer: Objid, verb: Uuid) {
+        self.metrics.entry((definer, verb)).or_default().calls += 1;
+    }
+
+    pub fn record_ticks(&self, definer: Objid, verb: Uuid, ticks: usize) {
+        if ticks == 0 {
+            return;
+        }
+        self.metrics.entry((definer, verb)).or_default().ticks += ticks;
+    }
+
+    /// Return the counters for the given verb, or zeroes if it has never been executed.
+    pub fn metric(&self, definer: Objid, verb: Uuid) -> VerbMetric {
+        self.metrics
+            .get(&(definer, verb))
+            .map(|m| *m)
+            .unwrap_or_default()
+    }
+}
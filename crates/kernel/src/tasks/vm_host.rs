@@ -12,28 +12,35 @@
 // this program. If not, see <https://www.gnu.org/licenses/>.
 //
 
+use crate::config::TaskLimits;
+use crate::tasks::clock::Clock;
 use crate::tasks::command_parse::ParsedCommand;
+use crate::tasks::coverage::TaskCoverage;
+use crate::tasks::program_cache::ProgramCache;
 use crate::tasks::scheduler::AbortLimitReason;
 use crate::tasks::sessions::Session;
-use crate::tasks::task_messages::SchedulerControlMsg;
+use crate::tasks::task_messages::{QueuedVerbCall, SchedulerControlMsg};
+use crate::tasks::verb_metrics::VerbMetrics;
 use crate::tasks::vm_host::VMHostResponse::{AbortLimit, ContinueOk, DispatchFork, Suspend};
 use crate::tasks::{PhantomUnsend, PhantomUnsync, TaskId, VerbCall};
+use crate::vm::activation::Caller;
 use crate::vm::{ExecutionResult, Fork, VerbExecutionRequest, VM};
 use crate::vm::{FinallyReason, VMExecState};
 use crate::vm::{UncaughtException, VmExecParams};
 use kanal::Sender;
 use moor_compiler::Name;
 use moor_compiler::Program;
-use moor_values::model::BinaryType;
+use moor_values::model::HasUuid;
 use moor_values::model::VerbInfo;
 use moor_values::model::WorldState;
-use moor_values::util::SliceRef;
+use moor_values::var::Error;
 use moor_values::var::Objid;
 use moor_values::var::Var;
-use moor_values::AsByteBuffer;
+use moor_values::NOTHING;
 use std::sync::Arc;
 use std::time::{Duration, SystemTime};
 use tracing::{trace, warn};
+use uuid::Uuid;
 
 /// Return values from exec_interpreter back to the Task scheduler loop
 pub enum VMHostResponse {
@@ -43,8 +50,8 @@ pub enum VMHostResponse {
     DispatchFork(Fork),
     /// Tell the task to suspend us.
     Suspend(Option<Duration>),
-    /// Tell the task Johnny 5 needs input from the client (`read` invocation).
-    SuspendNeedInput,
+    /// Tell the task Johnny 5 needs input from the given player's client (`read` invocation).
+    SuspendNeedInput(Objid),
     /// Task timed out or exceeded ticks.
     AbortLimit(AbortLimitReason),
     /// Tell the task that execution has completed, and the task is successful.
@@ -69,6 +76,17 @@ pub struct VmHost {
     max_time: Duration,
     sessions: Arc<dyn Session>,
     scheduler_control_sender: Sender<(TaskId, SchedulerControlMsg)>,
+    /// Where we accumulate per-verb call counts and tick usage.
+    verb_metrics: Arc<VerbMetrics>,
+    /// Programs compiled on demand from verbs stored as source.
+    program_cache: Arc<ProgramCache>,
+    /// What this task has executed, if coverage mode is on.
+    coverage: Option<Arc<TaskCoverage>>,
+    /// Where the time builtins get the time from.
+    clock: Clock,
+    /// An error to raise in the running verb the next time the interpreter is entered, for
+    /// failures that are only discovered by the task after the VM has yielded (e.g. fork quota).
+    pending_error: Option<Error>,
     running: bool,
 
     unsend: PhantomUnsend,
@@ -78,11 +96,11 @@ pub struct VmHost {
 impl VmHost {
     pub fn new(
         task_id: TaskId,
-        max_stack_depth: usize,
-        max_ticks: usize,
-        max_time: Duration,
+        limits: TaskLimits,
         sessions: Arc<dyn Session>,
         scheduler_control_sender: Sender<(TaskId, SchedulerControlMsg)>,
+        verb_metrics: Arc<VerbMetrics>,
+        program_cache: Arc<ProgramCache>,
     ) -> Self {
         let vm = VM::new();
         let vm_exec_state = VMExecState::new(task_id);
@@ -91,11 +109,16 @@ impl VmHost {
         Self {
             vm,
             vm_exec_state,
-            max_stack_depth,
-            max_ticks,
-            max_time,
+            max_stack_depth: limits.max_stack_depth,
+            max_ticks: limits.max_ticks,
+            max_time: Duration::from_secs(limits.max_seconds),
             sessions,
             scheduler_control_sender,
+            verb_metrics,
+            program_cache,
+            coverage: None,
+            clock: Clock::System,
+            pending_error: None,
             running: false,
             unsend: Default::default(),
             unsync: Default::default(),
@@ -104,6 +127,27 @@ impl VmHost {
 }
 
 impl VmHost {
+    /// Change the limits for this task. They take effect the next time the interpreter is
+    /// entered, so a task that's already past a lowered limit is aborted then.
+    pub fn set_limits(&mut self, limits: TaskLimits) {
+        self.max_stack_depth = limits.max_stack_depth;
+        self.max_ticks = limits.max_ticks;
+        self.max_time = Duration::from_secs(limits.max_seconds);
+        if self.vm_exec_state.maximum_time.is_some() {
+            self.vm_exec_state.maximum_time = Some(self.max_time);
+        }
+    }
+
+    /// Have the time builtins read `clock` rather than the system clock.
+    pub fn set_clock(&mut self, clock: Clock) {
+        self.clock = clock;
+    }
+
+    /// Start recording which opcodes and lines this task executes, for `coverage()`.
+    pub fn enable_coverage(&mut self) {
+        self.coverage.get_or_insert_with(Default::default);
+    }
+
     /// Setup for executing a method initiated from a command.
     pub fn start_call_command_verb(
         &mut self,
@@ -113,7 +157,9 @@ impl VmHost {
         command: ParsedCommand,
         permissions: Objid,
     ) {
-        let binary = Self::decode_program(vi.verbdef().binary_type(), vi.binary().as_slice());
+        let binary = self
+            .program_cache
+            .program_for(&vi.verbdef(), vi.binary().as_slice());
         let call_request = VerbExecutionRequest {
             permissions,
             resolved_verb: vi,
@@ -133,10 +179,9 @@ impl VmHost {
         verb_info: VerbInfo,
         verb_call: VerbCall,
     ) {
-        let binary = Self::decode_program(
-            verb_info.verbdef().binary_type(),
-            verb_info.binary().as_slice(),
-        );
+        let binary = self
+            .program_cache
+            .program_for(&verb_info.verbdef(), verb_info.binary().as_slice());
 
         let call_request = VerbExecutionRequest {
             permissions: perms,
@@ -170,6 +215,7 @@ impl VmHost {
         self.vm_exec_state.maximum_time = Some(self.max_time);
         self.vm_exec_state.tick_count = 0;
         self.vm_exec_state.task_id = task_id;
+        self.record_verb_call(&verb_execution_request);
         self.vm
             .exec_call_request(&mut self.vm_exec_state, verb_execution_request);
         self.running = true;
@@ -197,6 +243,9 @@ impl VmHost {
         let exec_params = VmExecParams {
             scheduler_sender: self.scheduler_control_sender.clone(),
             max_stack_depth: self.max_stack_depth,
+            verb_metrics: self.verb_metrics.clone(),
+            coverage: self.coverage.clone(),
+            clock: self.clock.clone(),
         };
 
         // Check existing ticks and seconds, and abort the task if we've exceeded the limits.
@@ -210,18 +259,23 @@ impl VmHost {
             }
         };
 
-        // Grant the loop its next tick slice.
-        self.vm_exec_state.tick_slice = self.max_ticks - self.vm_exec_state.tick_count;
+        // The loop runs until the task's tick count reaches this, not for this many more ticks.
+        self.vm_exec_state.tick_slice = self.max_ticks;
 
         let pre_exec_tick_count = self.vm_exec_state.tick_count;
+        let executing_verb = self.executing_verb();
 
         // Actually invoke the VM, asking it to loop until it's ready to yield back to us.
-        let mut result = self.vm.exec(
-            &exec_params,
-            &mut self.vm_exec_state,
-            world_state,
-            self.sessions.clone(),
-        );
+        // (Unless there's an error waiting to be raised, in which case it goes first.)
+        let mut result = match self.pending_error.take() {
+            Some(code) => self.vm.push_error(&mut self.vm_exec_state, code),
+            None => self.vm.exec(
+                &exec_params,
+                &mut self.vm_exec_state,
+                world_state,
+                self.sessions.clone(),
+            ),
+        };
 
         let post_exec_tick_count = self.vm_exec_state.tick_count;
         trace!(
@@ -230,6 +284,13 @@ impl VmHost {
             ?result,
             "Executed ticks",
         );
+        if let Some((definer, verb)) = executing_verb {
+            self.verb_metrics.record_ticks(
+                definer,
+                verb,
+                post_exec_tick_count - pre_exec_tick_count,
+            );
+        }
         while self.is_running() {
             match result {
                 ExecutionResult::More => return ContinueOk,
@@ -246,10 +307,9 @@ impl VmHost {
                     self.vm_exec_state.top_mut().bf_trampoline_arg = trampoline_arg;
                     self.vm_exec_state.top_mut().bf_trampoline = trampoline;
 
-                    let program = Self::decode_program(
-                        resolved_verb.verbdef().binary_type(),
-                        resolved_verb.binary().as_slice(),
-                    );
+                    let program = self
+                        .program_cache
+                        .program_for(&resolved_verb.verbdef(), resolved_verb.binary().as_slice());
 
                     let call_request = VerbExecutionRequest {
                         permissions,
@@ -259,6 +319,7 @@ impl VmHost {
                         program,
                     };
 
+                    self.record_verb_call(&call_request);
                     self.vm
                         .exec_call_request(&mut self.vm_exec_state, call_request);
                     return ContinueOk;
@@ -283,6 +344,9 @@ impl VmHost {
                     let exec_params = VmExecParams {
                         max_stack_depth: self.max_stack_depth,
                         scheduler_sender: self.scheduler_control_sender.clone(),
+                        verb_metrics: self.verb_metrics.clone(),
+                        coverage: self.coverage.clone(),
+                        clock: self.clock.clone(),
                     };
                     // Ask the VM to execute the builtin function.
                     // This will push the result onto the stack.
@@ -303,8 +367,8 @@ impl VmHost {
                 ExecutionResult::Suspend(delay) => {
                     return Suspend(delay);
                 }
-                ExecutionResult::NeedInput => {
-                    return VMHostResponse::SuspendNeedInput;
+                ExecutionResult::NeedInput(player) => {
+                    return VMHostResponse::SuspendNeedInput(player);
                 }
                 ExecutionResult::Complete(a) => {
                     trace!(task_id, "Task completed");
@@ -345,6 +409,26 @@ impl VmHost {
         trace!(task_id = self.vm_exec_state.task_id, "Resuming VMHost");
     }
 
+    fn record_verb_call(&self, request: &VerbExecutionRequest) {
+        let verbdef = request.resolved_verb.verbdef();
+        self.verb_metrics
+            .record_call(verbdef.location(), verbdef.uuid());
+    }
+
+    /// The (definer, uuid) of the verb at the top of the stack, if it's a real verb and not an
+    /// eval or builtin frame.
+    fn executing_verb(&self) -> Option<(Objid, Uuid)> {
+        let top = self.vm_exec_state.stack.last()?;
+        if top.bf_index.is_some() {
+            return None;
+        }
+        let verbdef = top.verb_info.verbdef();
+        if verbdef.location() == NOTHING {
+            return None;
+        }
+        Some((verbdef.location(), verbdef.uuid()))
+    }
+
     pub fn is_running(&self) -> bool {
         self.running
     }
@@ -354,12 +438,22 @@ impl VmHost {
         self.running = false;
     }
 
-    pub fn decode_program(binary_type: BinaryType, binary_bytes: &[u8]) -> Program {
-        match binary_type {
-            BinaryType::LambdaMoo18X => Program::from_sliceref(SliceRef::from_bytes(binary_bytes))
-                .expect("Could not decode MOO program"),
-            _ => panic!("Unsupported binary type {:?}", binary_type),
-        }
+    /// Throw away all execution state, so that the task can be started again from the top.
+    pub fn reset(&mut self) {
+        trace!(task_id = self.vm_exec_state.task_id, "Resetting VMHost");
+        self.vm_exec_state = VMExecState::new(self.vm_exec_state.task_id);
+        self.pending_error = None;
+        self.running = false;
+    }
+
+    /// Take the verb calls queued to be made after the transaction commits.
+    pub(crate) fn take_queued_calls(&mut self) -> Vec<QueuedVerbCall> {
+        std::mem::take(&mut self.vm_exec_state.queued_calls)
+    }
+
+    /// Raise `code` in the running verb when execution next resumes.
+    pub fn raise_error(&mut self, code: Error) {
+        self.pending_error = Some(code);
     }
     pub fn set_variable(&mut self, task_id_var: &Name, value: Var) {
         self.vm_exec_state
@@ -368,6 +462,10 @@ impl VmHost {
             .set_var_offset(task_id_var, value)
             .expect("Could not set forked task id");
     }
+    /// Whether there's a verb activation on the stack for the accessors below to look at.
+    pub fn has_activation(&self) -> bool {
+        !self.vm_exec_state.stack.is_empty()
+    }
     pub fn permissions(&self) -> Objid {
         self.vm_exec_state.top().permissions
     }
@@ -380,6 +478,9 @@ impl VmHost {
     pub fn this(&self) -> Objid {
         self.vm_exec_state.top().this
     }
+    pub(crate) fn callers(&self) -> Vec<Caller> {
+        self.vm_exec_state.callers()
+    }
     pub fn line_number(&self) -> usize {
         self.vm_exec_state
             .top()
@@ -18,22 +18,25 @@ use std::io;
 use std::io::BufReader;
 use std::path::PathBuf;
 use std::rc::Rc;
-use std::sync::Arc;
 
-use tracing::{info, span, trace};
+use strum::{Display, EnumString, VariantNames};
+use tracing::{info, span, trace, warn};
+use uuid::Uuid;
 
 use moor_compiler::compile;
 use moor_compiler::Program;
 use moor_db::loader::LoaderInterface;
+use moor_values::model::BinaryType;
 use moor_values::model::Preposition;
 use moor_values::model::PropFlag;
 use moor_values::model::VerbFlag;
+use moor_values::model::WorldStateError;
 use moor_values::model::{ArgSpec, PrepSpec, VerbArgsSpec};
-use moor_values::model::{ObjAttrs, ObjFlag};
+use moor_values::model::{HasUuid, ObjAttrs, ObjFlag, ObjSet};
 use moor_values::util::BitEnum;
 use moor_values::var::Objid;
 use moor_values::var::Var;
-use moor_values::AsByteBuffer;
+use moor_values::{AsByteBuffer, NOTHING};
 
 use crate::textdump::read::TextdumpReaderError;
 use crate::textdump::{
@@ -43,6 +46,7 @@ use crate::textdump::{
 
 struct RProp {
     definer: Objid,
+    uuid: Option<Uuid>,
     name: String,
     owner: Objid,
     flags: u8,
@@ -56,6 +60,7 @@ fn resolve_prop(omap: &BTreeMap<Objid, Object>, offset: usize, o: &Object) -> Op
         let pval = &o.propvals[offset];
         return Some(RProp {
             definer: o.id,
+            uuid: o.propdef_uuids.get(offset).copied(),
             name,
             owner: pval.owner,
             flags: pval.flags,
@@ -69,6 +74,22 @@ fn resolve_prop(omap: &BTreeMap<Objid, Object>, offset: usize, o: &Object) -> Op
     resolve_prop(omap, offset, parent)
 }
 
+/// The object and its ancestors through first parents only, which is as far as the object
+/// records' property values go. This follows the world rather than the dump, since a delta dump
+/// needn't have the ancestors.
+fn first_parent_lineage(
+    loader: &dyn LoaderInterface,
+    id: Objid,
+) -> Result<Vec<Objid>, WorldStateError> {
+    let mut lineage = vec![id];
+    let mut ancestor = loader.get_object(id)?.parent;
+    while let Some(parent) = ancestor.filter(|p| *p != NOTHING) {
+        lineage.push(parent);
+        ancestor = loader.get_object(parent)?.parent;
+    }
+    Ok(lineage)
+}
+
 fn cv_prep_flag(vprep: i16) -> PrepSpec {
     match vprep {
         PREP_ANY => PrepSpec::Any,
@@ -88,11 +109,35 @@ fn cv_aspec_flag(flags: u16) -> ArgSpec {
     }
 }
 
+/// When the verb programs in a textdump get compiled.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, EnumString, Display, VariantNames)]
+#[strum(serialize_all = "lowercase")]
+pub enum VerbCompilation {
+    /// All of them, during import. A verb that doesn't compile fails the import.
+    #[default]
+    Eager,
+    /// Each on its first call. Verbs are stored as source, which makes importing a large core
+    /// much quicker; a verb that doesn't compile raises an error only when it's called.
+    Lazy,
+    /// All of them, during import, like `Eager`, but a verb that doesn't compile is stored as
+    /// source (as with `Lazy`) and the import carries on. The failures are listed in the
+    /// `LoadSummary`.
+    Lenient,
+}
+
+/// What an import got past without failing.
+#[derive(Debug, Default)]
+pub struct LoadSummary {
+    /// The verbs that didn't compile under `VerbCompilation::Lenient`, as `VerbCompileError`s.
+    pub verb_compile_errors: Vec<TextdumpReaderError>,
+}
+
 #[tracing::instrument(skip(ldr))]
 pub fn textdump_load(
     ldr: Rc<dyn LoaderInterface>,
     path: PathBuf,
-) -> Result<(), TextdumpReaderError> {
+    compilation: VerbCompilation,
+) -> Result<LoadSummary, TextdumpReaderError> {
     let textdump_import_span = span!(tracing::Level::INFO, "textdump_import");
     let _enter = textdump_import_span.enter();
 
@@ -101,16 +146,20 @@ pub fn textdump_load(
 
     let br = BufReader::new(corefile);
 
-    read_textdump(ldr, br)?
+    read_textdump(ldr, br, compilation)?
 }
 
 pub fn read_textdump<T: io::Read>(
     loader: Rc<dyn LoaderInterface>,
     reader: BufReader<T>,
-) -> Result<Result<(), TextdumpReaderError>, TextdumpReaderError> {
+    compilation: VerbCompilation,
+) -> Result<Result<LoadSummary, TextdumpReaderError>, TextdumpReaderError> {
     let mut tdr = TextdumpReader::new(reader);
     let td = tdr.read_textdump()?;
+    let mut summary = LoadSummary::default();
 
+    // Objects which already exist are updated in place rather than created, so that a partial
+    // dump (see `textdump_save_delta`) can be applied on top of an existing world.
     info!("Instantiating objects");
     for (objid, o) in &td.objects {
         let flags: BitEnum<ObjFlag> = BitEnum::from_u8(o.flags);
@@ -118,35 +167,86 @@ pub fn read_textdump<T: io::Read>(
         trace!(
             objid = ?objid, name=o.name, flags=?flags, "Creating object",
         );
-        loader
-            .create_object(
-                Some(*objid),
-                ObjAttrs::new()
-                    // .owner(o.owner)
-                    // .location(o.location)
-                    .name(o.name.as_str())
-                    // .parent(o.parent)
-                    .flags(flags),
+        let mut attrs = ObjAttrs::new();
+        attrs.name(o.name.as_str()).flags(flags);
+        let exists = loader.object_valid(*objid).map_err(|e| {
+            TextdumpReaderError::LoadError(
+                o.position,
+                format!("checking object {}", objid),
+                e.clone(),
             )
-            .map_err(|e| {
-                TextdumpReaderError::LoadError(format!("creating object {}", objid), e.clone())
-            })?;
+        })?;
+        let result = if exists {
+            loader.update_object(*objid, &attrs)
+        } else {
+            loader.create_object(Some(*objid), &attrs).map(|_| ())
+        };
+        result.map_err(|e| {
+            TextdumpReaderError::LoadError(
+                o.position,
+                format!("creating object {}", objid),
+                e.clone(),
+            )
+        })?;
     }
 
     info!("Setting object attributes (parent/location/owner)");
     for (objid, o) in &td.objects {
         trace!(owner = ?o.owner, parent = ?o.parent, location = ?o.location, "Setting attributes");
-        loader.set_object_owner(*objid, o.owner).map_err(|e| {
-            TextdumpReaderError::LoadError(format!("setting owner of {}", objid), e.clone())
+        let current = loader.get_object(*objid).map_err(|e| {
+            TextdumpReaderError::LoadError(
+                o.position,
+                format!("getting object {}", objid),
+                e.clone(),
+            )
         })?;
-        loader.set_object_parent(*objid, o.parent).map_err(|e| {
-            TextdumpReaderError::LoadError(format!("setting parent of {}", objid), e.clone())
+        loader.set_object_owner(*objid, o.owner).map_err(|e| {
+            TextdumpReaderError::LoadError(
+                o.position,
+                format!("setting owner of {}", objid),
+                e.clone(),
+            )
         })?;
-        loader
-            .set_object_location(*objid, o.location)
-            .map_err(|e| {
-                TextdumpReaderError::LoadError(format!("setting location of {}", objid), e.clone())
+        if current.parent != Some(o.parent) {
+            loader.set_object_parent(*objid, o.parent).map_err(|e| {
+                TextdumpReaderError::LoadError(
+                    o.position,
+                    format!("setting parent of {}", objid),
+                    e.clone(),
+                )
             })?;
+        }
+        // Any additional parents it already had go too; those it still has are restored from the
+        // extra parents section after the properties.
+        let parents = loader.get_object_parents(*objid).map_err(|e| {
+            TextdumpReaderError::LoadError(
+                o.position,
+                format!("getting parents of {}", objid),
+                e.clone(),
+            )
+        })?;
+        if parents.len() > 1 {
+            loader
+                .set_object_parents(*objid, ObjSet::from(&[o.parent]))
+                .map_err(|e| {
+                    TextdumpReaderError::LoadError(
+                        o.position,
+                        format!("setting parents of {}", objid),
+                        e.clone(),
+                    )
+                })?;
+        }
+        if current.location != Some(o.location) {
+            loader
+                .set_object_location(*objid, o.location)
+                .map_err(|e| {
+                    TextdumpReaderError::LoadError(
+                        o.position,
+                        format!("setting location of {}", objid),
+                        e.clone(),
+                    )
+                })?;
+        }
     }
 
     info!("Defining properties...");
@@ -155,43 +255,94 @@ pub fn read_textdump<T: io::Read>(
     // initially-clear state in all the descendants. A second pass will then go through and update
     // flags and values for the children.
     for (objid, o) in &td.objects {
-        for (pnum, _p) in o.propvals.iter().enumerate() {
+        // Definitions the object already has are kept, and any it no longer has are removed.
+        let existing = loader.get_object_properties(*objid).map_err(|e| {
+            TextdumpReaderError::LoadError(
+                o.position,
+                format!("getting properties of {}", objid),
+                e.clone(),
+            )
+        })?;
+        for p in existing.iter() {
+            if p.definer() == *objid && !o.propdefs.iter().any(|name| name == p.name()) {
+                loader.remove_property(*objid, p.uuid()).map_err(|e| {
+                    TextdumpReaderError::LoadError(
+                        o.position,
+                        format!("removing property from {}", objid),
+                        e.clone(),
+                    )
+                })?;
+            }
+        }
+        for pnum in 0..o.propdefs.len() {
             let resolved = resolve_prop(&td.objects, pnum, o).unwrap();
+            if existing
+                .find_first_named(resolved.name.as_str())
+                .is_some_and(|p| p.definer() == *objid)
+            {
+                continue;
+            }
             let flags: BitEnum<PropFlag> = BitEnum::from_u8(resolved.flags);
-            if resolved.definer == *objid {
-                trace!(definer = ?objid.0, name = resolved.name, "Defining property");
-                let value = Some(resolved.value);
-                loader
-                    .define_property(
-                        resolved.definer,
-                        *objid,
-                        resolved.name.as_str(),
-                        resolved.owner,
-                        flags,
-                        value,
+            trace!(definer = ?objid.0, name = resolved.name, "Defining property");
+            let value = Some(resolved.value);
+            loader
+                .define_property(
+                    resolved.definer,
+                    *objid,
+                    resolved.name.as_str(),
+                    resolved.owner,
+                    flags,
+                    value,
+                    resolved.uuid,
+                )
+                .map_err(|e| {
+                    TextdumpReaderError::LoadError(
+                        o.position,
+                        format!("defining property on {}", objid),
+                        e.clone(),
                     )
-                    .map_err(|e| {
-                        TextdumpReaderError::LoadError(
-                            format!("defining property on {}", objid),
-                            e.clone(),
-                        )
-                    })?;
-            }
+                })?;
         }
     }
 
     info!("Setting property values & info");
     for (objid, o) in &td.objects {
+        // Inherited property values are positional, relative to the definitions up the
+        // inheritance chain. If an ancestor isn't in the dump, look the names up in the world.
+        let mut world_propdefs = None;
         for (pnum, p) in o.propvals.iter().enumerate() {
-            let resolved = resolve_prop(&td.objects, pnum, o).unwrap();
+            let name = match resolve_prop(&td.objects, pnum, o) {
+                Some(resolved) => resolved.name,
+                None => {
+                    if world_propdefs.is_none() {
+                        let all = loader.get_all_property_values(*objid).map_err(|e| {
+                            TextdumpReaderError::LoadError(
+                                o.position,
+                                format!("getting properties of {}", objid),
+                                e.clone(),
+                            )
+                        })?;
+                        world_propdefs = Some(all);
+                    }
+                    let Some((propdef, _)) = world_propdefs.as_ref().unwrap().get(pnum) else {
+                        return Err(TextdumpReaderError::ParseError(
+                            o.position,
+                            format!("property value {} has no definition", pnum),
+                            objid.to_string(),
+                        ));
+                    };
+                    propdef.name().to_string()
+                }
+            };
             let flags: BitEnum<PropFlag> = BitEnum::from_u8(p.flags);
-            trace!(objid = ?objid.0, name = resolved.name, flags = ?flags, "Setting property");
+            trace!(objid = ?objid.0, name, flags = ?flags, "Setting property");
             let value = (!p.is_clear).then(|| p.value.clone());
 
             loader
-                .set_property(*objid, resolved.name.as_str(), p.owner, flags, value)
+                .set_property(*objid, name.as_str(), p.owner, flags, value)
                 .map_err(|e| {
                     TextdumpReaderError::LoadError(
+                        o.position,
                         format!("setting property on {}", objid),
                         e.clone(),
                     )
@@ -199,8 +350,82 @@ pub fn read_textdump<T: io::Read>(
         }
     }
 
+    if !td.extra_parents.is_empty() {
+        info!("Restoring additional parents...");
+
+        // All the parents first, so that every object has all the properties it inherits before
+        // any of their values are set.
+        for record in &td.extra_parents {
+            if record.parents.is_empty() {
+                continue;
+            }
+            let parent = td.objects.get(&record.id).map_or(NOTHING, |o| o.parent);
+            let parents = ObjSet::from(&[parent]).with_appended(&record.parents);
+            loader.set_object_parents(record.id, parents).map_err(|e| {
+                TextdumpReaderError::LoadError(
+                    record.position,
+                    format!("setting parents of {}", record.id),
+                    e,
+                )
+            })?;
+        }
+
+        // The record's values line up with the properties the object inherits from outside its
+        // first-parent lineage, in the order the world resolves them.
+        for record in &td.extra_parents {
+            let lineage = first_parent_lineage(loader.as_ref(), record.id).map_err(|e| {
+                TextdumpReaderError::LoadError(
+                    record.position,
+                    format!("getting ancestors of {}", record.id),
+                    e,
+                )
+            })?;
+            let properties = loader.get_all_property_values(record.id).map_err(|e| {
+                TextdumpReaderError::LoadError(
+                    record.position,
+                    format!("getting properties of {}", record.id),
+                    e,
+                )
+            })?;
+            let inherited_otherwise = properties
+                .iter()
+                .filter(|(p, _)| !lineage.contains(&p.definer()));
+            for ((p, _), pval) in inherited_otherwise.zip(&record.propvals) {
+                let flags: BitEnum<PropFlag> = BitEnum::from_u8(pval.flags);
+                trace!(objid = ?record.id.0, name = p.name(), flags = ?flags, "Setting property");
+                let value = (!pval.is_clear).then(|| pval.value.clone());
+                loader
+                    .set_property(record.id, p.name(), pval.owner, flags, value)
+                    .map_err(|e| {
+                        TextdumpReaderError::LoadError(
+                            record.position,
+                            format!("setting property on {}", record.id),
+                            e,
+                        )
+                    })?;
+            }
+        }
+    }
+
     info!("Defining verbs...");
     for (objid, o) in &td.objects {
+        // The dump has all of an object's verbs, so any it already had are replaced.
+        let existing = loader.get_object_verbs(*objid).map_err(|e| {
+            TextdumpReaderError::LoadError(
+                o.position,
+                format!("getting verbs of {}", objid),
+                e.clone(),
+            )
+        })?;
+        for v in existing.iter() {
+            loader.remove_verb(*objid, v.uuid()).map_err(|e| {
+                TextdumpReaderError::LoadError(
+                    o.position,
+                    format!("removing verb from {}", objid),
+                    e.clone(),
+                )
+            })?;
+        }
         for (vn, v) in o.verbdefs.iter().enumerate() {
             let mut flags: BitEnum<VerbFlag> = BitEnum::new();
             let permflags = v.flags & VF_PERMMASK;
@@ -227,35 +452,49 @@ pub fn read_textdump<T: io::Read>(
 
             let names: Vec<&str> = v.name.split(' ').collect();
 
-            let program = match td.verbs.get(&(*objid, vn)) {
-                Some(verb) if verb.program.is_some() => {
-                    compile(verb.program.clone().unwrap().as_str()).map_err(|e| {
-                        TextdumpReaderError::VerbCompileError(
-                            format!("compiling verb #{}/{} ({:?})", objid.0, vn, names),
-                            e.clone(),
-                        )
-                    })?
+            let source = td
+                .verbs
+                .get(&(*objid, vn))
+                .and_then(|verb| verb.program.as_ref().map(|program| (verb, program)));
+            let (binary, binary_type) = match source {
+                Some((_, program)) if compilation == VerbCompilation::Lazy => {
+                    (program.as_bytes().to_vec(), BinaryType::MooSource)
                 }
+                Some((verb, program)) => match compile(program.as_str()) {
+                    Ok(compiled) => (encode_program(&compiled), BinaryType::LambdaMoo18X),
+                    Err(e) => {
+                        let e = TextdumpReaderError::VerbCompileError(
+                            verb.position,
+                            format!("compiling verb #{}/{} ({:?})", objid.0, vn, names),
+                            e,
+                        );
+                        if compilation != VerbCompilation::Lenient {
+                            return Err(e);
+                        }
+                        warn!(error = %e, "Verb failed to compile; storing its source as is");
+                        summary.verb_compile_errors.push(e);
+                        (program.as_bytes().to_vec(), BinaryType::MooSource)
+                    }
+                },
                 // If the verb program is missing, then it's an empty program, and we'll put in
                 // an empty binary.
-                _ => Program {
-                    literals: vec![],
-                    jump_labels: vec![],
-                    var_names: Default::default(),
-                    main_vector: Arc::new(vec![]),
-                    fork_vectors: vec![],
-                    line_number_spans: vec![],
-                },
+                None => (encode_program(&Program::new()), BinaryType::LambdaMoo18X),
             };
 
-            let binary =
-                // Encode the binary (for now using bincode)
-                program.with_byte_buffer(|d| Vec::from(d)).expect("Failed to encode program");
-
             loader
-                .add_verb(*objid, names.clone(), v.owner, flags, argspec, binary)
+                .add_verb(
+                    *objid,
+                    names.clone(),
+                    v.owner,
+                    flags,
+                    argspec,
+                    binary,
+                    binary_type,
+                    v.uuid,
+                )
                 .map_err(|e| {
                     TextdumpReaderError::LoadError(
+                        o.position,
                         format!("adding verb #{}/{} ({:?})", objid.0, vn, names),
                         e.clone(),
                     )
@@ -265,7 +504,20 @@ pub fn read_textdump<T: io::Read>(
     }
     info!("Verbs defined.");
 
+    if !summary.verb_compile_errors.is_empty() {
+        warn!(
+            "{} verbs failed to compile, and were stored uncompiled",
+            summary.verb_compile_errors.len()
+        );
+    }
     info!("Import complete.");
 
-    Ok(Ok(()))
+    Ok(Ok(summary))
+}
+
+fn encode_program(program: &Program) -> Vec<u8> {
+    // Encode the binary (for now using bincode)
+    program
+        .with_byte_buffer(|d| Vec::from(d))
+        .expect("Failed to encode program")
 }
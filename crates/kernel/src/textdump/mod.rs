@@ -16,12 +16,13 @@
 /// file.
 use std::collections::BTreeMap;
 
-pub use load_db::{read_textdump, textdump_load};
+pub use load_db::{read_textdump, textdump_load, LoadSummary, VerbCompilation};
 use moor_values::var::Objid;
 use moor_values::var::Var;
-pub use read::TextdumpReader;
+pub use read::{TextdumpPosition, TextdumpReader, TextdumpReaderError, TextdumpVersion};
+use uuid::Uuid;
 pub use write::TextdumpWriter;
-pub use write_db::make_textdump;
+pub use write_db::{make_textdump, textdump_save, textdump_save_delta};
 
 mod load_db;
 mod read;
@@ -47,6 +48,8 @@ pub struct Verbdef {
     pub owner: Objid,
     pub flags: u16,
     pub prep: i16,
+    /// The verb's identity, in dumps whose format records it.
+    pub uuid: Option<Uuid>,
 }
 
 #[derive(Clone)]
@@ -58,6 +61,8 @@ pub struct Propval {
 }
 
 pub struct Object {
+    /// Where the object starts in the dump it was read from; zeroes if it wasn't.
+    pub position: TextdumpPosition,
     pub id: Objid,
     pub owner: Objid,
     pub location: Objid,
@@ -70,16 +75,35 @@ pub struct Object {
     pub flags: u8,
     pub verbdefs: Vec<Verbdef>,
     pub propdefs: Vec<String>,
+    /// The identities of `propdefs`, in the same order, in dumps whose format records them.
+    /// Otherwise empty.
+    pub propdef_uuids: Vec<Uuid>,
     pub propvals: Vec<Propval>,
 }
 
 #[derive(Clone, Debug)]
 pub struct Verb {
+    /// Where the program starts in the dump it was read from; zeroes if it wasn't.
+    pub position: TextdumpPosition,
     pub objid: Objid,
     pub verbnum: usize,
     pub program: Option<String>,
 }
 
+/// An object's parents after its first, and the values of the properties it inherits through
+/// them. Object records have no room for either, so these go in a section of their own at the end
+/// of the dump, which loaders that don't know about it never get as far as reading.
+pub struct ExtraParents {
+    /// Where the record starts in the dump it was read from; zeroes if it wasn't.
+    pub position: TextdumpPosition,
+    pub id: Objid,
+    /// Empty for an object with one parent that inherits through another object's extra parents.
+    pub parents: Vec<Objid>,
+    /// Values of the properties defined outside the object's first-parent lineage, in the order
+    /// the world resolves them.
+    pub propvals: Vec<Propval>,
+}
+
 pub struct Textdump {
     #[allow(dead_code)]
     pub version: String,
@@ -87,6 +111,8 @@ pub struct Textdump {
     #[allow(dead_code)]
     pub users: Vec<Objid>,
     pub verbs: BTreeMap<(Objid, usize), Verb>,
+    /// Empty unless some object has more than one parent.
+    pub extra_parents: Vec<ExtraParents>,
 }
 
 const PREP_ANY: i16 = -2;
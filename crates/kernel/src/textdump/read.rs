@@ -13,81 +13,187 @@
 //
 
 use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
 use std::io::{BufRead, BufReader, Read};
 
 use moor_values::model::WorldStateError;
-use text_io::scan;
-use tracing::info;
+use text_io::try_scan;
+use tracing::{info, warn};
+use uuid::Uuid;
 
 use moor_compiler::CompileError;
 use moor_values::var::Objid;
 use moor_values::var::{v_err, v_float, v_int, v_none, v_objid, v_str, Var, VarType};
 use moor_values::var::{v_listv, Error};
 
-use crate::textdump::{Object, Propval, Textdump, Verb, Verbdef};
+use crate::textdump::{ExtraParents, Object, Propval, Textdump, Verb, Verbdef};
 use moor_compiler::Label;
 
 pub const TYPE_CLEAR: i64 = 5;
 
+/// What follows the record count on the line that opens the section of additional parents.
+pub const EXTRA_PARENTS_SECTION: &str = "objects with extra parents";
+const LAMBDAMOO_VERSION_PREFIX: &str = "** LambdaMOO Database, Format Version ";
+const MOOR_VERSION_PREFIX: &str = "** moor Textdump DB Version ";
+
+/// The newest LambdaMOO database format we know how to read (`DBV_BFBugFixed`).
+const LAMBDAMOO_MAX_VERSION: u16 = 4;
+const MOOR_MAX_VERSION: u16 = 2;
+
+/// The dump format, as declared by the header line at the top of a textdump.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum TextdumpVersion {
+    /// `** LambdaMOO Database, Format Version N **`
+    LambdaMOO(u16),
+    /// `** moor Textdump DB Version N **`
+    Moor(u16),
+}
+
+/// A place in a textdump, for error messages. Lines and columns count from 1.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub struct TextdumpPosition {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Display for TextdumpPosition {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}, column {}", self.line, self.column)
+    }
+}
+
+impl TextdumpVersion {
+    /// Parse the header, which is always the first line of the dump.
+    pub fn parse(header: &str) -> Result<Self, TextdumpReaderError> {
+        let header = header.trim();
+        let unsupported = || {
+            TextdumpReaderError::UnsupportedVersion(
+                TextdumpPosition { line: 1, column: 1 },
+                header.to_string(),
+            )
+        };
+        let (prefix, max, ctor): (_, _, fn(u16) -> Self) =
+            if header.starts_with(LAMBDAMOO_VERSION_PREFIX) {
+                (
+                    LAMBDAMOO_VERSION_PREFIX,
+                    LAMBDAMOO_MAX_VERSION,
+                    Self::LambdaMOO,
+                )
+            } else if header.starts_with(MOOR_VERSION_PREFIX) {
+                (MOOR_VERSION_PREFIX, MOOR_MAX_VERSION, Self::Moor)
+            } else {
+                return Err(unsupported());
+            };
+        let Some(number) = header[prefix.len()..].strip_suffix(" **") else {
+            return Err(unsupported());
+        };
+        let Ok(number) = number.parse::<u16>() else {
+            return Err(unsupported());
+        };
+        if number > max {
+            return Err(unsupported());
+        }
+        Ok(ctor(number))
+    }
+
+    /// Whether verb and property definitions carry their UUIDs, which moor has written since
+    /// version 2 of its format. LambdaMOO has no such thing.
+    pub fn has_uuids(&self) -> bool {
+        matches!(self, Self::Moor(v) if *v >= 2)
+    }
+}
+
 pub struct TextdumpReader<R: Read> {
     reader: BufReader<R>,
+    /// Whether the dump being read carries verb and property UUIDs, per its header.
+    has_uuids: bool,
+    /// The number of the line most recently read.
+    line: usize,
 }
 
 impl<R: Read> TextdumpReader<R> {
     pub fn new(reader: BufReader<R>) -> Self {
-        Self { reader }
+        Self {
+            reader,
+            has_uuids: false,
+            line: 0,
+        }
     }
 }
 #[derive(Debug, thiserror::Error)]
 pub enum TextdumpReaderError {
     #[error("could not open file: {0}")]
     CouldNotOpenFile(String),
-    #[error("io error: {0}")]
-    IoError(std::io::Error),
-    #[error("parse error: {0}")]
-    ParseError(String),
-    #[error("db error while {0}: {1}")]
-    LoadError(String, WorldStateError),
-    #[error("compile error while {0}: {1}")]
-    VerbCompileError(String, CompileError),
+    #[error("io error at {0}: {1}")]
+    IoError(TextdumpPosition, std::io::Error),
+    /// Where, what was wrong, and the offending text.
+    #[error("parse error at {0}: {1}: {2:?}")]
+    ParseError(TextdumpPosition, String, String),
+    /// Positioned at the object or verb being loaded.
+    #[error("db error at {0} while {1}: {2}")]
+    LoadError(TextdumpPosition, String, WorldStateError),
+    /// Positioned at the verb program's header line.
+    #[error("compile error at {0} while {1}: {2}")]
+    VerbCompileError(TextdumpPosition, String, CompileError),
+    #[error("unsupported textdump version at {0}: {1:?}")]
+    UnsupportedVersion(TextdumpPosition, String),
 }
 
 impl<R: Read> TextdumpReader<R> {
     fn read_next_line(&mut self) -> Result<String, TextdumpReaderError> {
+        self.line += 1;
         let mut buf = String::new();
         if let Err(e) = self.reader.read_line(&mut buf) {
-            return Err(TextdumpReaderError::IoError(e));
+            return Err(TextdumpReaderError::IoError(
+                TextdumpPosition {
+                    line: self.line,
+                    column: 1,
+                },
+                e,
+            ));
         }
         Ok(buf)
     }
 
+    /// The position of the start of the line most recently read.
+    fn position(&self) -> TextdumpPosition {
+        TextdumpPosition {
+            line: self.line,
+            column: 1,
+        }
+    }
+
+    /// A parse error for `token`, found in `line`, the line most recently read.
+    fn parse_error(&self, what: &str, line: &str, token: &str) -> TextdumpReaderError {
+        let column = line.find(token).map(|i| i + 1).unwrap_or(1);
+        TextdumpReaderError::ParseError(
+            TextdumpPosition {
+                line: self.line,
+                column,
+            },
+            what.to_string(),
+            token.to_string(),
+        )
+    }
+
     fn read_num(&mut self) -> Result<i64, TextdumpReaderError> {
         let buf = self.read_next_line()?;
         let Ok(i) = buf.trim().parse() else {
-            return Err(TextdumpReaderError::ParseError(format!(
-                "invalid number: {}",
-                buf
-            )));
+            return Err(self.parse_error("invalid number", &buf, buf.trim()));
         };
         Ok(i)
     }
     fn read_objid(&mut self) -> Result<Objid, TextdumpReaderError> {
         let buf = self.read_next_line()?;
         let Ok(u) = buf.trim().parse() else {
-            return Err(TextdumpReaderError::ParseError(format!(
-                "invalid objid: {}",
-                buf
-            )));
+            return Err(self.parse_error("invalid objid", &buf, buf.trim()));
         };
         Ok(Objid(u))
     }
     fn read_float(&mut self) -> Result<f64, TextdumpReaderError> {
         let buf = self.read_next_line()?;
         let Ok(f) = buf.trim().parse() else {
-            return Err(TextdumpReaderError::ParseError(format!(
-                "invalid float: {}",
-                buf
-            )));
+            return Err(self.parse_error("invalid float", &buf, buf.trim()));
         };
         Ok(f)
     }
@@ -96,32 +202,54 @@ impl<R: Read> TextdumpReader<R> {
         let buf = buf.trim_matches('\n');
         Ok(buf.to_string())
     }
+    fn read_uuid(&mut self) -> Result<Uuid, TextdumpReaderError> {
+        let buf = self.read_next_line()?;
+        let Ok(uuid) = Uuid::parse_str(buf.trim()) else {
+            return Err(self.parse_error("invalid uuid", &buf, buf.trim()));
+        };
+        Ok(uuid)
+    }
     fn read_verbdef(&mut self) -> Result<Verbdef, TextdumpReaderError> {
         let name = self.read_string()?;
         let owner = self.read_objid()?;
         let perms = self.read_num()? as u16;
         let prep = self.read_num()? as i16;
+        let uuid = if self.has_uuids {
+            Some(self.read_uuid()?)
+        } else {
+            None
+        };
         Ok(Verbdef {
             name,
             owner,
             flags: perms,
             prep,
+            uuid,
         })
     }
+    /// Read a value of type `t_num`, which was the last line read.
     fn read_var_value(&mut self, t_num: i64) -> Result<Var, TextdumpReaderError> {
-        let vtype: VarType = VarType::from_repr(t_num as u8).expect("Invalid var type");
+        let Some(vtype) = u8::try_from(t_num).ok().and_then(VarType::from_repr) else {
+            let t_num = t_num.to_string();
+            return Err(self.parse_error("invalid value type", &t_num, &t_num));
+        };
         let v = match vtype {
             VarType::TYPE_INT => v_int(self.read_num()?),
             VarType::TYPE_OBJ => v_objid(self.read_objid()?),
             VarType::TYPE_STR => v_str(&self.read_string()?),
             VarType::TYPE_ERR => {
                 let e_num = self.read_num()?;
-                let etype: Error = Error::from_repr(e_num as u8).expect("Invalid error code");
+                let Some(etype) = u8::try_from(e_num).ok().and_then(Error::from_repr) else {
+                    let e_num = e_num.to_string();
+                    return Err(self.parse_error("invalid error code", &e_num, &e_num));
+                };
                 v_err(etype)
             }
             VarType::TYPE_LIST => {
                 let l_size = self.read_num()?;
-                let v: Vec<Var> = (0..l_size).map(|_l| self.read_var().unwrap()).collect();
+                let v = (0..l_size)
+                    .map(|_l| self.read_var())
+                    .collect::<Result<Vec<_>, _>>()?;
                 v_listv(v)
             }
             VarType::TYPE_NONE => v_none(),
@@ -159,8 +287,9 @@ impl<R: Read> TextdumpReader<R> {
         })
     }
     fn read_object(&mut self) -> Result<Option<Object>, TextdumpReaderError> {
-        let ospec = self.read_string()?;
-        let ospec = ospec.trim();
+        let line = self.read_string()?;
+        let position = self.position();
+        let ospec = line.trim();
 
         let ospec_split = ospec.trim().split_once(' ');
         let ospec = match ospec_split {
@@ -175,20 +304,12 @@ impl<R: Read> TextdumpReader<R> {
 
         match ospec.chars().next() {
             Some('#') => {}
-            _ => {
-                return Err(TextdumpReaderError::ParseError(format!(
-                    "invalid object spec: {}",
-                    ospec
-                )))
-            }
+            _ => return Err(self.parse_error("invalid object spec", &line, ospec)),
         }
         // TODO: handle "recycled" flag in textdump loading.
         let oid_str = &ospec[1..];
         let Ok(oid) = oid_str.trim().parse() else {
-            return Err(TextdumpReaderError::ParseError(format!(
-                "invalid objid: {}",
-                oid_str
-            )));
+            return Err(self.parse_error("invalid objid", &line, oid_str));
         };
         let oid = Objid(oid);
         let name = self.read_string()?;
@@ -208,8 +329,12 @@ impl<R: Read> TextdumpReader<R> {
         }
         let num_pdefs = self.read_num()? as usize;
         let mut propdefs = Vec::with_capacity(num_pdefs);
+        let mut propdef_uuids = vec![];
         for _ in 0..num_pdefs {
             propdefs.push(self.read_string()?);
+            if self.has_uuids {
+                propdef_uuids.push(self.read_uuid()?);
+            }
         }
         let num_pvals = self.read_num()? as usize;
         let mut propvals = Vec::with_capacity(num_pvals);
@@ -218,6 +343,7 @@ impl<R: Read> TextdumpReader<R> {
         }
 
         Ok(Some(Object {
+            position,
             id: oid,
             owner,
             location,
@@ -230,14 +356,17 @@ impl<R: Read> TextdumpReader<R> {
             flags,
             verbdefs,
             propdefs,
+            propdef_uuids,
             propvals,
         }))
     }
 
     fn read_verb(&mut self) -> Result<Verb, TextdumpReaderError> {
         let header = self.read_string()?;
-        let (oid, verbnum): (i64, usize);
-        scan!(header.bytes() => "#{}:{}", oid, verbnum);
+        let position = self.position();
+        let Ok((oid, verbnum)) = parse_verb_header(&header) else {
+            return Err(self.parse_error("invalid verb header", &header, header.trim()));
+        };
 
         // Collect lines
         let mut program_lines = vec![];
@@ -250,15 +379,113 @@ impl<R: Read> TextdumpReader<R> {
         }
         let program = program_lines.join("\n");
         Ok(Verb {
+            position,
             objid: Objid(oid),
             verbnum,
             program: Some(program),
         })
     }
 
+    fn read_extra_parents_record(&mut self) -> Result<ExtraParents, TextdumpReaderError> {
+        let header = self.read_string()?;
+        let position = self.position();
+        let ospec = header.trim();
+        let Some(Ok(id)) = ospec.strip_prefix('#').map(str::parse::<i64>) else {
+            return Err(self.parse_error("invalid object spec", &header, ospec));
+        };
+        let num_parents = self.read_num()? as usize;
+        let mut parents = Vec::with_capacity(num_parents);
+        for _ in 0..num_parents {
+            parents.push(self.read_objid()?);
+        }
+        let num_pvals = self.read_num()? as usize;
+        let mut propvals = Vec::with_capacity(num_pvals);
+        for _ in 0..num_pvals {
+            propvals.push(self.read_propval()?);
+        }
+        Ok(ExtraParents {
+            position,
+            id: Objid(id),
+            parents,
+            propvals,
+        })
+    }
+
+    /// Read the objects' additional parents, from the section that follows the tasks if the dump
+    /// has one.
+    fn read_extra_parents(&mut self) -> Result<Vec<ExtraParents>, TextdumpReaderError> {
+        let line = self.read_next_line()?;
+        let Some(count) = line
+            .trim()
+            .strip_suffix(EXTRA_PARENTS_SECTION)
+            .map(|count| count.trim())
+        else {
+            return Ok(vec![]);
+        };
+        let Ok(count) = count.parse::<usize>() else {
+            return Err(self.parse_error("invalid record count", &line, count));
+        };
+        let mut records = Vec::with_capacity(count);
+        for _ in 0..count {
+            records.push(self.read_extra_parents_record()?);
+        }
+        Ok(records)
+    }
+
+    /// Read a `"<count> <section>"` line, e.g. `"2 queued tasks"`. Returns `None` at end of
+    /// file, as dumps truncated after the programs section are still usable.
+    fn read_section_count(&mut self, section: &str) -> Result<Option<usize>, TextdumpReaderError> {
+        let line = self.read_next_line()?;
+        if line.is_empty() {
+            return Ok(None);
+        }
+        let count = line
+            .trim()
+            .strip_suffix(section)
+            .and_then(|n| n.trim().parse().ok());
+        let Some(count) = count else {
+            return Err(self.parse_error(
+                &format!("invalid {} count", section),
+                &line,
+                line.trim(),
+            ));
+        };
+        Ok(Some(count))
+    }
+
+    /// Read the task sections that follow the programs. Tasks aren't restored from textdumps,
+    /// so this only validates the section headers. Old-style clock entries are one line each
+    /// and get skipped; queued and suspended tasks carry whole VM activations, so if there are
+    /// any we stop reading there. Returns whether it got past them all, to whatever follows.
+    fn read_task_sections(&mut self) -> Result<bool, TextdumpReaderError> {
+        let Some(nclocks) = self.read_section_count("clocks")? else {
+            return Ok(false);
+        };
+        for _ in 0..nclocks {
+            self.read_string()?;
+        }
+        let Some(nqueued) = self.read_section_count("queued tasks")? else {
+            return Ok(false);
+        };
+        if nqueued > 0 {
+            warn!("Ignoring {} queued tasks in textdump", nqueued);
+            return Ok(false);
+        }
+        let Some(nsuspended) = self.read_section_count("suspended tasks")? else {
+            return Ok(false);
+        };
+        if nsuspended > 0 {
+            warn!("Ignoring {} suspended tasks in textdump", nsuspended);
+            return Ok(false);
+        }
+        Ok(true)
+    }
+
     pub fn read_textdump(&mut self) -> Result<Textdump, TextdumpReaderError> {
         let version = self.read_string()?;
-        info!("version {}", version);
+        let dump_version = TextdumpVersion::parse(&version)?;
+        info!("version {:?}", dump_version);
+        self.has_uuids = dump_version.has_uuids();
         let nobjs = self.read_num()? as usize;
         info!("# objs: {}", nobjs);
         let nprogs = self.read_num()? as usize;
@@ -287,11 +514,27 @@ impl<R: Read> TextdumpReader<R> {
             verbs.insert((verb.objid, verb.verbnum), verb);
         }
 
+        info!("Reading tasks...");
+        let extra_parents = if self.read_task_sections()? {
+            info!("Reading additional parents...");
+            self.read_extra_parents()?
+        } else {
+            vec![]
+        };
+
         Ok(Textdump {
             version,
             objects,
             users,
             verbs,
+            extra_parents,
         })
     }
 }
+
+/// Parse a `#<objid>:<verbnum>` program header.
+fn parse_verb_header(header: &str) -> Result<(i64, usize), text_io::Error> {
+    let (oid, verbnum): (i64, usize);
+    try_scan!(header.bytes() => "#{}:{}", oid, verbnum);
+    Ok((oid, verbnum))
+}
@@ -12,22 +12,27 @@
 // this program. If not, see <https://www.gnu.org/licenses/>.
 //
 
-use std::collections::BTreeMap;
 use std::io;
 
 use moor_values::var::Objid;
 use moor_values::var::{Var, VarType, Variant};
+use uuid::Uuid;
 
-use crate::textdump::read::TYPE_CLEAR;
-use crate::textdump::{Object, Propval, Textdump, Verb, Verbdef};
+use crate::textdump::read::{EXTRA_PARENTS_SECTION, TYPE_CLEAR};
+use crate::textdump::{ExtraParents, Object, Propval, Textdump, TextdumpVersion, Verb, Verbdef};
 
 pub struct TextdumpWriter<W: io::Write> {
     writer: W,
+    /// Whether to write verb and property UUIDs, which depends on the version in the header.
+    with_uuids: bool,
 }
 
 impl<W: io::Write> TextdumpWriter<W> {
     pub fn new(writer: W) -> Self {
-        Self { writer }
+        Self {
+            writer,
+            with_uuids: false,
+        }
     }
 }
 
@@ -37,7 +42,13 @@ impl<W: io::Write> TextdumpWriter<W> {
             self.writer,
             "{}\n{}\n{}\n{}",
             verbdef.name, verbdef.owner.0, verbdef.flags, verbdef.prep
-        )
+        )?;
+        if self.with_uuids {
+            // Verbs read from a dump without UUIDs get a fresh one, as they would on loading.
+            let uuid = verbdef.uuid.unwrap_or_else(Uuid::new_v4);
+            writeln!(self.writer, "{}", uuid)?;
+        }
+        Ok(())
     }
 
     fn write_var(&mut self, var: &Var, is_clear: bool) -> Result<(), io::Error> {
@@ -83,7 +94,7 @@ impl<W: io::Write> TextdumpWriter<W> {
         Ok(())
     }
 
-    fn write_object(&mut self, object: &Object) -> Result<(), io::Error> {
+    pub fn write_object(&mut self, object: &Object) -> Result<(), io::Error> {
         writeln!(self.writer, "#{}\n{}\n", object.id.0, &object.name,)?;
 
         writeln!(self.writer, "{}", object.flags)?;
@@ -99,8 +110,12 @@ impl<W: io::Write> TextdumpWriter<W> {
             self.write_verbdef(verbdef)?;
         }
         writeln!(self.writer, "{}", object.propdefs.len())?;
-        for propdef in &object.propdefs {
+        for (i, propdef) in object.propdefs.iter().enumerate() {
             writeln!(self.writer, "{}", propdef)?;
+            if self.with_uuids {
+                let uuid = object.propdef_uuids.get(i).copied();
+                writeln!(self.writer, "{}", uuid.unwrap_or_else(Uuid::new_v4))?;
+            }
         }
         writeln!(self.writer, "{}", object.propvals.len())?;
         for propval in &object.propvals {
@@ -109,51 +124,93 @@ impl<W: io::Write> TextdumpWriter<W> {
         Ok(())
     }
 
-    fn write_verbs(&mut self, verbs: &BTreeMap<(Objid, usize), Verb>) -> Result<(), io::Error> {
-        for verb in verbs.values() {
-            let Some(program) = verb.program.clone() else {
-                continue;
-            };
+    fn write_extra_parents(&mut self, record: &ExtraParents) -> Result<(), io::Error> {
+        writeln!(self.writer, "#{}", record.id.0)?;
+        writeln!(self.writer, "{}", record.parents.len())?;
+        for parent in &record.parents {
+            writeln!(self.writer, "{}", parent.0)?;
+        }
+        writeln!(self.writer, "{}", record.propvals.len())?;
+        for propval in &record.propvals {
+            self.write_propval(propval)?;
+        }
+        Ok(())
+    }
+
+    /// Write a verb's program. Verbs without one don't appear in the programs section at all.
+    pub fn write_verb(&mut self, verb: &Verb) -> Result<(), io::Error> {
+        let Some(program) = &verb.program else {
+            return Ok(());
+        };
+        writeln!(
+            self.writer,
+            "#{}:{}\n{}\n.",
+            verb.objid.0, verb.verbnum, program
+        )
+    }
+
+    /// Write the version line and the counts that precede the objects. `nprogs` counts only the
+    /// verbs that have programs, as those are all that go in the programs section. The version
+    /// also decides whether verb and property UUIDs get written.
+    pub fn write_header(
+        &mut self,
+        version: &str,
+        nobjects: usize,
+        nprogs: usize,
+        users: &[Objid],
+    ) -> Result<(), io::Error> {
+        self.with_uuids = TextdumpVersion::parse(version).is_ok_and(|v| v.has_uuids());
+        writeln!(self.writer, "{}", version)?;
+        writeln!(self.writer, "{}\n{}\n0\n{}", nobjects, nprogs, users.len())?;
+        for user in users {
+            writeln!(self.writer, "{}", user.0)?;
+        }
+        Ok(())
+    }
 
+    /// Write everything that follows the programs section.
+    pub fn write_footer(&mut self, extra_parents: &[ExtraParents]) -> Result<(), io::Error> {
+        // TODO: Suspended tasks, clocks, queued tasks in textdump write
+        //    actually write clocks/tasks/suspended tasks, but for now we just write 0 for each
+        writeln!(self.writer, "0 clocks")?;
+        writeln!(self.writer, "0 queued tasks")?;
+        writeln!(self.writer, "0 suspended tasks")?;
+
+        // Only written when there's something to put in it, so that a world with no additional
+        // parents dumps exactly as it always has.
+        if !extra_parents.is_empty() {
             writeln!(
                 self.writer,
-                "#{}:{}\n{}\n.",
-                verb.objid.0, verb.verbnum, program
+                "{} {}",
+                extra_parents.len(),
+                EXTRA_PARENTS_SECTION
             )?;
+            for record in extra_parents {
+                self.write_extra_parents(record)?;
+            }
         }
         Ok(())
     }
 
     pub fn write_textdump(&mut self, textdump: &Textdump) -> Result<(), io::Error> {
-        writeln!(self.writer, "{}", &textdump.version)?;
-
         // We only count the existence of programs, not verbs, here.
         let nprogs = textdump
             .verbs
             .iter()
             .filter(|(_, v)| v.program.is_some())
             .count();
-        writeln!(
-            self.writer,
-            "{}\n{}\n0\n{}",
+        self.write_header(
+            &textdump.version,
             textdump.objects.len(),
             nprogs,
-            textdump.users.len()
+            &textdump.users,
         )?;
-        for user in &textdump.users {
-            writeln!(self.writer, "{}", user.0)?;
-        }
         for object in textdump.objects.values() {
             self.write_object(object)?;
         }
-        self.write_verbs(&textdump.verbs)?;
-
-        // TODO: Suspended tasks, clocks, queued tasks in textdump write
-        //    actually write clocks/tasks/suspended tasks, but for now we just write 0 for each
-        writeln!(self.writer, "0 clocks")?;
-        writeln!(self.writer, "0 queued tasks")?;
-        writeln!(self.writer, "0 suspended tasks")?;
-
-        Ok(())
+        for verb in textdump.verbs.values() {
+            self.write_verb(verb)?;
+        }
+        self.write_footer(&textdump.extra_parents)
     }
 }
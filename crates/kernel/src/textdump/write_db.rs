@@ -13,26 +13,30 @@
 //
 
 use std::collections::BTreeMap;
+use std::fs::File;
+use std::io;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
 use std::rc::Rc;
 
 use moor_compiler::Program;
 use moor_db::loader::LoaderInterface;
 use moor_values::model::{ArgSpec, PrepSpec, VerbArgsSpec};
 use moor_values::model::{BinaryType, VerbFlag};
-use moor_values::model::{HasUuid, Named};
+use moor_values::model::{HasUuid, Named, ObjAttrs, PropDef, VerbDef};
 use moor_values::util::BitEnum;
 use moor_values::util::SliceRef;
-use moor_values::var::v_none;
 use moor_values::var::Objid;
+use moor_values::var::{v_none, Var};
 use moor_values::{AsByteBuffer, NOTHING};
 
 use crate::textdump::{
-    Object, Propval, Textdump, Verb, Verbdef, VF_ASPEC_ANY, VF_ASPEC_NONE, VF_ASPEC_THIS,
-    VF_DOBJSHIFT, VF_IOBJSHIFT,
+    ExtraParents, Object, Propval, Textdump, TextdumpWriter, Verb, Verbdef, VF_ASPEC_ANY,
+    VF_ASPEC_NONE, VF_ASPEC_THIS, VF_DOBJSHIFT, VF_IOBJSHIFT,
 };
 
 /// What we use if the passed-in format at write time is None
-pub const MOOR_TEXTDUMP_DB_VERSION: &str = "** moor Textdump DB Version 1 **";
+pub const MOOR_TEXTDUMP_DB_VERSION: &str = "** moor Textdump DB Version 2 **";
 
 /// Convert verbargs spec to flags & preps accordingly
 fn cv_arg(flags: BitEnum<VerbFlag>, arg: VerbArgsSpec) -> (u16, i16) {
@@ -57,43 +61,226 @@ fn cv_arg(flags: BitEnum<VerbFlag>, arg: VerbArgsSpec) -> (u16, i16) {
     (flags | arg_flags, prepflags)
 }
 
-/// Take a transaction, and scan the relations and build a Textdump representing a snapshot of the world as it
-/// exists in the transaction.
-pub fn make_textdump(tx: Rc<dyn LoaderInterface>, version: Option<&str>) -> Textdump {
-    // To create the objects list, we need to scan all objects.
-    // For now, the expectation would be we can simply iterate from 0 to max object, checking validity of each
-    // object, and then adding it to the list.
+/// The parent -> children and location -> contents lists, in object id order, from which the
+/// textdump's child/sibling and contents/next links are derived.
+struct Linkage {
+    children: BTreeMap<Objid, Vec<Objid>>,
+    contents: BTreeMap<Objid, Vec<Objid>>,
+}
+
+impl Linkage {
+    fn new(tx: &dyn LoaderInterface, object_ids: &[Objid]) -> Self {
+        let mut children = BTreeMap::new();
+        let mut contents = BTreeMap::new();
+        for id in object_ids {
+            let obj = tx.get_object(*id).expect("Failed to get object");
+            children
+                .entry(obj.parent.unwrap())
+                .or_insert_with(Vec::new)
+                .push(*id);
+            contents
+                .entry(obj.location.unwrap())
+                .or_insert_with(Vec::new)
+                .push(*id);
+        }
+        Self { children, contents }
+    }
+
+    /// The first of `id`'s entries in `map`, and the one after `id` among its peers under `under`.
+    fn first_and_next(
+        map: &BTreeMap<Objid, Vec<Objid>>,
+        id: Objid,
+        under: Objid,
+    ) -> (Objid, Objid) {
+        let first = map
+            .get(&id)
+            .and_then(|entries| entries.first().copied())
+            .unwrap_or(NOTHING);
+        let peers = map.get(&under).expect("Failed to get peers");
+        let position = peers
+            .iter()
+            .position(|x| *x == id)
+            .expect("Failed to find object among its peers");
+        let next = peers.get(position + 1).copied().unwrap_or(NOTHING);
+        (first, next)
+    }
+}
 
-    // Find all the ids
-    let object_ids = tx.get_objects().expect("Failed to get objects");
+/// All object ids in the database, in order.
+fn sorted_objects(tx: &dyn LoaderInterface) -> Vec<Objid> {
+    let mut object_ids: Vec<_> = tx
+        .get_objects()
+        .expect("Failed to get objects")
+        .iter()
+        .collect();
+    object_ids.sort();
+    object_ids
+}
+
+/// Decompile a verb's program, or `None` if it has no code.
+fn verb_program(tx: &dyn LoaderInterface, objid: Objid, verb: &VerbDef) -> Option<String> {
+    let binary = tx
+        .get_verb_binary(objid, verb.uuid())
+        .expect("Failed to get verb binary");
+
+    // Verbs imported lazily are still source, and go back out as they came in.
+    if verb.binary_type() == BinaryType::MooSource {
+        let source = String::from_utf8_lossy(&binary).into_owned();
+        return (!source.is_empty()).then_some(source);
+    }
+
+    // Get and decompile the binary. We only support MOO for now.
+    if verb.binary_type() != BinaryType::LambdaMoo18X {
+        panic!("Unsupported binary type: {:?}", verb.binary_type());
+    }
+
+    let program =
+        Program::from_sliceref(SliceRef::from_vec(binary)).expect("Failed to parse verb binary");
+    if program.main_vector.is_empty() {
+        return None;
+    }
+    let ast = moor_compiler::program_to_tree(&program).expect("Failed to decompile verb binary");
+    let program = moor_compiler::unparse(&ast).expect("Failed to decompile verb binary");
+    Some(program.join("\n"))
+}
 
-    // Retrieve all the objects
-    let mut db_objects = BTreeMap::new();
-    for id in object_ids.iter() {
-        db_objects.insert(id, tx.get_object(id).expect("Failed to get object"));
+/// The object and its ancestors through first parents only, which is as much of its ancestry as
+/// an object record can describe.
+fn first_parent_lineage(tx: &dyn LoaderInterface, id: Objid, db_obj: &ObjAttrs) -> Vec<Objid> {
+    let mut lineage = vec![id];
+    let mut ancestor = db_obj.parent;
+    while let Some(parent) = ancestor.filter(|p| *p != NOTHING) {
+        lineage.push(parent);
+        ancestor = tx.get_object(parent).expect("Failed to get object").parent;
     }
+    lineage
+}
+
+fn textdump_propvals(properties: Vec<(PropDef, Option<Var>)>) -> Vec<Propval> {
+    properties
+        .into_iter()
+        .map(|(p, value)| Propval {
+            is_clear: value.is_none(),
+            value: value.unwrap_or(v_none()),
+            owner: p.owner(),
+            flags: p.flags().to_u16() as u8,
+        })
+        .collect()
+}
 
-    // Build a map of parent -> children
-    let mut children_map = BTreeMap::new();
-    for id in db_objects.keys() {
-        let obj = db_objects.get(id).expect("Failed to get object");
-        let parent = obj.parent.unwrap();
-        children_map
-            .entry(parent)
-            .or_insert_with(Vec::new)
-            .push(*id);
+/// Whether a verb has any code, without going to the trouble of decompiling it.
+fn verb_has_program(tx: &dyn LoaderInterface, objid: Objid, verb: &VerbDef) -> bool {
+    let binary = tx
+        .get_verb_binary(objid, verb.uuid())
+        .expect("Failed to get verb binary");
+    if verb.binary_type() == BinaryType::MooSource {
+        return !binary.is_empty();
     }
+    let program =
+        Program::from_sliceref(SliceRef::from_vec(binary)).expect("Failed to parse verb binary");
+    !program.main_vector.is_empty()
+}
+
+/// The textdump form of a single object, without its verb programs, and the record of its
+/// additional parents if it has any or inherits through another object's.
+fn textdump_object(
+    tx: &dyn LoaderInterface,
+    id: Objid,
+    db_obj: &ObjAttrs,
+    linkage: &Linkage,
+) -> (Object, Option<ExtraParents>) {
+    // 'contents' is the first object located in this one, and 'next' the object after this one
+    // in its location's contents.
+    let location = db_obj.location.unwrap();
+    let (contents, next) = Linkage::first_and_next(&linkage.contents, id, location);
+    let next = if location == NOTHING { NOTHING } else { next };
 
-    // Same with location -> contents
-    let mut contents_map = BTreeMap::new();
-    for id in db_objects.keys() {
-        let obj = db_objects.get(id).expect("Failed to get object");
-        let location = obj.location.unwrap();
-        contents_map
-            .entry(location)
-            .or_insert_with(Vec::new)
-            .push(*id);
+    // Same for 'child' and 'sibling' using children/parent
+    let parent = db_obj.parent.unwrap();
+    let (child, sibling) = Linkage::first_and_next(&linkage.children, id, parent);
+
+    // Find the verbdefs and transform them into textdump verbdefs
+    let verbdefs = tx
+        .get_object_verbs(id)
+        .expect("Failed to get verbs")
+        .iter()
+        .map(|db_verbdef| {
+            let name = db_verbdef.names().join(" ");
+            let owner = db_verbdef.owner();
+            let (flags, prep) = cv_arg(db_verbdef.flags(), db_verbdef.args());
+            Verbdef {
+                name,
+                owner,
+                flags,
+                prep,
+                uuid: Some(db_verbdef.uuid()),
+            }
+        })
+        .collect();
+
+    // propvals have wonky logic which resolve relative to position in the inheritance hierarchy of
+    // propdefs up to the root. So we grab that all from the loader_client, and then we can just
+    // iterate through them all.
+    //
+    // The object record only has room for what's inherited through first parents, which is all
+    // older loaders know to look for. Anything inherited through the others goes in a record of
+    // its own, along with those parents.
+    let lineage = first_parent_lineage(tx, id, db_obj);
+    let (properties, inherited_otherwise): (Vec<_>, Vec<_>) = tx
+        .get_all_property_values(id)
+        .unwrap()
+        .into_iter()
+        .partition(|(p, _)| lineage.contains(&p.definer()));
+    let other_parents: Vec<_> = tx
+        .get_object_parents(id)
+        .expect("Failed to get parents")
+        .iter()
+        .skip(1)
+        .collect();
+    let extra_parents =
+        (!other_parents.is_empty() || !inherited_otherwise.is_empty()).then(|| ExtraParents {
+            position: Default::default(),
+            id,
+            parents: other_parents,
+            propvals: textdump_propvals(inherited_otherwise),
+        });
+
+    let mut propdefs = vec![];
+    let mut propdef_uuids = vec![];
+    for (p, _) in &properties {
+        if p.definer() != id {
+            break;
+        }
+        propdefs.push(p.name().into());
+        propdef_uuids.push(p.uuid());
     }
+    let propvals = textdump_propvals(properties);
+
+    let object = Object {
+        position: Default::default(),
+        id,
+        owner: db_obj.owner.unwrap(),
+        location,
+        contents,
+        next,
+        parent,
+        child,
+        sibling,
+        name: db_obj.name.clone().unwrap(),
+        flags: db_obj.flags.unwrap().to_u16() as _,
+        verbdefs,
+        propdefs,
+        propdef_uuids,
+        propvals,
+    };
+    (object, extra_parents)
+}
+
+/// Take a transaction, and scan the relations and build a Textdump representing a snapshot of the world as it
+/// exists in the transaction.
+pub fn make_textdump(tx: Rc<dyn LoaderInterface>, version: Option<&str>) -> Textdump {
+    let object_ids = sorted_objects(tx.as_ref());
+    let linkage = Linkage::new(tx.as_ref(), &object_ids);
 
     // Objid -> Object
     let mut objects = BTreeMap::new();
@@ -101,158 +288,27 @@ pub fn make_textdump(tx: Rc<dyn LoaderInterface>, version: Option<&str>) -> Text
     // (Objid, usize) -> Verb, where usize is the verb number (0-indexed)
     let mut verbs = BTreeMap::new();
 
-    for (db_objid, db_obj) in db_objects.iter() {
-        // To find 'next' for contents, we seek the contents of our location, and find the object right after
-        // the current object in that vector
-        let location = db_obj.location.unwrap();
-
-        let next = if location != NOTHING {
-            let roommates = contents_map
-                .get_mut(&location)
-                .expect("Failed to get contents");
-
-            let position = roommates
-                .iter()
-                .position(|x| x == db_objid)
-                .expect("Failed to find object in contents of location");
-            // If position is at the end, 'next' is -1.
-            if position == roommates.len() - 1 {
-                Objid(-1)
-            } else {
-                roommates[position + 1]
-            }
-        } else {
-            NOTHING
-        };
-
-        // To find 'contents' we're looking for the first object whose location is the current object
-        let contents = match contents_map.get_mut(db_objid) {
-            Some(contents) => *contents.first().unwrap_or(&Objid(-1)),
-            None => NOTHING,
-        };
-
-        let parent = db_obj.parent.unwrap();
-
-        // Same for 'sibling' using children/parent
-        let siblings = children_map
-            .get_mut(&parent)
-            .expect("Failed to get siblings");
-        let position = siblings
-            .iter()
-            .position(|x| x == db_objid)
-            .expect("Failed to find object in siblings");
-        let sibling = if position == siblings.len() - 1 {
-            NOTHING
-        } else {
-            siblings[position + 1]
-        };
-
-        // To find child, we need to find the first object whose parent is the current object
-        let child = match children_map.get_mut(db_objid) {
-            Some(children) => *children.first().unwrap_or(&NOTHING),
-            None => NOTHING,
-        };
-
-        // Find the verbdefs and transform them into textdump verbdefs
-        let db_verbdefs = tx.get_object_verbs(*db_objid).expect("Failed to get verbs");
-        let verbdefs = db_verbdefs
-            .iter()
-            .map(|db_verbdef| {
-                let name = db_verbdef.names().join(" ");
-                let owner = db_verbdef.owner();
-                let (flags, prep) = cv_arg(db_verbdef.flags(), db_verbdef.args());
-                Verbdef {
-                    name,
-                    owner,
-                    flags,
-                    prep,
-                }
-            })
-            .collect();
-        // Produce the verbmap
-        for (verbnum, verb) in db_verbdefs.iter().enumerate() {
-            // Get and decompile the binary. We only support MOO for now.
-            if verb.binary_type() != BinaryType::LambdaMoo18X {
-                panic!("Unsupported binary type: {:?}", verb.binary_type());
-            }
+    let mut extra_parents = vec![];
+
+    for objid in object_ids {
+        let db_obj = tx.get_object(objid).expect("Failed to get object");
+        let (object, extra) = textdump_object(tx.as_ref(), objid, &db_obj, &linkage);
+        objects.insert(objid, object);
+        extra_parents.extend(extra);
 
-            let binary = tx
-                .get_verb_binary(*db_objid, verb.uuid())
-                .expect("Failed to get verb binary");
-
-            let program = Program::from_sliceref(SliceRef::from_vec(binary))
-                .expect("Failed to parse verb binary");
-            let program = if !program.main_vector.is_empty() {
-                let ast = moor_compiler::program_to_tree(&program)
-                    .expect("Failed to decompile verb binary");
-                let program =
-                    moor_compiler::unparse(&ast).expect("Failed to decompile verb binary");
-                Some(program.join("\n"))
-            } else {
-                None
-            };
-            let objid = *db_objid;
+        let db_verbdefs = tx.get_object_verbs(objid).expect("Failed to get verbs");
+        for (verbnum, verb) in db_verbdefs.iter().enumerate() {
+            let program = verb_program(tx.as_ref(), objid, &verb);
             verbs.insert(
                 (objid, verbnum),
                 Verb {
+                    position: Default::default(),
                     objid,
                     verbnum,
                     program,
                 },
             );
         }
-
-        // let db_propdefs = tx.get_object_properties(*db_objid).unwrap();
-        // let propdefs: Vec<_> = db_propdefs
-        //     .iter()
-        //     .filter(|p| p.definer() == *db_objid && p.location() == *db_objid)
-        //     .map(|p| p.name().into())
-        //     .collect();
-
-        // propvals have wonky logic which resolve relative to position in the inheritance hierarchy of
-        // propdefs up to the root. So we grab that all from the loader_client, and then we can just
-        // iterate through them all.
-        let properties = tx.get_all_property_values(*db_objid).unwrap();
-
-        let mut propdefs = vec![];
-        for (p, _) in &properties {
-            if p.definer() != *db_objid {
-                break;
-            }
-            propdefs.push(p.name().into());
-        }
-
-        let mut propvals = vec![];
-        for (p, value) in properties {
-            let owner = p.owner();
-            let flags = p.flags().to_u16() as u8;
-            let is_clear = value.is_none();
-            propvals.push(Propval {
-                value: value.unwrap_or(v_none()),
-                owner,
-                flags,
-                is_clear,
-            });
-        }
-        // To construct the child linkage list, we need to scan all objects, and find all objects whose parent
-        // is the current object, and add them to the list.
-        let obj = Object {
-            id: *db_objid,
-            owner: db_obj.owner.unwrap(),
-            location: db_obj.location.unwrap(),
-            contents,
-            next,
-            parent,
-            child,
-            sibling,
-            name: db_obj.name.clone().unwrap(),
-            flags: db_obj.flags.unwrap().to_u16() as _,
-            verbdefs,
-            propdefs,
-            propvals,
-        };
-
-        objects.insert(*db_objid, obj);
     }
 
     let users = tx
@@ -266,5 +322,102 @@ pub fn make_textdump(tx: Rc<dyn LoaderInterface>, version: Option<&str>) -> Text
         objects,
         users,
         verbs,
+        extra_parents,
+    }
+}
+
+/// Write a textdump of the world as it exists in the transaction to `path`, as `make_textdump`
+/// followed by `TextdumpWriter::write_textdump` would, but a single object or verb at a time
+/// rather than holding the whole world in memory.
+pub fn textdump_save(
+    tx: Rc<dyn LoaderInterface>,
+    path: PathBuf,
+    version: Option<&str>,
+) -> Result<(), io::Error> {
+    let object_ids = sorted_objects(tx.as_ref());
+    let linkage = Linkage::new(tx.as_ref(), &object_ids);
+    let users: Vec<_> = tx
+        .get_players()
+        .expect("Failed to get players list")
+        .iter()
+        .collect();
+    save_objects(tx.as_ref(), &object_ids, &linkage, &users, path, version)
+}
+
+/// Like `textdump_save`, but only the objects changed by transactions committed after timestamp
+/// `since` (as from `LoaderInterface::timestamp`), each with all its verbs and properties. The
+/// result can be applied on top of the world it was taken from with `textdump_load`.
+/// Objects recycled since aren't recorded.
+pub fn textdump_save_delta(
+    tx: Rc<dyn LoaderInterface>,
+    since: u64,
+    path: PathBuf,
+    version: Option<&str>,
+) -> Result<(), io::Error> {
+    let all_objects = sorted_objects(tx.as_ref());
+    let linkage = Linkage::new(tx.as_ref(), &all_objects);
+    let modified = tx
+        .objects_modified_since(since)
+        .expect("Failed to get modified objects");
+    let object_ids: Vec<_> = all_objects
+        .into_iter()
+        .filter(|o| modified.contains(*o))
+        .collect();
+    let users: Vec<_> = tx
+        .get_players()
+        .expect("Failed to get players list")
+        .iter()
+        .filter(|o| modified.contains(*o))
+        .collect();
+    save_objects(tx.as_ref(), &object_ids, &linkage, &users, path, version)
+}
+
+fn save_objects(
+    tx: &dyn LoaderInterface,
+    object_ids: &[Objid],
+    linkage: &Linkage,
+    users: &[Objid],
+    path: PathBuf,
+    version: Option<&str>,
+) -> Result<(), io::Error> {
+    // The header counts the verbs with programs, so that takes a pass of its own.
+    let mut nprogs = 0;
+    for objid in object_ids {
+        let db_verbdefs = tx.get_object_verbs(*objid).expect("Failed to get verbs");
+        for verb in db_verbdefs.iter() {
+            if verb_has_program(tx, *objid, &verb) {
+                nprogs += 1;
+            }
+        }
+    }
+
+    let mut output = BufWriter::new(File::create(path)?);
+    let mut writer = TextdumpWriter::new(&mut output);
+    writer.write_header(
+        version.unwrap_or(MOOR_TEXTDUMP_DB_VERSION),
+        object_ids.len(),
+        nprogs,
+        users,
+    )?;
+    // The additional parents go at the very end, so they're held on to until then.
+    let mut extra_parents = vec![];
+    for objid in object_ids {
+        let db_obj = tx.get_object(*objid).expect("Failed to get object");
+        let (object, extra) = textdump_object(tx, *objid, &db_obj, linkage);
+        writer.write_object(&object)?;
+        extra_parents.extend(extra);
+    }
+    for objid in object_ids {
+        let db_verbdefs = tx.get_object_verbs(*objid).expect("Failed to get verbs");
+        for (verbnum, verb) in db_verbdefs.iter().enumerate() {
+            writer.write_verb(&Verb {
+                position: Default::default(),
+                objid: *objid,
+                verbnum,
+                program: verb_program(tx, *objid, &verb),
+            })?;
+        }
     }
+    writer.write_footer(&extra_parents)?;
+    output.flush()
 }
@@ -33,7 +33,7 @@ use moor_compiler::{Label, Name};
 use moor_compiler::{Op, EMPTY_PROGRAM};
 
 // {this, verb-name, programmer, verb-loc, player, line-number}
-#[derive(Clone)]
+#[derive(Debug, Clone)]
 pub struct Caller {
     pub this: Objid,
     pub verb_name: String,
@@ -157,7 +157,9 @@ impl Frame {
 
     #[inline]
     pub fn set_var_offset(&mut self, offset: &Name, value: Var) -> Result<(), Error> {
-        if offset.0 as usize >= self.environment.len() {
+        // Check against the variables the program declares, not `environment.len()`, which only
+        // counts the ones that have been assigned so far.
+        if offset.0 as usize >= self.program.var_names.width() {
             return Err(E_VARNF);
         }
         self.environment.set(offset.0 as usize, value);
@@ -196,12 +198,6 @@ impl Frame {
         self.valstack.last_mut().expect("stack underflow")
     }
 
-    #[inline]
-    pub fn peek_range(&self, width: usize) -> Vec<Var> {
-        let l = self.valstack.len();
-        Vec::from(&self.valstack[l - width..])
-    }
-
     #[inline]
     pub(crate) fn peek_abs(&self, amt: usize) -> &Var {
         &self.valstack[amt]
@@ -318,7 +314,7 @@ impl Activation {
         }
     }
 
-    pub fn for_eval(permissions: Objid, player: Objid, program: Program) -> Self {
+    pub fn for_eval(permissions: Objid, player: Objid, caller: Objid, program: Program) -> Self {
         let environment = BitArray::new();
 
         let verb_info = VerbInfo::new(
@@ -347,7 +343,7 @@ impl Activation {
         set_constants(&mut frame);
         frame.set_gvar(GlobalName::this, v_objid(player));
         frame.set_gvar(GlobalName::player, v_objid(player));
-        frame.set_gvar(GlobalName::caller, v_objid(player));
+        frame.set_gvar(GlobalName::caller, v_objid(caller));
         frame.set_gvar(GlobalName::verb, v_str("eval"));
         frame.set_gvar(GlobalName::args, v_empty_list());
         frame.set_gvar(GlobalName::argstr, v_str(""));
@@ -12,6 +12,7 @@
 // this program. If not, see <https://www.gnu.org/licenses/>.
 //
 
+use crate::tasks::task_messages::QueuedVerbCall;
 use crate::tasks::{PhantomUnsend, PhantomUnsync, TaskId};
 use crate::vm::activation::{Activation, Caller};
 use moor_values::var::Objid;
@@ -38,6 +39,8 @@ pub struct VMExecState {
     pub(crate) start_time: Option<SystemTime>,
     /// The amount of time the task is allowed to run.
     pub(crate) maximum_time: Option<Duration>,
+    /// Verb calls to be made once the task's transaction commits.
+    pub(crate) queued_calls: Vec<QueuedVerbCall>,
 
     unsend: PhantomUnsend,
     unsync: PhantomUnsync,
@@ -52,6 +55,7 @@ impl VMExecState {
             start_time: None,
             tick_slice: 0,
             maximum_time: None,
+            queued_calls: vec![],
             unsend: Default::default(),
             unsync: Default::default(),
         }
@@ -138,8 +142,12 @@ impl VMExecState {
 
     /// Update the permissions of the current task, as called by the `set_task_perms`
     /// built-in.
+    /// Like `task_perms`, this applies to the innermost verb frame, not the builtin's own
+    /// activation (which is about to be popped).
     pub(crate) fn set_task_perms(&mut self, perms: Objid) {
-        self.top_mut().permissions = perms;
+        if let Some(activation) = self.stack.iter_mut().rev().find(|a| a.bf_index.is_none()) {
+            activation.permissions = perms;
+        }
     }
 
     /// Pop a value off the value stack.
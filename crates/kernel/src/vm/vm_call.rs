@@ -187,13 +187,15 @@ impl VM {
         player: Objid,
         program: Program,
     ) {
+        // The eval'd code's `caller` is whoever called `eval()`, or #-1 at the top of a task.
+        let caller = vm_state.caller();
         if !vm_state.stack.is_empty() {
             // We need to set up a trampoline to return back into `bf_eval`
             vm_state.top_mut().bf_trampoline_arg = None;
             vm_state.top_mut().bf_trampoline = Some(BF_SERVER_EVAL_TRAMPOLINE_RESUME);
         }
 
-        let a = Activation::for_eval(permissions, player, program);
+        let a = Activation::for_eval(permissions, player, caller, program);
 
         vm_state.stack.push(a);
     }
@@ -261,6 +263,9 @@ impl VM {
             session: session.clone(),
             args,
             scheduler_sender: exec_args.scheduler_sender.clone(),
+            verb_metrics: exec_args.verb_metrics.clone(),
+            coverage: exec_args.coverage.clone(),
+            clock: exec_args.clock.clone(),
         };
 
         let call_results = match bf.call(&mut bf_args) {
@@ -307,6 +312,9 @@ impl VM {
             session: sessions,
             args,
             scheduler_sender: exec_args.scheduler_sender.clone(),
+            verb_metrics: exec_args.verb_metrics.clone(),
+            coverage: exec_args.coverage.clone(),
+            clock: exec_args.clock.clone(),
         };
 
         match bf.call(&mut bf_args) {
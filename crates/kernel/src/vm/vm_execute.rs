@@ -18,14 +18,17 @@ use std::time::Duration;
 
 use moor_compiler::{Name, Offset};
 
+use crate::tasks::clock::Clock;
 use crate::tasks::command_parse::ParsedCommand;
+use crate::tasks::coverage::TaskCoverage;
 use crate::tasks::sessions::Session;
 use crate::tasks::task_messages::SchedulerControlMsg;
+use crate::tasks::verb_metrics::VerbMetrics;
 use crate::tasks::{TaskId, VerbCall};
 use moor_compiler::Program;
 use moor_compiler::{Op, ScatterLabel};
-use moor_values::model::VerbInfo;
 use moor_values::model::WorldState;
+use moor_values::model::{HasUuid, VerbInfo};
 use moor_values::var::Error::{E_ARGS, E_DIV, E_INVARG, E_MAXREC, E_RANGE, E_TYPE, E_VARNF};
 use moor_values::var::Objid;
 use moor_values::var::Variant;
@@ -63,6 +66,11 @@ pub struct Fork {
 pub struct VmExecParams {
     pub scheduler_sender: Sender<(TaskId, SchedulerControlMsg)>,
     pub max_stack_depth: usize,
+    pub verb_metrics: Arc<VerbMetrics>,
+    /// Where to record executed opcodes and lines, if the task is running in coverage mode.
+    pub coverage: Option<Arc<TaskCoverage>>,
+    /// Where the time builtins get the time from.
+    pub clock: Clock,
 }
 #[derive(Eq, PartialEq, Debug, Clone)]
 pub enum ExecutionResult {
@@ -105,8 +113,8 @@ pub enum ExecutionResult {
     /// If the duration is None, then the task is suspended indefinitely, until it is killed or
     /// resumed using `resume()` or `kill_task()`.
     Suspend(Option<Duration>),
-    /// Request input from the client.
-    NeedInput,
+    /// Request a line of input from the given player's connection.
+    NeedInput(Objid),
     /// Request `eval` execution, which is a kind of special activation creation where we've already
     /// been given the program to execute instead of having to look it up.
     PerformEval {
@@ -143,6 +151,15 @@ macro_rules! binary_var_op {
     };
 }
 
+#[inline]
+fn is_zero_divisor(v: &Var) -> bool {
+    match v.variant() {
+        Variant::Int(i) => *i == 0,
+        Variant::Float(f) => *f == 0.0,
+        _ => false,
+    }
+}
+
 #[inline]
 pub(crate) fn one_to_zero_index(v: &Var) -> Result<usize, Error> {
     let Variant::Int(index) = v.variant() else {
@@ -202,6 +219,17 @@ impl VM {
             let op = &opcodes[f.pc];
             f.pc += 1;
 
+            if let Some(coverage) = &exec_params.coverage {
+                let verbdef = a.verb_info.verbdef();
+                coverage.record(
+                    verbdef.location(),
+                    verbdef.uuid(),
+                    &a.verb_name,
+                    f.pc - 1,
+                    f.find_line_no(f.pc),
+                );
+            }
+
             match op {
                 Op::If(label) | Op::Eif(label) | Op::IfQues(label) | Op::While(label) => {
                     let cond = f.pop();
@@ -436,11 +464,13 @@ impl VM {
                     binary_var_op!(self, f, state, sub);
                 }
                 Op::Div => {
-                    // Explicit division by zero check to raise E_DIV.
-                    // Note that LambdaMOO consider 1/0.0 to be E_DIV, but Rust permits it, creating
-                    // `inf`. I'll follow Rust's lead here, unless it leads to problems.
-                    let divargs = f.peek_range(2);
-                    if let Variant::Int(0) = divargs[1].variant() {
+                    // Explicit division by zero check to raise E_DIV. As in LambdaMOO, that
+                    // includes 0.0, which Rust would otherwise turn into `inf`.
+                    if is_zero_divisor(f.peek_top()) {
+                        // Both operands come off the stack before the error is raised, as they do
+                        // when the operation itself fails in `binary_var_op!`.
+                        f.pop();
+                        f.pop();
                         return self.push_error(state, E_DIV);
                     };
                     binary_var_op!(self, f, state, div);
@@ -452,6 +482,12 @@ impl VM {
                     binary_var_op!(self, f, state, pow);
                 }
                 Op::Mod => {
+                    // As for division.
+                    if is_zero_divisor(f.peek_top()) {
+                        f.pop();
+                        f.pop();
+                        return self.push_error(state, E_DIV);
+                    };
                     binary_var_op!(self, f, state, modulus);
                 }
                 Op::And(label) => {
@@ -19,20 +19,25 @@ mod tests {
 
     use moor_values::model::PropFlag;
     use moor_values::model::VerbArgsSpec;
+    use moor_values::model::PREP_LIST;
     use moor_values::model::{BinaryType, VerbFlag};
     use moor_values::model::{WorldState, WorldStateSource};
     use moor_values::util::BitEnum;
-    use moor_values::var::Error::E_DIV;
+    use moor_values::var::Error::{
+        E_DIV, E_FLOAT, E_INVARG, E_INVIND, E_MAXREC, E_PERM, E_PROPNF, E_QUOTA, E_RECMOVE, E_TYPE,
+    };
     use moor_values::var::Objid;
+    use moor_values::var::Variant;
     use moor_values::var::{
-        v_bool, v_empty_list, v_err, v_int, v_list, v_none, v_obj, v_objid, v_str, Var,
+        v_bool, v_empty_list, v_err, v_float, v_int, v_list, v_listv, v_none, v_obj, v_objid,
+        v_str, Var,
     };
 
-    use moor_values::NOTHING;
     use moor_values::{AsByteBuffer, SYSTEM_OBJECT};
+    use moor_values::{AMBIGUOUS, FAILED_MATCH, NOTHING};
 
     use crate::tasks::sessions::NoopClientSession;
-    use crate::tasks::vm_test_utils::call_verb;
+    use crate::tasks::vm_test_utils::{call_verb, call_verb_with_coverage};
     use moor_compiler::compile;
     use moor_compiler::Names;
     use moor_compiler::Op;
@@ -365,6 +370,55 @@ mod tests {
         assert_eq!(result, v_int(666));
     }
 
+    #[test]
+    fn test_property_write_visible_to_called_verb() {
+        // A property write has to be seen by verbs called later in the same task, both on the
+        // object written to and on its descendants, even before anything commits.
+        let db = test_db_with_verbs(&[
+            (
+                "set_then_call",
+                &compile("before = {#1.x, #2.x}; #1.x = 5; return {before, this:read_x()};")
+                    .unwrap(),
+            ),
+            ("read_x", &compile("return {#1.x, #2.x};").unwrap()),
+        ]);
+        let mut state = db.new_world_state().unwrap();
+        let parent = state
+            .create_object(SYSTEM_OBJECT, NOTHING, SYSTEM_OBJECT, BitEnum::all())
+            .unwrap();
+        let child = state
+            .create_object(SYSTEM_OBJECT, parent, SYSTEM_OBJECT, BitEnum::all())
+            .unwrap();
+        assert_eq!((parent, child), (Objid(1), Objid(2)));
+        state
+            .define_property(
+                SYSTEM_OBJECT,
+                parent,
+                parent,
+                "x",
+                SYSTEM_OBJECT,
+                BitEnum::new_with(PropFlag::Read) | PropFlag::Write,
+                Some(v_int(1)),
+            )
+            .unwrap();
+        state.commit().unwrap();
+
+        let mut state = db.new_world_state().unwrap();
+        let session = Arc::new(NoopClientSession::new());
+        let result = call_verb(state.as_mut(), session, "set_then_call", vec![]);
+        assert_eq!(
+            result,
+            v_list(&[v_list(&[v_int(1), v_int(1)]), v_list(&[v_int(5), v_int(5)])])
+        );
+        state.rollback().unwrap();
+
+        // Nothing was committed, so a fresh transaction still sees the old value.
+        let mut state = db.new_world_state().unwrap();
+        let session = Arc::new(NoopClientSession::new());
+        let result = call_verb(state.as_mut(), session, "read_x", vec![]);
+        assert_eq!(result, v_list(&[v_int(1), v_int(1)]));
+    }
+
     fn world_with_test_program(program: &str) -> Box<dyn WorldState> {
         let binary = compile(program).unwrap();
         test_db_with_verb("test", &binary)
@@ -533,6 +587,212 @@ mod tests {
         assert_eq!(result, v_none());
     }
 
+    #[test]
+    fn test_verb_metrics() {
+        let inner = compile("x = 0; for i in [1..10] x = x + i; endfor return x;").unwrap();
+        let outer = compile(
+            r#"metrics = {};
+               for i in [1..3]
+                 this:inner();
+                 metrics = {@metrics, verb_metrics(this, "inner")};
+               endfor
+               return metrics;"#,
+        )
+        .unwrap();
+        let mut state = test_db_with_verbs(&[("inner", &inner), ("test", &outer)])
+            .new_world_state()
+            .unwrap();
+        let session = Arc::new(NoopClientSession::new());
+        let result = call_verb(state.as_mut(), session, "test", vec![]);
+
+        let Variant::List(metrics) = result.variant() else {
+            panic!("Expected list of metrics, got {:?}", result);
+        };
+        assert_eq!(metrics.len(), 3);
+        let mut last_ticks = 0;
+        for (i, metric) in metrics.iter().enumerate() {
+            let Variant::List(metric) = metric.variant() else {
+                panic!("Expected {{calls, ticks}}, got {:?}", metric);
+            };
+            assert_eq!(metric[0], v_int(i as i64 + 1));
+            let Variant::Int(ticks) = metric[1].variant() else {
+                panic!("Expected integer ticks, got {:?}", metric[1]);
+            };
+            assert!(*ticks > last_ticks);
+            last_ticks = *ticks;
+        }
+    }
+
+    #[test]
+    fn test_verb_cfg() {
+        let branchy = compile("if (args) x = 1; else x = 2; endif return x;").unwrap();
+        let test = compile(r#"return verb_cfg(this, "branchy");"#).unwrap();
+        let mut state = test_db_with_verbs(&[("branchy", &branchy), ("test", &test)])
+            .new_world_state()
+            .unwrap();
+        let session = Arc::new(NoopClientSession::new());
+        let result = call_verb(state.as_mut(), session, "test", vec![]);
+
+        let Variant::Str(dot) = result.variant() else {
+            panic!("Expected DOT string, got {:?}", result);
+        };
+        let dot = dot.as_str();
+        assert!(dot.starts_with("digraph cfg {"));
+        // Condition, then-branch, else-branch, the join, and the trailing Done.
+        assert_eq!(dot.lines().filter(|l| l.ends_with("\\l\"];")).count(), 5);
+        // The condition falls through to the then-branch or branches to the else; both reach the
+        // join.
+        assert_eq!(dot.matches(" -> ").count(), 4);
+        assert!(dot.contains("b0 -> b1;"));
+        assert!(dot.contains("b0 -> b2 [label=\"branch\"];"));
+        assert!(dot.contains("b1 -> b3 [label=\"jump\"];"));
+        assert!(dot.contains("b2 -> b3;"));
+    }
+
+    #[test]
+    fn test_coverage() {
+        let branchy = compile(
+            r#"x = 1;
+               if (args)
+                 y = 2;
+               else
+                 y = 3;
+               endif
+               return y;"#,
+        )
+        .unwrap();
+        let test = compile(r#"this:branchy(); return `coverage() ! ANY';"#).unwrap();
+        let mut state = test_db_with_verbs(&[("branchy", &branchy), ("test", &test)])
+            .new_world_state()
+            .unwrap();
+        let session = Arc::new(NoopClientSession::new());
+        let result = call_verb_with_coverage(state.as_mut(), session.clone(), "test", vec![]);
+
+        let Variant::List(verbs) = result.variant() else {
+            panic!("Expected list of verb coverage, got {:?}", result);
+        };
+        let branchy_coverage = verbs
+            .iter()
+            .find_map(|v| match v.variant() {
+                Variant::List(l) if l[1] == v_str("branchy") => Some(l.clone()),
+                _ => None,
+            })
+            .expect("No coverage for branchy");
+        assert_eq!(branchy_coverage[0], v_objid(SYSTEM_OBJECT));
+        // Only the else branch ran.
+        assert_eq!(
+            branchy_coverage[3],
+            v_list(&[v_int(1), v_int(2), v_int(5), v_int(7)])
+        );
+        let Variant::List(opcodes) = branchy_coverage[2].variant() else {
+            panic!("Expected list of opcodes, got {:?}", branchy_coverage[2]);
+        };
+        assert!(opcodes.len() < branchy.main_vector.len());
+
+        // Outside coverage mode there's nothing to report.
+        let result = call_verb(state.as_mut(), session, "test", vec![]);
+        assert_eq!(result, v_err(E_INVARG));
+    }
+
+    #[test]
+    fn test_move_calls_exitfunc_and_enterfunc() {
+        let accept = compile("return 1;").unwrap();
+        let enterfunc = compile(r#"#0.log = {@#0.log, {"enter", args[1].location}};"#).unwrap();
+        let exitfunc = compile(r#"#0.log = {@#0.log, {"exit", args[1].location}};"#).unwrap();
+        let test = compile(
+            r#"add_property(#0, "log", {}, {#0, "rw"});
+               o = create(#-1);
+               move(o, #0);
+               move(o, #-1);
+               return #0.log;"#,
+        )
+        .unwrap();
+        let mut state = test_db_with_verbs(&[
+            ("accept", &accept),
+            ("enterfunc", &enterfunc),
+            ("exitfunc", &exitfunc),
+            ("test", &test),
+        ])
+        .new_world_state()
+        .unwrap();
+        let session = Arc::new(NoopClientSession::new());
+        let result = call_verb(state.as_mut(), session, "test", vec![]);
+
+        // Both run after the object has been moved.
+        assert_eq!(
+            result,
+            v_list(&[
+                v_list(&[v_str("enter"), v_obj(0)]),
+                v_list(&[v_str("exit"), v_objid(NOTHING)]),
+            ])
+        );
+    }
+
+    /// An enterfunc and exitfunc that keep moving the object back and forth are stopped by the
+    /// stack depth limit, rather than running forever.
+    #[test]
+    fn test_move_callback_ping_pong() {
+        let accept = compile("return 1;").unwrap();
+        let enterfunc = compile("move(args[1], #-1);").unwrap();
+        let exitfunc = compile("move(args[1], #0);").unwrap();
+        let test = compile("o = create(#-1); return `move(o, #0) ! ANY';").unwrap();
+        let mut state = test_db_with_verbs(&[
+            ("accept", &accept),
+            ("enterfunc", &enterfunc),
+            ("exitfunc", &exitfunc),
+            ("test", &test),
+        ])
+        .new_world_state()
+        .unwrap();
+        let session = Arc::new(NoopClientSession::new());
+        let result = call_verb(state.as_mut(), session, "test", vec![]);
+        assert_eq!(result, v_err(E_MAXREC));
+    }
+
+    #[test]
+    fn test_caller_perms_across_frames() {
+        let inner = compile("return caller_perms();").unwrap();
+        let outer = compile(
+            r#"o = create(#-1);
+               before = this:inner();
+               set_task_perms(o);
+               after = this:inner();
+               return {before, after, caller_perms(), `set_task_perms(#0) ! ANY'};"#,
+        )
+        .unwrap();
+        let mut state = test_db_with_verbs(&[("inner", &inner), ("test", &outer)])
+            .new_world_state()
+            .unwrap();
+        let session = Arc::new(NoopClientSession::new());
+        let result = call_verb(state.as_mut(), session, "test", vec![]);
+
+        // The inner verb sees the outer frame's permissions, including after they've been
+        // changed, while the outer verb has no caller.
+        assert_eq!(
+            result,
+            v_list(&[v_obj(0), v_obj(1), v_objid(NOTHING), v_err(E_PERM)])
+        );
+    }
+
+    #[test]
+    fn test_callers_nesting_depth() {
+        let inner = compile("return {length(callers()), callers()[1][2]};").unwrap();
+        let middle = compile("return {length(callers()), @this:inner()};").unwrap();
+        let outer = compile("return {length(callers()), @this:middle()};").unwrap();
+        let mut state =
+            test_db_with_verbs(&[("inner", &inner), ("middle", &middle), ("test", &outer)])
+                .new_world_state()
+                .unwrap();
+        let session = Arc::new(NoopClientSession::new());
+        let result = call_verb(state.as_mut(), session, "test", vec![]);
+
+        // Each frame sees one caller per frame beneath it, innermost first.
+        assert_eq!(
+            result,
+            v_list(&[v_int(0), v_int(1), v_int(2), v_str("middle")])
+        );
+    }
+
     #[test_case("return 1;", v_int(1); "simple return")]
     #[test_case(
         r#"rest = "me:words"; rest[1..0] = ""; return rest;"#,
@@ -543,6 +803,11 @@ mod tests {
     #[test_case(r#"return (!1 || 1);"#, v_int(1); "not/or precedence")]
     #[test_case(r#"return {1, eval("return $test;")};"#, 
             v_list(&[v_int(1), v_list(&[v_bool(true), v_int(1)])]); "eval builtin")]
+    #[test_case(r#"return eval("return 1+1;");"#, v_list(&[v_int(1), v_int(2)]); "eval arithmetic")]
+    #[test_case(r#"r = eval("return 1 +;"); return {r[1], typeof(r[2]) == STR};"#,
+            v_list(&[v_int(0), v_int(1)]); "eval compile error")]
+    #[test_case(r#"set_task_perms(create(#-1)); return eval("return `set_player_flag(#0, 0) ! ANY';");"#,
+            v_list(&[v_int(1), v_err(E_PERM)]); "eval runs with caller perms")]
     #[test_case(
         r#"string="you";
                          i = index("abcdefghijklmnopqrstuvwxyz", string[1]);
@@ -592,6 +857,8 @@ mod tests {
         v_int(666); "try finally")]
     #[test_case("try a; except e (E_VARNF) return 666; endtry return 333;", 
         v_int(666); "try except")]
+    #[test_case("try 1/0; except (E_PERM) return 1; except (E_DIV) return 2; endtry return 3;",
+        v_int(2); "try except second arm")]
     #[test_case("return `1/0 ! ANY';", v_err(E_DIV); "catch expr 1")]
     #[test_case("return {`x ! e_varnf => 666', `321 ! e_verbnf => 123'};",
         v_list(&[v_int(666), v_int(321)]); "catch expr 2")]
@@ -623,6 +890,386 @@ mod tests {
         v_list(&[v_int(4), v_int(10)]); "for list loop")]
     #[test_case(r#"if (E_INVARG == (vi = `verb_info(#-1, "blerg") ! ANY')) return 666; endif return 333;"#, 
         v_int(666); "verb_info invalid object error")]
+    #[test_case("return floatstr(3.14159, 0);", v_str("3"); "floatstr precision 0")]
+    #[test_case("return floatstr(3.14159, 2);", v_str("3.14"); "floatstr precision 2")]
+    #[test_case("return floatstr(1234.5, 2, 1);", v_str("1.23e+03"); "floatstr scientific")]
+    #[test_case("return floatstr(0.0015, 1, 1);", v_str("1.5e-03"); "floatstr scientific negative exponent")]
+    #[test_case("return `floatstr(1.5, -1) ! ANY';", v_err(E_INVARG); "floatstr negative precision")]
+    #[test_case("return `floatstr(1, 2) ! ANY';", v_err(E_TYPE); "floatstr non-float")]
+    #[test_case("return `1.0 / 0.0 ! ANY';", v_err(E_DIV); "float division by zero")]
+    #[test_case("return `1 % 0 ! ANY';", v_err(E_DIV); "modulus by zero")]
+    #[test_case("return `1e308 * 10.0 ! ANY';", v_err(E_FLOAT); "float overflow")]
+    #[test_case("return `10.0 ^ 400 ! ANY';", v_err(E_FLOAT); "float exponent overflow")]
+    #[test_case("return 1e308 * 1.0;", v_float(1e308); "large float")]
+    #[test_case("return toliteral({E_NONE, E_TYPE, E_DIV, E_PERM, E_PROPNF, E_VERBNF, E_VARNF, E_INVIND, E_RECMOVE, E_MAXREC, E_RANGE, E_ARGS, E_NACC, E_INVARG, E_QUOTA, E_FLOAT});",
+        v_str("{E_NONE, E_TYPE, E_DIV, E_PERM, E_PROPNF, E_VERBNF, E_VARNF, E_INVIND, E_RECMOVE, E_MAXREC, E_RANGE, E_ARGS, E_NACC, E_INVARG, E_QUOTA, E_FLOAT}");
+        "toliteral error codes")]
+    #[test_case(r#"return toliteral({1, "a \"b\"", #3, E_PERM, {}});"#,
+        v_str(r#"{1, "a \"b\"", #3, E_PERM, {}}"#); "toliteral quoting")]
+    #[test_case(r#"x = {1, 2.5, "say \"hi\" \\ there", #-1, E_PERM, {{}, "nested"}};
+                   r = eval("return " + toliteral(x) + ";");
+                   return r[2];"#,
+        v_list(&[
+            v_int(1),
+            v_float(2.5),
+            v_str(r#"say "hi" \ there"#),
+            v_obj(-1),
+            v_err(E_PERM),
+            v_list(&[v_empty_list(), v_str("nested")]),
+        ]); "toliteral eval round trip")]
+    #[test_case("return value_bytes({1, 2, 3, 4, 5, 6, 7, 8, 9, 10}) > value_bytes({1});",
+        v_int(1); "value_bytes larger list")]
+    #[test_case("return object_bytes(#0) > 0;", v_int(1); "object_bytes valid object")]
+    #[test_case("return `object_bytes(#-1) ! ANY';", v_err(E_INVARG); "object_bytes invalid object")]
+    #[test_case(r#"o = create(#-1);
+        add_property(o, "ownership_quota", 2, {o, "r"});
+        a = create(#-1, o);
+        b = create(#-1, o);
+        r = `create(#-1, o) ! ANY';
+        return {valid(a), valid(b), r, o.ownership_quota};"#,
+        v_list(&[v_int(1), v_int(1), v_err(E_QUOTA), v_int(0)]); "create ownership quota")]
+    #[test_case(r#"p = create(#-1);
+        a = create(p);
+        b = create(a);
+        c = create(a);
+        recycle(a);
+        return {valid(a), parent(b) == p, parent(c) == p,
+                b in children(p) && c in children(p) && length(children(p)) == 2};"#,
+        v_list(&[v_int(0), v_int(1), v_int(1), v_int(1)]); "recycle reparents children")]
+    #[test_case(r##"o = create(#-1);
+        add_verb(o, {#0, "rxd", "recycle"}, {"this", "none", "this"});
+        set_verb_code(o, "recycle", {"#0.test = 42;"});
+        recycle(o);
+        return {valid(o), #0.test};"##,
+        v_list(&[v_int(0), v_int(42)]); "recycle calls recycle verb")]
+    #[test_case(r##"r = create(#-1);
+        add_verb(r, {#0, "rxd", "_recycle"}, {"this", "none", "this"});
+        set_verb_code(r, "_recycle", {"#0.test = args[1];"});
+        add_property(#0, "recycler", r, {#0, "r"});
+        o = create(#-1);
+        recycle(o);
+        return {valid(o), #0.test == o};"##,
+        v_list(&[v_int(1), v_int(1)]); "recycle hands off to recycler")]
+    #[test_case(r##"r = create(#-1);
+        add_verb(r, {#0, "rxd", "_recycle"}, {"this", "none", "this"});
+        set_verb_code(r, "_recycle", {"#0.test = args[1]; recycle(args[1]);"});
+        add_property(#0, "recycler", r, {#0, "r"});
+        o = create(#-1);
+        recycle(o);
+        return {valid(o), #0.test == o};"##,
+        v_list(&[v_int(0), v_int(1)]); "recycler can recycle for real")]
+    #[test_case(r#"u = create(#-1);
+        theirs = create(#-1);
+        mine = create(#-1, u);
+        o = create(#-1);
+        add_verb(o, {u, "rxd", "try_recycle"}, {"this", "none", "this"});
+        set_verb_code(o, "try_recycle", {"r = `recycle(args[1]) ! ANY';", "recycle(args[2]);", "return r;"});
+        r = o:try_recycle(theirs, mine);
+        return {r, valid(theirs), valid(mine)};"#,
+        v_list(&[v_err(E_PERM), v_int(1), v_int(0)]); "recycle requires owner or wizard")]
+    #[test_case("return `recycle(#-1) ! ANY';", v_err(E_INVARG); "recycle invalid object")]
+    #[test_case(r#"o = create(#-1);
+        add_property(o, "ownership_quota", 1, {o, "r"});
+        a = create(#-1, o);
+        r = `create(#-1, o) ! ANY';
+        recycle(a);
+        return {r, o.ownership_quota, valid(create(#-1, o))};"#,
+        v_list(&[v_err(E_QUOTA), v_int(1), v_int(1)]); "recycle refunds ownership quota")]
+    #[test_case(r#"a = create(#-1);
+        b = create(#-1);
+        c = create(#-1);
+        set_player_flag(a, 1);
+        set_player_flag(c, 1);
+        p = players();
+        return {length(p), (#0 in p) != 0, (a in p) != 0, b in p, (c in p) != 0};"#,
+        v_list(&[v_int(3), v_int(1), v_int(1), v_int(0), v_int(1)]); "players")]
+    #[test_case(r#"o = create(#-1);
+        r = {is_player(o)};
+        set_player_flag(o, "yes");
+        r = {@r, is_player(o)};
+        set_player_flag(o, 0);
+        return {@r, is_player(o), `set_player_flag(#-1, 1) ! ANY', `set_player_flag(1, 1) ! ANY'};"#,
+        v_list(&[v_int(0), v_int(1), v_int(0), v_err(E_INVARG), v_err(E_TYPE)]); "set_player_flag")]
+    #[test_case(r#"o = create(#-1);
+        u = create(#-1);
+        set_player_flag(u, 1);
+        set_task_perms(u);
+        return {`set_player_flag(o, 1) ! ANY', is_player(o), `set_player_flag(u, 0) ! ANY', is_player(u)};"#,
+        v_list(&[v_err(E_PERM), v_int(0), v_err(E_PERM), v_int(1)]); "set_player_flag requires wizard")]
+    #[test_case(r#"o = create(#-1);
+        flags = {"player", "programmer", "wizard", "r", "w", "f"};
+        for f in (flags)
+            set_player_flag(o, f, 1);
+        endfor
+        r = {{is_player(o), o.programmer, o.wizard, o.r, o.w, o.f}};
+        for f in (flags)
+            set_player_flag(o, f, 0);
+        endfor
+        r = {@r, {is_player(o), o.programmer, o.wizard, o.r, o.w, o.f}};
+        return {@r, `set_player_flag(o, "bogus", 1) ! ANY', `set_player_flag(o, 1, 1) ! ANY'};"#,
+        v_list(&[
+            v_listv(vec![v_int(1); 6]),
+            v_listv(vec![v_int(0); 6]),
+            v_err(E_INVARG),
+            v_err(E_TYPE),
+        ]); "set_player_flag named flags")]
+    #[test_case(r#"u = create(#-1);
+        o = create(#-1, u);
+        x = create(#-1);
+        set_task_perms(u);
+        return {`set_player_flag(o, "r", 1) ! ANY', o.r, `set_player_flag(x, "f", 1) ! ANY',
+                `set_player_flag(o, "wizard", 1) ! ANY', `set_player_flag(u, "programmer", 1) ! ANY',
+                o.wizard, u.programmer};"#,
+        v_list(&[v_none(), v_int(1), v_err(E_PERM), v_err(E_PERM), v_err(E_PERM), v_int(0), v_int(0)]);
+        "set_player_flag base flags need owner, privileged flags need wizard")]
+    #[test_case(r#"u = create(#-1);
+        set_player_flag(u, 1);
+        set_player_flag(u, "programmer", 1);
+        set_player_flag(u, "wizard", 1);
+        set_task_perms(u);
+        set_player_flag(u, "wizard", 0);
+        return {u.wizard, u.programmer, `set_player_flag(u, "wizard", 1) ! ANY', u.wizard};"#,
+        v_list(&[v_int(0), v_int(1), v_err(E_PERM), v_int(0)]); "set_player_flag wizard dropping own bit")]
+    #[test_case(r#"return {notify(#0, "hi"), notify(#0, "<b>hi</b>", "text/html"), notify(#0, "hi", 1), `notify(#0, "hi", "image/png") ! ANY'};"#,
+        v_list(&[v_int(1), v_int(1), v_int(1), v_err(E_INVARG)]); "notify content type")]
+    #[test_case(r##"o = create(#-1);
+        set_task_perms(o);
+        return {`notify_oob(#0, "#$#mcp version: 2.1 to: 2.1") ! ANY', notify_oob(o, "#$#mcp version: 2.1 to: 2.1")};"##,
+        v_list(&[v_err(E_PERM), v_int(1)]); "notify_oob")]
+    #[test_case(r#"a = create(#-1);
+        b = create(#-1);
+        m = max_object();
+        recycle(b);
+        return {m == b, max_object() == b};"#,
+        v_list(&[v_int(1), v_int(1)]); "max_object")]
+    #[test_case(r#"a = create(#-1);
+        b = create(#-1);
+        c = create(#-1);
+        recycle(c);
+        recycle(b);
+        m = max_object();
+        reset_max_object();
+        return {m == c, max_object() == a, create(#-1) == b};"#,
+        v_list(&[v_int(1), v_int(1), v_int(1)]); "reset_max_object reclaims trailing numbers")]
+    #[test_case(r#"a = create(#-1);
+        b = create(a);
+        move(b, a);
+        r = verify_database();
+        set_task_perms(b);
+        return {r, `verify_database() ! ANY'};"#,
+        v_list(&[v_list(&[]), v_err(E_PERM)]); "verify_database")]
+    #[test_case(r#"a = create(#-1);
+        b = create(a);
+        c = create(b);
+        r = {`chparent(a, c) ! ANY', `chparent(a, b) ! ANY', `chparent(a, a) ! ANY'};
+        add_property(c, "x", 1, {c, "r"});
+        d = create(#-1);
+        add_property(d, "x", 2, {d, "r"});
+        return {@r, `chparent(b, d) ! ANY', parent(a), parent(b) == a, parent(c) == b};"#,
+        v_list(&[v_err(E_RECMOVE), v_err(E_RECMOVE), v_err(E_RECMOVE), v_err(E_INVARG),
+            v_objid(NOTHING), v_int(1), v_int(1)]); "chparent rejects cycles and property clashes")]
+    #[test_case(r#"a = create(#-1);
+        b = create(#-1);
+        add_property(b, "x", 2, {b, "r"});
+        add_verb(b, {#0, "rxd", "hi"}, {"this", "none", "this"});
+        set_verb_code(b, "hi", {"return 5;"});
+        o = create(a);
+        chparents(o, {a, b});
+        c = create(#-1);
+        add_property(c, "x", 3, {c, "r"});
+        r = {parents(o) == {a, b}, parent(o) == a, o.x, o:hi()};
+        return {@r, `chparents(o, {b, c}) ! ANY', `chparents(o, {b, b}) ! ANY', parents(o) == {a, b}};"#,
+        v_list(&[v_int(1), v_int(1), v_int(2), v_int(5), v_err(E_INVARG), v_err(E_INVARG), v_int(1)]);
+        "chparents resolves through later parents")]
+    #[test_case(r#"root = create(#-1);
+        add_property(root, "x", 1, {root, "rw"});
+        add_verb(root, {#0, "rxd", "hi"}, {"this", "none", "this"});
+        set_verb_code(root, "hi", {"return this.x;"});
+        left = create(root);
+        right = create(root);
+        o = create(left);
+        chparents(o, {left, right});
+        o.x = 7;
+        r = {o.x, o:hi(), left.x, right.x, `add_property(o, "x", 2, {o, "r"}) ! ANY'};
+        clear_property(o, "x");
+        return {@r, o.x};"#,
+        v_list(&[v_int(7), v_int(7), v_int(1), v_int(1), v_err(E_INVARG), v_int(1)]);
+        "chparents inherits a diamond's shared ancestor once")]
+    #[test_case(r#"root = create(#-1);
+        left = create(root);
+        right = create(root);
+        o = create(left);
+        chparents(o, {left, right});
+        other = create(#-1);
+        return {isa(o, root), isa(o, right), isa(o, o), isa(o, other), isa(root, o), `isa(#-1, root) ! ANY'};"#,
+        v_list(&[v_int(1), v_int(1), v_int(1), v_int(0), v_int(0), v_err(E_INVARG)]);
+        "isa walks every parent of a diamond")]
+    #[test_case(r#"s = "naïve café";
+        return {length(s), byte_length(s), s[3], s[$], s[3..5], index(s, "v"), rindex(s, "é"),
+                s[index(s, "café")..$], length(s[1..$]) == length(s)};"#,
+        v_list(&[v_int(10), v_int(12), v_str("ï"), v_str("é"), v_str("ïve"), v_int(4), v_int(10),
+            v_str("café"), v_int(1)]);
+        "multibyte strings are measured and indexed by character")]
+    #[test_case(r#"h = crypt("sekrit", "Xy");
+        return {password_verify("sekrit", h), password_verify("sekrix", h), password_verify("", h),
+                `password_verify("sekrit", 5) ! ANY'};"#,
+        v_list(&[v_int(1), v_int(0), v_int(0), v_err(E_TYPE)]);
+        "password_verify checks against a crypt hash")]
+    #[test_case(r#"return {stable_hash("abc"), stable_hash({1, "two", #3}), stable_hash(5),
+                stable_hash("ABC") != stable_hash("abc"), stable_hash(5) != stable_hash("5")};"#,
+        v_list(&[
+            v_str("6cc43f858fbb763301637b5af970e2a46b46f461f27e5a0f41e009c59b827b25"),
+            v_str("d4ad7784cf809e5e0eab035886fcad71dc8f23fe1e9aad0d739b84ba9a94c0c5"),
+            v_str("ef2d127de37b942baad06145e54b0c619a1f22327b2ebbcfbec78f5564afe39d"),
+            v_int(1),
+            v_int(1),
+        ]);
+        "stable_hash is the sha-256 of the literal")]
+    #[test_case(r#"o = create(#-1);
+        add_property(o, "x", 1, {o, "r"});
+        p = create(#-1);
+        p.programmer = 1;
+        q = create(#-1, p);
+        r = {property_info(o, "x")[1] == o};
+        set_task_perms(p);
+        r = {@r, `add_verb(q, {#0, "rx", "theirs"}, {"this", "none", "this"}) ! ANY'};
+        add_verb(q, {p, "rx", "mine"}, {"this", "none", "this"});
+        return {@r, verb_info(q, "mine")[1] == p};"#,
+        v_list(&[v_int(1), v_err(E_PERM), v_int(1)]);
+        "property and verb owners come from their info")]
+    #[test_case(r#"o = create(#-1);
+        p = create(#-1);
+        gone = create(#-1);
+        recycle(gone);
+        add_property(o, "secret", 1, {o, ""});
+        set_task_perms(p);
+        r = {};
+        for t in ({{o, "secret"}, {o, "nope"}, {gone, "secret"}})
+          {o, n} = t;
+          r = {@r, {`o.(n) ! ANY', `o.(n) = 2 ! ANY', `property_info(o, n) ! ANY',
+                    `set_property_info(o, n, {p, "r"}) ! ANY', `is_clear_property(o, n) ! ANY',
+                    `clear_property(o, n) ! ANY', `delete_property(o, n) ! ANY'}};
+        endfor
+        return r;"#,
+        v_list(&[v_listv(vec![v_err(E_PERM); 7]), v_listv(vec![v_err(E_PROPNF); 7]), v_listv(vec![v_err(E_INVIND); 7])]);
+        "property errors: object, then existence, then permission")]
+    #[test_case(r#"o = create(#-1);
+        add_verb(o, {#0, "rxd", "x"}, {"this", "none", "this"});
+        r = {};
+        for i in [0..14]
+          set_verb_args(o, "x", {"this", tostr(i), "this"});
+          prep = verb_args(o, "x")[2];
+          set_verb_args(o, "x", {"any", prep, "any"});
+          r = {@r, verb_args(o, "x")[2] == prep ? prep | {"mismatch", prep}};
+        endfor
+        return r;"#,
+        v_listv(PREP_LIST.iter().map(|p| v_str(p)).collect());
+        "verb_args prepositions round trip")]
+    #[test_case(r#"room = create(#-1);
+        lamp = create(#-1);
+        ball = create(#-1);
+        box = create(#-1);
+        lamp.name = "lamp";
+        ball.name = "red ball";
+        box.name = "red box";
+        move(lamp, room);
+        move(ball, room);
+        move(box, room);
+        return {match_object("lamp", room) == lamp, match_object("red b", room),
+                match_object("table", room), match_object("red ba", room) == ball};"#,
+        v_list(&[v_int(1), v_objid(AMBIGUOUS), v_objid(FAILED_MATCH), v_int(1)]);
+        "match_object from a location")]
+    #[test_case(r#"room = create(#-1);
+        lamp = create(#-1);
+        lamp.name = "lamp";
+        move(lamp, room);
+        move(#0, room);
+        return {match_object("lamp") == lamp, match_object("me"), match_object("here") == room,
+                match_object("lamp", create(#-1))};"#,
+        v_list(&[v_int(1), v_objid(SYSTEM_OBJECT), v_int(1), v_objid(FAILED_MATCH)]);
+        "match_object from the player")]
+    #[test_case(r#"room = create(#-1);
+        add_property(#0, "failed_match", #-30, {#0, "r"});
+        add_property(#0, "ambiguous_match", #-20, {#0, "r"});
+        for name in ({"red ball", "red box"})
+            o = create(#-1);
+            o.name = name;
+            move(o, room);
+        endfor
+        return {match_object("lamp", room), match_object("red", room)};"#,
+        v_list(&[v_objid(Objid(-30)), v_objid(Objid(-20))]);
+        "match_object uses the core's match constants")]
+    #[test_case(r#"a = create(#-1);
+        b = create(#-1);
+        move(b, a);
+        return {`move(a, b) ! ANY', `move(a, a) ! ANY', a.location, b.location == a};"#,
+        v_list(&[v_err(E_RECMOVE), v_err(E_RECMOVE), v_objid(NOTHING), v_int(1)]);
+        "move into own contents")]
+    #[test_case(r#"last = ticks_left();
+        for i in [1..50]
+            now = ticks_left();
+            if (now >= last)
+                return {last, now};
+            endif
+            last = now;
+        endfor
+        return {last > 0 && last < 90000, seconds_left() <= 5};"#,
+        v_list(&[v_int(1), v_int(1)]); "ticks_left decreases within a loop")]
+    #[test_case(r#"a = create(#-1);
+        b = create(a);
+        move(b, a);
+        r = {repair_database(), repair_database(1)};
+        set_task_perms(b);
+        return {r, `repair_database() ! ANY'};"#,
+        v_list(&[v_list(&[v_list(&[]), v_list(&[])]), v_err(E_PERM)]); "repair_database")]
+    #[test_case(r#"o = create(#-1);
+        set_task_perms(o);
+        return `reset_max_object() ! ANY';"#,
+        v_err(E_PERM); "reset_max_object requires wizard")]
+    #[test_case(r#"o = create(#-1);
+        add_property(o, "x", 1, {player, ""});
+        outer = savepoint();
+        o.x = 2;
+        inner = savepoint();
+        o.x = 3;
+        made = create(#-1);
+        rollback_to(outer);
+        return {o.x, valid(made), `rollback_to(inner) ! ANY'};"#,
+        v_list(&[v_int(1), v_int(0), v_err(E_INVARG)]); "rollback_to undoes changes since savepoint")]
+    #[test_case(r#"o = create(#-1);
+        set_task_perms(o);
+        return `savepoint() ! ANY';"#,
+        v_err(E_PERM); "savepoint requires wizard")]
+    #[test_case(r#"a = create(#-1);
+        b = create(#-1);
+        c = create(b);
+        recycle(a);
+        n = renumber(b);
+        return {n == a, valid(b), parent(c) == n, children(n) == {c}, renumber(n) == n};"#,
+        v_list(&[v_int(1), v_int(0), v_int(1), v_int(1), v_int(1)]); "renumber to lowest free")]
+    #[test_case(r#"a = create(#-1);
+        b = create(#-1);
+        recycle(a);
+        set_task_perms(b);
+        return {`renumber(b) ! ANY', `renumber(#-1) ! ANY'};"#,
+        v_list(&[v_err(E_PERM), v_err(E_INVARG)]); "renumber requires wizard")]
+    #[test_case(r#"p = create(#-1);
+        o = create_with_id(#500, p);
+        return {o == #500, valid(#500), parent(o) == p, max_object() == o, create(#-1) == #501};"#,
+        v_list(&[v_int(1), v_int(1), v_int(1), v_int(1), v_int(1)]); "create_with_id at a free id")]
+    #[test_case(r#"p = create(#-1);
+        o = create(#-1);
+        r = {`create_with_id(p, #-1) ! ANY', `create_with_id(#-5, #-1) ! ANY', parent(p)};
+        set_task_perms(o);
+        return {@r, `create_with_id(#600, #-1) ! ANY', valid(#600)};"#,
+        v_list(&[v_err(E_INVARG), v_err(E_INVARG), v_objid(NOTHING), v_err(E_PERM), v_int(0)]);
+        "create_with_id rejects occupied ids and non-wizards")]
+    #[test_case(r#"return {`suspend(-1) ! ANY', `suspend("1") ! ANY', `suspend(1, 2) ! ANY'};"#,
+        v_list(&[v_err(E_INVARG), v_err(E_TYPE), v_err(E_INVARG)]); "suspend bad arguments")]
+    #[test_case(r##"o = create(#-1);
+        set_task_perms(o);
+        return {`read(o) ! ANY', `read("#0") ! ANY'};"##,
+        v_list(&[v_err(E_PERM), v_err(E_TYPE)]); "read from another player requires wizard")]
     fn test_run(program: &str, expected_result: Var) {
         let mut state = world_with_test_program(program);
         let session = Arc::new(NoopClientSession::new());
@@ -325,11 +325,28 @@ impl VM {
     ) -> ExecutionResult {
         // Walk activation stack from bottom to top, tossing frames as we go.
         while let Some(a) = state.stack.last_mut() {
-            while a.frame.valstack.pop().is_some() {
-                // Check the handler stack to see if we've hit a finally or catch handler that
-                // was registered for this position in the value stack.
-                let Some(handler) = a.frame.pop_applicable_handler() else {
-                    continue;
+            loop {
+                // A catch registered right at the current position applies before anything else
+                // is popped; this is where a frame we've unwound back into from a verb or builtin
+                // call will be sitting.
+                let handler = match a.frame.handler_stack.last() {
+                    Some(h)
+                        if h.valstack_pos == a.frame.valstack.len()
+                            && matches!(h.handler_type, HandlerType::Catch(_)) =>
+                    {
+                        a.frame.handler_stack.pop().expect("handler vanished")
+                    }
+                    _ => {
+                        if a.frame.valstack.pop().is_none() {
+                            break;
+                        }
+                        // Check the handler stack to see if we've hit a finally or catch handler
+                        // that was registered for this position in the value stack.
+                        let Some(handler) = a.frame.pop_applicable_handler() else {
+                            continue;
+                        };
+                        handler
+                    }
                 };
 
                 match handler.handler_type {
@@ -345,26 +362,31 @@ impl VM {
                         trace!(jump = ?label, ?why, "matched finally handler");
                         return ExecutionResult::More;
                     }
-                    HandlerType::Catch(_) => {
-                        let FinallyReason::Raise { code, .. } = &why else {
-                            continue;
-                        };
+                    HandlerType::Catch(num_excepts) => {
+                        // Each arm left a label on the handler stack and its error codes on the
+                        // value stack. Take all of them off, whether or not this is an error
+                        // we're unwinding for. They come off last arm first.
+                        let mut arms = Vec::with_capacity(num_excepts);
+                        for _ in 0..num_excepts {
+                            let Some(HandlerType::CatchLabel(label)) =
+                                a.frame.handler_stack.pop().map(|h| h.handler_type)
+                            else {
+                                panic!("Expected CatchLabel");
+                            };
+                            arms.push((label, a.frame.pop()));
+                        }
 
-                        let Some(handler) = a.frame.pop_applicable_handler() else {
+                        let FinallyReason::Raise { code, .. } = &why else {
                             continue;
                         };
-                        let HandlerType::CatchLabel(pushed_label) = &handler.handler_type else {
-                            panic!("Expected CatchLabel");
-                        };
 
-                        // The value at the top of the stack could be the error codes list.
-                        let v = a.frame.pop();
-                        let found = match v.variant() {
+                        // The first arm (in source order) whose codes match gets it.
+                        let matched = arms.iter().rev().find(|(_, codes)| match codes.variant() {
                             Variant::List(error_codes) => error_codes.contains(&v_err(*code)),
                             _ => true,
-                        };
-                        if found {
-                            a.frame.jump(pushed_label);
+                        });
+                        if let Some((label, _)) = matched {
+                            a.frame.jump(label);
                             a.frame.push(v_list(&[v_err(*code)]));
                             return ExecutionResult::More;
                         }
@@ -18,14 +18,22 @@ mod test {
     use moor_db::loader::LoaderInterface;
     use moor_db::odb::RelBoxWorldState;
     use moor_db::Database;
-    use moor_kernel::textdump::{make_textdump, read_textdump, textdump_load, TextdumpReader};
-    use moor_values::model::CommitResult;
+    use moor_kernel::tasks::sessions::NoopClientSession;
+    use moor_kernel::tasks::vm_test_utils::call_verb;
+    use moor_kernel::textdump::{
+        make_textdump, read_textdump, textdump_load, textdump_save, textdump_save_delta,
+        TextdumpPosition, TextdumpReader, TextdumpReaderError, TextdumpVersion, VerbCompilation,
+    };
+    use moor_values::model::BinaryType;
+    use moor_values::model::ObjSet;
     use moor_values::model::VerbArgsSpec;
     use moor_values::model::VerbFlag;
     use moor_values::model::WorldStateSource;
+    use moor_values::model::{CommitResult, ObjAttrs};
     use moor_values::model::{HasUuid, Named};
-    use moor_values::util::SliceRef;
-    use moor_values::var::Objid;
+    use moor_values::util::{BitEnum, SliceRef};
+    use moor_values::var::Error::E_INVARG;
+    use moor_values::var::{v_err, v_objid, v_str, Objid};
     use moor_values::{AsByteBuffer, SYSTEM_OBJECT};
     use std::collections::BTreeSet;
     use std::fs::File;
@@ -42,7 +50,8 @@ mod test {
     }
 
     fn load_textdump_file(tx: Rc<dyn LoaderInterface>, path: &str) {
-        textdump_load(tx.clone(), PathBuf::from(path)).expect("Could not load textdump");
+        textdump_load(tx.clone(), PathBuf::from(path), VerbCompilation::Eager)
+            .expect("Could not load textdump");
         assert_eq!(tx.commit().unwrap(), CommitResult::Success);
     }
 
@@ -177,7 +186,7 @@ mod test {
         let (db, _) = RelBoxWorldState::open(None, 1 << 30);
         let db = Arc::new(db);
         let tx = db.clone().loader_client().unwrap();
-        textdump_load(tx.clone(), minimal_db).unwrap();
+        textdump_load(tx.clone(), minimal_db, VerbCompilation::Eager).unwrap();
         assert_eq!(tx.commit().unwrap(), CommitResult::Success);
 
         // Check a few things in a new transaction.
@@ -231,6 +240,347 @@ mod test {
         assert_diff(&input, &output, "", 0);
     }
 
+    fn load_textdump_string(textdump: &str) -> Arc<RelBoxWorldState> {
+        let (db, _) = RelBoxWorldState::open(None, 1 << 30);
+        let db = Arc::new(db);
+        let tx = db.clone().loader_client().unwrap();
+        read_textdump(
+            tx.clone(),
+            BufReader::new(textdump.as_bytes()),
+            VerbCompilation::Eager,
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(tx.commit().unwrap(), CommitResult::Success);
+        db
+    }
+
+    /// The parents an object has beyond its first, along with what it inherits through them, go
+    /// in a section after the tasks; here a diamond, whose shared root is inherited once. The
+    /// object records are written as if each object only had its first parent, so a loader that
+    /// stops before that section still gets a consistent world.
+    #[test]
+    fn extra_parents_survive_save_and_load() {
+        let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let minimal_db = manifest_dir.join("tests/Minimal.db");
+
+        let (db, _) = RelBoxWorldState::open(None, 1 << 30);
+        let db = Arc::new(db);
+        load_textdump_file(
+            db.clone().loader_client().unwrap(),
+            minimal_db.to_str().unwrap(),
+        );
+
+        // root <- {left, right} <- thing
+        let (root, left, right, thing) = (Objid(4), Objid(5), Objid(6), Objid(7));
+        let tx = db.clone().loader_client().unwrap();
+        for (obj, name, parent) in [
+            (root, "root", Objid(1)),
+            (left, "left", root),
+            (right, "right", root),
+            (thing, "thing", left),
+        ] {
+            tx.create_object(Some(obj), ObjAttrs::new().name(name))
+                .unwrap();
+            tx.set_object_parent(obj, parent).unwrap();
+        }
+        tx.define_property(
+            root,
+            root,
+            "shared",
+            Objid(3),
+            BitEnum::new(),
+            Some(v_str("root")),
+            None,
+        )
+        .unwrap();
+        tx.define_property(
+            right,
+            right,
+            "colour",
+            Objid(3),
+            BitEnum::new(),
+            Some(v_str("blue")),
+            None,
+        )
+        .unwrap();
+        tx.set_object_parents(thing, ObjSet::from(&[left, right]))
+            .unwrap();
+        tx.set_property(
+            thing,
+            "colour",
+            Objid(3),
+            BitEnum::new(),
+            Some(v_str("red")),
+        )
+        .unwrap();
+        tx.set_property(
+            thing,
+            "shared",
+            Objid(3),
+            BitEnum::new(),
+            Some(v_str("thing")),
+        )
+        .unwrap();
+        assert_eq!(tx.commit().unwrap(), CommitResult::Success);
+
+        let value_of = |db: &Arc<RelBoxWorldState>, name: &str| {
+            let tx = db.clone().loader_client().unwrap();
+            let prop = tx
+                .get_object_properties(thing)
+                .unwrap()
+                .find_first_named(name)?;
+            tx.get_property_value(thing, prop.uuid()).unwrap()
+        };
+        let parents_of = |db: &Arc<RelBoxWorldState>, obj: Objid| {
+            let tx = db.clone().loader_client().unwrap();
+            tx.get_object_parents(obj)
+                .unwrap()
+                .iter()
+                .collect::<Vec<_>>()
+        };
+
+        let version = "** moor Textdump DB Version 1 **";
+        let textdump = write_textdump(db.clone(), version);
+
+        // The streaming writer has to hold the section back until the end, but writes the same.
+        let saved = tempfile::NamedTempFile::new().unwrap();
+        let tx = db.loader_client().unwrap();
+        textdump_save(tx.clone(), saved.path().to_path_buf(), Some(version)).unwrap();
+        assert_eq!(tx.commit().unwrap(), CommitResult::Success);
+        assert_diff(
+            &textdump,
+            &std::fs::read_to_string(saved.path()).unwrap(),
+            "",
+            0,
+        );
+
+        let reloaded = load_textdump_string(&textdump);
+        assert_eq!(parents_of(&reloaded, thing), vec![left, right]);
+        let props = reloaded
+            .clone()
+            .loader_client()
+            .unwrap()
+            .get_object_properties(thing)
+            .unwrap();
+        assert_eq!(props.iter().filter(|p| p.name() == "shared").count(), 1);
+        assert_eq!(value_of(&reloaded, "shared"), Some(v_str("thing")));
+        assert_eq!(value_of(&reloaded, "colour"), Some(v_str("red")));
+
+        // What a loader that doesn't know about the section would see.
+        let tasks_end = textdump.find("0 suspended tasks\n").unwrap() + "0 suspended tasks\n".len();
+        let legacy = load_textdump_string(&textdump[..tasks_end]);
+        assert_eq!(parents_of(&legacy, thing), vec![left]);
+        assert_eq!(value_of(&legacy, "shared"), Some(v_str("thing")));
+        assert_eq!(value_of(&legacy, "colour"), None);
+    }
+
+    /// Save a loaded db with the streaming writer, and load the result into a fresh db. The output
+    /// should match what the buffered writer produces, and reload to the same objects and verbs.
+    #[test]
+    fn save_then_reload() {
+        let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let minimal_db = manifest_dir.join("tests/Minimal.db");
+
+        let (db1, _) = RelBoxWorldState::open(None, 1 << 30);
+        let db1 = Arc::new(db1);
+        load_textdump_file(
+            db1.clone().loader_client().unwrap(),
+            minimal_db.to_str().unwrap(),
+        );
+
+        let version = "** LambdaMOO Database, Format Version 1 **";
+        let saved = tempfile::NamedTempFile::new().unwrap();
+        let tx = db1.clone().loader_client().unwrap();
+        textdump_save(tx.clone(), saved.path().to_path_buf(), Some(version)).unwrap();
+        assert_eq!(tx.commit().unwrap(), CommitResult::Success);
+
+        let output = std::fs::read_to_string(saved.path()).unwrap();
+        assert_diff(&write_textdump(db1.clone(), version), &output, "", 0);
+
+        let (db2, _) = RelBoxWorldState::open(None, 1 << 30);
+        let db2 = Arc::new(db2);
+        load_textdump_file(
+            db2.clone().loader_client().unwrap(),
+            saved.path().to_str().unwrap(),
+        );
+
+        let tx1 = db1.loader_client().unwrap();
+        let tx2 = db2.loader_client().unwrap();
+        assert_eq!(
+            tx1.get_objects().unwrap().len(),
+            tx2.get_objects().unwrap().len()
+        );
+
+        let verb = tx2
+            .get_object_verbs(SYSTEM_OBJECT)
+            .unwrap()
+            .find_first_named("do_login_command")
+            .unwrap();
+        let binary = tx2.get_verb_binary(SYSTEM_OBJECT, verb.uuid()).unwrap();
+        let program = Program::from_sliceref(SliceRef::from_vec(binary)).unwrap();
+        let ast = moor_compiler::program_to_tree(&program).unwrap();
+        assert_eq!(moor_compiler::unparse(&ast).unwrap(), vec!["return #3;"]);
+    }
+
+    /// Save to `version` and load the result into a fresh database.
+    fn save_and_reload(db: Arc<RelBoxWorldState>, version: Option<&str>) -> Arc<RelBoxWorldState> {
+        let saved = tempfile::NamedTempFile::new().unwrap();
+        let tx = db.loader_client().unwrap();
+        textdump_save(tx.clone(), saved.path().to_path_buf(), version).unwrap();
+        assert_eq!(tx.commit().unwrap(), CommitResult::Success);
+
+        let (reloaded, _) = RelBoxWorldState::open(None, 1 << 30);
+        let reloaded = Arc::new(reloaded);
+        load_textdump_file(
+            reloaded.clone().loader_client().unwrap(),
+            saved.path().to_str().unwrap(),
+        );
+        reloaded
+    }
+
+    /// moor's own format keeps verb and property UUIDs across a save and load; LambdaMOO's has
+    /// nowhere to put them, so they're assigned afresh.
+    #[test]
+    fn uuids_survive_save_and_load() {
+        let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let minimal_db = manifest_dir.join("tests/Minimal.db");
+
+        let (db, _) = RelBoxWorldState::open(None, 1 << 30);
+        let db = Arc::new(db);
+        load_textdump_file(
+            db.clone().loader_client().unwrap(),
+            minimal_db.to_str().unwrap(),
+        );
+
+        let tx = db.clone().loader_client().unwrap();
+        tx.define_property(
+            Objid(1),
+            Objid(1),
+            "description",
+            Objid(3),
+            BitEnum::new(),
+            Some(v_str("A thing.")),
+            None,
+        )
+        .unwrap();
+        assert_eq!(tx.commit().unwrap(), CommitResult::Success);
+
+        let identities = |db: Arc<RelBoxWorldState>| {
+            let tx = db.loader_client().unwrap();
+            let prop = tx
+                .get_object_properties(Objid(1))
+                .unwrap()
+                .find_first_named("description")
+                .unwrap()
+                .uuid();
+            let verb = tx
+                .get_object_verbs(SYSTEM_OBJECT)
+                .unwrap()
+                .find_first_named("do_login_command")
+                .unwrap()
+                .uuid();
+            (prop, verb)
+        };
+        let original = identities(db.clone());
+
+        let reloaded = save_and_reload(db.clone(), None);
+        assert_eq!(identities(reloaded.clone()), original);
+        let tx = reloaded.loader_client().unwrap();
+        let value = tx.get_property_value(Objid(1), original.0).unwrap();
+        assert_eq!(value, Some(v_str("A thing.")));
+
+        let legacy = save_and_reload(db, Some("** LambdaMOO Database, Format Version 4 **"));
+        let (prop, verb) = identities(legacy);
+        assert_ne!(prop, original.0);
+        assert_ne!(verb, original.1);
+    }
+
+    /// A delta dump has just the objects changed since the given timestamp, and applying it on
+    /// top of the earlier world brings that up to date.
+    #[test]
+    fn save_delta_then_apply() {
+        let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let minimal_db = manifest_dir.join("tests/Minimal.db");
+
+        let (db, _) = RelBoxWorldState::open(None, 1 << 30);
+        let db = Arc::new(db);
+        let tx = db.clone().loader_client().unwrap();
+        textdump_load(tx.clone(), minimal_db, VerbCompilation::Eager).unwrap();
+        tx.create_object(Some(Objid(4)), ObjAttrs::new().name("Thing"))
+            .unwrap();
+        tx.set_object_parent(Objid(4), Objid(1)).unwrap();
+        assert_eq!(tx.commit().unwrap(), CommitResult::Success);
+
+        let base = tempfile::NamedTempFile::new().unwrap();
+        let tx = db.clone().loader_client().unwrap();
+        assert_eq!(tx.get_objects().unwrap().len(), 5);
+        textdump_save(tx.clone(), base.path().to_path_buf(), None).unwrap();
+        let since = tx.timestamp();
+        assert_eq!(tx.commit().unwrap(), CommitResult::Success);
+
+        // Change two of the five objects.
+        let tx = db.clone().loader_client().unwrap();
+        tx.update_object(Objid(2), ObjAttrs::new().name("The Second Room"))
+            .unwrap();
+        tx.define_property(
+            Objid(4),
+            Objid(4),
+            "colour",
+            Objid(3),
+            BitEnum::new(),
+            Some(v_str("blue")),
+            None,
+        )
+        .unwrap();
+        assert_eq!(tx.commit().unwrap(), CommitResult::Success);
+
+        let delta = tempfile::NamedTempFile::new().unwrap();
+        let tx = db.clone().loader_client().unwrap();
+        textdump_save_delta(tx.clone(), since, delta.path().to_path_buf(), None).unwrap();
+        assert_eq!(tx.commit().unwrap(), CommitResult::Success);
+
+        let mut tdr = TextdumpReader::new(BufReader::new(File::open(delta.path()).unwrap()));
+        let td = tdr.read_textdump().unwrap();
+        assert_eq!(
+            td.objects.keys().copied().collect::<Vec<_>>(),
+            vec![Objid(2), Objid(4)]
+        );
+
+        // Restore the earlier world, and apply the delta on top.
+        let (db2, _) = RelBoxWorldState::open(None, 1 << 30);
+        let db2 = Arc::new(db2);
+        load_textdump_file(
+            db2.clone().loader_client().unwrap(),
+            base.path().to_str().unwrap(),
+        );
+        load_textdump_file(
+            db2.clone().loader_client().unwrap(),
+            delta.path().to_str().unwrap(),
+        );
+
+        let tx = db2.loader_client().unwrap();
+        assert_eq!(tx.get_objects().unwrap().len(), 5);
+        assert_eq!(
+            tx.get_object(Objid(2)).unwrap().name,
+            Some("The Second Room".to_string())
+        );
+        let colour = tx
+            .get_object_properties(Objid(4))
+            .unwrap()
+            .find_first_named("colour")
+            .unwrap();
+        assert_eq!(
+            tx.get_property_value(Objid(4), colour.uuid()).unwrap(),
+            Some(v_str("blue"))
+        );
+        let verb = tx
+            .get_object_verbs(SYSTEM_OBJECT)
+            .unwrap()
+            .find_first_named("do_login_command");
+        assert!(verb.is_some());
+    }
+
     #[test]
     // This is an expensive test, so it's not run by default.
     #[ignore]
@@ -269,7 +619,7 @@ mod test {
         let db2 = Arc::new(db2);
         let buffered_string_reader = std::io::BufReader::new(textdump.as_bytes());
         let lc = db2.clone().loader_client().unwrap();
-        let _ = read_textdump(lc.clone(), buffered_string_reader).unwrap();
+        let _ = read_textdump(lc.clone(), buffered_string_reader, VerbCompilation::Eager).unwrap();
         assert_eq!(lc.commit().unwrap(), CommitResult::Success);
 
         // Now go through the properties and verbs of all the objects on db1, and verify that
@@ -388,4 +738,240 @@ mod test {
             }
         }
     }
+
+    /// Swap the header line of Minimal.db for `header`, leaving the rest of the dump alone.
+    fn minimal_db_with_header(header: &str) -> String {
+        let mut input = String::new();
+        get_minimal_db().read_to_string(&mut input).unwrap();
+        let (_, rest) = input.split_once('\n').unwrap();
+        format!("{}\n{}", header, rest)
+    }
+
+    #[test]
+    fn parse_version_headers() {
+        assert_eq!(
+            TextdumpVersion::parse("** LambdaMOO Database, Format Version 1 **").unwrap(),
+            TextdumpVersion::LambdaMOO(1)
+        );
+        assert_eq!(
+            TextdumpVersion::parse("** LambdaMOO Database, Format Version 4 **\n").unwrap(),
+            TextdumpVersion::LambdaMOO(4)
+        );
+        assert_eq!(
+            TextdumpVersion::parse("** moor Textdump DB Version 1 **").unwrap(),
+            TextdumpVersion::Moor(1)
+        );
+        assert_eq!(
+            TextdumpVersion::parse("** moor Textdump DB Version 2 **").unwrap(),
+            TextdumpVersion::Moor(2)
+        );
+        for header in [
+            "** LambdaMOO Database, Format Version 17 **",
+            "** LambdaMOO Database, Format Version four **",
+            "** moor Textdump DB Version 3 **",
+            "** ToastCore Database, Version 2 **",
+            "",
+        ] {
+            assert!(
+                matches!(
+                    TextdumpVersion::parse(header),
+                    Err(TextdumpReaderError::UnsupportedVersion(..))
+                ),
+                "{header:?} should be rejected"
+            );
+        }
+    }
+
+    /// Minimal.db reads the same whichever known LambdaMOO format version it claims to be.
+    #[test]
+    fn read_known_versions() {
+        for n in 1..=4 {
+            let header = format!("** LambdaMOO Database, Format Version {} **", n);
+            let input = minimal_db_with_header(&header);
+            let mut tdr = TextdumpReader::new(BufReader::new(input.as_bytes()));
+            let td = tdr.read_textdump().expect("Failed to read textdump");
+            assert_eq!(td.version, header);
+            assert_eq!(td.users, vec![Objid(3)]);
+            assert_eq!(td.objects.len(), 4);
+            assert_eq!(td.verbs.len(), 1);
+        }
+    }
+
+    #[test]
+    fn read_unknown_version_fails() {
+        let input = minimal_db_with_header("** LambdaMOO Database, Format Version 17 **");
+        let mut tdr = TextdumpReader::new(BufReader::new(input.as_bytes()));
+        let Err(TextdumpReaderError::UnsupportedVersion(_, v)) = tdr.read_textdump() else {
+            panic!("expected an unsupported version error");
+        };
+        assert_eq!(v, "** LambdaMOO Database, Format Version 17 **");
+    }
+
+    /// The task sections after the programs are validated, but their contents aren't restored.
+    #[test]
+    fn read_task_sections() {
+        let input = minimal_db_with_header("** LambdaMOO Database, Format Version 4 **");
+
+        let with_clocks = input.replace("0 clocks\n", "1 clocks\n0 0 #0:foo\n");
+        let mut tdr = TextdumpReader::new(BufReader::new(with_clocks.as_bytes()));
+        tdr.read_textdump().expect("Failed to read textdump");
+
+        let truncated = input.replace("0 clocks\n0 queued tasks\n0 suspended tasks\n", "");
+        let mut tdr = TextdumpReader::new(BufReader::new(truncated.as_bytes()));
+        tdr.read_textdump().expect("Failed to read textdump");
+
+        let garbled = input.replace("0 queued tasks", "some queued tasks");
+        let mut tdr = TextdumpReader::new(BufReader::new(garbled.as_bytes()));
+        let Err(TextdumpReaderError::ParseError(position, _, token)) = tdr.read_textdump() else {
+            panic!("expected a parse error");
+        };
+        assert_eq!(
+            position,
+            TextdumpPosition {
+                line: 75,
+                column: 1
+            }
+        );
+        assert_eq!(token, "some queued tasks");
+    }
+
+    /// A bad value in a property value is reported at its line, with the text that was there.
+    #[test]
+    fn read_malformed_propval_reports_line() {
+        let input = minimal_db_with_header("** LambdaMOO Database, Format Version 4 **");
+        // Give #1 a property definition, and a value for it whose INT is garbage. #1's verb,
+        // propdef and propval counts start at line 36.
+        let (before, after) = input.split_once("#1\nRoot Class\n").unwrap();
+        let after = after.replacen("-1\n0\n0\n0\n#2", "-1\n0\n1\nfoo\n1\n0\n  12x\n3\n5\n#2", 1);
+        let input = format!("{}#1\nRoot Class\n{}", before, after);
+
+        let mut tdr = TextdumpReader::new(BufReader::new(input.as_bytes()));
+        let err = tdr.read_textdump().err().expect("expected a parse error");
+        let TextdumpReaderError::ParseError(position, what, token) = &err else {
+            panic!("expected a parse error, got {err:?}");
+        };
+        assert_eq!(
+            *position,
+            TextdumpPosition {
+                line: 41,
+                column: 3
+            }
+        );
+        assert_eq!(what, "invalid number");
+        assert_eq!(token, "12x");
+        assert_eq!(
+            err.to_string(),
+            "parse error at line 41, column 3: invalid number: \"12x\""
+        );
+    }
+
+    /// Minimal.db with two more verbs on #0: `broken`, which doesn't compile, and `try_broken`,
+    /// which calls it and returns the error it raises.
+    fn minimal_db_with_broken_verb() -> String {
+        let input = minimal_db_with_header("** LambdaMOO Database, Format Version 4 **");
+        let input = input.replacen("4\n1\n0\n1\n3\n", "4\n3\n0\n1\n3\n", 1);
+        let input = input.replacen(
+            "1\ndo_login_command\n3\n173\n-1\n",
+            "3\ndo_login_command\n3\n173\n-1\nbroken\n3\n173\n-1\ntry_broken\n3\n173\n-1\n",
+            1,
+        );
+        input.replacen(
+            "#0:0\nreturn #3;\n.\n",
+            "#0:0\nreturn #3;\n.\n#0:1\nreturn (;\n.\n\
+             #0:2\ntry\n  return #0:broken();\nexcept e (ANY)\n  return e[1];\nendtry\n.\n",
+            1,
+        )
+    }
+
+    /// Lazily imported verbs are stored as source, and compiled when they're first called.
+    #[test]
+    fn lazy_import_compiles_on_call() {
+        let (db, _) = RelBoxWorldState::open(None, 1 << 30);
+        let db = Arc::new(db);
+        let input = minimal_db_with_broken_verb();
+        let lc = db.clone().loader_client().unwrap();
+        read_textdump(
+            lc.clone(),
+            BufReader::new(input.as_bytes()),
+            VerbCompilation::Lazy,
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(lc.commit().unwrap(), CommitResult::Success);
+
+        let mut tx = db.new_world_state().unwrap();
+        let dlc = tx
+            .get_verb(Objid(3), SYSTEM_OBJECT, "do_login_command")
+            .unwrap();
+        assert_eq!(dlc.binary_type(), BinaryType::MooSource);
+
+        let session = Arc::new(NoopClientSession::new());
+        let result = call_verb(tx.as_mut(), session.clone(), "do_login_command", vec![]);
+        assert_eq!(result, v_objid(Objid(3)));
+
+        // The verb that doesn't compile only complains once something calls it.
+        let result = call_verb(tx.as_mut(), session, "try_broken", vec![]);
+        assert_eq!(result, v_err(E_INVARG));
+    }
+
+    /// Eager import still refuses a textdump with a verb that doesn't compile.
+    #[test]
+    fn eager_import_fails_on_broken_verb() {
+        let (db, _) = RelBoxWorldState::open(None, 1 << 30);
+        let db = Arc::new(db);
+        let input = minimal_db_with_broken_verb();
+        let lc = db.clone().loader_client().unwrap();
+        let result = read_textdump(
+            lc.clone(),
+            BufReader::new(input.as_bytes()),
+            VerbCompilation::Eager,
+        );
+        let Err(TextdumpReaderError::VerbCompileError(..)) = result else {
+            panic!("expected a verb compile error");
+        };
+    }
+
+    /// Lenient import carries on past a verb that doesn't compile, storing it as source, and
+    /// lists it in the summary.
+    #[test]
+    fn lenient_import_skips_broken_verb() {
+        let (db, _) = RelBoxWorldState::open(None, 1 << 30);
+        let db = Arc::new(db);
+        let input = minimal_db_with_broken_verb();
+        let lc = db.clone().loader_client().unwrap();
+        let summary = read_textdump(
+            lc.clone(),
+            BufReader::new(input.as_bytes()),
+            VerbCompilation::Lenient,
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(lc.commit().unwrap(), CommitResult::Success);
+
+        let [TextdumpReaderError::VerbCompileError(_, what, _)] =
+            summary.verb_compile_errors.as_slice()
+        else {
+            panic!(
+                "expected one verb compile error, got {:?}",
+                summary.verb_compile_errors
+            );
+        };
+        assert!(what.contains("#0/1"), "{what}");
+
+        let mut tx = db.new_world_state().unwrap();
+        for (verb, binary_type) in [
+            ("do_login_command", BinaryType::LambdaMoo18X),
+            ("broken", BinaryType::MooSource),
+            ("try_broken", BinaryType::LambdaMoo18X),
+        ] {
+            let vd = tx.get_verb(Objid(3), SYSTEM_OBJECT, verb).unwrap();
+            assert_eq!(vd.binary_type(), binary_type, "{verb}");
+        }
+
+        let session = Arc::new(NoopClientSession::new());
+        let result = call_verb(tx.as_mut(), session.clone(), "do_login_command", vec![]);
+        assert_eq!(result, v_objid(Objid(3)));
+        let result = call_verb(tx.as_mut(), session, "try_broken", vec![]);
+        assert_eq!(result, v_err(E_INVARG));
+    }
 }
@@ -18,7 +18,7 @@ use moor_db::odb::RelBoxWorldState;
 use moor_db::Database;
 use moor_kernel::tasks::sessions::NoopClientSession;
 use moor_kernel::tasks::vm_test_utils::call_verb;
-use moor_kernel::textdump::textdump_load;
+use moor_kernel::textdump::{textdump_load, VerbCompilation};
 use moor_values::model::CommitResult;
 use moor_values::model::Named;
 use moor_values::model::VerbArgsSpec;
@@ -38,7 +38,12 @@ fn testsuite_dir() -> PathBuf {
 /// Create a minimal Db to support the test harness.
 fn load_textdump(db: Arc<dyn Database>) {
     let tx = db.loader_client().unwrap();
-    textdump_load(tx.clone(), testsuite_dir().join("Minimal.db")).expect("Could not load textdump");
+    textdump_load(
+        tx.clone(),
+        testsuite_dir().join("Minimal.db"),
+        VerbCompilation::Eager,
+    )
+    .expect("Could not load textdump");
     assert_eq!(tx.commit().unwrap(), CommitResult::Success);
 }
 
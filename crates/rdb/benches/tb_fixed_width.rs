@@ -0,0 +1,79 @@
+// Copyright (C) 2024 Ryan Daum <ryan.daum@gmail.com>
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU General Public License as published by the Free Software
+// Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// this program. If not, see <https://www.gnu.org/licenses/>.
+//
+
+//! Compares tuple allocation throughput for a relation declared fixed-width (and so stored in
+//! fixed-size pages) against the same relation stored in variable-size pages.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use moor_rdb::index::{AttrType, IndexType};
+use moor_rdb::{RelBox, RelationId, RelationInfo};
+use moor_values::util::SliceRef;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+const TUPLES_PER_ITER: u64 = 10_000;
+
+fn test_db(fixed_width: Option<usize>) -> Arc<RelBox> {
+    let relations = [RelationInfo {
+        name: "relation".to_string(),
+        domain_type: AttrType::Integer,
+        codomain_type: AttrType::Integer,
+        secondary_indexed: false,
+        unique_domain: true,
+        index_type: IndexType::Hash,
+        codomain_index_type: None,
+        fixed_width,
+        page_size: None,
+    }];
+
+    RelBox::new(1 << 28, None, &relations, 0)
+}
+
+fn from_val(value: u64) -> SliceRef {
+    SliceRef::from_bytes(&value.to_le_bytes()[..])
+}
+
+fn insert_workload(iters: u64, fixed_width: Option<usize>) -> Duration {
+    let mut cumulative = Duration::new(0, 0);
+    for _ in 0..iters {
+        let db = test_db(fixed_width);
+        let start = Instant::now();
+        let tx = db.clone().start_tx();
+        for i in 0..TUPLES_PER_ITER {
+            tx.relation(RelationId(0))
+                .insert_tuple(from_val(i), from_val(i))
+                .unwrap();
+        }
+        tx.commit().unwrap();
+        black_box(());
+        cumulative += start.elapsed();
+    }
+    cumulative
+}
+
+pub fn allocation_bench(c: &mut Criterion) {
+    let mut group = c.benchmark_group("allocation");
+    group.measurement_time(Duration::from_secs(10));
+    group.throughput(criterion::Throughput::Elements(TUPLES_PER_ITER));
+    group.bench_function("variable_width", |b| {
+        b.iter_custom(|iters| insert_workload(iters, None));
+    });
+    group.bench_function("fixed_width", |b| {
+        b.iter_custom(|iters| insert_workload(iters, Some(16)));
+    });
+    group.finish();
+}
+
+criterion_group!(benches, allocation_bench);
+criterion_main!(benches);
@@ -43,6 +43,8 @@ fn test_db() -> Arc<RelBox> {
             unique_domain: true,
             index_type: IndexType::AdaptiveRadixTree,
             codomain_index_type: None,
+            fixed_width: None,
+            page_size: None,
         })
         .collect::<Vec<_>>();
 
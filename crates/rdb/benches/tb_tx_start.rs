@@ -0,0 +1,95 @@
+// Copyright (C) 2024 Ryan Daum <ryan.daum@gmail.com>
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU General Public License as published by the Free Software
+// Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// this program. If not, see <https://www.gnu.org/licenses/>.
+//
+
+//! Measures the fixed cost of starting a transaction (and doing one read in it) against a
+//! populated database, for the persistent base indexes, at each isolation level. The difference
+//! is what a serializable transaction pays for its copy of the canonical relations. (The ART
+//! index, which isn't persistent, has to be deep copied, and costs a good deal more.)
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use moor_rdb::index::{AttrType, IndexType};
+use moor_rdb::{IsolationLevel, RelBox, RelationId, RelationInfo};
+use moor_values::util::SliceRef;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+const RELATIONS: usize = 16;
+const TUPLES_PER_RELATION: u64 = 10_000;
+
+fn from_val(value: u64) -> SliceRef {
+    SliceRef::from_bytes(&value.to_le_bytes()[..])
+}
+
+fn populated_db(index_type: IndexType) -> Arc<RelBox> {
+    let relations = (0..RELATIONS)
+        .map(|i| RelationInfo {
+            name: format!("relation_{}", i),
+            domain_type: AttrType::Integer,
+            codomain_type: AttrType::Integer,
+            secondary_indexed: false,
+            unique_domain: true,
+            index_type,
+            codomain_index_type: None,
+            fixed_width: None,
+            page_size: None,
+        })
+        .collect::<Vec<_>>();
+    let db = RelBox::new(1 << 28, None, &relations, 0);
+
+    let tx = db.clone().start_tx();
+    for r in 0..RELATIONS {
+        for i in 0..TUPLES_PER_RELATION {
+            tx.relation(RelationId(r))
+                .insert_tuple(from_val(i), from_val(i))
+                .unwrap();
+        }
+    }
+    tx.commit().unwrap();
+    db
+}
+
+fn start_tx_workload(iters: u64, db: &Arc<RelBox>, isolation: IsolationLevel) -> Duration {
+    let start = Instant::now();
+    for i in 0..iters {
+        let tx = db.clone().start_tx_with_isolation(isolation);
+        let key = from_val(i % TUPLES_PER_RELATION);
+        black_box(
+            tx.relation(RelationId(0))
+                .seek_unique_by_domain(key)
+                .unwrap(),
+        );
+        tx.rollback().unwrap();
+    }
+    start.elapsed()
+}
+
+pub fn tx_start_bench(c: &mut Criterion) {
+    let mut group = c.benchmark_group("tx_start");
+    group.measurement_time(Duration::from_secs(10));
+    for (name, index_type) in [("hash", IndexType::Hash), ("btree", IndexType::BTree)] {
+        let db = populated_db(index_type);
+        for (level, isolation) in [
+            ("snapshot", IsolationLevel::Snapshot),
+            ("serializable", IsolationLevel::Serializable),
+        ] {
+            group.bench_function(format!("{name}_{level}"), |b| {
+                b.iter_custom(|iters| start_tx_workload(iters, &db, isolation));
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, tx_start_bench);
+criterion_main!(benches);
@@ -13,11 +13,12 @@
 //
 
 use std::collections::HashSet;
+use std::ops::RangeBounds;
 use tracing::error;
 
 use moor_values::util::SliceRef;
 
-use crate::index::{pick_base_index, Index};
+use crate::index::{pick_base_index, Index, KeyRange};
 use crate::tuples::{TupleId, TupleRef};
 use crate::{RelationError, RelationId, RelationInfo};
 
@@ -96,6 +97,11 @@ impl BaseRelation {
         self.tuples.contains_key(tuple)
     }
 
+    /// The number of tuples in the relation.
+    pub(crate) fn tuple_count(&self) -> usize {
+        self.tuples.len()
+    }
+
     pub fn check_domain_constraints(&self, domain: &SliceRef) -> Result<(), RelationError> {
         self.domain_index.check_constraints(domain)
     }
@@ -127,6 +133,25 @@ impl BaseRelation {
         self.tuples.values().filter(|t| f(t)).cloned().collect()
     }
 
+    /// The tuples whose domain falls in `range`. With an ordered domain index this walks just
+    /// that part of the index, in domain order; otherwise it's a scan of the whole relation.
+    pub fn seek_range(&self, range: &KeyRange) -> Vec<TupleRef> {
+        match self.domain_index.seek_range(range) {
+            Some(ids) => ids
+                .map(|id| {
+                    self.tuples
+                        .get(&id)
+                        .expect("missing tuple for indexed id")
+                        .clone()
+                })
+                .collect(),
+            None => self
+                .predicate_scan(&|t| range.contains(&t.domain()))
+                .into_iter()
+                .collect(),
+        }
+    }
+
     /// Remove a specific tuple from the relation, and update indexes accordingly.
     pub(crate) fn remove_tuple(&mut self, tuple: &TupleId) -> Result<(), RelationError> {
         let Some(tuple_ref) = self.tuples.remove(tuple) else {
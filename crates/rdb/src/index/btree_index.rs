@@ -12,11 +12,12 @@
 // this program. If not, see <https://www.gnu.org/licenses/>.
 //
 
-use crate::index::{AttrType, Index};
+use crate::index::{is_empty_range, AttrType, Index, KeyRange};
 use crate::tuples::TupleId;
 use crate::{IndexType, RelationError};
 use moor_values::util::SliceRef;
 use std::collections::{BTreeMap, HashSet};
+use std::ops::Bound;
 
 #[derive(Clone)]
 pub struct BtreeIndex {
@@ -75,6 +76,14 @@ fn to_key(slice_ref: &SliceRef, attr_type: AttrType) -> Result<Key, RelationErro
     }
 }
 
+fn to_key_bound(bound: &Bound<SliceRef>, attr_type: AttrType) -> Result<Bound<Key>, RelationError> {
+    Ok(match bound {
+        Bound::Included(k) => Bound::Included(to_key(k, attr_type)?),
+        Bound::Excluded(k) => Bound::Excluded(to_key(k, attr_type)?),
+        Bound::Unbounded => Bound::Unbounded,
+    })
+}
+
 pub struct Iter<'a> {
     iter: Box<dyn Iterator<Item = TupleId> + 'a>,
 }
@@ -138,6 +147,20 @@ impl Index for BtreeIndex {
         }))
     }
 
+    // Keys of every type compare by their bytes, so the map's order is the range's.
+    fn seek_range(&self, range: &KeyRange) -> Option<Box<dyn Iterator<Item = TupleId> + '_>> {
+        if is_empty_range(range) {
+            return Some(Box::new(std::iter::empty()));
+        }
+        let start = to_key_bound(&range.0, self.attr_type).ok()?;
+        let end = to_key_bound(&range.1, self.attr_type).ok()?;
+        Some(Box::new(
+            self.index
+                .range((start, end))
+                .flat_map(|(_, tuples)| tuples.iter().cloned()),
+        ))
+    }
+
     fn index_tuple(&mut self, key: &SliceRef, tuple_id: TupleId) -> Result<(), RelationError> {
         let key = to_key(key, self.attr_type)?;
         let entry = self.index.entry(key).or_default();
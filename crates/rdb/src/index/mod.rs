@@ -35,6 +35,7 @@ pub use art_index::ArtArrayIndex;
 pub use hash_index::HashIndex;
 pub use im_hash_index::ImHashIndex;
 use moor_values::util::SliceRef;
+use std::ops::Bound;
 use strum::EnumString;
 
 /// Types that domains or codomains can be for the purpose of indexing.
@@ -82,6 +83,19 @@ impl IndexType {
     }
 }
 
+/// A range of keys, ordered by their bytes.
+pub type KeyRange = (Bound<SliceRef>, Bound<SliceRef>);
+
+/// True if no key can fall in `range`. The ordered maps' own range functions panic on these.
+fn is_empty_range(range: &KeyRange) -> bool {
+    match range {
+        (Bound::Included(start), Bound::Included(end)) => start > end,
+        (Bound::Included(start) | Bound::Excluded(start), Bound::Excluded(end))
+        | (Bound::Excluded(start), Bound::Included(end)) => start >= end,
+        _ => false,
+    }
+}
+
 pub trait Index {
     /// Return the index type for the index.
     fn index_type(&self) -> IndexType;
@@ -94,6 +108,11 @@ pub trait Index {
         &self,
         domain: &SliceRef,
     ) -> Result<Box<dyn Iterator<Item = TupleId> + '_>, RelationError>;
+    /// Seek the tuples whose keys fall in `range`, in key order. Only indexes ordered the way
+    /// `KeyRange` is (by the keys' bytes) can walk a range; the rest return None.
+    fn seek_range(&self, _range: &KeyRange) -> Option<Box<dyn Iterator<Item = TupleId> + '_>> {
+        None
+    }
     /// Index the given tuple.
     fn index_tuple(&mut self, key: &SliceRef, tuple_id: TupleId) -> Result<(), RelationError>;
     /// Remove the given tuple from the index.
@@ -18,18 +18,22 @@
 //! if possible, and the whole set of relations is swapped out for the set of modified tuples.
 //!
 //! The tuples themselves are written out at commit time to a backing store, and then re-read at
-//! system initialization.
+//! system initialization. Each commit goes to the write-ahead log as a single entry before the new
+//! relations are swapped in, and recovery replays whole entries only, so a crash mid-commit leaves
+//! either all of it or none of it. See `SyncPolicy` for whether commit waits for that entry to be
+//! on disk.
 //!
 //! TLDR Transactions continue to see a fully snapshot isolated view of the world.
 
 pub use index::AttrType;
 pub use index::IndexType;
+pub use paging::{FitPolicy, SpaceUsage, SyncPolicy};
 pub use relbox::{RelBox, RelationInfo};
 use std::fmt::Display;
 use std::str::FromStr;
 use strum::EnumProperty;
 use thiserror::Error;
-pub use tx::{CommitError, Transaction};
+pub use tx::{CommitError, IsolationLevel, Savepoint, Transaction};
 
 mod base_relation;
 mod paging;
@@ -65,6 +69,8 @@ pub enum RelationError {
     AmbiguousTuple,
     #[error("Invalid key type")]
     BadKey,
+    #[error("Savepoint was taken in a different transaction")]
+    ForeignSavepoint,
 }
 
 /// Convert an enum schema description into RelationInfo (see WorldStateRelation for example)
@@ -123,6 +129,24 @@ pub fn relation_info_for<E: EnumProperty + Display>(relation: E) -> RelationInfo
         None
     };
 
+    let fixed_width = relation.get_str("FixedWidth").map(|it| {
+        it.parse::<usize>().unwrap_or_else(|_| {
+            panic!(
+                "Invalid fixed width: {} for declared relation {}",
+                it, relation
+            )
+        })
+    });
+
+    let page_size = relation.get_str("PageSize").map(|it| {
+        it.parse::<usize>().unwrap_or_else(|_| {
+            panic!(
+                "Invalid page size: {} for declared relation {}",
+                it, relation
+            )
+        })
+    });
+
     RelationInfo {
         name: relation.to_string(),
         domain_type,
@@ -131,5 +155,7 @@ pub fn relation_info_for<E: EnumProperty + Display>(relation: E) -> RelationInfo
         unique_domain: true,
         index_type,
         codomain_index_type,
+        fixed_width,
+        page_size,
     }
 }
@@ -16,7 +16,7 @@
 //! Used for write-ahead type storage at commit-time, and backed by whatever preferred physical
 //! storage mechanism is desired.
 
-use kanal::Sender;
+use kanal::{Receiver, Sender};
 use std::thread::yield_now;
 
 use crate::tx::WorkingSet;
@@ -26,8 +26,20 @@ pub struct BackingStoreClient {
     join_handle: std::thread::JoinHandle<()>,
 }
 
+/// When a commit is considered done, relative to its write-ahead log entry.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum SyncPolicy {
+    /// Hand the commit off to the writer thread and carry on. A crash can lose the most recent
+    /// commits, though never part of one.
+    #[default]
+    Lazy,
+    /// Wait for the commit's log entry to be synced to disk before making the commit visible.
+    Durable,
+}
+
 pub enum WriterMessage {
-    Commit(u64, WorkingSet, Vec<u64>),
+    /// A committed working set, and where to say so once it's durable, if anybody's waiting.
+    Commit(u64, WorkingSet, Vec<u64>, Option<Sender<()>>),
     Shutdown,
 }
 
@@ -41,11 +53,24 @@ impl BackingStoreClient {
 
     /// Sync out the working set from a committed transaction for the given transaction timestamp.
     /// Used to support persistent storage of committed transactions, effectively as a write-ahead
-    /// log.
-    pub fn sync(&self, ts: u64, ws: WorkingSet, sequences: Vec<u64>) {
+    /// log. Under `SyncPolicy::Durable`, doesn't return until the write-ahead log entry for it
+    /// has been synced.
+    pub fn sync(&self, ts: u64, ws: WorkingSet, sequences: Vec<u64>, policy: SyncPolicy) {
+        let (done_send, done_receive): (_, Option<Receiver<()>>) = match policy {
+            SyncPolicy::Lazy => (None, None),
+            SyncPolicy::Durable => {
+                let (send, receive) = kanal::bounded(1);
+                (Some(send), Some(receive))
+            }
+        };
         self.sender
-            .send(WriterMessage::Commit(ts, ws, sequences))
+            .send(WriterMessage::Commit(ts, ws, sequences, done_send))
             .expect("Unable to send write-ahead sync message");
+        if let Some(done_receive) = done_receive {
+            done_receive
+                .recv()
+                .expect("Write-ahead log writer went away before sync completed");
+        }
     }
 
     /// Shutdown the backing store writer thread.
@@ -70,7 +70,7 @@ impl ColdStorage {
             Ok(wal) => wal,
             Err(e) => {
                 error!(?e, "Unable to recover write-ahead log");
-                panic!("Unable to recover write-ahead log");
+                panic!("Unable to recover write-ahead log: {e:?}");
             }
         };
 
@@ -154,8 +154,12 @@ impl ColdStorage {
         ps.clone().start();
         loop {
             match writer_receive.recv() {
-                Ok(WriterMessage::Commit(ts, ws, sequences)) => {
+                Ok(WriterMessage::Commit(ts, ws, sequences, done)) => {
                     Self::perform_writes(wal.clone(), tuple_box.clone(), ts, ws, sequences);
+                    if let Some(done) = done {
+                        // The waiter may have given up on us; that's its business.
+                        let _ = done.send(());
+                    }
                 }
                 Ok(WriterMessage::Shutdown) => {
                     // Flush the WAL
@@ -229,9 +233,10 @@ impl ColdStorage {
                 match &t.op {
                     TxTupleOp::Insert(new_tuple) => {
                         let (tuple_offset, tuple_size) = tuple_box
-                            .page_for(new_tuple.id().page)
+                            .with_page(new_tuple.id().page, |page| {
+                                page.offset_of(new_tuple.id().slot)
+                            })
                             .expect("Unable to get page for tuple")
-                            .offset_of(new_tuple.id().slot)
                             .expect("Unable to get tuple offset");
                         let slotbuf = new_tuple.slot_buffer();
                         assert!(tuple_size >= slotbuf.len(), "Tuple size too small");
@@ -255,9 +260,10 @@ impl ColdStorage {
                         to_tuple: new_tuple,
                     } => {
                         let (tuple_offset, tuple_size) = tuple_box
-                            .page_for(new_tuple.id().page)
+                            .with_page(new_tuple.id().page, |page| {
+                                page.offset_of(new_tuple.id().slot)
+                            })
                             .expect("Unable to get page for tuple")
-                            .offset_of(new_tuple.id().slot)
                             .expect("Unable to get tuple offset");
                         let slotbuf = new_tuple.slot_buffer();
                         assert!(tuple_size >= slotbuf.len(), "Tuple size too small");
@@ -301,27 +307,47 @@ impl ColdStorage {
 
         // Now write out the updated page headers for the dirty pages
         for (page_id, r) in &dirty_pages {
-            // Get the slotboxy page for this tuple.
-            let Ok(page) = tuple_box.page_for(*page_id) else {
+            // Copy the page into the WAL entry directly.
+            let Ok(wal_entry_buffer) = tuple_box.with_page(*page_id, |page| {
+                make_wal_entry(
+                    WalEntryType::PageHeader,
+                    *page_id,
+                    Some(*r),
+                    0, /* not used */
+                    ts,
+                    0,
+                    page.header_size(),
+                    |buf| page.write_header(buf),
+                )
+                .expect("Failed to encode page index WAL entry")
+            }) else {
                 // If the slot or page is already gone, ce la vie, we don't need to sync it.
                 continue;
             };
-
-            // Copy the page into the WAL entry directly.
-            let wal_entry_buffer = make_wal_entry(
-                WalEntryType::PageHeader,
-                *page_id,
-                Some(*r),
-                0, /* not used */
-                ts,
-                0,
-                page.header_size(),
-                |buf| page.write_header(buf),
-            )
-            .expect("Failed to encode page index WAL entry");
             write_batch.push((*page_id, Some(wal_entry_buffer)));
         }
 
+        // Compacted pages had their tuples moved around, so the tuple writes above (and any before
+        // them) no longer line up with what's on disk. Write them out whole.
+        for (page_id, r) in tuple_box.take_compacted_pages() {
+            let Ok(wal_entry_buffer) = tuple_box.with_page(page_id, |page| {
+                make_wal_entry(
+                    WalEntryType::PageHeader,
+                    page_id,
+                    Some(r),
+                    0, /* not used */
+                    ts,
+                    0,
+                    page.page_size(),
+                    |buf| page.write_page(buf),
+                )
+                .expect("Failed to encode compacted page WAL entry")
+            }) else {
+                continue;
+            };
+            write_batch.push((page_id, Some(wal_entry_buffer)));
+        }
+
         let mut sync_wal = wal.begin_entry().expect("Failed to begin WAL entry");
         for (_page_id, wal_entry_buf) in write_batch {
             if let Some(wal_entry_buf) = wal_entry_buf {
@@ -330,6 +356,9 @@ impl ColdStorage {
                     .expect("Failed to write to WAL");
             }
         }
+        // Everything for the commit goes in the one entry, and okaywal discards entries which
+        // weren't completely written, so recovery sees either all of a commit or none of it.
+        // `commit` returns once the entry is synced to disk.
         sync_wal.commit().expect("Failed to commit WAL entry");
     }
 }
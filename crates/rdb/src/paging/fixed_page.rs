@@ -0,0 +1,321 @@
+// Copyright (C) 2024 Ryan Daum <ryan.daum@gmail.com>
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU General Public License as published by the Free Software
+// Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// this program. If not, see <https://www.gnu.org/licenses/>.
+//
+
+//! A variant of the slotted page for slots which are all the same size.
+//!
+//! Since every slot is the same size, there's no need for per-slot offsets or lengths: slot `n`
+//! lives at a fixed position after the index, and the index is just a used bit and a refcount per
+//! slot. Slots are packed densely, and freed slots are reused before the high water mark is moved.
+//!
+//! Layout: [PageHeader][one u32 per slot, padded to 8 bytes][slot 0][slot 1]...
+//!
+//! Fixed pages share the `PageHeader` (and so the locking) of variable-sized pages, and are told
+//! apart by it carrying a fixed slot size. `SlottedPage` dispatches to this when it sees one, so
+//! the rest of the system (page guards, cold storage, swap) doesn't need to know the difference.
+
+use std::pin::Pin;
+
+use crate::paging::slotted_page::{PageHeader, SlotId};
+use crate::paging::TupleBoxError;
+
+const USED: u32 = 1 << 31;
+const REFCOUNT_MASK: u32 = 0xffff;
+
+pub struct FixedSlottedPage<'a> {
+    base_address: *mut u8,
+    page_size: u32,
+
+    _marker: std::marker::PhantomData<&'a u8>,
+}
+
+#[inline]
+const fn padded(size: usize) -> usize {
+    (size + 7) & !7
+}
+
+/// How many slots of `slot_size` fit in a page of `page_size`.
+pub fn fixed_page_capacity(page_size: usize, slot_size: usize) -> usize {
+    let header_size = std::mem::size_of::<PageHeader>();
+    let slot_size = padded(slot_size);
+    let mut capacity = (page_size - header_size) / (slot_size + std::mem::size_of::<u32>());
+    while header_size + index_length(capacity) + capacity * slot_size > page_size {
+        capacity -= 1;
+    }
+    capacity
+}
+
+/// The size in bytes available for slots in an empty fixed page.
+pub fn fixed_page_empty_size(page_size: usize, slot_size: usize) -> usize {
+    fixed_page_capacity(page_size, slot_size) * padded(slot_size)
+}
+
+#[inline]
+fn index_length(capacity: usize) -> usize {
+    padded(capacity * std::mem::size_of::<u32>())
+}
+
+impl<'a> FixedSlottedPage<'a> {
+    pub(crate) fn new(base_address: *mut u8, page_size: usize) -> Self {
+        Self {
+            base_address,
+            page_size: page_size as u32,
+            _marker: Default::default(),
+        }
+    }
+
+    /// Set up a freshly allocated (zeroed) page to hold slots of `slot_size`.
+    pub(crate) fn init(base_address: *mut u8, page_size: usize, slot_size: usize) {
+        let capacity = fixed_page_capacity(page_size, slot_size);
+        let header = unsafe { &mut *(base_address as *mut PageHeader) };
+        header.set_fixed_slot_size(slot_size);
+        header.index_length = index_length(capacity) as u32;
+    }
+
+    #[inline]
+    fn header(&self) -> &PageHeader {
+        unsafe { &*(self.base_address as *const PageHeader) }
+    }
+
+    #[inline]
+    #[allow(clippy::mut_from_ref)]
+    fn header_mut(&self) -> &mut PageHeader {
+        unsafe { &mut *(self.base_address as *mut PageHeader) }
+    }
+
+    #[inline]
+    fn slot_size(&self) -> usize {
+        self.header().fixed_slot_size().unwrap()
+    }
+
+    #[inline]
+    fn capacity(&self) -> usize {
+        fixed_page_capacity(self.page_size as usize, self.slot_size())
+    }
+
+    #[inline]
+    fn num_used(&self) -> usize {
+        self.header().used_bytes as usize / self.slot_size()
+    }
+
+    #[inline]
+    fn entry(&self, slot_id: SlotId) -> u32 {
+        unsafe { *self.entry_ptr(slot_id) }
+    }
+
+    #[inline]
+    fn set_entry(&self, slot_id: SlotId, entry: u32) {
+        unsafe { *self.entry_ptr(slot_id) = entry }
+    }
+
+    #[inline]
+    fn entry_ptr(&self, slot_id: SlotId) -> *mut u32 {
+        let offset =
+            std::mem::size_of::<PageHeader>() + (slot_id as usize) * std::mem::size_of::<u32>();
+        unsafe { self.base_address.add(offset) as *mut u32 }
+    }
+
+    #[inline]
+    fn slot_offset(&self, slot_id: SlotId) -> usize {
+        std::mem::size_of::<PageHeader>()
+            + self.header().index_length as usize
+            + (slot_id as usize) * padded(self.slot_size())
+    }
+
+    fn check_used(&self, slot_id: SlotId) -> Result<(), TupleBoxError> {
+        if slot_id >= self.header().num_slots || self.entry(slot_id) & USED == 0 {
+            return Err(TupleBoxError::TupleNotFound(slot_id as usize));
+        }
+        Ok(())
+    }
+
+    pub(crate) fn free_space_bytes(&self) -> usize {
+        self.available_content_bytes()
+    }
+
+    /// Space for how many more slots there are, in bytes.
+    pub(crate) fn available_content_bytes(&self) -> usize {
+        (self.capacity() - self.num_used()) * padded(self.slot_size())
+    }
+
+    pub(crate) fn allocate(
+        &self,
+        size: usize,
+        initial_value: Option<&[u8]>,
+    ) -> Result<(SlotId, usize, Pin<&'a mut [u8]>), TupleBoxError> {
+        let slot_size = self.slot_size();
+        assert_eq!(
+            size, slot_size,
+            "slot of size {size} allocated on a page of fixed size {slot_size} slots"
+        );
+
+        // Reuse a freed slot if there is one, otherwise move the high water mark. There can only
+        // be freed slots if not everything below the mark is in use.
+        let num_slots = self.header().num_slots;
+        let free_slot = if self.num_used() < num_slots as usize {
+            (0..num_slots).find(|s| self.entry(*s) & USED == 0)
+        } else {
+            None
+        };
+        let slot_id = match free_slot {
+            Some(slot_id) => slot_id,
+            None if (num_slots as usize) < self.capacity() => {
+                self.header_mut().num_slots += 1;
+                num_slots
+            }
+            None => {
+                return Err(TupleBoxError::BoxFull(size, self.available_content_bytes()));
+            }
+        };
+        self.set_entry(slot_id, USED);
+        self.header_mut().used_bytes += slot_size as u32;
+
+        let offset = self.slot_offset(slot_id);
+        let slot = unsafe { std::slice::from_raw_parts_mut(self.base_address.add(offset), size) };
+        if let Some(initial_value) = initial_value {
+            slot.copy_from_slice(initial_value);
+        }
+        Ok((slot_id, self.available_content_bytes(), unsafe {
+            Pin::new_unchecked(slot)
+        }))
+    }
+
+    /// Reset the refcounts of all used slots to 1 after a load, returning them.
+    pub(crate) fn load(&self) -> Vec<(SlotId, usize, *mut u8)> {
+        let mut slots = vec![];
+        for slot_id in 0..self.header().num_slots {
+            if self.entry(slot_id) & USED == 0 {
+                continue;
+            }
+            self.set_entry(slot_id, USED | 1);
+            let ptr = unsafe { self.base_address.add(self.slot_offset(slot_id)) };
+            slots.push((slot_id, self.slot_size(), ptr));
+        }
+        slots
+    }
+
+    pub(crate) fn remove_slot(
+        &self,
+        slot_id: SlotId,
+    ) -> Result<(usize, usize, bool), TupleBoxError> {
+        assert!(
+            self.check_used(slot_id).is_ok(),
+            "attempt to free unused slot {}; double-free?",
+            slot_id
+        );
+        self.set_entry(slot_id, 0);
+
+        let slot_size = self.slot_size();
+        let header = self.header_mut();
+        header.used_bytes -= slot_size as u32;
+        let is_empty = header.used_bytes == 0;
+        if is_empty {
+            header.num_slots = 0;
+        }
+        Ok((self.available_content_bytes(), slot_size, is_empty))
+    }
+
+    pub(crate) fn refcount(&self, slot_id: SlotId) -> Result<u16, TupleBoxError> {
+        self.check_used(slot_id)?;
+        Ok((self.entry(slot_id) & REFCOUNT_MASK) as u16)
+    }
+
+    pub(crate) fn upcount(&self, slot_id: SlotId) -> Result<(), TupleBoxError> {
+        let entry = self.entry(slot_id);
+        self.set_entry(slot_id, entry + 1);
+        Ok(())
+    }
+
+    pub(crate) fn dncount(&self, slot_id: SlotId) -> Result<bool, TupleBoxError> {
+        let entry = self.entry(slot_id) - 1;
+        self.set_entry(slot_id, entry);
+        Ok(entry & REFCOUNT_MASK == 0)
+    }
+
+    pub(crate) fn get_slot(&self, slot_id: SlotId) -> Result<Pin<&'a [u8]>, TupleBoxError> {
+        self.check_used(slot_id)?;
+        let offset = self.slot_offset(slot_id);
+        let slot =
+            unsafe { std::slice::from_raw_parts(self.base_address.add(offset), self.slot_size()) };
+        Ok(unsafe { Pin::new_unchecked(slot) })
+    }
+
+    pub(crate) fn get_slot_mut(&self, slot_id: SlotId) -> Result<Pin<&'a mut [u8]>, TupleBoxError> {
+        self.check_used(slot_id)?;
+        let offset = self.slot_offset(slot_id);
+        let slot = unsafe {
+            std::slice::from_raw_parts_mut(self.base_address.add(offset), self.slot_size())
+        };
+        Ok(unsafe { Pin::new_unchecked(slot) })
+    }
+
+    pub(crate) fn offset_of(&self, slot_id: SlotId) -> Result<(usize, usize), TupleBoxError> {
+        if slot_id >= self.header().num_slots {
+            return Err(TupleBoxError::TupleNotFound(slot_id as usize));
+        }
+        Ok((self.slot_offset(slot_id), padded(self.slot_size())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::paging::fixed_page::{fixed_page_capacity, FixedSlottedPage};
+    use crate::paging::slotted_page::{PageHeader, SlottedPage};
+
+    #[test]
+    fn test_fill_and_free() {
+        let page_size = 4096;
+        let mut page_memory = vec![0u8; page_size];
+        let page_ptr = page_memory.as_mut_ptr();
+        FixedSlottedPage::init(page_ptr, page_size, 24);
+
+        let capacity = fixed_page_capacity(page_size, 24);
+        assert!(
+            std::mem::size_of::<PageHeader>() + capacity * (24 + 4) <= page_size,
+            "capacity {capacity} overflows page"
+        );
+
+        let mut page = SlottedPage::for_page_mut(page_ptr, page_size);
+        let mut slots = vec![];
+        for i in 0..capacity {
+            let value = [i as u8; 24];
+            let (slot, _, _) = page.allocate(24, Some(&value)).unwrap();
+            page.upcount(slot).unwrap();
+            slots.push((slot, value));
+        }
+        assert_eq!(page.available_content_bytes(), 0);
+        assert!(page.allocate(24, Some(&[0; 24])).is_err());
+        for (slot, value) in &slots {
+            assert_eq!(&page.get_slot(*slot).unwrap()[..], &value[..]);
+        }
+
+        // Free one, and its slot is the next one handed out.
+        let (freed, _) = slots.remove(3);
+        assert!(page.dncount(freed).unwrap());
+        let (_, _, is_empty) = page.remove_slot(freed).unwrap();
+        assert!(!is_empty);
+        assert_eq!(page.available_content_bytes(), 24);
+        let (slot, _, _) = page.allocate(24, Some(&[0xff; 24])).unwrap();
+        assert_eq!(slot, freed);
+        page.upcount(slot).unwrap();
+        slots.push((slot, [0xff; 24]));
+
+        // Free everything and the page reports empty.
+        let mut last_empty = false;
+        for (slot, _) in slots {
+            assert!(page.dncount(slot).unwrap());
+            last_empty = page.remove_slot(slot).unwrap().2;
+        }
+        assert!(last_empty);
+    }
+}
@@ -14,16 +14,19 @@
 
 use thiserror::Error;
 
+pub use backing::SyncPolicy;
 pub use pager::Pager;
 pub use slotted_page::SlotId;
-pub use tuple_box::{PageId, TupleBox};
+pub use tuple_box::{FitPolicy, PageId, SpaceUsage, TupleBox};
 pub use tuple_ptr::TuplePtr;
 
 mod backing;
 mod cold_storage;
+mod fixed_page;
 mod page_storage;
 mod pager;
 mod slotted_page;
+mod swap;
 mod tuple_box;
 mod tuple_ptr;
 mod wal;
@@ -32,12 +32,21 @@ use std::{
     },
 };
 
-use super::{backing::BackingStoreClient, cold_storage::ColdStorage, PageId, TupleBox};
+use super::{
+    backing::{BackingStoreClient, SyncPolicy},
+    cold_storage::ColdStorage,
+    swap::SwapFile,
+    PageId, TupleBox,
+};
 
 pub struct Pager {
     inner: Inner,
     next_pid: AtomicUsize,
     cold_storage: Mutex<Option<BackingStoreClient>>,
+    sync_policy: Mutex<SyncPolicy>,
+    /// If present, pages can be evicted from the pool to here under memory pressure, and faulted
+    /// back in on access.
+    swap: Option<Mutex<SwapFile>>,
 }
 
 struct Inner {
@@ -56,10 +65,67 @@ impl Pager {
                 page_table: DashMap::new(),
             },
             cold_storage: Mutex::new(None),
+            sync_policy: Mutex::new(SyncPolicy::default()),
             next_pid: AtomicUsize::new(0),
+            swap: None,
         })
     }
 
+    /// Construct a pager as with `new`, but which, when the pool fills, is able to evict pages to a
+    /// swap file at `swap_path` rather than refusing further allocation.
+    pub fn new_with_swap(size: usize, swap_path: PathBuf) -> Result<Self, BufferPoolError> {
+        let mut pager = Self::new(size)?;
+        pager.swap = Some(Mutex::new(SwapFile::create(swap_path)?));
+        Ok(pager)
+    }
+
+    /// Whether pages can be evicted from this pager's pool.
+    pub fn can_evict(&self) -> bool {
+        self.swap.is_some()
+    }
+
+    /// Whether the page has been evicted, and must be paged back in before it can be accessed.
+    pub fn is_paged_out(&self, page_id: PageId) -> bool {
+        self.swap
+            .as_ref()
+            .map(|swap| swap.lock().unwrap().contains(page_id))
+            .unwrap_or(false)
+    }
+
+    /// Copy a page out to swap and give its buffer back to the pool. The caller is responsible for
+    /// making sure nothing is still pointing into the page.
+    pub fn page_out(&self, page_id: PageId) -> Result<(), BufferPoolError> {
+        let Some(swap) = &self.swap else {
+            return Err(BufferPoolError::SwapFailed("no swap file".to_string()));
+        };
+        let (ptr, size) = self.resolve_ptr(page_id)?;
+        let page = unsafe { std::slice::from_raw_parts(ptr, size) };
+        swap.lock().unwrap().write(page_id, page)?;
+        let (_, bid) = self
+            .inner
+            .page_table
+            .remove(&page_id)
+            .ok_or(BufferPoolError::InvalidPage)?;
+        self.inner.pool.free(bid)
+    }
+
+    /// Bring a paged-out page back into a (new) buffer, returning its new address.
+    pub fn page_in(&self, page_id: PageId) -> Result<(*mut u8, usize), BufferPoolError> {
+        let Some(swap) = &self.swap else {
+            return Err(BufferPoolError::InvalidPage);
+        };
+        let mut swap = swap.lock().unwrap();
+        let page_size = swap.size_of(page_id).ok_or(BufferPoolError::InvalidPage)?;
+        let (bid, buf_ptr, used_size) = self.inner.pool.alloc(page_size)?;
+        let buf = unsafe { std::slice::from_raw_parts_mut(buf_ptr, page_size) };
+        if let Err(e) = swap.read(page_id, buf) {
+            self.inner.pool.free(bid)?;
+            return Err(e);
+        }
+        self.inner.page_table.insert(page_id, bid);
+        Ok((buf_ptr, used_size))
+    }
+
     /// Restore pages and the tuples they contain, and the indexes to those tuples, and set up
     /// the pager to use the provided directory for cold storage.
     pub fn open(
@@ -101,6 +167,13 @@ impl Pager {
 
     /// Free a page, and return it to the pool.
     pub fn free(&self, page_id: PageId) -> Result<(), BufferPoolError> {
+        if let Some(swap) = &self.swap {
+            let mut swap = swap.lock().unwrap();
+            if swap.contains(page_id) {
+                swap.discard(page_id);
+                return Ok(());
+            }
+        }
         let bid = self
             .inner
             .page_table
@@ -143,11 +216,17 @@ impl Pager {
         Ok((AtomicPtr::new(buf_ptr), used_size))
     }
 
+    /// Set whether syncs to cold storage wait for the write to be durable.
+    pub fn set_sync_policy(&self, sync_policy: SyncPolicy) {
+        *self.sync_policy.lock().unwrap() = sync_policy;
+    }
+
     /// Sync the working set to cold storage (if any)
     pub fn sync(&self, ts: u64, ws: WorkingSet, sequences: Vec<u64>) {
+        let policy = *self.sync_policy.lock().unwrap();
         let cs = self.cold_storage.lock().unwrap();
         if let Some(cold_storage) = cs.as_ref() {
-            cold_storage.sync(ts, ws, sequences);
+            cold_storage.sync(ts, ws, sequences, policy);
         }
     }
 
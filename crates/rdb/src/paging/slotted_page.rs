@@ -35,6 +35,7 @@ use std::sync::atomic::Ordering::{Acquire, Relaxed, Release, SeqCst};
 use atomic_wait::{wait, wake_all, wake_one};
 use tracing::error;
 
+use crate::paging::fixed_page::FixedSlottedPage;
 use crate::paging::TupleBoxError;
 
 pub type SlotId = u32;
@@ -47,14 +48,14 @@ pub type SlotId = u32;
 #[repr(C, align(8))]
 pub struct PageHeader {
     // The number of bytes used in the page
-    used_bytes: u32,
+    pub(super) used_bytes: u32,
     // The length of our slots index in bytes. Starts at initial zero.
-    index_length: u32,
+    pub(super) index_length: u32,
     // The length of our slots content in bytes. Starts at initial zero.
     // The page is full when index_length + content_length + sizeof(slotted_page) == PAGE_SIZE
-    content_length: u32,
+    pub(super) content_length: u32,
     // The number of available/used slots in the page
-    num_slots: u32,
+    pub(super) num_slots: u32,
 
     /// The number of read locks times two, plus one if there's a writer waiting.
     /// u32::MAX if write locked.
@@ -65,7 +66,25 @@ pub struct PageHeader {
     _pin: std::marker::PhantomPinned,
 }
 
+/// Tags `content_length` as holding the slot size of a page of fixed-size slots.
+const FIXED_SLOT_SIZE: u32 = 1 << 31;
+
 impl PageHeader {
+    /// For pages of fixed-size slots (see `FixedSlottedPage`), the size of every slot.
+    /// Such pages have no use for `content_length`, so keep the slot size there, tagged so it can't
+    /// be mistaken for a real content length, which never gets near that large.
+    #[inline]
+    pub(super) fn fixed_slot_size(&self) -> Option<usize> {
+        if self.content_length & FIXED_SLOT_SIZE == 0 {
+            return None;
+        }
+        Some((self.content_length & !FIXED_SLOT_SIZE) as usize)
+    }
+
+    pub(super) fn set_fixed_slot_size(&mut self, slot_size: usize) {
+        self.content_length = FIXED_SLOT_SIZE | slot_size as u32;
+    }
+
     /// Explicit unlock. Used by both the guard
     fn unlock_for_writes(self: Pin<&mut Self>) {
         self.lock_state.store(0, Release);
@@ -105,6 +124,17 @@ impl PageHeader {
         }
     }
 
+    // Update accounting after compaction, which leaves `num_slots` index entries, and content
+    // `content_length` bytes back from the end of the page.
+    fn set_compacted(mut self: Pin<&mut Self>, num_slots: u32, content_length: usize) {
+        unsafe {
+            let header = self.as_mut().get_unchecked_mut();
+            header.num_slots = num_slots;
+            header.index_length = num_slots * std::mem::size_of::<IndexEntry>() as u32;
+            header.content_length = content_length as u32;
+        }
+    }
+
     // Clear this page, and all the slots.
     fn clear(mut self: Pin<&mut Self>) {
         unsafe {
@@ -175,6 +205,15 @@ impl IndexEntry {
         }
     }
 
+    // Point the entry at where its content was moved to by compaction.
+    fn relocate(mut self: Pin<&mut Self>, offset: usize, allocated: usize) {
+        unsafe {
+            let index_entry = self.as_mut().get_unchecked_mut();
+            index_entry.offset = offset as u32;
+            index_entry.allocated = allocated as u32;
+        }
+    }
+
     // Mark a previously used entry as free, and return the number of bytes it was using.
     fn mark_free(mut self: Pin<&mut Self>) -> usize {
         unsafe {
@@ -221,6 +260,25 @@ impl<'a> SlottedPage<'a> {
         Self::as_page_mut(base_address, page_size).write_lock()
     }
 
+    /// Clear the lock state of a page whose bytes were just copied back in from swap. Whatever locks
+    /// were recorded in them belong to the page's previous residency, and are no longer held.
+    pub(crate) fn reset_locks(base_address: *mut u8, page_size: usize) {
+        let sp = Self::as_page_mut(base_address, page_size);
+        let header = sp.header_mut();
+        header.lock_state.store(0, SeqCst);
+        header.writer_wake_counter.store(0, SeqCst);
+    }
+
+    /// If this page holds fixed-size slots, the fixed-size view of it.
+    #[inline]
+    fn fixed(&self) -> Option<FixedSlottedPage<'a>> {
+        self.header().fixed_slot_size()?;
+        Some(FixedSlottedPage::new(
+            self.base_address,
+            self.page_size as usize,
+        ))
+    }
+
     fn as_page(base_address: *const u8, page_size: usize) -> Self {
         Self {
             base_address: base_address as *mut u8,
@@ -240,6 +298,9 @@ impl<'a> SlottedPage<'a> {
     /// How much space is available in this page?
     #[allow(dead_code)]
     pub(crate) fn free_space_bytes(&self) -> usize {
+        if let Some(fixed) = self.fixed() {
+            return fixed.free_space_bytes();
+        }
         let header = self.header();
         let used = (header.num_slots * std::mem::size_of::<IndexEntry>() as u32) as usize
             + header.used_bytes as usize
@@ -250,6 +311,9 @@ impl<'a> SlottedPage<'a> {
     /// How many bytes are available for appending to this page (i.e. not counting the space
     /// we could re-use, via e.g. used_bytes)
     pub(crate) fn available_content_bytes(&self) -> usize {
+        if let Some(fixed) = self.fixed() {
+            return fixed.available_content_bytes();
+        }
         let header = self.header();
         let content_length = header.content_length as usize;
         let index_length = header.index_length as usize;
@@ -266,6 +330,9 @@ impl<'a> SlottedPage<'a> {
         size: usize,
         initial_value: Option<&[u8]>,
     ) -> Result<(SlotId, usize, Pin<&'a mut [u8]>), TupleBoxError> {
+        if let Some(fixed) = self.fixed() {
+            return fixed.allocate(size, initial_value);
+        }
         // See if we can use an existing slot to put the slot in, or if there's any fit at all.
         let (can_fit, fit_slot) = self.find_fit(size);
         if !can_fit {
@@ -299,7 +366,7 @@ impl<'a> SlottedPage<'a> {
 
         // Find position and verify that we can fit the slot.
         let current_content_length = header.content_length as usize;
-        let current_index_end = header.index_length as usize;
+        let current_index_end = std::mem::size_of::<PageHeader>() + header.index_length as usize;
         let content_size = (size + 7) & !7;
         let content_start_position =
             self.page_size as usize - current_content_length - content_size;
@@ -309,7 +376,7 @@ impl<'a> SlottedPage<'a> {
 
         // If the content start bleeds over into the index (+ our new entry), then we can't fit the slot.
         let index_entry_size = std::mem::size_of::<IndexEntry>();
-        if content_start_position <= current_index_end + index_entry_size {
+        if content_start_position < current_index_end + index_entry_size {
             return Err(TupleBoxError::BoxFull(
                 size + index_entry_size,
                 self.available_content_bytes(),
@@ -364,6 +431,10 @@ impl<'a> SlottedPage<'a> {
         header.lock_state.store(0, SeqCst);
         header.writer_wake_counter.store(0, SeqCst);
 
+        if let Some(fixed) = self.fixed() {
+            return fixed.load();
+        }
+
         // Now reset all the refcounts to 1, and collect the list of all active slots.,
         let mut slots = vec![];
         let num_slots = header.num_slots;
@@ -380,6 +451,9 @@ impl<'a> SlottedPage<'a> {
     }
 
     fn remove_slot(&self, slot_id: SlotId) -> Result<(usize, usize, bool), TupleBoxError> {
+        if let Some(fixed) = self.fixed() {
+            return fixed.remove_slot(slot_id);
+        }
         // TODO: slots at start of content-length can be removed by shrinking the content-length
         //   portion.
 
@@ -403,7 +477,65 @@ impl<'a> SlottedPage<'a> {
         Ok((self.available_content_bytes(), slot_size, is_empty))
     }
 
+    /// Close up the holes left by removed slots, by moving the content of all the live slots up
+    /// against the end of the page, and dropping the index entries of removed slots from the end of
+    /// the index. Slot ids stay the same, but their offsets change, so anything pointing into the
+    /// page has to be re-pointed after.
+    /// Returns the number of bytes now available for appending to the page.
+    fn compact(&self) -> usize {
+        if let Some(fixed) = self.fixed() {
+            // Fixed-size slots are reused in place, so there are no holes to close.
+            return fixed.available_content_bytes();
+        }
+        let header = self.header_mut();
+        let num_slots = header.num_slots as SlotId;
+        let mut live_slots: Vec<_> = (0..num_slots)
+            .filter_map(|slot_id| {
+                let index_entry = self.get_index_entry(slot_id);
+                index_entry.used.then_some((
+                    slot_id,
+                    index_entry.offset as usize,
+                    index_entry.used_bytes as usize,
+                ))
+            })
+            .collect();
+
+        // Working down from the end of the page, every slot only moves towards the end, so it can't
+        // land on one we have yet to move.
+        live_slots.sort_by_key(|(_, offset, _)| std::cmp::Reverse(*offset));
+        let memory_as_slice =
+            unsafe { std::slice::from_raw_parts_mut(self.base_address, self.page_size as usize) };
+        let mut content_start = self.page_size as usize;
+        for (slot_id, offset, used_bytes) in &live_slots {
+            let content_size = (used_bytes + 7) & !7;
+            content_start -= content_size;
+            memory_as_slice.copy_within(*offset..*offset + *used_bytes, content_start);
+            self.get_index_entry_mut(*slot_id)
+                .relocate(content_start, content_size);
+        }
+
+        // Removed slots before the last live one keep their place in the index, but no longer hold
+        // any content.
+        let num_slots = live_slots
+            .iter()
+            .map(|(slot_id, _, _)| slot_id + 1)
+            .max()
+            .unwrap_or(0);
+        for slot_id in 0..num_slots {
+            let index_entry = self.get_index_entry_mut(slot_id);
+            if !index_entry.used {
+                index_entry.relocate(content_start, 0);
+            }
+        }
+        header.set_compacted(num_slots, self.page_size as usize - content_start);
+
+        self.available_content_bytes()
+    }
+
     fn refcount(&self, slot_id: SlotId) -> Result<u16, TupleBoxError> {
+        if let Some(fixed) = self.fixed() {
+            return fixed.refcount(slot_id);
+        }
         let index_entry = self.get_index_entry(slot_id);
         if !index_entry.used {
             return Err(TupleBoxError::TupleNotFound(slot_id as usize));
@@ -412,12 +544,18 @@ impl<'a> SlottedPage<'a> {
     }
 
     fn upcount(&self, slot_id: SlotId) -> Result<(), TupleBoxError> {
+        if let Some(fixed) = self.fixed() {
+            return fixed.upcount(slot_id);
+        }
         let mut index_entry = self.get_index_entry_mut(slot_id);
         unsafe { index_entry.as_mut().get_unchecked_mut() }.refcount += 1;
         Ok(())
     }
 
     fn dncount(&self, slot_id: SlotId) -> Result<bool, TupleBoxError> {
+        if let Some(fixed) = self.fixed() {
+            return fixed.dncount(slot_id);
+        }
         let mut index_entry = self.get_index_entry_mut(slot_id);
         unsafe { index_entry.as_mut().get_unchecked_mut() }.refcount -= 1;
         if index_entry.refcount == 0 {
@@ -428,6 +566,9 @@ impl<'a> SlottedPage<'a> {
 
     #[allow(dead_code)]
     fn get_slot(&self, slot_id: SlotId) -> Result<Pin<&'a [u8]>, TupleBoxError> {
+        if let Some(fixed) = self.fixed() {
+            return fixed.get_slot(slot_id);
+        }
         // Check that the index is in bounds
         let num_slots = self.header().num_slots as SlotId;
         if slot_id >= num_slots {
@@ -456,6 +597,9 @@ impl<'a> SlottedPage<'a> {
     }
 
     fn get_slot_mut(&self, slot_id: SlotId) -> Result<Pin<&'a mut [u8]>, TupleBoxError> {
+        if let Some(fixed) = self.fixed() {
+            return fixed.get_slot_mut(slot_id);
+        }
         // Check that the index is in bounds
         let num_slots = self.header().num_slots as SlotId;
         if slot_id >= num_slots {
@@ -579,6 +723,9 @@ impl<'a> SlottedPage<'a> {
 
     /// Return the offset, size of the slot at the given index.
     pub(crate) fn offset_of(&self, tid: SlotId) -> Result<(usize, usize), TupleBoxError> {
+        if let Some(fixed) = self.fixed() {
+            return fixed.offset_of(tid);
+        }
         // Check that the index is in bounds
         let num_slots = self.header().num_slots as SlotId;
         if tid >= num_slots {
@@ -717,6 +864,18 @@ impl<'a> PageWriteGuard<'a> {
         sp.remove_slot(slot_id)
     }
 
+    #[inline]
+    pub fn compact(&mut self) -> usize {
+        let sp = SlottedPage::as_page_mut(self.base_address, self.page_size as usize);
+        sp.compact()
+    }
+
+    #[inline]
+    pub(crate) fn offset_of(&self, slot_id: SlotId) -> Result<(usize, usize), TupleBoxError> {
+        let sp = SlottedPage::as_page(self.base_address, self.page_size as usize);
+        sp.offset_of(slot_id)
+    }
+
     #[inline]
     pub fn load<LF: FnMut(Pin<&mut [u8]>)>(&mut self, lf: LF) -> Vec<(SlotId, usize, *mut u8)> {
         let sp = SlottedPage::as_page_mut(self.base_address, self.page_size as usize);
@@ -747,6 +906,17 @@ impl<'a> PageWriteGuard<'a> {
         let sp = SlottedPage::as_page_mut(self.base_address, self.page_size as usize);
         sp.available_content_bytes()
     }
+
+    #[inline(always)]
+    pub fn page_size(&self) -> usize {
+        self.page_size as usize
+    }
+
+    /// The bytes taken up by the tuples in the page.
+    #[inline(always)]
+    pub fn used_bytes(&self) -> usize {
+        self.header().used_bytes as usize
+    }
 }
 
 impl<'a> Drop for PageWriteGuard<'a> {
@@ -782,6 +952,23 @@ impl<'a> PageReadGuard<'a> {
         buf.copy_from_slice(header_as_slice);
     }
 
+    #[allow(dead_code)]
+    pub(crate) fn available_content_bytes(&self) -> usize {
+        let sp = SlottedPage::as_page(self.base_address, self.page_size as usize);
+        sp.available_content_bytes()
+    }
+
+    /// Write the whole page, content and all, into the provided buffer
+    pub(crate) fn write_page(&self, buf: &mut [u8]) {
+        let page_as_slice =
+            unsafe { std::slice::from_raw_parts(self.base_address, self.page_size as usize) };
+        buf.copy_from_slice(page_as_slice);
+    }
+
+    pub(crate) fn page_size(&self) -> usize {
+        self.page_size as usize
+    }
+
     pub(crate) fn header_size(&self) -> usize {
         let header = self.header();
 
@@ -859,6 +1046,53 @@ mod tests {
         collected_slots
     }
 
+    // Compacting closes up the holes left by removed slots, and trims removed slots off the end of
+    // the index, without disturbing the slots that are left.
+    #[test]
+    fn compact_after_removes() {
+        let mut page_memory = vec![0; 4096];
+        let page_ptr = page_memory.as_mut_ptr();
+        let mut page = SlottedPage::for_page_mut(page_ptr, 4096);
+        let mut slots = vec![];
+        for i in 0..20 {
+            let value = vec![i as u8; 10 + i * 3];
+            let (tid, _, _) = page.allocate(value.len(), Some(&value)).unwrap();
+            slots.push((tid, value));
+        }
+
+        // Remove every other slot, and the last few, so there's some index to trim too.
+        let mut kept = vec![];
+        let mut freed = 0;
+        for (i, (tid, value)) in slots.into_iter().enumerate() {
+            if i % 2 == 0 && i < 18 {
+                kept.push((tid, value));
+                continue;
+            }
+            page.remove_slot(tid).unwrap();
+            freed += (value.len() + 7) & !7;
+            if i > 16 {
+                freed += std::mem::size_of::<IndexEntry>();
+            }
+        }
+
+        let before = page.available_content_bytes();
+        let after = page.compact();
+        assert_eq!(after, page.available_content_bytes());
+        assert_eq!(after - before, freed);
+        for (tid, value) in &kept {
+            assert_eq!(page.get_slot(*tid).unwrap().to_vec(), *value);
+        }
+
+        // And the space can be used again.
+        let size = (after - std::mem::size_of::<IndexEntry>()) & !7;
+        let value = vec![123; size];
+        let (tid, _, _) = page.allocate(size, Some(&value)).unwrap();
+        assert_eq!(page.get_slot(tid).unwrap().to_vec(), value);
+        for (tid, value) in &kept {
+            assert_eq!(page.get_slot(*tid).unwrap().to_vec(), *value);
+        }
+    }
+
     #[test]
     fn simple_add_get() {
         let mut page_memory = vec![0; 4096];
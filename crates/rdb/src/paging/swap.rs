@@ -0,0 +1,102 @@
+// Copyright (C) 2024 Ryan Daum <ryan.daum@gmail.com>
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU General Public License as published by the Free Software
+// Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// this program. If not, see <https://www.gnu.org/licenses/>.
+//
+
+//! Where the pager puts pages it has evicted from the buffer pool, so they can be faulted back in
+//! later. This is scratch space, not persistence: its contents are meaningless once the process
+//! exits, and durability is still the job of cold storage.
+
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::os::unix::fs::FileExt;
+use std::path::PathBuf;
+
+use crate::paging::PageId;
+use crate::pool::BufferPoolError;
+
+pub struct SwapFile {
+    file: File,
+    /// Where each paged-out page lives in the file, and how big it is.
+    pages: HashMap<PageId, (u64, usize)>,
+    /// Offsets of slots freed by pages that have been paged back in, by size.
+    free: HashMap<usize, Vec<u64>>,
+    end: u64,
+}
+
+impl SwapFile {
+    pub fn create(path: PathBuf) -> Result<Self, BufferPoolError> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&path)
+            .map_err(|e| {
+                BufferPoolError::InitializationError(format!(
+                    "Unable to open swap file {}: {e}",
+                    path.display()
+                ))
+            })?;
+        Ok(Self {
+            file,
+            pages: HashMap::new(),
+            free: HashMap::new(),
+            end: 0,
+        })
+    }
+
+    pub fn contains(&self, page_id: PageId) -> bool {
+        self.pages.contains_key(&page_id)
+    }
+
+    pub fn size_of(&self, page_id: PageId) -> Option<usize> {
+        self.pages.get(&page_id).map(|(_, size)| *size)
+    }
+
+    pub fn write(&mut self, page_id: PageId, page: &[u8]) -> Result<(), BufferPoolError> {
+        let offset = match self.free.get_mut(&page.len()).and_then(|f| f.pop()) {
+            Some(offset) => offset,
+            None => {
+                let offset = self.end;
+                self.end += page.len() as u64;
+                offset
+            }
+        };
+        self.file
+            .write_all_at(page, offset)
+            .map_err(|e| BufferPoolError::SwapFailed(e.to_string()))?;
+        self.pages.insert(page_id, (offset, page.len()));
+        Ok(())
+    }
+
+    /// Read a page back into `buf`, and give up its space in the file.
+    pub fn read(&mut self, page_id: PageId, buf: &mut [u8]) -> Result<(), BufferPoolError> {
+        let (offset, size) = *self
+            .pages
+            .get(&page_id)
+            .ok_or(BufferPoolError::InvalidPage)?;
+        self.file
+            .read_exact_at(&mut buf[..size], offset)
+            .map_err(|e| BufferPoolError::SwapFailed(e.to_string()))?;
+        self.pages.remove(&page_id);
+        self.free.entry(size).or_default().push(offset);
+        Ok(())
+    }
+
+    /// Forget a paged-out page without reading it back.
+    pub fn discard(&mut self, page_id: PageId) {
+        if let Some((offset, size)) = self.pages.remove(&page_id) {
+            self.free.entry(size).or_default().push(offset);
+        }
+    }
+}
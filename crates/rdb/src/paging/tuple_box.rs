@@ -12,39 +12,38 @@
 // this program. If not, see <https://www.gnu.org/licenses/>.
 //
 
-// TODO: add fixed-size slotted page impl for Sized items,
-//  should be way more efficient for the most common case of fixed-size tuples.
-// TODO: implement the ability to expire and page-out tuples
-//       based on LRU or random/second
-//       chance eviction (ala leanstore). will require separate PageIds from Bids, and will
-//       involve rewriting TuplePtr on the fly to point to a new page when restored.
-//       TuplePtr will also get a new field for last-access-time, so that we can do our eviction
+// TODO: page eviction (when the pager has swap) is LRU over whole pages. Consider random/second
+//       chance eviction (ala leanstore) instead, which wouldn't need a scan of all tuple ptrs.
+//       Readers pin their page by holding the read lock on the tuple ptrs for the whole read,
+//       so a slow reader holds off every writer in the box, not just those touching its page.
 // TODO: verify locking/concurrency safety of the pager & tuple storage
 //       loom test, stateright, or jepsen, etc.
 // TODO: improve dynamic slot allocation packing in slotted page
-//       there is still some really gross stuff in here about the management of free space in
-//       pages in the allocator list. It's probably causing excessive fragmentation because we're
-//       considering only the reported available "content" area when fitting slots, and there seems
-//       to be a sporadic failure where we end up with a "Page not found" error in the allocator on
-//       free, meaning the page was not found in the used pages list.
-//       whether any of this is worth futzing with after the fixed-size impl is done, I don't know.
+//       pages are picked by the space left at the end of them, so holes left by freed tuples are
+//       only reused once a tuple happens to land on that page anyway, or the page is compacted.
+//       `space_usage` shows how bad this gets, but nothing decides yet when to compact.
+// TODO: there seems to be a sporadic failure where we end up with a "Page not found" error in the
+//       allocator on free, meaning the page was not found in the used pages list.
 
 use std::cmp::max;
 use std::collections::HashMap;
 use std::pin::Pin;
+use std::sync::atomic::AtomicU64;
 use std::sync::atomic::Ordering::SeqCst;
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, RwLock};
 
 use tracing::warn;
 
 use moor_values::util::{BitArray, Bitset64};
 
+use crate::paging::fixed_page::{fixed_page_empty_size, FixedSlottedPage};
 use crate::paging::slotted_page::{
-    slot_index_overhead, slot_page_empty_size, PageReadGuard, PageWriteGuard, SlottedPage,
+    slot_index_overhead, slot_page_empty_size, slot_page_overhead, PageReadGuard, PageWriteGuard,
+    SlottedPage,
 };
 use crate::paging::tuple_ptr::TuplePtr;
 use crate::paging::TupleBoxError;
-use crate::pool::BufferPoolError;
+use crate::pool::{BufferPoolError, HIGHEST_SIZE_CLASS_POWER_OF, LOWEST_SIZE_CLASS_POWER_OF};
 use crate::tuples::{TupleId, TupleRef};
 use crate::RelationId;
 
@@ -52,16 +51,109 @@ use super::pager::Pager;
 
 pub type PageId = usize;
 
+/// The "swizzelable" references to tuples, indexed by tuple id.
+/// There has to be a stable-memory address for each of these, as they are referenced by
+/// pointers in the TupleRefs themselves.
+type TuplePtrs = HashMap<TupleId, Pin<Box<TuplePtr>>>;
+
 /// A TupleBox is a collection of (variable sized) pages, each of which is a collection of slots, each of which is holds
 /// dynamically sized tuples.
+///
+/// Locking: anything which allocates, frees, or moves pages (including eviction and faulting pages
+/// back in) holds `inner`, and then the write lock on `tuple_ptrs`. The box's own reads (`get`,
+/// `with_page`, `refcount`, `upcount`) only take the read lock on `tuple_ptrs`, which keeps their
+/// page resident while they use it, and then the page's own lock; so they don't block each other.
+/// A `TupleRef` reading its tuple's bytes does the same through `TuplePtr::with_buffer`, which pins
+/// the page (faulting it back in first if it was evicted) until the read is done.
 pub struct TupleBox {
     inner: Mutex<Inner>,
+    tuple_ptrs: RwLock<TuplePtrs>,
+    pager: Arc<Pager>,
+    /// Logical clock used to stamp tuple accesses, for choosing which page to evict.
+    access_clock: AtomicU64,
+}
+
+/// How the allocator picks which of a relation's pages to put a new tuple on.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum FitPolicy {
+    /// The page with the least room that still fits the tuple, leaving roomier pages for bigger
+    /// tuples.
+    #[default]
+    BestFit,
+    /// The lowest numbered page with room for the tuple, packing tuples into older pages so that
+    /// newer ones are more likely to empty out and be freed.
+    FirstFit,
+}
+
+/// How much of the space in a relation's pages is actually holding tuples.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct SpaceUsage {
+    pub pages: usize,
+    /// The combined size of the relation's pages.
+    pub total_bytes: usize,
+    /// The bytes of live tuples in them. The rest is page and index overhead, holes left by freed
+    /// tuples, and space not used yet.
+    pub used_bytes: usize,
 }
 
 impl TupleBox {
     pub fn new(pager: Arc<Pager>) -> Self {
-        let inner = Mutex::new(Inner::new(pager));
-        Self { inner }
+        let inner = Mutex::new(Inner::new(pager.clone()));
+        Self {
+            inner,
+            tuple_ptrs: RwLock::new(HashMap::new()),
+            pager,
+            access_clock: AtomicU64::new(0),
+        }
+    }
+
+    /// Declare that every tuple allocated for `relation_id` is `slot_size` bytes, so that its
+    /// tuples can be packed into fixed-size pages. Must be done before any tuples for the
+    /// relation are allocated or loaded.
+    pub fn set_fixed_slot_size(&self, relation_id: RelationId, slot_size: usize) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.fixed_slot_sizes.set(relation_id.0, slot_size);
+    }
+
+    /// Use pages of `page_size` bytes for `relation_id`'s tuples, instead of the default. Tuples
+    /// too big for such a page still get one of their own. Must be done before any tuples for the
+    /// relation are allocated.
+    pub fn set_page_size(&self, relation_id: RelationId, page_size: usize) {
+        assert!(
+            page_size.is_power_of_two()
+                && (1 << LOWEST_SIZE_CLASS_POWER_OF..=1 << HIGHEST_SIZE_CLASS_POWER_OF)
+                    .contains(&page_size),
+            "page size {page_size} for relation {relation_id:?} is not a supported size class"
+        );
+        let mut inner = self.inner.lock().unwrap();
+        inner.page_sizes.set(relation_id.0, page_size);
+    }
+
+    pub fn set_fit_policy(&self, fit_policy: FitPolicy) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.fit_policy = fit_policy;
+    }
+
+    /// Space usage for each relation with pages, for keeping an eye on fragmentation.
+    pub fn space_usage(&self) -> Vec<(RelationId, SpaceUsage)> {
+        let inner = self.inner.lock().unwrap();
+        inner
+            .available_page_space
+            .iter()
+            .map(|(rid, ps)| {
+                let usage = SpaceUsage {
+                    pages: ps.len(),
+                    total_bytes: ps.page_bytes,
+                    used_bytes: ps.tuple_bytes,
+                };
+                (RelationId(rid), usage)
+            })
+            .collect()
+    }
+
+    #[inline]
+    pub(crate) fn tick(&self) -> u64 {
+        self.access_clock.fetch_add(1, SeqCst)
     }
 
     /// Allocates a new slot for a tuple, somewhere in one of the pages we managed.
@@ -74,14 +166,25 @@ impl TupleBox {
         initial_value: Option<&[u8]>,
     ) -> Result<TupleRef, TupleBoxError> {
         let mut inner = self.inner.lock().unwrap();
+        let mut tuple_ptrs = self.tuple_ptrs.write().unwrap();
 
-        inner.do_alloc(size, relation_id, initial_value, &self)
+        inner.do_alloc(size, relation_id, initial_value, &self, &mut tuple_ptrs)
     }
 
     #[inline(always)]
     pub fn get(&self, id: TupleId) -> Result<TupleRef, TupleBoxError> {
-        let mut inner = self.inner.lock().unwrap();
-        inner.do_get(id)
+        self.with_resident_page(id.page, |tuple_ptrs, page_address, page_size| {
+            // We should already have a tuple pointer for this, otherwise it's not in the box.
+            let Some(tptr) = tuple_ptrs.get(&id) else {
+                return Err(TupleBoxError::TupleNotFound(id.slot as usize));
+            };
+            let tptr_ptr = tptr.as_ref().get_ref() as *const TuplePtr as *mut TuplePtr;
+
+            // Upcount through the page directly rather than through the tuple ptr, which would try
+            // to take the lock again.
+            SlottedPage::for_page_mut(page_address, page_size).upcount(id.slot)?;
+            Ok(TupleRef::at_tptr(tptr_ptr))
+        })
     }
 
     /// Restore a page for `relation_id` from secondary storage, returning references to all the
@@ -94,6 +197,7 @@ impl TupleBox {
         mut lf: LF,
     ) -> Result<Vec<TupleRef>, TupleBoxError> {
         let mut inner = self.inner.lock().unwrap();
+        let mut tuple_ptrs = self.tuple_ptrs.write().unwrap();
 
         // Re-allocate the page.
         let mut page = inner.do_restore_page(id, page_size).unwrap();
@@ -108,54 +212,93 @@ impl TupleBox {
         for (slot, buflen, addr) in slot_ids.into_iter() {
             let tuple_id = TupleId { page: id, slot };
             let swizref = Box::pin(TuplePtr::create(self.clone(), tuple_id, addr, buflen));
-            inner.tuple_ptrs.insert(tuple_id, swizref);
-            let tuple_ptr = inner.tuple_ptrs.get_mut(&tuple_id).unwrap();
+            tuple_ptrs.insert(tuple_id, swizref);
+            let tuple_ptr = tuple_ptrs.get_mut(&tuple_id).unwrap();
             let sp = unsafe { Pin::into_inner_unchecked(tuple_ptr.as_mut()) };
             let ptr = sp as *mut TuplePtr;
             let tuple_ref = TupleRef::at_tptr(ptr);
             refs.push(tuple_ref);
         }
         // The allocator needs to know that this page is used.
-        inner.do_mark_page_used(relation_id, page.available_content_bytes(), id);
+        inner.do_mark_page_used(
+            relation_id,
+            page.available_content_bytes(),
+            id,
+            page.page_size(),
+            page.used_bytes(),
+        );
         Ok(refs)
     }
 
-    #[allow(dead_code)]
-    pub(crate) fn page_fault(&self, tuple_id: TupleId) -> Result<(), TupleBoxError> {
-        let mut inner = self.inner.lock().unwrap();
-        inner
-            .do_handle_page_fault(tuple_id)
-            .expect("Could not page in");
-        Ok(())
+    /// Run `f` with `page_num` resident, and kept that way until `f` returns. This is how tuples
+    /// are read through their raw addresses, which eviction would otherwise be free to pull out
+    /// from under them. `f` must not call back into the box for anything that takes the write
+    /// lock (like dropping a `TupleRef`), or it will deadlock.
+    pub(crate) fn with_pinned_page<R>(&self, page_num: PageId, f: impl FnOnce() -> R) -> R {
+        let mut f = Some(f);
+        self.with_resident_page(page_num, |_, _, _| Ok(f.take().unwrap()()))
+            .expect("Could not page in")
     }
 
-    #[inline(always)]
-    pub(crate) fn page_for<'a>(&self, id: PageId) -> Result<PageReadGuard<'a>, TupleBoxError> {
-        let inner = self.inner.lock().unwrap();
-        inner.page_for(id)
+    /// Run `f` against the page, pinned as in `with_pinned_page`.
+    pub(crate) fn with_page<R>(
+        &self,
+        id: PageId,
+        f: impl FnOnce(&PageReadGuard) -> R,
+    ) -> Result<R, TupleBoxError> {
+        let mut f = Some(f);
+        self.with_resident_page(id, |_, page_address, page_size| {
+            Ok(f.take().unwrap()(&SlottedPage::for_page(
+                page_address,
+                page_size,
+            )))
+        })
+    }
+
+    /// The read path: run `f` against the (resident) page, holding only the read lock on the tuple
+    /// ptrs. If the page has been evicted, fault it back in (under the write locks) and try again.
+    fn with_resident_page<R, F>(&self, page_num: PageId, mut f: F) -> Result<R, TupleBoxError>
+    where
+        F: FnMut(&TuplePtrs, *mut u8, usize) -> Result<R, TupleBoxError>,
+    {
+        loop {
+            {
+                let tuple_ptrs = self.tuple_ptrs.read().unwrap();
+                // Pages are only evicted with the write lock held, so if it's resident now it
+                // stays that way until we're done.
+                if !self.pager.is_paged_out(page_num) {
+                    let (page_address, page_size) = resolve_ptr(&self.pager, page_num)?;
+                    return f(&tuple_ptrs, page_address, page_size);
+                }
+            }
+            let mut inner = self.inner.lock().unwrap();
+            let mut tuple_ptrs = self.tuple_ptrs.write().unwrap();
+            inner.fault_in(page_num, &mut tuple_ptrs)?;
+        }
     }
 
     pub fn refcount(&self, id: TupleId) -> Result<u16, TupleBoxError> {
-        let inner = self.inner.lock().unwrap();
-        let page_handle = inner.page_for(id.page)?;
-        page_handle.refcount(id.slot)
+        self.with_resident_page(id.page, |_, page_address, page_size| {
+            SlottedPage::for_page(page_address, page_size).refcount(id.slot)
+        })
     }
 
     #[inline(always)]
     pub fn upcount(&self, id: TupleId) -> Result<(), TupleBoxError> {
-        let inner = self.inner.lock().unwrap();
-        let mut page_handle = inner.page_for_mut(id.page)?;
-        page_handle.upcount(id.slot)
+        self.with_resident_page(id.page, |_, page_address, page_size| {
+            SlottedPage::for_page_mut(page_address, page_size).upcount(id.slot)
+        })
     }
 
     #[inline(always)]
     pub fn dncount(&self, id: TupleId) -> Result<(), TupleBoxError> {
         let mut inner = self.inner.lock().unwrap();
-        let mut page_handle = inner.page_for_mut(id.page)?;
+        let mut tuple_ptrs = self.tuple_ptrs.write().unwrap();
+        let mut page_handle = inner.page_for_mut(id.page, &mut tuple_ptrs)?;
         if page_handle.dncount(id.slot)? {
             // Ownership of the page write lock transfers to do_remove, so it doesn't need to take a new one and
             // cause deadlock.
-            inner.do_remove(id, page_handle)?;
+            inner.do_remove(id, page_handle, &mut tuple_ptrs)?;
         }
         Ok(())
     }
@@ -165,8 +308,9 @@ impl TupleBox {
         id: TupleId,
         mut f: F,
     ) -> Result<(), TupleBoxError> {
-        let inner = self.inner.lock().unwrap();
-        let mut page_handle = inner.page_for_mut(id.page)?;
+        let mut inner = self.inner.lock().unwrap();
+        let mut tuple_ptrs = self.tuple_ptrs.write().unwrap();
+        let mut page_handle = inner.page_for_mut(id.page, &mut tuple_ptrs)?;
 
         let existing = page_handle.get_slot_mut(id.slot).expect("Invalid tuple id");
 
@@ -174,6 +318,24 @@ impl TupleBox {
         Ok(())
     }
 
+    /// Close up the holes left in a page by freed tuples, moving its live tuples together so that
+    /// the space is available for new ones again. Returns the number of bytes recovered.
+    /// Tuples keep their ids, but their content moves. This takes the write lock on the tuple ptrs,
+    /// so it waits for anything reading the page (which pins it under the read lock) to finish,
+    /// and the tuples' pointers are swizzled to their new places before anyone can read again.
+    pub fn compact_page(&self, page_id: PageId) -> Result<usize, TupleBoxError> {
+        let mut inner = self.inner.lock().unwrap();
+        let mut tuple_ptrs = self.tuple_ptrs.write().unwrap();
+        inner.do_compact_page(page_id, &mut tuple_ptrs)
+    }
+
+    /// The pages compacted since the last call, with their relations. Offsets of their tuples have
+    /// changed, so they have to be written out whole rather than tuple by tuple.
+    pub(crate) fn take_compacted_pages(&self) -> Vec<(PageId, RelationId)> {
+        let mut inner = self.inner.lock().unwrap();
+        std::mem::take(&mut inner.compacted_pages)
+    }
+
     pub fn used_bytes(&self) -> usize {
         let inner = self.inner.lock().unwrap();
         inner.used_bytes()
@@ -198,18 +360,25 @@ impl TupleBox {
     }
 }
 
+/// The size of the pages a relation's tuples go on, unless it asks for something else.
+const DEFAULT_PAGE_SIZE: usize = 32768;
+
 struct Inner {
     // TODO: Consolidate page allocation vs buffer locking
     //   buffer pool has its own locks per size class, so we might not need this inside another lock
-    //   *but* the other two items here are not thread-safe, and we need to maintain consistency across the three.
+    //   *but* the allocation records here are not thread-safe, and we need to keep them consistent with it.
     //   so we can maybe get rid of the locks in the buffer pool...
     pager: Arc<Pager>,
     /// The set of used pages, indexed by relation, in sorted order of the free space available in them.
     available_page_space: Box<BitArray<PageSpace, 64, Bitset64<1>>>,
-    /// The "swizzelable" references to tuples, indexed by tuple id.
-    /// There has to be a stable-memory address for each of these, as they are referenced by
-    /// pointers in the TupleRefs themselves.
-    tuple_ptrs: HashMap<TupleId, Pin<Box<TuplePtr>>>,
+    /// The slot size of relations whose tuples are all the same size, and so go on fixed-size
+    /// pages.
+    fixed_slot_sizes: Box<BitArray<usize, 64, Bitset64<1>>>,
+    /// The page size of relations which don't use the default.
+    page_sizes: Box<BitArray<usize, 64, Bitset64<1>>>,
+    fit_policy: FitPolicy,
+    /// Pages compacted since they were last written out.
+    compacted_pages: Vec<(PageId, RelationId)>,
 }
 
 impl Inner {
@@ -217,30 +386,62 @@ impl Inner {
         Self {
             available_page_space: Box::new(BitArray::new()),
             pager,
-            tuple_ptrs: HashMap::new(),
+            fixed_slot_sizes: Box::new(BitArray::new()),
+            page_sizes: Box::new(BitArray::new()),
+            fit_policy: FitPolicy::default(),
+            compacted_pages: vec![],
         }
     }
 
+    /// The allocation record for the relation's pages, starting an empty one if need be.
+    fn page_space_for(&mut self, relation_id: RelationId) -> &mut PageSpace {
+        if self.available_page_space.get(relation_id.0).is_none() {
+            // Tuples on fixed-size pages have no index entry of their own; just their slot.
+            let index_overhead = match self.fixed_slot_sizes.get(relation_id.0) {
+                Some(_) => 0,
+                None => slot_index_overhead(),
+            };
+            self.available_page_space
+                .set(relation_id.0, PageSpace::new(index_overhead));
+        }
+        self.available_page_space.get_mut(relation_id.0).unwrap()
+    }
+
     fn do_alloc(
         &mut self,
         size: usize,
         relation_id: RelationId,
         initial_value: Option<&[u8]>,
         sb: &Arc<TupleBox>,
+        tuple_ptrs: &mut TuplePtrs,
     ) -> Result<TupleRef, TupleBoxError> {
-        let tuple_size = size + slot_index_overhead();
-        let page_size = max(32768, tuple_size.next_power_of_two());
+        if let Some(slot_size) = self.fixed_slot_sizes.get(relation_id.0) {
+            assert_eq!(
+                size, *slot_size,
+                "tuple of size {size} allocated in relation {relation_id:?} of fixed size {slot_size}"
+            );
+        }
+        // Big tuples get a page of their own, big enough for the page's header, the tuple, and its
+        // index entry.
+        let relation_page_size = self
+            .page_sizes
+            .get(relation_id.0)
+            .copied()
+            .unwrap_or(DEFAULT_PAGE_SIZE);
+        let page_size = max(
+            relation_page_size,
+            (slot_page_overhead() + ((size + 7) & !7) + slot_index_overhead()).next_power_of_two(),
+        );
 
         // Our selected page should not in theory get taken while we're holding this allocation lock,
         // but to be paranoid, we'll loop around and try again if it does.
         let mut tries = 0;
         loop {
             // Check if we have a free spot for this relation that can fit the tuple.
-            let (page, offset) =
-                { self.find_space(relation_id, tuple_size, slot_page_empty_size(page_size))? };
-            let mut page_handle = self.page_for_mut(page)?;
+            let (page, offset) = { self.find_space(relation_id, size, page_size, tuple_ptrs)? };
+            let mut page_handle = self.page_for_mut(page, tuple_ptrs)?;
             if let Ok((slot, page_remaining, mut buf)) = page_handle.allocate(size, initial_value) {
-                self.finish_alloc(page, relation_id, offset, page_remaining);
+                self.finish_alloc(relation_id, offset, page_remaining, size);
 
                 // Make a swizzlable ptr reference and shove it in our set, and then return a tuple ref
                 // which has a ptr to it.
@@ -254,7 +455,7 @@ impl Inner {
                     Box::pin(TuplePtr::create(sb.clone(), tuple_id, bufaddr, buflen));
                 let tuple_ptr_addr =
                     unsafe { tuple_ptr.as_mut().get_unchecked_mut() } as *mut TuplePtr;
-                self.tuple_ptrs.insert(tuple_id, tuple_ptr);
+                tuple_ptrs.insert(tuple_id, tuple_ptr);
 
                 // Establish initial refcount using this existing lock.
                 page_handle.upcount(slot).unwrap();
@@ -268,20 +469,6 @@ impl Inner {
         }
     }
 
-    fn do_get(&mut self, id: TupleId) -> Result<TupleRef, TupleBoxError> {
-        // We should already have a tuple pointer for this, otherwise it's not in the box.
-        self.tuple_ptrs
-            .get_mut(&id)
-            .map(|tptr| {
-                let tptr_ref = unsafe { Pin::into_inner_unchecked(tptr.as_mut()) };
-                let tptr_ptr = tptr_ref.as_mut_ptr();
-
-                tptr_ref.upcount();
-                TupleRef::at_tptr(tptr_ptr)
-            })
-            .map_or_else(|| Err(TupleBoxError::TupleNotFound(id.slot as usize)), Ok)
-    }
-
     fn do_restore_page<'a>(
         &mut self,
         id: PageId,
@@ -300,72 +487,160 @@ impl Inner {
         Ok(SlottedPage::for_page_mut(addr.load(SeqCst), page_size))
     }
 
-    fn do_mark_page_used(&mut self, relation_id: RelationId, free_space: usize, pid: PageId) {
-        let Some(available_page_space) = self.available_page_space.get_mut(relation_id.0) else {
-            self.available_page_space
-                .set(relation_id.0, PageSpace::new(free_space, pid));
-            return;
-        };
-
+    fn do_mark_page_used(
+        &mut self,
+        relation_id: RelationId,
+        free_space: usize,
+        pid: PageId,
+        page_size: usize,
+        used_bytes: usize,
+    ) {
+        let available_page_space = self.page_space_for(relation_id);
         available_page_space.insert(free_space, pid);
+        available_page_space.page_bytes += page_size;
+        available_page_space.tuple_bytes += used_bytes;
     }
 
     fn do_remove(
         &mut self,
         id: TupleId,
         mut page_handle: PageWriteGuard,
+        tuple_ptrs: &mut TuplePtrs,
     ) -> Result<(), TupleBoxError> {
-        let (new_free, _, is_empty) = page_handle.remove_slot(id.slot)?;
-        self.report_free(id.page, new_free, is_empty);
-        self.tuple_ptrs.remove(&id);
+        let (new_free, freed, is_empty) = page_handle.remove_slot(id.slot)?;
+        self.report_free(id.page, new_free, freed, is_empty, page_handle.page_size());
+        tuple_ptrs.remove(&id);
 
         Ok(())
     }
 
-    fn do_handle_page_fault(&mut self, _id: TupleId) -> Result<(), TupleBoxError> {
-        // Ask the pager to restore the page, and then we'll swizzle the pointer to it.
-
-        // 1. Ask the pager to restore the page, and get an address.
-
-        // 2. Find the tuple pointer for this tuple id.
-        // let tuple_ptr = self.tuple_ptrs.get_mut(&id).unwrap();
+    fn do_compact_page(
+        &mut self,
+        page_num: PageId,
+        tuple_ptrs: &mut TuplePtrs,
+    ) -> Result<usize, TupleBoxError> {
+        let Some(relation_id) = self.relation_of(page_num) else {
+            return Err(TupleBoxError::TupleNotFound(page_num));
+        };
+        let (page_address, page_size) = self.resolve_page(page_num, tuple_ptrs)?;
+        let mut page_handle = SlottedPage::for_page_mut(page_address, page_size);
+        let before = page_handle.available_content_bytes();
+        let after = page_handle.compact();
+
+        // Swizzle the pointers of the tuples on the page to where their content is now.
+        for (id, tuple_ptr) in tuple_ptrs.iter() {
+            if id.page != page_num {
+                continue;
+            }
+            let (offset, _) = page_handle.offset_of(id.slot)?;
+            tuple_ptr.mark_paged_in(unsafe { page_address.add(offset) });
+        }
 
-        // 3. Mark the tuple as paged with the new address.
-        // tuple_ptr.mark_paged_in(self.pager.resolve_ptr(id.page)?.0);
+        self.report_free(page_num, after, 0, false, page_size);
+        if !self.compacted_pages.contains(&(page_num, relation_id)) {
+            self.compacted_pages.push((page_num, relation_id));
+        }
+        Ok(after - before)
+    }
 
-        todo!("page in");
+    fn fault_in(
+        &mut self,
+        page_num: PageId,
+        tuple_ptrs: &mut TuplePtrs,
+    ) -> Result<(), TupleBoxError> {
+        // Someone else may have faulted the page back in while we waited for the lock.
+        if self.pager.is_paged_out(page_num) {
+            self.page_in(page_num, tuple_ptrs)?;
+        }
+        Ok(())
     }
 
-    fn page_for<'a>(&self, page_num: PageId) -> Result<PageReadGuard<'a>, TupleBoxError> {
-        let (page_address, page_size) = match self.pager.resolve_ptr(page_num) {
-            Ok(v) => v,
-            Err(BufferPoolError::CouldNotAccess) => {
-                return Err(TupleBoxError::TupleNotFound(page_num));
-            }
-            Err(BufferPoolError::InvalidPage) => {
-                return Err(TupleBoxError::TupleNotFound(page_num));
-            }
-            _ => {
-                panic!("Unexpected buffer pool error");
+    /// Bring an evicted page back in from swap, evicting others if need be to make room for it, and
+    /// then swizzle the pointers of all the tuples on it to their new addresses.
+    fn page_in(
+        &mut self,
+        page_num: PageId,
+        tuple_ptrs: &mut TuplePtrs,
+    ) -> Result<(), TupleBoxError> {
+        let (page_address, page_size) = loop {
+            match self.pager.page_in(page_num) {
+                Ok(v) => break v,
+                Err(BufferPoolError::InsufficientRoom { desired, available }) => {
+                    if !self.evict_lru(tuple_ptrs)? {
+                        return Err(TupleBoxError::BoxFull(desired, available));
+                    }
+                }
+                Err(e) => {
+                    panic!("Unexpected buffer pool error: {:?}", e);
+                }
             }
         };
+        SlottedPage::reset_locks(page_address, page_size);
+
         let page_handle = SlottedPage::for_page(page_address, page_size);
-        Ok(page_handle)
+        for (id, tuple_ptr) in tuple_ptrs.iter() {
+            if id.page != page_num {
+                continue;
+            }
+            let (offset, _) = page_handle.offset_of(id.slot)?;
+            tuple_ptr.mark_paged_in(unsafe { page_address.add(offset) });
+        }
+        Ok(())
     }
 
-    fn page_for_mut<'a>(&self, page_num: PageId) -> Result<PageWriteGuard<'a>, TupleBoxError> {
-        let (page_address, page_size) = match self.pager.resolve_ptr(page_num) {
-            Ok(v) => v,
-            Err(BufferPoolError::CouldNotAccess) => {
-                return Err(TupleBoxError::TupleNotFound(page_num));
-            }
-            Err(BufferPoolError::InvalidPage) => {
-                return Err(TupleBoxError::TupleNotFound(page_num));
-            }
-            _ => {
-                panic!("Unexpected buffer pool error");
+    /// Evict the resident page whose tuples have gone longest without being read, copying it out to
+    /// swap and nulling out its tuples' pointers so that the next access to any of them faults it
+    /// back in.
+    /// Returns false if eviction isn't possible, because there's no swap or nothing to evict.
+    fn evict_lru(&mut self, tuple_ptrs: &mut TuplePtrs) -> Result<bool, TupleBoxError> {
+        if !self.pager.can_evict() {
+            return Ok(false);
+        }
+
+        // The recency of a page is that of its most recently read tuple.
+        let mut page_access: HashMap<PageId, u64> = HashMap::new();
+        for (id, tuple_ptr) in tuple_ptrs.iter() {
+            if tuple_ptr.is_paged_out() {
+                continue;
             }
+            let last_access = page_access.entry(id.page).or_default();
+            *last_access = max(*last_access, tuple_ptr.last_access());
+        }
+        let Some((victim, _)) = page_access.into_iter().min_by_key(|(_, t)| *t) else {
+            return Ok(false);
         };
+
+        // No one else can be in the page while we copy it out: readers pin their page under the
+        // read lock on the tuple ptrs, and we're holding the write lock, so there's no need for the
+        // page's own write lock (and no guard left to unlock a buffer that's gone once the page is
+        // out).
+        if let Err(e) = self.pager.page_out(victim) {
+            panic!("Unable to page out page {}: {:?}", victim, e);
+        }
+
+        for (id, tuple_ptr) in tuple_ptrs.iter() {
+            if id.page == victim {
+                tuple_ptr.mark_paged_out();
+            }
+        }
+        Ok(true)
+    }
+
+    fn resolve_page(
+        &mut self,
+        page_num: PageId,
+        tuple_ptrs: &mut TuplePtrs,
+    ) -> Result<(*mut u8, usize), TupleBoxError> {
+        self.fault_in(page_num, tuple_ptrs)?;
+        resolve_ptr(&self.pager, page_num)
+    }
+
+    fn page_for_mut<'a>(
+        &mut self,
+        page_num: PageId,
+        tuple_ptrs: &mut TuplePtrs,
+    ) -> Result<PageWriteGuard<'a>, TupleBoxError> {
+        let (page_address, page_size) = self.resolve_page(page_num, tuple_ptrs)?;
         Ok(SlottedPage::for_page_mut(page_address, page_size))
     }
 
@@ -373,30 +648,35 @@ impl Inner {
         &mut self,
         relation_id: RelationId,
         page_size: usize,
+        tuple_ptrs: &mut TuplePtrs,
     ) -> Result<(PageId, usize), TupleBoxError> {
-        // Ask the buffer pool for a new page of the given size.
-        let (pid, actual_size) = match self.pager.alloc(page_size, |_| {}) {
-            Ok(v) => v,
-            Err(BufferPoolError::InsufficientRoom { desired, available }) => {
-                return Err(TupleBoxError::BoxFull(desired, available));
-            }
-            Err(e) => {
-                panic!("Unexpected buffer pool error: {:?}", e);
+        // Ask the buffer pool for a new page of the given size, making room by evicting if we're
+        // full and able to.
+        let (pid, actual_size) = loop {
+            match self.pager.alloc(page_size, |_| {}) {
+                Ok(v) => break v,
+                Err(BufferPoolError::InsufficientRoom { desired, available }) => {
+                    if !self.evict_lru(tuple_ptrs)? {
+                        return Err(TupleBoxError::BoxFull(desired, available));
+                    }
+                }
+                Err(e) => {
+                    panic!("Unexpected buffer pool error: {:?}", e);
+                }
             }
         };
-        match self.available_page_space.get_mut(relation_id.0) {
-            Some(available_page_space) => {
-                available_page_space.insert(slot_page_empty_size(actual_size), pid);
-                Ok((pid, available_page_space.len() - 1))
+        let empty_size = match self.fixed_slot_sizes.get(relation_id.0) {
+            Some(slot_size) => {
+                let (page_address, _) = self.pager.resolve_ptr(pid).unwrap();
+                FixedSlottedPage::init(page_address, actual_size, *slot_size);
+                fixed_page_empty_size(actual_size, *slot_size)
             }
-            None => {
-                self.available_page_space.set(
-                    relation_id.0,
-                    PageSpace::new(slot_page_empty_size(actual_size), pid),
-                );
-                Ok((pid, 0))
-            }
-        }
+            None => slot_page_empty_size(actual_size),
+        };
+        let available_page_space = self.page_space_for(relation_id);
+        available_page_space.insert(empty_size, pid);
+        available_page_space.page_bytes += actual_size;
+        Ok((pid, available_page_space.seek(pid).unwrap()))
     }
 
     /// Find room to allocate a new tuple of the given size, does not do the actual allocation yet,
@@ -407,39 +687,46 @@ impl Inner {
         relation_id: RelationId,
         tuple_size: usize,
         page_size: usize,
+        tuple_ptrs: &mut TuplePtrs,
     ) -> Result<(PageId, usize), TupleBoxError> {
-        // Do we have a used pages set for this relation? If not, we can start one, and allocate a
-        // new full page to it, and return. When we actually do the allocation, we'll be able to
-        // find the page in the used pages set.
-        let Some(available_page_space) = self.available_page_space.get_mut(relation_id.0) else {
-            // Ask the buffer pool for a new buffer.
-            return self.alloc(relation_id, page_size);
-        };
-
         // Can we find some room?
-        if let Some(found) = available_page_space.find_room(tuple_size) {
+        let fit_policy = self.fit_policy;
+        if let Some(found) = self
+            .page_space_for(relation_id)
+            .find_room(tuple_size, fit_policy)
+        {
             return Ok(found);
         }
 
         // Out of room, need to allocate a new page.
-        self.alloc(relation_id, page_size)
+        self.alloc(relation_id, page_size, tuple_ptrs)
     }
 
     fn finish_alloc(
         &mut self,
-        _pid: PageId,
         relation_id: RelationId,
         offset: usize,
         page_remaining_bytes: usize,
+        tuple_size: usize,
     ) {
         let available_page_space = self.available_page_space.get_mut(relation_id.0).unwrap();
         available_page_space.finish(offset, page_remaining_bytes);
+        available_page_space.tuple_bytes += tuple_size;
     }
 
-    fn report_free(&mut self, pid: PageId, new_size: usize, is_empty: bool) {
+    fn report_free(
+        &mut self,
+        pid: PageId,
+        new_size: usize,
+        freed_size: usize,
+        is_empty: bool,
+        page_size: usize,
+    ) {
         for (_, available_page_space) in self.available_page_space.iter_mut() {
             if available_page_space.update_page(pid, new_size, is_empty) {
+                available_page_space.tuple_bytes -= freed_size;
                 if is_empty {
+                    available_page_space.page_bytes -= page_size;
                     self.pager.free(pid).expect("Could not free page");
                 }
                 return;
@@ -454,6 +741,13 @@ impl Inner {
         );
     }
 
+    fn relation_of(&self, pid: PageId) -> Option<RelationId> {
+        self.available_page_space
+            .iter()
+            .find(|(_, ps)| ps.seek(pid).is_some())
+            .map(|(rid, _)| RelationId(rid))
+    }
+
     fn used_bytes(&self) -> usize {
         self.available_page_space
             .iter()
@@ -462,6 +756,17 @@ impl Inner {
     }
 }
 
+fn resolve_ptr(pager: &Pager, page_num: PageId) -> Result<(*mut u8, usize), TupleBoxError> {
+    match pager.resolve_ptr(page_num) {
+        Ok(v) => Ok(v),
+        Err(BufferPoolError::CouldNotAccess) => Err(TupleBoxError::TupleNotFound(page_num)),
+        Err(BufferPoolError::InvalidPage) => Err(TupleBoxError::TupleNotFound(page_num)),
+        _ => {
+            panic!("Unexpected buffer pool error");
+        }
+    }
+}
+
 /// The amount of space available for each page known to the allocator for a relation.
 /// Page id & available space are encoded in a single u128, so that we can sort by available space
 /// without having to do a lot of gymnastics.
@@ -472,6 +777,11 @@ struct PageSpace {
     // without a lot of gymnastics, and hopefully eventually use some SIMD instructions to do
     // the sorting?
     entries: Vec<u128>,
+    /// What each tuple costs in its page's index, on top of its content.
+    index_overhead: usize,
+    /// The combined size of the pages, and how much of that is live tuples.
+    page_bytes: usize,
+    tuple_bytes: usize,
 }
 
 #[inline(always)]
@@ -485,9 +795,12 @@ fn encode(pid: PageId, available: usize) -> u128 {
 }
 
 impl PageSpace {
-    fn new(available: usize, pid: PageId) -> Self {
+    fn new(index_overhead: usize) -> Self {
         Self {
-            entries: vec![encode(pid, available)],
+            entries: vec![],
+            index_overhead,
+            page_bytes: 0,
+            tuple_bytes: 0,
         }
     }
 
@@ -529,7 +842,21 @@ impl PageSpace {
     }
 
     /// Find which page in this relation has room for a tuple of the given size.
-    fn find_room(&self, available: usize) -> Option<(PageId, usize)> {
+    fn find_room(&self, tuple_size: usize, fit_policy: FitPolicy) -> Option<(PageId, usize)> {
+        // The page needs room for the tuple's content, rounded up to the page's alignment, and for
+        // its entry in the page's index. Pages report how much they have left for both together.
+        let available = ((tuple_size + 7) & !7) + self.index_overhead;
+
+        if fit_policy == FitPolicy::FirstFit {
+            return self
+                .entries
+                .iter()
+                .enumerate()
+                .filter(|(_, entry)| decode(**entry).1 >= available)
+                .min_by_key(|(_, entry)| decode(**entry).0)
+                .map(|(entry_num, entry)| (decode(*entry).0, entry_num));
+        }
+
         // Look for the first page with enough space in our vector of used pages, which is kept
         // sorted by free space.
         let found = self
@@ -578,15 +905,17 @@ impl PageSpace {
 
 #[cfg(test)]
 mod tests {
+    use std::sync::atomic::AtomicBool;
+    use std::sync::atomic::Ordering::SeqCst;
     use std::sync::Arc;
 
     use rand::distributions::Alphanumeric;
     use rand::{thread_rng, Rng};
 
-    use crate::paging::slotted_page::slot_page_empty_size;
-    use crate::paging::tuple_box::{TupleBox, TupleBoxError};
+    use crate::paging::slotted_page::{slot_index_overhead, slot_page_empty_size};
+    use crate::paging::tuple_box::{FitPolicy, TupleBox, TupleBoxError};
     use crate::paging::Pager;
-    use crate::tuples::TupleRef;
+    use crate::tuples::{TupleId, TupleRef};
     use crate::RelationId;
 
     fn fill_until_full(sb: &Arc<TupleBox>) -> Vec<(TupleRef, Vec<u8>)> {
@@ -739,6 +1068,97 @@ mod tests {
         }
     }
 
+    // Freeing the last tuple on a page belonging to a relation other than the first one with pages
+    // has to find the page in that relation's list, free it, and leave the others alone.
+    #[test]
+    fn test_free_in_later_relation() {
+        let pager = Arc::new(Pager::new(32768 * 64).unwrap());
+        let sb = Arc::new(TupleBox::new(pager));
+
+        let kept: Vec<_> = (0..10u8)
+            .map(|i| {
+                let value = vec![i; 32];
+                let tref =
+                    TupleRef::allocate(RelationId(0), sb.clone(), 0, &value, &value).unwrap();
+                (tref, value)
+            })
+            .collect();
+        let relation_0_pages = sb.used_pages();
+
+        let value = vec![0xff; 32];
+        let freed = TupleRef::allocate(RelationId(2), sb.clone(), 0, &value, &value).unwrap();
+        let freed_id = freed.id();
+        assert!(!relation_0_pages.contains(&freed_id.page));
+        assert_eq!(sb.num_pages(), relation_0_pages.len() + 1);
+
+        drop(freed);
+        assert!(sb.get(freed_id).is_err());
+        assert_eq!(sb.used_pages(), relation_0_pages);
+        for (tref, value) in &kept {
+            assert_eq!(tref.domain().as_slice(), &value[..]);
+        }
+    }
+
+    // Lots of mid-size tuples, some freed and replaced by others, should still be packed into not
+    // many more pages than they strictly need, whichever fit policy is used.
+    #[test]
+    fn test_fragmentation() {
+        for fit_policy in [FitPolicy::BestFit, FitPolicy::FirstFit] {
+            let pager = Arc::new(Pager::new(32768 * 1024).unwrap());
+            let sb = Arc::new(TupleBox::new(pager));
+            sb.set_fit_policy(fit_policy);
+
+            let mut tuples = vec![];
+            for i in 0..2000 {
+                let tuple = vec![i as u8; 500 + (i * 37) % 1500];
+                let tref = sb
+                    .clone()
+                    .allocate(tuple.len(), RelationId(0), Some(&tuple))
+                    .unwrap();
+                tuples.push((tref, tuple));
+            }
+            // Free every third one, and put some more in.
+            let mut i = 0;
+            tuples.retain(|_| {
+                i += 1;
+                i % 3 != 0
+            });
+            for i in 0..500 {
+                let tuple = vec![i as u8; 500 + (i * 53) % 1500];
+                let tref = sb
+                    .clone()
+                    .allocate(tuple.len(), RelationId(0), Some(&tuple))
+                    .unwrap();
+                tuples.push((tref, tuple));
+            }
+
+            let usage = sb.space_usage();
+            assert_eq!(usage.len(), 1);
+            let (relation_id, usage) = usage[0];
+            assert_eq!(relation_id, RelationId(0));
+            let tuple_bytes: usize = tuples.iter().map(|(_, t)| t.len()).sum();
+            assert_eq!(usage.used_bytes, tuple_bytes);
+            assert_eq!(usage.pages, sb.num_pages());
+            assert_eq!(usage.total_bytes, usage.pages * 32768);
+
+            // Every tuple costs its (aligned) content plus an index entry.
+            let needed: usize = tuples
+                .iter()
+                .map(|(_, t)| ((t.len() + 7) & !7) + slot_index_overhead())
+                .sum();
+            let min_pages = needed.div_ceil(slot_page_empty_size(32768));
+            assert!(
+                usage.pages <= min_pages * 3 / 2,
+                "{fit_policy:?}: {} pages for what fits in {min_pages}",
+                usage.pages
+            );
+
+            for (tref, expected) in &tuples {
+                assert_eq!(tref.slot_buffer().as_slice(), &expected[..]);
+            }
+        }
+    }
+
     // Fill a box with tuples, then go and free some random ones, verify their non-presence, then
     // fill back up again and verify the new presence.
     #[test]
@@ -787,6 +1207,349 @@ mod tests {
         }
     }
 
+    // With swap available, filling past the pool's capacity should evict the least recently read
+    // pages rather than fail, and reading an evicted tuple should transparently fault it back in.
+    #[test]
+    fn test_evict_and_fault_in() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let pager = Arc::new(Pager::new_with_swap(32768 * 8, tmpdir.path().join("swap")).unwrap());
+        let sb = Arc::new(TupleBox::new(pager.clone()));
+
+        // Enough ~1k tuples to fill the pool several times over.
+        let mut rng = thread_rng();
+        let mut tuples = vec![];
+        for _ in 0..1024 {
+            let value: Vec<u8> = (&mut rng).sample_iter(&Alphanumeric).take(1000).collect();
+            let tuple = TupleRef::allocate(RelationId(0), sb.clone(), 0, &value, &value).unwrap();
+            tuples.push((tuple, value));
+        }
+
+        // The first tuple's page hasn't been touched since, so it should have been evicted.
+        let (first, first_value) = &tuples[0];
+        assert!(pager.is_paged_out(first.id().page));
+
+        // But we can still read it, which brings it back in.
+        assert_eq!(*first_value, first.domain().as_slice());
+        assert_eq!(*first_value, first.codomain().as_slice());
+        assert!(!pager.is_paged_out(first.id().page));
+
+        // And everything else is intact, whether it was evicted or not.
+        for (tuple, expected_value) in &tuples {
+            assert_eq!(*expected_value, tuple.domain().as_slice());
+            assert_eq!(*expected_value, tuple.codomain().as_slice());
+        }
+    }
+
+    // Free every other tuple on a page, then compact it; the tuples left should read the same, and
+    // the room recovered should go to the next tuple that fits in it.
+    #[test]
+    fn test_compact_page() {
+        let pager = Arc::new(Pager::new(32768 * 64).unwrap());
+        let sb = Arc::new(TupleBox::new(pager));
+        let mut rng = thread_rng();
+        let mut tuples = vec![];
+        for _ in 0..100 {
+            let tuple_len = rng.gen_range(100..200);
+            let value: Vec<u8> = (&mut rng)
+                .sample_iter(&Alphanumeric)
+                .take(tuple_len)
+                .collect();
+            let tuple = sb
+                .clone()
+                .allocate(value.len(), RelationId(0), Some(&value))
+                .unwrap();
+            tuples.push((tuple, value));
+        }
+        let page = tuples[0].0.id().page;
+        assert!(tuples.iter().all(|(tuple, _)| tuple.id().page == page));
+
+        let mut freed = 0;
+        let mut kept = vec![];
+        for (i, (tuple, value)) in tuples.into_iter().enumerate() {
+            if i % 2 == 1 {
+                kept.push((tuple, value));
+            } else {
+                freed += (value.len() + 7) & !7;
+            }
+        }
+        assert_eq!(sb.compact_page(page).unwrap(), freed);
+        for (tuple, value) in &kept {
+            assert_eq!(*value, tuple.slot_buffer().as_slice());
+            assert_eq!(*value, sb.get(tuple.id()).unwrap().slot_buffer().as_slice());
+        }
+        assert_eq!(sb.compact_page(page).unwrap(), 0);
+        assert_eq!(sb.take_compacted_pages(), vec![(page, RelationId(0))]);
+
+        // A tuple that only fits in the recovered space goes on the same page.
+        let available = sb
+            .with_page(page, |page| page.available_content_bytes())
+            .unwrap();
+        assert!(available >= freed);
+        let value = vec![b'x'; (available - slot_index_overhead()) & !7];
+        let tuple = sb
+            .clone()
+            .allocate(value.len(), RelationId(0), Some(&value))
+            .unwrap();
+        assert_eq!(tuple.id().page, page);
+        assert_eq!(sb.num_pages(), 1);
+        for (tuple, value) in &kept {
+            assert_eq!(*value, tuple.slot_buffer().as_slice());
+        }
+    }
+
+    // Threads reading tuples through their `TupleRef`s while another keeps freeing tuples and
+    // compacting their pages should always read the right values, never content that's been moved
+    // out from under them.
+    #[test]
+    fn test_reads_during_compaction() {
+        let pager = Arc::new(Pager::new(32768 * 64).unwrap());
+        let sb = Arc::new(TupleBox::new(pager));
+        let mut rng = thread_rng();
+        let mut tuples = vec![];
+        let mut spares = vec![];
+        for i in 0..400 {
+            let value: Vec<u8> = (&mut rng).sample_iter(&Alphanumeric).take(100).collect();
+            let tuple = TupleRef::allocate(RelationId(0), sb.clone(), 0, &value, &value).unwrap();
+            if i % 2 == 0 {
+                tuples.push((tuple, value));
+            } else {
+                spares.push(tuple);
+            }
+        }
+        let tuples = Arc::new(tuples);
+
+        let barrier = Arc::new(std::sync::Barrier::new(5));
+        let done = Arc::new(AtomicBool::new(false));
+        let readers: Vec<_> = (0..4)
+            .map(|_| {
+                let tuples = tuples.clone();
+                let barrier = barrier.clone();
+                let done = done.clone();
+                std::thread::spawn(move || {
+                    barrier.wait();
+                    while !done.load(SeqCst) {
+                        for (tuple, value) in tuples.iter() {
+                            assert_eq!(*value, tuple.domain().as_slice());
+                            assert_eq!(*value, tuple.codomain().as_slice());
+                        }
+                    }
+                })
+            })
+            .collect();
+
+        // Each spare freed leaves a hole between two tuples the readers are reading, which the
+        // compaction then closes up by moving them.
+        barrier.wait();
+        let mut recovered = 0;
+        while let Some(spare) = spares.pop() {
+            let page = spare.id().page;
+            drop(spare);
+            recovered += sb.compact_page(page).unwrap();
+        }
+        done.store(true, SeqCst);
+        for reader in readers {
+            reader.join().unwrap();
+        }
+        assert!(recovered > 0);
+    }
+
+    // Threads reading tuples through their `TupleRef`s, while another thread fills the pool with
+    // swap enabled so that their pages keep getting evicted, should never read a page that's been
+    // paged out from under them.
+    #[test]
+    fn test_reads_during_eviction() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let pager = Arc::new(Pager::new_with_swap(32768 * 8, tmpdir.path().join("swap")).unwrap());
+        let sb = Arc::new(TupleBox::new(pager));
+
+        let mut rng = thread_rng();
+        let mut tuples = vec![];
+        for _ in 0..64 {
+            let value: Vec<u8> = (&mut rng).sample_iter(&Alphanumeric).take(1000).collect();
+            let tuple = TupleRef::allocate(RelationId(0), sb.clone(), 0, &value, &value).unwrap();
+            tuples.push((tuple, value));
+        }
+        let tuples = Arc::new(tuples);
+
+        let done = Arc::new(AtomicBool::new(false));
+        let readers: Vec<_> = (0..4)
+            .map(|_| {
+                let tuples = tuples.clone();
+                let done = done.clone();
+                std::thread::spawn(move || {
+                    while !done.load(SeqCst) {
+                        for (tuple, value) in tuples.iter() {
+                            assert_eq!(*value, tuple.domain().as_slice());
+                            assert_eq!(*value, tuple.codomain().as_slice());
+                        }
+                    }
+                })
+            })
+            .collect();
+
+        let mut filler = vec![];
+        for _ in 0..2048 {
+            let value: Vec<u8> = (&mut rng).sample_iter(&Alphanumeric).take(1000).collect();
+            let tuple = TupleRef::allocate(RelationId(1), sb.clone(), 0, &value, &value).unwrap();
+            filler.push(tuple);
+        }
+        done.store(true, SeqCst);
+        for reader in readers {
+            reader.join().unwrap();
+        }
+    }
+
+    // Many threads reading tuples at once, while another keeps allocating and freeing tuples in
+    // another relation, should all see the right values.
+    #[test]
+    fn test_parallel_reads() {
+        let pager = Arc::new(Pager::new(32768 * 256).unwrap());
+        let sb = Arc::new(TupleBox::new(pager));
+
+        let mut rng = thread_rng();
+        let mut tuples = vec![];
+        for _ in 0..2000 {
+            let tuple_len = rng.gen_range(1..512);
+            let value: Vec<u8> = (&mut rng)
+                .sample_iter(&Alphanumeric)
+                .take(tuple_len)
+                .collect();
+            let tuple = TupleRef::allocate(RelationId(0), sb.clone(), 0, &value, &value).unwrap();
+            tuples.push((tuple, value));
+        }
+        let expected: Arc<Vec<(TupleId, Vec<u8>)>> = Arc::new(
+            tuples
+                .iter()
+                .map(|(tuple, value)| (tuple.id(), value.clone()))
+                .collect(),
+        );
+
+        let done = Arc::new(AtomicBool::new(false));
+        let writer = {
+            let sb = sb.clone();
+            let done = done.clone();
+            std::thread::spawn(move || {
+                let mut rng = thread_rng();
+                while !done.load(SeqCst) {
+                    let value: Vec<u8> = (&mut rng).sample_iter(&Alphanumeric).take(256).collect();
+                    let tuple =
+                        TupleRef::allocate(RelationId(1), sb.clone(), 0, &value, &value).unwrap();
+                    assert_eq!(value, tuple.domain().as_slice());
+                }
+            })
+        };
+
+        let readers: Vec<_> = (0..8)
+            .map(|_| {
+                let sb = sb.clone();
+                let expected = expected.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..10 {
+                        for (id, value) in expected.iter() {
+                            let tuple = sb.get(*id).unwrap();
+                            assert_eq!(*value, tuple.domain().as_slice());
+                            assert_eq!(*value, tuple.codomain().as_slice());
+                            // At least our reference, and the one in `tuples`.
+                            assert!(sb.refcount(*id).unwrap() >= 2);
+                            let (_, size) = sb
+                                .with_page(id.page, |page| page.offset_of(id.slot))
+                                .unwrap()
+                                .unwrap();
+                            assert!(size >= value.len());
+                        }
+                    }
+                })
+            })
+            .collect();
+        for reader in readers {
+            reader.join().unwrap();
+        }
+        done.store(true, SeqCst);
+        writer.join().unwrap();
+
+        // Every reference the readers took has been given back.
+        for (tuple, _) in &tuples {
+            assert_eq!(sb.refcount(tuple.id()).unwrap(), 1);
+        }
+    }
+
+    // Tuples of a fixed-width relation go on fixed-size pages, which should hold the same tuples
+    // in fewer pages than the variable-size path, and give them all back when they're freed.
+    #[test]
+    fn test_fixed_width_relation() {
+        let fixed_pager = Arc::new(Pager::new(32768 * 64).unwrap());
+        let fixed_sb = Arc::new(TupleBox::new(fixed_pager));
+        fixed_sb.set_fixed_slot_size(RelationId(0), TupleRef::slot_size_for(16));
+        let var_pager = Arc::new(Pager::new(32768 * 64).unwrap());
+        let var_sb = Arc::new(TupleBox::new(var_pager));
+
+        let mut fixed_tuples = vec![];
+        let mut var_tuples = vec![];
+        for i in 0..10_000u64 {
+            let domain = i.to_le_bytes();
+            let codomain = (i * 2).to_le_bytes();
+            fixed_tuples.push(
+                TupleRef::allocate(RelationId(0), fixed_sb.clone(), i, &domain, &codomain).unwrap(),
+            );
+            var_tuples.push(
+                TupleRef::allocate(RelationId(0), var_sb.clone(), i, &domain, &codomain).unwrap(),
+            );
+        }
+        assert!(fixed_sb.num_pages() < var_sb.num_pages());
+
+        for (i, tuple) in fixed_tuples.iter().enumerate() {
+            let i = i as u64;
+            assert_eq!(tuple.ts(), i);
+            assert_eq!(tuple.domain().as_slice(), i.to_le_bytes());
+            assert_eq!(tuple.codomain().as_slice(), (i * 2).to_le_bytes());
+        }
+
+        // Free every other tuple, and the freed slots get reused before any new page is taken.
+        let pages = fixed_sb.num_pages();
+        let kept: Vec<_> = fixed_tuples
+            .into_iter()
+            .enumerate()
+            .filter_map(|(i, t)| (i % 2 == 0).then_some(t))
+            .collect();
+        let mut refilled = vec![];
+        for i in 0..5_000u64 {
+            let v = i.to_le_bytes();
+            refilled.push(TupleRef::allocate(RelationId(0), fixed_sb.clone(), 0, &v, &v).unwrap());
+        }
+        assert_eq!(fixed_sb.num_pages(), pages);
+        assert_eq!(kept[1].domain().as_slice(), 2u64.to_le_bytes());
+    }
+
+    // Relations with their own page size get pages of that size, and everything else the default.
+    #[test]
+    fn test_relation_page_sizes() {
+        let pager = Arc::new(Pager::new(32768 * 64).unwrap());
+        let sb = Arc::new(TupleBox::new(pager));
+        sb.set_page_size(RelationId(0), 4096);
+        sb.set_page_size(RelationId(1), 65536);
+
+        let mut tuples = vec![];
+        for i in 0..1000u64 {
+            let v = i.to_le_bytes();
+            for rid in 0..3 {
+                tuples.push(TupleRef::allocate(RelationId(rid), sb.clone(), i, &v, &v).unwrap());
+            }
+        }
+        // A tuple too big for its relation's pages still gets a page of its own.
+        let big = vec![1u8; 6000];
+        tuples.push(TupleRef::allocate(RelationId(0), sb.clone(), 0, &big, &big).unwrap());
+
+        let usage: std::collections::HashMap<_, _> = sb.space_usage().into_iter().collect();
+        let small = usage[&RelationId(0)];
+        assert_eq!(small.total_bytes, (small.pages - 1) * 4096 + 16384);
+        let large = usage[&RelationId(1)];
+        assert_eq!(large.total_bytes, large.pages * 65536);
+        let default = usage[&RelationId(2)];
+        assert_eq!(default.total_bytes, default.pages * 32768);
+        assert!(small.pages > default.pages);
+        assert!(large.pages < default.pages);
+    }
+
     #[test]
     fn alloc_encode_decode() {
         let pid = 12345;
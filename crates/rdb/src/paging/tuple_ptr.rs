@@ -13,11 +13,9 @@
 //
 
 use std::hash::Hash;
-use std::sync::atomic::AtomicPtr;
+use std::sync::atomic::{AtomicPtr, AtomicU64};
 use std::sync::Arc;
 
-use moor_values::util::ByteSource;
-
 use crate::paging::TupleBox;
 use crate::tuples::TupleId;
 
@@ -32,6 +30,9 @@ pub struct TuplePtr {
     id: TupleId,
     buflen: u32,
     bufaddr: AtomicPtr<u8>,
+    /// When this tuple was last read, according to its TupleBox's access clock. Used to pick which
+    /// page to evict when the box is full.
+    last_access: AtomicU64,
 
     _pin: std::marker::PhantomPinned,
 }
@@ -48,6 +49,7 @@ impl TuplePtr {
             id: tuple_id,
             bufaddr: AtomicPtr::new(bufaddr),
             buflen: buflen as u32,
+            last_access: AtomicU64::new(sb.tick()),
             _pin: std::marker::PhantomPinned,
         }
     }
@@ -75,50 +77,48 @@ impl TuplePtr {
 
     /// Mark the tuple as paged out. Accesses to the tuple will fault, and we'll need to page it back in.
     #[inline]
-    #[allow(dead_code)]
-    pub(crate) fn mark_paged_out(&mut self) {
+    pub(crate) fn mark_paged_out(&self) {
         self.bufaddr
             .store(std::ptr::null_mut(), std::sync::atomic::Ordering::SeqCst);
     }
 
-    #[allow(dead_code)]
-    pub(crate) fn mark_paged_in(&mut self, bufaddr: *mut u8) {
+    pub(crate) fn mark_paged_in(&self, bufaddr: *mut u8) {
         self.bufaddr
             .store(bufaddr, std::sync::atomic::Ordering::SeqCst);
     }
 
     #[inline]
-    pub(crate) fn as_ptr<T>(&self) -> *const T {
-        if self
-            .bufaddr
-            .load(std::sync::atomic::Ordering::SeqCst)
-            .is_null()
-        {
-            self.tb.page_fault(self.id).unwrap();
-        }
-        self.bufaddr.load(std::sync::atomic::Ordering::SeqCst) as *const T
+    pub(crate) fn last_access(&self) -> u64 {
+        self.last_access.load(std::sync::atomic::Ordering::Relaxed)
     }
 
+    /// Run `f` over the tuple's buffer, faulting its page back in first if it has been evicted,
+    /// and noting the access for the eviction policy. The page is pinned until `f` returns, so
+    /// the buffer mustn't be held on to past that.
     #[inline]
-    pub(crate) fn as_mut_ptr<T>(&mut self) -> *mut T {
-        // TODO: if the ptr is null, this is a page fault, and we'll
-        //   need to ask the tuplebox to ask the pager to page us in
-        self.bufaddr.load(std::sync::atomic::Ordering::SeqCst) as *mut T
+    pub(crate) fn with_buffer<R>(&self, f: impl FnOnce(&[u8]) -> R) -> R {
+        let buflen = self.buflen as usize;
+        self.with_bufaddr(|bufaddr| f(unsafe { std::slice::from_raw_parts(bufaddr, buflen) }))
     }
 
+    /// As `with_buffer`, but for writing to the tuple in place.
     #[inline]
-    pub(crate) fn buffer(&self) -> &[u8] {
-        let buf_addr = self.as_ptr();
-        unsafe { std::slice::from_raw_parts(buf_addr, self.buflen as usize) }
+    pub(crate) fn with_buffer_mut<R>(&mut self, f: impl FnOnce(&mut [u8]) -> R) -> R {
+        let buflen = self.buflen as usize;
+        self.with_bufaddr(|bufaddr| f(unsafe { std::slice::from_raw_parts_mut(bufaddr, buflen) }))
     }
 
     #[inline]
-    #[allow(dead_code)]
-    pub fn byte_source(&self) -> SlotByteSource {
-        SlotByteSource::new(self)
+    fn with_bufaddr<R>(&self, f: impl FnOnce(*mut u8) -> R) -> R {
+        self.last_access
+            .store(self.tb.tick(), std::sync::atomic::Ordering::Relaxed);
+        self.tb.with_pinned_page(self.id.page, || {
+            let bufaddr = self.bufaddr.load(std::sync::atomic::Ordering::SeqCst);
+            debug_assert!(!bufaddr.is_null(), "tuple pointer not swizzled in");
+            f(bufaddr)
+        })
     }
 
-    #[allow(dead_code)]
     pub fn is_paged_out(&self) -> bool {
         self.bufaddr
             .load(std::sync::atomic::Ordering::SeqCst)
@@ -140,52 +140,3 @@ impl TuplePtr {
         self.tb.dncount(self.id).unwrap();
     }
 }
-
-/// So we can build SliceRefs off of TuplePtrs
-pub struct SlotByteSource {
-    ptr: *const TuplePtr,
-}
-
-unsafe impl Send for SlotByteSource {}
-unsafe impl Sync for SlotByteSource {}
-
-impl SlotByteSource {
-    fn new(ptr: *const TuplePtr) -> Self {
-        // upcnt
-        let tp = unsafe { &(*ptr) };
-        tp.upcount();
-        SlotByteSource { ptr }
-    }
-}
-impl ByteSource for SlotByteSource {
-    #[inline]
-    fn as_slice(&self) -> &[u8] {
-        let buffer = (unsafe { &(*self.ptr) }).buffer();
-        buffer
-    }
-
-    #[inline]
-    fn len(&self) -> usize {
-        let buffer = (unsafe { &(*self.ptr) }).buffer();
-        buffer.len()
-    }
-
-    fn touch(&self) {}
-}
-
-impl Clone for SlotByteSource {
-    fn clone(&self) -> Self {
-        // Upcount
-        let tp = unsafe { &(*self.ptr) };
-        tp.upcount();
-        SlotByteSource { ptr: self.ptr }
-    }
-}
-
-impl Drop for SlotByteSource {
-    fn drop(&mut self) {
-        // Downcount
-        let tp = unsafe { &(*self.ptr) };
-        tp.dncount();
-    }
-}
@@ -15,6 +15,7 @@
 use binary_layout::{binary_layout, Field, LayoutAs};
 use okaywal::{Entry, EntryId, LogManager, SegmentReader, WriteAheadLog};
 use std::fmt::{Debug, Formatter};
+use std::io::ErrorKind;
 use std::sync::Arc;
 use strum::FromRepr;
 use thiserror::Error;
@@ -44,9 +45,19 @@ impl Debug for WalManager {
 
 impl LogManager for WalManager {
     fn recover(&mut self, entry: &mut Entry<'_>) -> std::io::Result<()> {
-        let Some(chunks) = entry.read_all_chunks()? else {
-            info!("No chunks found, nothing to recover");
-            return Ok(());
+        // An entry which didn't get completely written (we died in the middle of it) is a commit
+        // which never happened, and gets dropped whole.
+        let chunks = match entry.read_all_chunks() {
+            Ok(Some(chunks)) => chunks,
+            Ok(None) => {
+                info!("Discarding incompletely written WAL entry");
+                return Ok(());
+            }
+            Err(e) if e.kind() == ErrorKind::InvalidData => {
+                warn!(?e, "Discarding corrupt (torn?) WAL entry");
+                return Ok(());
+            }
+            Err(e) => return Err(e),
         };
         let mut write_batch = vec![];
         let mut evicted = vec![];
@@ -13,6 +13,7 @@
 //
 
 pub use buffer_pool::MmapBufferPool;
+pub(crate) use buffer_pool::{HIGHEST_SIZE_CLASS_POWER_OF, LOWEST_SIZE_CLASS_POWER_OF};
 use std::sync::atomic::AtomicPtr;
 
 mod buffer_pool;
@@ -43,6 +44,9 @@ pub enum BufferPoolError {
 
     #[error("Invalid page")]
     InvalidPage,
+
+    #[error("Could not move page to or from swap: {0}")]
+    SwapFailed(String),
 }
 
 pub trait BufferPool {
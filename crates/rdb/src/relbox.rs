@@ -18,9 +18,10 @@
 
 use crate::base_relation::BaseRelation;
 use crate::index::{AttrType, IndexType};
-use crate::paging::TupleBox;
+use crate::paging::{FitPolicy, SpaceUsage, SyncPolicy, TupleBox};
+use crate::tuples::TupleRef;
 use crate::tx::WorkingSet;
-use crate::tx::{CommitError, CommitSet, Transaction};
+use crate::tx::{CommitError, CommitSet, IsolationLevel, Transaction};
 use crate::RelationId;
 use std::fmt::Debug;
 use std::path::PathBuf;
@@ -46,6 +47,12 @@ pub struct RelationInfo {
     pub index_type: IndexType,
     /// Type of the codomain index (only used if `secondary_indexed` is true)
     pub codomain_index_type: Option<IndexType>,
+    /// If every tuple's domain and codomain together are always exactly this many bytes, its
+    /// tuples are packed into fixed-size pages, which is denser and cheaper to allocate in.
+    pub fixed_width: Option<usize>,
+    /// Size of the pages this relation's tuples go on, if not the default (32KiB). Relations of
+    /// large tuples waste less of each page on bigger pages; relations of small tuples, on smaller.
+    pub page_size: Option<usize>,
 }
 
 /// The "RelBox" is the set of relations, referenced by their unique (usize) relation ID.
@@ -96,6 +103,13 @@ impl RelBox {
         let tuple_box = Arc::new(TupleBox::new(pager.clone()));
         let mut base_relations = Vec::with_capacity(relations.len());
         for (rid, r) in relations.iter().enumerate() {
+            if let Some(fixed_width) = r.fixed_width {
+                tuple_box
+                    .set_fixed_slot_size(RelationId(rid), TupleRef::slot_size_for(fixed_width));
+            }
+            if let Some(page_size) = r.page_size {
+                tuple_box.set_page_size(RelationId(rid), page_size);
+            }
             base_relations.push(BaseRelation::new(RelationId(rid), r.clone(), 0));
         }
         let mut sequences = vec![0; num_sequences];
@@ -126,12 +140,17 @@ impl RelBox {
         self.relation_info.clone()
     }
 
-    /// Begin a transaction against the current canonical relations.
+    /// Begin a transaction against the current canonical relations, with snapshot isolation.
     pub fn start_tx(self: Arc<Self>) -> Transaction {
+        self.start_tx_with_isolation(IsolationLevel::Snapshot)
+    }
+
+    /// Begin a transaction against the current canonical relations, at the given isolation level.
+    pub fn start_tx_with_isolation(self: Arc<Self>, isolation: IsolationLevel) -> Transaction {
         let next_ts = self
             .maximum_transaction
             .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
-        Transaction::new(next_ts, self.tuple_box.clone(), self.clone())
+        Transaction::new(next_ts, self.tuple_box.clone(), self.clone(), isolation)
     }
 
     pub fn next_ts(self: Arc<Self>) -> u64 {
@@ -150,6 +169,11 @@ impl RelBox {
         self.sequences[sequence_number].load(std::sync::atomic::Ordering::SeqCst)
     }
 
+    /// Set the given sequence to `value`, even if that's lower than its current value.
+    pub fn set_sequence(self: Arc<Self>, sequence_number: usize, value: u64) {
+        self.sequences[sequence_number].store(value, std::sync::atomic::Ordering::SeqCst);
+    }
+
     /// Update the given sequence to `value` iff `value` is greater than the current value.
     pub fn update_sequence_max(self: Arc<Self>, sequence_number: usize, value: u64) {
         let sequence = &self.sequences[sequence_number];
@@ -169,7 +193,11 @@ impl RelBox {
         }
     }
 
-    pub fn with_relation<R, F: Fn(&BaseRelation) -> R>(&self, relation_id: RelationId, f: F) -> R {
+    pub fn with_relation<R, F: FnOnce(&BaseRelation) -> R>(
+        &self,
+        relation_id: RelationId,
+        f: F,
+    ) -> R {
         let rl = self.canonical.read().unwrap();
         f(rl.get(relation_id.0).unwrap())
     }
@@ -202,12 +230,28 @@ impl RelBox {
         self.tuple_box.used_bytes()
     }
 
+    /// Set how new tuples are fitted into relations' existing pages.
+    pub fn set_fit_policy(&self, fit_policy: FitPolicy) {
+        self.tuple_box.set_fit_policy(fit_policy)
+    }
+
+    /// Set whether commits wait for their write-ahead log entry to be synced to disk before
+    /// becoming visible.
+    pub fn set_sync_policy(&self, sync_policy: SyncPolicy) {
+        self.pager.set_sync_policy(sync_policy)
+    }
+
+    /// How much of each relation's pages is holding tuples, to watch for fragmentation.
+    pub fn relation_space_usage(&self) -> Vec<(RelationId, SpaceUsage)> {
+        self.tuple_box.space_usage()
+    }
+
     pub fn shutdown(&self) {
         self.pager.shutdown();
     }
 
-    /// Get a copy of the set of the database's canonical relations (generally used for
-    /// testing purposes only.)
+    /// Get a copy of the set of the database's canonical relations. Serializable transactions take
+    /// one of these when they start, to read from.
     pub fn copy_canonical(&self) -> Vec<BaseRelation> {
         self.canonical.read().unwrap().clone()
     }
@@ -18,6 +18,7 @@ use std::pin::Pin;
 use std::sync::Arc;
 
 use moor_values::util::SliceRef;
+use moor_values::{AsByteBuffer, DecodingError};
 
 use crate::paging::TuplePtr;
 use crate::paging::{TupleBox, TupleBoxError};
@@ -36,6 +37,19 @@ struct TupleHeader {
     codomain_size: u32,
 }
 
+impl TupleHeader {
+    /// The header at the start of a tuple's buffer.
+    #[inline]
+    fn of(buffer: &[u8]) -> &TupleHeader {
+        unsafe { &*(buffer.as_ptr() as *const TupleHeader) }
+    }
+
+    #[inline]
+    fn of_mut(buffer: &mut [u8]) -> &mut TupleHeader {
+        unsafe { &mut *(buffer.as_mut_ptr() as *mut TupleHeader) }
+    }
+}
+
 unsafe impl Send for TupleRef {}
 unsafe impl Sync for TupleRef {}
 impl TupleRef {
@@ -46,6 +60,12 @@ impl TupleRef {
         Self { sp }
     }
 
+    /// The size of the slot needed for a tuple whose domain and codomain together are
+    /// `value_width` bytes.
+    pub(crate) const fn slot_size_for(value_width: usize) -> usize {
+        std::mem::size_of::<TupleHeader>() + value_width
+    }
+
     /// Allocate the given tuple in a slotbox.
     pub fn allocate(
         relation_id: RelationId,
@@ -54,7 +74,7 @@ impl TupleRef {
         domain: &[u8],
         codomain: &[u8],
     ) -> Result<TupleRef, TupleBoxError> {
-        let total_size = std::mem::size_of::<TupleHeader>() + domain.len() + codomain.len();
+        let total_size = Self::slot_size_for(domain.len() + codomain.len());
         let tuple_ref = sb.clone().allocate(total_size, relation_id, None)?;
         sb.update_with(tuple_ref.id(), |mut buffer| {
             let domain_len = domain.len();
@@ -87,61 +107,58 @@ impl TupleRef {
     /// Update the timestamp of the tuple.
     #[inline]
     pub fn update_timestamp(&mut self, ts: u64) {
-        let header = self.header_mut();
-        header.ts = ts;
+        let slot_ptr = self.resolve_slot_ptr_mut();
+        unsafe { slot_ptr.get_unchecked_mut() }
+            .with_buffer_mut(|buffer| TupleHeader::of_mut(buffer).ts = ts);
     }
 
     /// The timestamp of the tuple.
     #[inline]
     pub fn ts(&self) -> u64 {
-        let header = self.header();
-        header.ts
+        self.resolve_slot_ptr()
+            .with_buffer(|buffer| TupleHeader::of(buffer).ts)
     }
 
     /// The domain of the tuple.
     #[inline]
     pub fn domain(&self) -> SliceRef {
-        let header = self.header();
-        let domain_size = header.domain_size as usize;
-        let buffer = self.slot_buffer();
-        let domain_start = std::mem::size_of::<TupleHeader>();
-        buffer.slice(domain_start..domain_start + domain_size)
+        self.resolve_slot_ptr().with_buffer(|buffer| {
+            let header = TupleHeader::of(buffer);
+            let domain_start = std::mem::size_of::<TupleHeader>();
+            let domain_end = domain_start + header.domain_size as usize;
+            SliceRef::from_vec(buffer[domain_start..domain_end].to_vec())
+        })
     }
 
     /// The codomain of the tuple.
     #[inline]
     pub fn codomain(&self) -> SliceRef {
-        let header = self.header();
-        let domain_size = header.domain_size as usize;
-        let codomain_size = header.codomain_size as usize;
-        let buffer = self.slot_buffer();
-        let codomain_start = std::mem::size_of::<TupleHeader>() + domain_size;
-        buffer.slice(codomain_start..codomain_start + codomain_size)
+        self.resolve_slot_ptr().with_buffer(|buffer| {
+            let header = TupleHeader::of(buffer);
+            let codomain_start = std::mem::size_of::<TupleHeader>() + header.domain_size as usize;
+            let codomain_end = codomain_start + header.codomain_size as usize;
+            SliceRef::from_vec(buffer[codomain_start..codomain_end].to_vec())
+        })
     }
 
-    /// The raw buffer of the tuple, including the header, not dividing up the domain and codomain.
-    pub fn slot_buffer(&self) -> SliceRef {
-        let slot_ptr = self.resolve_slot_ptr();
-        let buffer = slot_ptr.buffer();
-        SliceRef::from_vec(buffer.to_vec())
+    /// The domain of the tuple, decoded as the type it was stored as.
+    pub fn domain_as<T: AsByteBuffer>(&self) -> Result<T, DecodingError> {
+        T::from_sliceref(self.domain())
     }
-}
 
-impl TupleRef {
-    #[inline]
-    fn header(&self) -> &TupleHeader {
-        let slot_ptr = self.resolve_slot_ptr();
-        let header: *const TupleHeader = slot_ptr.as_ptr();
-        unsafe { &*header }
+    /// The codomain of the tuple, decoded as the type it was stored as.
+    pub fn codomain_as<T: AsByteBuffer>(&self) -> Result<T, DecodingError> {
+        T::from_sliceref(self.codomain())
     }
 
-    #[inline]
-    fn header_mut(&mut self) -> &mut TupleHeader {
-        let slot_ptr = self.resolve_slot_ptr_mut();
-        let header: *mut TupleHeader = unsafe { slot_ptr.get_unchecked_mut() }.as_mut_ptr();
-        unsafe { &mut *header }
+    /// The raw buffer of the tuple, including the header, not dividing up the domain and codomain.
+    pub fn slot_buffer(&self) -> SliceRef {
+        self.resolve_slot_ptr()
+            .with_buffer(|buffer| SliceRef::from_vec(buffer.to_vec()))
     }
+}
 
+impl TupleRef {
     #[inline]
     fn resolve_slot_ptr(&self) -> Pin<&TuplePtr> {
         unsafe { Pin::new_unchecked(&*self.sp) }
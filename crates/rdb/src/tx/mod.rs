@@ -12,7 +12,7 @@
 // this program. If not, see <https://www.gnu.org/licenses/>.
 //
 
-pub use transaction::{CommitError, CommitSet, Transaction};
+pub use transaction::{CommitError, CommitSet, IsolationLevel, Savepoint, Transaction};
 pub use working_set::WorkingSet;
 
 mod relvar;
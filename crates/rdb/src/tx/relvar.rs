@@ -13,6 +13,7 @@
 //
 
 use std::collections::HashSet;
+use std::ops::Bound;
 
 use moor_values::util::SliceRef;
 
@@ -50,6 +51,16 @@ impl<'a> RelVar<'a> {
         self.tx.insert_tuple(self.id, domain, codomain)
     }
 
+    /// Insert a batch of tuples into the relation, taking the relation lock once rather than per
+    /// tuple. Returns how many were inserted; on failure, the first error and how many were
+    /// inserted before it.
+    pub fn insert_tuples<I: Iterator<Item = (SliceRef, SliceRef)>>(
+        &self,
+        tuples: I,
+    ) -> Result<usize, (RelationError, usize)> {
+        self.tx.insert_tuples(self.id, tuples)
+    }
+
     /// Update a tuple in the relation.
     pub fn update_by_domain(
         &self,
@@ -73,6 +84,27 @@ impl<'a> RelVar<'a> {
         self.tx.remove_by_domain(self.id, domain)
     }
 
+    /// The tuples whose domain falls between `start` and `end`, in domain order (that is, the byte
+    /// order of the domain values).
+    pub fn scan_range(
+        &self,
+        start: Bound<SliceRef>,
+        end: Bound<SliceRef>,
+    ) -> Result<Vec<TupleRef>, RelationError> {
+        self.tx.scan_range(self.id, start, end)
+    }
+
+    /// The number of tuples in the relation, as seen by this transaction. Cheaper than counting a
+    /// `predicate_scan`, as no tuples are collected.
+    pub fn count(&self) -> Result<usize, RelationError> {
+        self.tx.count(self.id)
+    }
+
+    /// Whether the relation has no tuples, as seen by this transaction.
+    pub fn is_empty(&self) -> Result<bool, RelationError> {
+        Ok(self.count()? == 0)
+    }
+
     pub fn predicate_scan<F: Fn(&TupleRef) -> bool>(
         &self,
         f: &F,
@@ -14,8 +14,8 @@
 
 use std::cell::RefCell;
 use std::collections::HashSet;
+use std::ops::Bound;
 use std::sync::{Arc, RwLockWriteGuard};
-use std::thread::yield_now;
 
 use thiserror::Error;
 
@@ -28,9 +28,31 @@ use crate::relbox::RelBox;
 use crate::tuples::TupleRef;
 use crate::tx::relvar::RelVar;
 use crate::tx::tx_tuple::TxTupleOp;
-use crate::tx::working_set::WorkingSet;
+use crate::tx::working_set::{TxBaseRelation, WorkingSet};
 use crate::{RelationError, RelationId};
 
+/// How far a transaction is protected from the effects of others running at the same time.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub enum IsolationLevel {
+    /// The transaction reads each tuple as it is the first time it touches it, and keeps seeing
+    /// that version after, and fails to commit if anything it wrote was changed by another
+    /// transaction in the meantime. That falls short of snapshot isolation: its reads of
+    /// different tuples can straddle another transaction's commit (read skew). And two
+    /// transactions that each read what the other writes can both commit (write skew), leaving
+    /// a state that no serial ordering of them could have produced.
+    #[default]
+    Snapshot,
+    /// The transaction takes a copy of the canonical relations when it starts and reads only
+    /// from that, so everything it reads is from one moment. It also remembers what it read, and
+    /// fails to commit if any of that was changed by a concurrent commit, so that the outcome is
+    /// always as if it ran alone, after everything that committed before it. Domain lookups are
+    /// tracked per domain and range scans per range, so they conflict only with writes
+    /// (including inserts) inside what was read. Predicate scans, counts and codomain lookups
+    /// cover the whole relation, so a
+    /// transaction which does those conflicts with any write to that relation.
+    Serializable,
+}
+
 /// A versioned transaction, which is a fork of the current canonical base relations.
 pub struct Transaction {
     /// Where we came from, for referencing back to the base relations.
@@ -44,12 +66,27 @@ pub struct Transaction {
     unsync: PhantomUnsync,
 }
 
+/// A point in a transaction that it can later be rolled back to with `Transaction::rollback_to`,
+/// undoing everything done since without abandoning the transaction as a whole.
+pub struct Savepoint {
+    /// The timestamp of the transaction the savepoint was taken in.
+    ts: u64,
+    relations: Vec<(usize, TxBaseRelation)>,
+    /// The relations' tuples live in the db's pages, so it has to outlive them, even if the
+    /// savepoint outlives its transaction.
+    _db: Arc<RelBox>,
+}
+
 /// Errors which can occur during a commit.
 #[derive(Debug, Clone, Eq, PartialEq, Error)]
 pub enum CommitError {
     /// A version conflict was detected during the preparation of the commit set.
-    #[error("Version conflict")]
-    TupleVersionConflict,
+    /// Carries the relation and domain of the first tuple found to be in conflict.
+    #[error("Version conflict in relation {relation:?} on domain {domain:?}")]
+    TupleVersionConflict {
+        relation: RelationId,
+        domain: SliceRef,
+    },
     /// Multiple writers attempted to modify the same tuple at the same time, and our validated
     /// commit set was potentially invalidated by a concurrent commit.
     #[error("Relation contention conflict")]
@@ -57,13 +94,29 @@ pub enum CommitError {
     /// A unique constraint violation was detected during the preparation of the commit set.
     /// This can happen when the transaction has prepared an insert into a relation that has already been
     /// inserted into for the same unique domain.
-    #[error("Unique constraint violation")]
-    UniqueConstraintViolation,
+    #[error("Unique constraint violation in relation {relation:?} on domain {domain:?}")]
+    UniqueConstraintViolation {
+        relation: RelationId,
+        domain: SliceRef,
+    },
+    /// A serializable transaction read something in this relation which a concurrent commit has
+    /// since changed.
+    #[error("Serialization conflict on a read of relation {relation:?}")]
+    SerializationConflict { relation: RelationId },
 }
 
 impl Transaction {
-    pub fn new(ts: u64, slotbox: Arc<TupleBox>, db: Arc<RelBox>) -> Self {
-        let ws = WorkingSet::new(slotbox.clone(), &db.relation_info(), ts);
+    pub fn new(
+        ts: u64,
+        slotbox: Arc<TupleBox>,
+        db: Arc<RelBox>,
+        isolation: IsolationLevel,
+    ) -> Self {
+        let base = match isolation {
+            IsolationLevel::Snapshot => None,
+            IsolationLevel::Serializable => Some(db.copy_canonical()),
+        };
+        let ws = WorkingSet::new(slotbox.clone(), &db.relation_info(), base, ts, isolation);
 
         Self {
             db,
@@ -83,41 +136,34 @@ impl Transaction {
     pub fn update_sequence_max(&self, sequence_number: usize, value: u64) {
         self.db.clone().update_sequence_max(sequence_number, value)
     }
+    pub fn set_sequence(&self, sequence_number: usize, value: u64) {
+        self.db.clone().set_sequence(sequence_number, value)
+    }
     pub fn commit(&self) -> Result<(), CommitError> {
-        let mut tries = 0;
-        'retry: loop {
-            tries += 1;
-            let commit_ts = self.db.clone().next_ts();
-            let mut working_set = self.working_set.borrow_mut();
-            let commit_set = self
-                .db
-                .prepare_commit_set(commit_ts, working_set.as_mut().unwrap())?;
-            match commit_set.try_commit() {
-                Ok(()) => {
-                    let working_set = working_set.take().unwrap();
-                    self.db.sync(commit_ts, working_set);
-                    return Ok(());
-                }
-                Err(CommitError::RelationContentionConflict) => {
-                    if tries > 50 {
-                        return Err(CommitError::RelationContentionConflict);
-                    } else {
-                        // Release the lock, pause a bit, and retry the commit set.
-                        yield_now();
-                        continue 'retry;
-                    }
-                }
-                Err(e) => {
-                    return Err(e);
-                }
-            }
-        }
+        let commit_ts = self.db.clone().next_ts();
+        let mut working_set = self.working_set.borrow_mut();
+        let commit_set = self
+            .db
+            .prepare_commit_set(commit_ts, working_set.as_mut().unwrap())?;
+
+        // The commit set holds the canonical write lock, so nothing else commits until we're done.
+        // Get the commit into the write-ahead log before swapping it in, so that (depending on the
+        // sync policy) nothing can see it before it would survive a crash.
+        let working_set = working_set.take().unwrap();
+        self.db.sync(commit_ts, working_set);
+        commit_set.try_commit()
     }
 
     pub fn db_usage_bytes(&self) -> usize {
         self.db.db_usage_bytes()
     }
 
+    /// The timestamp of this transaction. It sees what was committed before it started, and its
+    /// own tuples carry this timestamp once committed.
+    pub fn ts(&self) -> u64 {
+        self.working_set.borrow().as_ref().unwrap().ts
+    }
+
     pub fn rollback(&self) -> Result<(), CommitError> {
         let Some(mut ws) = self.working_set.borrow_mut().take() else {
             return Ok(());
@@ -126,6 +172,32 @@ impl Transaction {
         Ok(())
     }
 
+    /// Mark the current state of the transaction, so that later changes can be undone with
+    /// `rollback_to`.
+    /// Sequences are shared with other transactions and are not rolled back.
+    pub fn savepoint(&self) -> Savepoint {
+        let ws = self.working_set.borrow();
+        let ws = ws.as_ref().unwrap();
+        Savepoint {
+            ts: ws.ts,
+            relations: ws.snapshot(),
+            _db: self.db.clone(),
+        }
+    }
+
+    /// Undo everything done in this transaction since `savepoint` was taken. The savepoint stays
+    /// valid, so it can be rolled back to more than once.
+    /// Fails with `ForeignSavepoint` if the savepoint was taken in some other transaction.
+    pub fn rollback_to(&self, savepoint: &Savepoint) -> Result<(), RelationError> {
+        let mut ws = self.working_set.borrow_mut();
+        let ws = ws.as_mut().unwrap();
+        if ws.ts != savepoint.ts {
+            return Err(RelationError::ForeignSavepoint);
+        }
+        ws.restore(&savepoint.relations);
+        Ok(())
+    }
+
     /// Grab a handle to a relation, which can be used to perform operations on it in the context
     /// of this transaction.
     pub fn relation(&self, relation_id: RelationId) -> RelVar {
@@ -187,6 +259,19 @@ impl Transaction {
             .insert_tuple(&self.db, relation_id, domain, codomain)
     }
 
+    /// Insert a batch of tuples in one go, for bulk loading. On failure, returns the first error
+    /// along with how many tuples were inserted before it.
+    pub(crate) fn insert_tuples<I: Iterator<Item = (SliceRef, SliceRef)>>(
+        &self,
+        relation_id: RelationId,
+        tuples: I,
+    ) -> Result<usize, (RelationError, usize)> {
+        let mut ws = self.working_set.borrow_mut();
+        ws.as_mut()
+            .unwrap()
+            .insert_tuples(&self.db, relation_id, tuples)
+    }
+
     pub(crate) fn predicate_scan<F: Fn(&TupleRef) -> bool>(
         &self,
         relation_id: RelationId,
@@ -198,6 +283,30 @@ impl Transaction {
             .predicate_scan(&self.db, relation_id, f)
     }
 
+    /// The number of tuples in the relation, including this transaction's own uncommitted inserts
+    /// and removes.
+    pub(crate) fn count(&self, relation_id: RelationId) -> Result<usize, RelationError> {
+        let mut ws = self.working_set.borrow_mut();
+        ws.as_mut().unwrap().count(&self.db, relation_id)
+    }
+
+    /// The tuples whose domain falls between `start` and `end`, in domain order. Domains are
+    /// ordered by their bytes, as in the ordered indexes.
+    pub(crate) fn scan_range(
+        &self,
+        relation_id: RelationId,
+        start: Bound<SliceRef>,
+        end: Bound<SliceRef>,
+    ) -> Result<Vec<TupleRef>, RelationError> {
+        let mut ws = self.working_set.borrow_mut();
+        let mut tuples = ws
+            .as_mut()
+            .unwrap()
+            .scan_range(&self.db, relation_id, (start, end))?;
+        tuples.sort_by_key(|t| t.domain());
+        Ok(tuples)
+    }
+
     /// Attempt to update a tuple in the transaction's working set, with the intent of eventually
     /// committing it to the canonical base relations.
     pub(crate) fn update_by_domain(
@@ -266,6 +375,7 @@ impl<'a> CommitSet<'a> {
     }
 
     pub(crate) fn prepare(&mut self, tx_working_set: &mut WorkingSet) -> Result<(), CommitError> {
+        tx_working_set.validate_reads(&self.write_guard)?;
         for (_, local_relation) in tx_working_set.relations.iter_mut() {
             let relation_id = local_relation.id;
             // scan through the local working set, and for each tuple, check to see if it's safe to
@@ -286,14 +396,20 @@ impl<'a> CommitSet<'a> {
                         let mut replacements = im::HashSet::new();
                         for t in results_canonical {
                             if canonical.info.unique_domain && t.ts() > tuple.ts() {
-                                return Err(CommitError::UniqueConstraintViolation);
+                                return Err(CommitError::UniqueConstraintViolation {
+                                    relation: relation_id,
+                                    domain: tuple.domain(),
+                                });
                             }
                             // Check the timestamp on the upstream value, if it's newer than the read-timestamp,
                             // we have for this tuple then that's a conflict, because it means someone else has
                             // already committed a change to this tuple.
                             // Otherwise, we clobber their value.
                             if t.ts() > tuple.ts() {
-                                return Err(CommitError::TupleVersionConflict);
+                                return Err(CommitError::TupleVersionConflict {
+                                    relation: relation_id,
+                                    domain: tuple.domain(),
+                                });
                             }
                             replacements.insert(t);
                         }
@@ -317,7 +433,10 @@ impl<'a> CommitSet<'a> {
                         if !canonical.has_tuple(&old_tuple.id()) {
                             // Someone got here first and deleted the tuple we're trying to update.
                             // By definition, this is a conflict.
-                            return Err(CommitError::TupleVersionConflict);
+                            return Err(CommitError::TupleVersionConflict {
+                                relation: relation_id,
+                                domain: old_tuple.domain(),
+                            });
                         };
 
                         // TODO tuple uniqueness constraint check?
@@ -343,7 +462,10 @@ impl<'a> CommitSet<'a> {
                                     .expect("failed to seek for constraints check");
                                 for t in results_canonical {
                                     if t.ts() > tuple.ts() {
-                                        return Err(CommitError::UniqueConstraintViolation);
+                                        return Err(CommitError::UniqueConstraintViolation {
+                                            relation: relation_id,
+                                            domain: tuple.domain(),
+                                        });
                                     }
                                 }
                             }
@@ -389,17 +511,21 @@ impl<'a> CommitSet<'a> {
 
 #[cfg(test)]
 mod tests {
+    use std::ops::Bound;
     use std::sync::Arc;
 
     use rand::Rng;
 
+    use moor_values::model::{ArgSpec, PrepSpec, VerbArgsSpec};
     use moor_values::util::SliceRef;
+    use moor_values::var::Objid;
+    use moor_values::AsByteBuffer;
 
     use crate::index::{AttrType, IndexType};
     use crate::relbox::{RelBox, RelationInfo};
     use crate::tuples::TupleRef;
     use crate::tx::transaction::CommitError;
-    use crate::{RelationError, RelationId, Transaction};
+    use crate::{IsolationLevel, RelationError, RelationId, Transaction};
 
     fn attr(slice: &[u8]) -> SliceRef {
         SliceRef::from_bytes(slice)
@@ -422,6 +548,8 @@ mod tests {
                     unique_domain: true,
                     index_type: IndexType::Hash,
                     codomain_index_type: Some(IndexType::Hash),
+                    fixed_width: None,
+                    page_size: None,
                 },
                 RelationInfo {
                     name: "test2".to_string(),
@@ -431,12 +559,259 @@ mod tests {
                     unique_domain: true,
                     index_type: IndexType::AdaptiveRadixTree,
                     codomain_index_type: None,
+                    fixed_width: None,
+                    page_size: None,
                 },
             ],
             0,
         )
     }
 
+    /// Values stored through `AsByteBuffer` come back out of tuples as their own types.
+    #[test]
+    fn typed_domain_and_codomain() {
+        let db = test_db();
+        let rid = RelationId(0);
+        let argspec = VerbArgsSpec {
+            dobj: ArgSpec::This,
+            prep: PrepSpec::Any,
+            iobj: ArgSpec::None,
+        };
+        let tx = db.clone().start_tx();
+        tx.insert_tuple(
+            rid,
+            Objid(42).as_sliceref().unwrap(),
+            argspec.as_sliceref().unwrap(),
+        )
+        .unwrap();
+        tx.commit().unwrap();
+
+        let tx = db.clone().start_tx();
+        let t = tx
+            .seek_unique_by_domain(rid, Objid(42).as_sliceref().unwrap())
+            .unwrap();
+        assert_eq!(t.domain_as::<Objid>().unwrap(), Objid(42));
+        assert_eq!(t.codomain_as::<VerbArgsSpec>().unwrap(), argspec);
+    }
+
+    /// A transaction reads "a", then another commits changes to both "a" and "b", then the first
+    /// reads "b". Only a serializable one still sees "b" as it was when it started (and then
+    /// can't commit, having read something that's since changed).
+    #[test]
+    fn read_skew_only_under_snapshot() {
+        let read_skew = |isolation| {
+            let db = test_db();
+            let rid = RelationId(0);
+            let tx = db.clone().start_tx();
+            tx.insert_tuple(rid, attr(b"a"), attr(b"1")).unwrap();
+            tx.insert_tuple(rid, attr(b"b"), attr(b"1")).unwrap();
+            tx.commit().unwrap();
+
+            let reader = db.clone().start_tx_with_isolation(isolation);
+            assert_eq!(
+                reader
+                    .seek_unique_by_domain(rid, attr(b"a"))
+                    .unwrap()
+                    .codomain(),
+                attr(b"1")
+            );
+
+            let writer = db.clone().start_tx();
+            writer
+                .update_by_domain(rid, attr(b"a"), attr(b"2"))
+                .unwrap();
+            writer
+                .update_by_domain(rid, attr(b"b"), attr(b"2"))
+                .unwrap();
+            writer.commit().unwrap();
+
+            let b = reader
+                .seek_unique_by_domain(rid, attr(b"b"))
+                .unwrap()
+                .codomain();
+            (b, reader.commit())
+        };
+
+        assert_eq!(read_skew(IsolationLevel::Snapshot), (attr(b"2"), Ok(())));
+        assert_eq!(
+            read_skew(IsolationLevel::Serializable),
+            (
+                attr(b"1"),
+                Err(CommitError::SerializationConflict {
+                    relation: RelationId(0)
+                })
+            )
+        );
+    }
+
+    /// Classic write skew: each of two concurrent transactions reads both "a" and "b", and
+    /// updates a different one of them based on what it read. Snapshot isolation lets both
+    /// commit, even though neither would have written what it did had it run after the other.
+    /// Serializable rejects whichever commits second.
+    #[test]
+    fn write_skew_rejected_when_serializable() {
+        let write_skew = |isolation| {
+            let db = test_db();
+            let rid = RelationId(0);
+            let tx = db.clone().start_tx();
+            tx.insert_tuple(rid, attr(b"a"), attr(b"1")).unwrap();
+            tx.insert_tuple(rid, attr(b"b"), attr(b"1")).unwrap();
+            tx.commit().unwrap();
+
+            let t1 = db.clone().start_tx_with_isolation(isolation);
+            let t2 = db.clone().start_tx_with_isolation(isolation);
+            for tx in [&t1, &t2] {
+                tx.seek_unique_by_domain(rid, attr(b"a")).unwrap();
+                tx.seek_unique_by_domain(rid, attr(b"b")).unwrap();
+            }
+            t1.update_by_domain(rid, attr(b"a"), attr(b"0")).unwrap();
+            t2.update_by_domain(rid, attr(b"b"), attr(b"0")).unwrap();
+            t1.commit().unwrap();
+            t2.commit()
+        };
+
+        assert_eq!(write_skew(IsolationLevel::Snapshot), Ok(()));
+        assert_eq!(
+            write_skew(IsolationLevel::Serializable),
+            Err(CommitError::SerializationConflict {
+                relation: RelationId(0)
+            })
+        );
+    }
+
+    /// A serializable transaction only conflicts on what it read: domain lookups on the domains
+    /// looked up, scans on the whole relation.
+    #[test]
+    fn serializable_read_tracking() {
+        let db = test_db();
+        let rid = RelationId(0);
+        let tx = db.clone().start_tx();
+        tx.insert_tuple(rid, attr(b"a"), attr(b"1")).unwrap();
+        tx.insert_tuple(rid, attr(b"b"), attr(b"1")).unwrap();
+        tx.commit().unwrap();
+
+        let looker = db
+            .clone()
+            .start_tx_with_isolation(IsolationLevel::Serializable);
+        let scanner = db
+            .clone()
+            .start_tx_with_isolation(IsolationLevel::Serializable);
+        looker.seek_unique_by_domain(rid, attr(b"a")).unwrap();
+        looker.insert_tuple(rid, attr(b"c"), attr(b"1")).unwrap();
+        assert_eq!(scanner.predicate_scan(rid, &|_| true).unwrap().len(), 2);
+        scanner.insert_tuple(rid, attr(b"d"), attr(b"1")).unwrap();
+
+        let writer = db.clone().start_tx();
+        writer
+            .update_by_domain(rid, attr(b"b"), attr(b"2"))
+            .unwrap();
+        writer.commit().unwrap();
+
+        looker.commit().unwrap();
+        assert_eq!(
+            scanner.commit(),
+            Err(CommitError::SerializationConflict { relation: rid })
+        );
+    }
+
+    /// A phantom: a transaction reads everything matching a predicate, and another concurrently
+    /// inserts a new tuple which matches it. Snapshot isolation doesn't notice, as the two wrote
+    /// different tuples; serializable does, as the reader's scan no longer holds.
+    #[test]
+    fn phantom_insert_rejected_when_serializable() {
+        let phantom = |isolation| {
+            let db = test_db();
+            let rid = RelationId(0);
+            let tx = db.clone().start_tx();
+            tx.insert_tuple(rid, attr(b"a"), attr(b"on")).unwrap();
+            tx.commit().unwrap();
+
+            let reader = db.clone().start_tx_with_isolation(isolation);
+            let on = reader
+                .predicate_scan(rid, &|t| t.codomain() == attr(b"on"))
+                .unwrap();
+            assert_eq!(on.len(), 1);
+            reader
+                .insert_tuple(rid, attr(b"count"), attr(b"1"))
+                .unwrap();
+
+            let inserter = db.clone().start_tx();
+            inserter.insert_tuple(rid, attr(b"b"), attr(b"on")).unwrap();
+            inserter.commit().unwrap();
+
+            reader.commit()
+        };
+
+        assert_eq!(phantom(IsolationLevel::Snapshot), Ok(()));
+        assert_eq!(
+            phantom(IsolationLevel::Serializable),
+            Err(CommitError::SerializationConflict {
+                relation: RelationId(0)
+            })
+        );
+    }
+
+    /// A range scan only conflicts with concurrent inserts into the range, not elsewhere in the
+    /// relation.
+    #[test]
+    fn serializable_range_reads() {
+        let db = test_db();
+        let rid = RelationId(1);
+        let tx = db.clone().start_tx();
+        for i in [1, 2, 8] {
+            tx.insert_tuple(rid, attr2(i), attr(b"x")).unwrap();
+        }
+        tx.commit().unwrap();
+
+        let scan = |db: &Arc<RelBox>, marker| {
+            let tx = db
+                .clone()
+                .start_tx_with_isolation(IsolationLevel::Serializable);
+            let found = tx
+                .scan_range(rid, Bound::Included(attr2(1)), Bound::Included(attr2(4)))
+                .unwrap();
+            assert_eq!(found.len(), 2);
+            tx.insert_tuple(rid, attr2(marker), attr(b"y")).unwrap();
+            tx
+        };
+        let insert = |db: &Arc<RelBox>, i| {
+            let tx = db.clone().start_tx();
+            tx.insert_tuple(rid, attr2(i), attr(b"x")).unwrap();
+            tx.commit().unwrap();
+        };
+
+        let outside = scan(&db, 100);
+        insert(&db, 9);
+        outside.commit().unwrap();
+
+        let inside = scan(&db, 101);
+        insert(&db, 3);
+        assert_eq!(
+            inside.commit(),
+            Err(CommitError::SerializationConflict { relation: rid })
+        );
+
+        // Taking something out of the range counts as much as putting something in.
+        let removed = db
+            .clone()
+            .start_tx_with_isolation(IsolationLevel::Serializable);
+        assert_eq!(
+            removed
+                .scan_range(rid, Bound::Included(attr2(1)), Bound::Included(attr2(4)))
+                .unwrap()
+                .len(),
+            3
+        );
+        removed.insert_tuple(rid, attr2(102), attr(b"y")).unwrap();
+        let tx = db.clone().start_tx();
+        tx.remove_by_domain(rid, attr2(2)).unwrap();
+        tx.commit().unwrap();
+        assert_eq!(
+            removed.commit(),
+            Err(CommitError::SerializationConflict { relation: rid })
+        );
+    }
+
     /// Verifies that base relations ("canonical") get updated when successful commits happen.
     #[test]
     fn basic_commit() {
@@ -604,6 +979,211 @@ mod tests {
         }
     }
 
+    /// Rolling back to a savepoint undoes later changes but keeps the earlier ones.
+    #[test]
+    fn savepoint_rollback() {
+        let db = test_db();
+        let tx = db.clone().start_tx();
+        let rid = RelationId(0);
+        tx.insert_tuple(rid, attr(b"abc"), attr(b"def")).unwrap();
+        tx.insert_tuple(rid, attr(b"zzz"), attr(b"def")).unwrap();
+
+        let savepoint = tx.savepoint();
+        tx.update_by_domain(rid, attr(b"abc"), attr(b"123"))
+            .unwrap();
+        tx.remove_by_domain(rid, attr(b"zzz")).unwrap();
+        tx.insert_tuple(rid, attr(b"ghi"), attr(b"def")).unwrap();
+        tx.insert_tuple(RelationId(1), attr2(1), attr(b"one"))
+            .unwrap();
+
+        tx.rollback_to(&savepoint).unwrap();
+        let values = |codomain: &[u8]| {
+            let mut domains: Vec<_> = tx
+                .seek_by_codomain(rid, attr(codomain))
+                .unwrap()
+                .iter()
+                .map(|t| t.domain())
+                .collect();
+            domains.sort();
+            domains
+        };
+        assert_eq!(values(b"def"), vec![attr(b"abc"), attr(b"zzz")]);
+        assert!(values(b"123").is_empty());
+        assert_eq!(
+            tx.seek_unique_by_domain(rid, attr(b"ghi")),
+            Err(RelationError::TupleNotFound)
+        );
+        assert_eq!(
+            tx.seek_unique_by_domain(RelationId(1), attr2(1)),
+            Err(RelationError::TupleNotFound)
+        );
+
+        // The savepoint is still good, and the transaction carries on as normal from it.
+        tx.insert_tuple(rid, attr(b"ghi"), attr(b"jkl")).unwrap();
+        tx.rollback_to(&savepoint).unwrap();
+        tx.insert_tuple(rid, attr(b"ghi"), attr(b"mno")).unwrap();
+        tx.commit().unwrap();
+
+        let tx = db.clone().start_tx();
+        let committed = |domain: &[u8]| {
+            tx.seek_unique_by_domain(rid, attr(domain))
+                .map(|t| t.codomain())
+        };
+        assert_eq!(committed(b"abc"), Ok(attr(b"def")));
+        assert_eq!(committed(b"zzz"), Ok(attr(b"def")));
+        assert_eq!(committed(b"ghi"), Ok(attr(b"mno")));
+        assert_eq!(
+            tx.seek_unique_by_domain(RelationId(1), attr2(1)),
+            Err(RelationError::TupleNotFound)
+        );
+    }
+
+    /// Savepoints nest: rolling back to an inner one keeps what was done before it, and then
+    /// rolling back to an outer one undoes the rest.
+    #[test]
+    fn nested_savepoints() {
+        let db = test_db();
+        let rid = RelationId(0);
+        let tx = db.clone().start_tx();
+        tx.insert_tuple(rid, attr(b"a"), attr(b"v")).unwrap();
+        let outer = tx.savepoint();
+        tx.insert_tuple(rid, attr(b"b"), attr(b"v")).unwrap();
+        let inner = tx.savepoint();
+        tx.insert_tuple(rid, attr(b"c"), attr(b"v")).unwrap();
+
+        let domains = |tx: &Transaction| {
+            let mut domains: Vec<_> = tx
+                .seek_by_codomain(rid, attr(b"v"))
+                .unwrap()
+                .iter()
+                .map(|t| t.domain())
+                .collect();
+            domains.sort();
+            domains
+        };
+        tx.rollback_to(&inner).unwrap();
+        assert_eq!(domains(&tx), vec![attr(b"a"), attr(b"b")]);
+        tx.rollback_to(&outer).unwrap();
+        assert_eq!(domains(&tx), vec![attr(b"a")]);
+        tx.commit().unwrap();
+
+        let tx = db.clone().start_tx();
+        assert_eq!(domains(&tx), vec![attr(b"a")]);
+    }
+
+    /// A savepoint from one transaction can't be used to roll back another.
+    #[test]
+    fn foreign_savepoint_rejected() {
+        let db = test_db();
+        let rid = RelationId(0);
+        let tx_a = db.clone().start_tx();
+        let tx_b = db.clone().start_tx();
+        let savepoint = tx_a.savepoint();
+        tx_b.insert_tuple(rid, attr(b"abc"), attr(b"def")).unwrap();
+        assert_eq!(
+            tx_b.rollback_to(&savepoint),
+            Err(RelationError::ForeignSavepoint)
+        );
+        assert_eq!(
+            tx_b.seek_unique_by_domain(rid, attr(b"abc"))
+                .map(|t| t.codomain()),
+            Ok(attr(b"def"))
+        );
+    }
+
+    /// Secondary (codomain) index changes are as private to a transaction as the tuples
+    /// themselves: another transaction doesn't see them before commit, and a rollback leaves
+    /// nothing behind in the index.
+    #[test]
+    fn codomain_index_isolation() {
+        let db = test_db();
+        let rid = RelationId(0);
+        let tx = db.clone().start_tx();
+        tx.insert_tuple(rid, attr(b"abc"), attr(b"def")).unwrap();
+        tx.commit().unwrap();
+
+        let t1 = db.clone().start_tx();
+        let t2 = db.clone().start_tx();
+        t1.update_by_domain(rid, attr(b"abc"), attr(b"123"))
+            .unwrap();
+        t1.insert_tuple(rid, attr(b"ghi"), attr(b"123")).unwrap();
+        assert_eq!(t1.seek_by_codomain(rid, attr(b"123")).unwrap().len(), 2);
+
+        // T2 still sees the old index entry, and none of T1's.
+        assert!(t2.seek_by_codomain(rid, attr(b"123")).unwrap().is_empty());
+        let old = t2.seek_by_codomain(rid, attr(b"def")).unwrap();
+        assert_eq!(old.len(), 1);
+        assert_eq!(old.iter().next().unwrap().domain(), attr(b"abc"));
+        t2.commit().unwrap();
+
+        t1.rollback().unwrap();
+        let tx = db.clone().start_tx();
+        assert!(tx.seek_by_codomain(rid, attr(b"123")).unwrap().is_empty());
+        assert_eq!(tx.seek_by_codomain(rid, attr(b"def")).unwrap().len(), 1);
+        let canonical = db.copy_canonical();
+        assert_eq!(
+            canonical[0].seek_by_codomain(attr(b"123")).unwrap().len(),
+            0
+        );
+    }
+
+    /// Tuples are refcounted, so versions replaced by later commits are freed as soon as no
+    /// transaction can still see them, without any separate vacuuming. A transaction that started
+    /// before the overwrites keeps its version alive until it's done.
+    #[test]
+    fn replaced_versions_are_freed() {
+        let db = test_db();
+        let rid = RelationId(0);
+        let tx = db.clone().start_tx();
+        tx.insert_tuple(rid, attr(b"abc"), attr(b"0")).unwrap();
+        tx.commit().unwrap();
+
+        let reader = db.clone().start_tx();
+        assert_eq!(
+            reader
+                .seek_unique_by_domain(rid, attr(b"abc"))
+                .unwrap()
+                .codomain(),
+            attr(b"0")
+        );
+        let overwrite = |i: usize| {
+            let tx = db.clone().start_tx();
+            tx.update_by_domain(rid, attr(b"abc"), attr(format!("{i}").as_bytes()))
+                .unwrap();
+            tx.commit().unwrap();
+        };
+        overwrite(1);
+        let baseline = db.db_usage_bytes();
+        for i in 2..1000 {
+            overwrite(i);
+        }
+        assert!(
+            db.db_usage_bytes() <= baseline,
+            "{} bytes used after overwrites, {} before",
+            db.db_usage_bytes(),
+            baseline
+        );
+
+        // The reader still sees the version it started with, and the latest is what's committed.
+        assert_eq!(
+            reader
+                .seek_unique_by_domain(rid, attr(b"abc"))
+                .unwrap()
+                .codomain(),
+            attr(b"0")
+        );
+        reader.rollback().unwrap();
+        let tx = db.clone().start_tx();
+        assert_eq!(
+            tx.seek_unique_by_domain(rid, attr(b"abc"))
+                .unwrap()
+                .codomain(),
+            attr(b"999")
+        );
+        assert_eq!(tx.seek_by_codomain(rid, attr(b"999")).unwrap().len(), 1);
+        assert!(tx.seek_by_codomain(rid, attr(b"998")).unwrap().is_empty());
+    }
+
     /// Tests basic serial/sequential logic, where transactions mutate the same tuple but do so
     /// sequentially without potential for conflict.
     #[test]
@@ -751,7 +1331,10 @@ mod tests {
         assert!(tx1.commit().is_ok());
         assert_eq!(
             tx2.commit().expect_err("Expected constraint violation"),
-            CommitError::UniqueConstraintViolation
+            CommitError::UniqueConstraintViolation {
+                relation: rid,
+                domain: attr(b"abc"),
+            }
         );
     }
 
@@ -796,10 +1379,44 @@ mod tests {
         assert!(tx1.commit().is_ok());
         assert_eq!(
             tx2.commit().expect_err("Expected conflict"),
-            CommitError::TupleVersionConflict
+            CommitError::TupleVersionConflict {
+                relation: rid,
+                domain: attr(b"abc"),
+            }
         );
     }
 
+    /// A conflict in one relation should be reported against that relation and the domain of the
+    /// tuple in conflict, not the first relation touched by the transaction.
+    #[test]
+    fn conflict_identifies_relation() {
+        let db = test_db();
+        let r0 = RelationId(0);
+        let r1 = RelationId(1);
+
+        let init_tx = db.clone().start_tx();
+        init_tx.insert_tuple(r1, attr2(1), attr(b"one")).unwrap();
+        init_tx.insert_tuple(r1, attr2(2), attr(b"two")).unwrap();
+        init_tx.commit().unwrap();
+
+        let tx1 = db.clone().start_tx();
+        let tx2 = db.clone().start_tx();
+        tx1.update_by_domain(r1, attr2(2), attr(b"deux")).unwrap();
+
+        // tx2 touches the other relation without conflict, and then the contended tuple.
+        tx2.insert_tuple(r0, attr(b"abc"), attr(b"def")).unwrap();
+        tx2.update_by_domain(r1, attr2(1), attr(b"uno")).unwrap();
+        tx2.update_by_domain(r1, attr2(2), attr(b"dos")).unwrap();
+
+        assert!(tx1.commit().is_ok());
+        let err = tx2.commit().expect_err("Expected conflict");
+        let CommitError::TupleVersionConflict { relation, domain } = err else {
+            panic!("Expected version conflict, got {:?}", err);
+        };
+        assert_eq!(relation, r1);
+        assert_eq!(domain, attr2(2));
+    }
+
     fn random_tuple() -> (Vec<u8>, Vec<u8>) {
         let mut rng = rand::thread_rng();
         let domain = (0..16).map(|_| rng.gen::<u8>()).collect::<Vec<u8>>();
@@ -856,6 +1473,166 @@ mod tests {
         verify(&tx, vec![b"a", b"b", b"d"]);
     }
 
+    /// A range scan sees committed tuples and the transaction's own changes, in domain order, and
+    /// honours the bounds; whether it walks an ordered index or has to scan a hashed one.
+    #[test]
+    fn scan_range() {
+        for index_type in [IndexType::Hash, IndexType::BTree] {
+            let db = RelBox::new(
+                1 << 24,
+                None,
+                &[RelationInfo {
+                    name: "test".to_string(),
+                    domain_type: AttrType::String,
+                    codomain_type: AttrType::String,
+                    secondary_indexed: false,
+                    unique_domain: true,
+                    index_type,
+                    codomain_index_type: None,
+                    fixed_width: None,
+                    page_size: None,
+                }],
+                0,
+            );
+            let rid = RelationId(0);
+            let tx = db.clone().start_tx();
+            for d in [b"a", b"b", b"c", b"d", b"e"] {
+                tx.insert_tuple(rid, attr(d), attr(b"v")).unwrap();
+            }
+            tx.commit().unwrap();
+
+            let tx = db.clone().start_tx();
+            tx.remove_by_domain(rid, attr(b"c")).unwrap();
+            tx.insert_tuple(rid, attr(b"bb"), attr(b"v")).unwrap();
+            let scan = |start, end| -> Vec<SliceRef> {
+                tx.relation(rid)
+                    .scan_range(start, end)
+                    .unwrap()
+                    .iter()
+                    .map(|t| t.domain())
+                    .collect()
+            };
+            assert_eq!(
+                scan(Bound::Included(attr(b"b")), Bound::Excluded(attr(b"e"))),
+                vec![attr(b"b"), attr(b"bb"), attr(b"d")]
+            );
+            assert_eq!(
+                scan(Bound::Excluded(attr(b"b")), Bound::Included(attr(b"e"))),
+                vec![attr(b"bb"), attr(b"d"), attr(b"e")]
+            );
+            assert_eq!(
+                scan(Bound::Unbounded, Bound::Excluded(attr(b"b"))),
+                vec![attr(b"a")]
+            );
+            assert!(scan(Bound::Included(attr(b"d")), Bound::Excluded(attr(b"b"))).is_empty());
+            assert!(scan(Bound::Excluded(attr(b"d")), Bound::Excluded(attr(b"d"))).is_empty());
+        }
+    }
+
+    /// `count` agrees with a full scan through committed tuples and the transaction's own inserts,
+    /// updates, and removes.
+    #[test]
+    fn count() {
+        let db = test_db();
+        let rid = RelationId(0);
+        let tx = db.clone().start_tx();
+        assert!(tx.relation(rid).is_empty().unwrap());
+        for d in [b"a", b"b", b"c", b"d", b"e"] {
+            tx.insert_tuple(rid, attr(d), attr(b"v")).unwrap();
+        }
+        assert_eq!(tx.relation(rid).count().unwrap(), 5);
+        tx.commit().unwrap();
+
+        let tx = db.clone().start_tx();
+        let scanned = |tx: &Transaction| tx.relation(rid).predicate_scan(&|_| true).unwrap().len();
+        assert_eq!(tx.relation(rid).count().unwrap(), 5);
+
+        tx.seek_unique_by_domain(rid, attr(b"a")).unwrap();
+        tx.update_by_domain(rid, attr(b"b"), attr(b"w")).unwrap();
+        tx.upsert_by_domain(rid, attr(b"d"), attr(b"w")).unwrap();
+        tx.remove_by_domain(rid, attr(b"c")).unwrap();
+        tx.insert_tuple(rid, attr(b"f"), attr(b"v")).unwrap();
+        tx.insert_tuple(rid, attr(b"g"), attr(b"v")).unwrap();
+        tx.remove_by_domain(rid, attr(b"g")).unwrap();
+        assert_eq!(tx.relation(rid).count().unwrap(), 5);
+        assert_eq!(tx.relation(rid).count().unwrap(), scanned(&tx));
+
+        // Another transaction sees none of that yet.
+        let other = db.clone().start_tx();
+        assert_eq!(other.relation(rid).count().unwrap(), 5);
+        assert_eq!(scanned(&other), 5);
+
+        for d in [b"a", b"b", b"d", b"e", b"f"] {
+            tx.remove_by_domain(rid, attr(d)).unwrap();
+        }
+        assert_eq!(tx.relation(rid).count().unwrap(), scanned(&tx));
+        assert!(tx.relation(rid).is_empty().unwrap());
+        tx.commit().unwrap();
+        other.rollback().unwrap();
+
+        let tx = db.clone().start_tx();
+        assert!(tx.relation(rid).is_empty().unwrap());
+    }
+
+    /// A batch insert ends up with exactly what inserting the tuples one by one does, and stops at
+    /// the first bad tuple.
+    #[test]
+    fn insert_tuples_batch() {
+        let tuples: Vec<_> = (0..10_000)
+            .map(|i| (attr2(i), attr(format!("value {i}").as_bytes())))
+            .collect();
+        let rid = RelationId(0);
+        let contents = |db: &Arc<RelBox>| {
+            let tx = db.clone().start_tx();
+            let mut all: Vec<_> = tx
+                .predicate_scan(rid, &|_| true)
+                .unwrap()
+                .iter()
+                .map(|t| (t.domain(), t.codomain()))
+                .collect();
+            all.sort();
+            all
+        };
+
+        let batched = test_db();
+        let tx = batched.clone().start_tx();
+        assert_eq!(
+            tx.relation(rid).insert_tuples(tuples.iter().cloned()),
+            Ok(10_000)
+        );
+        tx.commit().unwrap();
+
+        let one_by_one = test_db();
+        let tx = one_by_one.clone().start_tx();
+        for (d, c) in &tuples {
+            tx.insert_tuple(rid, d.clone(), c.clone()).unwrap();
+        }
+        tx.commit().unwrap();
+
+        let expected = contents(&one_by_one);
+        assert_eq!(expected.len(), 10_000);
+        assert_eq!(contents(&batched), expected);
+
+        // A domain that's already there, either committed or earlier in the batch, stops it.
+        let tx = batched.clone().start_tx();
+        let batch = [
+            (attr2(10_000), attr(b"new")),
+            (attr2(10_001), attr(b"new")),
+            (attr2(5), attr(b"dupe")),
+            (attr2(10_002), attr(b"new")),
+        ];
+        assert_eq!(
+            tx.relation(rid).insert_tuples(batch.into_iter()),
+            Err((RelationError::UniqueConstraintViolation, 2))
+        );
+        assert_eq!(
+            tx.relation(rid)
+                .insert_tuples([(attr2(1), attr(b"dupe"))].into_iter()),
+            Err((RelationError::UniqueConstraintViolation, 0))
+        );
+        assert_eq!(tx.relation(rid).count().unwrap(), 10_002);
+    }
+
     #[test]
     fn predicate_scan_with_predicate() {
         let db = test_db();
@@ -267,12 +267,22 @@ impl TxTupleEvent {
         let apply = match &self.op {
             // Inserts are removed.
             TxTupleOp::Insert(t) => Some(self.fork_to(None, None, Some(t.clone()))),
-            // If it was an update or echo'd value, we can just replace it with a tombstone.
-            TxTupleOp::Update { to_tuple: t, .. } | TxTupleOp::Value(t) => Some(self.fork_to(
+            // If it was an echo'd value, we can just replace it with a tombstone.
+            TxTupleOp::Value(t) => Some(self.fork_to(
                 Some(TxTupleOp::Tombstone(t.clone(), t.ts())),
                 Some(t.clone()),
                 Some(t.clone()),
             )),
+            // An update's new version never made it to the base relation, so it's the version it
+            // replaced that has to be tombstoned.
+            TxTupleOp::Update {
+                from_tuple,
+                to_tuple,
+            } => Some(self.fork_to(
+                Some(TxTupleOp::Tombstone(from_tuple.clone(), from_tuple.ts())),
+                Some(from_tuple.clone()),
+                Some(to_tuple.clone()),
+            )),
             // If it was a tombstone, error NotFound.
             TxTupleOp::Tombstone(_, _) => {
                 return Err(RelationError::TupleNotFound);
@@ -13,19 +13,35 @@
 //
 
 use std::collections::{HashMap, HashSet};
+use std::ops::RangeBounds;
 use std::sync::Arc;
 use tracing::{error, warn};
 
 use moor_values::util::{BitArray, Bitset64};
 use moor_values::util::{PhantomUnsend, PhantomUnsync, SliceRef};
 
-use crate::index::{pick_tx_index, Index};
+use crate::base_relation::BaseRelation;
+use crate::index::{pick_tx_index, Index, KeyRange};
 use crate::paging::TupleBox;
 use crate::relbox::{RelBox, RelationInfo};
 use crate::tuples::{TupleId, TupleRef};
 use crate::tx::tx_tuple::{DataSource, OpSource, TupleApply, TxTupleEvent, TxTupleOp};
+use crate::tx::{CommitError, IsolationLevel};
 use crate::{RelationError, RelationId};
 
+/// What a serializable transaction has read from its snapshot, to be checked at commit time
+/// against what has been committed since.
+#[derive(Default)]
+pub(crate) struct ReadSet {
+    /// Domains looked up in each relation.
+    domains: HashSet<(RelationId, SliceRef)>,
+    /// Ranges of domains scanned in each relation.
+    ranges: Vec<(RelationId, KeyRange)>,
+    /// Relations scanned by predicate, counted, or searched by codomain, where a change anywhere
+    /// in the relation could have changed what was read.
+    relations: HashSet<RelationId>,
+}
+
 /// The local tx "working set" of mutations to base relations, and consists of the set of operations
 /// we will attempt to make permanent when the transaction commits.
 /// The working set is also referred to for reads/updates during the lifetime of the transaction.  
@@ -35,19 +51,38 @@ pub struct WorkingSet {
     pub(crate) schema: Vec<RelationInfo>,
     pub(crate) tuplebox: Arc<TupleBox>,
     pub(crate) relations: Box<BitArray<TxBaseRelation, 64, Bitset64<1>>>,
+    /// The canonical base relations as they were when the transaction started, if it's
+    /// serializable. It reads from these, so that everything it reads is from one moment, and
+    /// checks them against the canonical relations at commit to see whether what it read has
+    /// changed. Otherwise reads go to the canonical relations as they are at the time.
+    base: Option<Vec<BaseRelation>>,
+    /// What's been read, if the transaction is serializable. Snapshot isolation doesn't need it.
+    reads: Option<ReadSet>,
 
     unsend: PhantomUnsend,
     unsync: PhantomUnsync,
 }
 
 impl WorkingSet {
-    pub(crate) fn new(slotbox: Arc<TupleBox>, schema: &[RelationInfo], ts: u64) -> Self {
+    pub(crate) fn new(
+        slotbox: Arc<TupleBox>,
+        schema: &[RelationInfo],
+        base: Option<Vec<BaseRelation>>,
+        ts: u64,
+        isolation: IsolationLevel,
+    ) -> Self {
         let relations = Box::new(BitArray::new());
+        let reads = match isolation {
+            IsolationLevel::Snapshot => None,
+            IsolationLevel::Serializable => Some(ReadSet::default()),
+        };
         Self {
             ts,
             tuplebox: slotbox,
             schema: schema.to_vec(),
             relations,
+            base,
+            reads,
             unsend: Default::default(),
             unsync: Default::default(),
         }
@@ -60,6 +95,103 @@ impl WorkingSet {
         }
     }
 
+    /// Copy off the current state of every relation we've touched, so that it can be put back
+    /// with `restore`.
+    pub(crate) fn snapshot(&self) -> Vec<(usize, TxBaseRelation)> {
+        self.relations
+            .iter()
+            .map(|(id, rel)| (id, rel.snapshot()))
+            .collect()
+    }
+
+    /// Put the working set back the way it was when `snapshot` was taken. Relations first touched
+    /// since then are dropped, which leaves them to be re-read from the base relations.
+    pub(crate) fn restore(&mut self, snapshot: &[(usize, TxBaseRelation)]) {
+        self.relations.clear();
+        for (id, rel) in snapshot {
+            self.relations.set(*id, rel.snapshot());
+        }
+    }
+
+    fn note_domain_read(&mut self, relation_id: RelationId, domain: &SliceRef) {
+        if let Some(reads) = &mut self.reads {
+            reads.domains.insert((relation_id, domain.clone()));
+        }
+    }
+
+    fn note_relation_read(&mut self, relation_id: RelationId) {
+        if let Some(reads) = &mut self.reads {
+            reads.relations.insert(relation_id);
+        }
+    }
+
+    /// Check that nothing this transaction read has been changed in `canonical` since its
+    /// snapshot was taken. Always passes for snapshot isolation, which doesn't track reads.
+    pub(crate) fn validate_reads(&self, canonical: &[BaseRelation]) -> Result<(), CommitError> {
+        let (Some(reads), Some(base)) = (&self.reads, &self.base) else {
+            return Ok(());
+        };
+        for relation_id in &reads.relations {
+            // A relation's timestamp is that of the last commit to touch it.
+            if canonical[relation_id.0].ts != base[relation_id.0].ts {
+                return Err(CommitError::SerializationConflict {
+                    relation: *relation_id,
+                });
+            }
+        }
+        for (relation_id, range) in &reads.ranges {
+            let (canonical, base) = (&canonical[relation_id.0], &base[relation_id.0]);
+            // Nothing has been committed to the relation since we copied it.
+            if canonical.ts == base.ts {
+                continue;
+            }
+            // Anything inserted into or changed within the range since is a tuple our copy
+            // doesn't have, and anything removed from it is one the canonical relation no longer
+            // has. Both sides walk the range in the domain index, where that's ordered.
+            let changed = canonical
+                .seek_range(range)
+                .iter()
+                .any(|t| !base.has_tuple(&t.id()))
+                || base
+                    .seek_range(range)
+                    .iter()
+                    .any(|t| !canonical.has_tuple(&t.id()));
+            if changed {
+                return Err(CommitError::SerializationConflict {
+                    relation: *relation_id,
+                });
+            }
+        }
+        for (relation_id, domain) in &reads.domains {
+            let ids = |relation: &BaseRelation| -> HashSet<TupleId> {
+                relation
+                    .seek_by_domain(domain.clone())
+                    .map(|tuples| tuples.iter().map(|t| t.id()).collect())
+                    .unwrap_or_default()
+            };
+            if ids(&canonical[relation_id.0]) != ids(&base[relation_id.0]) {
+                return Err(CommitError::SerializationConflict {
+                    relation: *relation_id,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Run `f` against the base relation this transaction reads from: its own copy if it took
+    /// one at the start, otherwise the canonical one.
+    fn with_base<R, F: FnOnce(&BaseRelation) -> R>(
+        base: &Option<Vec<BaseRelation>>,
+        db: &RelBox,
+        relation_id: RelationId,
+        f: F,
+    ) -> R {
+        match base {
+            Some(base) => f(&base[relation_id.0]),
+            None => db.with_relation(relation_id, f),
+        }
+    }
+
     fn get_relation_mut<'a>(
         relation_id: RelationId,
         schema: &[RelationInfo],
@@ -92,10 +224,11 @@ impl WorkingSet {
         relation_id: RelationId,
         domain: SliceRef,
     ) -> Result<HashSet<TupleRef>, RelationError> {
+        self.note_domain_read(relation_id, &domain);
         let relation = Self::get_relation_mut(relation_id, &self.schema, self.relations.as_mut());
 
         // Get the list of matches from the base relation, and then apply the local working set overtop.
-        let tuples = db.with_relation(relation_id, |relation| {
+        let tuples = Self::with_base(&self.base, db, relation_id, |relation| {
             relation.seek_by_domain(domain.clone())
         })?;
 
@@ -132,6 +265,7 @@ impl WorkingSet {
         relation_id: RelationId,
         domain: SliceRef,
     ) -> Result<TupleRef, RelationError> {
+        self.note_domain_read(relation_id, &domain);
         let relation = Self::get_relation_mut(relation_id, &self.schema, self.relations.as_mut());
 
         // Check local first.
@@ -152,7 +286,7 @@ impl WorkingSet {
                 };
             }
         }
-        let canon_t = db.with_relation(relation_id, |relation| {
+        let canon_t = Self::with_base(&self.base, db, relation_id, |relation| {
             let tuples = relation.seek_by_domain(domain.clone())?;
             if tuples.is_empty() {
                 return Err(RelationError::TupleNotFound);
@@ -188,6 +322,7 @@ impl WorkingSet {
         // to go back to the canonical relation, get the list of tuples for the codomain, then materialize
         // them into our local working set -- which will update the codomain index -- and then actually
         // use the local index.  Complicated enough?
+        self.note_relation_read(relation_id);
         let tuples_for_codomain = {
             let relation = Self::get_relation_mut(relation_id, &self.schema, &mut self.relations);
 
@@ -196,7 +331,7 @@ impl WorkingSet {
                 panic!("Attempted to seek by codomain on a relation with no secondary index");
             }
 
-            db.with_relation(relation_id, |relation| {
+            Self::with_base(&self.base, db, relation_id, |relation| {
                 relation.seek_by_codomain(codomain.clone())
             })?
         };
@@ -232,7 +367,7 @@ impl WorkingSet {
 
         // Enforce unique domain constraint before doing anything else
         relation.domain_index.check_constraints(&domain)?;
-        db.with_relation(relation_id, |relation| {
+        Self::with_base(&self.base, db, relation_id, |relation| {
             relation.check_domain_constraints(&domain)?;
             Ok(())
         })?;
@@ -256,18 +391,95 @@ impl WorkingSet {
         Ok(())
     }
 
+    /// Insert a batch of tuples, checking them all against the base relation under one lock.
+    /// Stops at the first failure, returning it along with the number inserted before it.
+    pub(crate) fn insert_tuples<I: Iterator<Item = (SliceRef, SliceRef)>>(
+        &mut self,
+        db: &Arc<RelBox>,
+        relation_id: RelationId,
+        tuples: I,
+    ) -> Result<usize, (RelationError, usize)> {
+        let relation = Self::get_relation_mut(relation_id, &self.schema, &mut self.relations);
+        let (tuplebox, ts) = (self.tuplebox.clone(), self.ts);
+        Self::with_base(&self.base, db, relation_id, |base| {
+            let mut inserted = 0;
+            for (domain, codomain) in tuples {
+                relation
+                    .domain_index
+                    .check_constraints(&domain)
+                    .and_then(|_| base.check_domain_constraints(&domain))
+                    .map_err(|e| (e, inserted))?;
+                let new_t = TupleRef::allocate(
+                    relation_id,
+                    tuplebox.clone(),
+                    ts,
+                    domain.as_slice(),
+                    codomain.as_slice(),
+                )
+                .unwrap();
+                let apply = TupleApply {
+                    data_source: DataSource::Base,
+                    op_source: OpSource::Insert,
+                    replacement_op: Some(TxTupleOp::Insert(new_t.clone())),
+                    add_tuple: Some(new_t),
+                    del_tuple: None,
+                };
+                relation.tuple_apply(apply).map_err(|e| (e, inserted))?;
+                inserted += 1;
+            }
+            Ok(inserted)
+        })
+    }
+
     pub(crate) fn predicate_scan<F: Fn(&TupleRef) -> bool>(
         &mut self,
         db: &Arc<RelBox>,
         relation_id: RelationId,
         f: F,
     ) -> Result<Vec<TupleRef>, RelationError> {
-        // First collect all the tuples from the canonical relation that match.
-        let mut tuples: HashMap<TupleId, TupleRef> = db
-            .with_relation(relation_id, |relation| relation.predicate_scan(&f))
-            .iter()
-            .map(|t| (t.id(), t.clone()))
-            .collect();
+        self.note_relation_read(relation_id);
+        self.scan(db, relation_id, f)
+    }
+
+    /// The tuples whose domain falls in `range`, in no particular order. The base relation's
+    /// part of the range comes from walking its domain index, where that's ordered.
+    pub(crate) fn scan_range(
+        &mut self,
+        db: &Arc<RelBox>,
+        relation_id: RelationId,
+        range: KeyRange,
+    ) -> Result<Vec<TupleRef>, RelationError> {
+        if let Some(reads) = &mut self.reads {
+            reads.ranges.push((relation_id, range.clone()));
+        }
+        let base_tuples = Self::with_base(&self.base, db, relation_id, |relation| {
+            relation.seek_range(&range)
+        });
+        self.overlay(relation_id, base_tuples, |t| range.contains(&t.domain()))
+    }
+
+    fn scan<F: Fn(&TupleRef) -> bool>(
+        &mut self,
+        db: &Arc<RelBox>,
+        relation_id: RelationId,
+        f: F,
+    ) -> Result<Vec<TupleRef>, RelationError> {
+        let base_tuples = Self::with_base(&self.base, db, relation_id, |relation| {
+            relation.predicate_scan(&f)
+        });
+        self.overlay(relation_id, base_tuples, f)
+    }
+
+    /// Apply the working set's changes over `base_tuples`, the tuples matching `f` in the base
+    /// relation.
+    fn overlay<F: Fn(&TupleRef) -> bool>(
+        &mut self,
+        relation_id: RelationId,
+        base_tuples: impl IntoIterator<Item = TupleRef>,
+        f: F,
+    ) -> Result<Vec<TupleRef>, RelationError> {
+        let mut tuples: HashMap<TupleId, TupleRef> =
+            base_tuples.into_iter().map(|t| (t.id(), t)).collect();
 
         // Group by domain...
         let mut by_domain: HashMap<SliceRef, HashSet<TupleRef>> =
@@ -323,6 +535,38 @@ impl WorkingSet {
         Ok(tuples.values().cloned().collect())
     }
 
+    /// The number of tuples in the relation as this transaction sees it: the size of the base
+    /// relation, adjusted for what we've inserted and removed locally. Nothing is materialized.
+    pub(crate) fn count(
+        &mut self,
+        db: &Arc<RelBox>,
+        relation_id: RelationId,
+    ) -> Result<usize, RelationError> {
+        self.note_relation_read(relation_id);
+        let relation = Self::get_relation_mut(relation_id, &self.schema, &mut self.relations);
+        let ts = self.ts;
+        let count = Self::with_base(&self.base, db, relation_id, |base| {
+            let mut count = base.tuple_count() as isize;
+            for (tr, t) in &relation.tx_tuple_events {
+                if t.op.ts() > ts {
+                    // Not visible to us, as in `predicate_scan`.
+                    if base.has_tuple(tr) {
+                        count -= 1;
+                    }
+                    continue;
+                }
+                match &t.op {
+                    TxTupleOp::Insert(_) => count += 1,
+                    // Every tombstone stands in for a base tuple; removed inserts just vanish.
+                    TxTupleOp::Tombstone(..) => count -= 1,
+                    TxTupleOp::Update { .. } | TxTupleOp::Value(_) => {}
+                }
+            }
+            count
+        });
+        Ok(count.max(0) as usize)
+    }
+
     pub(crate) fn update_by_domain(
         &mut self,
         db: &Arc<RelBox>,
@@ -374,7 +618,7 @@ impl WorkingSet {
         // Check canonical for existing values.  And get timestamps for each...
         // We will use the ts on that to determine the derivation timestamp for our own version.
         // If there's nothing there or its tombstoned, that's NotFound, and die.
-        let canon_tuples = db.with_relation(relation_id, |relation| {
+        let canon_tuples = Self::with_base(&self.base, db, relation_id, |relation| {
             let tuples = relation.seek_by_domain(domain.clone())?;
 
             Ok(tuples)
@@ -460,7 +704,7 @@ impl WorkingSet {
         }
 
         // Nothing, local, do canonical...
-        let apply = db.with_relation(relation_id, |relation| {
+        let apply = Self::with_base(&self.base, db, relation_id, |relation| {
             let old_tuples = relation.seek_by_domain(domain.clone())?;
             // If there's more than one value for this domain, this operation makes no sense, so raise an
             // ambig error.
@@ -553,7 +797,7 @@ impl WorkingSet {
             return Ok(());
         }
 
-        let old_tuples = db.with_relation(relation_id, |relation| {
+        let old_tuples = Self::with_base(&self.base, db, relation_id, |relation| {
             let tuples = relation.seek_by_domain(domain.clone())?;
 
             if relation.info.unique_domain {
@@ -603,6 +847,19 @@ impl TxBaseRelation {
         self.tx_tuple_events.values_mut()
     }
 
+    /// A copy of this relation's local tuple events and indexes.
+    fn snapshot(&self) -> TxBaseRelation {
+        TxBaseRelation {
+            id: self.id,
+            relation_info: self.relation_info.clone(),
+            tx_tuple_events: self.tx_tuple_events.clone(),
+            domain_index: self.domain_index.clone_index(),
+            codomain_index: self.codomain_index.as_ref().map(|i| i.clone_index() as _),
+            unsend: Default::default(),
+            unsync: Default::default(),
+        }
+    }
+
     pub(crate) fn clear(&mut self) {
         self.tx_tuple_events.clear();
         self.domain_index.clear();
@@ -18,14 +18,14 @@ mod support;
 #[cfg(test)]
 mod test {
     use std::collections::HashMap;
-    use std::path::PathBuf;
+    use std::path::{Path, PathBuf};
     use std::rc::Rc;
     use std::sync::Arc;
     use tracing_test::traced_test;
 
     use crate::support::{History, Type, Value};
     use moor_rdb::index::{AttrType, IndexType};
-    use moor_rdb::{RelBox, RelationInfo};
+    use moor_rdb::{RelBox, RelationInfo, SyncPolicy};
     use moor_rdb::{RelationId, Transaction};
     use moor_values::util::SliceRef;
 
@@ -93,6 +93,8 @@ mod test {
                 unique_domain: true,
                 index_type: IndexType::AdaptiveRadixTree,
                 codomain_index_type: None,
+                fixed_width: None,
+                page_size: None,
             })
             .collect::<Vec<_>>();
 
@@ -166,4 +168,85 @@ mod test {
             db.shutdown();
         }
     }
+
+    fn copy_dir(from: &Path, to: &Path) {
+        std::fs::create_dir_all(to).unwrap();
+        for entry in std::fs::read_dir(from).unwrap() {
+            let entry = entry.unwrap();
+            if entry.file_type().unwrap().is_dir() {
+                copy_dir(&entry.path(), &to.join(entry.file_name()));
+            } else {
+                std::fs::copy(entry.path(), to.join(entry.file_name())).unwrap();
+            }
+        }
+    }
+
+    fn domains_in(db: &Arc<RelBox>, relation: RelationId) -> Vec<i64> {
+        let mut domains: Vec<_> = db.with_relation(relation, |r| {
+            r.predicate_scan(&|_| true)
+                .iter()
+                .map(|t| to_val(t.domain()))
+                .collect()
+        });
+        domains.sort();
+        domains
+    }
+
+    // A commit whose write-ahead log entry made it to disk, but none of whose page writes did, is
+    // put back together from the log at startup. One whose entry was only partly written is
+    // dropped entirely.
+    #[test]
+    #[traced_test]
+    fn recover_torn_commit() {
+        let live_dir = tempfile::tempdir().unwrap();
+        let db = test_db(live_dir.path().into());
+        db.set_sync_policy(SyncPolicy::Durable);
+        for batch in 0..2 {
+            let tx = db.clone().start_tx();
+            for v in batch * 100..(batch + 1) * 100 {
+                tx.relation(RelationId(batch as usize))
+                    .insert_tuple(from_val(v), from_val(v))
+                    .unwrap();
+            }
+            tx.commit().unwrap();
+        }
+
+        // Take just the log, as if we'd died the moment the last commit returned, without any of
+        // the pages having been written.
+        let crashed_dir = tempfile::tempdir().unwrap();
+        copy_dir(
+            &live_dir.path().join("wal"),
+            &crashed_dir.path().join("wal"),
+        );
+
+        // And another copy where we died partway through writing the last commit's entry: the
+        // tail end of what's been written never made it.
+        let torn_dir = tempfile::tempdir().unwrap();
+        copy_dir(&live_dir.path().join("wal"), &torn_dir.path().join("wal"));
+        db.shutdown();
+        for entry in std::fs::read_dir(torn_dir.path().join("wal")).unwrap() {
+            let path = entry.unwrap().path();
+            let mut segment = std::fs::read(&path).unwrap();
+            // Segments are preallocated, so the end of what's been written is the last non-zero
+            // byte.
+            let Some(end) = segment.iter().rposition(|b| *b != 0) else {
+                continue;
+            };
+            segment[end.saturating_sub(64)..=end].fill(0);
+            std::fs::write(&path, segment).unwrap();
+        }
+
+        let db = test_db(crashed_dir.path().into());
+        assert_eq!(domains_in(&db, RelationId(0)), (0..100).collect::<Vec<_>>());
+        assert_eq!(
+            domains_in(&db, RelationId(1)),
+            (100..200).collect::<Vec<_>>()
+        );
+        db.shutdown();
+
+        let db = test_db(torn_dir.path().into());
+        assert_eq!(domains_in(&db, RelationId(0)), (0..100).collect::<Vec<_>>());
+        assert_eq!(domains_in(&db, RelationId(1)), Vec::<i64>::new());
+        db.shutdown();
+    }
 }
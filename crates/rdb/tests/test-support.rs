@@ -30,6 +30,8 @@ pub fn test_db(dir: PathBuf) -> Arc<RelBox> {
             unique_domain: true,
             index_type: IndexType::AdaptiveRadixTree,
             codomain_index_type: None,
+            fixed_width: None,
+            page_size: None,
         })
         .collect::<Vec<_>>();
 
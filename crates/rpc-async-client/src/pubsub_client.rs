@@ -18,26 +18,31 @@ use tmq::subscribe::Subscribe;
 use tracing::trace;
 use uuid::Uuid;
 
-use rpc_common::{BroadcastEvent, ConnectionEvent, RpcError};
+use rpc_common::{
+    decode_payload, decode_payload_events, BroadcastEvent, ConnectionEvent, RpcError,
+};
 
+/// Receive the next message on the narrative channel. The daemon batches up the events for a
+/// connection, so this may be several `ConnectionEvent`s, which are returned in order.
 pub async fn narrative_recv(
     client_id: Uuid,
     subscribe: &mut Subscribe,
-) -> Result<ConnectionEvent, RpcError> {
+) -> Result<Vec<ConnectionEvent>, RpcError> {
     let Some(Ok(mut inbound)) = subscribe.next().await else {
         return Err(RpcError::CouldNotReceive(
             "Unable to receive narrative message".to_string(),
         ));
     };
 
-    // bincode decode the message, and it should be ConnectionEvent
-    if inbound.len() != 2 {
+    // The client id, followed by one or more frames of bincoded ConnectionEvents, as built by
+    // `encode_payload`, and so possibly compressed
+    if inbound.len() < 2 {
         return Err(RpcError::CouldNotDecode(format!(
             "Unexpected message length: {}",
             inbound.len()
         )));
     }
-    let (Some(received_client_id), Some(event)) = (inbound.pop_front(), inbound.pop_front()) else {
+    let Some(received_client_id) = inbound.pop_front() else {
         return Err(RpcError::CouldNotDecode(
             "Unexpected message format".to_string(),
         ));
@@ -53,12 +58,11 @@ pub async fn narrative_recv(
         return Err(RpcError::CouldNotDecode("Unexpected client ID".to_string()));
     }
 
-    let decode_result = bincode::decode_from_slice(event.as_ref(), bincode::config::standard());
-    let (msg, _msg_size): (ConnectionEvent, usize) = decode_result.map_err(|e| {
-        RpcError::CouldNotDecode(format!("Unable to decode narrative message: {}", e))
-    })?;
-
-    Ok(msg)
+    inbound
+        .iter()
+        .map(|frame| decode_payload_events::<ConnectionEvent>(frame))
+        .collect::<Result<Vec<_>, _>>()
+        .map(|frames| frames.into_iter().flatten().collect())
 }
 
 pub async fn broadcast_recv(subscribe: &mut Subscribe) -> Result<BroadcastEvent, RpcError> {
@@ -95,8 +99,9 @@ pub async fn broadcast_recv(subscribe: &mut Subscribe) -> Result<BroadcastEvent,
         ));
     };
 
+    let event = decode_payload(&event)?;
     let (msg, _msg_size): (BroadcastEvent, usize) =
-        bincode::decode_from_slice(event.as_ref(), bincode::config::standard()).map_err(|e| {
+        bincode::decode_from_slice(&event, bincode::config::standard()).map_err(|e| {
             RpcError::CouldNotDecode(format!("Unable to decode broadcast message: {}", e))
         })?;
     Ok(msg)
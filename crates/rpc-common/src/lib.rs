@@ -16,6 +16,7 @@ use bincode::{Decode, Encode};
 use moor_values::model::{CommandError, NarrativeEvent, WorldStateError};
 use moor_values::var::Objid;
 use moor_values::var::Var;
+use std::borrow::Cow;
 use std::time::SystemTime;
 use thiserror::Error;
 
@@ -24,6 +25,13 @@ pub const BROADCAST_TOPIC: &[u8; 9] = b"broadcast";
 pub const MOOR_SESSION_TOKEN_FOOTER: &str = "key-id:moor_rpc";
 pub const MOOR_AUTH_TOKEN_FOOTER: &str = "key-id:moor_player";
 
+/// The first byte of every narrative / broadcast event frame says how the rest of it is encoded.
+pub const PAYLOAD_UNCOMPRESSED: u8 = 0;
+pub const PAYLOAD_DEFLATE: u8 = 1;
+
+/// Event payloads smaller than this aren't worth the cost of compressing.
+pub const COMPRESSION_THRESHOLD: usize = 1024;
+
 /// Errors at the RPC transport / encoding layer.
 #[derive(Debug, Error)]
 pub enum RpcError {
@@ -63,12 +71,15 @@ pub enum RpcRequest {
     RequestedInput(ClientToken, AuthToken, u128, String),
     /// Send an "out of band" command to be executed.
     OutOfBand(ClientToken, AuthToken, String),
-    /// Evaluate a MOO expression.
-    Eval(ClientToken, AuthToken, String),
+    /// Evaluate a MOO expression. If asked to, the player is also shown the result, as `;` shows
+    /// it in LambdaMOO; programmatic callers that only want the value back leave that off.
+    Eval(ClientToken, AuthToken, String, bool /* show result? */),
     /// Respond to a ping request.
     Pong(ClientToken, SystemTime),
     /// We're done with this connection, buh-bye.
     Detach(ClientToken),
+    /// Ask that large event payloads for this connection be compressed from now on.
+    RequestCompression(ClientToken),
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Encode, Decode)]
@@ -96,6 +107,7 @@ pub enum RpcResponse {
     EvalResult(Var),
     ThanksPong(SystemTime),
     Disconnected,
+    CompressionEnabled,
 }
 
 /// Errors at the call/request level.
@@ -151,3 +163,164 @@ pub enum BroadcastEvent {
     PingPong(SystemTime),
     // TODO: Shutdown, Broadcast BroadcastEvent messages in RPC layer
 }
+
+/// Frame encoded events for the pubsub channel, deflating them if `compress` is set and they're
+/// at least `COMPRESSION_THRESHOLD` bytes. A frame may hold several events back to back.
+pub fn encode_payload(event_bytes: &[u8], compress: bool) -> Vec<u8> {
+    if compress && event_bytes.len() >= COMPRESSION_THRESHOLD {
+        let compressed = miniz_oxide::deflate::compress_to_vec(event_bytes, 6);
+        if compressed.len() < event_bytes.len() {
+            let mut frame = Vec::with_capacity(compressed.len() + 1);
+            frame.push(PAYLOAD_DEFLATE);
+            frame.extend_from_slice(&compressed);
+            return frame;
+        }
+    }
+    let mut frame = Vec::with_capacity(event_bytes.len() + 1);
+    frame.push(PAYLOAD_UNCOMPRESSED);
+    frame.extend_from_slice(event_bytes);
+    frame
+}
+
+/// Unwrap a pubsub event frame produced by `encode_payload`, back into the encoded event.
+pub fn decode_payload(frame: &[u8]) -> Result<Cow<'_, [u8]>, RpcError> {
+    match frame.split_first() {
+        Some((&PAYLOAD_UNCOMPRESSED, event_bytes)) => Ok(Cow::Borrowed(event_bytes)),
+        Some((&PAYLOAD_DEFLATE, compressed)) => miniz_oxide::inflate::decompress_to_vec(compressed)
+            .map(Cow::Owned)
+            .map_err(|e| {
+                RpcError::CouldNotDecode(format!("Unable to decompress payload: {:?}", e))
+            }),
+        Some((tag, _)) => Err(RpcError::CouldNotDecode(format!(
+            "Unknown payload encoding: {}",
+            tag
+        ))),
+        None => Err(RpcError::CouldNotDecode("Empty payload".to_string())),
+    }
+}
+
+/// Decode all the events packed into a pubsub event frame, in order.
+pub fn decode_payload_events<T: Decode>(frame: &[u8]) -> Result<Vec<T>, RpcError> {
+    let event_bytes = decode_payload(frame)?;
+    let mut remaining = event_bytes.as_ref();
+    let mut events = vec![];
+    while !remaining.is_empty() {
+        let (event, size) = bincode::decode_from_slice(remaining, bincode::config::standard())
+            .map_err(|e| RpcError::CouldNotDecode(format!("Unable to decode event: {}", e)))?;
+        events.push(event);
+        remaining = &remaining[size..];
+    }
+    Ok(events)
+}
+
+#[cfg(test)]
+mod tests {
+    use moor_values::model::{ContentType, Event, NarrativeEvent};
+    use moor_values::var::Objid;
+
+    use crate::{
+        decode_payload, encode_payload, ConnectionEvent, PAYLOAD_DEFLATE, PAYLOAD_UNCOMPRESSED,
+    };
+
+    fn round_trip(event: &ConnectionEvent) -> ConnectionEvent {
+        let bytes = bincode::encode_to_vec(event, bincode::config::standard()).unwrap();
+        let (decoded, _) = bincode::decode_from_slice(&bytes, bincode::config::standard()).unwrap();
+        decoded
+    }
+
+    #[test]
+    fn test_narrative_content_type_round_trip() {
+        let event = ConnectionEvent::Narrative(
+            Objid(1),
+            NarrativeEvent::notify(Objid(2), "<b>hi</b>".to_string(), ContentType::TextHtml),
+        );
+        let decoded = round_trip(&event);
+        assert_eq!(decoded, event);
+        let ConnectionEvent::Narrative(_, narrative) = decoded else {
+            panic!("expected a narrative event");
+        };
+        assert_eq!(
+            narrative.event(),
+            Event::TextNotify("<b>hi</b>".to_string(), ContentType::TextHtml)
+        );
+    }
+
+    #[test]
+    fn test_narrative_content_type_defaults_to_plain() {
+        let event = ConnectionEvent::Narrative(
+            Objid(1),
+            NarrativeEvent::notify_text(Objid(2), "hi".to_string()),
+        );
+        let ConnectionEvent::Narrative(_, narrative) = round_trip(&event) else {
+            panic!("expected a narrative event");
+        };
+        assert_eq!(
+            narrative.event(),
+            Event::TextNotify("hi".to_string(), ContentType::TextPlain)
+        );
+        assert_eq!(ContentType::default(), ContentType::TextPlain);
+    }
+
+    #[test]
+    fn test_narrative_oob_round_trip() {
+        let payload = "#$#mcp version: 2.1 to: 2.1".to_string();
+        let oob = ConnectionEvent::Narrative(
+            Objid(1),
+            NarrativeEvent::notify_oob(Objid(2), payload.clone()),
+        );
+        let plain = ConnectionEvent::Narrative(
+            Objid(1),
+            NarrativeEvent::notify_text(Objid(2), payload.clone()),
+        );
+        let (
+            ConnectionEvent::Narrative(_, oob_narrative),
+            ConnectionEvent::Narrative(_, plain_narrative),
+        ) = (round_trip(&oob), round_trip(&plain))
+        else {
+            panic!("expected narrative events");
+        };
+        assert!(oob_narrative.is_oob());
+        assert!(!plain_narrative.is_oob());
+        assert_eq!(oob_narrative.event(), plain_narrative.event());
+    }
+
+    #[test]
+    fn test_large_payload_compressed() {
+        let event = ConnectionEvent::Narrative(
+            Objid(1),
+            NarrativeEvent::notify_text(Objid(2), "#123:look_self   this none this\n".repeat(500)),
+        );
+        let bytes = bincode::encode_to_vec(&event, bincode::config::standard()).unwrap();
+        let frame = encode_payload(&bytes, true);
+        assert_eq!(frame[0], PAYLOAD_DEFLATE);
+        assert!(frame.len() < bytes.len() / 10);
+
+        let decoded = decode_payload(&frame).unwrap();
+        assert_eq!(decoded.as_ref(), bytes.as_slice());
+        let (decoded, _): (ConnectionEvent, usize) =
+            bincode::decode_from_slice(&decoded, bincode::config::standard()).unwrap();
+        assert_eq!(decoded, event);
+
+        // Not negotiated, so left alone regardless of size.
+        let frame = encode_payload(&bytes, false);
+        assert_eq!(frame[0], PAYLOAD_UNCOMPRESSED);
+        assert_eq!(&frame[1..], bytes.as_slice());
+    }
+
+    #[test]
+    fn test_small_payload_uncompressed() {
+        let event = ConnectionEvent::RequestInput(1);
+        let bytes = bincode::encode_to_vec(&event, bincode::config::standard()).unwrap();
+        let frame = encode_payload(&bytes, true);
+        assert_eq!(frame[0], PAYLOAD_UNCOMPRESSED);
+        assert_eq!(&frame[1..], bytes.as_slice());
+        assert_eq!(decode_payload(&frame).unwrap().as_ref(), bytes.as_slice());
+    }
+
+    #[test]
+    fn test_bad_payload_encoding() {
+        assert!(decode_payload(&[]).is_err());
+        assert!(decode_payload(&[7, 1, 2, 3]).is_err());
+        assert!(decode_payload(&[PAYLOAD_DEFLATE, 0xff, 0xff]).is_err());
+    }
+}
@@ -17,25 +17,32 @@ use tracing::trace;
 use uuid::Uuid;
 use zmq::Socket;
 
-use rpc_common::{BroadcastEvent, ConnectionEvent, RpcError};
+use rpc_common::{
+    decode_payload, decode_payload_events, BroadcastEvent, ConnectionEvent, RpcError,
+};
 
-/// Blocking receive on the narrative channel, returning a `ConnectionEvent`.
-pub fn narrative_recv(client_id: Uuid, subscribe: &Socket) -> Result<ConnectionEvent, RpcError> {
+/// Blocking receive on the narrative channel. The daemon batches up the events for a connection,
+/// so this may be several `ConnectionEvent`s, which are returned in order.
+pub fn narrative_recv(
+    client_id: Uuid,
+    subscribe: &Socket,
+) -> Result<Vec<ConnectionEvent>, RpcError> {
     let Ok(inbound) = subscribe.recv_multipart(0) else {
         return Err(RpcError::CouldNotReceive(
             "Unable to receive narrative message".to_string(),
         ));
     };
 
-    // bincode decode the message, and it should be ConnectionEvent
-    if inbound.len() != 2 {
+    // The client id, followed by one or more frames of bincoded ConnectionEvents, as built by
+    // `encode_payload`, and so possibly compressed
+    if inbound.len() < 2 {
         return Err(RpcError::CouldNotDecode(format!(
             "Unexpected message length: {}",
             inbound.len()
         )));
     }
 
-    let (received_client_id, event) = (&inbound[0], &inbound[1]);
+    let (received_client_id, events) = (&inbound[0], &inbound[1..]);
 
     let Ok(received_client_id) = Uuid::from_slice(received_client_id) else {
         return Err(RpcError::CouldNotDecode(
@@ -47,12 +54,11 @@ pub fn narrative_recv(client_id: Uuid, subscribe: &Socket) -> Result<ConnectionE
         return Err(RpcError::CouldNotDecode("Unexpected client ID".to_string()));
     }
 
-    let decode_result = bincode::decode_from_slice(event.as_ref(), bincode::config::standard());
-    let (msg, _msg_size): (ConnectionEvent, usize) = decode_result.map_err(|e| {
-        RpcError::CouldNotDecode(format!("Unable to decode narrative message: {}", e))
-    })?;
-
-    Ok(msg)
+    events
+        .iter()
+        .map(|frame| decode_payload_events::<ConnectionEvent>(frame))
+        .collect::<Result<Vec<_>, _>>()
+        .map(|frames| frames.into_iter().flatten().collect())
 }
 
 /// Blocking receive on the broadcast channel, returning a `BroadcastEvent`.
@@ -80,9 +86,85 @@ pub fn broadcast_recv(subscribe: &mut Socket) -> Result<BroadcastEvent, RpcError
         )));
     }
 
+    let event = decode_payload(event)?;
     let (msg, _msg_size): (BroadcastEvent, usize) =
-        bincode::decode_from_slice(event.as_ref(), bincode::config::standard()).map_err(|e| {
+        bincode::decode_from_slice(&event, bincode::config::standard()).map_err(|e| {
             RpcError::CouldNotDecode(format!("Unable to decode broadcast message: {}", e))
         })?;
     Ok(msg)
 }
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use rpc_common::{encode_payload, ConnectionEvent, RpcError, PAYLOAD_DEFLATE};
+
+    use crate::pubsub_client::narrative_recv;
+
+    fn encode(event: &ConnectionEvent) -> Vec<u8> {
+        bincode::encode_to_vec(event, bincode::config::standard()).unwrap()
+    }
+
+    /// Publish `frames` to `client_id` over an inproc socket, and receive them back.
+    fn publish_and_recv(
+        endpoint: &str,
+        client_id: Uuid,
+        frames: Vec<Vec<u8>>,
+    ) -> Result<Vec<ConnectionEvent>, RpcError> {
+        let ctx = zmq::Context::new();
+        let publish = ctx.socket(zmq::PUB).unwrap();
+        publish.bind(endpoint).unwrap();
+        let subscribe = ctx.socket(zmq::SUB).unwrap();
+        subscribe.connect(endpoint).unwrap();
+        subscribe.set_subscribe(client_id.as_bytes()).unwrap();
+        subscribe.set_rcvtimeo(100).unwrap();
+
+        let mut payload = vec![client_id.as_bytes().to_vec()];
+        payload.extend(frames);
+
+        // The subscription takes a moment to reach the publisher, and until then messages are
+        // dropped, so keep sending until one arrives.
+        let mut received = Err(RpcError::CouldNotReceive("never sent".to_string()));
+        for _ in 0..50 {
+            publish.send_multipart(payload.clone(), 0).unwrap();
+            received = narrative_recv(client_id, &subscribe);
+            if received.is_ok() {
+                break;
+            }
+        }
+        received
+    }
+
+    #[test]
+    fn test_narrative_recv_batch() {
+        let client_id = Uuid::new_v4();
+        let events = vec![
+            ConnectionEvent::RequestInput(1),
+            ConnectionEvent::RequestInput(2),
+            ConnectionEvent::Disconnect(),
+        ];
+        let frames = events
+            .iter()
+            .map(|e| encode_payload(&encode(e), false))
+            .collect();
+        let received = publish_and_recv("inproc://narrative-batch", client_id, frames);
+        assert_eq!(received.unwrap(), events);
+    }
+
+    /// A large batch packed into a single compressed frame comes back out as the same events.
+    #[test]
+    fn test_narrative_recv_compressed() {
+        let client_id = Uuid::new_v4();
+        let events: Vec<_> = (0..1000)
+            .map(|i| ConnectionEvent::RequestInput(i % 4))
+            .collect();
+        let packed: Vec<u8> = events.iter().flat_map(encode).collect();
+        let frame = encode_payload(&packed, true);
+        assert_eq!(frame[0], PAYLOAD_DEFLATE);
+        assert!(frame.len() < packed.len());
+
+        let received = publish_and_recv("inproc://narrative-compressed", client_id, vec![frame]);
+        assert_eq!(received.unwrap(), events);
+    }
+}
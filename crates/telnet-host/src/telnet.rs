@@ -25,7 +25,7 @@ use tmq::{request, subscribe};
 use tokio::net::{TcpListener, TcpStream};
 use tokio::select;
 use tokio_util::codec::{Framed, LinesCodec};
-use tracing::{debug, error, info, trace};
+use tracing::{debug, error, info, trace, warn};
 use uuid::Uuid;
 
 use moor_values::model::CommandError;
@@ -120,23 +120,27 @@ impl TelnetConnection {
                         }
                     }
                 }
-                Ok(event) = narrative_recv(self.client_id, narrative_sub) => {
-                    trace!(?event, "narrative_event");
-                    match event {
-                        ConnectionEvent::SystemMessage(_author, msg) => {
-                            self.write.send(msg).await.with_context(|| "Unable to send message to client")?;
-                        }
-                        ConnectionEvent::Narrative(_author, event) => {
-                            let msg = event.event();
-                            let moor_values::model::Event::TextNotify(msg_text) = msg;
-                            self.write.send(msg_text).await.with_context(|| "Unable to send message to client")?;
-                        }
-                        ConnectionEvent::RequestInput(_request_id) => {
-                            bail!("RequestInput before login");
-                        }
-                        ConnectionEvent::Disconnect() => {
-                            self.write.close().await?;
-                            bail!("Disconnect before login");
+                Ok(events) = narrative_recv(self.client_id, narrative_sub) => {
+                    for event in events {
+                        trace!(?event, "narrative_event");
+                        match event {
+                            ConnectionEvent::SystemMessage(_author, msg) => {
+                                self.write.send(msg).await.with_context(|| "Unable to send message to client")?;
+                            }
+                            ConnectionEvent::Narrative(_author, event) => {
+                                // Out-of-band (MCP) lines go out verbatim too; telnet clients pick them
+                                // out of the stream by their prefix.
+                                let msg = event.event();
+                                let moor_values::model::Event::TextNotify(msg_text, _) = msg;
+                                self.write.send(msg_text).await.with_context(|| "Unable to send message to client")?;
+                            }
+                            ConnectionEvent::RequestInput(_request_id) => {
+                                bail!("RequestInput before login");
+                            }
+                            ConnectionEvent::Disconnect() => {
+                                self.write.close().await?;
+                                bail!("Disconnect before login");
+                            }
                         }
                     }
                 }
@@ -227,24 +231,28 @@ impl TelnetConnection {
                         }
                     }
                 }
-                Ok(event) = narrative_recv(self.client_id, narrative_sub) => {
-                    match event {
-                        ConnectionEvent::SystemMessage(_author, msg) => {
-                            self.write.send(msg).await.with_context(|| "Unable to send message to client")?;
-                        }
-                        ConnectionEvent::Narrative(_author, event) => {
-                            let msg = event.event();
-                            let moor_values::model::Event::TextNotify(msg_text) = msg;
-                            self.write.send(msg_text).await.with_context(|| "Unable to send message to client")?;
-                        }
-                        ConnectionEvent::RequestInput(request_id) => {
-                            // Server is requesting that the next line of input get sent through as a response to this request.
-                            expecting_input_reply = Some(request_id);
-                        }
-                        ConnectionEvent::Disconnect() => {
-                            self.write.send("** Disconnected **".to_string()).await.expect("Unable to send disconnect message to client");
-                            self.write.close().await.expect("Unable to close connection");
-                            return Ok(())
+                Ok(events) = narrative_recv(self.client_id, narrative_sub) => {
+                    for event in events {
+                        match event {
+                            ConnectionEvent::SystemMessage(_author, msg) => {
+                                self.write.send(msg).await.with_context(|| "Unable to send message to client")?;
+                            }
+                            ConnectionEvent::Narrative(_author, event) => {
+                                // Out-of-band (MCP) lines go out verbatim too; telnet clients pick them
+                                // out of the stream by their prefix.
+                                let msg = event.event();
+                                let moor_values::model::Event::TextNotify(msg_text, _) = msg;
+                                self.write.send(msg_text).await.with_context(|| "Unable to send message to client")?;
+                            }
+                            ConnectionEvent::RequestInput(request_id) => {
+                                // Server is requesting that the next line of input get sent through as a response to this request.
+                                expecting_input_reply = Some(request_id);
+                            }
+                            ConnectionEvent::Disconnect() => {
+                                self.write.send("** Disconnected **".to_string()).await.expect("Unable to send disconnect message to client");
+                                self.write.close().await.expect("Unable to close connection");
+                                return Ok(())
+                            }
                         }
                     }
                 }
@@ -306,6 +314,15 @@ pub async fn telnet_listen_loop(
             };
             debug!(client_id = ?client_id, connection = ?connection_oid, "Connection established");
 
+            // Large outputs (a big @list, say) are worth compressing on the way to us.
+            match rpc_client
+                .make_rpc_call(client_id, RpcRequest::RequestCompression(token.clone()))
+                .await
+            {
+                Ok(RpcResult::Success(RpcResponse::CompressionEnabled)) => {}
+                other => warn!(?other, "Unable to enable payload compression"),
+            }
+
             // Before attempting login, we subscribe to the narrative channel, using our client
             // id. The daemon should be sending events here.
             let narrative_sub = subscribe(&zmq_ctx)
@@ -12,7 +12,7 @@
 // this program. If not, see <https://www.gnu.org/licenses/>.
 //
 
-pub use encode::AsByteBuffer;
+pub use encode::{AsByteBuffer, DecodingError, EncodingError};
 
 use crate::var::Objid;
 
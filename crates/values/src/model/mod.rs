@@ -76,6 +76,8 @@ pub enum WorldStateError {
     DuplicatePropertyDefinition(Objid, String),
     #[error("Property type mismatch")]
     PropertyTypeMismatch,
+    #[error("Cannot clear property on its definer: {0}.{1}")]
+    ClearPropertyOnDefiner(Objid, String),
 
     #[error("Verb not found: {0}:{1}")]
     VerbNotFound(Objid, String),
@@ -89,11 +91,17 @@ pub enum WorldStateError {
     #[error("Verb already exists: {0}:{1}")]
     DuplicateVerb(Objid, String),
 
+    #[error("Ownership quota exhausted for: {0}")]
+    QuotaExceeded(Objid),
+
     #[error("Failed object match: {0}")]
     FailedMatch(String),
     #[error("Ambiguous object match: {0}")]
     AmbiguousMatch(String),
 
+    #[error("No such savepoint: {0}")]
+    SavepointNotFound(usize),
+
     // Catch-alls for system level object DB errors.
     #[error("DB communications/internal error: {0}")]
     DatabaseError(String),
@@ -104,6 +112,7 @@ impl WorldStateError {
     pub fn to_error_code(&self) -> Error {
         match self {
             Self::ObjectNotFound(_) => Error::E_INVIND,
+            Self::ObjectAlreadyExists(_) => Error::E_INVARG,
             Self::ObjectPermissionDenied => Error::E_PERM,
             Self::RecursiveMove(_, _) => Error::E_RECMOVE,
             Self::VerbNotFound(_, _) => Error::E_VERBNF,
@@ -115,6 +124,9 @@ impl WorldStateError {
             Self::PropertyDefinitionNotFound(_, _) => Error::E_PROPNF,
             Self::DuplicatePropertyDefinition(_, _) => Error::E_INVARG,
             Self::PropertyTypeMismatch => Error::E_TYPE,
+            Self::ClearPropertyOnDefiner(_, _) => Error::E_INVARG,
+            Self::QuotaExceeded(_) => Error::E_QUOTA,
+            Self::SavepointNotFound(_) => Error::E_INVARG,
             _ => {
                 panic!("Unhandled error code: {:?}", self);
             }
@@ -142,25 +154,81 @@ pub struct NarrativeEvent {
     author: Objid,
     /// The event itself.
     pub event: Event,
+    /// Whether this is out-of-band (e.g. MCP) output, which clients should hand to their protocol
+    /// handlers rather than show to the user.
+    is_oob: bool,
 }
 
 /// Types of events we can send to the session.
 #[derive(Debug, Clone, PartialEq, Eq, Encode, Decode)]
 pub enum Event {
-    /// The typical "something happened" descriptive event.
-    TextNotify(String),
+    /// The typical "something happened" descriptive event, tagged with the type of its content so
+    /// that rich clients can render it appropriately.
+    TextNotify(String, ContentType),
     // TODO: Other Event types on Session stream
     //   other events that might happen here would be things like (local) "object moved" or "object
     //   created."
 }
 
+/// The type of content carried by a `TextNotify` event. Clients that can't render a given type
+/// (e.g. telnet) fall back to presenting it as plain text.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Encode, Decode)]
+pub enum ContentType {
+    #[default]
+    TextPlain,
+    TextHtml,
+    TextMarkdown,
+    ApplicationJson,
+}
+
+impl ContentType {
+    /// Parse a MIME type string as passed to `notify`, e.g. "text/html".
+    #[must_use]
+    pub fn from_mime_type(mime_type: &str) -> Option<Self> {
+        match mime_type.trim().to_lowercase().as_str() {
+            "text/plain" => Some(Self::TextPlain),
+            "text/html" => Some(Self::TextHtml),
+            "text/markdown" => Some(Self::TextMarkdown),
+            "application/json" => Some(Self::ApplicationJson),
+            _ => None,
+        }
+    }
+
+    #[must_use]
+    pub fn mime_type(&self) -> &'static str {
+        match self {
+            Self::TextPlain => "text/plain",
+            Self::TextHtml => "text/html",
+            Self::TextMarkdown => "text/markdown",
+            Self::ApplicationJson => "application/json",
+        }
+    }
+}
+
 impl NarrativeEvent {
     #[must_use]
     pub fn notify_text(author: Objid, event: String) -> Self {
+        Self::notify(author, event, ContentType::TextPlain)
+    }
+
+    #[must_use]
+    pub fn notify(author: Objid, event: String, content_type: ContentType) -> Self {
         Self {
             timestamp: SystemTime::now(),
             author,
-            event: Event::TextNotify(event),
+            event: Event::TextNotify(event, content_type),
+            is_oob: false,
+        }
+    }
+
+    /// An out-of-band line, such as an MCP message, sent as-is to the player's connections.
+    #[must_use]
+    pub fn notify_oob(author: Objid, payload: String) -> Self {
+        Self {
+            timestamp: SystemTime::now(),
+            author,
+            event: Event::TextNotify(payload, ContentType::TextPlain),
+            is_oob: true,
         }
     }
 
@@ -176,6 +244,10 @@ impl NarrativeEvent {
     pub fn event(&self) -> Event {
         self.event.clone()
     }
+    #[must_use]
+    pub fn is_oob(&self) -> bool {
+        self.is_oob
+    }
 }
 
 /// Errors related to command matching.
@@ -101,6 +101,9 @@ pub enum BinaryType {
     None = 0,
     /// Opcodes match almost 1:1 with LambdaMOO 1.8.x, but is not "binary" compatible.
     LambdaMoo18X = 1,
+    /// MOO source text, not yet compiled; see `VerbCompilation::Lazy` in the textdump loader.
+    /// Compiled into `LambdaMoo18X` form when the verb is first called.
+    MooSource = 2,
 }
 
 impl LayoutAs<u8> for BinaryType {
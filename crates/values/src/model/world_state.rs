@@ -76,15 +76,37 @@ pub trait WorldState {
         flags: BitEnum<ObjFlag>,
     ) -> Result<Objid, WorldStateError>;
 
+    /// Create a new object numbered `id`, for tooling that needs to recreate specific objects.
+    /// Wizards only, and `id` must not already be in use.
+    fn create_object_with_id(
+        &mut self,
+        perms: Objid,
+        id: Objid,
+        parent: Objid,
+        owner: Objid,
+        flags: BitEnum<ObjFlag>,
+    ) -> Result<Objid, WorldStateError>;
+
     /// Recycles (destroys) the given object, and re-parents all its children to the next parent up
     /// the chain, including removing property definitions inherited from the object.
     /// If the object is a location, the contents of that location are moved to #-1.
     /// (It is the caller's (bf_recycle) responsibility to execute :exitfunc for those objects).
     fn recycle_object(&mut self, perms: Objid, obj: Objid) -> Result<(), WorldStateError>;
 
+    /// Renumber an object to the lowest unused object number lower than its own, if there is one,
+    /// and return its new number (which is its old one if there's no free number below it).
+    /// Parent/children, location/contents, and ownership links (of objects, verbs, and properties)
+    /// are rewritten to refer to the new number. As in LambdaMOO, object numbers held in property
+    /// values or verb code are left alone.
+    fn renumber_object(&mut self, perms: Objid, obj: Objid) -> Result<Objid, WorldStateError>;
+
     /// Return the highest used object # in the system.
     fn max_object(&self, perms: Objid) -> Result<Objid, WorldStateError>;
 
+    /// Lower the highest used object # to that of the highest valid object, so the numbers of
+    /// recycled objects above it can be reused.
+    fn reset_max_object(&mut self, perms: Objid) -> Result<(), WorldStateError>;
+
     /// Move an object to a new location.
     /// (Note it is the caller's responsibility to execute :accept, :enterfunc, :exitfunc, etc.)
     fn move_object(
@@ -104,6 +126,20 @@ pub trait WorldState {
     /// inherited from its parent.
     fn properties(&self, perms: Objid, obj: Objid) -> Result<PropDefs, WorldStateError>;
 
+    /// Get the verbs on the given object and on each of its ancestors, in the order they're
+    /// resolved in, each paired with whether it's defined on the object itself (`true`) or
+    /// inherited (`false`).
+    fn all_verbs(&self, perms: Objid, obj: Objid) -> Result<Vec<(VerbDef, bool)>, WorldStateError>;
+
+    /// Get the definitions of every property the given object has, each paired with whether it's
+    /// defined on the object itself (`true`) or inherited (`false`). The definer of an inherited
+    /// property is the ancestor it was defined on.
+    fn all_properties(
+        &self,
+        perms: Objid,
+        obj: Objid,
+    ) -> Result<Vec<(PropDef, bool)>, WorldStateError>;
+
     /// Retrieve a property from the given object, walking transitively up its inheritance chain.
     fn retrieve_property(
         &self,
@@ -128,6 +164,16 @@ pub trait WorldState {
         attrs: PropAttrs,
     ) -> Result<(), WorldStateError>;
 
+    /// Set the info for several properties on the given object, identified by uuid, in one go.
+    /// Either all of the changes are applied, or (if any property is missing or not writable, or
+    /// would be renamed to a name that's taken) none of them are.
+    fn batch_set_property_info(
+        &mut self,
+        perms: Objid,
+        obj: Objid,
+        updates: Vec<(Uuid, PropAttrs)>,
+    ) -> Result<(), WorldStateError>;
+
     /// Update a property on the given object.
     fn update_property(
         &mut self,
@@ -219,6 +265,17 @@ pub trait WorldState {
         verb_attrs: VerbAttrs,
     ) -> Result<(), WorldStateError>;
 
+    /// Give the verb on the given object whose names are exactly `old_names` the new set of names,
+    /// leaving everything else about it alone. Fails if any of the new names is already used by
+    /// another verb on the same object.
+    fn rename_verb(
+        &mut self,
+        perms: Objid,
+        obj: Objid,
+        old_names: &[String],
+        new_names: Vec<String>,
+    ) -> Result<(), WorldStateError>;
+
     /// Get the verbdef with the given name on the given object. Without doing inheritance resolution.
     fn get_verb(&self, perms: Objid, obj: Objid, vname: &str) -> Result<VerbDef, WorldStateError>;
 
@@ -269,9 +326,38 @@ pub trait WorldState {
         new_parent: Objid,
     ) -> Result<(), WorldStateError>;
 
+    /// Get the ordered list of parents of the given object: its primary parent (as `parent_of`
+    /// returns it) followed by any additional parents.
+    fn parents_of(&self, perms: Objid, obj: Objid) -> Result<ObjSet, WorldStateError>;
+
+    /// Give the given object an ordered list of (distinct) parents, the first of which becomes its
+    /// primary parent. Verbs and inherited property values are then looked up on the object and
+    /// its ancestors depth-first, left to right, with each ancestor visited once. Fails if the
+    /// object would end up inheriting two different properties of the same name.
+    fn change_parents(
+        &mut self,
+        perms: Objid,
+        obj: Objid,
+        new_parents: &ObjSet,
+    ) -> Result<(), WorldStateError>;
+
+    /// Get the ancestors of the given object (not including itself), in the order properties and
+    /// verbs are inherited: its parent, then its parent's parent, and so on up to the root.
+    /// Through multiple parents, the walk is depth-first, left to right, with each ancestor
+    /// listed once.
+    fn ancestors(&self, perms: Objid, obj: Objid) -> Result<ObjSet, WorldStateError>;
+
+    /// Whether `ancestor` is the given object or one of its ancestors, through any of its parents.
+    fn is_a(&self, obj: Objid, ancestor: Objid) -> Result<bool, WorldStateError>;
+
     /// Get the children of the given object.
     fn children_of(&self, perms: Objid, obj: Objid) -> Result<ObjSet, WorldStateError>;
 
+    /// Get all the descendants of the given object (not including itself), breadth first: its
+    /// children, their children, and so on. An object with several parents in the subtree is
+    /// listed once.
+    fn descendants(&self, perms: Objid, obj: Objid) -> Result<ObjSet, WorldStateError>;
+
     /// Check the validity of an object.
     fn valid(&self, obj: Objid) -> Result<bool, WorldStateError>;
 
@@ -281,11 +367,26 @@ pub trait WorldState {
     /// Returns the (rough) total number of bytes used by database storage subsystem.
     fn db_usage(&self) -> Result<usize, WorldStateError>;
 
+    /// Check the database for inconsistencies, returning a description of each one found.
+    fn verify_database(&self) -> Result<Vec<String>, WorldStateError>;
+
+    /// Fix the inconsistencies `verify_database` finds, where that can be done mechanically,
+    /// returning a description of each fix. With `dry_run`, nothing is changed.
+    fn repair_database(&mut self, dry_run: bool) -> Result<Vec<String>, WorldStateError>;
+
     /// Commit all modifications made to the state of this world since the start of its transaction.
     fn commit(&mut self) -> Result<CommitResult, WorldStateError>;
 
     /// Rollback all modifications made to the state of this world since the start of its transaction.
     fn rollback(&mut self) -> Result<(), WorldStateError>;
+
+    /// Mark the current state of this world, returning a handle which `rollback_to` can later
+    /// return it to. Handles are only good within this transaction.
+    fn savepoint(&mut self, perms: Objid) -> Result<usize, WorldStateError>;
+
+    /// Undo every modification made since `savepoint` was taken, without abandoning the
+    /// transaction. Savepoints taken after it are discarded; it can itself be rolled back to again.
+    fn rollback_to(&mut self, perms: Objid, savepoint: usize) -> Result<(), WorldStateError>;
 }
 
 pub trait WorldStateSource: Send + Sync {
@@ -61,6 +61,18 @@ pub fn verbname_cmp(vname: &str, candidate: &str) -> bool {
     true
 }
 
+/// Compare two byte strings in time that depends only on their lengths, not on where they first
+/// differ. For checking secrets such as password hashes, where an early-out comparison would let
+/// a caller learn the correct prefix by timing repeated guesses.
+#[must_use]
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    let diff = a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y));
+    std::hint::black_box(diff) == 0
+}
+
 // Simple MOO quasi-C style string quoting.
 // In MOO, there's just \" and \\, no \n, \t, etc.
 // So no need to produce anything else.
@@ -128,7 +140,7 @@ pub fn parse_into_words(input: &str) -> Vec<String> {
 
 #[cfg(test)]
 mod tests {
-    use crate::util::{quote_str, verbname_cmp};
+    use crate::util::{constant_time_eq, quote_str, verbname_cmp};
 
     #[test]
     fn test_string_quote() {
@@ -178,4 +190,13 @@ mod tests {
         // Regression for 'do_object' matching 'do'
         assert!(!verbname_cmp("do", "do_object"));
     }
+
+    #[test]
+    fn test_constant_time_eq() {
+        assert!(constant_time_eq(b"", b""));
+        assert!(constant_time_eq(b"abXyZ12", b"abXyZ12"));
+        assert!(!constant_time_eq(b"abXyZ12", b"abXyZ13"));
+        assert!(!constant_time_eq(b"abXyZ12", b"bbXyZ12"));
+        assert!(!constant_time_eq(b"abXyZ12", b"abXyZ1"));
+    }
 }
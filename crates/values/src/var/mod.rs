@@ -445,6 +445,32 @@ mod tests {
         assert_eq!(v_str("ab").index(2), Ok(v_err(E_RANGE)));
     }
 
+    #[test]
+    fn test_multibyte_strings() {
+        // Lengths, indexes, and ranges all count characters.
+        let s = v_str("naïve café");
+        assert_eq!(s.len(), Ok(v_int(10)));
+        assert_eq!(s.index(2), Ok(v_str("ï")));
+        assert_eq!(s.index(3), Ok(v_str("v")));
+        assert_eq!(s.index(9), Ok(v_str("é")));
+        assert_eq!(s.index(10), Ok(v_err(E_RANGE)));
+        assert_eq!(s.range(3, 5), Ok(v_str("ïve")));
+        assert_eq!(s.range(7, 10), Ok(v_str("café")));
+        assert_eq!(s.range(8, 11), Ok(v_err(E_RANGE)));
+
+        let mut t = v_str("café");
+        assert_eq!(t.index_set(3, v_str("e")), Ok(v_str("cafe")));
+        assert_eq!(t.index_set(4, v_str("e")), Err(E_RANGE));
+        assert_eq!(
+            v_str("café").rangeset(v_str("ÉS"), 4, 4),
+            Ok(v_str("cafÉS"))
+        );
+        assert_eq!(
+            v_str("ünïcödé").rangeset(v_str("x"), 2, 6),
+            Ok(v_str("üxé"))
+        );
+    }
+
     #[test]
     fn test_eq() {
         assert_eq!(v_int(1), v_int(1));
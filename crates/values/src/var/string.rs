@@ -23,6 +23,12 @@ use bincode::{Decode, Encode};
 use crate::var::error::Error;
 use crate::var::{v_err, v_str, v_string, Var};
 
+/// A MOO string.
+///
+/// Lengths, indexes, and ranges count characters (Unicode scalar values), not bytes, so that
+/// `length()`, `s[i]`, and `s[i..j]` agree with each other on multibyte UTF-8 content and never
+/// split a character. For pure ASCII strings, the usual case, the two are the same thing. The size
+/// in bytes is available separately as `byte_len` (`byte_length()` in MOO).
 #[derive(Clone, Encode, Decode, Ord, PartialOrd)]
 pub struct Str {
     inner: Arc<String>,
@@ -34,26 +40,50 @@ impl Str {
         Self { inner: Arc::new(s) }
     }
 
+    /// The byte offset of the character at `char_offset`, or of the end of the string if that's
+    /// where it lands.
+    fn byte_offset(&self, char_offset: usize) -> Option<usize> {
+        if self.inner.is_ascii() {
+            return (char_offset <= self.inner.len()).then_some(char_offset);
+        }
+        self.inner
+            .char_indices()
+            .map(|(i, _)| i)
+            .chain(std::iter::once(self.inner.len()))
+            .nth(char_offset)
+    }
+
+    /// The character at `offset`, as a string.
     pub fn get(&self, offset: usize) -> Option<Var> {
-        let r = self.inner.get(offset..=offset);
-        r.map(v_str)
+        self.inner
+            .chars()
+            .nth(offset)
+            .map(|c| v_string(c.to_string()))
     }
 
+    /// Replace the character at `offset` with the single character in `r`.
     #[must_use]
     pub fn set(&self, offset: usize, r: &Self) -> Var {
         if r.len() != 1 {
             return v_err(Error::E_RANGE);
         }
-        if offset >= self.inner.len() {
+        if offset >= self.len() {
             return v_err(Error::E_RANGE);
         }
         let mut s = self.inner.as_str().to_string();
-        s.replace_range(offset..=offset, r.as_str());
+        let (start, end) = (
+            self.byte_offset(offset).unwrap(),
+            self.byte_offset(offset + 1).unwrap(),
+        );
+        s.replace_range(start..end, r.as_str());
         v_string(s)
     }
 
+    /// The characters in `range`.
     pub fn get_range(&self, range: Range<usize>) -> Option<Var> {
-        let r = self.inner.get(range);
+        let start = self.byte_offset(range.start)?;
+        let end = self.byte_offset(range.end)?;
+        let r = self.inner.get(start..end);
         r.map(v_str)
     }
 
@@ -72,8 +102,18 @@ impl Str {
         v_string(format!("{}{}", self.inner, other))
     }
 
+    /// The length in characters.
     #[must_use]
     pub fn len(&self) -> usize {
+        if self.inner.is_ascii() {
+            return self.inner.len();
+        }
+        self.inner.chars().count()
+    }
+
+    /// The length in bytes of the UTF-8 encoding.
+    #[must_use]
+    pub fn byte_len(&self) -> usize {
         self.inner.len()
     }
 
@@ -87,10 +127,15 @@ impl Str {
         self.inner.as_str()
     }
 
+    /// The characters in `range`. Panics if the range is out of bounds.
     #[must_use]
     pub fn substring(&self, range: Range<usize>) -> Self {
+        let start = self
+            .byte_offset(range.start)
+            .expect("substring out of range");
+        let end = self.byte_offset(range.end).expect("substring out of range");
         Self {
-            inner: Arc::new(self.inner[range].to_string()),
+            inner: Arc::new(self.inner[start..end].to_string()),
         }
     }
 }
@@ -13,7 +13,7 @@
 //
 
 use crate::var::error::Error;
-use crate::var::error::Error::{E_INVARG, E_RANGE, E_TYPE};
+use crate::var::error::Error::{E_FLOAT, E_INVARG, E_RANGE, E_TYPE};
 use crate::var::variant::Variant;
 use crate::var::{v_empty_list, v_empty_str, v_listv, Var};
 use crate::var::{v_err, v_float, v_int};
@@ -24,16 +24,26 @@ macro_rules! binary_numeric_coercion_op {
     ($op:tt ) => {
         pub fn $op(&self, v: &Var) -> Result<Var, Error> {
             match (self.variant(), v.variant()) {
-                (Variant::Float(l), Variant::Float(r)) => Ok(v_float(l.$op(*r))),
+                (Variant::Float(l), Variant::Float(r)) => float_result(l.$op(*r)),
                 (Variant::Int(l), Variant::Int(r)) => Ok(v_int(l.$op(*r))),
-                (Variant::Float(l), Variant::Int(r)) => Ok(v_float(l.$op(*r as f64))),
-                (Variant::Int(l), Variant::Float(r)) => Ok(v_float((*l as f64).$op(*r))),
+                (Variant::Float(l), Variant::Int(r)) => float_result(l.$op(*r as f64)),
+                (Variant::Int(l), Variant::Float(r)) => float_result((*l as f64).$op(*r)),
                 (_, _) => Ok(v_err(E_TYPE)),
             }
         }
     };
 }
 
+/// As in LambdaMOO, arithmetic which overflows to infinity or produces NaN is `E_FLOAT`, rather
+/// than a value.
+fn float_result(f: f64) -> Result<Var, Error> {
+    if f.is_finite() {
+        Ok(v_float(f))
+    } else {
+        Err(E_FLOAT)
+    }
+}
+
 impl Var {
     #[must_use]
     pub fn is_true(&self) -> bool {
@@ -56,7 +66,7 @@ impl Var {
                 Ok(l.set(i, value))
             }
             Variant::Str(s) => {
-                if !i < s.len() {
+                if i >= s.len() {
                     return Err(E_RANGE);
                 }
 
@@ -99,10 +109,10 @@ impl Var {
 
     pub fn add(&self, v: &Self) -> Result<Self, Error> {
         match (self.variant(), v.variant()) {
-            (Variant::Float(l), Variant::Float(r)) => Ok(v_float(*l + *r)),
+            (Variant::Float(l), Variant::Float(r)) => float_result(*l + *r),
             (Variant::Int(l), Variant::Int(r)) => Ok(v_int(l + r)),
-            (Variant::Float(l), Variant::Int(r)) => Ok(v_float(*l + (*r as f64))),
-            (Variant::Int(l), Variant::Float(r)) => Ok(v_float(*l as f64 + *r)),
+            (Variant::Float(l), Variant::Int(r)) => float_result(*l + (*r as f64)),
+            (Variant::Int(l), Variant::Float(r)) => float_result(*l as f64 + *r),
             (Variant::Str(s), Variant::Str(r)) => Ok(s.append(r)),
             (_, _) => Ok(v_err(E_TYPE)),
         }
@@ -118,20 +128,20 @@ impl Var {
 
     pub fn modulus(&self, v: &Self) -> Result<Self, Error> {
         match (self.variant(), v.variant()) {
-            (Variant::Float(l), Variant::Float(r)) => Ok(v_float(*l % *r)),
+            (Variant::Float(l), Variant::Float(r)) => float_result(*l % *r),
             (Variant::Int(l), Variant::Int(r)) => Ok(v_int(l % r)),
-            (Variant::Float(l), Variant::Int(r)) => Ok(v_float(*l % (*r as f64))),
-            (Variant::Int(l), Variant::Float(r)) => Ok(v_float(*l as f64 % (*r))),
+            (Variant::Float(l), Variant::Int(r)) => float_result(*l % (*r as f64)),
+            (Variant::Int(l), Variant::Float(r)) => float_result(*l as f64 % (*r)),
             (_, _) => Ok(v_err(E_TYPE)),
         }
     }
 
     pub fn pow(&self, v: &Self) -> Result<Self, Error> {
         match (self.variant(), v.variant()) {
-            (Variant::Float(l), Variant::Float(r)) => Ok(v_float(l.powf(*r))),
+            (Variant::Float(l), Variant::Float(r)) => float_result(l.powf(*r)),
             (Variant::Int(l), Variant::Int(r)) => Ok(v_int(l.pow(*r as u32))),
-            (Variant::Float(l), Variant::Int(r)) => Ok(v_float(l.powi(*r as i32))),
-            (Variant::Int(l), Variant::Float(r)) => Ok(v_float((*l as f64).powf(*r))),
+            (Variant::Float(l), Variant::Int(r)) => float_result(l.powi(*r as i32)),
+            (Variant::Int(l), Variant::Float(r)) => float_result((*l as f64).powf(*r)),
             (_, _) => Ok(v_err(E_TYPE)),
         }
     }
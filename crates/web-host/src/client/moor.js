@@ -184,7 +184,10 @@ function generateElements(html) {
 }
 
 function write_markdown(markdown, destination, style) {
-    let html = context.showdown.makeHtml(markdown);
+    write_html(context.showdown.makeHtml(markdown), destination, style);
+}
+
+function write_html(html, destination, style) {
     let elements = generateElements(html);
     while (elements.length > 0) {
         if (style) {
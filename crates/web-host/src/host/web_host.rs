@@ -414,7 +414,7 @@ pub async fn eval_handler(
     let response = match rpc_client
         .make_rpc_call(
             client_id,
-            RpcRequest::Eval(client_token.clone(), auth_token, expression),
+            RpcRequest::Eval(client_token.clone(), auth_token, expression, false),
         )
         .await
     {
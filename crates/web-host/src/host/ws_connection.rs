@@ -50,6 +50,10 @@ pub struct NarrativeOutput {
     system_message: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     message: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    content_type: Option<String>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    is_oob: bool,
     server_time: SystemTime,
 }
 
@@ -69,6 +73,8 @@ impl WebSocketConnection {
                 origin_player: self.player.0,
                 system_message: Some(connect_message.to_string()),
                 message: None,
+                content_type: None,
+                is_oob: false,
                 server_time: SystemTime::now(),
             },
         )
@@ -95,40 +101,46 @@ impl WebSocketConnection {
                         }
                     }
                 }
-                Ok(event) = narrative_recv(self.client_id, &mut self.narrative_sub) => {
-                    trace!(?event, "narrative_event");
-                    match event {
-                        ConnectionEvent::SystemMessage(author, msg) => {
-                            Self::emit_event(&mut ws_sender, NarrativeOutput {
-                                origin_player: author.0,
-                                system_message: Some(msg),
-                                message: None,
-                                server_time: SystemTime::now(),
-                            }).await;
-                        }
-                        ConnectionEvent::Narrative(author, event) => {
-                            let msg = event.event();
-                            Self::emit_event(&mut ws_sender, NarrativeOutput {
-                                origin_player: author.0,
-                                system_message: None,
-                                message: Some(match msg {
-                                    moor_values::model::Event::TextNotify(msg) => msg,
-                                }),
-                                server_time: event.timestamp(),
-                            }).await;
-                        }
-                        ConnectionEvent::RequestInput(request_id) => {
-                            expecting_input = Some(request_id);
-                        }
-                        ConnectionEvent::Disconnect() => {
-                            Self::emit_event(&mut ws_sender, NarrativeOutput {
-                                origin_player: self.player.0,
-                                system_message: Some("** Disconnected **".to_string()),
-                                message: None,
-                                server_time: SystemTime::now(),
-                            }).await;
-                            ws_sender.close().await.expect("Unable to close connection");
-                            return ;
+                Ok(events) = narrative_recv(self.client_id, &mut self.narrative_sub) => {
+                    for event in events {
+                        trace!(?event, "narrative_event");
+                        match event {
+                            ConnectionEvent::SystemMessage(author, msg) => {
+                                Self::emit_event(&mut ws_sender, NarrativeOutput {
+                                    origin_player: author.0,
+                                    system_message: Some(msg),
+                                    message: None,
+                                    content_type: None,
+                                    is_oob: false,
+                                    server_time: SystemTime::now(),
+                                }).await;
+                            }
+                            ConnectionEvent::Narrative(author, event) => {
+                                let moor_values::model::Event::TextNotify(msg, content_type) = event.event();
+                                Self::emit_event(&mut ws_sender, NarrativeOutput {
+                                    origin_player: author.0,
+                                    system_message: None,
+                                    message: Some(msg),
+                                    content_type: Some(content_type.mime_type().to_string()),
+                                    is_oob: event.is_oob(),
+                                    server_time: event.timestamp(),
+                                }).await;
+                            }
+                            ConnectionEvent::RequestInput(request_id) => {
+                                expecting_input = Some(request_id);
+                            }
+                            ConnectionEvent::Disconnect() => {
+                                Self::emit_event(&mut ws_sender, NarrativeOutput {
+                                    origin_player: self.player.0,
+                                    system_message: Some("** Disconnected **".to_string()),
+                                    message: None,
+                                    content_type: None,
+                                    is_oob: false,
+                                    server_time: SystemTime::now(),
+                                }).await;
+                                ws_sender.close().await.expect("Unable to close connection");
+                                return ;
+                            }
                         }
                     }
                 }
@@ -183,6 +195,8 @@ impl WebSocketConnection {
                         origin_player: self.player.0,
                         system_message: Some("I don't understand that.".to_string()),
                         message: None,
+                        content_type: None,
+                        is_oob: false,
                         server_time: SystemTime::now(),
                     },
                 )
@@ -195,6 +209,8 @@ impl WebSocketConnection {
                         origin_player: self.player.0,
                         system_message: Some("I don't know what you're talking about.".to_string()),
                         message: None,
+                        content_type: None,
+                        is_oob: false,
                         server_time: SystemTime::now(),
                     },
                 )
@@ -207,6 +223,8 @@ impl WebSocketConnection {
                         origin_player: self.player.0,
                         system_message: Some("I don't know how to do that.".to_string()),
                         message: None,
+                        content_type: None,
+                        is_oob: false,
                         server_time: SystemTime::now(),
                     },
                 )
@@ -219,6 +237,8 @@ impl WebSocketConnection {
                         origin_player: self.player.0,
                         system_message: Some("You can't do that.".to_string()),
                         message: None,
+                        content_type: None,
+                        is_oob: false,
                         server_time: SystemTime::now(),
                     },
                 )